@@ -0,0 +1,28 @@
+// file: build.rs
+// version: 1.0.0
+// guid: 9a4f2c71-6e3b-4d08-b5a9-2c7e1f8d4b60
+
+//! Generate the gRPC ExecutionService code when the `grpc` feature is enabled
+
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc();
+}
+
+#[cfg(feature = "grpc")]
+fn grpc() {
+    const PROTO: &str = "proto/safe_ai_util/v1/execution.proto";
+    println!("cargo:rerun-if-changed={}", PROTO);
+
+    // Use the vendored protoc unless PROTOC points at another one
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc =
+            protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc for this platform");
+        std::env::set_var("PROTOC", protoc);
+    }
+    tonic_prost_build::configure()
+        .build_client(true)
+        .build_server(true)
+        .compile_protos(&[PROTO], &["proto"])
+        .expect("failed to compile the ExecutionService proto");
+}
@@ -0,0 +1,529 @@
+// file: src/activity.rs
+// version: 1.1.0
+// guid: 7c036e9c-1ebb-4831-bed4-61f8590f918d
+
+//! Concurrent session awareness
+//!
+//! Several agents (or an agent and a person) often work in the same checkout at once. Every
+//! session that runs commands through the Executor is registered in `sessions.json` in the
+//! state directory, together with its workspace, the agent driving it and the files its
+//! commands modified. The registry is shared between processes and guarded by a lock file.
+//!
+//! Before a command runs, the Executor warns when other sessions are active in the same
+//! workspace and when the command's path arguments were recently modified by one of them.
+//! A file counts as modified when its modification time changed while a command that named
+//! it was running, so read-only commands never produce conflicts.
+//!
+//! Like metrics, tracking is best effort: registry failures are logged and never affect the
+//! command being run.
+
+use crate::error::{AgentError, Result};
+use crate::security::sandbox::canonicalize_lenient;
+use crate::{session, utils};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+/// Environment variable naming the agent driving the session, e.g. `copilot` or `claude`
+pub const AGENT_ENV: &str = "SAFE_AI_AGENT";
+
+/// Minutes since its last command for which a session counts as active
+pub const ACTIVE_MINUTES: i64 = 15;
+
+/// Hours an inactive session is kept in the registry
+pub const RETENTION_HOURS: i64 = 24;
+
+/// Modified files remembered per session
+const MAX_MODIFIED: usize = 200;
+
+/// Path arguments watched per command
+const MAX_WATCHED: usize = 64;
+
+/// Seconds after which a leftover lock file is considered abandoned
+const STALE_LOCK_SECS: u64 = 10;
+
+/// Sessions known to the registry, keyed by session ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionRegistry {
+    pub sessions: BTreeMap<String, SessionRecord>,
+}
+
+/// One session and what it has been doing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub session_id: String,
+    /// Agent from `SAFE_AI_AGENT`, if set
+    pub agent: Option<String>,
+    pub user: Option<String>,
+    /// Process that ran the latest command
+    pub pid: u32,
+    pub workspace: PathBuf,
+    pub started_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub last_command: Option<String>,
+    #[serde(default)]
+    pub modified: Vec<ModifiedFile>,
+}
+
+/// A file a session's command changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModifiedFile {
+    pub path: PathBuf,
+    pub at: DateTime<Utc>,
+    pub command: String,
+}
+
+/// A path about to be used that another active session recently modified
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: PathBuf,
+    pub session: SessionRecord,
+    pub modified: ModifiedFile,
+}
+
+impl SessionRecord {
+    /// Whether the session ran a command within [`ACTIVE_MINUTES`]
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        now - self.last_seen <= Duration::minutes(ACTIVE_MINUTES)
+    }
+
+    /// Whether the session works in, above or below `workspace`
+    pub fn shares_workspace(&self, workspace: &Path) -> bool {
+        self.workspace.starts_with(workspace) || workspace.starts_with(&self.workspace)
+    }
+
+    /// Agent, user and process, for messages
+    pub fn describe(&self) -> String {
+        let who = match (&self.agent, &self.user) {
+            (Some(agent), Some(user)) => format!("{} as {}", agent, user),
+            (Some(agent), None) => agent.clone(),
+            (None, Some(user)) => user.clone(),
+            (None, None) => "unknown".to_string(),
+        };
+        format!("{} ({}, pid {})", short_id(&self.session_id), who, self.pid)
+    }
+}
+
+impl SessionRegistry {
+    /// Path of the persisted registry
+    pub fn path() -> PathBuf {
+        utils::state_dir().join("sessions.json")
+    }
+
+    /// Load the registry, starting empty if none has been saved yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Load, change and save the registry while holding its lock
+    pub fn update<T>(change: impl FnOnce(&mut Self) -> T) -> Result<T> {
+        let path = Self::path();
+        let dir = path
+            .parent()
+            .ok_or_else(|| AgentError::system("Invalid sessions path"))?;
+        fs::create_dir_all(dir)?;
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut registry = Self::load()?;
+        let result = change(&mut registry);
+        registry.prune(Utc::now());
+
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&registry)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(result)
+    }
+
+    /// Register a session or refresh its heartbeat
+    pub fn touch(&mut self, record: SessionRecord) {
+        match self.sessions.get_mut(&record.session_id) {
+            Some(existing) => {
+                existing.pid = record.pid;
+                existing.last_seen = record.last_seen;
+                existing.last_command = record.last_command;
+                existing.workspace = record.workspace;
+                if record.agent.is_some() {
+                    existing.agent = record.agent;
+                }
+            }
+            None => {
+                self.sessions.insert(record.session_id.clone(), record);
+            }
+        }
+    }
+
+    /// Remember files a session's command changed
+    pub fn record_modified(&mut self, session_id: &str, files: Vec<ModifiedFile>) {
+        if let Some(record) = self.sessions.get_mut(session_id) {
+            for file in files {
+                record
+                    .modified
+                    .retain(|existing| existing.path != file.path);
+                record.modified.push(file);
+            }
+            let excess = record.modified.len().saturating_sub(MAX_MODIFIED);
+            record.modified.drain(..excess);
+        }
+    }
+
+    /// Other active sessions sharing `workspace`
+    pub fn others_in_workspace(
+        &self,
+        session_id: &str,
+        workspace: &Path,
+        now: DateTime<Utc>,
+    ) -> Vec<&SessionRecord> {
+        self.sessions
+            .values()
+            .filter(|record| record.session_id != session_id)
+            .filter(|record| record.is_active(now) && record.shares_workspace(workspace))
+            .collect()
+    }
+
+    /// Paths among `paths` that another active session modified within [`ACTIVE_MINUTES`]
+    pub fn conflicts(
+        &self,
+        session_id: &str,
+        paths: &[PathBuf],
+        now: DateTime<Utc>,
+    ) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for record in self.sessions.values() {
+            if record.session_id == session_id || !record.is_active(now) {
+                continue;
+            }
+            for modified in &record.modified {
+                let recent = now - modified.at <= Duration::minutes(ACTIVE_MINUTES);
+                if recent && paths.contains(&modified.path) {
+                    conflicts.push(Conflict {
+                        path: modified.path.clone(),
+                        session: record.clone(),
+                        modified: modified.clone(),
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Drop sessions idle for longer than [`RETENTION_HOURS`]
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        self.sessions
+            .retain(|_, record| now - record.last_seen <= Duration::hours(RETENTION_HOURS));
+    }
+}
+
+/// Tracks this process's session for the Executor
+#[derive(Debug)]
+pub struct Activity {
+    workspace: PathBuf,
+    /// Whether other sessions in the workspace have been reported yet
+    announced: AtomicBool,
+}
+
+/// Path arguments of a running command and their modification times before it started
+#[derive(Debug)]
+pub struct Watch {
+    command: String,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Activity {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            workspace: canonicalize_lenient(workspace),
+            announced: AtomicBool::new(false),
+        }
+    }
+
+    /// Canonical workspace of this session
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Register the command, warn about other sessions and snapshot its path arguments
+    pub fn before(&self, command: &str, args: &[String]) -> Watch {
+        let command_line = std::iter::once(command)
+            .chain(args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let paths = path_arguments(&self.workspace, args);
+        let now = Utc::now();
+        let session_id = session::current().session_id();
+        let record = SessionRecord {
+            session_id: session_id.to_string(),
+            agent: std::env::var(AGENT_ENV)
+                .ok()
+                .filter(|agent| !agent.is_empty()),
+            user: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .ok(),
+            pid: std::process::id(),
+            workspace: self.workspace.clone(),
+            started_at: now,
+            last_seen: now,
+            last_command: Some(command_line.clone()),
+            modified: Vec::new(),
+        };
+
+        let announce = !self.announced.swap(true, Ordering::Relaxed);
+        let result = SessionRegistry::update(|registry| {
+            registry.touch(record);
+            let others: Vec<SessionRecord> = if announce {
+                registry
+                    .others_in_workspace(session_id, &self.workspace, now)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            (others, registry.conflicts(session_id, &paths, now))
+        });
+
+        match result {
+            Ok((others, conflicts)) => {
+                if !others.is_empty() {
+                    let names: Vec<String> = others.iter().map(|other| other.describe()).collect();
+                    warn!(
+                        "{} other session(s) active in {}: {} (see `safe-ai-util sessions list`)",
+                        others.len(),
+                        self.workspace.display(),
+                        names.join(", ")
+                    );
+                }
+                for conflict in conflicts {
+                    warn!(
+                        "{} was modified {} ago by session {} running `{}`",
+                        conflict.path.display(),
+                        format_age(now - conflict.modified.at),
+                        conflict.session.describe(),
+                        conflict.modified.command
+                    );
+                }
+            }
+            Err(e) => warn!("Failed to update the session registry: {}", e),
+        }
+
+        Watch {
+            command: command_line,
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let mtime = modified_time(&path);
+                    (path, mtime)
+                })
+                .collect(),
+        }
+    }
+
+    /// Record the watched files whose modification time changed
+    pub fn after(&self, watch: Watch) {
+        let now = Utc::now();
+        let changed: Vec<ModifiedFile> = watch
+            .files
+            .into_iter()
+            .filter(|(path, before)| modified_time(path) != *before)
+            .map(|(path, _)| ModifiedFile {
+                path,
+                at: now,
+                command: watch.command.clone(),
+            })
+            .collect();
+        if changed.is_empty() {
+            return;
+        }
+
+        debug!("Command modified {} watched file(s)", changed.len());
+        let session_id = session::current().session_id();
+        if let Err(e) =
+            SessionRegistry::update(|registry| registry.record_modified(session_id, changed))
+        {
+            warn!("Failed to update the session registry: {}", e);
+        }
+    }
+}
+
+/// Arguments naming files in or below the workspace, canonicalized; options of the form
+/// `--name=value` contribute their value. Paths that do not exist yet are included so that
+/// files a command creates are noticed.
+fn path_arguments(workspace: &Path, args: &[String]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for arg in args {
+        let candidate = match arg.strip_prefix('-') {
+            Some(option) => match option.split_once('=') {
+                Some((_, value)) => value,
+                None => continue,
+            },
+            None => arg.as_str(),
+        };
+        if candidate.is_empty() || candidate.contains("://") || candidate.contains('\n') {
+            continue;
+        }
+        let path = canonicalize_lenient(&workspace.join(candidate));
+        if path.starts_with(workspace) && !path.is_dir() && !paths.contains(&path) {
+            paths.push(path);
+        }
+        if paths.len() == MAX_WATCHED {
+            break;
+        }
+    }
+    paths
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// First block of a session ID, enough to tell sessions apart in messages
+pub fn short_id(session_id: &str) -> &str {
+    session_id.split('-').next().unwrap_or(session_id)
+}
+
+/// Compact age such as `45s`, `12m` or `3h`
+pub fn format_age(age: Duration) -> String {
+    let seconds = age.num_seconds().max(0);
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        _ => format!("{}h", seconds / 3600),
+    }
+}
+
+/// Exclusive lock held while a registry in the state directory is read and rewritten
+pub(crate) struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    pub(crate) fn acquire(path: &Path) -> Result<Self> {
+        for _ in 0..100 {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(_) => {
+                    return Ok(Self {
+                        path: path.to_path_buf(),
+                    })
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    // A process that died while holding the lock leaves the file behind
+                    let abandoned = fs::metadata(path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age.as_secs() >= STALE_LOCK_SECS);
+                    if abandoned {
+                        let _ = fs::remove_file(path);
+                        continue;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(AgentError::system(format!(
+            "Timed out waiting for the lock {}",
+            path.display()
+        )))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, workspace: &str, last_seen: DateTime<Utc>) -> SessionRecord {
+        SessionRecord {
+            session_id: id.to_string(),
+            agent: Some("copilot".to_string()),
+            user: None,
+            pid: 42,
+            workspace: PathBuf::from(workspace),
+            started_at: last_seen,
+            last_seen,
+            last_command: None,
+            modified: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_other_sessions_and_conflicts() {
+        let now = Utc::now();
+        let mut registry = SessionRegistry::default();
+        registry.touch(record("aaaa-1", "/work/repo", now));
+        registry.touch(record(
+            "bbbb-2",
+            "/work/repo/sub",
+            now - Duration::minutes(2),
+        ));
+        registry.touch(record("cccc-3", "/work/other", now));
+        registry.touch(record("dddd-4", "/work/repo", now - Duration::hours(2)));
+
+        let modified = |path: &str, minutes: i64| ModifiedFile {
+            path: PathBuf::from(path),
+            at: now - Duration::minutes(minutes),
+            command: "sed -i s/a/b/ src/lib.rs".to_string(),
+        };
+        registry.record_modified(
+            "bbbb-2",
+            vec![
+                modified("/work/repo/src/lib.rs", 1),
+                modified("/work/repo/old.rs", 30),
+            ],
+        );
+
+        let others = registry.others_in_workspace("aaaa-1", Path::new("/work/repo"), now);
+        assert_eq!(others.len(), 1);
+        assert_eq!(others[0].describe(), "bbbb (copilot, pid 42)");
+
+        let paths = [
+            PathBuf::from("/work/repo/src/lib.rs"),
+            PathBuf::from("/work/repo/old.rs"),
+        ];
+        let conflicts = registry.conflicts("aaaa-1", &paths, now);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, paths[0]);
+        assert!(registry.conflicts("bbbb-2", &paths, now).is_empty());
+
+        registry.prune(now + Duration::hours(RETENTION_HOURS - 1));
+        assert_eq!(
+            registry.sessions.keys().collect::<Vec<_>>(),
+            ["aaaa-1", "bbbb-2", "cccc-3"]
+        );
+    }
+
+    #[test]
+    fn test_path_arguments() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let workspace = dir.path().canonicalize().unwrap();
+        fs::create_dir(workspace.join("src")).unwrap();
+        fs::write(workspace.join("src/lib.rs"), "").unwrap();
+
+        let args: Vec<String> = ["-i", "src/lib.rs", "--output=new.txt", "src", "/etc/hosts"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(
+            path_arguments(&workspace, &args),
+            [workspace.join("src/lib.rs"), workspace.join("new.txt")]
+        );
+        assert_eq!(format_age(Duration::seconds(90)), "1m");
+    }
+}
@@ -0,0 +1,225 @@
+// file: src/batch.rs
+// version: 1.3.0
+// guid: 3e9b7c21-8a4f-4d6e-b5c2-7f1a0d9e4b63
+
+//! Concurrent execution of independent commands
+//!
+//! [`crate::executor::Executor::execute_many`] runs a list of commands at most
+//! [`BatchOptions::jobs`] at a time, each through the same validation and capture path as
+//! a single command. With [`FailurePolicy::FailFast`] the first failure stops the commands
+//! still running and skips those not yet started; with [`FailurePolicy::KeepGoing`] every
+//! command runs. The results come back in the order the commands were given.
+
+use crate::executor::Truncation;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Cursor};
+use std::path::PathBuf;
+
+/// A command to run: the program and its arguments, never interpreted by a shell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandSpec {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Working directory, instead of the executor's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// Environment variables set on top of the executor's environment
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Standard input; without it the command reads nothing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin: Option<InputSource>,
+}
+
+/// Data fed to a command's standard input, written `{ text: ... }` or `{ file: ... }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InputSource {
+    /// The given text
+    Text { text: String },
+    /// The content of a file, which must lie inside the workspace roots
+    File { file: PathBuf },
+}
+
+impl InputSource {
+    /// Read the input, for commands implemented in-process
+    pub fn reader(&self) -> io::Result<Box<dyn BufRead>> {
+        Ok(match self {
+            Self::Text { text } => Box::new(Cursor::new(text.clone().into_bytes())),
+            Self::File { file } => Box::new(BufReader::new(fs::File::open(file)?)),
+        })
+    }
+}
+
+impl CommandSpec {
+    /// Split a command line into words using shell quoting rules, without running a shell
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = shell_words::split(line)
+            .map_err(|e| format!("Invalid command line '{}': {}", line, e))?
+            .into_iter();
+        let command = words
+            .next()
+            .ok_or_else(|| "Empty command line".to_string())?;
+        Ok(Self {
+            command,
+            args: words.collect(),
+            cwd: None,
+            env: BTreeMap::new(),
+            stdin: None,
+        })
+    }
+}
+
+impl fmt::Display for CommandSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words = vec![self.command.as_str()];
+        words.extend(self.args.iter().map(String::as_str));
+        f.write_str(&shell_words::join(words))
+    }
+}
+
+/// How a batch reacts to a failing command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// Stop running commands and skip the rest after the first failure
+    #[default]
+    FailFast,
+    /// Run every command regardless of failures
+    KeepGoing,
+}
+
+/// Options for [`crate::executor::Executor::execute_many`]
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    /// Commands running at the same time; at least one
+    pub jobs: usize,
+    pub on_failure: FailurePolicy,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            jobs: default_jobs(),
+            on_failure: FailurePolicy::default(),
+        }
+    }
+}
+
+/// Number of CPUs, the default concurrency
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Outcome of one command of a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    /// Exited with status 0
+    Succeeded,
+    /// Exited with a non-zero status
+    Failed,
+    /// Could not be run, was refused, or timed out
+    Error,
+    /// Stopped because another command failed
+    Cancelled,
+    /// Not started because another command failed
+    Skipped,
+}
+
+impl BatchStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Error => "error",
+            Self::Cancelled => "cancelled",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+/// Result of one command of a batch
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResult {
+    #[serde(flatten)]
+    pub spec: CommandSpec,
+    pub status: BatchStatus,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Bytes dropped from output longer than `output.max_capture_bytes`
+    #[serde(skip_serializing_if = "Truncation::is_empty")]
+    pub truncated: Truncation,
+    pub duration_ms: u64,
+    /// Why the command could not run or was stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    /// A result for a command that produced no output
+    pub fn without_output(spec: CommandSpec, status: BatchStatus, error: Option<String>) -> Self {
+        Self {
+            spec,
+            status,
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            truncated: Truncation::default(),
+            duration_ms: 0,
+            error,
+        }
+    }
+}
+
+/// Count the results per status, as JSON
+pub fn summary(results: &[BatchResult]) -> Value {
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    json!({
+        "total": results.len(),
+        "succeeded": count(BatchStatus::Succeeded),
+        "failed": count(BatchStatus::Failed),
+        "error": count(BatchStatus::Error),
+        "cancelled": count(BatchStatus::Cancelled),
+        "skipped": count(BatchStatus::Skipped),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command_line() {
+        let spec = CommandSpec::parse("git commit -m 'two words'").unwrap();
+        assert_eq!(spec.command, "git");
+        assert_eq!(spec.args, vec!["commit", "-m", "two words"]);
+        assert_eq!(spec.to_string(), "git commit -m 'two words'");
+        assert!(CommandSpec::parse("  ").is_err());
+        assert!(CommandSpec::parse("echo 'unterminated").is_err());
+    }
+
+    #[test]
+    fn test_stdin_from_text_or_file() {
+        let spec: CommandSpec =
+            serde_json::from_str(r#"{"command": "sort", "stdin": {"text": "b\na\n"}}"#).unwrap();
+        let mut lines = String::new();
+        spec.stdin.unwrap().reader().unwrap().read_to_string(&mut lines).unwrap();
+        assert_eq!(lines, "b\na\n");
+
+        let spec: CommandSpec = serde_yaml::from_str("command: jq\nstdin: { file: data.json }\n").unwrap();
+        let input = InputSource::File {
+            file: PathBuf::from("data.json"),
+        };
+        assert_eq!(spec.stdin, Some(input.clone()));
+        assert_eq!(serde_json::to_value(&input).unwrap(), json!({"file": "data.json"}));
+        assert!(input.reader().is_err());
+    }
+}
@@ -0,0 +1,360 @@
+// file: src/budget.rs
+// version: 1.2.0
+// guid: 0c6f8e2a-5d1b-4f7e-9a3c-2b8d4e6f1a70
+
+//! Execution budgets
+//!
+//! A budget caps what a session may spend so that a runaway agent loop stops predictably:
+//! wall-clock time, the number of subprocesses spawned, and the bytes transferred by
+//! built-in network operations. Network traffic of spawned tools (curl, git, helm, the cloud
+//! CLIs and the like) is not metered, so it is not limited either. Limits come from the
+//! `[budget]` configuration table or the `--budget-*` flags, and a task manifest can declare a
+//! tighter `budget` for its run (see [`Budget::narrow`]).
+//!
+//! Nested invocations share the budget of the session: child processes receive what is left
+//! in `SAFE_AI_BUDGET_*` environment variables, and a nested run never gets more than that,
+//! whatever its own configuration says. When a limit is reached the operation fails with
+//! [`AgentError::BudgetExceeded`], whose [`BudgetExceeded`] names the limit.
+
+use crate::config::BudgetConfig;
+use crate::error::{AgentError, Result};
+use crate::error_codes;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// Environment variable carrying the session deadline, in milliseconds since the Unix epoch
+pub const DEADLINE_ENV: &str = "SAFE_AI_BUDGET_DEADLINE_MS";
+
+/// Environment variable carrying the number of subprocesses the session may still spawn
+pub const SUBPROCESSES_ENV: &str = "SAFE_AI_BUDGET_SUBPROCESSES";
+
+/// Environment variable carrying the network bytes the session may still transfer
+pub const NETWORK_BYTES_ENV: &str = "SAFE_AI_BUDGET_NETWORK_BYTES";
+
+/// A budgeted resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resource {
+    /// Wall-clock time in seconds
+    WallClock,
+    /// Spawned subprocesses
+    Subprocesses,
+    /// Bytes transferred by built-in network operations
+    NetworkBytes,
+}
+
+impl Resource {
+    /// Configuration key setting the limit
+    pub fn config_key(self) -> &'static str {
+        match self {
+            Self::WallClock => "budget.max_wall_clock_seconds",
+            Self::Subprocesses => "budget.max_subprocesses",
+            Self::NetworkBytes => "budget.max_network_bytes",
+        }
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::WallClock => "wall-clock seconds",
+            Self::Subprocesses => "subprocesses",
+            Self::NetworkBytes => "network bytes",
+        })
+    }
+}
+
+/// Which limit was reached, and by how much
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BudgetExceeded {
+    pub resource: Resource,
+    pub limit: u64,
+    /// Amount spent, including the request that was refused
+    pub used: u64,
+}
+
+impl BudgetExceeded {
+    /// Machine-readable form, printed on stderr when a run ends because of it
+    pub fn to_json(&self) -> Value {
+        json!({
+            "error": "budget_exceeded",
+            "code": error_codes::BUDGET_EXCEEDED,
+            "resource": self.resource,
+            "limit": self.limit,
+            "used": self.used,
+            "config_key": self.resource.config_key(),
+        })
+    }
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} limit of {} reached ({} used); raise {} to allow more",
+            self.resource,
+            self.limit,
+            self.used,
+            self.resource.config_key()
+        )
+    }
+}
+
+/// Stored for a limit that is not set
+const UNLIMITED: u64 = u64::MAX;
+
+/// Spending of one invocation against its limits
+///
+/// Limits are atomics so that they can be narrowed while commands hold the budget.
+#[derive(Debug)]
+pub struct Budget {
+    started: Instant,
+    /// Milliseconds this invocation may run, measured from `started`
+    wall_clock_ms: AtomicU64,
+    subprocesses: AtomicU64,
+    network_bytes: AtomicU64,
+    spawned: AtomicU64,
+    transferred: AtomicU64,
+}
+
+impl Budget {
+    /// Limits of `config`, narrowed to what an enclosing invocation left over
+    pub fn new(config: &BudgetConfig) -> Self {
+        let inherited_deadline = env_u64(DEADLINE_ENV)
+            .map(|deadline| Duration::from_millis(deadline.saturating_sub(now_millis())));
+        let wall_clock = min_option(
+            config.max_wall_clock_seconds.map(Duration::from_secs),
+            inherited_deadline,
+        );
+        Self {
+            started: Instant::now(),
+            wall_clock_ms: AtomicU64::new(wall_clock.map_or(UNLIMITED, |d| d.as_millis() as u64)),
+            subprocesses: AtomicU64::new(
+                min_option(config.max_subprocesses, env_u64(SUBPROCESSES_ENV)).unwrap_or(UNLIMITED),
+            ),
+            network_bytes: AtomicU64::new(
+                min_option(config.max_network_bytes, env_u64(NETWORK_BYTES_ENV))
+                    .unwrap_or(UNLIMITED),
+            ),
+            spawned: AtomicU64::new(0),
+            transferred: AtomicU64::new(0),
+        }
+    }
+
+    /// Tighten the limits to what `config` allows from now on, e.g. for the run of a task
+    /// manifest declaring its own budget; limits already lower are kept
+    pub fn narrow(&self, config: &BudgetConfig) {
+        if let Some(seconds) = config.max_wall_clock_seconds {
+            let deadline = self.started.elapsed().as_millis() as u64 + seconds.saturating_mul(1000);
+            self.wall_clock_ms.fetch_min(deadline, Ordering::SeqCst);
+        }
+        if let Some(count) = config.max_subprocesses {
+            let limit = self.spawned.load(Ordering::SeqCst).saturating_add(count);
+            self.subprocesses.fetch_min(limit, Ordering::SeqCst);
+        }
+        if let Some(bytes) = config.max_network_bytes {
+            let limit = self
+                .transferred
+                .load(Ordering::SeqCst)
+                .saturating_add(bytes);
+            self.network_bytes.fetch_min(limit, Ordering::SeqCst);
+        }
+        debug!("Budget narrowed to {:?}", config);
+    }
+
+    /// Whether any limit is set
+    pub fn is_limited(&self) -> bool {
+        self.wall_clock().is_some()
+            || self.subprocess_limit().is_some()
+            || self.network_limit().is_some()
+    }
+
+    /// Fail if the wall-clock budget is used up
+    pub fn check_time(&self) -> Result<()> {
+        match self.wall_clock() {
+            Some(limit) if self.started.elapsed() >= limit => Err(exceeded(
+                Resource::WallClock,
+                limit.as_secs(),
+                self.started.elapsed().as_secs(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Time left before the wall-clock budget is used up
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.wall_clock()
+            .map(|limit| limit.saturating_sub(self.started.elapsed()))
+    }
+
+    /// Account for a subprocess about to be spawned, refusing it if none are left or the
+    /// wall-clock budget is used up
+    pub fn spawn(&self) -> Result<()> {
+        self.check_time()?;
+        let used = self.spawned.fetch_add(1, Ordering::SeqCst) + 1;
+        match self.subprocess_limit() {
+            Some(limit) if used > limit => {
+                self.spawned.fetch_sub(1, Ordering::SeqCst);
+                Err(exceeded(Resource::Subprocesses, limit, used))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Account for bytes transferred by a built-in network operation
+    pub fn charge_network(&self, bytes: u64) -> Result<()> {
+        let used = self.transferred.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        match self.network_limit() {
+            Some(limit) if used > limit => Err(exceeded(Resource::NetworkBytes, limit, used)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Environment variables handing what is left of the budget to a child process
+    pub fn child_env(&self) -> Vec<(&'static str, String)> {
+        let mut env = Vec::new();
+        if let Some(remaining) = self.remaining_time() {
+            let deadline = now_millis() + remaining.as_millis() as u64;
+            env.push((DEADLINE_ENV, deadline.to_string()));
+        }
+        if let Some(limit) = self.subprocess_limit() {
+            let left = limit.saturating_sub(self.spawned.load(Ordering::SeqCst));
+            env.push((SUBPROCESSES_ENV, left.to_string()));
+        }
+        if let Some(limit) = self.network_limit() {
+            let left = limit.saturating_sub(self.transferred.load(Ordering::SeqCst));
+            env.push((NETWORK_BYTES_ENV, left.to_string()));
+        }
+        env
+    }
+
+    fn wall_clock(&self) -> Option<Duration> {
+        limit(&self.wall_clock_ms).map(Duration::from_millis)
+    }
+
+    fn subprocess_limit(&self) -> Option<u64> {
+        limit(&self.subprocesses)
+    }
+
+    fn network_limit(&self) -> Option<u64> {
+        limit(&self.network_bytes)
+    }
+
+    /// Amounts spent so far, as JSON
+    pub fn usage(&self) -> Value {
+        json!({
+            "wall_clock_seconds": self.started.elapsed().as_secs(),
+            "subprocesses": self.spawned.load(Ordering::SeqCst),
+            "network_bytes": self.transferred.load(Ordering::SeqCst),
+        })
+    }
+}
+
+fn exceeded(resource: Resource, limit: u64, used: u64) -> AgentError {
+    let exceeded = BudgetExceeded {
+        resource,
+        limit,
+        used,
+    };
+    debug!("Budget exceeded: {:?}", exceeded);
+    AgentError::BudgetExceeded(exceeded)
+}
+
+fn limit(value: &AtomicU64) -> Option<u64> {
+    Some(value.load(Ordering::SeqCst)).filter(|limit| *limit != UNLIMITED)
+}
+
+fn min_option<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok()?.trim().parse().ok()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subprocess_and_network_limits() {
+        let budget = Budget::new(&BudgetConfig {
+            max_subprocesses: Some(2),
+            max_network_bytes: Some(100),
+            ..Default::default()
+        });
+        assert!(budget.spawn().is_ok());
+        assert!(budget.spawn().is_ok());
+        match budget.spawn() {
+            Err(AgentError::BudgetExceeded(e)) => {
+                assert_eq!(e.resource, Resource::Subprocesses);
+                assert_eq!((e.limit, e.used), (2, 3));
+                assert_eq!(e.to_json()["error"], "budget_exceeded");
+            }
+            other => panic!("expected budget error, got {:?}", other),
+        }
+
+        assert!(budget.charge_network(60).is_ok());
+        assert!(budget.charge_network(60).is_err());
+        let env = budget.child_env();
+        assert!(env.contains(&(SUBPROCESSES_ENV, "0".to_string())));
+        assert!(env.contains(&(NETWORK_BYTES_ENV, "0".to_string())));
+    }
+
+    #[test]
+    fn test_wall_clock_limit() {
+        let budget = Budget::new(&BudgetConfig {
+            max_wall_clock_seconds: Some(0),
+            ..Default::default()
+        });
+        assert!(budget.check_time().is_err());
+        assert!(matches!(
+            budget.spawn(),
+            Err(AgentError::BudgetExceeded(BudgetExceeded {
+                resource: Resource::WallClock,
+                ..
+            }))
+        ));
+        assert!(!Budget::new(&BudgetConfig::default()).is_limited());
+    }
+
+    #[test]
+    fn test_narrow_keeps_the_lower_limit() {
+        let budget = Budget::new(&BudgetConfig {
+            max_subprocesses: Some(10),
+            ..Default::default()
+        });
+        assert!(budget.spawn().is_ok());
+        budget.narrow(&BudgetConfig {
+            max_subprocesses: Some(1),
+            max_network_bytes: Some(50),
+            ..Default::default()
+        });
+        assert!(budget.is_limited());
+        assert!(budget.spawn().is_ok());
+        assert!(budget.spawn().is_err());
+        assert!(budget.charge_network(60).is_err());
+
+        budget.narrow(&BudgetConfig {
+            max_subprocesses: Some(100),
+            max_wall_clock_seconds: Some(0),
+            ..Default::default()
+        });
+        assert!(budget.spawn().is_err());
+        assert!(budget.check_time().is_err());
+    }
+}
@@ -0,0 +1,236 @@
+// file: src/capability.rs
+// version: 1.1.0
+// guid: b83e1f5a-6d27-4c90-9e4b-0a7c5d2f81e6
+
+//! Capability requests for missing tools
+//!
+//! A command needing a tool that is not installed, or older than its `[tools.<name>]` pin
+//! requires, fails with a [`CapabilityRequest`] rather than a bare "Command not found": the
+//! tool and version needed, why, and the `tools install` command providing it. Agents read it
+//! from a structured line on stderr, like a policy denial, and can ask for the tool instead of
+//! retrying.
+//!
+//! With `capabilities.queue` set, requests are also kept in `capability-requests.json` in the
+//! state directory, one per tool and version, until someone approves them with
+//! `tools install` or turns them down with `tools dismiss`.
+
+use crate::activity::FileLock;
+use crate::error::{AgentError, Result};
+use crate::error_codes;
+use crate::utils;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Why a tool cannot be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shortfall {
+    /// Not found on the search path
+    Missing,
+    /// Older than the pinned minimum version
+    Outdated,
+}
+
+/// A tool a command needs, and how to provide it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityRequest {
+    pub message: String,
+    pub tool: String,
+    pub shortfall: Shortfall,
+    /// Lowest version accepted, from the tool's pin
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_version: Option<String>,
+    /// Version installed, when it is too old
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub found_version: Option<String>,
+    /// Command line that needed the tool
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub needed_by: Vec<String>,
+    /// Command that installs the tool
+    pub install: String,
+    pub requested_at: DateTime<Utc>,
+    /// Whether the request waits in the queue for approval
+    #[serde(default)]
+    pub queued: bool,
+}
+
+impl CapabilityRequest {
+    /// A request for a tool that is not installed
+    pub fn missing(tool: &str, required_version: Option<&str>) -> Self {
+        Self::new(
+            format!("Command not found: {}", tool),
+            tool,
+            Shortfall::Missing,
+            required_version,
+            None,
+        )
+    }
+
+    /// A request for a newer version of an installed tool
+    pub fn outdated(
+        tool: &str,
+        message: String,
+        required_version: &str,
+        found_version: String,
+    ) -> Self {
+        Self::new(
+            message,
+            tool,
+            Shortfall::Outdated,
+            Some(required_version),
+            Some(found_version),
+        )
+    }
+
+    fn new(
+        message: String,
+        tool: &str,
+        shortfall: Shortfall,
+        required_version: Option<&str>,
+        found_version: Option<String>,
+    ) -> Self {
+        Self {
+            message,
+            tool: tool.to_string(),
+            shortfall,
+            required_version: required_version.map(str::to_string),
+            found_version,
+            needed_by: Vec::new(),
+            install: install_command(tool, required_version),
+            requested_at: Utc::now(),
+            queued: false,
+        }
+    }
+
+    /// Machine-readable form, printed on stderr when a run ends because of it
+    pub fn to_json(&self) -> Value {
+        json!({
+            "error": "capability_request",
+            "code": error_codes::TOOL_UNAVAILABLE,
+            "message": self.message,
+            "tool": self.tool,
+            "shortfall": self.shortfall,
+            "required_version": self.required_version,
+            "found_version": self.found_version,
+            "needed_by": self.needed_by,
+            "install": self.install,
+            "queued": self.queued,
+        })
+    }
+
+    /// Whether both requests ask for the same tool at the same version
+    fn same_need(&self, other: &Self) -> bool {
+        self.tool == other.tool && self.required_version == other.required_version
+    }
+}
+
+impl fmt::Display for CapabilityRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}; install it with `{}`", self.message, self.install)
+    }
+}
+
+/// The `tools install` command providing a tool, at its pinned version if it has one
+pub fn install_command(tool: &str, version: Option<&str>) -> String {
+    match version {
+        Some(version) => format!("safe-ai-util tools install {}@{}", tool, version),
+        None => format!("safe-ai-util tools install {}", tool),
+    }
+}
+
+/// Requests waiting for approval, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CapabilityQueue {
+    pub requests: Vec<CapabilityRequest>,
+}
+
+impl CapabilityQueue {
+    /// Path of the persisted queue
+    pub fn path() -> PathBuf {
+        utils::state_dir().join("capability-requests.json")
+    }
+
+    /// Load the queue, starting empty if nothing has been queued yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Load, change and save the queue while holding its lock
+    pub fn update<T>(change: impl FnOnce(&mut Self) -> T) -> Result<T> {
+        let path = Self::path();
+        let dir = path
+            .parent()
+            .ok_or_else(|| AgentError::system("Invalid capability requests path"))?;
+        fs::create_dir_all(dir)?;
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut queue = Self::load()?;
+        let result = change(&mut queue);
+
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&queue)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(result)
+    }
+
+    /// Queue a request, replacing an earlier one for the same tool and version
+    pub fn push(&mut self, mut request: CapabilityRequest) {
+        request.queued = true;
+        self.requests.retain(|queued| !queued.same_need(&request));
+        self.requests.push(request);
+    }
+
+    /// Remove the requests for a tool, returning how many there were
+    pub fn remove(&mut self, tool: &str) -> usize {
+        let before = self.requests.len();
+        self.requests.retain(|queued| queued.tool != tool);
+        before - self.requests.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_json() {
+        let mut request = CapabilityRequest::missing("protoc", Some("25.1"));
+        request.needed_by = vec!["protoc".to_string(), "--version".to_string()];
+        let json = request.to_json();
+        assert_eq!(json["error"], "capability_request");
+        assert_eq!(json["shortfall"], "missing");
+        assert_eq!(json["install"], "safe-ai-util tools install protoc@25.1");
+        assert!(request
+            .to_string()
+            .starts_with("Command not found: protoc; install it"));
+    }
+
+    #[test]
+    fn test_queue_keeps_one_request_per_need() {
+        let mut queue = CapabilityQueue::default();
+        queue.push(CapabilityRequest::missing("protoc", None));
+        queue.push(CapabilityRequest::missing("buf", None));
+        queue.push(CapabilityRequest::missing("protoc", None));
+        queue.push(CapabilityRequest::outdated(
+            "buf",
+            "buf is too old".to_string(),
+            "1.30",
+            "1.2".to_string(),
+        ));
+        let tools: Vec<&str> = queue.requests.iter().map(|r| r.tool.as_str()).collect();
+        assert_eq!(tools, ["buf", "protoc", "buf"]);
+        assert!(queue.requests.iter().all(|r| r.queued));
+
+        assert_eq!(queue.remove("buf"), 2);
+        assert_eq!(queue.requests.len(), 1);
+    }
+}
@@ -1,7 +1,8 @@
 // file: src/commands/awk.rs
-// version: 1.0.0
+// version: 1.1.1
 // guid: 9b2c3d4e-5f6a-7b8c-9d0e-1f2a3b4c5d6e
 
+use super::export::Effect;
 use crate::executor::Executor;
 use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches, Command};
@@ -14,6 +15,7 @@ use std::path::Path;
 /// Build the awk command with comprehensive options
 pub fn build_command() -> Command {
     Command::new("awk")
+        .add(Effect::Destructive)
         .about("Pattern scanning and processing language (Rust implementation)")
         .arg(Arg::new("program")
             .help("AWK program text")
@@ -40,7 +42,7 @@ pub fn build_command() -> Command {
 }
 
 /// Execute awk commands with Rust-native implementation
-pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
     let program_text = if let Some(prog_file) = matches.get_one::<String>("file-program") {
         fs::read_to_string(prog_file)?
     } else {
@@ -54,7 +56,7 @@ pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
         .unwrap_or_default();
 
     let field_separator = matches.get_one::<String>("field-separator")
-        .map(|s| s.clone())
+        .cloned()
         .unwrap_or_else(|| " ".to_string());
 
     let assignments = matches.get_many::<String>("assign")
@@ -68,8 +70,12 @@ pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
     let mut context = AwkContext::new(field_separator, assignments)?;
 
     if files.is_empty() {
-        // Read from stdin
-        process_input(Box::new(io::stdin().lock()), &program, &mut context)?;
+        // Read from stdin, or the file given with --stdin-file
+        let reader: Box<dyn BufRead> = match executor.stdin() {
+            Some(input) => input.reader()?,
+            None => Box::new(io::stdin().lock()),
+        };
+        process_input(reader, &program, &mut context)?;
     } else {
         // Process files
         for file_path in &files {
@@ -351,14 +357,14 @@ fn parse_action(action_text: &str) -> Result<AwkAction> {
             return Ok(AwkAction::Print(None));
         }
 
-        if inner.starts_with("print ") {
-            let expr = inner[6..].trim().to_string();
+        if let Some(expr) = inner.strip_prefix("print ") {
+            let expr = expr.trim().to_string();
             return Ok(AwkAction::Print(Some(expr)));
         }
 
-        if inner.starts_with("printf ") {
+        if let Some(args) = inner.strip_prefix("printf ") {
             // Simple printf parsing
-            let args = inner[7..].trim();
+            let args = args.trim();
             return Ok(AwkAction::PrintF(args.to_string(), Vec::new()));
         }
 
@@ -459,8 +465,7 @@ fn evaluate_expression(expr: &str, context: &AwkContext, line: &str) -> Result<b
     }
 
     // Handle simple field references
-    if expr.starts_with('$') {
-        let field_num_str = &expr[1..];
+    if let Some(field_num_str) = expr.strip_prefix('$') {
         if let Ok(field_num) = field_num_str.parse::<usize>() {
             let field_value = context.get_field(field_num);
             return Ok(!field_value.is_empty());
@@ -475,8 +480,7 @@ fn evaluate_expression(expr: &str, context: &AwkContext, line: &str) -> Result<b
 fn evaluate_field_or_variable(expr: &str, context: &AwkContext) -> Result<String> {
     let expr = expr.trim();
 
-    if expr.starts_with('$') {
-        let field_ref = &expr[1..];
+    if let Some(field_ref) = expr.strip_prefix('$') {
         if let Ok(field_num) = field_ref.parse::<usize>() {
             Ok(context.get_field(field_num))
         } else {
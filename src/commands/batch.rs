@@ -0,0 +1,123 @@
+// file: src/commands/batch.rs
+// version: 1.0.1
+// guid: 9a4d2f7e-1c3b-4e8a-b6d5-0f2e7c9a1b84
+
+//! `batch` command: run independent commands concurrently and report every result
+
+use super::export::Effect;
+use crate::batch::{self, BatchOptions, BatchResult, BatchStatus, CommandSpec, FailurePolicy};
+use crate::executor::Executor;
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+
+/// Build the batch command
+pub fn build_command() -> Command {
+    Command::new("batch")
+        .add(Effect::Destructive)
+        .about("Run independent commands concurrently with a concurrency limit")
+        .long_about(
+            "Run independent commands concurrently with a concurrency limit. Each command is a \
+             single argument split into words like a shell would, but no shell is run; every \
+             command goes through the same validation as when run on its own.",
+        )
+        .arg(
+            Arg::new("commands")
+                .value_name("COMMAND")
+                .num_args(1..)
+                .required(true)
+                .help("Command lines to run, e.g. 'cargo fmt --check' 'cargo clippy'"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .value_name("N")
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .help("Commands running at the same time (default: number of CPUs)"),
+        )
+        .arg(
+            Arg::new("keep-going")
+                .long("keep-going")
+                .short('k')
+                .action(ArgAction::SetTrue)
+                .help("Run every command even after one fails, instead of stopping the rest"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+}
+
+/// Execute the batch command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let commands = matches
+        .get_many::<String>("commands")
+        .unwrap_or_default()
+        .map(|line| CommandSpec::parse(line).map_err(|e| anyhow!(e)))
+        .collect::<Result<Vec<_>>>()?;
+    let options = BatchOptions {
+        jobs: matches
+            .get_one::<u16>("jobs")
+            .map(|jobs| *jobs as usize)
+            .unwrap_or_else(batch::default_jobs),
+        on_failure: if matches.get_flag("keep-going") {
+            FailurePolicy::KeepGoing
+        } else {
+            FailurePolicy::FailFast
+        },
+    };
+
+    let results = executor.execute_many(&commands, options).await;
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let report = json!({"results": results, "summary": batch::summary(&results)});
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_text(&results);
+    }
+
+    let unsuccessful = results
+        .iter()
+        .filter(|r| r.status != BatchStatus::Succeeded)
+        .count();
+    if unsuccessful > 0 {
+        bail!(
+            "{} of {} commands did not succeed",
+            unsuccessful,
+            results.len()
+        );
+    }
+    Ok(())
+}
+
+/// Print each command's output under a header, then a summary line
+fn print_text(results: &[BatchResult]) {
+    for result in results {
+        let detail = match (result.status, result.exit_code) {
+            (BatchStatus::Skipped, _) => String::new(),
+            (BatchStatus::Failed, Some(code)) => {
+                format!(" (exit {}, {} ms)", code, result.duration_ms)
+            }
+            _ => format!(" ({} ms)", result.duration_ms),
+        };
+        println!("==> [{}] {}{}", result.status.as_str(), result.spec, detail);
+        print!("{}", result.stdout);
+        eprint!("{}", result.stderr);
+        if let Some(error) = &result.error {
+            println!("    {}", error);
+        }
+    }
+
+    let summary = batch::summary(results);
+    let counts: Vec<String> = ["succeeded", "failed", "error", "cancelled", "skipped"]
+        .iter()
+        .filter(|status| summary[**status].as_u64().unwrap_or(0) > 0)
+        .map(|status| format!("{} {}", summary[*status], status))
+        .collect();
+    println!("{} commands: {}", results.len(), counts.join(", "));
+}
@@ -0,0 +1,135 @@
+// file: src/commands/binaries.rs
+// version: 1.0.1
+// guid: 5f8a2c14-7e3d-4b96-a0c1-e29d6b74f385
+
+//! `binaries` command: review and approve the hashes `safety.verify_binaries` checks
+
+use super::export::Effect;
+use crate::activity::format_age;
+use crate::executor::Executor;
+use crate::integrity::{self, short_hash, Approvals, ApprovedBinary};
+use crate::tools;
+use anyhow::Result;
+use chrono::Utc;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+
+/// Build the binaries command
+pub fn build_command() -> Command {
+    Command::new("binaries")
+        .about("Review and approve the external binaries commands may run")
+        .long_about(
+            "With safety.verify_binaries set, the SHA-256 of every external binary is recorded \
+             the first time it runs, and a binary whose contents change is refused until its \
+             new contents are approved here.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("List approved binaries and whether they still match")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("approve")
+                .about("Approve the current contents of the binaries commands resolve to")
+                .arg(
+                    Arg::new("command")
+                        .value_name("COMMAND")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .help("Command whose binary to approve, e.g. git"),
+                ),
+        )
+}
+
+/// Execute the binaries command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("list", sub_matches)) => list(sub_matches),
+        Some(("approve", sub_matches)) => approve(sub_matches, executor),
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+/// Whether an approved binary still has its approved contents
+fn status(binary: &ApprovedBinary) -> &'static str {
+    match integrity::fingerprint(&binary.path) {
+        Ok((_, sha256)) if sha256 == binary.sha256 => "ok",
+        Ok(_) => "changed",
+        Err(_) => "missing",
+    }
+}
+
+fn list(matches: &ArgMatches) -> Result<()> {
+    let approvals = Approvals::load()?;
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let binaries = approvals
+            .binaries
+            .iter()
+            .map(|binary| {
+                let mut entry = serde_json::to_value(binary)?;
+                entry["status"] = json!(status(binary));
+                Ok(entry)
+            })
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        println!("{}", serde_json::to_string_pretty(&binaries)?);
+        return Ok(());
+    }
+    if approvals.binaries.is_empty() {
+        println!("No approved binaries; set safety.verify_binaries to record them as they run");
+        return Ok(());
+    }
+    let now = Utc::now();
+    println!(
+        "{:<16} {:<8} {:>8}  {:<16}  PATH",
+        "COMMAND", "STATUS", "AGE", "SHA256"
+    );
+    for binary in &approvals.binaries {
+        println!(
+            "{:<16} {:<8} {:>8}  {:<16}  {}",
+            binary.command,
+            status(binary),
+            format_age(now - binary.approved_at),
+            short_hash(&binary.sha256),
+            binary.path.display()
+        );
+    }
+    Ok(())
+}
+
+fn approve(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    for command in matches.get_many::<String>("command").unwrap() {
+        let program = tools::resolve(command, executor.config().tools.get(command))?;
+        let (path, sha256) = integrity::fingerprint(&program)?;
+        let previous = Approvals::update(|approvals| {
+            let previous = approvals.find(&path).map(|binary| binary.sha256.clone());
+            approvals.approve(command, &path, &sha256);
+            previous
+        })?;
+        match previous {
+            Some(previous) if previous != sha256 => println!(
+                "Approved {} for {}: SHA-256 {} (was {})",
+                path.display(),
+                command,
+                short_hash(&sha256),
+                short_hash(&previous)
+            ),
+            _ => println!(
+                "Approved {} for {}: SHA-256 {}",
+                path.display(),
+                command,
+                short_hash(&sha256)
+            ),
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,30 @@
 // file: src/commands/buf.rs
-// version: 1.1.0
+// version: 1.8.1
 // guid: 7e8f9a0b-1c2d-3e4f-5a6b-7c8d9e0f1a2b
 
+use super::export::{Effect, Remote};
+use super::http::host_allowed;
+use crate::batch::{self, BatchOptions, BatchStatus, CommandSpec, FailurePolicy, InputSource};
+use crate::credentials::{self, DEFAULT_BUF_HOST};
 use crate::executor::Executor;
-use anyhow::Result;
-use clap::{Arg, ArgMatches, Command};
+use crate::generated::Registry;
+use crate::line_editor;
+use crate::parsers::{self, Diagnostic, Tool};
+use crate::security::audit;
+use crate::snapshot::Changes;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
-use tracing::info;
+use std::fs;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::{debug, info, warn};
 
 /// Helper function to append additional arguments from environment variable
 fn append_additional_args(mut args: Vec<String>) -> Vec<String> {
@@ -33,7 +51,35 @@ pub fn build_command() -> Command {
                         .long("module")
                         .short('m')
                         .value_name("MODULE")
-                        .help("Generate for specific module"),
+                        .value_delimiter(',')
+                        .action(ArgAction::Append)
+                        .help(
+                            "Generate for specific modules, one buf run per module: workspace \
+                             directories by path or name, otherwise pkg/MODULE/proto",
+                        ),
+                )
+                .arg(
+                    Arg::new("all-modules")
+                        .long("all-modules")
+                        .short('a')
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("module")
+                        .help("Generate for every module of buf.work.yaml or buf.yaml, one buf run each"),
+                )
+                .arg(
+                    Arg::new("template")
+                        .long("template")
+                        .short('t')
+                        .value_name("FILE")
+                        .help("Generation template to use instead of buf.gen.yaml"),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Runs at the same time (default: number of CPUs)"),
                 )
                 .arg(
                     Arg::new("path")
@@ -52,6 +98,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("lint")
+                .add(Effect::ReadOnly)
                 .about("Lint protocol buffer files")
                 .arg(
                     Arg::new("path")
@@ -86,17 +133,41 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("breaking")
+                .add(Effect::ReadOnly)
                 .about("Check for breaking changes")
+                .arg(
+                    Arg::new("input")
+                        .value_name("INPUT")
+                        .help("Module or workspace to check (defaults to current directory)"),
+                )
                 .arg(
                     Arg::new("against")
                         .long("against")
                         .value_name("REF")
-                        .help("Git reference to compare against")
+                        .help(
+                            "Git branch, tag or commit, a git input such as .git#branch=main, \
+                             or a BSR module such as buf.build/acme/petapis[:COMMIT]",
+                        )
                         .default_value("main"),
+                )
+                .arg(
+                    Arg::new("against-config")
+                        .long("against-config")
+                        .value_name("FILE")
+                        .help("buf.yaml of the input compared against, when it has none or another"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format: buf's own output, or a JSON report of the violations"),
                 ),
         )
         .subcommand(
             Command::new("build")
+                .add(Effect::ReadOnly)
                 .about("Build protocol buffer modules")
                 .arg(
                     Arg::new("path")
@@ -107,6 +178,8 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("push")
+                .add(Effect::Destructive)
+                .add(Remote)
                 .about("Push to Buf Schema Registry")
                 .arg(
                     Arg::new("tag")
@@ -116,6 +189,168 @@ pub fn build_command() -> Command {
                         .help("Tag for the push"),
                 ),
         )
+        .subcommand(
+            Command::new("dep")
+                .about("Manage the dependencies of a module or workspace")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("update")
+                        .add(Remote)
+                        .about("Update the dependencies in buf.lock and list what changed")
+                        .arg(directory_arg()),
+                )
+                .subcommand(
+                    Command::new("graph")
+                        .add(Effect::ReadOnly)
+                        .add(Remote)
+                        .about("Show the dependency graph")
+                        .arg(directory_arg())
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .short('f')
+                                .value_parser(["text", "json", "dot"])
+                                .default_value("text")
+                                .help("Output format: a tree, the modules as JSON, or buf's DOT"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("curl")
+                .add(Remote)
+                .about("Invoke an RPC of a server in buf.curl_allowed_hosts")
+                .arg(
+                    Arg::new("url")
+                        .value_name("URL")
+                        .required(true)
+                        .help("Endpoint with the method's path, e.g. http://localhost:8080/pet.v1.PetService/GetPet"),
+                )
+                .arg(
+                    Arg::new("schema")
+                        .long("schema")
+                        .value_name("SOURCE")
+                        .action(ArgAction::Append)
+                        .help("Module, directory or image describing the service (default: server reflection)"),
+                )
+                .arg(
+                    Arg::new("protocol")
+                        .long("protocol")
+                        .value_parser(["connect", "grpc", "grpcweb"])
+                        .help("RPC protocol (default: connect)"),
+                )
+                .arg(
+                    Arg::new("header")
+                        .long("header")
+                        .short('H')
+                        .value_name("NAME: VALUE")
+                        .action(ArgAction::Append)
+                        .help("Request header"),
+                )
+                .arg(
+                    Arg::new("data")
+                        .long("data")
+                        .short('d')
+                        .value_name("JSON")
+                        .conflicts_with_all(["data-file", "data-stdin"])
+                        .help("Request message as JSON"),
+                )
+                .arg(
+                    Arg::new("data-file")
+                        .long("data-file")
+                        .value_name("FILE")
+                        .conflicts_with("data-stdin")
+                        .help("Read the request message from FILE"),
+                )
+                .arg(
+                    Arg::new("data-stdin")
+                        .long("data-stdin")
+                        .action(ArgAction::SetTrue)
+                        .help("Read the request message from standard input"),
+                )
+                .arg(
+                    Arg::new("http2-prior-knowledge")
+                        .long("http2-prior-knowledge")
+                        .action(ArgAction::SetTrue)
+                        .help("Use HTTP/2 without TLS, as plaintext gRPC servers need"),
+                )
+                .arg(
+                    Arg::new("cacert")
+                        .long("cacert")
+                        .value_name("FILE")
+                        .help("CA certificates to verify the server with"),
+                )
+                .arg(
+                    Arg::new("cert")
+                        .long("cert")
+                        .value_name("FILE")
+                        .requires("key")
+                        .help("Client certificate for mutual TLS"),
+                )
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .value_name("FILE")
+                        .requires("cert")
+                        .help("Private key of the client certificate"),
+                )
+                .arg(
+                    Arg::new("servername")
+                        .long("servername")
+                        .value_name("NAME")
+                        .help("Server name to verify the certificate against, instead of the URL's host"),
+                )
+                .arg(
+                    Arg::new("insecure")
+                        .long("insecure")
+                        .short('k')
+                        .action(ArgAction::SetTrue)
+                        .help("Skip verifying the server certificate"),
+                ),
+        )
+        .subcommand(
+            Command::new("mod")
+                .about("Manage modules")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("init")
+                        .about("Create a buf.yaml for a new module")
+                        .arg(directory_arg()),
+                ),
+        )
+        .subcommand(
+            Command::new("registry")
+                .about("Manage Buf Schema Registry tokens, kept in the OS keyring")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("login")
+                        .about("Store a token read from standard input, once the registry accepts it")
+                        .arg(host_arg().default_value(DEFAULT_BUF_HOST)),
+                )
+                .subcommand(
+                    Command::new("logout")
+                        .add(Effect::Destructive)
+                        .about("Remove the stored token of a registry")
+                        .arg(host_arg().default_value(DEFAULT_BUF_HOST)),
+                )
+                .subcommand(
+                    Command::new("whoami")
+                        .add(Effect::ReadOnly)
+                        .add(Remote)
+                        .about("Show the user a registry knows the stored token as")
+                        .arg(host_arg().help("Registry host (defaults to every one logged in to)")),
+                ),
+        )
+}
+
+fn directory_arg() -> Arg {
+    Arg::new("directory")
+        .value_name("DIR")
+        .help("Module or workspace directory (defaults to current directory)")
+        .default_value(".")
+}
+
+fn host_arg() -> Arg {
+    Arg::new("host").value_name("HOST").help("Registry host")
 }
 
 /// Execute buf commands
@@ -127,6 +362,24 @@ pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
         Some(("breaking", sub_matches)) => execute_breaking(sub_matches, executor).await,
         Some(("build", sub_matches)) => execute_build(sub_matches, executor).await,
         Some(("push", sub_matches)) => execute_push(sub_matches, executor).await,
+        Some(("dep", sub_matches)) => match sub_matches.subcommand() {
+            Some(("update", update_matches)) => execute_dep_update(update_matches, executor).await,
+            Some(("graph", graph_matches)) => execute_dep_graph(graph_matches, executor).await,
+            _ => unreachable!("subcommand required"),
+        },
+        Some(("curl", sub_matches)) => execute_curl(sub_matches, executor).await,
+        Some(("mod", sub_matches)) => match sub_matches.subcommand() {
+            Some(("init", init_matches)) => execute_mod_init(init_matches, executor).await,
+            _ => unreachable!("subcommand required"),
+        },
+        Some(("registry", sub_matches)) => match sub_matches.subcommand() {
+            Some(("login", login_matches)) => execute_registry_login(login_matches, executor).await,
+            Some(("logout", logout_matches)) => execute_registry_logout(logout_matches),
+            Some(("whoami", whoami_matches)) => {
+                execute_registry_whoami(whoami_matches, executor).await
+            }
+            _ => unreachable!("subcommand required"),
+        },
         _ => {
             println!("No buf subcommand specified. Use 'buf --help' for usage information.");
             Ok(())
@@ -135,28 +388,258 @@ pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
 }
 
 async fn execute_generate(matches: &ArgMatches, executor: &Executor) -> Result<()> {
-    let mut args = vec!["buf".to_string(), "generate".to_string()];
+    let template = match matches.get_one::<String>("template") {
+        Some(template) => Some(executor.check_path(template)?),
+        None => None,
+    };
+    let modules: Vec<String> = if matches.get_flag("all-modules") {
+        let modules = workspace_modules();
+        if modules.is_empty() {
+            bail!("No modules to generate: buf.work.yaml and buf.yaml list none");
+        }
+        modules
+    } else {
+        let workspace = workspace_modules();
+        matches
+            .get_many::<String>("module")
+            .unwrap_or_default()
+            .map(|module| module_path(module, &workspace))
+            .collect()
+    };
 
-    if let Some(module) = matches.get_one::<String>("module") {
-        args.push("--path".to_string());
-        args.push(format!("pkg/{}/proto", module));
+    // Each module is generated by its own buf run; without modules there is a single run
+    let module_paths: Vec<Option<String>> = if modules.is_empty() {
+        vec![None]
+    } else {
+        modules.into_iter().map(Some).collect()
+    };
+
+    // Files the runs write are recorded in the generated-files registry
+    let scope = if executor.config().safety.dry_run {
+        None
+    } else {
+        let roots = output_dirs(matches, template.as_deref());
+        let mut scope = executor.watch_scope(&roots)?;
+        // Generated code is often ignored by git; the workspace is only scanned without it
+        scope.include_ignored = !roots.is_empty();
+        Some(scope)
+    };
+
+    let mut sources = Vec::new();
+    let mut specs = Vec::new();
+    for module_path in module_paths {
+        let mut args = vec!["generate".to_string()];
+        sources.push(
+            module_path
+                .clone()
+                .or_else(|| matches.get_one::<String>("path").cloned())
+                .unwrap_or_else(|| ".".to_string()),
+        );
+
+        if let Some(module_path) = module_path {
+            args.push("--path".to_string());
+            args.push(module_path);
+        }
+
+        if let Some(path) = matches.get_one::<String>("path") {
+            args.push("--path".to_string());
+            args.push(path.clone());
+        }
+
+        if let Some(template) = &template {
+            args.push("--template".to_string());
+            args.push(template.display().to_string());
+        }
+
+        if let Some(output) = matches.get_one::<String>("output") {
+            args.push("--output".to_string());
+            args.push(output.clone());
+        }
+
+        // Append additional arguments from file
+        args = append_additional_args(args);
+
+        specs.push(CommandSpec {
+            command: "buf".to_string(),
+            args,
+            cwd: None,
+            env: BTreeMap::new(),
+            stdin: None,
+        });
     }
 
-    if let Some(path) = matches.get_one::<String>("path") {
-        args.push("--path".to_string());
-        args.push(path.clone());
+    let options = BatchOptions {
+        jobs: matches
+            .get_one::<usize>("jobs")
+            .copied()
+            .unwrap_or_else(batch::default_jobs),
+        on_failure: FailurePolicy::FailFast,
+    };
+    info!(
+        "Generating protocol buffers: {} runs, {} at a time",
+        specs.len(),
+        options.jobs.max(1)
+    );
+    let before = scope.as_ref().map(|scope| scope.snapshot());
+    let results = executor.execute_many(&specs, options).await;
+    for result in &results {
+        if results.len() > 1 {
+            println!("==> [{}] {}", result.status.as_str(), result.spec);
+        }
+        print!("{}", result.stdout);
+        eprint!("{}", result.stderr);
+        if let Some(error) = &result.error {
+            eprintln!("{}", error);
+        }
     }
 
-    if let Some(output) = matches.get_one::<String>("output") {
-        args.push("--output".to_string());
-        args.push(output.clone());
+    if let (Some(scope), Some(before)) = (&scope, before) {
+        let changes = before.changes(&before.retake());
+        // Runs that overlap in time cannot be told apart, so every file changed is recorded
+        // as generated from all of them
+        let source = sources.join(" ");
+        let generator = specs
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        let mut registry = Registry::load(&scope.base)?;
+        for path in changes.created.iter().chain(&changes.modified) {
+            match registry.record(path, &source, &generator) {
+                Ok(path) => debug!("Recorded generated file {}", path),
+                Err(e) => warn!("Not recording generated file {}: {}", path.display(), e),
+            }
+        }
+        registry.save()?;
+        print_output_changes(&changes);
     }
 
-    // Append additional arguments from file
-    args = append_additional_args(args);
+    let unsuccessful = results
+        .iter()
+        .filter(|result| result.status != BatchStatus::Succeeded)
+        .count();
+    if unsuccessful > 0 {
+        bail!(
+            "{} of {} buf generate runs did not succeed",
+            unsuccessful,
+            results.len()
+        );
+    }
+    Ok(())
+}
 
-    info!("Generating protocol buffers with args: {:?}", args);
-    executor.execute_secure("buf", &args[1..]).await
+/// Summarize the files generation created, modified and deleted
+fn print_output_changes(changes: &Changes) {
+    if changes.is_empty() {
+        println!("Generated outputs are unchanged");
+        return;
+    }
+    println!(
+        "Outputs: {} created, {} modified, {} deleted",
+        changes.created.len(),
+        changes.modified.len(),
+        changes.deleted.len()
+    );
+    for (mark, paths) in [
+        ('+', &changes.created),
+        ('~', &changes.modified),
+        ('-', &changes.deleted),
+    ] {
+        for path in paths {
+            println!("  {} {}", mark, path.display());
+        }
+    }
+}
+
+/// Module directories of a `buf.work.yaml`
+#[derive(Debug, Default, Deserialize)]
+struct BufWork {
+    #[serde(default)]
+    directories: Vec<String>,
+}
+
+/// Modules of a version 2 `buf.yaml`
+#[derive(Debug, Default, Deserialize)]
+struct BufYaml {
+    #[serde(default)]
+    modules: Vec<BufModule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BufModule {
+    path: String,
+}
+
+/// Module directories of the workspace in the current directory: the `directories` of
+/// `buf.work.yaml`, or the `modules` of a version 2 `buf.yaml`; empty outside a workspace
+fn workspace_modules() -> Vec<String> {
+    if let Ok(content) = fs::read_to_string("buf.work.yaml") {
+        if let Ok(work) = serde_yaml::from_str::<BufWork>(&content) {
+            return work.directories;
+        }
+    }
+    fs::read_to_string("buf.yaml")
+        .ok()
+        .and_then(|content| serde_yaml::from_str::<BufYaml>(&content).ok())
+        .map(|config| {
+            config
+                .modules
+                .into_iter()
+                .map(|module| module.path)
+                .filter(|path| path != ".")
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Directory of the module `--module` names: a workspace directory given by path or by its
+/// last component, otherwise `pkg/MODULE/proto`
+fn module_path(module: &str, workspace: &[String]) -> String {
+    let trimmed = module.trim_end_matches('/');
+    workspace
+        .iter()
+        .find(|dir| dir.trim_end_matches('/') == trimmed)
+        .or_else(|| {
+            workspace.iter().find(|dir| {
+                Path::new(dir)
+                    .file_name()
+                    .is_some_and(|name| name == trimmed)
+            })
+        })
+        .cloned()
+        .unwrap_or_else(|| format!("pkg/{}/proto", module))
+}
+
+/// Plugin outputs in `buf.gen.yaml`
+#[derive(Debug, Default, Deserialize)]
+struct GenTemplate {
+    #[serde(default)]
+    plugins: Vec<GenPlugin>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenPlugin {
+    out: Option<String>,
+}
+
+/// Directories `buf generate` writes to: `--output`, or the plugin outputs in the template
+/// (`buf.gen.yaml` unless `--template` names another); empty when neither is known
+fn output_dirs(matches: &ArgMatches, template: Option<&Path>) -> Vec<PathBuf> {
+    if let Some(output) = matches.get_one::<String>("output") {
+        return vec![PathBuf::from(output)];
+    }
+    let template = fs::read_to_string(template.unwrap_or(Path::new("buf.gen.yaml")))
+        .ok()
+        .and_then(|content| serde_yaml::from_str::<GenTemplate>(&content).ok())
+        .unwrap_or_default();
+    let mut dirs: Vec<PathBuf> = template
+        .plugins
+        .into_iter()
+        .filter_map(|plugin| plugin.out.map(PathBuf::from))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
 }
 
 async fn execute_lint(matches: &ArgMatches, executor: &Executor) -> Result<()> {
@@ -193,21 +676,77 @@ async fn execute_format(matches: &ArgMatches, executor: &Executor) -> Result<()>
 }
 
 async fn execute_breaking(matches: &ArgMatches, executor: &Executor) -> Result<()> {
-    let against = matches.get_one::<String>("against").unwrap();
-    let args = vec![
-        "buf".to_string(),
-        "breaking".to_string(),
-        "--against".to_string(),
-        against.clone(),
-    ];
+    let input = match matches.get_one::<String>("input") {
+        Some(input) => executor.check_path(input)?,
+        None => env::current_dir()?,
+    };
+    let against = against_input(matches.get_one::<String>("against").unwrap(), &input);
+    let mut args = vec!["breaking".to_string()];
+    if matches.get_one::<String>("input").is_some() {
+        args.push(input.display().to_string());
+    }
+    args.extend(["--against".to_string(), against.clone()]);
+    if let Some(config) = matches.get_one::<String>("against-config") {
+        let config = executor.check_path(config)?;
+        args.extend(["--against-config".to_string(), config.display().to_string()]);
+    }
 
     info!("Checking for breaking changes against: {}", against);
-    executor.execute_secure("buf", &args[1..]).await
+    if matches.get_one::<String>("format").map(String::as_str) != Some("json") {
+        return executor.execute_secure("buf", &args).await;
+    }
+
+    args.push("--error-format=json".to_string());
+    let output = executor.execute_secure_capture("buf", &args).await?;
+    let violations: Vec<Diagnostic> = Tool::Buf.parse(&output.stdout, &output.stderr);
+    let mut report = json!({
+        "against": against,
+        "exit_code": output.exit_code,
+        "violations": violations,
+        "summary": parsers::summary(&violations),
+    });
+    // Without violations, buf's own errors are the only explanation of a failure
+    if !output.success() && violations.is_empty() {
+        report["stderr"] = json!(output.stderr);
+    }
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if !violations.is_empty() {
+        bail!("buf breaking found {} breaking changes", violations.len());
+    }
+    if !output.success() {
+        bail!("buf breaking failed against {}", against);
+    }
+    Ok(())
+}
+
+/// The buf input `--against` names
+///
+/// Git inputs (`.git#branch=main`), BSR modules (`buf.build/acme/petapis`, optionally with
+/// `:COMMIT` or `:LABEL`) and existing directories or images are passed as given. Anything
+/// else is a branch, tag or commit of the repository holding `input`, compared with the same
+/// subdirectory of it.
+fn against_input(against: &str, input: &Path) -> String {
+    let is_module = against
+        .split_once('/')
+        .is_some_and(|(host, _)| host.contains('.') && host != "." && host != "..");
+    if against.contains('#') || is_module || Path::new(against).exists() {
+        return against.to_string();
+    }
+    let Some(root) = input.ancestors().find(|dir| dir.join(".git").is_dir()) else {
+        return format!(".git#ref={}", against);
+    };
+    let git = root.join(".git").display().to_string();
+    match input.strip_prefix(root) {
+        Ok(subdir) if !subdir.as_os_str().is_empty() => {
+            format!("{}#ref={},subdir={}", git, against, subdir.display())
+        }
+        _ => format!("{}#ref={}", git, against),
+    }
 }
 
 async fn execute_build(matches: &ArgMatches, executor: &Executor) -> Result<()> {
     let path = matches.get_one::<String>("path").unwrap();
-    let args = vec!["buf".to_string(), "build".to_string(), path.clone()];
+    let args = ["buf".to_string(), "build".to_string(), path.clone()];
 
     info!("Building protocol buffers at path: {}", path);
     executor.execute_secure("buf", &args[1..]).await
@@ -224,3 +763,577 @@ async fn execute_push(matches: &ArgMatches, executor: &Executor) -> Result<()> {
     info!("Pushing to Buf Schema Registry");
     executor.execute_secure("buf", &args[1..]).await
 }
+
+/// `buf dep update`, then the dependencies whose pinned commit it added, changed or removed
+async fn execute_dep_update(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let directory = executor.check_path(matches.get_one::<String>("directory").unwrap())?;
+    let lock = directory.join("buf.lock");
+    let before = locked_commits(&lock)?;
+    let args = [
+        "dep".to_string(),
+        "update".to_string(),
+        directory.display().to_string(),
+    ];
+
+    info!("Updating buf dependencies in {}", directory.display());
+    executor.execute_secure("buf", &args).await?;
+    if executor.config().safety.dry_run {
+        return Ok(());
+    }
+    let changes = lock_changes(&before, &locked_commits(&lock)?);
+    if changes.is_empty() {
+        println!("{} is up to date", lock.display());
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+/// `buf.lock`, as buf 1.32 and later (`version: v2`) and earlier versions write it
+#[derive(Debug, Default, Deserialize)]
+struct BufLock {
+    #[serde(default)]
+    deps: Vec<LockedDep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockedDep {
+    name: Option<String>,
+    remote: Option<String>,
+    owner: Option<String>,
+    repository: Option<String>,
+    #[serde(default)]
+    commit: String,
+}
+
+impl LockedDep {
+    fn name(&self) -> String {
+        match (&self.name, &self.remote, &self.owner, &self.repository) {
+            (Some(name), ..) => name.clone(),
+            (None, Some(remote), Some(owner), Some(repository)) => {
+                format!("{}/{}/{}", remote, owner, repository)
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+/// The commit each dependency in a `buf.lock` is pinned to; empty if there is no lock file
+fn locked_commits(path: &Path) -> Result<BTreeMap<String, String>> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(BTreeMap::new());
+    };
+    let lock: BufLock = serde_yaml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(lock
+        .deps
+        .into_iter()
+        .map(|dep| (dep.name(), dep.commit))
+        .collect())
+}
+
+/// One line per added, updated and removed dependency, by name
+fn lock_changes(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let short = |commit: &str| commit.chars().take(12).collect::<String>();
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    names
+        .into_iter()
+        .filter_map(|name| match (before.get(name), after.get(name)) {
+            (None, Some(new)) => Some(format!("Added {} {}", name, short(new))),
+            (Some(_), None) => Some(format!("Removed {}", name)),
+            (Some(old), Some(new)) if old != new => {
+                Some(format!("Updated {} {} -> {}", name, short(old), short(new)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+async fn execute_dep_graph(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let directory = executor.check_path(matches.get_one::<String>("directory").unwrap())?;
+    let args = [
+        "dep".to_string(),
+        "graph".to_string(),
+        directory.display().to_string(),
+    ];
+
+    info!(
+        "Reading the buf dependency graph of {}",
+        directory.display()
+    );
+    let output = executor.execute_secure_capture("buf", &args).await?;
+    if !output.success() {
+        eprint!("{}", output.stderr);
+        bail!("buf dep graph failed");
+    }
+    let modules = parse_dep_graph(&output.stdout);
+    match matches.get_one::<String>("format").map(String::as_str) {
+        Some("dot") => print!("{}", output.stdout),
+        Some("json") => println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({ "modules": modules }))?
+        ),
+        _ => {
+            for line in dep_tree(&modules) {
+                println!("{}", line);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A module in the dependency graph, with the modules it depends on directly
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct GraphModule {
+    name: String,
+    /// Commit the module is pinned to; unset for modules of the workspace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit: Option<String>,
+    dependencies: Vec<String>,
+}
+
+/// The modules of `buf dep graph`'s DOT output, sorted by name
+///
+/// Nodes are quoted module names, followed by `:COMMIT` for remote modules, and each
+/// `"a" -> "b"` line is a dependency of `a` on `b`.
+fn parse_dep_graph(dot: &str) -> Vec<GraphModule> {
+    static NODE: OnceLock<Regex> = OnceLock::new();
+    let node = NODE.get_or_init(|| Regex::new(r#""([^"]+)""#).expect("valid node pattern"));
+
+    let mut modules: BTreeMap<String, GraphModule> = BTreeMap::new();
+    let mut add = |id: &str| {
+        let (name, commit) = match id.rsplit_once(':') {
+            Some((name, commit)) if !commit.contains('/') => (name, Some(commit.to_string())),
+            _ => (id, None),
+        };
+        let module = modules
+            .entry(name.to_string())
+            .or_insert_with(|| GraphModule {
+                name: name.to_string(),
+                commit: None,
+                dependencies: Vec::new(),
+            });
+        if commit.is_some() {
+            module.commit = commit;
+        }
+        name.to_string()
+    };
+    let mut edges = Vec::new();
+    for line in dot.lines() {
+        let ids: Vec<String> = node
+            .captures_iter(line)
+            .map(|captures| add(&captures[1]))
+            .collect();
+        if let [from, to] = ids.as_slice() {
+            if line.contains("->") {
+                edges.push((from.clone(), to.clone()));
+            }
+        }
+    }
+    for (from, to) in edges {
+        let dependencies = &mut modules.get_mut(&from).expect("added above").dependencies;
+        if !dependencies.contains(&to) {
+            dependencies.push(to);
+        }
+    }
+    modules.into_values().collect()
+}
+
+/// The graph as a tree from the modules nothing depends on; a module whose dependencies were
+/// already shown is marked `(*)`
+fn dep_tree(modules: &[GraphModule]) -> Vec<String> {
+    let by_name: BTreeMap<&str, &GraphModule> = modules
+        .iter()
+        .map(|module| (module.name.as_str(), module))
+        .collect();
+    let depended: BTreeSet<&str> = modules
+        .iter()
+        .flat_map(|module| module.dependencies.iter().map(String::as_str))
+        .collect();
+    let mut roots: Vec<&str> = by_name
+        .keys()
+        .copied()
+        .filter(|name| !depended.contains(name))
+        .collect();
+    // Only a cycle leaves no module undepended upon
+    if roots.is_empty() {
+        roots = by_name.keys().copied().collect();
+    }
+
+    fn visit<'a>(
+        name: &'a str,
+        depth: usize,
+        by_name: &BTreeMap<&str, &'a GraphModule>,
+        shown: &mut BTreeSet<&'a str>,
+        lines: &mut Vec<String>,
+    ) {
+        let module = by_name.get(name);
+        let commit = module
+            .and_then(|module| module.commit.as_deref())
+            .map(|commit| format!(" ({})", commit.chars().take(12).collect::<String>()))
+            .unwrap_or_default();
+        let dependencies = module
+            .map(|module| module.dependencies.as_slice())
+            .unwrap_or_default();
+        let repeated = !dependencies.is_empty() && !shown.insert(name);
+        lines.push(format!(
+            "{}{}{}{}",
+            "  ".repeat(depth),
+            name,
+            commit,
+            if repeated { " (*)" } else { "" }
+        ));
+        if !repeated {
+            for dependency in dependencies {
+                visit(dependency, depth + 1, by_name, shown, lines);
+            }
+        }
+    }
+
+    let mut shown = BTreeSet::new();
+    let mut lines = Vec::new();
+    for root in roots {
+        visit(root, 0, &by_name, &mut shown, &mut lines);
+    }
+    lines
+}
+
+/// Invoke an RPC with `buf curl`, the request message going to its standard input
+async fn execute_curl(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let endpoint = matches.get_one::<String>("url").unwrap();
+    let url = Url::parse(endpoint).context("Invalid URL")?;
+    check_curl_url(executor, &url)?;
+
+    let mut args = vec!["curl".to_string()];
+    for schema in matches.get_many::<String>("schema").unwrap_or_default() {
+        // Local schemas must lie in the workspace; others name a module of a registry
+        let schema = if Path::new(schema).exists() {
+            executor.check_path(schema)?.display().to_string()
+        } else {
+            schema.clone()
+        };
+        args.extend(["--schema".to_string(), schema]);
+    }
+    if let Some(protocol) = matches.get_one::<String>("protocol") {
+        args.extend(["--protocol".to_string(), protocol.clone()]);
+    }
+    for header in matches.get_many::<String>("header").unwrap_or_default() {
+        args.extend(["--header".to_string(), header.clone()]);
+    }
+    for (id, flag) in [
+        ("http2-prior-knowledge", "--http2-prior-knowledge"),
+        ("insecure", "--insecure"),
+    ] {
+        if matches.get_flag(id) {
+            args.push(flag.to_string());
+        }
+    }
+    for id in ["cacert", "cert", "key"] {
+        if let Some(file) = matches.get_one::<String>(id) {
+            let path = executor.check_path(file)?;
+            args.extend([format!("--{}", id), path.display().to_string()]);
+        }
+    }
+    if let Some(name) = matches.get_one::<String>("servername") {
+        args.extend(["--servername".to_string(), name.clone()]);
+    }
+
+    let stdin = if let Some(data) = matches.get_one::<String>("data") {
+        Some(InputSource::Text { text: data.clone() })
+    } else if let Some(file) = matches.get_one::<String>("data-file") {
+        Some(InputSource::File {
+            file: executor.check_path(file)?,
+        })
+    } else if matches.get_flag("data-stdin") {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .context("Failed to read the request message from standard input")?;
+        Some(InputSource::Text { text })
+    } else {
+        None
+    };
+    if stdin.is_some() {
+        args.extend(["--data".to_string(), "@-".to_string()]);
+    }
+    args.push(endpoint.clone());
+
+    let spec = CommandSpec {
+        command: "buf".to_string(),
+        args,
+        cwd: None,
+        env: BTreeMap::new(),
+        stdin,
+    };
+    info!("Invoking {}", url.path());
+    let output = executor.execute_spec_capture_full(&spec).await?;
+    print!("{}", output.stdout);
+    eprint!("{}", output.stderr);
+    if !output.success() {
+        bail!(
+            "buf curl {} failed with {}",
+            endpoint,
+            output
+                .exit_code
+                .map(|code| format!("exit code {}", code))
+                .unwrap_or_else(|| "a signal".to_string())
+        );
+    }
+    Ok(())
+}
+
+/// Refuse endpoints outside `buf.curl_allowed_hosts`, and every endpoint with `--no-network`
+fn check_curl_url(executor: &Executor, url: &Url) -> Result<()> {
+    let refuse = |message: String| {
+        audit::log_security_violation("buf", &["curl".to_string(), url.to_string()], &message);
+        Err(anyhow!(message))
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return refuse(format!("buf curl needs an http or https URL, not {}", url));
+    }
+    if executor.config().safety.no_network {
+        return refuse("Network access is disabled by --no-network".to_string());
+    }
+    let host = url.host_str().unwrap_or_default();
+    if !host_allowed(host, &executor.config().buf.curl_allowed_hosts) {
+        return refuse(format!(
+            "Host '{}' is not in buf.curl_allowed_hosts; add it to call it",
+            host
+        ));
+    }
+    Ok(())
+}
+
+/// Create a `buf.yaml` with `buf config init`, which replaced `buf mod init` in buf 1.32
+async fn execute_mod_init(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let directory = executor.check_path(matches.get_one::<String>("directory").unwrap())?;
+    if directory.join("buf.yaml").exists() {
+        bail!("{} already has a buf.yaml", directory.display());
+    }
+    let args = [
+        "config".to_string(),
+        "init".to_string(),
+        directory.display().to_string(),
+    ];
+
+    info!("Creating buf.yaml in {}", directory.display());
+    executor.execute_secure("buf", &args).await
+}
+
+/// Store a registry token in the OS keyring, keeping it only if `buf registry whoami` accepts
+/// it; the token never appears in arguments or logs
+async fn execute_registry_login(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let host = registry_host(matches)?;
+    if executor.config().safety.no_network {
+        bail!(
+            "Logging in checks the token with {}, which --no-network prevents",
+            host
+        );
+    }
+    let token = read_token(&host)?;
+    if executor.config().safety.dry_run {
+        println!(
+            "DRY RUN: would store the token for {} in the OS keyring",
+            host
+        );
+        return Ok(());
+    }
+
+    let previous = credentials::store_buf_token(&host, &token)?;
+    if env::var_os(credentials::BUF_TOKEN_ENV).is_some() {
+        warn!(
+            "{} is set and buf uses it instead of the stored token, which was not checked",
+            credentials::BUF_TOKEN_ENV
+        );
+        println!("Stored the token for {} in the OS keyring", host);
+        return Ok(());
+    }
+    let output = executor
+        .execute_secure_capture("buf", &["registry", "whoami", host.as_str()])
+        .await?;
+    if !output.success() {
+        match previous {
+            Some(previous) => credentials::store_buf_token(&host, &previous).map(drop)?,
+            None => credentials::delete_buf_token(&host).map(drop)?,
+        }
+        eprint!("{}", output.stderr);
+        bail!("{} rejected the token, which was not stored", host);
+    }
+    print!("{}", output.stdout);
+    println!("Stored the token for {} in the OS keyring", host);
+    Ok(())
+}
+
+/// Remove a registry token from the OS keyring
+fn execute_registry_logout(matches: &ArgMatches) -> Result<()> {
+    let host = registry_host(matches)?;
+    if credentials::delete_buf_token(&host)? {
+        println!("Removed the token for {} from the OS keyring", host);
+    } else {
+        println!("No token for {} is stored", host);
+    }
+    Ok(())
+}
+
+/// `buf registry whoami` for one host, or every host a token is stored for
+async fn execute_registry_whoami(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let hosts = match matches.get_one::<String>("host") {
+        Some(_) => vec![registry_host(matches)?],
+        None => {
+            let hosts = credentials::buf_hosts();
+            if hosts.is_empty() {
+                bail!("No registry token is stored; log in with 'buf registry login'");
+            }
+            hosts
+        }
+    };
+    for host in hosts {
+        info!("Checking the token for {}", host);
+        executor
+            .execute_secure("buf", &["registry", "whoami", host.as_str()])
+            .await?;
+    }
+    Ok(())
+}
+
+/// The `host` argument, which names the keyring entry and so must be a plain host name
+fn registry_host(matches: &ArgMatches) -> Result<String> {
+    static HOST: OnceLock<Regex> = OnceLock::new();
+    let host = matches
+        .get_one::<String>("host")
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_BUF_HOST);
+    let pattern = HOST.get_or_init(|| {
+        Regex::new(r"^[A-Za-z0-9]([A-Za-z0-9.-]*[A-Za-z0-9])?(:[0-9]{1,5})?$").unwrap()
+    });
+    if !pattern.is_match(host) {
+        bail!("'{}' is not a registry host name", host);
+    }
+    Ok(host.to_ascii_lowercase())
+}
+
+/// Read a token from standard input, prompting for it without echo on a terminal
+fn read_token(host: &str) -> Result<String> {
+    let mut token = String::new();
+    if std::io::stdin().is_terminal() {
+        token = line_editor::read_secret(&format!("Token for {}: ", host))?
+            .ok_or_else(|| anyhow!("Login cancelled"))?;
+    } else {
+        std::io::stdin()
+            .read_to_string(&mut token)
+            .context("Failed to read the token from standard input")?;
+    }
+    let token = token.trim();
+    if token.is_empty()
+        || token
+            .chars()
+            .any(|c| c.is_whitespace() || c == ',' || c == '@')
+    {
+        bail!("Expected a single token on standard input");
+    }
+    Ok(token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dep_graph_and_tree() {
+        let dot = r#"digraph {
+
+  "buf.build/acme/petapis" -> "buf.build/acme/paymentapis:6e230f46113f498392c82d12b1a07b70"
+  "buf.build/acme/petapis" -> "buf.build/googleapis/googleapis:61b203b9a9164be9a834f58c37be6f62"
+  "buf.build/acme/paymentapis:6e230f46113f498392c82d12b1a07b70" -> "buf.build/googleapis/googleapis:61b203b9a9164be9a834f58c37be6f62"
+
+}
+"#;
+        let modules = parse_dep_graph(dot);
+        assert_eq!(
+            modules[0],
+            GraphModule {
+                name: "buf.build/acme/paymentapis".to_string(),
+                commit: Some("6e230f46113f498392c82d12b1a07b70".to_string()),
+                dependencies: vec!["buf.build/googleapis/googleapis".to_string()],
+            }
+        );
+        assert_eq!(modules[2].name, "buf.build/googleapis/googleapis");
+        assert_eq!(modules[2].dependencies, Vec::<String>::new());
+        assert_eq!(
+            dep_tree(&modules),
+            [
+                "buf.build/acme/petapis",
+                "  buf.build/acme/paymentapis (6e230f46113f)",
+                "    buf.build/googleapis/googleapis (61b203b9a916)",
+                "  buf.build/googleapis/googleapis (61b203b9a916)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_against_input() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::create_dir_all(dir.path().join("proto")).unwrap();
+        let git = dir.path().join(".git").display().to_string();
+
+        assert_eq!(
+            against_input("main", dir.path()),
+            format!("{}#ref=main", git)
+        );
+        assert_eq!(
+            against_input("3f2a9c1", &dir.path().join("proto")),
+            format!("{}#ref=3f2a9c1,subdir=proto", git)
+        );
+        for given in [
+            ".git#branch=release/1.2",
+            "buf.build/acme/petapis",
+            "buf.build/acme/petapis:6e230f46113f498392c82d12b1a07b70",
+        ] {
+            assert_eq!(against_input(given, dir.path()), given);
+        }
+        assert_eq!(
+            against_input("feature/x", dir.path()),
+            format!("{}#ref=feature/x", git)
+        );
+    }
+
+    #[test]
+    fn test_module_path() {
+        let workspace = vec!["proto/pet".to_string(), "vendor/common/".to_string()];
+        assert_eq!(module_path("proto/pet", &workspace), "proto/pet");
+        assert_eq!(module_path("common", &workspace), "vendor/common/");
+        assert_eq!(module_path("auth", &workspace), "pkg/auth/proto");
+    }
+
+    #[test]
+    fn test_lock_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let lock = dir.path().join("buf.lock");
+        fs::write(
+            &lock,
+            "version: v1\ndeps:\n  - remote: buf.build\n    owner: googleapis\n    repository: googleapis\n    commit: 61b203b9a9164be9a834f58c37be6f62\n",
+        )
+        .unwrap();
+        let before = locked_commits(&lock).unwrap();
+        fs::write(
+            &lock,
+            "version: v2\ndeps:\n  - name: buf.build/googleapis/googleapis\n    commit: 751cbe31638d43a9bfb6162cd2352e67\n    digest: b5:1\n  - name: buf.build/bufbuild/protovalidate\n    commit: a3320276596649bcad929ac829d451f4\n    digest: b5:2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            lock_changes(&before, &locked_commits(&lock).unwrap()),
+            [
+                "Added buf.build/bufbuild/protovalidate a33202765966",
+                "Updated buf.build/googleapis/googleapis 61b203b9a916 -> 751cbe31638d",
+            ]
+        );
+        assert!(locked_commits(&dir.path().join("missing.lock"))
+            .unwrap()
+            .is_empty());
+    }
+}
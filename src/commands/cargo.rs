@@ -0,0 +1,306 @@
+// file: src/commands/cargo.rs
+// version: 1.0.1
+// guid: 3a9d6e21-8c4f-4b57-b1e0-7f25c8d94a16
+
+//! `cargo` command: common cargo workflows with package selection and summarized results
+//!
+//! `build`, `test`, `clippy` and `doc` work on the packages given with `--package`, or the
+//! whole workspace with `--workspace`, and pass `--locked` when `cargo.locked` is set, as the
+//! `ci` profile does, so a stale `Cargo.lock` fails the run instead of being rewritten.
+//!
+//! With `--format json` cargo runs with `--message-format=json`, and instead of its output a
+//! single report is printed: compiler and clippy messages and failed tests as diagnostics (see
+//! [`crate::parsers::cargo`]), a count per severity and, for `test`, how many tests passed,
+//! failed and were ignored.
+
+use crate::executor::Executor;
+use crate::parsers::{self, cargo, Diagnostic, Tool};
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+
+/// Build the cargo command
+pub fn build_command() -> Command {
+    Command::new("cargo")
+        .about("Build, test, lint, format and document Rust code with cargo")
+        .long_about(
+            "Run common cargo workflows on selected packages or the whole workspace. With \
+             cargo.locked set, as in the ci profile, Cargo.lock must be up to date. --format \
+             json summarizes compiler messages, clippy lints and failed tests.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(workflow("build", "Compile the selected packages").arg(release()))
+        .subcommand(
+            workflow("test", "Run the tests of the selected packages")
+                .arg(release())
+                .arg(
+                    Arg::new("no-fail-fast")
+                        .long("no-fail-fast")
+                        .action(ArgAction::SetTrue)
+                        .help("Run every test binary even after one fails"),
+                ),
+        )
+        .subcommand(
+            workflow("clippy", "Lint the selected packages with clippy")
+                .arg(
+                    Arg::new("all-targets")
+                        .long("all-targets")
+                        .action(ArgAction::SetTrue)
+                        .help("Also lint tests, benches and examples"),
+                )
+                .arg(
+                    Arg::new("deny-warnings")
+                        .long("deny-warnings")
+                        .short('D')
+                        .action(ArgAction::SetTrue)
+                        .help("Fail on any warning (-D warnings)"),
+                ),
+        )
+        .subcommand(
+            workflow("doc", "Build the documentation of the selected packages").arg(
+                Arg::new("no-deps")
+                    .long("no-deps")
+                    .action(ArgAction::SetTrue)
+                    .help("Do not document dependencies"),
+            ),
+        )
+        .subcommand(
+            selection(Command::new("fmt").about("Format the selected packages with rustfmt"))
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .action(ArgAction::SetTrue)
+                        .help("Fail if files are not formatted instead of rewriting them"),
+                )
+                .arg(extra_args()),
+        )
+}
+
+/// A subcommand that compiles: package selection, `--locked`, `--format` and extra arguments
+fn workflow(name: &'static str, about: &'static str) -> Command {
+    selection(Command::new(name).about(about))
+        .arg(
+            Arg::new("locked")
+                .long("locked")
+                .action(ArgAction::SetTrue)
+                .help("Fail if Cargo.lock needs changes (the default with cargo.locked)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format: cargo's own output, or a JSON summary"),
+        )
+        .arg(extra_args())
+}
+
+fn selection(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("package")
+                .long("package")
+                .short('p')
+                .value_name("SPEC")
+                .action(ArgAction::Append)
+                .conflicts_with("workspace")
+                .help("Package to work on; repeat for several"),
+        )
+        .arg(
+            Arg::new("workspace")
+                .long("workspace")
+                .action(ArgAction::SetTrue)
+                .help("Work on every package in the workspace"),
+        )
+}
+
+fn release() -> Arg {
+    Arg::new("release")
+        .long("release")
+        .short('r')
+        .action(ArgAction::SetTrue)
+        .help("Build with the release profile")
+}
+
+fn extra_args() -> Arg {
+    Arg::new("args")
+        .value_name("ARGS")
+        .num_args(0..)
+        .last(true)
+        .allow_hyphen_values(true)
+        .help(
+            "Further arguments for cargo after --; those after a second -- go to the test \
+             binaries, the lints or rustfmt, e.g. -- my_test -- --nocapture",
+        )
+}
+
+/// Execute the cargo command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let Some((subcommand, sub_matches)) = matches.subcommand() else {
+        unreachable!("subcommand required")
+    };
+    let json = subcommand != "fmt"
+        && sub_matches.get_one::<String>("format").map(String::as_str) == Some("json");
+    let args = cargo_args(
+        subcommand,
+        sub_matches,
+        executor.config().cargo.locked,
+        json,
+    );
+    if !json {
+        return executor.execute_secure("cargo", &args).await;
+    }
+
+    let output = executor.execute_secure_capture("cargo", &args).await?;
+    let root = executor.sandbox().base();
+    let diagnostics: Vec<Diagnostic> = Tool::Cargo
+        .parse(&output.stdout, &output.stderr)
+        .into_iter()
+        .map(|diagnostic| diagnostic.relative_to(root))
+        .collect();
+    let mut report = json!({
+        "command": subcommand,
+        "args": args,
+        "exit_code": output.exit_code,
+        "diagnostics": diagnostics,
+        "summary": parsers::summary(&diagnostics),
+    });
+    if subcommand == "test" {
+        report["tests"] = json!(cargo::test_counts(&output.stdout));
+    }
+    // Without findings, cargo's own errors are the only explanation of a failure
+    if !output.success() && diagnostics.is_empty() {
+        report["stderr"] = json!(output.stderr);
+    }
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if !output.success() {
+        bail!(
+            "cargo {} failed with {}",
+            subcommand,
+            output
+                .exit_code
+                .map(|code| format!("exit code {}", code))
+                .unwrap_or_else(|| "a signal".to_string())
+        );
+    }
+    Ok(())
+}
+
+/// Arguments for `cargo <subcommand>`; `locked` is `cargo.locked`
+fn cargo_args(subcommand: &str, matches: &ArgMatches, locked: bool, json: bool) -> Vec<String> {
+    let flag = |id: &str| matches.try_get_one::<bool>(id).ok().flatten() == Some(&true);
+    let mut args = vec![subcommand.to_string()];
+    if json {
+        args.push("--message-format=json".to_string());
+    }
+    if subcommand != "fmt" && (locked || flag("locked")) {
+        args.push("--locked".to_string());
+    }
+    if flag("workspace") {
+        // rustfmt predates workspaces and calls them --all
+        args.push(
+            if subcommand == "fmt" {
+                "--all"
+            } else {
+                "--workspace"
+            }
+            .to_string(),
+        );
+    }
+    for package in matches.get_many::<String>("package").unwrap_or_default() {
+        args.push("--package".to_string());
+        args.push(package.clone());
+    }
+    for id in ["release", "no-fail-fast", "all-targets", "no-deps", "check"] {
+        if flag(id) {
+            args.push(format!("--{}", id));
+        }
+    }
+
+    let extra: Vec<String> = matches
+        .get_many::<String>("args")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    // Lint levels for clippy follow a `--`, which the extra arguments may already have
+    let separated = extra.iter().any(|arg| arg == "--");
+    args.extend(extra);
+    if flag("deny-warnings") {
+        if !separated {
+            args.push("--".to_string());
+        }
+        args.extend(["-D".to_string(), "warnings".to_string()]);
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command_line: &[&str], locked: bool, json: bool) -> Vec<String> {
+        let matches = build_command()
+            .try_get_matches_from(std::iter::once("cargo").chain(command_line.iter().copied()))
+            .unwrap();
+        let (subcommand, matches) = matches.subcommand().unwrap();
+        cargo_args(subcommand, matches, locked, json)
+    }
+
+    #[test]
+    fn test_cargo_args() {
+        assert_eq!(
+            args(
+                &[
+                    "test",
+                    "-p",
+                    "core",
+                    "-p",
+                    "cli",
+                    "--",
+                    "parse",
+                    "--",
+                    "--nocapture"
+                ],
+                true,
+                true
+            ),
+            [
+                "test",
+                "--message-format=json",
+                "--locked",
+                "--package",
+                "core",
+                "--package",
+                "cli",
+                "parse",
+                "--",
+                "--nocapture",
+            ]
+        );
+        assert_eq!(
+            args(
+                &["clippy", "--workspace", "--all-targets", "-D"],
+                false,
+                false
+            ),
+            [
+                "clippy",
+                "--workspace",
+                "--all-targets",
+                "--",
+                "-D",
+                "warnings"
+            ]
+        );
+        assert_eq!(
+            args(&["fmt", "--workspace", "--check"], true, false),
+            ["fmt", "--all", "--check"]
+        );
+        assert!(build_command()
+            .try_get_matches_from(["cargo", "build", "-p", "core", "--workspace"])
+            .is_err());
+    }
+}
@@ -0,0 +1,204 @@
+// file: src/commands/cloud.rs
+// version: 1.0.1
+// guid: 5a1e8c3d-72f4-4b96-8d0a-e6c2b9f4137d
+
+//! `cloud` command: run the aws, gcloud and az CLIs, read-only unless asked otherwise
+//!
+//! `cloud aws ...`, `cloud gcloud ...` and `cloud az ...` pass their arguments to the CLI after
+//! printing the account it is about to act as, with the project or subscription, on stderr, so
+//! a call against the wrong one stands out. Commands that describe, list, get or show
+//! resources run as they are. Anything else needs `--allow-mutations` and
+//! `policy.allow_cloud_mutations`; the policy switch also governs these CLIs run any other way.
+
+use super::export::{Effect, Remote};
+use crate::executor::Executor;
+use crate::security::audit;
+use crate::security::policy::cloud_mutates;
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::Value;
+
+/// Build the cloud command
+pub fn build_command() -> Command {
+    Command::new("cloud")
+        .add(Effect::Destructive)
+        .add(Remote)
+        .about("Run aws, gcloud and az; only describe, list and get without --allow-mutations")
+        .long_about(
+            "Run the AWS, Google Cloud and Azure CLIs. The account, project or subscription a \
+             call will use is printed first. Commands other than describe, list, get and show \
+             need --allow-mutations and policy.allow_cloud_mutations.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(cli(Command::new("aws").about("Run the AWS CLI"), "s3 ls"))
+        .subcommand(cli(
+            Command::new("gcloud").about("Run the Google Cloud CLI"),
+            "compute instances list",
+        ))
+        .subcommand(cli(
+            Command::new("az").about("Run the Azure CLI"),
+            "vm list",
+        ))
+}
+
+/// Add `--allow-mutations` and the arguments for the CLI
+fn cli(command: Command, example: &str) -> Command {
+    command
+        .arg(
+            Arg::new("allow-mutations")
+                .long("allow-mutations")
+                .action(ArgAction::SetTrue)
+                .help("Allow commands that create, change or delete resources (also needs policy.allow_cloud_mutations)"),
+        )
+        .arg(
+            Arg::new("args")
+                .value_name("ARGS")
+                .num_args(1..)
+                .required(true)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .help(format!("Arguments for the CLI, e.g. {}", example)),
+        )
+}
+
+/// Execute the cloud command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let Some((cli, sub_matches)) = matches.subcommand() else {
+        unreachable!("subcommand required");
+    };
+    let args: Vec<String> = sub_matches
+        .get_many::<String>("args")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+
+    if cloud_mutates(cli, &args) {
+        if !sub_matches.get_flag("allow-mutations") {
+            bail!(
+                "{} {} may change resources; pass --allow-mutations to run it",
+                cli,
+                args.join(" ")
+            );
+        }
+        if let Err(e) = executor.policy().evaluate_cloud_mutations(cli) {
+            audit::log_security_violation(cli, &args, &e.to_string());
+            return Err(e.into());
+        }
+    }
+
+    if !executor.config().safety.dry_run {
+        eprintln!("{}", identity(executor, cli, &args).await?);
+    }
+    executor.execute_secure(cli, &args).await
+}
+
+/// Who `cli` would act as with `args`: the account, and the project or subscription
+async fn identity(executor: &Executor, cli: &str, args: &[String]) -> Result<String> {
+    let mut query: Vec<String> = match cli {
+        "aws" => vec!["sts", "get-caller-identity", "--output", "json"],
+        "gcloud" => vec!["config", "list", "--format=json"],
+        _ => vec!["account", "show", "--output", "json"],
+    }
+    .into_iter()
+    .map(str::to_string)
+    .collect();
+    // Options that select another account
+    let selector = match cli {
+        "aws" => "--profile",
+        "gcloud" => "--configuration",
+        _ => "--subscription",
+    };
+    if let Some(value) = option_value(args, selector) {
+        query.push(format!("{}={}", selector, value));
+    }
+
+    let output = executor.execute_secure_capture(cli, &query).await?;
+    if !output.success() {
+        bail!(
+            "Cannot tell which account {} would use; log in first: {}",
+            cli,
+            output.stderr.trim()
+        );
+    }
+    let value: Value = serde_json::from_str(&output.stdout)
+        .with_context(|| format!("Failed to parse {} {}", cli, query.join(" ")))?;
+    describe_identity(cli, &value, args)
+        .with_context(|| format!("{} reports no active account; log in first", cli))
+}
+
+/// One line naming the account in `value`, the output of the CLI's identity query
+fn describe_identity(cli: &str, value: &Value, args: &[String]) -> Option<String> {
+    match cli {
+        "aws" => Some(format!(
+            "AWS account {} as {}",
+            value["Account"].as_str()?,
+            value["Arn"].as_str()?
+        )),
+        "gcloud" => {
+            let project = option_value(args, "--project")
+                .or_else(|| value["core"]["project"].as_str())
+                .unwrap_or("(none)");
+            Some(format!(
+                "GCP account {}, project {}",
+                value["core"]["account"].as_str()?,
+                project
+            ))
+        }
+        _ => Some(format!(
+            "Azure subscription {} ({}) as {}",
+            value["name"].as_str()?,
+            value["id"].as_str()?,
+            value["user"]["name"].as_str().unwrap_or("unknown user")
+        )),
+    }
+}
+
+/// The value of `--name value` or `--name=value` in `args`
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == name {
+            args.get(i + 1).map(String::as_str)
+        } else {
+            arg.strip_prefix(name)?.strip_prefix('=')
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_describe_identity() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let aws = json!({"UserId": "AIDA", "Account": "123456789012", "Arn": "arn:aws:iam::123456789012:user/dev"});
+        assert_eq!(
+            describe_identity("aws", &aws, &[]).unwrap(),
+            "AWS account 123456789012 as arn:aws:iam::123456789012:user/dev"
+        );
+
+        let gcloud = json!({"core": {"account": "dev@example.com", "project": "sandbox"}});
+        assert_eq!(
+            describe_identity(
+                "gcloud",
+                &gcloud,
+                &args(&["projects", "list", "--project=prod"])
+            )
+            .unwrap(),
+            "GCP account dev@example.com, project prod"
+        );
+        assert!(describe_identity("gcloud", &json!({"core": {}}), &[]).is_none());
+
+        let az = json!({"name": "Dev", "id": "0000-1111", "user": {"name": "dev@example.com", "type": "user"}});
+        assert_eq!(
+            describe_identity("az", &az, &[]).unwrap(),
+            "Azure subscription Dev (0000-1111) as dev@example.com"
+        );
+        assert_eq!(
+            option_value(&args(&["--profile", "ops", "s3", "ls"]), "--profile"),
+            Some("ops")
+        );
+    }
+}
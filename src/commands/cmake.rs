@@ -0,0 +1,496 @@
+// file: src/commands/cmake.rs
+// version: 1.0.0
+// guid: 4b9e2d71-06a8-4c3f-b5d2-e81f7a6c0934
+
+//! `cmake` command: configure, build and test CMake projects inside the workspace
+//!
+//! `configure` runs `cmake -S SRC -B BUILD`, or `cmake --preset NAME`; `build` runs
+//! `cmake --build` and `test` runs ctest in the build directory. The build directory is checked
+//! against the workspace roots before CMake runs, also when a preset's `binaryDir` names it:
+//! `${sourceDir}`, `${sourceParentDir}`, `${sourceDirName}` and `${presetName}` are expanded,
+//! and a preset whose build directory depends on anything else, such as `$env{HOME}`, is
+//! refused, since where it leads cannot be checked.
+//!
+//! `cmake.jobs` limits parallel build jobs and concurrent tests unless `-j` is given.
+//! `test --format json` reports every test with its status and duration (see
+//! [`crate::parsers::ctest`]).
+
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use crate::parsers::{self, ctest, Diagnostic, Tool};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files CMake reads presets from, in the source directory
+const PRESET_FILES: &[&str] = &["CMakePresets.json", "CMakeUserPresets.json"];
+
+/// Build the cmake command
+pub fn build_command() -> Command {
+    Command::new("cmake")
+        .about(
+            "Configure, build and test CMake projects with build directories kept in the workspace",
+        )
+        .long_about(
+            "Run cmake and ctest with build directories checked against the workspace roots, \
+             including the binaryDir of presets. cmake.jobs limits parallel jobs; test --format \
+             json reports the status of every test.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            project_args(Command::new("configure").about("Generate the build system"))
+                .arg(
+                    Arg::new("generator")
+                        .long("generator")
+                        .short('G')
+                        .value_name("NAME")
+                        .help("Generator, e.g. Ninja (default: cmake.generator)"),
+                )
+                .arg(
+                    Arg::new("build-type")
+                        .long("build-type")
+                        .value_name("TYPE")
+                        .help("CMAKE_BUILD_TYPE, e.g. Debug or Release"),
+                )
+                .arg(
+                    Arg::new("define")
+                        .short('D')
+                        .value_name("VAR=VALUE")
+                        .action(ArgAction::Append)
+                        .help("Set a cache variable"),
+                ),
+        )
+        .subcommand(
+            project_args(Command::new("build").about("Build the project"))
+                .arg(jobs())
+                .arg(config())
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .short('t')
+                        .value_name("TARGET")
+                        .action(ArgAction::Append)
+                        .help("Build TARGET instead of the default targets"),
+                )
+                .arg(
+                    Arg::new("clean-first")
+                        .long("clean-first")
+                        .action(ArgAction::SetTrue)
+                        .help("Remove the build outputs first"),
+                ),
+        )
+        .subcommand(
+            project_args(Command::new("test").about("Run the tests with ctest"))
+                .arg(jobs())
+                .arg(config())
+                .arg(
+                    Arg::new("tests-regex")
+                        .long("tests-regex")
+                        .short('R')
+                        .value_name("REGEX")
+                        .help("Only run tests whose names match REGEX"),
+                )
+                .arg(
+                    Arg::new("exclude-regex")
+                        .long("exclude-regex")
+                        .short('E')
+                        .value_name("REGEX")
+                        .help("Skip tests whose names match REGEX"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format: ctest's own output, or a JSON report"),
+                ),
+        )
+}
+
+/// Add the arguments that locate the source and build directories
+fn project_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("source")
+                .long("source")
+                .short('S')
+                .value_name("DIR")
+                .default_value(".")
+                .help("Source directory, with CMakeLists.txt and any presets"),
+        )
+        .arg(
+            Arg::new("build-dir")
+                .long("build-dir")
+                .short('B')
+                .value_name("DIR")
+                .help("Build directory (default: the preset's binaryDir, or cmake.build_dir)"),
+        )
+        .arg(
+            Arg::new("preset")
+                .long("preset")
+                .value_name("NAME")
+                .help("Preset of CMakePresets.json or CMakeUserPresets.json"),
+        )
+}
+
+fn jobs() -> Arg {
+    Arg::new("jobs")
+        .long("jobs")
+        .short('j')
+        .value_name("N")
+        .value_parser(clap::value_parser!(usize))
+        .help("Parallel build jobs or concurrent tests (default: cmake.jobs)")
+}
+
+fn config() -> Arg {
+    Arg::new("config")
+        .long("config")
+        .short('C')
+        .value_name("CONFIG")
+        .help("Configuration of a multi-config generator, e.g. Release")
+}
+
+/// Execute the cmake command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let Some((subcommand, sub_matches)) = matches.subcommand() else {
+        unreachable!("subcommand required");
+    };
+    let source = executor.check_path(sub_matches.get_one::<String>("source").unwrap())?;
+    let preset = sub_matches.get_one::<String>("preset");
+    let build_dir = build_dir(subcommand, sub_matches, &source, executor)?;
+    let config = &executor.config().cmake;
+    let jobs = sub_matches
+        .try_get_one::<usize>("jobs")
+        .ok()
+        .flatten()
+        .copied()
+        .or(config.jobs);
+    let value = |id: &str| sub_matches.try_get_one::<String>(id).ok().flatten();
+
+    let mut args: Vec<String> = Vec::new();
+    let program = match subcommand {
+        "configure" => {
+            match preset {
+                Some(preset) => args.extend(["--preset".to_string(), preset.clone()]),
+                None => args.extend(["-S".to_string(), source.display().to_string()]),
+            }
+            args.extend(["-B".to_string(), build_dir.display().to_string()]);
+            if let Some(generator) =
+                value("generator").or(config.generator.as_ref().filter(|_| preset.is_none()))
+            {
+                args.extend(["-G".to_string(), generator.clone()]);
+            }
+            if let Some(build_type) = value("build-type") {
+                args.push(format!("-DCMAKE_BUILD_TYPE={}", build_type));
+            }
+            for define in sub_matches.get_many::<String>("define").unwrap_or_default() {
+                args.push(format!("-D{}", define));
+            }
+            "cmake"
+        }
+        "build" => {
+            check_configured(&build_dir, executor)?;
+            args.push("--build".to_string());
+            match preset {
+                Some(preset) => args.extend(["--preset".to_string(), preset.clone()]),
+                None => args.push(build_dir.display().to_string()),
+            }
+            if let Some(jobs) = jobs {
+                args.extend(["--parallel".to_string(), jobs.to_string()]);
+            }
+            if let Some(config) = value("config") {
+                args.extend(["--config".to_string(), config.clone()]);
+            }
+            for target in sub_matches.get_many::<String>("target").unwrap_or_default() {
+                args.extend(["--target".to_string(), target.clone()]);
+            }
+            if sub_matches.get_flag("clean-first") {
+                args.push("--clean-first".to_string());
+            }
+            "cmake"
+        }
+        _ => {
+            check_configured(&build_dir, executor)?;
+            args.push("--output-on-failure".to_string());
+            match preset {
+                Some(preset) => args.extend(["--preset".to_string(), preset.clone()]),
+                None => args.extend(["--test-dir".to_string(), build_dir.display().to_string()]),
+            }
+            if let Some(jobs) = jobs {
+                args.extend(["--parallel".to_string(), jobs.to_string()]);
+            }
+            for (id, flag) in [
+                ("config", "-C"),
+                ("tests-regex", "-R"),
+                ("exclude-regex", "-E"),
+            ] {
+                if let Some(value) = value(id) {
+                    args.extend([flag.to_string(), value.clone()]);
+                }
+            }
+            "ctest"
+        }
+    };
+
+    let spec = CommandSpec {
+        command: program.to_string(),
+        args,
+        cwd: Some(source.clone()),
+        env: BTreeMap::new(),
+        stdin: None,
+    };
+    let output = executor.execute_spec_capture_full(&spec).await?;
+    let json = value("format").map(String::as_str) == Some("json");
+    if json {
+        let results = ctest::results(&output.stdout);
+        let diagnostics: Vec<Diagnostic> = Tool::Ctest.parse(&output.stdout, &output.stderr);
+        let mut report = json!({
+            "command": "test",
+            "args": spec.args,
+            "exit_code": output.exit_code,
+            "build_dir": build_dir,
+            "tests": ctest::test_counts(&results),
+            "results": results,
+            "diagnostics": diagnostics,
+            "summary": parsers::summary(&diagnostics),
+        });
+        // Without failed tests, ctest's own errors are the only explanation of a failure
+        if !output.success() && diagnostics.is_empty() {
+            report["stderr"] = json!(output.stderr);
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+    }
+    if !output.success() {
+        bail!(
+            "{} failed with {}",
+            spec,
+            output
+                .exit_code
+                .map(|code| format!("exit code {}", code))
+                .unwrap_or_else(|| "a signal".to_string())
+        );
+    }
+    Ok(())
+}
+
+/// The build directory `subcommand` works in: `-B`, the preset's `binaryDir` or
+/// `cmake.build_dir`, refused if it lies outside the workspace
+fn build_dir(
+    subcommand: &str,
+    matches: &ArgMatches,
+    source: &Path,
+    executor: &Executor,
+) -> Result<PathBuf> {
+    if let Some(dir) = matches.get_one::<String>("build-dir") {
+        return executor.check_path(dir);
+    }
+    let dir = match matches.get_one::<String>("preset") {
+        Some(preset) => match Presets::load(source)?.binary_dir(subcommand, preset)? {
+            Some(dir) => dir,
+            // A build or test preset builds where its configure preset configured
+            None if subcommand != "configure" => bail!(
+                "Preset '{}' sets no binaryDir; pass the build directory with -B",
+                preset
+            ),
+            None => source.join(&executor.config().cmake.build_dir),
+        },
+        None => source.join(&executor.config().cmake.build_dir),
+    };
+    executor.check_path(dir)
+}
+
+/// Refuse to build or test in a directory that was never configured
+fn check_configured(build_dir: &Path, executor: &Executor) -> Result<()> {
+    if !executor.config().safety.dry_run && !build_dir.join("CMakeCache.txt").is_file() {
+        bail!(
+            "{} is not configured; run cmake configure first",
+            build_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// The presets of a project: those of its preset files and of the files they include
+struct Presets {
+    source: PathBuf,
+    configure: Vec<Value>,
+    build: Vec<Value>,
+    test: Vec<Value>,
+}
+
+impl Presets {
+    fn load(source: &Path) -> Result<Self> {
+        let mut presets = Self {
+            source: source.to_path_buf(),
+            configure: Vec::new(),
+            build: Vec::new(),
+            test: Vec::new(),
+        };
+        let mut pending: Vec<PathBuf> = PRESET_FILES
+            .iter()
+            .rev()
+            .map(|name| source.join(name))
+            .filter(|path| path.is_file())
+            .collect();
+        if pending.is_empty() {
+            bail!("{} has no {}", source.display(), PRESET_FILES.join(" or "));
+        }
+        let mut seen = HashSet::new();
+        while let Some(file) = pending.pop() {
+            if !seen.insert(file.clone()) {
+                continue;
+            }
+            let content = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let document: Value = serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", file.display()))?;
+            for (key, list) in [
+                ("configurePresets", &mut presets.configure),
+                ("buildPresets", &mut presets.build),
+                ("testPresets", &mut presets.test),
+            ] {
+                list.extend(document[key].as_array().cloned().unwrap_or_default());
+            }
+            let dir = file.parent().unwrap_or(source);
+            for include in document["include"].as_array().into_iter().flatten() {
+                if let Some(include) = include.as_str() {
+                    pending.push(dir.join(include));
+                }
+            }
+        }
+        Ok(presets)
+    }
+
+    /// The expanded `binaryDir` of the configure preset that `name`, a preset for
+    /// `subcommand`, configures with; `None` if it sets none
+    fn binary_dir(&self, subcommand: &str, name: &str) -> Result<Option<PathBuf>> {
+        let (kind, list) = match subcommand {
+            "configure" => ("configure", &self.configure),
+            "build" => ("build", &self.build),
+            _ => ("test", &self.test),
+        };
+        if !list.iter().any(|preset| preset["name"] == name) {
+            bail!("No {} preset named '{}'", kind, name);
+        }
+        let configure = match kind {
+            "configure" => name.to_string(),
+            _ => field(list, name, "configurePreset", 0)
+                .ok_or_else(|| anyhow!("{} preset '{}' names no configurePreset", kind, name))?,
+        };
+        match field(&self.configure, &configure, "binaryDir", 0) {
+            Some(dir) => Ok(Some(self.expand(&dir, &configure)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// `dir` with the macros of the configure preset `preset` expanded, relative to the
+    /// source directory
+    fn expand(&self, dir: &str, preset: &str) -> Result<PathBuf> {
+        let name = |path: &Path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let parent = self.source.parent().unwrap_or(&self.source);
+        let expanded = dir
+            .replace("${sourceDir}", &self.source.to_string_lossy())
+            .replace("${sourceParentDir}", &parent.to_string_lossy())
+            .replace("${sourceDirName}", &name(&self.source))
+            .replace("${presetName}", preset);
+        if ["${", "$env{", "$penv{", "$vendor{"]
+            .iter()
+            .any(|start| expanded.replace("${dollar}", "").contains(start))
+        {
+            bail!(
+                "binaryDir '{}' of preset '{}' uses macros whose value cannot be checked; pass the \
+                 build directory with -B",
+                dir,
+                preset
+            );
+        }
+        Ok(self.source.join(expanded.replace("${dollar}", "$")))
+    }
+}
+
+/// `key` of the preset `name` in `list`, or of the first preset it inherits from that sets it
+fn field(list: &[Value], name: &str, key: &str, depth: usize) -> Option<String> {
+    // Presets cannot inherit in a cycle, but a broken file should not hang the command
+    if depth > 32 {
+        return None;
+    }
+    let preset = list.iter().find(|preset| preset["name"] == name)?;
+    if let Some(value) = preset[key].as_str() {
+        return Some(value.to_string());
+    }
+    let parents: Vec<&str> = match &preset["inherits"] {
+        Value::String(parent) => vec![parent.as_str()],
+        Value::Array(parents) => parents.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    parents
+        .into_iter()
+        .find_map(|parent| field(list, parent, key, depth + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_binary_dirs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let source = dir.path().join("app");
+        fs::create_dir_all(source.join("cmake")).unwrap();
+        fs::write(
+            source.join("CMakePresets.json"),
+            r#"{
+                "version": 6,
+                "include": ["cmake/ci.json"],
+                "configurePresets": [
+                    {"name": "base", "hidden": true, "binaryDir": "${sourceDir}/out/${presetName}"},
+                    {"name": "debug", "inherits": "base"},
+                    {"name": "home", "binaryDir": "$env{HOME}/build"},
+                    {"name": "bare"}
+                ],
+                "buildPresets": [{"name": "debug", "configurePreset": "debug"}],
+                "testPresets": [{"name": "unit", "inherits": ["other", "ci"]}]
+            }"#,
+        )
+        .unwrap();
+        fs::write(
+            source.join("cmake/ci.json"),
+            r#"{
+                "version": 6,
+                "configurePresets": [{"name": "ci", "binaryDir": "../${sourceDirName}-ci"}],
+                "testPresets": [{"name": "ci", "hidden": true, "configurePreset": "ci"}]
+            }"#,
+        )
+        .unwrap();
+
+        let presets = Presets::load(&source).unwrap();
+        assert_eq!(
+            presets.binary_dir("configure", "debug").unwrap(),
+            Some(source.join("out/debug"))
+        );
+        assert_eq!(
+            presets.binary_dir("build", "debug").unwrap(),
+            Some(source.join("out/debug"))
+        );
+        assert_eq!(
+            presets.binary_dir("test", "unit").unwrap(),
+            Some(source.join("../app-ci"))
+        );
+        assert_eq!(presets.binary_dir("configure", "bare").unwrap(), None);
+        let error = presets.binary_dir("configure", "home").unwrap_err();
+        assert!(error.to_string().contains("cannot be checked"));
+        assert!(presets.binary_dir("build", "release").is_err());
+    }
+}
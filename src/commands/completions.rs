@@ -0,0 +1,212 @@
+// file: src/commands/completions.rs
+// version: 1.1.0
+// guid: 4d1f8b62-9e3a-4c75-a0b8-6f2d7e9c1a34
+
+//! `completions` command: shell completion scripts
+//!
+//! The script printed for a shell registers the utility as its own completer: on every Tab the
+//! shell runs `COMPLETE=<shell> safe-ai-util -- <words>`, which [`CompleteEnv`] answers from
+//! the command tree before anything else runs, so completions never fall behind the
+//! subcommands. Values that depend on the machine and the project are looked up when asked
+//! for: the profiles of the configuration files in use for `--profile`, and the steps of the
+//! manifest given with `--manifest` for `run`. [`complete_line`] answers the same way for a
+//! line typed at the `repl` prompt.
+//!
+//! [`CompleteEnv`]: clap_complete::CompleteEnv
+
+use crate::config::Config;
+use crate::manifest::Manifest;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::Shells;
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Environment variable through which a registered shell asks for completions
+pub const COMPLETE_ENV: &str = "COMPLETE";
+
+/// Shells completion scripts are printed for
+pub const SHELLS: [&str; 4] = ["bash", "zsh", "fish", "powershell"];
+
+thread_local! {
+    /// Words and working directory of a line completed by [`complete_line`], which the value
+    /// candidates read instead of this process's arguments
+    static LINE: RefCell<Option<(Vec<String>, PathBuf)>> = const { RefCell::new(None) };
+}
+
+/// Build the completions command
+pub fn build_command() -> Command {
+    Command::new("completions")
+        .about("Print the shell completion script")
+        .long_about(
+            "Print the completion script for a shell. Load it from the shell's startup file, \
+             e.g. `source <(safe-ai-util completions bash)` in ~/.bashrc, \
+             `source <(safe-ai-util completions zsh)` after compinit in ~/.zshrc, \
+             `safe-ai-util completions fish | source` in config.fish, or \
+             `safe-ai-util completions powershell | Out-String | Invoke-Expression` in $PROFILE. \
+             `setup` installs it for bash, zsh and fish.",
+        )
+        .arg(
+            Arg::new("shell")
+                .value_name("SHELL")
+                .value_parser(SHELLS)
+                .required(true)
+                .help("Shell to complete for"),
+        )
+}
+
+/// Execute the completions command; `cli` is the whole command line interface
+pub fn execute(matches: &ArgMatches, cli: &Command) -> Result<()> {
+    let shell = matches.get_one::<String>("shell").unwrap();
+    print!("{}", script(shell, cli)?);
+    Ok(())
+}
+
+/// The script registering this executable as the completer of `shell`
+pub fn script(shell: &str, cli: &Command) -> Result<String> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell)
+        .ok_or_else(|| anyhow!("Completions for {} are not supported", shell))?;
+
+    // Complete the name this executable was invoked as, so the legacy name works too, and run
+    // it by that name unless it was given as a path
+    let invoked = std::env::args_os()
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let bin = invoked
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| cli.get_name().to_string());
+    let program = if invoked.components().count() > 1 {
+        std::env::current_dir()?.join(&invoked)
+    } else {
+        invoked
+    };
+
+    let mut script = Vec::new();
+    completer.write_registration(
+        COMPLETE_ENV,
+        cli.get_name(),
+        &bin,
+        &program.to_string_lossy(),
+        &mut script,
+    )?;
+    Ok(String::from_utf8(script)?)
+}
+
+/// Built-in profiles and those defined in the configuration files in use, for `--profile`
+pub fn profile_candidates() -> Vec<CompletionCandidate> {
+    let mut names = Config::profile_names(&toml::Value::Table(Default::default()));
+    for source in Config::config_layers(None) {
+        let Some(content) = source
+            .path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+        else {
+            continue;
+        };
+        if let Ok(value) = toml::from_str::<toml::Value>(&content) {
+            names.extend(Config::profile_names(&value));
+        }
+    }
+    names.sort();
+    names.dedup();
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Steps of the manifest given with `--manifest` on the command line being completed, with
+/// the matrix steps their variants belong to, for `run`
+pub fn step_candidates() -> Vec<CompletionCandidate> {
+    let (args, cwd) = LINE
+        .with(|line| line.borrow().clone())
+        .unwrap_or_else(|| (std::env::args().collect(), PathBuf::new()));
+    let Some(path) = manifest_arg(&args) else {
+        return Vec::new();
+    };
+    let Ok(manifest) = Manifest::load(&cwd.join(path)) else {
+        return Vec::new();
+    };
+    let mut names: Vec<&str> = Vec::new();
+    for step in &manifest.steps {
+        if let Some(variant) = &step.variant {
+            if !names.contains(&variant.group.as_str()) {
+                names.push(&variant.group);
+            }
+        }
+        names.push(&step.name);
+    }
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Complete the word at `index` of `words`, which start with the program name, as the shell
+/// would for that command line run in `cwd`
+pub fn complete_line(cli: &mut Command, words: &[String], index: usize, cwd: &Path) -> Vec<String> {
+    LINE.with(|line| *line.borrow_mut() = Some((words.to_vec(), cwd.to_path_buf())));
+    let args = words.iter().map(OsString::from).collect();
+    let candidates = clap_complete::engine::complete(cli, args, index, Some(cwd));
+    LINE.with(|line| line.borrow_mut().take());
+    candidates
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|candidate| !candidate.is_hide_set())
+        .map(|candidate| candidate.get_value().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// The value of the last `--manifest` or `-m` option among the words being completed
+fn manifest_arg(args: &[String]) -> Option<&str> {
+    let mut found = None;
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--manifest" || arg == "-m" {
+            found = args.get(i + 1).map(String::as_str);
+        } else if let Some(value) = arg.strip_prefix("--manifest=") {
+            found = Some(value);
+        }
+    }
+    found.filter(|path| !path.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_arg() {
+        let args = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            manifest_arg(&args(&[
+                "safe-ai-util",
+                "--",
+                "safe-ai-util",
+                "run",
+                "-m",
+                "tasks.yaml",
+                ""
+            ])),
+            Some("tasks.yaml")
+        );
+        assert_eq!(
+            manifest_arg(&args(&[
+                "safe-ai-util",
+                "run",
+                "--manifest=ci.toml",
+                "lint"
+            ])),
+            Some("ci.toml")
+        );
+        assert_eq!(manifest_arg(&args(&["safe-ai-util", "run", "-m"])), None);
+        assert_eq!(manifest_arg(&args(&["safe-ai-util", "run", "lint"])), None);
+    }
+
+    #[test]
+    fn test_script_registers_completer() {
+        let cli = Command::new("safe-ai-util").subcommand(build_command());
+        for shell in SHELLS {
+            let script = script(shell, &cli).unwrap();
+            assert!(script.contains(COMPLETE_ENV), "{}: {}", shell, script);
+        }
+    }
+}
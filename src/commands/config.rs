@@ -0,0 +1,503 @@
+// file: src/commands/config.rs
+// version: 1.4.2
+// guid: bd5cf17b-3f9c-43b1-aefb-d5635a8d408e
+
+//! `config` command: inspect, create, edit and validate configuration
+
+use super::export::Effect;
+use crate::config::{
+    self, is_known_key, validate_toml, Config, ConfigSource, LoadOptions, ResolvedConfig,
+    BUILTIN_PROFILES, CONFIG_KEYS, PROJECT_CONFIG_FILE,
+};
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+/// Build the config command
+pub fn build_command() -> Command {
+    Command::new("config")
+        .about("Inspect, create, edit and validate configuration")
+        .subcommand(
+            Command::new("show")
+                .add(Effect::ReadOnly)
+                .about("Print the effective configuration after merging all layers")
+                .arg(
+                    Arg::new("resolved")
+                        .long("resolved")
+                        .action(ArgAction::SetTrue)
+                        .help("Annotate every value with the layer it came from"),
+                ),
+        )
+        .subcommand(
+            with_target(Command::new("init").about("Write a commented configuration file")).arg(
+                Arg::new("force")
+                    .long("force")
+                    .action(ArgAction::SetTrue)
+                    .help("Overwrite an existing file"),
+            ),
+        )
+        .subcommand(
+            Command::new("get")
+                .add(Effect::ReadOnly)
+                .about("Print the effective value of a key")
+                .arg(
+                    Arg::new("key")
+                        .value_name("KEY")
+                        .help("Dotted key, e.g. safety.dry_run")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            with_target(
+                Command::new("set")
+                    .add(Effect::Destructive)
+                    .about("Set a key in a configuration file"),
+            )
+            .arg(
+                Arg::new("key")
+                    .value_name("KEY")
+                    .help("Dotted key, e.g. safety.dry_run")
+                    .required(true),
+            )
+            .arg(
+                Arg::new("value")
+                    .value_name("VALUE")
+                    .help("TOML value; bare words are treated as strings")
+                    .required(true),
+            ),
+        )
+        .subcommand(
+            Command::new("profiles")
+                .add(Effect::ReadOnly)
+                .about("List available profiles"),
+        )
+        .subcommand(
+            Command::new("validate")
+                .add(Effect::ReadOnly)
+                .about("Check configuration files for syntax, unknown keys, types and policy rules")
+                .arg(
+                    Arg::new("files")
+                        .value_name("FILE")
+                        .num_args(0..)
+                        .help("Files to check (default: every configuration layer in use)"),
+                ),
+        )
+}
+
+/// Add the flags selecting which configuration file to write
+pub(crate) fn with_target(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("user")
+                .long("user")
+                .action(ArgAction::SetTrue)
+                .help("Use the user configuration file"),
+        )
+        .arg(
+            Arg::new("file")
+                .long("file")
+                .value_name("FILE")
+                .help("Use this file"),
+        )
+        .group(ArgGroup::new("target").args(["user", "file"]))
+}
+
+/// Execute the config command
+///
+/// `options` carries the global `--config` and `--profile` flags, so the layers shown
+/// match the ones the rest of the invocation uses.
+pub async fn execute(matches: &ArgMatches, options: LoadOptions<'_>) -> Result<()> {
+    match matches.subcommand() {
+        Some(("show", sub_matches)) => {
+            let resolved = Config::resolve(options).await?;
+            if sub_matches.get_flag("resolved") {
+                print!("{}", render_resolved(&resolved));
+            } else {
+                print!("{}", toml::to_string_pretty(&resolved.config)?);
+            }
+            Ok(())
+        }
+        Some(("init", sub_matches)) => {
+            let path = target_path(sub_matches, false)?;
+            if path.exists() && !sub_matches.get_flag("force") {
+                return Err(anyhow!(
+                    "{} already exists; use --force to overwrite it",
+                    path.display()
+                ));
+            }
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, render_template()?)?;
+            println!("Wrote {}", path.display());
+            Ok(())
+        }
+        Some(("get", sub_matches)) => {
+            let key = sub_matches.get_one::<String>("key").unwrap();
+            let resolved = Config::resolve(options).await?;
+            let value = lookup(&resolved.value, key).ok_or_else(|| {
+                if is_known_key(key) {
+                    anyhow!("{} is not set", key)
+                } else {
+                    anyhow!("Unknown configuration key '{}'", key)
+                }
+            })?;
+            match value {
+                toml::Value::String(text) => println!("{}", text),
+                toml::Value::Table(_) => print!("{}", toml::to_string_pretty(value)?),
+                other => println!("{}", other),
+            }
+            Ok(())
+        }
+        Some(("set", sub_matches)) => {
+            let key = sub_matches.get_one::<String>("key").unwrap();
+            let raw = sub_matches.get_one::<String>("value").unwrap();
+            let path = target_path(sub_matches, true)?;
+            set_in_file(&path, key, raw)?;
+            println!("Set {} in {}", key, path.display());
+            Ok(())
+        }
+        Some(("profiles", _)) => {
+            let resolved = Config::resolve(options).await?;
+            for name in Config::profile_names(&resolved.value) {
+                let builtin = BUILTIN_PROFILES.iter().any(|(n, _)| *n == name);
+                let defined = resolved
+                    .value
+                    .get("profiles")
+                    .and_then(|p| p.get(&name))
+                    .is_some();
+                let origin = match (builtin, defined) {
+                    (true, true) => "built-in, customized",
+                    (true, false) => "built-in",
+                    _ => "config",
+                };
+                let marker = if resolved.profile.as_deref() == Some(name.as_str()) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{} {:<12} ({})", marker, name, origin);
+            }
+            Ok(())
+        }
+        Some(("validate", sub_matches)) => {
+            let files: Vec<PathBuf> = match sub_matches.get_many::<String>("files") {
+                Some(files) => files.map(PathBuf::from).collect(),
+                None => Config::config_layers(options.file)
+                    .iter()
+                    .filter_map(ConfigSource::path)
+                    .filter(|path| path.exists())
+                    .map(Path::to_path_buf)
+                    .collect(),
+            };
+            validate_files(&files)
+        }
+        _ => {
+            println!("No config subcommand specified. Use 'config --help' for usage information.");
+            Ok(())
+        }
+    }
+}
+
+/// File selected by `--user`/`--file`, defaulting to the project configuration
+///
+/// `set` edits the nearest existing project file; `init` always writes to the current
+/// directory.
+pub(crate) fn target_path(matches: &ArgMatches, nearest_project: bool) -> Result<PathBuf> {
+    if let Some(file) = matches.get_one::<String>("file") {
+        return Ok(PathBuf::from(file));
+    }
+    if matches.get_flag("user") {
+        return Config::user_config_path()
+            .ok_or_else(|| anyhow!("Cannot determine the user configuration directory"));
+    }
+    if nearest_project {
+        if let Some(path) = Config::project_config_path() {
+            return Ok(path);
+        }
+    }
+    Ok(PathBuf::from(PROJECT_CONFIG_FILE))
+}
+
+fn lookup<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.')
+        .try_fold(value, |current, part| current.get(part))
+}
+
+/// Set a key in a configuration file, keeping its comments and layout
+fn set_in_file(path: &Path, key: &str, raw: &str) -> Result<()> {
+    if !is_known_key(key) {
+        return Err(anyhow!("Unknown configuration key '{}'", key));
+    }
+
+    let content = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+    let mut doc: DocumentMut = content
+        .parse()
+        .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+    let mut value: toml_edit::Value = config::parse_value(raw).to_string().parse()?;
+
+    let parts: Vec<&str> = key.split('.').collect();
+    let (last, parents) = parts.split_last().unwrap();
+    let mut item = doc.as_item_mut();
+    for part in parents {
+        let table = item
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("'{}' in {} is not a table", part, path.display()))?;
+        if table.get(part).is_none() {
+            table.insert(part, toml_edit::table());
+        }
+        item = table.get_mut(part).unwrap();
+    }
+    let table = item
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("Parent of '{}' in {} is not a table", key, path.display()))?;
+    // Keep an inline comment on the old value
+    if let Some(existing) = table.get(last).and_then(toml_edit::Item::as_value) {
+        *value.decor_mut() = existing.decor().clone();
+    }
+    table.insert(last, toml_edit::value(value));
+
+    let updated = doc.to_string();
+    let problems = validate_toml(&updated);
+    if !problems.is_empty() {
+        return Err(anyhow!(
+            "Refusing to write {}: {}",
+            path.display(),
+            problems.join("; ")
+        ));
+    }
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, updated)?;
+    Ok(())
+}
+
+fn validate_files(files: &[PathBuf]) -> Result<()> {
+    if files.is_empty() {
+        println!("No configuration files found; using defaults");
+        return Ok(());
+    }
+
+    let mut invalid = 0;
+    for path in files {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let problems = validate_toml(&content);
+        if problems.is_empty() {
+            println!("OK {}", path.display());
+        } else {
+            invalid += 1;
+            println!("{}:", path.display());
+            for problem in problems {
+                println!("  - {}", problem);
+            }
+        }
+    }
+
+    if invalid > 0 {
+        return Err(anyhow!("{} configuration files have problems", invalid));
+    }
+    Ok(())
+}
+
+/// Commented configuration listing every key with its default
+fn render_template() -> Result<String> {
+    let defaults = Config::default_value()?;
+    let mut out = String::from(
+        "# Safe AI Utility configuration\n\
+         #\n\
+         # Uncomment and edit the values to change. Commented values show the default.\n\
+         # Layers, lowest precedence first: defaults, system, user, project (.safe-ai-util.toml),\n\
+         # --config FILE, profile (--profile NAME), environment (SAFE_AI_UTIL__<SECTION>__<KEY>).\n\n",
+    );
+
+    let mut section = "";
+    for entry in CONFIG_KEYS {
+        // Top-level keys come first, before any table header
+        let (name, rest) = entry.key.split_once('.').unwrap_or(("", entry.key));
+        if name != section {
+            section = name;
+            out.push_str(&format!("\n[{}]\n", section));
+        }
+        let value = entry
+            .example
+            .map(str::to_string)
+            .or_else(|| lookup(&defaults, entry.key).map(ToString::to_string))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "# {}\n# {} = {}\n",
+            entry.description, rest, value
+        ));
+    }
+
+    out.push_str(
+        "\n# Profiles bundle settings selected with --profile NAME. Tables named after a built-in\n\
+         # profile (dev, ci, restricted) extend it; other names define new profiles.\n\
+         # [profiles.restricted.general]\n\
+         # timeout_seconds = 60\n\
+         \n\
+         # Tools can be pinned to an absolute path and a minimum --version; commands fail\n\
+         # before running if the binary found on PATH is another one, or is older.\n\
+         # [tools.git]\n\
+         # path = \"/usr/bin/git\"\n\
+         # min_version = \"2.40\"\n",
+    );
+    Ok(out)
+}
+
+/// Render the merged configuration as TOML with a source comment on every value
+fn render_resolved(resolved: &ResolvedConfig) -> String {
+    let mut out = String::from("# Layers (lowest precedence first): default");
+    for file in &resolved.files {
+        out.push_str(&format!(", {}", file));
+    }
+    if let Some(profile) = &resolved.profile {
+        out.push_str(&format!(", {}", ConfigSource::Profile(profile.clone())));
+    }
+    out.push('\n');
+
+    let Some(root) = resolved.value.as_table() else {
+        return out;
+    };
+
+    let mut top_level = Vec::new();
+    let mut sections = Vec::new();
+    for (key, value) in root {
+        match value.as_table() {
+            Some(table) => sections.push((key, table)),
+            None => top_level.push((key.clone(), value)),
+        }
+    }
+    render_lines(&mut out, "", &top_level, &resolved.sources);
+
+    for (section, table) in sections {
+        let mut lines = Vec::new();
+        flatten(table, "", &mut lines);
+        out.push_str(&format!("\n[{}]\n", section));
+        render_lines(&mut out, section, &lines, &resolved.sources);
+    }
+    out
+}
+
+fn flatten<'a>(table: &'a toml::Table, prefix: &str, lines: &mut Vec<(String, &'a toml::Value)>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value.as_table() {
+            Some(child) => flatten(child, &path, lines),
+            None => lines.push((path, value)),
+        }
+    }
+}
+
+fn render_lines(
+    out: &mut String,
+    section: &str,
+    lines: &[(String, &toml::Value)],
+    sources: &std::collections::BTreeMap<String, ConfigSource>,
+) {
+    let rendered: Vec<(String, String)> = lines
+        .iter()
+        .map(|(key, value)| {
+            let full_key = if section.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", section, key)
+            };
+            let source = sources
+                .get(&full_key)
+                .map(ToString::to_string)
+                .unwrap_or_else(|| ConfigSource::Default.to_string());
+            (format!("{} = {}", key, value), source)
+        })
+        .collect();
+
+    let width = rendered
+        .iter()
+        .map(|(line, _)| line.len())
+        .max()
+        .unwrap_or(0);
+    for (line, source) in rendered {
+        out.push_str(&format!("{:<width$}  # {}\n", line, source, width = width));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_render_resolved_annotates_sources() {
+        let config = Config::default();
+        let value = toml::Value::try_from(&config).unwrap();
+        let mut sources = BTreeMap::new();
+        sources.insert(
+            "safety.dry_run".to_string(),
+            ConfigSource::Env("COPILOT_AGENT_DRY_RUN".to_string()),
+        );
+        let resolved = ResolvedConfig {
+            config,
+            value,
+            sources,
+            files: vec![ConfigSource::Project(PathBuf::from(".safe-ai-util.toml"))],
+            profile: None,
+        };
+
+        let text = render_resolved(&resolved);
+        assert!(text.starts_with("# Layers (lowest precedence first): default, project"));
+        assert!(text.contains("[safety]\n"));
+        assert!(text.contains("# env (COPILOT_AGENT_DRY_RUN)"));
+        assert!(text.contains("resource_limits.max_memory_mb = 1024"));
+
+        // The annotated output is still valid TOML
+        let parsed: Config = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.general.timeout_seconds, 300);
+    }
+
+    #[test]
+    fn test_template_is_valid_when_uncommented() {
+        let template = render_template().unwrap();
+        assert!(validate_toml(&template).is_empty());
+
+        let uncommented: String = template
+            .lines()
+            .filter(|line| {
+                line.starts_with('[')
+                    || line.starts_with("# [")
+                    || (line.starts_with("# ") && line.contains(" = "))
+            })
+            .map(|line| format!("{}\n", line.trim_start_matches("# ")))
+            .collect();
+        assert_eq!(validate_toml(&uncommented), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_set_in_file_keeps_comments_and_validates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "# mine\n[safety]\ndry_run = false # keep\n").unwrap();
+
+        set_in_file(&path, "safety.dry_run", "true").unwrap();
+        set_in_file(&path, "execution.resource_limits.max_memory_mb", "512").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# mine\n[safety]\ndry_run = true # keep\n"));
+        assert!(content.contains("max_memory_mb = 512"));
+
+        assert!(set_in_file(&path, "safety.dry_rn", "true").is_err());
+        assert!(set_in_file(&path, "general.timeout_seconds", "soon").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+    }
+}
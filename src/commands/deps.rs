@@ -0,0 +1,447 @@
+// file: src/commands/deps.rs
+// version: 1.2.2
+// guid: 5791b04d-eb39-4759-b7ba-bd23eda3aac1
+
+//! `deps` command: dependency manifest hygiene
+//!
+//! `deps tidy` normalizes manifests without changing what they resolve to, so edits that
+//! add a dependency in an arbitrary spot do not produce noisy diffs:
+//!
+//! - `requirements*.txt`: requirements sorted by normalized name, extras sorted and
+//!   deduplicated, identical lines removed; options (`-r`, `-e`, `--index-url`) stay on top
+//! - `Cargo.toml`: feature lists in `[features]` and in dependency tables sorted and
+//!   deduplicated, keeping comments and layout
+//! - `package.json`: dependency sections sorted by package name
+//!
+//! `deps graph` queries the dependency graph of the workspace (see [`super::deps_graph`]) and
+//! `deps upgrade` plans and applies upgrades (see [`super::deps_upgrade`]).
+
+use super::{deps_graph, deps_upgrade};
+use super::file_move::walk_files;
+use crate::executor::Executor;
+use crate::json_order::{KeyOrder, Ordered};
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{Array, DocumentMut, Item, Table, Value};
+use tracing::{debug, info};
+
+/// Dependency sections of package.json that npm keeps sorted
+pub(super) const PACKAGE_JSON_SECTIONS: [&str; 5] = [
+    "dependencies",
+    "devDependencies",
+    "peerDependencies",
+    "optionalDependencies",
+    "bundleDependencies",
+];
+
+/// Dependency tables of Cargo.toml
+pub(super) const CARGO_DEPENDENCY_TABLES: [&str; 3] =
+    ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Supported manifest kinds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Manifest {
+    Requirements,
+    Cargo,
+    PackageJson,
+}
+
+impl Manifest {
+    fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name == "Cargo.toml" {
+            Some(Self::Cargo)
+        } else if name == "package.json" {
+            Some(Self::PackageJson)
+        } else if name.starts_with("requirements") && name.ends_with(".txt") {
+            Some(Self::Requirements)
+        } else {
+            None
+        }
+    }
+
+    fn tidy(self, content: &str) -> Result<String> {
+        match self {
+            Self::Requirements => Ok(tidy_requirements(content)),
+            Self::Cargo => tidy_cargo_toml(content),
+            Self::PackageJson => tidy_package_json(content),
+        }
+    }
+}
+
+/// Build the deps command
+pub fn build_command() -> Command {
+    Command::new("deps")
+        .about("Dependency manifest hygiene, dependency graph queries and upgrades")
+        .subcommand(
+            Command::new("tidy")
+                .about("Sort and deduplicate requirements.txt, Cargo.toml features and package.json dependencies")
+                .arg(
+                    Arg::new("paths")
+                        .value_name("PATH")
+                        .num_args(0..)
+                        .default_value(".")
+                        .help("Manifests or directories to search (respects .gitignore)"),
+                )
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .action(ArgAction::SetTrue)
+                        .help("Fail if any manifest is not tidy instead of rewriting it"),
+                ),
+        )
+        .subcommand(deps_graph::build_command())
+        .subcommand(deps_upgrade::build_command())
+}
+
+/// Execute the deps command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("tidy", sub_matches)) => execute_tidy(sub_matches, executor),
+        Some(("graph", sub_matches)) => deps_graph::execute(sub_matches, executor).await,
+        Some(("upgrade", sub_matches)) => deps_upgrade::execute(sub_matches, executor).await,
+        _ => {
+            println!("No deps subcommand specified. Use 'deps --help' for usage information.");
+            Ok(())
+        }
+    }
+}
+
+fn execute_tidy(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let check = matches.get_flag("check");
+    let write = !check && !executor.config().safety.dry_run;
+
+    let mut manifests = Vec::new();
+    for path in matches.get_many::<String>("paths").unwrap() {
+        manifests.extend(find_manifests(Path::new(path))?);
+    }
+    manifests.sort();
+    manifests.dedup();
+    info!("Checking {} manifests", manifests.len());
+
+    let mut untidy = Vec::new();
+    for (path, kind) in &manifests {
+        let content = fs::read_to_string(path)?;
+        let tidied = kind
+            .tidy(&content)
+            .map_err(|e| anyhow!("Failed to tidy {}: {}", path.display(), e))?;
+        if tidied == content {
+            debug!("{} is tidy", path.display());
+            continue;
+        }
+
+        if write {
+            executor.check_content(path, tidied.as_bytes())?;
+            fs::write(path, tidied)?;
+            println!("Tidied {}", path.display());
+        } else {
+            println!("Would tidy {}", path.display());
+        }
+        untidy.push(path);
+    }
+
+    if untidy.is_empty() {
+        println!("All {} manifests are tidy", manifests.len());
+    } else if check {
+        return Err(anyhow!(
+            "{} manifests need tidying; run 'deps tidy' to fix them",
+            untidy.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Collect supported manifests from a file or directory
+fn find_manifests(path: &Path) -> Result<Vec<(PathBuf, Manifest)>> {
+    if path.is_file() {
+        let kind = Manifest::detect(path)
+            .ok_or_else(|| anyhow!("Unsupported manifest: {}", path.display()))?;
+        return Ok(vec![(path.to_path_buf(), kind)]);
+    }
+
+    Ok(walk_files(path)?
+        .into_iter()
+        .filter(|file| !file.components().any(|c| c.as_os_str() == "node_modules"))
+        .filter_map(|file| Manifest::detect(&file).map(|kind| (file, kind)))
+        .collect())
+}
+
+// ---------------------------------------------------------------------------
+// requirements.txt
+// ---------------------------------------------------------------------------
+
+/// A requirement line (with continuation lines) and the comments directly above it
+struct Requirement {
+    comments: Vec<String>,
+    line: String,
+    key: String,
+}
+
+fn tidy_requirements(content: &str) -> String {
+    let extras = Regex::new(r"^([A-Za-z0-9._-]+)\s*\[([^\]]*)\]").unwrap();
+    let name = Regex::new(r"^[A-Za-z0-9._-]+").unwrap();
+
+    let mut header: Vec<String> = Vec::new();
+    let mut options: Vec<String> = Vec::new();
+    let mut requirements: Vec<Requirement> = Vec::new();
+    let mut pending: Vec<String> = Vec::new();
+
+    let mut lines = content.lines();
+    while let Some(first) = lines.next() {
+        // Join backslash continuations (e.g. --hash lines) into one logical line
+        let mut line = first.to_string();
+        while line.trim_end().ends_with('\\') {
+            match lines.next() {
+                Some(next) => {
+                    line.push('\n');
+                    line.push_str(next);
+                }
+                None => break,
+            }
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            // Comments before the first blank line form the header; later comment blocks
+            // stay with the requirement that follows them
+            if requirements.is_empty() && options.is_empty() {
+                header.append(&mut pending);
+                header.push(String::new());
+            }
+        } else if trimmed.starts_with('#') {
+            pending.push(line.trim_end().to_string());
+        } else if trimmed.starts_with('-') {
+            options.append(&mut pending);
+            options.push(line.trim_end().to_string());
+        } else {
+            let normalized = extras
+                .replace(trimmed, |caps: &regex::Captures| {
+                    let mut names: Vec<&str> = caps[2]
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                    names.sort_unstable();
+                    names.dedup();
+                    format!("{}[{}]", &caps[1], names.join(","))
+                })
+                .to_string();
+            let key = name
+                .find(&normalized)
+                .map(|m| m.as_str().to_lowercase().replace(['_', '.'], "-"))
+                .unwrap_or_default();
+            requirements.push(Requirement {
+                comments: std::mem::take(&mut pending),
+                line: normalized,
+                key,
+            });
+        }
+    }
+
+    requirements.sort_by(|a, b| a.key.cmp(&b.key).then_with(|| a.line.cmp(&b.line)));
+    let mut seen = std::collections::HashSet::new();
+    requirements.retain(|r| r.line.contains('\n') || seen.insert(r.line.clone()));
+
+    while header.last().is_some_and(|l| l.is_empty()) {
+        header.pop();
+    }
+
+    let mut blocks: Vec<Vec<String>> = Vec::new();
+    if !header.is_empty() {
+        blocks.push(header);
+    }
+    if !options.is_empty() {
+        blocks.push(options);
+    }
+    // Commented requirements are set apart by a blank line, as comment blocks usually are
+    let mut body: Vec<String> = Vec::new();
+    for requirement in requirements {
+        if !requirement.comments.is_empty() && !body.is_empty() {
+            body.push(String::new());
+        }
+        body.extend(requirement.comments);
+        body.push(requirement.line);
+    }
+    body.extend(pending);
+    if !body.is_empty() {
+        blocks.push(body);
+    }
+
+    if blocks.is_empty() {
+        return String::new();
+    }
+    let mut out = blocks
+        .iter()
+        .map(|block| block.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    out.push('\n');
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Cargo.toml
+// ---------------------------------------------------------------------------
+
+fn tidy_cargo_toml(content: &str) -> Result<String> {
+    let mut doc: DocumentMut = content.parse()?;
+
+    if let Some(features) = doc.get_mut("features").and_then(Item::as_table_like_mut) {
+        for (_, item) in features.iter_mut() {
+            if let Some(array) = item.as_array_mut() {
+                sort_array(array);
+            }
+        }
+    }
+
+    tidy_dependency_tables(doc.as_table_mut());
+    if let Some(workspace) = doc.get_mut("workspace").and_then(Item::as_table_mut) {
+        tidy_dependency_tables(workspace);
+    }
+    if let Some(targets) = doc.get_mut("target").and_then(Item::as_table_mut) {
+        for (_, target) in targets.iter_mut() {
+            if let Some(target) = target.as_table_mut() {
+                tidy_dependency_tables(target);
+            }
+        }
+    }
+
+    Ok(doc.to_string())
+}
+
+/// Sort the `features` list of every dependency in the dependency tables of `parent`
+fn tidy_dependency_tables(parent: &mut Table) {
+    for name in CARGO_DEPENDENCY_TABLES {
+        let Some(dependencies) = parent.get_mut(name).and_then(Item::as_table_like_mut) else {
+            continue;
+        };
+        for (_, dependency) in dependencies.iter_mut() {
+            let features = dependency
+                .as_table_like_mut()
+                .and_then(|table| table.get_mut("features"))
+                .and_then(Item::as_array_mut);
+            if let Some(features) = features {
+                sort_array(features);
+            }
+        }
+    }
+}
+
+/// Sort and deduplicate an array of strings, keeping each position's whitespace and comments
+fn sort_array(array: &mut Array) {
+    if array.iter().any(|v| v.as_str().is_none()) {
+        return;
+    }
+
+    let decors: Vec<_> = array.iter().map(|v| v.decor().clone()).collect();
+    let mut values: Vec<String> = array
+        .iter()
+        .filter_map(|v| v.as_str().map(str::to_string))
+        .collect();
+    values.sort();
+    values.dedup();
+
+    let unchanged = values.len() == array.len()
+        && values
+            .iter()
+            .zip(array.iter())
+            .all(|(a, b)| b.as_str() == Some(a));
+    if unchanged {
+        return;
+    }
+
+    array.clear();
+    for (value, decor) in values.into_iter().zip(decors) {
+        let mut value = Value::from(value);
+        *value.decor_mut() = decor;
+        array.push_formatted(value);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// package.json
+// ---------------------------------------------------------------------------
+
+fn tidy_package_json(content: &str) -> Result<String> {
+    let package: serde_json::Value = serde_json::from_str(content)?;
+    if !package.is_object() {
+        return Err(anyhow!("package.json is not an object"));
+    }
+
+    // The other keys are written back in the file's order
+    let mut order = KeyOrder::of(content)?;
+    let mut changed = false;
+    for section in PACKAGE_JSON_SECTIONS {
+        let Some(dependencies) = order.get(section) else {
+            continue;
+        };
+        let names: Vec<&str> = dependencies.keys().collect();
+        if names.windows(2).any(|pair| pair[0] > pair[1]) {
+            changed = true;
+            order.sort(section);
+        }
+    }
+
+    // Re-serializing would reformat the whole file, so only do it when something moved
+    if !changed {
+        return Ok(content.to_string());
+    }
+
+    let indent = content
+        .lines()
+        .nth(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    serde::Serialize::serialize(&Ordered::new(&package, &order), &mut serializer)?;
+
+    let mut out = String::from_utf8(out)?;
+    if content.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tidy_requirements() {
+        let content = "# Runtime deps\n\n-r base.txt\nrequests[socks,security,socks]>=2.0\n# pinned for CI\nDjango==4.2\nrequests[socks,security,socks]>=2.0\nattrs\n";
+        assert_eq!(
+            tidy_requirements(content),
+            "# Runtime deps\n\n-r base.txt\n\nattrs\n\n# pinned for CI\nDjango==4.2\nrequests[security,socks]>=2.0\n"
+        );
+
+        let tidy = tidy_requirements(content);
+        assert_eq!(tidy_requirements(&tidy), tidy);
+    }
+
+    #[test]
+    fn test_tidy_cargo_toml_features() {
+        let content = "[package]\nname = \"x\"\n\n[dependencies]\ntokio = { version = \"1\", features = [\"rt\", \"macros\", \"rt\"] } # async\n\n[features]\ndefault = [\n    \"std\",\n    \"alloc\",\n]\n";
+        assert_eq!(
+            tidy_cargo_toml(content).unwrap(),
+            "[package]\nname = \"x\"\n\n[dependencies]\ntokio = { version = \"1\", features = [\"macros\", \"rt\"] } # async\n\n[features]\ndefault = [\n    \"alloc\",\n    \"std\",\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_tidy_package_json() {
+        let content = "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"zod\": \"^3\",\n        \"axios\": \"^1\"\n    }\n}\n";
+        assert_eq!(
+            tidy_package_json(content).unwrap(),
+            "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"axios\": \"^1\",\n        \"zod\": \"^3\"\n    }\n}\n"
+        );
+
+        let sorted = "{\"name\": \"app\", \"dependencies\": {\"a\": \"1\"}}";
+        assert_eq!(tidy_package_json(sorted).unwrap(), sorted);
+    }
+}
@@ -0,0 +1,611 @@
+// file: src/commands/deps_graph.rs
+// version: 1.0.1
+// guid: 8e3a5c1f-7b2d-4f6e-a9c4-2d1b0e8f6a35
+
+//! Dependency graph for `deps graph`
+//!
+//! Every `Cargo.toml`, `package.json`, `pyproject.toml` and `requirements*.txt` in the
+//! workspace contributes its package and an edge to each dependency it declares. Nodes are
+//! named `<ecosystem>:<package>` (`cargo:serde`, `npm:react`, `pip:requests`); a requirements
+//! file belongs to the `pyproject.toml` project next to it, or to its directory otherwise.
+//!
+//! Queries answer who depends on a package, directly or through other workspace packages
+//! (`--rdeps`), and how one package reaches another (`--path`), as text, JSON or DOT.
+
+use super::deps::{CARGO_DEPENDENCY_TABLES, PACKAGE_JSON_SECTIONS};
+use super::export::Effect;
+use super::file_move::walk_files;
+use crate::executor::Executor;
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgMatches, Command};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// A package: declared by a workspace manifest, or only depended upon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Node {
+    pub id: String,
+    pub ecosystem: &'static str,
+    pub name: String,
+    /// Manifest declaring the package, for workspace packages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<PathBuf>,
+}
+
+/// A declared dependency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    /// Version requirement as written, empty if none
+    pub requirement: String,
+    /// Dependency section, e.g. `dev-dependencies` or `peerDependencies`
+    pub kind: String,
+}
+
+/// Packages and dependencies of a workspace
+#[derive(Debug, Default, Serialize)]
+pub struct DepGraph {
+    pub nodes: BTreeMap<String, Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// A package depending on the queried one, `depth` edges away
+#[derive(Debug, Serialize)]
+struct Dependent {
+    id: String,
+    depth: usize,
+    /// The package it depends on, one step closer to the queried package
+    via: String,
+}
+
+/// Build the `deps graph` subcommand
+pub fn build_command() -> Command {
+    Command::new("graph")
+        .add(Effect::ReadOnly)
+        .about("Query the dependency graph of Cargo, npm and pip manifests in the workspace")
+        .arg(
+            Arg::new("paths")
+                .value_name("PATH")
+                .num_args(0..)
+                .default_value(".")
+                .help("Directories to search for manifests (respects .gitignore)"),
+        )
+        .arg(
+            Arg::new("rdeps")
+                .long("rdeps")
+                .value_name("PACKAGE")
+                .conflicts_with("path")
+                .help("Show the packages depending on PACKAGE, directly or transitively"),
+        )
+        .arg(
+            Arg::new("path")
+                .long("path")
+                .value_names(["FROM", "TO"])
+                .num_args(2)
+                .help("Show the shortest dependency path from FROM to TO"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json", "dot"])
+                .default_value("text")
+                .help("Output format"),
+        )
+}
+
+/// Execute `deps graph`
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let mut graph = DepGraph::default();
+    for path in matches.get_many::<String>("paths").unwrap() {
+        let path = executor.check_path(path)?;
+        graph.scan(&path)?;
+    }
+    info!(
+        "Dependency graph: {} packages, {} dependencies",
+        graph.nodes.len(),
+        graph.edges.len()
+    );
+    let format = matches.get_one::<String>("format").unwrap().as_str();
+
+    if let Some(package) = matches.get_one::<String>("rdeps") {
+        let targets = graph.resolve(package)?;
+        let dependents = graph.reverse_dependencies(&targets);
+        match format {
+            "json" => println!(
+                "{}",
+                serde_json::to_string_pretty(
+                    &json!({"package": targets, "dependents": dependents})
+                )?
+            ),
+            "dot" => {
+                let mut ids: BTreeSet<&str> = targets.iter().map(String::as_str).collect();
+                ids.extend(dependents.iter().map(|d| d.id.as_str()));
+                print!(
+                    "{}",
+                    graph.to_dot(|e| ids.contains(e.from.as_str()) && ids.contains(e.to.as_str()))
+                );
+            }
+            _ => {
+                if dependents.is_empty() {
+                    println!("Nothing in the workspace depends on {}", targets.join(", "));
+                }
+                for dependent in &dependents {
+                    println!(
+                        "{}{} -> {}",
+                        "  ".repeat(dependent.depth - 1),
+                        dependent.id,
+                        dependent.via
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(mut ends) = matches.get_many::<String>("path") {
+        let from = graph.resolve_one(ends.next().unwrap())?;
+        let to = graph.resolve_one(ends.next().unwrap())?;
+        let path = graph
+            .shortest_path(&from, &to)
+            .ok_or_else(|| anyhow!("{} does not depend on {}", from, to))?;
+        match format {
+            "json" => println!("{}", serde_json::to_string_pretty(&json!({"path": path}))?),
+            "dot" => print!("{}", graph.to_dot(|e| path.contains(&e))),
+            _ => {
+                println!("{}", from);
+                for edge in &path {
+                    println!("  -> {} {} ({})", edge.to, edge.requirement, edge.kind);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&graph)?),
+        "dot" => print!("{}", graph.to_dot(|_| true)),
+        _ => {
+            for node in graph.nodes.values().filter(|n| n.manifest.is_some()) {
+                println!(
+                    "{} ({})",
+                    node.id,
+                    node.manifest.as_ref().unwrap().display()
+                );
+                for edge in graph.edges.iter().filter(|e| e.from == node.id) {
+                    println!("  -> {} {} ({})", edge.to, edge.requirement, edge.kind);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl DepGraph {
+    /// Add the packages and dependencies of every manifest under `root`
+    pub fn scan(&mut self, root: &Path) -> Result<()> {
+        let files = walk_files(root)?;
+        for file in files
+            .iter()
+            .filter(|file| !file.components().any(|c| c.as_os_str() == "node_modules"))
+        {
+            let Some(name) = file.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let parsed = match name {
+                "Cargo.toml" => self.add_cargo(file),
+                "package.json" => self.add_package_json(file),
+                "pyproject.toml" => self.add_pyproject(file),
+                _ if name.starts_with("requirements") && name.ends_with(".txt") => {
+                    self.add_requirements(file)
+                }
+                _ => continue,
+            };
+            parsed.map_err(|e| anyhow!("Failed to read {}: {}", file.display(), e))?;
+            debug!("Added {} to the dependency graph", file.display());
+        }
+        Ok(())
+    }
+
+    fn add_cargo(&mut self, path: &Path) -> Result<()> {
+        let doc: toml::Value = toml::from_str(&fs::read_to_string(path)?)?;
+        // A virtual workspace manifest declares no package of its own
+        let Some(name) = doc
+            .get("package")
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+        else {
+            return Ok(());
+        };
+        let from = self.add_package("cargo", name, path);
+
+        let mut tables: Vec<(&str, &toml::Value)> = CARGO_DEPENDENCY_TABLES
+            .iter()
+            .filter_map(|kind| doc.get(*kind).map(|t| (*kind, t)))
+            .collect();
+        if let Some(targets) = doc.get("target").and_then(|t| t.as_table()) {
+            for target in targets.values() {
+                tables.extend(
+                    CARGO_DEPENDENCY_TABLES
+                        .iter()
+                        .filter_map(|kind| target.get(*kind).map(|t| (*kind, t))),
+                );
+            }
+        }
+
+        for (kind, table) in tables {
+            for (key, spec) in table.as_table().into_iter().flatten() {
+                // `foo = { package = "bar" }` depends on bar under the name foo
+                let package = spec.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+                let requirement = match spec {
+                    toml::Value::String(version) => version.clone(),
+                    _ => spec
+                        .get("version")
+                        .and_then(|v| v.as_str())
+                        .or_else(|| spec.get("path").and_then(|p| p.as_str()))
+                        .unwrap_or_default()
+                        .to_string(),
+                };
+                self.add_edge(&from, "cargo", package, requirement, kind);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_package_json(&mut self, path: &Path) -> Result<()> {
+        let doc: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let name = doc["name"]
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| directory_name(path));
+        let from = self.add_package("npm", &name, path);
+        for kind in PACKAGE_JSON_SECTIONS {
+            for (package, requirement) in doc[kind].as_object().into_iter().flatten() {
+                let requirement = requirement.as_str().unwrap_or_default().to_string();
+                self.add_edge(&from, "npm", package, requirement, kind);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_pyproject(&mut self, path: &Path) -> Result<()> {
+        let doc: toml::Value = toml::from_str(&fs::read_to_string(path)?)?;
+        let Some(project) = doc.get("project") else {
+            return Ok(());
+        };
+        let name = project
+            .get("name")
+            .and_then(|n| n.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| directory_name(path));
+        let from = self.add_package("pip", &name, path);
+
+        let mut requirements: Vec<(String, &str)> = Vec::new();
+        for line in project
+            .get("dependencies")
+            .and_then(|d| d.as_array())
+            .into_iter()
+            .flatten()
+        {
+            requirements.extend(line.as_str().map(|l| ("dependencies".to_string(), l)));
+        }
+        for (extra, lines) in project
+            .get("optional-dependencies")
+            .and_then(|d| d.as_table())
+            .into_iter()
+            .flatten()
+        {
+            for line in lines.as_array().into_iter().flatten() {
+                requirements.extend(
+                    line.as_str()
+                        .map(|l| (format!("optional-dependencies.{}", extra), l)),
+                );
+            }
+        }
+        for (kind, line) in requirements {
+            if let Some((package, requirement)) = split_requirement(line) {
+                self.add_edge(&from, "pip", &package, requirement, &kind);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_requirements(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path)?;
+        // Requirements belong to the project declared next to them, if any
+        let pyproject = path.with_file_name("pyproject.toml");
+        let owner = self
+            .nodes
+            .values()
+            .find(|n| n.manifest.as_deref() == Some(pyproject.as_path()))
+            .map(|n| n.name.clone())
+            .unwrap_or_else(|| directory_name(path));
+        let from = self.add_package("pip", &owner, path);
+        let kind = path.file_name().unwrap().to_string_lossy().to_string();
+
+        for line in content.lines() {
+            let line = line.split(" #").next().unwrap().trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('-') {
+                continue;
+            }
+            if let Some((package, requirement)) = split_requirement(line) {
+                self.add_edge(&from, "pip", &package, requirement, &kind);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a workspace package, returning its id
+    fn add_package(&mut self, ecosystem: &'static str, name: &str, manifest: &Path) -> String {
+        let node = self.node(ecosystem, name);
+        // pyproject.toml is the better reference than the requirements files next to it
+        if node.manifest.is_none() || manifest.ends_with("pyproject.toml") {
+            node.manifest = Some(manifest.to_path_buf());
+        }
+        node.id.clone()
+    }
+
+    fn add_edge(
+        &mut self,
+        from: &str,
+        ecosystem: &'static str,
+        package: &str,
+        requirement: String,
+        kind: &str,
+    ) {
+        let to = self.node(ecosystem, package).id.clone();
+        let edge = Edge {
+            from: from.to_string(),
+            to,
+            requirement,
+            kind: kind.to_string(),
+        };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    fn node(&mut self, ecosystem: &'static str, name: &str) -> &mut Node {
+        let name = if ecosystem == "pip" {
+            normalize_pip_name(name)
+        } else {
+            name.to_string()
+        };
+        let id = node_id(ecosystem, &name);
+        self.nodes.entry(id.clone()).or_insert(Node {
+            id,
+            ecosystem,
+            name,
+            manifest: None,
+        })
+    }
+
+    /// Ids matching `query`: an exact id, or a package name in any ecosystem
+    pub fn resolve(&self, query: &str) -> Result<Vec<String>> {
+        if self.nodes.contains_key(query) {
+            return Ok(vec![query.to_string()]);
+        }
+        let matching: Vec<String> = self
+            .nodes
+            .values()
+            .filter(|n| {
+                n.name == query || (n.ecosystem == "pip" && n.name == normalize_pip_name(query))
+            })
+            .map(|n| n.id.clone())
+            .collect();
+        if matching.is_empty() {
+            bail!("No package named {} in the dependency graph", query);
+        }
+        Ok(matching)
+    }
+
+    /// The single id matching `query`
+    fn resolve_one(&self, query: &str) -> Result<String> {
+        let mut ids = self.resolve(query)?;
+        if ids.len() > 1 {
+            bail!("{} is ambiguous; use one of {}", query, ids.join(", "));
+        }
+        Ok(ids.remove(0))
+    }
+
+    /// Packages depending on any of `targets`, nearest first
+    fn reverse_dependencies(&self, targets: &[String]) -> Vec<Dependent> {
+        let mut seen: BTreeSet<&str> = targets.iter().map(String::as_str).collect();
+        let mut queue: VecDeque<(&str, usize)> = targets.iter().map(|t| (t.as_str(), 0)).collect();
+        let mut dependents = Vec::new();
+        while let Some((id, depth)) = queue.pop_front() {
+            for edge in self.edges.iter().filter(|e| e.to == id) {
+                if seen.insert(&edge.from) {
+                    dependents.push(Dependent {
+                        id: edge.from.clone(),
+                        depth: depth + 1,
+                        via: id.to_string(),
+                    });
+                    queue.push_back((&edge.from, depth + 1));
+                }
+            }
+        }
+        dependents
+    }
+
+    /// Fewest dependency edges leading from `from` to `to`
+    fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<&Edge>> {
+        let mut reached: HashMap<&str, Option<&Edge>> = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(id) = queue.pop_front() {
+            if id == to {
+                let mut path = Vec::new();
+                let mut current = to;
+                while let Some(Some(edge)) = reached.get(current) {
+                    path.push(*edge);
+                    current = &edge.from;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for edge in self.edges.iter().filter(|e| e.from == id) {
+                if !reached.contains_key(edge.to.as_str()) {
+                    reached.insert(&edge.to, Some(edge));
+                    queue.push_back(&edge.to);
+                }
+            }
+        }
+        None
+    }
+
+    /// Graphviz rendering of the edges accepted by `include`; workspace packages are boxes
+    fn to_dot(&self, include: impl Fn(&Edge) -> bool) -> String {
+        let edges: Vec<&Edge> = self.edges.iter().filter(|e| include(e)).collect();
+        let ids: BTreeSet<&str> = edges
+            .iter()
+            .flat_map(|e| [e.from.as_str(), e.to.as_str()])
+            .collect();
+        let mut dot = String::from("digraph dependencies {\n    rankdir=LR;\n");
+        for id in ids {
+            let shape = if self.nodes[id].manifest.is_some() {
+                "box"
+            } else {
+                "ellipse"
+            };
+            dot.push_str(&format!("    {:?} [shape={}];\n", id, shape));
+        }
+        for edge in edges {
+            dot.push_str(&format!(
+                "    {:?} -> {:?} [label={:?}];\n",
+                edge.from, edge.to, edge.requirement
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn node_id(ecosystem: &str, name: &str) -> String {
+    format!("{}:{}", ecosystem, name)
+}
+
+/// PEP 503 normalized name: lowercase, runs of `-`, `_` and `.` replaced by `-`
+fn normalize_pip_name(name: &str) -> String {
+    Regex::new(r"[-_.]+")
+        .unwrap()
+        .replace_all(name, "-")
+        .to_lowercase()
+}
+
+/// Split a PEP 508 requirement into the package name and the rest (extras, version, markers)
+fn split_requirement(line: &str) -> Option<(String, String)> {
+    let name = Regex::new(r"^\s*([A-Za-z0-9][A-Za-z0-9._-]*)").unwrap();
+    let found = name.captures(line)?.get(1)?;
+    Some((
+        found.as_str().to_string(),
+        line[found.end()..].trim().to_string(),
+    ))
+}
+
+/// Name of the directory containing `path`, for manifests without a package name
+fn directory_name(path: &Path) -> String {
+    path.canonicalize()
+        .ok()
+        .and_then(|p| {
+            p.parent()
+                .and_then(|d| d.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "workspace".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> (tempfile::TempDir, DepGraph) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("core")).unwrap();
+        fs::create_dir_all(root.join("cli")).unwrap();
+        fs::create_dir_all(root.join("web")).unwrap();
+        fs::create_dir_all(root.join("py")).unwrap();
+        fs::write(
+            root.join("core/Cargo.toml"),
+            "[package]\nname = \"core\"\n\n[dependencies]\nserde = { version = \"1\", features = [\"derive\"] }\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("cli/Cargo.toml"),
+            "[package]\nname = \"cli\"\n\n[dependencies]\ncore = { path = \"../core\" }\n\n[dev-dependencies]\nserde_json = \"1\"\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("web/package.json"),
+            "{\"name\": \"web\", \"dependencies\": {\"react\": \"^18\"}, \"devDependencies\": {\"serde\": \"0.1\"}}",
+        )
+        .unwrap();
+        fs::write(
+            root.join("py/pyproject.toml"),
+            "[project]\nname = \"tool\"\ndependencies = [\"Requests[socks]>=2\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("py/requirements-dev.txt"),
+            "-r base.txt\npytest==8.0  # tests\n",
+        )
+        .unwrap();
+
+        let mut graph = DepGraph::default();
+        graph.scan(root).unwrap();
+        (dir, graph)
+    }
+
+    #[test]
+    fn test_scan_manifests() {
+        let (_dir, graph) = workspace();
+        assert!(graph.nodes["cargo:core"].manifest.is_some());
+        assert!(graph.nodes["cargo:serde"].manifest.is_none());
+        assert!(graph.nodes["pip:tool"]
+            .manifest
+            .as_ref()
+            .unwrap()
+            .ends_with("pyproject.toml"));
+        assert!(graph.edges.iter().any(|e| e.from == "pip:tool"
+            && e.to == "pip:requests"
+            && e.requirement == "[socks]>=2"));
+        assert!(graph.edges.iter().any(|e| e.from == "pip:tool"
+            && e.to == "pip:pytest"
+            && e.kind == "requirements-dev.txt"));
+        assert!(graph.edges.iter().any(|e| e.from == "cargo:cli"
+            && e.to == "cargo:serde_json"
+            && e.kind == "dev-dependencies"));
+    }
+
+    #[test]
+    fn test_reverse_dependencies_and_paths() {
+        let (_dir, graph) = workspace();
+        assert_eq!(
+            graph.resolve("serde").unwrap(),
+            vec!["cargo:serde", "npm:serde"]
+        );
+        assert!(graph.resolve_one("serde").is_err());
+
+        let dependents = graph.reverse_dependencies(&["cargo:serde".to_string()]);
+        let ids: Vec<_> = dependents
+            .iter()
+            .map(|d| (d.id.as_str(), d.depth))
+            .collect();
+        assert_eq!(ids, vec![("cargo:core", 1), ("cargo:cli", 2)]);
+
+        let path = graph.shortest_path("cargo:cli", "cargo:serde").unwrap();
+        let hops: Vec<_> = path.iter().map(|e| e.to.as_str()).collect();
+        assert_eq!(hops, vec!["cargo:core", "cargo:serde"]);
+        assert!(graph.shortest_path("cargo:serde", "cargo:cli").is_none());
+
+        let dot = graph.to_dot(|e| path.contains(&e));
+        assert!(dot.contains("\"cargo:cli\" [shape=box];"));
+        assert!(dot.contains("\"cargo:core\" -> \"cargo:serde\" [label=\"1\"];"));
+    }
+}
@@ -0,0 +1,682 @@
+// file: src/commands/deps_upgrade.rs
+// version: 1.0.1
+// guid: 0b7e4c9a-2d5f-4e1b-8a6c-5f3d9e2b7a14
+
+//! Upgrade advisor for `deps upgrade`
+//!
+//! `--plan` asks each ecosystem's own tooling which dependencies of the project in the
+//! working directory have newer releases — `cargo search` for the requirements in
+//! `Cargo.toml`, `npm outdated`, `pip list --outdated` — and groups them by risk:
+//!
+//! - patch: only the patch version changes
+//! - minor: the minor version changes (for `0.x` versions, a patch change)
+//! - major: the major version changes (for `0.x` versions, a minor change), or the versions
+//!   could not be compared
+//!
+//! `--apply` rolls the groups out one at a time, lowest risk first. After each group the test
+//! commands run; if one fails, the manifests and lock files are restored to their state before
+//! that group and the rollout stops, leaving the groups that passed in place.
+
+use super::deps::CARGO_DEPENDENCY_TABLES;
+use super::export::Effect;
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Value};
+use tracing::{info, warn};
+
+/// Ecosystems `deps upgrade` knows how to query and upgrade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ecosystem {
+    Cargo,
+    Npm,
+    Pip,
+}
+
+impl Ecosystem {
+    const ALL: [Self; 3] = [Self::Cargo, Self::Npm, Self::Pip];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Cargo => "cargo",
+            Self::Npm => "npm",
+            Self::Pip => "pip",
+        }
+    }
+
+    /// Whether the project in `dir` uses this ecosystem
+    fn detect(self, dir: &Path) -> bool {
+        match self {
+            Self::Cargo => dir.join("Cargo.toml").is_file(),
+            Self::Npm => dir.join("package.json").is_file(),
+            Self::Pip => dir.join("pyproject.toml").is_file() || !requirement_files(dir).is_empty(),
+        }
+    }
+
+    /// Release history page of a package
+    fn changelog(self, name: &str) -> String {
+        match self {
+            Self::Cargo => format!("https://crates.io/crates/{}/versions", name),
+            Self::Npm => format!("https://www.npmjs.com/package/{}?activeTab=versions", name),
+            Self::Pip => format!("https://pypi.org/project/{}/#history", name),
+        }
+    }
+
+    /// Test command gating upgrades of this ecosystem, unless `--test` is given
+    fn default_test(self) -> &'static str {
+        match self {
+            Self::Cargo => "cargo test",
+            Self::Npm => "npm test",
+            Self::Pip => "python -m pytest",
+        }
+    }
+
+    /// Files restored when a group of upgrades fails its tests
+    fn state_files(self, dir: &Path) -> Vec<PathBuf> {
+        match self {
+            Self::Cargo => vec![dir.join("Cargo.toml"), dir.join("Cargo.lock")],
+            Self::Npm => vec![dir.join("package.json"), dir.join("package-lock.json")],
+            Self::Pip => {
+                let mut files = requirement_files(dir);
+                files.push(dir.join("pyproject.toml"));
+                files
+            }
+        }
+    }
+}
+
+/// How likely an upgrade is to break the project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Risk {
+    const ALL: [Self; 3] = [Self::Patch, Self::Minor, Self::Major];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Patch => "patch",
+            Self::Minor => "minor",
+            Self::Major => "major",
+        }
+    }
+
+    /// Compare two versions, ignoring requirement operators such as `^` or `>=`
+    pub fn classify(current: &str, latest: &str) -> Self {
+        let (Some(current), Some(latest)) = (parse_version(current), parse_version(latest)) else {
+            return Self::Major;
+        };
+        let (major, minor) = if current[0] == 0 && latest[0] == 0 {
+            (current[1] != latest[1], current[2] != latest[2])
+        } else {
+            (current[0] != latest[0], current[1] != latest[1])
+        };
+        if major {
+            Self::Major
+        } else if minor {
+            Self::Minor
+        } else {
+            Self::Patch
+        }
+    }
+}
+
+/// An available upgrade of a direct dependency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Upgrade {
+    pub ecosystem: Ecosystem,
+    pub name: String,
+    pub current: String,
+    pub latest: String,
+    pub risk: Risk,
+    pub changelog: String,
+}
+
+impl Upgrade {
+    fn new(ecosystem: Ecosystem, name: &str, current: &str, latest: &str) -> Self {
+        Self {
+            ecosystem,
+            name: name.to_string(),
+            current: current.to_string(),
+            latest: latest.to_string(),
+            risk: Risk::classify(current, latest),
+            changelog: ecosystem.changelog(name),
+        }
+    }
+}
+
+/// Build the `deps upgrade` subcommand
+pub fn build_command() -> Command {
+    Command::new("upgrade")
+        .add(Effect::Destructive)
+        .about("Plan dependency upgrades grouped by risk and apply them group by group")
+        .arg(
+            Arg::new("plan")
+                .long("plan")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("apply")
+                .help("List available upgrades grouped by risk (the default)"),
+        )
+        .arg(
+            Arg::new("apply")
+                .long("apply")
+                .action(ArgAction::SetTrue)
+                .help("Apply the upgrades one risk group at a time, running the tests after each"),
+        )
+        .arg(
+            Arg::new("ecosystem")
+                .long("ecosystem")
+                .short('e')
+                .value_parser(["cargo", "npm", "pip"])
+                .action(ArgAction::Append)
+                .help("Only consider this ecosystem (repeatable; default: all detected)"),
+        )
+        .arg(
+            Arg::new("max-risk")
+                .long("max-risk")
+                .value_parser(["patch", "minor", "major"])
+                .default_value("major")
+                .help("Ignore upgrades riskier than this"),
+        )
+        .arg(
+            Arg::new("test")
+                .long("test")
+                .value_name("COMMAND")
+                .action(ArgAction::Append)
+                .help("Command gating each group, e.g. 'cargo test' (repeatable; default: each ecosystem's test command)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format of the plan"),
+        )
+}
+
+/// Execute `deps upgrade`
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let dir = executor
+        .config()
+        .general
+        .working_directory
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."));
+    let selected: Vec<Ecosystem> = match matches.get_many::<String>("ecosystem") {
+        Some(names) => {
+            let names: Vec<&String> = names.collect();
+            Ecosystem::ALL
+                .into_iter()
+                .filter(|e| names.iter().any(|n| *n == e.as_str()))
+                .collect()
+        }
+        None => Ecosystem::ALL
+            .into_iter()
+            .filter(|e| e.detect(&dir))
+            .collect(),
+    };
+    if selected.is_empty() {
+        bail!(
+            "No Cargo.toml, package.json, pyproject.toml or requirements*.txt in {}",
+            dir.display()
+        );
+    }
+    let max_risk = match matches.get_one::<String>("max-risk").map(String::as_str) {
+        Some("patch") => Risk::Patch,
+        Some("minor") => Risk::Minor,
+        _ => Risk::Major,
+    };
+
+    let mut upgrades = Vec::new();
+    for ecosystem in &selected {
+        info!("Checking {} dependencies for upgrades", ecosystem.as_str());
+        upgrades.extend(find_upgrades(executor, *ecosystem, &dir).await?);
+    }
+    upgrades.retain(|u| u.risk <= max_risk);
+    upgrades.sort_by(|a, b| (a.risk, a.ecosystem, &a.name).cmp(&(b.risk, b.ecosystem, &b.name)));
+
+    if !matches.get_flag("apply") {
+        print_plan(
+            &upgrades,
+            matches.get_one::<String>("format").map(String::as_str) == Some("json"),
+        )?;
+        return Ok(());
+    }
+
+    let tests = matches
+        .get_many::<String>("test")
+        .map(|commands| {
+            commands
+                .map(|line| CommandSpec::parse(line).map_err(|e| anyhow!(e)))
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+    apply(executor, &dir, &upgrades, tests.as_deref()).await
+}
+
+/// Available upgrades of the direct dependencies of one ecosystem
+async fn find_upgrades(
+    executor: &Executor,
+    ecosystem: Ecosystem,
+    dir: &Path,
+) -> Result<Vec<Upgrade>> {
+    match ecosystem {
+        Ecosystem::Cargo => {
+            let manifest = fs::read_to_string(dir.join("Cargo.toml"))?;
+            let mut upgrades = Vec::new();
+            for (name, requirement) in cargo_requirements(&manifest)? {
+                let output = executor
+                    .execute_secure_capture("cargo", &["search", name.as_str(), "--limit", "1"])
+                    .await?;
+                match parse_cargo_search(&output.stdout, &name) {
+                    Some(latest) if parse_version(&latest) > parse_version(&requirement) => {
+                        upgrades.push(Upgrade::new(ecosystem, &name, &requirement, &latest));
+                    }
+                    Some(_) => {}
+                    None => warn!("crates.io does not know {}", name),
+                }
+            }
+            Ok(upgrades)
+        }
+        Ecosystem::Npm => {
+            // npm outdated exits with status 1 when anything is outdated
+            let output = executor
+                .execute_secure_capture("npm", &["outdated", "--json"])
+                .await?;
+            parse_npm_outdated(&output.stdout)
+        }
+        Ecosystem::Pip => {
+            let output = executor
+                .execute_secure_capture("pip", &["list", "--outdated", "--format=json"])
+                .await?;
+            if !output.success() {
+                bail!("pip list failed: {}", output.stderr.trim());
+            }
+            parse_pip_outdated(&output.stdout)
+        }
+    }
+}
+
+fn print_plan(upgrades: &[Upgrade], json: bool) -> Result<()> {
+    if json {
+        let groups: serde_json::Map<String, JsonValue> = Risk::ALL
+            .iter()
+            .map(|risk| {
+                let group: Vec<&Upgrade> = upgrades.iter().filter(|u| u.risk == *risk).collect();
+                (risk.as_str().to_string(), json!(group))
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({ "groups": groups }))?
+        );
+        return Ok(());
+    }
+
+    if upgrades.is_empty() {
+        println!("All dependencies are up to date");
+        return Ok(());
+    }
+    for risk in Risk::ALL {
+        let group: Vec<&Upgrade> = upgrades.iter().filter(|u| u.risk == risk).collect();
+        if group.is_empty() {
+            continue;
+        }
+        println!("{} ({}):", risk.as_str(), group.len());
+        for upgrade in group {
+            println!(
+                "  {:<5} {} {} -> {}  {}",
+                upgrade.ecosystem.as_str(),
+                upgrade.name,
+                upgrade.current,
+                upgrade.latest,
+                upgrade.changelog
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Apply the upgrades group by group, restoring a group's files if its tests fail
+async fn apply(
+    executor: &Executor,
+    dir: &Path,
+    upgrades: &[Upgrade],
+    tests: Option<&[CommandSpec]>,
+) -> Result<()> {
+    let dry_run = executor.config().safety.dry_run;
+    if upgrades.is_empty() {
+        println!("All dependencies are up to date");
+        return Ok(());
+    }
+
+    for risk in Risk::ALL {
+        let group: Vec<&Upgrade> = upgrades.iter().filter(|u| u.risk == risk).collect();
+        if group.is_empty() {
+            continue;
+        }
+        let mut ecosystems: Vec<Ecosystem> = group.iter().map(|u| u.ecosystem).collect();
+        ecosystems.dedup();
+        println!("==> Applying {} {} upgrades", group.len(), risk.as_str());
+
+        let snapshot: Vec<(PathBuf, Option<Vec<u8>>)> = ecosystems
+            .iter()
+            .flat_map(|e| e.state_files(dir))
+            .map(|path| {
+                let content = fs::read(&path).ok();
+                (path, content)
+            })
+            .collect();
+
+        let mut outcome = Ok(());
+        for upgrade in &group {
+            println!(
+                "  {} {} {} -> {}",
+                upgrade.ecosystem.as_str(),
+                upgrade.name,
+                upgrade.current,
+                upgrade.latest
+            );
+            outcome = apply_upgrade(executor, dir, upgrade).await;
+            if outcome.is_err() {
+                break;
+            }
+        }
+        if outcome.is_ok() {
+            let gates: Vec<CommandSpec> = match tests {
+                Some(tests) => tests.to_vec(),
+                None => ecosystems
+                    .iter()
+                    .map(|e| CommandSpec::parse(e.default_test()).map_err(|e| anyhow!(e)))
+                    .collect::<Result<_>>()?,
+            };
+            for gate in &gates {
+                println!("==> Testing {} upgrades: {}", risk.as_str(), gate);
+                if let Err(e) = executor.execute_secure(&gate.command, &gate.args).await {
+                    outcome = Err(anyhow!("'{}' failed: {}", gate, e));
+                    break;
+                }
+            }
+        }
+
+        if let Err(e) = outcome {
+            if !dry_run {
+                for (path, content) in &snapshot {
+                    match content {
+                        Some(content) => fs::write(path, content)?,
+                        None if path.exists() => fs::remove_file(path)?,
+                        None => {}
+                    }
+                }
+            }
+            bail!(
+                "Stopped at the {} upgrades and restored their files: {}; earlier groups remain applied",
+                risk.as_str(),
+                e
+            );
+        }
+        println!(
+            "==> {} {} upgrades applied and tested",
+            group.len(),
+            risk.as_str()
+        );
+    }
+    Ok(())
+}
+
+async fn apply_upgrade(executor: &Executor, dir: &Path, upgrade: &Upgrade) -> Result<()> {
+    match upgrade.ecosystem {
+        Ecosystem::Cargo => {
+            let path = dir.join("Cargo.toml");
+            let updated =
+                set_cargo_requirement(&fs::read_to_string(&path)?, &upgrade.name, &upgrade.latest)?;
+            executor.check_content(&path, updated.as_bytes())?;
+            if executor.config().safety.dry_run {
+                println!(
+                    "DRY RUN: Would set {} = \"{}\" in {}",
+                    upgrade.name,
+                    upgrade.latest,
+                    path.display()
+                );
+            } else {
+                fs::write(&path, updated)?;
+            }
+            executor
+                .execute_secure("cargo", &["update", "-p", upgrade.name.as_str()])
+                .await
+        }
+        Ecosystem::Npm => {
+            let package = format!("{}@{}", upgrade.name, upgrade.latest);
+            executor
+                .execute_secure("npm", &["install", package.as_str()])
+                .await
+        }
+        Ecosystem::Pip => {
+            let pin = format!("{}=={}", upgrade.name, upgrade.latest);
+            executor
+                .execute_secure("pip", &["install", pin.as_str()])
+                .await?;
+            for path in requirement_files(dir) {
+                let content = fs::read_to_string(&path)?;
+                let updated = set_pip_pin(&content, &upgrade.name, &upgrade.latest);
+                if updated != content {
+                    executor.check_content(&path, updated.as_bytes())?;
+                    if !executor.config().safety.dry_run {
+                        fs::write(&path, updated)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `requirements*.txt` files directly in `dir`
+fn requirement_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("requirements") && n.ends_with(".txt"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Numeric major, minor and patch of a version or requirement like `^1.2` or `>=0.4.1`
+fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let digits = Regex::new(r"(\d+)(?:\.(\d+))?(?:\.(\d+))?").unwrap();
+    let found = digits.captures(version)?;
+    let part = |i| {
+        found
+            .get(i)
+            .map_or(Some(0), |m: regex::Match| m.as_str().parse().ok())
+    };
+    Some([part(1)?, part(2)?, part(3)?])
+}
+
+/// Dependencies of Cargo.toml with a registry version requirement
+fn cargo_requirements(manifest: &str) -> Result<Vec<(String, String)>> {
+    let doc: DocumentMut = manifest.parse()?;
+    let mut requirements = Vec::new();
+    for kind in CARGO_DEPENDENCY_TABLES {
+        let Some(table) = doc.get(kind).and_then(Item::as_table_like) else {
+            continue;
+        };
+        for (name, spec) in table.iter() {
+            let version = match spec {
+                Item::Value(Value::String(version)) => Some(version.value().clone()),
+                _ => spec
+                    .as_table_like()
+                    .filter(|t| !t.contains_key("path") && !t.contains_key("git"))
+                    .and_then(|t| t.get("version"))
+                    .and_then(Item::as_str)
+                    .map(str::to_string),
+            };
+            if let Some(version) = version {
+                requirements.push((name.to_string(), version));
+            }
+        }
+    }
+    Ok(requirements)
+}
+
+/// Set the version requirement of `name` in every dependency table, keeping the layout
+fn set_cargo_requirement(manifest: &str, name: &str, version: &str) -> Result<String> {
+    let mut doc: DocumentMut = manifest.parse()?;
+    for kind in CARGO_DEPENDENCY_TABLES {
+        let Some(spec) = doc
+            .get_mut(kind)
+            .and_then(Item::as_table_like_mut)
+            .and_then(|t| t.get_mut(name))
+        else {
+            continue;
+        };
+        if spec.is_str() {
+            let decor = spec.as_value().unwrap().decor().clone();
+            *spec = toml_edit::value(version);
+            *spec.as_value_mut().unwrap().decor_mut() = decor;
+        } else if let Some(table) = spec.as_table_like_mut() {
+            table.insert("version", toml_edit::value(version));
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// Latest version of `name` from `cargo search` output
+fn parse_cargo_search(output: &str, name: &str) -> Option<String> {
+    let line = Regex::new(r#"^(\S+) = "([^"]+)""#).unwrap();
+    output.lines().find_map(|l| {
+        let found = line.captures(l)?;
+        (&found[1] == name).then(|| found[2].to_string())
+    })
+}
+
+/// Upgrades from `npm outdated --json`; packages that are not installed are skipped
+fn parse_npm_outdated(output: &str) -> Result<Vec<Upgrade>> {
+    if output.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let outdated: JsonValue = serde_json::from_str(output)?;
+    Ok(outdated
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, info)| {
+            let current = info["current"].as_str()?;
+            let latest = info["latest"].as_str()?;
+            (current != latest).then(|| Upgrade::new(Ecosystem::Npm, name, current, latest))
+        })
+        .collect())
+}
+
+/// Upgrades from `pip list --outdated --format=json`
+fn parse_pip_outdated(output: &str) -> Result<Vec<Upgrade>> {
+    let outdated: JsonValue = serde_json::from_str(output)?;
+    Ok(outdated
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            Some(Upgrade::new(
+                Ecosystem::Pip,
+                package["name"].as_str()?,
+                package["version"].as_str()?,
+                package["latest_version"].as_str()?,
+            ))
+        })
+        .collect())
+}
+
+/// Replace exact pins (`name==x`) of `name` in a requirements file
+fn set_pip_pin(content: &str, name: &str, version: &str) -> String {
+    let pin = Regex::new(&format!(
+        r"(?im)^({}(?:\[[^\]]*\])?\s*==\s*)[^\s;#]+",
+        regex::escape(name)
+    ))
+    .unwrap();
+    pin.replace_all(content, |caps: &regex::Captures| {
+        format!("{}{}", &caps[1], version)
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_risk() {
+        assert_eq!(Risk::classify("1.2.3", "1.2.9"), Risk::Patch);
+        assert_eq!(Risk::classify("^1.2", "1.5.0"), Risk::Minor);
+        assert_eq!(Risk::classify("1.35", "2.0.1"), Risk::Major);
+        assert_eq!(Risk::classify("0.4.1", "0.4.3"), Risk::Minor);
+        assert_eq!(Risk::classify("0.4", "0.5.0"), Risk::Major);
+        assert_eq!(Risk::classify("latest", "1.0.0"), Risk::Major);
+    }
+
+    #[test]
+    fn test_parse_tool_output() {
+        let npm = r#"{"react": {"current": "18.2.0", "wanted": "18.3.1", "latest": "19.0.0"}, "left-pad": {"wanted": "1.3.0", "latest": "1.3.0"}}"#;
+        let upgrades = parse_npm_outdated(npm).unwrap();
+        assert_eq!(upgrades.len(), 1);
+        assert_eq!(upgrades[0].risk, Risk::Major);
+        assert_eq!(
+            upgrades[0].changelog,
+            "https://www.npmjs.com/package/react?activeTab=versions"
+        );
+
+        let pip = r#"[{"name": "requests", "version": "2.31.0", "latest_version": "2.32.3", "latest_filetype": "wheel"}]"#;
+        assert_eq!(parse_pip_outdated(pip).unwrap()[0].risk, Risk::Minor);
+
+        let search = "serde = \"1.0.210\"    # A generic serialization framework\nserde_json = \"1.0.128\"\n";
+        assert_eq!(
+            parse_cargo_search(search, "serde").as_deref(),
+            Some("1.0.210")
+        );
+        assert_eq!(parse_cargo_search(search, "serde_derive"), None);
+    }
+
+    #[test]
+    fn test_rewrite_requirements() {
+        let manifest = "[dependencies]\nregex = \"1.10\" # matching\nlocal = { path = \"../local\" }\ntokio = { version = \"1.35\", features = [\"full\"] }\n";
+        assert_eq!(
+            cargo_requirements(manifest).unwrap(),
+            vec![
+                ("regex".to_string(), "1.10".to_string()),
+                ("tokio".to_string(), "1.35".to_string())
+            ]
+        );
+        assert_eq!(
+            set_cargo_requirement(manifest, "tokio", "1.48").unwrap(),
+            "[dependencies]\nregex = \"1.10\" # matching\nlocal = { path = \"../local\" }\ntokio = { version = \"1.48\", features = [\"full\"] }\n"
+        );
+        assert_eq!(
+            set_cargo_requirement(manifest, "regex", "1.11").unwrap(),
+            "[dependencies]\nregex = \"1.11\" # matching\nlocal = { path = \"../local\" }\ntokio = { version = \"1.35\", features = [\"full\"] }\n"
+        );
+
+        let requirements = "Requests[socks]==2.31.0 ; python_version > '3.8'\nrequests-mock==1.0\n";
+        assert_eq!(
+            set_pip_pin(requirements, "requests", "2.32.3"),
+            "Requests[socks]==2.32.3 ; python_version > '3.8'\nrequests-mock==1.0\n"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+// file: src/commands/diagnostics.rs
+// version: 1.4.0
+// guid: 2713b788-0884-475e-9d4c-9046d93c9e85
+
+//! `diagnostics` command: run a tool and report its findings as uniform diagnostics
+//!
+//! The tool is run with its machine-readable output format (see
+//! [`crate::parsers::Tool::invocation`]), or output saved earlier is read with `--input`, and
+//! every finding is printed as `file:line:column: severity[code]: message` or as JSON.
+//!
+//! With `--new-since REF` the tool also runs on a temporary git worktree of `REF`, and with
+//! `--baseline FILE` findings are read from an earlier `--save-baseline`; either way only
+//! findings the baseline does not have are reported, and only new errors fail the command.
+
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use crate::parsers::{self, Diagnostic, Severity, Tool};
+use anyhow::{anyhow, bail, Result};
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Build the diagnostics command
+pub fn build_command() -> Command {
+    Command::new("diagnostics")
+        .about("Run a tool and report its errors, lint findings and failed tests as uniform diagnostics")
+        .long_about(
+            "Run cargo, eslint, ruff, go test, pytest, buf, shellcheck, gradle, maven or ctest \
+             with its machine-readable or plain output format and report every finding the same \
+             way, whichever tool produced it. Fails if the tool fails or reports an error.",
+        )
+        .arg(
+            Arg::new("tool")
+                .value_name("TOOL")
+                .required(true)
+                .value_parser(PossibleValuesParser::new(
+                    Tool::ALL.iter().map(|tool| tool.as_str()),
+                ))
+                .help("Tool to run"),
+        )
+        .arg(
+            Arg::new("args")
+                .value_name("ARGS")
+                .num_args(0..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .help(
+                    "Arguments for the tool, e.g. clippy --all-targets for cargo (defaults: cargo \
+                     check, eslint ., ruff check ., go test ./..., buf lint, gradle build, mvn \
+                     verify, ctest --test-dir build)",
+                ),
+        )
+        .arg(
+            Arg::new("input")
+                .long("input")
+                .short('i')
+                .value_name("FILE")
+                .conflicts_with("args")
+                .help("Parse output the tool already printed, saved in FILE ('-' for stdin), instead of running it"),
+        )
+        .arg(
+            Arg::new("new-since")
+                .long("new-since")
+                .value_name("REF")
+                .conflicts_with_all(["input", "baseline"])
+                .help("Only report findings the tool does not also report on a checkout of REF, e.g. origin/main"),
+        )
+        .arg(
+            Arg::new("baseline")
+                .long("baseline")
+                .value_name("FILE")
+                .help("Only report findings that are not in FILE, written earlier with --save-baseline"),
+        )
+        .arg(
+            Arg::new("save-baseline")
+                .long("save-baseline")
+                .value_name("FILE")
+                .help("Write all findings to FILE, to compare later runs against"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+}
+
+/// Findings of one run of a tool
+struct Run {
+    diagnostics: Vec<Diagnostic>,
+    /// Exit code of the tool; `None` when saved output was parsed
+    exit_code: Option<Option<i32>>,
+    stderr: String,
+}
+
+impl Run {
+    fn failed(&self) -> bool {
+        self.exit_code.is_some_and(|code| code != Some(0))
+    }
+}
+
+/// Execute the diagnostics command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let tool: Tool = matches
+        .get_one::<String>("tool")
+        .unwrap()
+        .parse()
+        .map_err(|e: String| anyhow!(e))?;
+    let args: Vec<String> = matches
+        .get_many::<String>("args")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+
+    let run = match matches.get_one::<String>("input") {
+        Some(input) => {
+            let root = working_directory(executor);
+            Run {
+                diagnostics: tool
+                    .parse(&read_input(input, executor)?, "")
+                    .into_iter()
+                    .map(|diagnostic| diagnostic.relative_to(&root))
+                    .collect(),
+                exit_code: None,
+                stderr: String::new(),
+            }
+        }
+        None => run_tool(executor, tool, &args, None).await?,
+    };
+    // Without findings, the tool's own errors are the only explanation of a failure
+    let unexplained = run.failed() && run.diagnostics.is_empty();
+    if let Some(path) = matches.get_one::<String>("save-baseline") {
+        save_baseline(executor, path, tool, &run.diagnostics)?;
+    }
+
+    let baseline = match (
+        matches.get_one::<String>("new-since"),
+        matches.get_one::<String>("baseline"),
+    ) {
+        (Some(reference), _) => Some(base_findings(executor, tool, &args, reference).await?),
+        (None, Some(path)) => Some(load_baseline(executor, path)?),
+        (None, None) => None,
+    };
+    let total = run.diagnostics.len();
+    let diagnostics = match &baseline {
+        Some(baseline) => parsers::new_findings(run.diagnostics, baseline),
+        None => run.diagnostics,
+    };
+    let known = total - diagnostics.len();
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let mut report = json!({
+            "tool": tool,
+            "diagnostics": diagnostics,
+            "summary": parsers::summary(&diagnostics),
+        });
+        if baseline.is_some() {
+            report["known"] = json!(known);
+        }
+        if let Some(code) = run.exit_code {
+            report["exit_code"] = json!(code);
+        }
+        if unexplained {
+            report["stderr"] = json!(run.stderr);
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for diagnostic in &diagnostics {
+            println!("{}", diagnostic);
+        }
+        if unexplained {
+            eprint!("{}", run.stderr);
+        }
+        match baseline {
+            Some(_) => eprintln!(
+                "New: {}; {} known finding(s) not shown",
+                describe(&diagnostics),
+                known
+            ),
+            None => eprintln!("{}", describe(&diagnostics)),
+        }
+    }
+
+    let errors = count(&diagnostics, Severity::Error);
+    if errors > 0 {
+        bail!(
+            "{} reported {} {}error(s)",
+            tool,
+            errors,
+            if baseline.is_some() { "new " } else { "" }
+        );
+    }
+    // Against a baseline, known errors fail the tool without failing the command
+    if baseline.is_none() || unexplained {
+        if let Some(code) = run.exit_code.filter(|code| *code != Some(0)) {
+            bail!(
+                "{} failed with {}",
+                tool,
+                code.map(|code| format!("exit code {}", code))
+                    .unwrap_or_else(|| "a signal".to_string())
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Run the tool with `args` in `cwd`, or in the working directory
+async fn run_tool(
+    executor: &Executor,
+    tool: Tool,
+    args: &[String],
+    cwd: Option<&Path>,
+) -> Result<Run> {
+    let (program, args) = tool.invocation(args);
+    let spec = CommandSpec {
+        command: program.to_string(),
+        args,
+        cwd: cwd.map(Path::to_path_buf),
+        env: BTreeMap::new(),
+        stdin: None,
+    };
+    let output = executor.execute_spec_capture_full(&spec).await?;
+    let root = cwd
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| working_directory(executor));
+    Ok(Run {
+        diagnostics: tool
+            .parse(&output.stdout, &output.stderr)
+            .into_iter()
+            .map(|diagnostic| diagnostic.relative_to(&root))
+            .collect(),
+        exit_code: Some(output.exit_code),
+        stderr: output.stderr,
+    })
+}
+
+/// Findings of the tool on a checkout of `reference`, made in a temporary git worktree and
+/// run in the same subdirectory as the working tree
+async fn base_findings(
+    executor: &Executor,
+    tool: Tool,
+    args: &[String],
+    reference: &str,
+) -> Result<Vec<Diagnostic>> {
+    if reference.starts_with('-') {
+        bail!("Invalid git reference '{}'", reference);
+    }
+    if executor.config().safety.dry_run {
+        println!(
+            "Would compare with the findings of {} at {}",
+            tool, reference
+        );
+        return Ok(Vec::new());
+    }
+
+    let prefix = executor
+        .execute_secure_capture("git", &["rev-parse", "--show-prefix"])
+        .await?;
+    if !prefix.success() {
+        bail!(
+            "--new-since needs a git repository: {}",
+            prefix.stderr.trim()
+        );
+    }
+    let checkout = tempfile::Builder::new()
+        .prefix("safe-ai-util-base-")
+        .tempdir()?;
+    let tree = checkout.path().join("tree");
+    let tree_arg = tree.display().to_string();
+    let added = executor
+        .execute_secure_capture(
+            "git",
+            &["worktree", "add", "--detach", &tree_arg, reference],
+        )
+        .await?;
+    if !added.success() {
+        bail!("Cannot check out {}: {}", reference, added.stderr.trim());
+    }
+
+    let run = run_tool(executor, tool, args, Some(&tree.join(prefix.stdout.trim()))).await;
+    let removed = executor
+        .execute_secure_capture("git", &["worktree", "remove", "--force", &tree_arg])
+        .await;
+    if !removed.is_ok_and(|output| output.success()) {
+        warn!(
+            "Could not remove the worktree {}; `git worktree prune` forgets it once it is deleted",
+            tree.display()
+        );
+    }
+
+    let run = run?;
+    if run.failed() && run.diagnostics.is_empty() {
+        bail!(
+            "{} failed on {} without reporting findings: {}",
+            tool,
+            reference,
+            run.stderr.trim()
+        );
+    }
+    Ok(run.diagnostics)
+}
+
+/// Write findings as a baseline: the JSON report without summary, which
+/// [`load_baseline`] reads back
+fn save_baseline(
+    executor: &Executor,
+    path: &str,
+    tool: Tool,
+    diagnostics: &[Diagnostic],
+) -> Result<()> {
+    let path = executor.check_path(path)?;
+    let baseline = json!({"tool": tool, "diagnostics": diagnostics});
+    fs::write(&path, serde_json::to_string_pretty(&baseline)? + "\n")
+        .map_err(|e| anyhow!("Cannot write {}: {}", path.display(), e))
+}
+
+/// Findings saved with `--save-baseline`, or a `--format json` report
+fn load_baseline(executor: &Executor, path: &str) -> Result<Vec<Diagnostic>> {
+    let path = executor.check_path(path)?;
+    let text =
+        fs::read_to_string(&path).map_err(|e| anyhow!("Cannot read {}: {}", path.display(), e))?;
+    let mut baseline: Value = serde_json::from_str(&text)
+        .map_err(|e| anyhow!("{} is not a diagnostics baseline: {}", path.display(), e))?;
+    let diagnostics = match baseline.get_mut("diagnostics") {
+        Some(diagnostics) => diagnostics.take(),
+        None => baseline,
+    };
+    serde_json::from_value(diagnostics)
+        .map_err(|e| anyhow!("{} is not a diagnostics baseline: {}", path.display(), e))
+}
+
+fn working_directory(executor: &Executor) -> PathBuf {
+    executor
+        .config()
+        .general
+        .working_directory
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Saved tool output, from a file inside the workspace or from stdin
+fn read_input(input: &str, executor: &Executor) -> Result<String> {
+    if input == "-" {
+        let mut output = String::new();
+        io::stdin().read_to_string(&mut output)?;
+        return Ok(output);
+    }
+    let path = executor.check_path(input)?;
+    fs::read_to_string(&path).map_err(|e| anyhow!("Cannot read {}: {}", path.display(), e))
+}
+
+fn count(diagnostics: &[Diagnostic], severity: Severity) -> usize {
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == severity)
+        .count()
+}
+
+/// `2 errors, 1 warning`
+pub(crate) fn describe(diagnostics: &[Diagnostic]) -> String {
+    if diagnostics.is_empty() {
+        return "No diagnostics".to_string();
+    }
+    let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+    let mut parts = vec![
+        plural(count(diagnostics, Severity::Error), "error"),
+        plural(count(diagnostics, Severity::Warning), "warning"),
+    ];
+    let info = count(diagnostics, Severity::Info);
+    if info > 0 {
+        parts.push(plural(info, "note"));
+    }
+    parts.join(", ")
+}
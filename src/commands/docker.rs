@@ -0,0 +1,648 @@
+// file: src/commands/docker.rs
+// version: 1.0.2
+// guid: 6f2c8b14-93ad-4e75-a0d1-5b7e3c9f2a48
+
+//! `docker` command: build images, run compose projects and clean up, with guards
+//!
+//! Build contexts and Dockerfiles, of `build` and of the services `compose up` builds, must lie
+//! inside the workspace roots, since everything in a context is sent to the daemon and can end
+//! up in an image. `compose up` refuses services that declare `privileged: true` unless
+//! `policy.allow_privileged` is set; `--privileged` on the command line is refused by the
+//! argument checks in any case.
+//!
+//! `rm`, `image prune`, `system prune` and `compose down --volumes` delete containers, images
+//! or volumes that cannot be brought back, so they ask first unless `--yes` is given or
+//! `safety.confirm_destructive` is off.
+
+use super::export::{Effect, Remote};
+use crate::executor::Executor;
+use crate::security::audit;
+use crate::utils;
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_yaml::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files docker compose reads when no `--file` is given
+const COMPOSE_FILES: &[&str] = &[
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+    "compose.override.yaml",
+    "compose.override.yml",
+    "docker-compose.override.yaml",
+    "docker-compose.override.yml",
+];
+
+/// Build the docker command
+pub fn build_command() -> Command {
+    Command::new("docker")
+        .about("Build images, run compose projects and clean up containers and images")
+        .long_about(
+            "Run common docker workflows. Build contexts must lie inside the workspace roots, \
+             compose services declaring privileged: true need policy.allow_privileged, and rm, \
+             image prune, system prune and compose down --volumes ask before deleting anything.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("build")
+                .add(Remote)
+                .about("Build an image from a context in the workspace")
+                .arg(
+                    Arg::new("context")
+                        .value_name("CONTEXT")
+                        .default_value(".")
+                        .help("Build context directory"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .short('t')
+                        .value_name("NAME[:TAG]")
+                        .action(ArgAction::Append)
+                        .help("Name of the image; repeat for several"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .short('f')
+                        .value_name("DOCKERFILE")
+                        .help("Dockerfile to build (default: CONTEXT/Dockerfile)"),
+                )
+                .arg(
+                    Arg::new("build-arg")
+                        .long("build-arg")
+                        .value_name("KEY=VALUE")
+                        .action(ArgAction::Append)
+                        .help("Build-time variable; repeat for several"),
+                )
+                .arg(
+                    Arg::new("no-cache")
+                        .long("no-cache")
+                        .action(ArgAction::SetTrue)
+                        .help("Build every layer again"),
+                )
+                .arg(
+                    Arg::new("pull")
+                        .long("pull")
+                        .action(ArgAction::SetTrue)
+                        .help("Pull newer versions of the base images"),
+                )
+                .arg(extra_args()),
+        )
+        .subcommand(
+            Command::new("compose")
+                .about("Start and stop compose projects")
+                .subcommand_required(true)
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .short('f')
+                        .value_name("FILE")
+                        .action(ArgAction::Append)
+                        .help("Compose file (default: compose.yaml or docker-compose.yml)"),
+                )
+                .subcommand(
+                    Command::new("up")
+                        .add(Remote)
+                        .about("Create and start the services")
+                        .arg(
+                            Arg::new("services")
+                                .value_name("SERVICE")
+                                .action(ArgAction::Append)
+                                .help("Services to start (default: all)"),
+                        )
+                        .arg(
+                            Arg::new("detach")
+                                .long("detach")
+                                .short('d')
+                                .action(ArgAction::SetTrue)
+                                .help("Run the containers in the background"),
+                        )
+                        .arg(
+                            Arg::new("build")
+                                .long("build")
+                                .action(ArgAction::SetTrue)
+                                .help("Build images before starting the containers"),
+                        )
+                        .arg(extra_args()),
+                )
+                .subcommand(
+                    Command::new("down")
+                        .add(Effect::Destructive)
+                        .about("Stop and remove the containers and networks of the services")
+                        .arg(
+                            Arg::new("volumes")
+                                .long("volumes")
+                                .short('v')
+                                .action(ArgAction::SetTrue)
+                                .help("Also remove the volumes of the services, and their data"),
+                        )
+                        .arg(
+                            Arg::new("remove-orphans")
+                                .long("remove-orphans")
+                                .action(ArgAction::SetTrue)
+                                .help("Also remove containers of services no longer defined"),
+                        )
+                        .arg(yes()),
+                ),
+        )
+        .subcommand(
+            Command::new("ps")
+                .add(Effect::ReadOnly)
+                .about("List containers")
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .short('a')
+                        .action(ArgAction::SetTrue)
+                        .help("Include stopped containers"),
+                )
+                .arg(extra_args()),
+        )
+        .subcommand(
+            Command::new("logs")
+                .add(Effect::ReadOnly)
+                .about("Show the output of a container")
+                .arg(Arg::new("container").value_name("CONTAINER").required(true))
+                .arg(
+                    Arg::new("tail")
+                        .long("tail")
+                        .short('n')
+                        .value_name("LINES")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Only the last LINES lines"),
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .value_name("TIME")
+                        .help("Only lines since TIME, e.g. 10m or 2024-01-02T15:04:05"),
+                )
+                .arg(
+                    Arg::new("timestamps")
+                        .long("timestamps")
+                        .short('t')
+                        .action(ArgAction::SetTrue)
+                        .help("Prefix lines with their time"),
+                )
+                .arg(
+                    Arg::new("follow")
+                        .long("follow")
+                        .short('f')
+                        .action(ArgAction::SetTrue)
+                        .help("Keep printing new output until interrupted"),
+                ),
+        )
+        .subcommand(
+            Command::new("image")
+                .about("Manage images")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("prune")
+                        .add(Effect::Destructive)
+                        .about("Remove dangling images")
+                        .arg(all_unused("Remove every image no container uses"))
+                        .arg(
+                            Arg::new("filter")
+                                .long("filter")
+                                .value_name("FILTER")
+                                .action(ArgAction::Append)
+                                .help("Only images matching FILTER, e.g. until=24h"),
+                        )
+                        .arg(yes()),
+                ),
+        )
+        .subcommand(
+            Command::new("rm")
+                .add(Effect::Destructive)
+                .about("Remove containers")
+                .arg(
+                    Arg::new("containers")
+                        .value_name("CONTAINER")
+                        .action(ArgAction::Append)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .short('f')
+                        .action(ArgAction::SetTrue)
+                        .help("Stop running containers first"),
+                )
+                .arg(
+                    Arg::new("volumes")
+                        .long("volumes")
+                        .short('v')
+                        .action(ArgAction::SetTrue)
+                        .help("Also remove the anonymous volumes of the containers"),
+                )
+                .arg(yes()),
+        )
+        .subcommand(
+            Command::new("system")
+                .about("Manage docker")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("prune")
+                        .add(Effect::Destructive)
+                        .about(
+                            "Remove stopped containers, unused networks, dangling images and \
+                             build cache",
+                        )
+                        .arg(all_unused("Also remove every image no container uses"))
+                        .arg(
+                            Arg::new("volumes")
+                                .long("volumes")
+                                .action(ArgAction::SetTrue)
+                                .help("Also remove unused volumes, and their data"),
+                        )
+                        .arg(yes()),
+                ),
+        )
+}
+
+fn all_unused(help: &'static str) -> Arg {
+    Arg::new("all")
+        .long("all")
+        .short('a')
+        .action(ArgAction::SetTrue)
+        .help(help)
+}
+
+fn yes() -> Arg {
+    Arg::new("yes")
+        .long("yes")
+        .short('y')
+        .action(ArgAction::SetTrue)
+        .help("Delete without asking for confirmation")
+}
+
+fn extra_args() -> Arg {
+    Arg::new("args")
+        .value_name("ARGS")
+        .num_args(0..)
+        .last(true)
+        .allow_hyphen_values(true)
+        .help("Further arguments for docker after --")
+}
+
+/// Execute the docker command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let args = match matches.subcommand() {
+        Some(("build", sub_matches)) => build_args(sub_matches, executor)?,
+        Some(("compose", sub_matches)) => match compose_args(sub_matches, executor)? {
+            Some(args) => args,
+            None => return Ok(()),
+        },
+        Some(("image", sub_matches)) => match sub_matches.subcommand() {
+            Some(("prune", prune_matches)) => {
+                let prompt = if prune_matches.get_flag("all") {
+                    "Remove every image no container uses?"
+                } else {
+                    "Remove dangling images?"
+                };
+                if !confirmed(prune_matches, executor, prompt)? {
+                    return Ok(());
+                }
+                let mut args = strings(&["image", "prune", "--force"]);
+                push_flags(&mut args, prune_matches, &["all"]);
+                for filter in prune_matches
+                    .get_many::<String>("filter")
+                    .unwrap_or_default()
+                {
+                    args.push("--filter".to_string());
+                    args.push(filter.clone());
+                }
+                args
+            }
+            _ => unreachable!("subcommand required"),
+        },
+        Some(("rm", sub_matches)) => {
+            let containers: Vec<String> = sub_matches
+                .get_many::<String>("containers")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            let prompt = format!(
+                "Remove {}{}?",
+                containers.join(", "),
+                if sub_matches.get_flag("volumes") {
+                    " and their anonymous volumes"
+                } else {
+                    ""
+                }
+            );
+            if !confirmed(sub_matches, executor, &prompt)? {
+                return Ok(());
+            }
+            let mut args = strings(&["rm"]);
+            push_flags(&mut args, sub_matches, &["force", "volumes"]);
+            args.extend(containers);
+            args
+        }
+        Some(("system", sub_matches)) => match sub_matches.subcommand() {
+            Some(("prune", prune_matches)) => {
+                let mut removed = vec![
+                    "stopped containers",
+                    "unused networks",
+                    if prune_matches.get_flag("all") {
+                        "every image no container uses"
+                    } else {
+                        "dangling images"
+                    },
+                    "build cache",
+                ];
+                if prune_matches.get_flag("volumes") {
+                    removed.push("unused volumes");
+                }
+                let prompt = format!("Remove {}?", removed.join(", "));
+                if !confirmed(prune_matches, executor, &prompt)? {
+                    return Ok(());
+                }
+                let mut args = strings(&["system", "prune", "--force"]);
+                push_flags(&mut args, prune_matches, &["all", "volumes"]);
+                args
+            }
+            _ => unreachable!("subcommand required"),
+        },
+        Some(("ps", sub_matches)) => {
+            let mut args = strings(&["ps"]);
+            push_flags(&mut args, sub_matches, &["all"]);
+            args.extend(extra(sub_matches));
+            args
+        }
+        Some(("logs", sub_matches)) => {
+            let mut args = strings(&["logs"]);
+            if let Some(lines) = sub_matches.get_one::<u32>("tail") {
+                args.push("--tail".to_string());
+                args.push(lines.to_string());
+            }
+            if let Some(since) = sub_matches.get_one::<String>("since") {
+                args.push("--since".to_string());
+                args.push(since.clone());
+            }
+            push_flags(&mut args, sub_matches, &["timestamps", "follow"]);
+            args.push(sub_matches.get_one::<String>("container").unwrap().clone());
+            args
+        }
+        _ => unreachable!("subcommand required"),
+    };
+    executor.execute_secure("docker", &args).await
+}
+
+/// Arguments for `docker build`, once the context and Dockerfile are known to be in the
+/// workspace
+fn build_args(matches: &ArgMatches, executor: &Executor) -> Result<Vec<String>> {
+    let context = matches.get_one::<String>("context").unwrap();
+    let resolved = executor.sandbox().check_path(Path::new(context))?;
+    if !resolved.is_dir() {
+        bail!(
+            "Build context {} is not a directory in the workspace",
+            context
+        );
+    }
+    let dockerfile = matches.get_one::<String>("file");
+    if let Some(dockerfile) = dockerfile {
+        executor.sandbox().check_path(Path::new(dockerfile))?;
+    }
+
+    let mut args = strings(&["build"]);
+    for tag in matches.get_many::<String>("tag").unwrap_or_default() {
+        args.push("--tag".to_string());
+        args.push(tag.clone());
+    }
+    if let Some(dockerfile) = dockerfile {
+        args.push("--file".to_string());
+        args.push(dockerfile.clone());
+    }
+    for build_arg in matches.get_many::<String>("build-arg").unwrap_or_default() {
+        args.push("--build-arg".to_string());
+        args.push(build_arg.clone());
+    }
+    push_flags(&mut args, matches, &["no-cache", "pull"]);
+    args.extend(extra(matches));
+    args.push(context.clone());
+    Ok(args)
+}
+
+/// Arguments for `docker compose`, or `None` when the user declined to remove volumes
+fn compose_args(matches: &ArgMatches, executor: &Executor) -> Result<Option<Vec<String>>> {
+    let files: Vec<String> = matches
+        .get_many::<String>("file")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let mut args = strings(&["compose"]);
+    for file in &files {
+        args.push("--file".to_string());
+        args.push(file.clone());
+    }
+    match matches.subcommand() {
+        Some(("up", up_matches)) => {
+            let paths: Vec<PathBuf> = if files.is_empty() {
+                COMPOSE_FILES
+                    .iter()
+                    .map(|name| executor.sandbox().base().join(name))
+                    .filter(|path| path.is_file())
+                    .collect()
+            } else {
+                files
+                    .iter()
+                    .map(|file| executor.sandbox().check_path(Path::new(file)))
+                    .collect::<std::result::Result<_, _>>()?
+            };
+            for path in &paths {
+                check_compose_file(path, executor)?;
+            }
+            args.push("up".to_string());
+            push_flags(&mut args, up_matches, &["detach", "build"]);
+            args.extend(extra(up_matches));
+            args.extend(
+                up_matches
+                    .get_many::<String>("services")
+                    .unwrap_or_default()
+                    .cloned(),
+            );
+        }
+        Some(("down", down_matches)) => {
+            if down_matches.get_flag("volumes")
+                && !confirmed(
+                    down_matches,
+                    executor,
+                    "Remove the volumes of the compose project, and the data in them?",
+                )?
+            {
+                return Ok(None);
+            }
+            args.push("down".to_string());
+            push_flags(&mut args, down_matches, &["volumes", "remove-orphans"]);
+        }
+        _ => unreachable!("subcommand required"),
+    }
+    Ok(Some(args))
+}
+
+/// A service of a compose file, as far as the guards are concerned
+#[derive(Debug, PartialEq, Eq)]
+struct Service {
+    name: String,
+    privileged: bool,
+    /// Build context, relative to the directory of the compose file
+    context: Option<String>,
+    dockerfile: Option<String>,
+}
+
+/// The services a compose file defines
+fn compose_services(text: &str) -> Result<Vec<Service>> {
+    let document: Value = serde_yaml::from_str(text)?;
+    let Some(services) = document.get("services").and_then(Value::as_mapping) else {
+        return Ok(Vec::new());
+    };
+    Ok(services
+        .iter()
+        .map(|(name, service)| {
+            let build = service.get("build");
+            let (context, dockerfile) = match build {
+                Some(Value::String(context)) => (Some(context.clone()), None),
+                Some(build) => (
+                    build
+                        .get("context")
+                        .and_then(Value::as_str)
+                        .map(String::from)
+                        .or_else(|| Some(".".to_string())),
+                    build
+                        .get("dockerfile")
+                        .and_then(Value::as_str)
+                        .map(String::from),
+                ),
+                None => (None, None),
+            };
+            Service {
+                name: name.as_str().unwrap_or_default().to_string(),
+                privileged: service.get("privileged").and_then(Value::as_bool) == Some(true),
+                context,
+                dockerfile,
+            }
+        })
+        .collect())
+}
+
+/// Refuse privileged services the policy does not allow and build contexts outside the
+/// workspace
+fn check_compose_file(path: &Path, executor: &Executor) -> Result<()> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let services = compose_services(&text)
+        .with_context(|| format!("Failed to parse compose file {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for service in services {
+        if service.privileged {
+            if let Err(e) = executor.policy().evaluate_privileged("docker") {
+                audit::log_access_denied("docker", &e.to_string());
+                return Err(anyhow::Error::from(e).context(format!(
+                    "Service '{}' of {} is privileged",
+                    service.name,
+                    path.display()
+                )));
+            }
+        }
+        // Remote contexts, such as git URLs, are fetched by the daemon and never read locally
+        let Some(context) = service.context.filter(|context| !context.contains("://")) else {
+            continue;
+        };
+        let context = dir.join(context);
+        executor.sandbox().check_path(&context)?;
+        if let Some(dockerfile) = service.dockerfile {
+            executor.sandbox().check_path(&context.join(dockerfile))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a deletion may go ahead: with `--yes`, with `safety.confirm_destructive` off, or
+/// once the user agrees to `prompt`
+fn confirmed(matches: &ArgMatches, executor: &Executor, prompt: &str) -> Result<bool> {
+    if !matches.get_flag("yes") && executor.config().safety.confirm_destructive && !utils::confirm(prompt)?
+    {
+        println!("Aborted; nothing was removed");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// Append `--id` for each of the flags `ids` that is set
+fn push_flags(args: &mut Vec<String>, matches: &ArgMatches, ids: &[&str]) {
+    for id in ids {
+        if matches.get_flag(id) {
+            args.push(format!("--{}", id));
+        }
+    }
+}
+
+fn extra(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .get_many::<String>("args")
+        .unwrap_or_default()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_services() {
+        let services = compose_services(
+            r#"
+services:
+  web:
+    build: ./web
+  agent:
+    image: monitor
+    privileged: true
+  api:
+    build:
+      context: ../api
+      dockerfile: Dockerfile.dev
+  worker:
+    build:
+      dockerfile: worker.Dockerfile
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            services
+                .iter()
+                .map(|s| (
+                    s.name.as_str(),
+                    s.context.as_deref(),
+                    s.dockerfile.as_deref()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("web", Some("./web"), None),
+                ("agent", None, None),
+                ("api", Some("../api"), Some("Dockerfile.dev")),
+                ("worker", Some("."), Some("worker.Dockerfile")),
+            ]
+        );
+        assert_eq!(
+            services
+                .iter()
+                .filter(|s| s.privileged)
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>(),
+            ["agent"]
+        );
+        assert!(compose_services("name: empty\n").unwrap().is_empty());
+    }
+}
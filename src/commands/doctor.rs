@@ -0,0 +1,1560 @@
+// file: src/commands/doctor.rs
+// version: 1.3.1
+// guid: 0e4bdabc-f389-4c61-a5ec-16ec1862eb12
+
+//! `doctor` command: check installed toolchains against what a project declares
+//!
+//! Every ecosystem detected in the project directory gets a set of deep checks:
+//!
+//! - Python: the virtual environment's interpreter against `requires-python` in
+//!   `pyproject.toml` (or the Poetry `python` dependency)
+//! - Node: the installed `node` against `engines.node` in `package.json` and against the
+//!   version in `.nvmrc` / `.node-version`
+//! - Rust: the channel, components and targets of `rust-toolchain.toml` against the
+//!   toolchains rustup has installed, and `rust-version` in `Cargo.toml` against the
+//!   compiler the project builds with
+//! - Tools: the external tools the commands run (git, buf, prettier, python3 and the uutils
+//!   `coreutils` binary) and every tool pinned under `[tools.<name>]`, for presence, the
+//!   pinned minimum version, and other copies on PATH that shadow a newer one
+//!
+//! Each failed or suspicious check carries a command that fixes it. The command exits with
+//! an error when any check fails, so it can gate CI jobs. Toolchains are run through the
+//! executor, so policy, sandbox, audit and budget apply to them; a virtual environment's
+//! interpreter is never run, its version is read from `pyvenv.cfg`.
+
+use super::export::Effect;
+use crate::batch::CommandSpec;
+use crate::capability;
+use crate::config::ToolPin;
+use crate::executor::Executor;
+use crate::toolchain;
+use crate::tools::{self, compare_versions, join_version};
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PYTHON: &str = "python";
+const NODE: &str = "node";
+const RUST: &str = "rust";
+const TOOLS: &str = "tools";
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Skip,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Skip => "skip",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Check {
+    ecosystem: &'static str,
+    name: String,
+    status: Status,
+    detail: String,
+    /// Command that resolves a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+impl Check {
+    fn new(
+        ecosystem: &'static str,
+        name: impl Into<String>,
+        status: Status,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            ecosystem,
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn with_fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    ok: usize,
+    skipped: usize,
+    warnings: usize,
+    failures: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    root: PathBuf,
+    checks: Vec<Check>,
+    summary: Summary,
+}
+
+/// Build the doctor command
+pub fn build_command() -> Command {
+    Command::new("doctor")
+        .add(Effect::ReadOnly)
+        .about("Check toolchains against the project's requirements and external tools against their pins")
+        .arg(
+            Arg::new("path")
+                .value_name("PATH")
+                .default_value(".")
+                .help("Project directory"),
+        )
+        .arg(
+            Arg::new("ecosystem")
+                .long("ecosystem")
+                .short('e')
+                .value_parser([PYTHON, NODE, RUST, TOOLS])
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help("Only run checks for these ecosystems (default: all)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Report format"),
+        )
+}
+
+/// Execute the doctor command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let root = executor.check_path(Path::new(matches.get_one::<String>("path").unwrap()))?;
+    if !root.is_dir() {
+        return Err(anyhow!("{} is not a directory", root.display()));
+    }
+    let selected: Vec<&str> = match matches.get_many::<String>("ecosystem") {
+        Some(values) => values.map(String::as_str).collect(),
+        None => vec![PYTHON, NODE, RUST, TOOLS],
+    };
+
+    let probe = Probe {
+        executor,
+        root: &root,
+    };
+    let mut checks = Vec::new();
+    if selected.contains(&PYTHON) {
+        checks.extend(check_python(&probe).await);
+    }
+    if selected.contains(&NODE) {
+        checks.extend(check_node(&probe).await);
+    }
+    if selected.contains(&RUST) {
+        checks.extend(check_rust(&probe).await);
+    }
+    if selected.contains(&TOOLS) {
+        checks.extend(check_tools(&probe));
+    }
+
+    let mut summary = Summary::default();
+    for check in &checks {
+        match check.status {
+            Status::Ok => summary.ok += 1,
+            Status::Skip => summary.skipped += 1,
+            Status::Warn => summary.warnings += 1,
+            Status::Fail => summary.failures += 1,
+        }
+    }
+    let report = Report {
+        root,
+        checks,
+        summary,
+    };
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    if report.summary.failures > 0 {
+        return Err(anyhow!(
+            "{} doctor check(s) failed",
+            report.summary.failures
+        ));
+    }
+    Ok(())
+}
+
+fn print_report(report: &Report) {
+    let mut ecosystem = "";
+    for check in &report.checks {
+        if check.ecosystem != ecosystem {
+            ecosystem = check.ecosystem;
+            println!("{}", ecosystem);
+        }
+        println!(
+            "  {:<4}  {:<12} {}",
+            check.status.label(),
+            check.name,
+            check.detail
+        );
+        if let Some(fix) = &check.fix {
+            println!("        fix: {}", fix);
+        }
+    }
+    let summary = &report.summary;
+    println!(
+        "{} ok, {} warnings, {} failures, {} skipped",
+        summary.ok, summary.warnings, summary.failures, summary.skipped
+    );
+}
+
+/// Locates and runs tools for the checks
+struct Probe<'a> {
+    executor: &'a Executor,
+    root: &'a Path,
+}
+
+impl Probe<'_> {
+    /// Find a tool on PATH, honoring `[tools.<name>]` pins
+    fn find(&self, name: &str) -> std::result::Result<PathBuf, String> {
+        tools::resolve(name, self.executor.config().tools.get(name)).map_err(|e| e.to_string())
+    }
+
+    /// Run a tool in the project directory through the executor and return its stdout if it
+    /// succeeds
+    ///
+    /// rustup is told not to install missing toolchains, which it would otherwise do when
+    /// a `rust-toolchain.toml` names one.
+    async fn output(&self, name: &str, args: &[&str]) -> Option<String> {
+        let spec = CommandSpec {
+            command: name.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            cwd: Some(self.root.to_path_buf()),
+            env: BTreeMap::from([("RUSTUP_AUTO_INSTALL".to_string(), "0".to_string())]),
+            stdin: None,
+        };
+        let output = self.executor.execute_spec_capture_full(&spec).await.ok()?;
+        output.success().then_some(output.stdout)
+    }
+
+    /// The version a tool reports with `--version`, run through the executor
+    async fn version(&self, name: &str) -> std::result::Result<Vec<u64>, String> {
+        let spec = CommandSpec {
+            command: name.to_string(),
+            args: vec!["--version".to_string()],
+            cwd: Some(self.root.to_path_buf()),
+            env: BTreeMap::new(),
+            stdin: None,
+        };
+        let output = self
+            .executor
+            .execute_spec_capture_full(&spec)
+            .await
+            .map_err(|e| e.to_string())?;
+        // Some tools (older Python releases among them) print their version on stderr
+        let text = format!("{}\n{}", output.stdout, output.stderr);
+        tools::parse_version(&text).ok_or_else(|| {
+            format!(
+                "could not determine the version of {} from '{}'",
+                name,
+                text.trim()
+            )
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Python
+// ---------------------------------------------------------------------------
+
+async fn check_python(probe: &Probe<'_>) -> Vec<Check> {
+    let root = probe.root;
+    if !["pyproject.toml", "setup.py", "requirements.txt"]
+        .iter()
+        .any(|marker| root.join(marker).exists())
+    {
+        return vec![Check::new(
+            PYTHON,
+            "project",
+            Status::Skip,
+            "no pyproject.toml, setup.py or requirements.txt",
+        )];
+    }
+
+    let mut checks = Vec::new();
+    let requirement = match read_requires_python(&root.join("pyproject.toml")) {
+        Ok(requirement) => requirement,
+        Err(e) => {
+            checks.push(Check::new(PYTHON, "pyproject", Status::Fail, e.to_string()));
+            None
+        }
+    };
+    let hint = requirement
+        .as_deref()
+        .and_then(parse_python_spec)
+        .and_then(|spec| lower_bound(&spec))
+        .map(|v| join_version(&v[..v.len().min(2)]));
+    let uv = probe.find("uv").is_ok();
+
+    let venv = find_venv(root);
+    let mut interpreter = "python";
+    let python = match &venv {
+        Some((dir, python)) => {
+            checks.push(Check::new(
+                PYTHON,
+                "virtualenv",
+                Status::Ok,
+                dir.display().to_string(),
+            ));
+            Some(python.clone())
+        }
+        None => {
+            checks.push(
+                Check::new(
+                    PYTHON,
+                    "virtualenv",
+                    Status::Warn,
+                    "no virtual environment found (.venv, venv or $VIRTUAL_ENV)",
+                )
+                .with_fix(venv_fix(uv, hint.as_deref(), ".venv", false)),
+            );
+            let found = ["python3", "python"]
+                .into_iter()
+                .find_map(|name| Some((name, probe.find(name).ok()?)));
+            if let Some((name, _)) = found {
+                interpreter = name;
+            }
+            found.map(|(_, path)| path)
+        }
+    };
+
+    let Some(python) = python else {
+        checks.push(
+            Check::new(
+                PYTHON,
+                "interpreter",
+                Status::Fail,
+                "no Python interpreter found",
+            )
+            .with_fix(match (&hint, uv) {
+                (Some(version), true) => format!("uv python install {}", version),
+                (None, true) => "uv python install".to_string(),
+                (Some(version), false) => format!("install Python {}", version),
+                (None, false) => "install Python 3".to_string(),
+            }),
+        );
+        return checks;
+    };
+    // The project's own interpreter is not run
+    let version = match &venv {
+        Some((dir, _)) => venv_version(dir),
+        None => probe.version(interpreter).await,
+    };
+    let version = match version {
+        Ok(version) => version,
+        Err(e) => {
+            checks.push(Check::new(PYTHON, "interpreter", Status::Fail, e));
+            return checks;
+        }
+    };
+
+    let found = format!("Python {} at {}", join_version(&version), python.display());
+    let Some(requirement) = requirement else {
+        checks.push(Check::new(
+            PYTHON,
+            "interpreter",
+            Status::Ok,
+            format!("{} (no requires-python declared)", found),
+        ));
+        return checks;
+    };
+    let check = match parse_python_spec(&requirement).map(|spec| satisfies(&spec, &version)) {
+        Some(true) => Check::new(
+            PYTHON,
+            "interpreter",
+            Status::Ok,
+            format!("{} satisfies requires-python '{}'", found, requirement),
+        ),
+        Some(false) => {
+            let venv_dir = venv
+                .as_ref()
+                .and_then(|(dir, _)| dir.strip_prefix(root).ok())
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_else(|| ".venv".to_string());
+            Check::new(
+                PYTHON,
+                "interpreter",
+                Status::Fail,
+                format!(
+                    "{} does not satisfy requires-python '{}'",
+                    found, requirement
+                ),
+            )
+            .with_fix(venv_fix(uv, hint.as_deref(), &venv_dir, venv.is_some()))
+        }
+        None => Check::new(
+            PYTHON,
+            "interpreter",
+            Status::Warn,
+            format!(
+                "{}; cannot evaluate requires-python '{}'",
+                found, requirement
+            ),
+        ),
+    };
+    checks.push(check);
+    checks
+}
+
+/// Read `project.requires-python`, falling back to Poetry's `python` dependency
+fn read_requires_python(pyproject: &Path) -> Result<Option<String>> {
+    if !pyproject.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(pyproject)?;
+    let value: toml::Value = toml::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse {}: {}", pyproject.display(), e))?;
+    let requirement = value
+        .get("project")
+        .and_then(|p| p.get("requires-python"))
+        .or_else(|| {
+            value
+                .get("tool")
+                .and_then(|t| t.get("poetry"))
+                .and_then(|p| p.get("dependencies"))
+                .and_then(|d| d.get("python"))
+        })
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    Ok(requirement)
+}
+
+/// The virtual environment of a project and its interpreter: `.venv` or `venv` in the
+/// project, or the active `$VIRTUAL_ENV`
+fn find_venv(root: &Path) -> Option<(PathBuf, PathBuf)> {
+    let candidates = [
+        Some(root.join(".venv")),
+        Some(root.join("venv")),
+        std::env::var_os("VIRTUAL_ENV").map(PathBuf::from),
+    ];
+    candidates.into_iter().flatten().find_map(|dir| {
+        let python = if cfg!(windows) {
+            dir.join("Scripts").join("python.exe")
+        } else {
+            dir.join("bin").join("python")
+        };
+        python.exists().then_some((dir, python))
+    })
+}
+
+/// The Python version a virtual environment was created with, from its `pyvenv.cfg`
+fn venv_version(dir: &Path) -> std::result::Result<Vec<u64>, String> {
+    let cfg = dir.join("pyvenv.cfg");
+    let content =
+        fs::read_to_string(&cfg).map_err(|e| format!("cannot read {}: {}", cfg.display(), e))?;
+    content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| matches!(key.trim(), "version" | "version_info"))
+        .and_then(|(_, value)| tools::parse_version(value))
+        .ok_or_else(|| format!("{} does not record a Python version", cfg.display()))
+}
+
+fn venv_fix(uv: bool, version: Option<&str>, dir: &str, recreate: bool) -> String {
+    match (uv, version) {
+        (true, Some(version)) => format!("uv venv --python {} {}", version, dir),
+        (true, None) => format!("uv venv {}", dir),
+        (false, version) => format!(
+            "python{} -m venv{} {}",
+            version.unwrap_or("3"),
+            if recreate { " --clear" } else { "" },
+            dir
+        ),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Node
+// ---------------------------------------------------------------------------
+
+async fn check_node(probe: &Probe<'_>) -> Vec<Check> {
+    let root = probe.root;
+    let package_json = root.join("package.json");
+    if !package_json.exists() {
+        return vec![Check::new(NODE, "project", Status::Skip, "no package.json")];
+    }
+
+    let mut checks = Vec::new();
+    let engines = match read_node_engine(&package_json) {
+        Ok(engines) => engines,
+        Err(e) => {
+            checks.push(Check::new(
+                NODE,
+                "package.json",
+                Status::Fail,
+                e.to_string(),
+            ));
+            None
+        }
+    };
+    let range = engines.as_deref().and_then(parse_npm_range);
+    let pinned = read_node_pin(root);
+    // nvm reads the version file, unless it pins a version engines.node rules out
+    let pin_usable = pinned
+        .as_ref()
+        .is_some_and(|(_, pin)| match (parse_partial(pin), &range) {
+            (Some(version), Some(range)) => satisfies(range, &version),
+            _ => true,
+        });
+    let install_fix = |major: Option<u64>| match (pin_usable, major) {
+        (true, _) => "nvm install && nvm use".to_string(),
+        (false, Some(major)) => format!("nvm install {0} && nvm use {0}", major),
+        (false, None) => "nvm install --lts && nvm use --lts".to_string(),
+    };
+    let wanted_major = range
+        .as_ref()
+        .and_then(|r| lower_bound(r))
+        .and_then(|v| v.first().copied());
+
+    let node = match probe.find("node") {
+        Ok(node) => node,
+        Err(e) => {
+            checks.push(
+                Check::new(NODE, "node", Status::Fail, e).with_fix(install_fix(wanted_major)),
+            );
+            return checks;
+        }
+    };
+    let version = match probe.version("node").await {
+        Ok(version) => version,
+        Err(e) => {
+            checks.push(Check::new(NODE, "node", Status::Fail, e));
+            return checks;
+        }
+    };
+    let found = format!("node {} at {}", join_version(&version), node.display());
+
+    match (&engines, &range) {
+        (None, _) => checks.push(Check::new(
+            NODE,
+            "engines",
+            Status::Ok,
+            format!("{} (no engines.node declared)", found),
+        )),
+        (Some(engines), None) => checks.push(Check::new(
+            NODE,
+            "engines",
+            Status::Warn,
+            format!("{}; cannot evaluate engines.node '{}'", found, engines),
+        )),
+        (Some(engines), Some(range)) if satisfies(range, &version) => checks.push(Check::new(
+            NODE,
+            "engines",
+            Status::Ok,
+            format!("{} satisfies engines.node '{}'", found, engines),
+        )),
+        (Some(engines), Some(_)) => checks.push(
+            Check::new(
+                NODE,
+                "engines",
+                Status::Fail,
+                format!("{} does not satisfy engines.node '{}'", found, engines),
+            )
+            .with_fix(install_fix(wanted_major)),
+        ),
+    }
+
+    if let Some((file, pin)) = &pinned {
+        checks.push(check_node_pin(
+            file,
+            pin,
+            &version,
+            engines.as_deref().zip(range.as_ref()),
+            wanted_major,
+        ));
+    }
+    checks
+}
+
+/// Compare the version pinned in `.nvmrc` / `.node-version` with the installed node and
+/// with `engines.node`
+fn check_node_pin(
+    file: &str,
+    pin: &str,
+    installed: &[u64],
+    engines: Option<(&str, &Vec<Vec<Comparator>>)>,
+    wanted_major: Option<u64>,
+) -> Check {
+    let Some(pinned) = parse_partial(pin) else {
+        // Aliases such as lts/* or node resolve through nvm and cannot be checked here
+        return Check::new(
+            NODE,
+            "version-file",
+            Status::Skip,
+            format!("{} names '{}', an alias", file, pin),
+        );
+    };
+    if let Some((engines, range)) = engines {
+        if !satisfies(range, &pinned) {
+            let fix = match wanted_major {
+                Some(major) => format!("echo {} > {}", major, file),
+                None => format!("update {} to a version matching '{}'", file, engines),
+            };
+            return Check::new(
+                NODE,
+                "version-file",
+                Status::Warn,
+                format!("{} pins {}, outside engines.node '{}'", file, pin, engines),
+            )
+            .with_fix(fix);
+        }
+    }
+    if installed.starts_with(&pinned) {
+        Check::new(
+            NODE,
+            "version-file",
+            Status::Ok,
+            format!("{} pins {}", file, pin),
+        )
+    } else {
+        Check::new(
+            NODE,
+            "version-file",
+            Status::Warn,
+            format!(
+                "{} pins {} but node {} is active",
+                file,
+                pin,
+                join_version(installed)
+            ),
+        )
+        .with_fix("nvm install && nvm use")
+    }
+}
+
+fn read_node_engine(package_json: &Path) -> Result<Option<String>> {
+    let content = fs::read_to_string(package_json)?;
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse {}: {}", package_json.display(), e))?;
+    Ok(value
+        .get("engines")
+        .and_then(|e| e.get("node"))
+        .and_then(|n| n.as_str())
+        .map(str::to_string))
+}
+
+/// The first line of `.nvmrc` or `.node-version`, with the file it came from
+fn read_node_pin(root: &Path) -> Option<(String, String)> {
+    [".nvmrc", ".node-version"].iter().find_map(|file| {
+        let content = fs::read_to_string(root.join(file)).ok()?;
+        let pin = content.lines().map(str::trim).find(|l| !l.is_empty())?;
+        Some((file.to_string(), pin.to_string()))
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Rust
+// ---------------------------------------------------------------------------
+
+/// The parts of `rust-toolchain.toml` that the checks compare
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ToolchainFile {
+    name: String,
+    channel: Option<String>,
+    components: Vec<String>,
+    targets: Vec<String>,
+}
+
+async fn check_rust(probe: &Probe<'_>) -> Vec<Check> {
+    let root = probe.root;
+    let cargo_toml = root.join("Cargo.toml");
+    let toolchain = match read_toolchain_file(root) {
+        Ok(toolchain) => toolchain,
+        Err(e) => {
+            return vec![Check::new(RUST, "toolchain", Status::Fail, e.to_string())];
+        }
+    };
+    if toolchain.is_none() && !cargo_toml.exists() {
+        return vec![Check::new(
+            RUST,
+            "project",
+            Status::Skip,
+            "no Cargo.toml or rust-toolchain file",
+        )];
+    }
+
+    let mut checks = Vec::new();
+    let rustup = probe.find("rustup");
+    let mut toolchain_installed = true;
+    match (&toolchain, &rustup) {
+        (Some(file), Err(e)) => {
+            toolchain_installed = false;
+            checks.push(
+                Check::new(
+                    RUST,
+                    "rustup",
+                    Status::Fail,
+                    format!("{}; {} needs rustup", e, file.name),
+                )
+                .with_fix("curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh"),
+            );
+        }
+        (Some(file), Ok(_)) => {
+            let list = probe
+                .output("rustup", &["toolchain", "list"])
+                .await
+                .unwrap_or_default();
+            let installed = parse_toolchain_list(&list);
+            checks.extend(check_toolchain(probe, file, &installed).await);
+            toolchain_installed = checks.iter().all(|c| c.status != Status::Fail);
+        }
+        (None, _) => {}
+    }
+
+    if let Some(check) =
+        check_rust_version(probe, &cargo_toml, toolchain.as_ref(), toolchain_installed).await
+    {
+        checks.push(check);
+    }
+    checks
+}
+
+/// Check the channel, components and targets of a toolchain file against rustup
+async fn check_toolchain(
+    probe: &Probe<'_>,
+    file: &ToolchainFile,
+    installed: &[String],
+) -> Vec<Check> {
+    let Some(channel) = &file.channel else {
+        return vec![Check::new(
+            RUST,
+            "toolchain",
+            Status::Skip,
+            format!(
+                "{} sets no channel; the default toolchain is used",
+                file.name
+            ),
+        )];
+    };
+    if !installed
+        .iter()
+        .any(|name| toolchain_matches(name, channel))
+    {
+        let mut fix = format!("rustup toolchain install {}", channel);
+        for component in &file.components {
+            fix.push_str(&format!(" --component {}", component));
+        }
+        for target in &file.targets {
+            fix.push_str(&format!(" --target {}", target));
+        }
+        return vec![Check::new(
+            RUST,
+            "toolchain",
+            Status::Fail,
+            format!("{} requires {}, which is not installed", file.name, channel),
+        )
+        .with_fix(fix)];
+    }
+
+    let mut checks = vec![Check::new(
+        RUST,
+        "toolchain",
+        Status::Ok,
+        format!("{} is installed", channel),
+    )];
+    for (kind, wanted) in [("component", &file.components), ("target", &file.targets)] {
+        if wanted.is_empty() {
+            continue;
+        }
+        let list = probe
+            .output(
+                "rustup",
+                &[kind, "list", "--installed", "--toolchain", channel],
+            )
+            .await
+            .unwrap_or_default();
+        let have: Vec<&str> = list.lines().map(str::trim).collect();
+        let missing: Vec<&str> = wanted
+            .iter()
+            .map(String::as_str)
+            .filter(|w| !have.iter().any(|h| toolchain_matches(h, w)))
+            .collect();
+        let name = if kind == "component" {
+            "components"
+        } else {
+            "targets"
+        };
+        if missing.is_empty() {
+            checks.push(Check::new(RUST, name, Status::Ok, wanted.join(", ")));
+        } else {
+            checks.push(
+                Check::new(
+                    RUST,
+                    name,
+                    Status::Fail,
+                    format!("missing for {}: {}", channel, missing.join(", ")),
+                )
+                .with_fix(format!(
+                    "rustup {} add {} --toolchain {}",
+                    kind,
+                    missing.join(" "),
+                    channel
+                )),
+            );
+        }
+    }
+    checks
+}
+
+/// Check `rust-version` in Cargo.toml against the compiler the project builds with
+async fn check_rust_version(
+    probe: &Probe<'_>,
+    cargo_toml: &Path,
+    toolchain: Option<&ToolchainFile>,
+    toolchain_installed: bool,
+) -> Option<Check> {
+    let content = fs::read_to_string(cargo_toml).ok()?;
+    let manifest: toml::Value = toml::from_str(&content).ok()?;
+    let msrv = manifest
+        .get("package")
+        .and_then(|p| p.get("rust-version"))
+        .or_else(|| {
+            manifest
+                .get("workspace")
+                .and_then(|w| w.get("package"))
+                .and_then(|p| p.get("rust-version"))
+        })
+        .and_then(|v| v.as_str())?
+        .to_string();
+    let required = tools::parse_version(&msrv)?;
+
+    if !toolchain_installed {
+        return Some(Check::new(
+            RUST,
+            "rust-version",
+            Status::Skip,
+            format!(
+                "rust-version {} not checked; the toolchain is missing",
+                msrv
+            ),
+        ));
+    }
+    let rustc = match probe.find("rustc") {
+        Ok(rustc) => rustc,
+        Err(e) => {
+            return Some(
+                Check::new(RUST, "rust-version", Status::Fail, e)
+                    .with_fix(format!("rustup toolchain install {}", msrv)),
+            )
+        }
+    };
+    let Some(version) = probe
+        .output("rustc", &["--version"])
+        .await
+        .and_then(|out| tools::parse_version(&out))
+    else {
+        return Some(Check::new(
+            RUST,
+            "rust-version",
+            Status::Warn,
+            format!("could not run {} --version", rustc.display()),
+        ));
+    };
+
+    let found = format!("rustc {}", join_version(&version));
+    if compare_versions(&version, &required) != Ordering::Less {
+        return Some(Check::new(
+            RUST,
+            "rust-version",
+            Status::Ok,
+            format!("{} satisfies rust-version {}", found, msrv),
+        ));
+    }
+    let fix = match toolchain.and_then(|t| t.channel.as_ref().map(|c| (t, c))) {
+        Some((file, channel)) => format!(
+            "change channel \"{}\" to \"{}\" in {} and run rustup toolchain install {}",
+            channel, msrv, file.name, msrv
+        ),
+        None => "rustup update stable".to_string(),
+    };
+    Some(
+        Check::new(
+            RUST,
+            "rust-version",
+            Status::Fail,
+            format!("{} is older than rust-version {}", found, msrv),
+        )
+        .with_fix(fix),
+    )
+}
+
+/// Read `rust-toolchain.toml`, or the legacy `rust-toolchain` file, which holds either the
+/// same TOML or just a channel name
+fn read_toolchain_file(root: &Path) -> Result<Option<ToolchainFile>> {
+    for name in ["rust-toolchain.toml", "rust-toolchain"] {
+        let path = root.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        return parse_toolchain_file(name, &content)
+            .map(Some)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e));
+    }
+    Ok(None)
+}
+
+fn parse_toolchain_file(name: &str, content: &str) -> Result<ToolchainFile> {
+    let trimmed = content.trim();
+    if name == "rust-toolchain" && !trimmed.contains('[') && !trimmed.contains('=') {
+        return Ok(ToolchainFile {
+            name: name.to_string(),
+            channel: Some(trimmed.to_string()).filter(|c| !c.is_empty()),
+            ..Default::default()
+        });
+    }
+
+    let value: toml::Value = toml::from_str(content)?;
+    let section = value
+        .get("toolchain")
+        .ok_or_else(|| anyhow!("missing [toolchain] table"))?;
+    let strings = |key: &str| -> Vec<String> {
+        section
+            .get(key)
+            .and_then(|v| v.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|i| i.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    Ok(ToolchainFile {
+        name: name.to_string(),
+        channel: section
+            .get("channel")
+            .and_then(|c| c.as_str())
+            .map(str::to_string),
+        components: strings("components"),
+        targets: strings("targets"),
+    })
+}
+
+/// Toolchain names from `rustup toolchain list`, without `(default)` and similar notes
+fn parse_toolchain_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| *name != "no" && *name != "info:")
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether an installed name (`stable-x86_64-unknown-linux-gnu`, `clippy-x86_64-...`)
+/// is the wanted toolchain or component, with or without a host triple
+///
+/// `nightly` does not match a dated `nightly-2024-05-01-...` toolchain.
+fn toolchain_matches(installed: &str, wanted: &str) -> bool {
+    installed == wanted
+        || installed
+            .strip_prefix(wanted)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .is_some_and(|triple| !triple.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+// ---------------------------------------------------------------------------
+// Tools
+// ---------------------------------------------------------------------------
+
+/// Binaries the commands run: git for the git commands, buf for protobuf, prettier for
+/// formatting, python3 for the Python commands and `coreutils` for uutils
+const MODULE_TOOLS: &[&str] = &["git", "buf", "prettier", "python3", "coreutils"];
+
+fn check_tools(probe: &Probe) -> Vec<Check> {
+    let pins = &probe.executor.config().tools;
+    // Relative entries would run binaries from the project tree
+    let search_path = std::env::var_os("PATH").and_then(|path| {
+        std::env::join_paths(std::env::split_paths(&path).filter(|entry| entry.is_absolute())).ok()
+    });
+    let mut names: Vec<&str> = MODULE_TOOLS.to_vec();
+    for name in pins.keys() {
+        if !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| check_tool(name, pins.get(name), search_path.as_deref()))
+        .collect()
+}
+
+/// Check one tool: the binary the executor would run, its version against the pin, and
+/// copies of it later on PATH
+fn check_tool(name: &str, pin: Option<&ToolPin>, search_path: Option<&OsStr>) -> Check {
+    let on_path = path_candidates(name, search_path);
+    let min_version = pin.and_then(|p| p.min_version.as_deref());
+    let required = min_version.and_then(tools::parse_version);
+    let accepts = |version: &[u64]| {
+        required.as_ref().map_or(true, |required| {
+            compare_versions(version, required) != Ordering::Less
+        })
+    };
+    let pin_fix = |path: &Path| {
+        format!(
+            "safe-ai-util config set tools.{}.path {}",
+            name,
+            path.display()
+        )
+    };
+    let install_fix = || capability::install_command(name, min_version);
+
+    let pinned = pin.and_then(|p| p.path.as_ref());
+    // The executor runs a version installed with `toolchain install` before the one on PATH
+    let managed = toolchain::managed_binary(name);
+    let used = match (pinned, managed.as_ref().or(on_path.first())) {
+        (Some(path), _) if !path.is_file() => {
+            let fix = on_path
+                .first()
+                .map(|found| pin_fix(found))
+                .unwrap_or_else(install_fix);
+            return Check::new(
+                TOOLS,
+                name,
+                Status::Fail,
+                format!("pinned to {}, which does not exist", path.display()),
+            )
+            .with_fix(fix);
+        }
+        // The executor refuses a pin that is not the binary it would otherwise run
+        (Some(path), found) => {
+            let canonical =
+                |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            match found {
+                Some(found) if canonical(found) == canonical(path) => path.clone(),
+                Some(found) => {
+                    return Check::new(
+                        TOOLS,
+                        name,
+                        Status::Fail,
+                        format!(
+                            "pinned to {}, but {} resolves to {}",
+                            path.display(),
+                            name,
+                            found.display()
+                        ),
+                    )
+                }
+                None => {
+                    return Check::new(
+                        TOOLS,
+                        name,
+                        Status::Fail,
+                        format!("pinned to {}, which is not on PATH", path.display()),
+                    )
+                }
+            }
+        }
+        (None, Some(found)) => found.clone(),
+        (None, None) => {
+            // Only a pinned tool is required; the others fail just the commands needing them
+            let status = if pin.is_some() {
+                Status::Fail
+            } else {
+                Status::Warn
+            };
+            return Check::new(TOOLS, name, status, "not found on PATH").with_fix(install_fix());
+        }
+    };
+
+    let version = match tools::tool_version(&used) {
+        Ok(version) => version,
+        Err(e) => {
+            let status = if required.is_some() {
+                Status::Fail
+            } else {
+                Status::Warn
+            };
+            return Check::new(TOOLS, name, status, e.to_string());
+        }
+    };
+    let found = format!("{} at {}", join_version(&version), used.display());
+
+    // Other binaries of the same name, with their versions
+    let used_real = fs::canonicalize(&used).unwrap_or_else(|_| used.clone());
+    let others: Vec<(PathBuf, Vec<u64>)> = on_path
+        .iter()
+        .filter(|path| fs::canonicalize(path).unwrap_or_else(|_| (*path).clone()) != used_real)
+        .filter_map(|path| Some((path.clone(), tools::tool_version(path).ok()?)))
+        .collect();
+
+    if !accepts(&version) {
+        let detail = format!(
+            "{} is older than tools.{}.min_version {}",
+            found,
+            name,
+            min_version.unwrap_or_default()
+        );
+        let fix = others
+            .iter()
+            .find(|(_, version)| accepts(version))
+            .map(|(path, _)| pin_fix(path))
+            .unwrap_or_else(install_fix);
+        return Check::new(TOOLS, name, Status::Fail, detail).with_fix(fix);
+    }
+
+    let newer = others
+        .iter()
+        .filter(|(_, other)| compare_versions(other, &version) == Ordering::Greater)
+        .max_by(|(_, a), (_, b)| compare_versions(a, b));
+    match (pinned.or(managed.as_ref()), newer) {
+        (None, Some((path, other))) => Check::new(
+            TOOLS,
+            name,
+            Status::Warn,
+            format!(
+                "{} shadows {} at {} on PATH",
+                found,
+                join_version(other),
+                path.display()
+            ),
+        )
+        .with_fix(pin_fix(path)),
+        _ => {
+            let mut detail = found;
+            if let Some(min_version) = min_version {
+                detail.push_str(&format!(" (min_version {})", min_version));
+            }
+            if pinned.is_some() {
+                detail.push_str(", pinned");
+            } else if managed.is_some() {
+                detail.push_str(", managed");
+            }
+            if !others.is_empty() {
+                detail.push_str(&format!("; {} more on PATH", others.len()));
+            }
+            Check::new(TOOLS, name, Status::Ok, detail)
+        }
+    }
+}
+
+/// Every binary named `name` on the search path, in order, skipping links to one already
+/// found
+fn path_candidates(name: &str, search_path: Option<&OsStr>) -> Vec<PathBuf> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut seen = Vec::new();
+    let mut found = Vec::new();
+    for path in which::which_in_all(name, search_path, cwd)
+        .into_iter()
+        .flatten()
+    {
+        let real = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if !seen.contains(&real) {
+            seen.push(real);
+            found.push(path);
+        }
+    }
+    found
+}
+
+// ---------------------------------------------------------------------------
+// Version requirements
+// ---------------------------------------------------------------------------
+
+/// A single version constraint; comparisons pad missing components with zeros
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Comparator {
+    Lt(Vec<u64>),
+    Le(Vec<u64>),
+    Gt(Vec<u64>),
+    Ge(Vec<u64>),
+    Eq(Vec<u64>),
+    Ne(Vec<u64>),
+    /// Leading components match (`==3.11.*`)
+    Prefix(Vec<u64>),
+    NotPrefix(Vec<u64>),
+}
+
+impl Comparator {
+    fn matches(&self, version: &[u64]) -> bool {
+        let cmp = |v: &[u64]| compare_versions(version, v);
+        match self {
+            Comparator::Lt(v) => cmp(v) == Ordering::Less,
+            Comparator::Le(v) => cmp(v) != Ordering::Greater,
+            Comparator::Gt(v) => cmp(v) == Ordering::Greater,
+            Comparator::Ge(v) => cmp(v) != Ordering::Less,
+            Comparator::Eq(v) => cmp(v) == Ordering::Equal,
+            Comparator::Ne(v) => cmp(v) != Ordering::Equal,
+            Comparator::Prefix(v) => has_prefix(version, v),
+            Comparator::NotPrefix(v) => !has_prefix(version, v),
+        }
+    }
+}
+
+fn has_prefix(version: &[u64], prefix: &[u64]) -> bool {
+    prefix
+        .iter()
+        .enumerate()
+        .all(|(i, p)| version.get(i).copied().unwrap_or(0) == *p)
+}
+
+/// Whether a version satisfies any of the alternatives, each a list of comparators that
+/// must all hold
+fn satisfies(alternatives: &[Vec<Comparator>], version: &[u64]) -> bool {
+    alternatives
+        .iter()
+        .any(|all| all.iter().all(|c| c.matches(version)))
+}
+
+/// The smallest version a requirement names, used to suggest what to install
+fn lower_bound(alternatives: &[Vec<Comparator>]) -> Option<Vec<u64>> {
+    alternatives.first()?.iter().find_map(|c| match c {
+        Comparator::Ge(v) | Comparator::Eq(v) | Comparator::Prefix(v) if !v.is_empty() => {
+            Some(v.clone())
+        }
+        _ => None,
+    })
+}
+
+/// Parse a possibly partial version (`3`, `3.11`, `v18.x`); `x` and `*` end the version.
+/// Pre-release and build suffixes are ignored.
+fn parse_partial(text: &str) -> Option<Vec<u64>> {
+    let text = text.trim().trim_start_matches(['v', 'V', '=']);
+    let text = text.split(['-', '+']).next().unwrap_or_default();
+    let mut parts = Vec::new();
+    for part in text.split('.') {
+        if matches!(part, "x" | "X" | "*") {
+            break;
+        }
+        parts.push(part.parse().ok()?);
+    }
+    Some(parts)
+}
+
+/// The exclusive upper bound of a partial version: `1.2` -> `1.3`
+fn bump(version: &[u64]) -> Vec<u64> {
+    let mut bumped = version.to_vec();
+    if let Some(last) = bumped.last_mut() {
+        *last += 1;
+    }
+    bumped
+}
+
+/// `^1.2.3` -> `>=1.2.3 <2`; `^0.2.3` -> `<0.3`; `^0.0.3` -> `<0.0.4`
+fn caret(version: Vec<u64>) -> Vec<Comparator> {
+    let significant = version
+        .iter()
+        .position(|&p| p != 0)
+        .unwrap_or(version.len().saturating_sub(1));
+    let upper = bump(&version[..(significant + 1).min(version.len())]);
+    vec![Comparator::Ge(version), Comparator::Lt(upper)]
+}
+
+/// `~1.2.3` -> `>=1.2.3 <1.3`; `~1` -> `>=1 <2`
+fn tilde(version: Vec<u64>) -> Vec<Comparator> {
+    let upper = bump(&version[..version.len().min(2)]);
+    vec![Comparator::Ge(version), Comparator::Lt(upper)]
+}
+
+/// Parse a PEP 440 specifier such as `>=3.9,<3.13` or `~=3.10`
+///
+/// Poetry's `^3.10` and `~3.10` forms are accepted too. Returns `None` for anything that
+/// cannot be evaluated.
+fn parse_python_spec(spec: &str) -> Option<Vec<Vec<Comparator>>> {
+    let mut all = Vec::new();
+    for clause in spec.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+        let (op, value) = split_operator(
+            clause,
+            &["===", "~=", "==", "!=", "<=", ">=", "<", ">", "^", "~"],
+        );
+        let wildcard = value.ends_with(".*");
+        let version = parse_partial(value)?;
+        if version.is_empty() {
+            continue;
+        }
+        match (op, wildcard) {
+            ("==" | "===" | "", true) => all.push(Comparator::Prefix(version)),
+            ("!=", true) => all.push(Comparator::NotPrefix(version)),
+            (_, true) => return None,
+            ("==" | "===" | "", false) => all.push(Comparator::Eq(version)),
+            ("!=", false) => all.push(Comparator::Ne(version)),
+            ("<", false) => all.push(Comparator::Lt(version)),
+            ("<=", false) => all.push(Comparator::Le(version)),
+            (">", false) => all.push(Comparator::Gt(version)),
+            (">=", false) => all.push(Comparator::Ge(version)),
+            ("~=", false) => {
+                if version.len() < 2 {
+                    return None;
+                }
+                let upper = bump(&version[..version.len() - 1]);
+                all.push(Comparator::Ge(version));
+                all.push(Comparator::Lt(upper));
+            }
+            ("^", false) => all.extend(caret(version)),
+            ("~", false) => all.extend(tilde(version)),
+            _ => return None,
+        }
+    }
+    Some(vec![all])
+}
+
+/// Parse an npm semver range such as `>=18 <21`, `^20.9.0 || >=22` or `18.x`
+fn parse_npm_range(range: &str) -> Option<Vec<Vec<Comparator>>> {
+    let mut alternatives = Vec::new();
+    for alternative in range.split("||") {
+        let alternative = alternative.trim();
+        if let Some((low, high)) = alternative.split_once(" - ") {
+            let low = parse_partial(low)?;
+            let high = parse_partial(high)?;
+            let upper = if high.len() < 3 && !high.is_empty() {
+                Comparator::Lt(bump(&high))
+            } else {
+                Comparator::Le(high)
+            };
+            alternatives.push(vec![Comparator::Ge(low), upper]);
+            continue;
+        }
+
+        // Operators may be separated from their version by spaces (`>= 18`)
+        let mut tokens = Vec::new();
+        let mut pending = String::new();
+        for token in alternative.split_whitespace() {
+            pending.push_str(token);
+            if !token.chars().all(|c| "<>=~^".contains(c)) {
+                tokens.push(std::mem::take(&mut pending));
+            }
+        }
+
+        let mut all = Vec::new();
+        for token in tokens {
+            let (op, value) = split_operator(&token, &[">=", "<=", ">", "<", "=", "^", "~"]);
+            let version = parse_partial(value)?;
+            let partial = version.len() < 3;
+            if version.is_empty() {
+                // `*`, `x`, `>=*`: any version, except `<*` and `>*`, which match none
+                if matches!(op, "<" | ">") {
+                    all.push(Comparator::Lt(vec![0]));
+                }
+                continue;
+            }
+            match op {
+                "^" => all.extend(caret(version)),
+                "~" => all.extend(tilde(version)),
+                ">=" => all.push(Comparator::Ge(version)),
+                "<" => all.push(Comparator::Lt(version)),
+                ">" if partial => all.push(Comparator::Ge(bump(&version))),
+                ">" => all.push(Comparator::Gt(version)),
+                "<=" if partial => all.push(Comparator::Lt(bump(&version))),
+                "<=" => all.push(Comparator::Le(version)),
+                _ if partial => {
+                    let upper = bump(&version);
+                    all.push(Comparator::Ge(version));
+                    all.push(Comparator::Lt(upper));
+                }
+                _ => all.push(Comparator::Eq(version)),
+            }
+        }
+        alternatives.push(all);
+    }
+    Some(alternatives)
+}
+
+/// Split the longest matching operator off the front of a clause
+fn split_operator<'a>(clause: &'a str, operators: &[&'static str]) -> (&'static str, &'a str) {
+    let clause = clause.trim();
+    operators
+        .iter()
+        .find_map(|op| clause.strip_prefix(op).map(|rest| (*op, rest.trim())))
+        .unwrap_or(("", clause))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn python(spec: &str, version: &[u64]) -> bool {
+        satisfies(&parse_python_spec(spec).unwrap(), version)
+    }
+
+    fn npm(range: &str, version: &[u64]) -> bool {
+        satisfies(&parse_npm_range(range).unwrap(), version)
+    }
+
+    #[test]
+    fn test_python_specifiers() {
+        assert!(python(">=3.9", &[3, 12, 1]));
+        assert!(!python(">=3.10,<3.12", &[3, 12, 0]));
+        assert!(python(">=3.10, <3.12", &[3, 11, 9]));
+        assert!(python("~=3.10", &[3, 13, 0]));
+        assert!(!python("~=3.10.2", &[3, 11, 0]));
+        assert!(python("==3.11.*", &[3, 11, 4]));
+        assert!(!python("==3.11", &[3, 11, 4]));
+        assert!(!python("!=3.11.*,>=3.10", &[3, 11, 0]));
+        assert!(python("^3.10", &[3, 12, 0]));
+        assert!(!python("^3.10", &[4, 0, 0]));
+        assert!(parse_python_spec(">=3.8rc1").is_none());
+
+        let spec = parse_python_spec("<4,>=3.10").unwrap();
+        assert_eq!(lower_bound(&spec), Some(vec![3, 10]));
+    }
+
+    #[test]
+    fn test_npm_ranges() {
+        assert!(npm(">=18", &[20, 11, 0]));
+        assert!(npm(">= 18 < 21", &[20, 11, 0]));
+        assert!(!npm(">=18 <21", &[21, 0, 0]));
+        assert!(npm("^18.17.0 || >=20.5.0", &[20, 9, 0]));
+        assert!(!npm("^18.17.0 || >=20.5.0", &[19, 0, 0]));
+        assert!(npm("18.x", &[18, 2, 0]));
+        assert!(!npm("18", &[19, 0, 0]));
+        assert!(npm("~18.2", &[18, 2, 7]));
+        assert!(!npm("~18.2", &[18, 3, 0]));
+        assert!(npm("16 - 18", &[18, 9, 9]));
+        assert!(!npm(">16", &[16, 5, 0]));
+        assert!(npm("<=20", &[20, 11, 0]));
+        assert!(npm("*", &[4, 0, 0]));
+        assert!(npm("^0.2.3", &[0, 2, 9]));
+        assert!(!npm("^0.2.3", &[0, 3, 0]));
+
+        let range = parse_npm_range("^20.9.0 || >=22").unwrap();
+        assert_eq!(lower_bound(&range), Some(vec![20, 9, 0]));
+    }
+
+    #[test]
+    fn test_venv_version() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(venv_version(dir.path()).is_err());
+        fs::write(
+            dir.path().join("pyvenv.cfg"),
+            "home = /usr/bin\nimplementation = CPython\nversion_info = 3.12.1.final.0\n",
+        )
+        .unwrap();
+        assert_eq!(venv_version(dir.path()).unwrap(), vec![3, 12, 1]);
+    }
+
+    #[test]
+    fn test_toolchains() {
+        let file = parse_toolchain_file(
+            "rust-toolchain.toml",
+            "[toolchain]\nchannel = \"1.78.0\"\ncomponents = [\"clippy\", \"rustfmt\"]\ntargets = [\"wasm32-unknown-unknown\"]\n",
+        )
+        .unwrap();
+        assert_eq!(file.channel.as_deref(), Some("1.78.0"));
+        assert_eq!(file.components, ["clippy", "rustfmt"]);
+        assert_eq!(file.targets, ["wasm32-unknown-unknown"]);
+
+        let legacy = parse_toolchain_file("rust-toolchain", "nightly-2024-05-01\n").unwrap();
+        assert_eq!(legacy.channel.as_deref(), Some("nightly-2024-05-01"));
+
+        let installed = parse_toolchain_list(
+            "stable-x86_64-unknown-linux-gnu (default)\nnightly-2024-05-01-x86_64-unknown-linux-gnu\n1.78.0-x86_64-unknown-linux-gnu (active)\n",
+        );
+        let has = |channel: &str| installed.iter().any(|t| toolchain_matches(t, channel));
+        assert!(has("stable"));
+        assert!(has("1.78.0"));
+        assert!(has("nightly-2024-05-01"));
+        assert!(!has("nightly"));
+        assert!(!has("1.78"));
+        assert!(toolchain_matches(
+            "clippy-x86_64-unknown-linux-gnu",
+            "clippy"
+        ));
+        assert!(toolchain_matches("rust-src", "rust-src"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tool_checks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let fake = |bin: &str, version: &str| {
+            let bin_dir = dir.path().join(bin);
+            fs::create_dir_all(&bin_dir).unwrap();
+            let path = bin_dir.join("fake-git");
+            fs::write(
+                &path,
+                format!("#!/bin/sh\necho 'git version {}'\n", version),
+            )
+            .unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let old = fake("old", "2.30.1");
+        let new = fake("new", "2.43.0");
+        let search_path =
+            std::env::join_paths([old.parent().unwrap(), new.parent().unwrap()]).unwrap();
+        let pin = |path: Option<&Path>, min: Option<&str>| ToolPin {
+            path: path.map(Path::to_path_buf),
+            min_version: min.map(str::to_string),
+            ..Default::default()
+        };
+
+        let check = check_tool("fake-git", None, Some(&search_path));
+        assert_eq!(check.status, Status::Warn);
+        assert!(check.detail.contains("shadows 2.43.0"), "{}", check.detail);
+        assert_eq!(
+            check.fix.as_deref(),
+            Some(
+                format!(
+                    "safe-ai-util config set tools.fake-git.path {}",
+                    new.display()
+                )
+                .as_str()
+            )
+        );
+
+        let check = check_tool(
+            "fake-git",
+            Some(&pin(None, Some("2.40"))),
+            Some(&search_path),
+        );
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.fix.unwrap().ends_with(&new.display().to_string()));
+
+        let check = check_tool(
+            "fake-git",
+            Some(&pin(Some(&new), Some("2.40"))),
+            Some(&search_path),
+        );
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.detail.contains("resolves to"), "{}", check.detail);
+        let new_first =
+            std::env::join_paths([new.parent().unwrap(), old.parent().unwrap()]).unwrap();
+        let check = check_tool(
+            "fake-git",
+            Some(&pin(Some(&new), Some("2.40"))),
+            Some(&new_first),
+        );
+        assert_eq!(check.status, Status::Ok, "{}", check.detail);
+
+        let check = check_tool(
+            "fake-git",
+            Some(&pin(None, Some("2.40"))),
+            Some(OsStr::new("")),
+        );
+        assert_eq!(check.status, Status::Fail);
+        assert_eq!(
+            check.fix.as_deref(),
+            Some("safe-ai-util tools install fake-git@2.40")
+        );
+        let check = check_tool("fake-git", None, Some(OsStr::new("")));
+        assert_eq!(check.status, Status::Warn);
+    }
+}
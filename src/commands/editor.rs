@@ -1,8 +1,10 @@
 // file: src/commands/editor.rs
-// version: 1.0.0
+// version: 1.2.0
 // guid: 0c1d2e3f-4a5b-6c7d-8e9f-0a1b2c3d4e5f
 
 use crate::executor::Executor;
+use crate::security::content::ContentPolicy;
+use crate::security::sandbox::Sandbox;
 use anyhow::Result;
 use clap::{Arg, ArgMatches, Command};
 use crossterm::{
@@ -50,8 +52,9 @@ pub fn build_command() -> Command {
 }
 
 /// Execute the custom Rust editor
-pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
     let file_path = matches.get_one::<String>("file").unwrap();
+    executor.check_path(file_path)?;
     let start_line = matches.get_one::<String>("line")
         .and_then(|s| s.parse::<usize>().ok())
         .unwrap_or(1);
@@ -62,7 +65,13 @@ pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
     let syntax_lang = matches.get_one::<String>("syntax");
 
     // Initialize the editor
-    let mut editor = RustEditor::new(file_path, readonly, syntax_lang)?;
+    let mut editor = RustEditor::new(
+        file_path,
+        readonly,
+        syntax_lang,
+        executor.sandbox().clone(),
+        executor.content_policy().clone(),
+    )?;
     editor.set_cursor_position(start_line.saturating_sub(1), start_column.saturating_sub(1));
 
     // Run the editor
@@ -82,6 +91,10 @@ struct RustEditor {
     syntax_lang: Option<String>,
     search_query: Option<String>,
     clipboard: String,
+    /// Checked before `:w <path>` writes to a new file
+    sandbox: Sandbox,
+    /// Checked before every save
+    content_policy: ContentPolicy,
 }
 
 #[derive(Debug, PartialEq)]
@@ -94,7 +107,13 @@ enum EditorMode {
 }
 
 impl RustEditor {
-    fn new(file_path: &str, readonly: bool, syntax_lang: Option<&String>) -> Result<Self> {
+    fn new(
+        file_path: &str,
+        readonly: bool,
+        syntax_lang: Option<&String>,
+        sandbox: Sandbox,
+        content_policy: ContentPolicy,
+    ) -> Result<Self> {
         let content = if Path::new(file_path).exists() {
             fs::read_to_string(file_path)?
                 .lines()
@@ -117,6 +136,8 @@ impl RustEditor {
             syntax_lang: syntax_lang.cloned(),
             search_query: None,
             clipboard: String::new(),
+            sandbox,
+            content_policy,
         })
     }
 
@@ -499,10 +520,8 @@ impl RustEditor {
             KeyCode::Char(c) => {
                 self.status_message.push(c);
             }
-            KeyCode::Backspace => {
-                if self.status_message.len() > 1 {
-                    self.status_message.pop();
-                }
+            KeyCode::Backspace if self.status_message.len() > 1 => {
+                self.status_message.pop();
             }
             _ => {}
         }
@@ -526,10 +545,8 @@ impl RustEditor {
             KeyCode::Char(c) => {
                 self.status_message.push(c);
             }
-            KeyCode::Backspace => {
-                if self.status_message.len() > 1 {
-                    self.status_message.pop();
-                }
+            KeyCode::Backspace if self.status_message.len() > 1 => {
+                self.status_message.pop();
             }
             _ => {}
         }
@@ -822,6 +839,10 @@ impl RustEditor {
         }
 
         let content = self.content.join("\n");
+        if let Err(e) = self.content_policy.check(Path::new(&self.file_path), content.as_bytes()) {
+            self.status_message = e.to_string();
+            return Ok(());
+        }
         fs::write(&self.file_path, content)?;
         self.modified = false;
         self.status_message = format!("Saved: {}", self.file_path);
@@ -833,8 +854,16 @@ impl RustEditor {
             self.status_message = "File is read-only!".to_string();
             return Ok(());
         }
+        if let Err(e) = self.sandbox.check_path(Path::new(path)) {
+            self.status_message = e.to_string();
+            return Ok(());
+        }
 
         let content = self.content.join("\n");
+        if let Err(e) = self.content_policy.check(Path::new(path), content.as_bytes()) {
+            self.status_message = e.to_string();
+            return Ok(());
+        }
         fs::write(path, content)?;
         self.file_path = path.to_string();
         self.modified = false;
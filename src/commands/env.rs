@@ -0,0 +1,239 @@
+// file: src/commands/env.rs
+// version: 1.0.0
+// guid: 7d3c9a51-2e8f-4b06-9c4d-a15e7f2b6038
+
+//! `env` command: carry an agent setup from one machine to another
+//!
+//! `export-profile` writes a workspace profile bundle (see [`crate::env_profile`]);
+//! `import-profile` applies one, writing the configuration file and task manifests it holds
+//! and reporting the tools and environment variables the machine still lacks. Files are
+//! written like `file write` writes them: inside the workspace roots and past the content
+//! policy.
+
+use crate::commands::config::{target_path, with_target};
+use crate::config::validate_toml;
+use crate::env_profile::{self, Bundle, ToolMatch, DEFAULT_FILE, DEFAULT_TASK_FILES};
+use crate::executor::Executor;
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Build the env command
+pub fn build_command() -> Command {
+    Command::new("env")
+        .about("Export an agent setup as a workspace profile and import it elsewhere")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("export-profile")
+                .about("Bundle settings, policy, tool pins, task manifests and required variables")
+                .long_about(
+                    "Write a workspace profile: the settings that differ from the defaults \
+                     (policy and tool pins included), the versions of pinned and managed tools, \
+                     task manifests and the names of the environment variables the setup needs. \
+                     Machine-specific settings and variable values are left out.",
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .default_value(DEFAULT_FILE)
+                        .help("File to write, or - for stdout"),
+                )
+                .arg(
+                    Arg::new("tasks")
+                        .long("tasks")
+                        .value_name("FILE")
+                        .action(ArgAction::Append)
+                        .help("Task manifest to include (default: tasks.yaml, tasks.yml or tasks.toml if present)"),
+                )
+                .arg(
+                    Arg::new("require-env")
+                        .long("require-env")
+                        .value_name("NAME")
+                        .action(ArgAction::Append)
+                        .help("Environment variable the setup needs, e.g. GITHUB_TOKEN"),
+                ),
+        )
+        .subcommand(
+            with_target(
+                Command::new("import-profile")
+                    .about("Apply a workspace profile: write its configuration and task manifests")
+                    .long_about(
+                        "Apply a workspace profile exported with `env export-profile`. The \
+                         configuration goes to the project file (.safe-ai-util.toml), or the \
+                         file chosen with --user or --file, and task manifests to their paths \
+                         in the current directory. Tools missing or at another version and \
+                         unset environment variables are reported, with the commands that \
+                         install managed tools.",
+                    ),
+            )
+            .arg(
+                Arg::new("profile-file")
+                    .value_name("FILE")
+                    .required(true)
+                    .help("Workspace profile to apply"),
+            )
+            .arg(
+                Arg::new("force")
+                    .long("force")
+                    .action(ArgAction::SetTrue)
+                    .help("Replace existing files whose content differs"),
+            ),
+        )
+}
+
+/// Execute the env command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("export-profile", sub_matches)) => export_profile(sub_matches, executor),
+        Some(("import-profile", sub_matches)) => import_profile(sub_matches, executor),
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+fn export_profile(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let tasks: Vec<PathBuf> = match matches.get_many::<String>("tasks") {
+        Some(files) => files.map(PathBuf::from).collect(),
+        None => DEFAULT_TASK_FILES
+            .iter()
+            .map(PathBuf::from)
+            .filter(|path| path.is_file())
+            .collect(),
+    };
+    let require_env: Vec<String> = matches
+        .get_many::<String>("require-env")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let bundle = Bundle::capture(executor.config(), &tasks, &require_env)?;
+    let content = serde_json::to_string_pretty(&bundle)? + "\n";
+
+    let output = matches.get_one::<String>("output").unwrap();
+    if output == "-" {
+        print!("{}", content);
+        return Ok(());
+    }
+    let path = Path::new(output);
+    executor.check_path(path)?;
+    executor.check_content(path, content.as_bytes())?;
+    if executor.config().safety.dry_run {
+        println!(
+            "DRY RUN: Would write the workspace profile to {}",
+            path.display()
+        );
+        return Ok(());
+    }
+    fs::write(path, &content)?;
+
+    println!(
+        "Exported {} tool(s), {} task manifest(s) and {} required variable(s) to {}",
+        bundle.tools.len(),
+        bundle.tasks.len(),
+        bundle.env.len(),
+        path.display()
+    );
+    let (_, dropped) = env_profile::settings(executor.config())?;
+    if !dropped.is_empty() {
+        println!("Left out machine-specific settings: {}", dropped.join(", "));
+    }
+    Ok(())
+}
+
+fn import_profile(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let bundle = Bundle::load(Path::new(
+        matches.get_one::<String>("profile-file").unwrap(),
+    ))?;
+    let problems = validate_toml(&bundle.config);
+    if !problems.is_empty() {
+        bail!(
+            "The profile's configuration is invalid: {}",
+            problems.join("; ")
+        );
+    }
+
+    let config_content = format!(
+        "# Imported from a workspace profile exported on {} by safe-ai-util {}\n{}",
+        bundle.created_at.format("%Y-%m-%d"),
+        bundle.created_with,
+        bundle.config
+    );
+    let mut files = vec![(target_path(matches, false)?, config_content)];
+    files.extend(
+        bundle
+            .tasks
+            .iter()
+            .map(|task| (task.path.clone(), task.content.clone())),
+    );
+
+    // Every file is checked before any is written, so a conflict leaves nothing half applied
+    let mut writes = Vec::new();
+    let mut conflicts = Vec::new();
+    for (path, content) in files {
+        executor.check_path(&path)?;
+        executor.check_content(&path, content.as_bytes())?;
+        match fs::read_to_string(&path) {
+            Ok(existing) if existing == content => {
+                println!("Unchanged: {}", path.display());
+            }
+            Ok(_) if !matches.get_flag("force") => conflicts.push(path),
+            _ => writes.push((path, content)),
+        }
+    }
+    if !conflicts.is_empty() {
+        bail!(
+            "{} already exist(s) with other content; use --force to replace",
+            conflicts
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    for (path, content) in writes {
+        if executor.config().safety.dry_run {
+            println!("DRY RUN: Would write {}", path.display());
+            continue;
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        println!("Wrote {}", path.display());
+    }
+
+    for (name, record) in &bundle.tools {
+        let expected = record.version.as_deref().unwrap_or("any version");
+        let install = record
+            .managed
+            .as_ref()
+            .map(|release| format!("; install it with `{}`", release.install_command(name)))
+            .unwrap_or_default();
+        match env_profile::match_tool(name, record) {
+            ToolMatch::Matches => println!("Tool {}: ok ({})", name, expected),
+            ToolMatch::Missing => println!(
+                "Tool {}: missing, profile has {}{}",
+                name, expected, install
+            ),
+            ToolMatch::Differs { found } => println!(
+                "Tool {}: version {} installed, profile has {}{}",
+                name, found, expected, install
+            ),
+        }
+    }
+    let unset: Vec<&str> = bundle
+        .env
+        .iter()
+        .filter(|name| std::env::var_os(name).is_none())
+        .map(String::as_str)
+        .collect();
+    if !unset.is_empty() {
+        println!(
+            "Set these environment variables before running: {}",
+            unset.join(", ")
+        );
+    }
+    Ok(())
+}
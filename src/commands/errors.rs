@@ -0,0 +1,97 @@
+// file: src/commands/errors.rs
+// version: 1.0.1
+// guid: 8e2f4a6c-1d7b-4c39-95e0-b3a6f18d2c74
+
+//! `errors` command: look up the stable `SAU-xxxx` codes failures are reported with
+
+use super::export::Effect;
+use crate::error_codes::{self, ErrorCode, CATALOG};
+use anyhow::{bail, Result};
+use clap::{Arg, ArgMatches, Command};
+
+/// Build the errors command
+pub fn build_command() -> Command {
+    Command::new("errors")
+        .add(Effect::ReadOnly)
+        .about("Explain the SAU-xxxx codes failures are reported with")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("explain")
+                .about("Print the description, common causes and remedies of an error code")
+                .arg(
+                    Arg::new("code")
+                        .value_name("CODE")
+                        .required(true)
+                        .help("Error code, e.g. SAU-3004 or 3004"),
+                )
+                .arg(format_arg("text")),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List every error code")
+                .arg(format_arg("table")),
+        )
+}
+
+fn format_arg(default: &'static str) -> Arg {
+    Arg::new("format")
+        .long("format")
+        .short('f')
+        .value_parser([default, "json"])
+        .default_value(default)
+        .help("Output format")
+}
+
+/// Execute the errors command
+pub async fn execute(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("explain", sub_matches)) => {
+            let code = sub_matches.get_one::<String>("code").unwrap();
+            let Some(entry) = error_codes::lookup(code) else {
+                bail!(
+                    "Unknown error code '{}'; `errors list` shows them all",
+                    code
+                );
+            };
+            if json(sub_matches) {
+                println!("{}", serde_json::to_string_pretty(entry)?);
+            } else {
+                print_entry(entry);
+            }
+            Ok(())
+        }
+        Some(("list", sub_matches)) => {
+            if json(sub_matches) {
+                println!("{}", serde_json::to_string_pretty(CATALOG)?);
+                return Ok(());
+            }
+            println!("{:<9} TITLE", "CODE");
+            for entry in CATALOG {
+                println!("{:<9} {}", entry.code, entry.title);
+            }
+            Ok(())
+        }
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+fn json(matches: &ArgMatches) -> bool {
+    matches.get_one::<String>("format").map(String::as_str) == Some("json")
+}
+
+fn print_entry(entry: &ErrorCode) {
+    println!("{}: {}", entry.code, entry.title);
+    println!();
+    println!("{}", entry.description);
+    println!();
+    println!("Common causes:");
+    for cause in entry.causes {
+        println!("  - {}", cause);
+    }
+    println!();
+    println!("Remedies:");
+    for remedy in entry.remedies {
+        println!("  - {}", remedy);
+    }
+}
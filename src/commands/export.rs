@@ -0,0 +1,569 @@
+// file: src/commands/export.rs
+// version: 1.1.0
+// guid: 2d146e63-c82f-43b8-bcca-095c059b70bd
+
+//! `export` command: describe the utility to other programs
+//!
+//! `export tool-spec` turns the command registry into tool definitions for LLM
+//! applications. Every runnable command becomes one tool, named after its command path
+//! (`git status` -> `git_status`), with a JSON Schema built from its arguments:
+//!
+//! - property names are the long option names, or the argument ids of positionals
+//! - flags are booleans, counted flags integers, numeric values numbers, choices enums and
+//!   repeatable arguments arrays
+//!
+//! A tool call maps back to a command line by passing each property as `--name value`
+//! (flags only when true, arrays repeated) and positionals in the order the schema lists
+//! them. Descriptions carry a safety note taken from the [`Effect`] and [`Remote`] marks
+//! commands are defined with, and the MCP format adds the matching annotations.
+
+use crate::executor::Executor;
+use anyhow::{anyhow, Result};
+use clap::builder::CommandExt;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::{json, Map, Value};
+use std::any::TypeId;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+/// Commands that are useless as tools: interactive, never finishing, without operations, run
+/// by git itself, or approving what an agent asked for
+const EXCLUDED: &[&str] = &[
+    "binaries approve",
+    "jvm approve-wrapper",
+    "completions",
+    "editor",
+    "env import-profile",
+    "export",
+    "repl",
+    "serve",
+    "setup",
+    "shell",
+    "stats serve",
+    "system",
+    "toolchain install",
+    "toolchain remove",
+    "tools dismiss",
+    "tools install",
+    "uutils yes",
+    "git merge-driver cargo-lock",
+    "git merge-driver package-lock",
+    "git merge-driver generated",
+    "buf registry login",
+];
+
+/// What running a command does to the system, for the safety note and MCP annotations of
+/// its tool
+///
+/// Attach it where the command is defined with `Command::add`; subcommands inherit their
+/// parent's effect unless they set their own, and commands without one are [`Effect::Writes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Only reads state
+    ReadOnly,
+    /// May create or modify files in the workspace
+    Writes,
+    /// Can discard or overwrite data
+    Destructive,
+}
+
+impl CommandExt for Effect {}
+
+/// Marks a command, and its subcommands, as talking to remote services
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Remote;
+
+impl CommandExt for Remote {}
+
+/// A command exported as a tool
+#[derive(Debug, Clone)]
+struct Tool {
+    /// Command path, e.g. `git stack sync`
+    path: String,
+    name: String,
+    description: String,
+    schema: Value,
+    effect: Effect,
+    remote: bool,
+}
+
+impl Tool {
+    fn read_only(&self) -> bool {
+        self.effect == Effect::ReadOnly
+    }
+
+    fn destructive(&self) -> bool {
+        self.effect == Effect::Destructive
+    }
+
+    fn remote(&self) -> bool {
+        self.remote
+    }
+}
+
+/// Whether a command path is listed, either itself or through one of its parents
+fn matches_path(list: &[&str], path: &str) -> bool {
+    list.iter().any(|entry| {
+        path == *entry
+            || path
+                .strip_prefix(entry)
+                .is_some_and(|rest| rest.starts_with(' '))
+    })
+}
+
+/// Build the export command
+pub fn build_command() -> Command {
+    Command::new("export")
+        .about("Export machine-readable descriptions of the utility")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("tool-spec")
+                .about("Emit tool definitions (names, argument JSON Schemas, descriptions, safety notes) for LLM applications")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["openai", "anthropic", "mcp"])
+                        .default_value("openai")
+                        .help("Tool definition format"),
+                )
+                .arg(
+                    Arg::new("only")
+                        .long("only")
+                        .value_name("COMMAND")
+                        .value_delimiter(',')
+                        .action(ArgAction::Append)
+                        .help("Only export these commands and their subcommands, e.g. git,file"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Write the definitions to a file instead of stdout"),
+                ),
+        )
+}
+
+/// Execute the export command; `cli` is the full command registry to describe
+pub async fn execute(matches: &ArgMatches, executor: &Executor, cli: &Command) -> Result<()> {
+    match matches.subcommand() {
+        Some(("tool-spec", sub_matches)) => execute_tool_spec(sub_matches, executor, cli),
+        _ => Err(anyhow!("Unknown export subcommand")),
+    }
+}
+
+fn execute_tool_spec(matches: &ArgMatches, executor: &Executor, cli: &Command) -> Result<()> {
+    let mut tools = collect_tools(cli);
+    if let Some(only) = matches.get_many::<String>("only") {
+        let only: Vec<&str> = only.map(String::as_str).collect();
+        tools.retain(|tool| matches_path(&only, &tool.path));
+        if tools.is_empty() {
+            return Err(anyhow!("No commands match --only {}", only.join(",")));
+        }
+    }
+
+    let spec = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("anthropic") => anthropic_spec(&tools),
+        Some("mcp") => mcp_spec(&tools),
+        _ => openai_spec(&tools),
+    };
+    let text = serde_json::to_string_pretty(&spec)?;
+
+    match matches.get_one::<String>("output") {
+        Some(output) => {
+            let output = executor.check_path(Path::new(output))?;
+            if executor.config().safety.dry_run {
+                println!(
+                    "DRY RUN: Would write {} tool definitions to {}",
+                    tools.len(),
+                    output.display()
+                );
+                return Ok(());
+            }
+            fs::write(&output, text + "\n")?;
+            info!(
+                "Wrote {} tool definitions to {}",
+                tools.len(),
+                output.display()
+            );
+        }
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+/// Walk the command tree and describe every runnable command
+///
+/// A command with subcommands is runnable on its own when it has arguments of its own and
+/// does not require a subcommand.
+fn collect_tools(cli: &Command) -> Vec<Tool> {
+    let mut tools = Vec::new();
+    for command in cli.get_subcommands() {
+        let path = command.get_name().to_string();
+        collect(command, path, Effect::Writes, false, &mut tools);
+    }
+    tools
+}
+
+/// Describe `command` and its subcommands, which inherit its [`Effect`] and [`Remote`] mark
+fn collect(command: &Command, path: String, effect: Effect, remote: bool, tools: &mut Vec<Tool>) {
+    if matches_path(EXCLUDED, &path) || command.is_hide_set() {
+        return;
+    }
+    let effect = command.get::<Effect>().copied().unwrap_or(effect);
+    let remote = remote || command.get::<Remote>().is_some();
+    let has_subcommands = command.has_subcommands();
+    let own_args = command.get_arguments().any(exported);
+    if !has_subcommands || (own_args && !command.is_subcommand_required_set()) {
+        tools.push(describe(command, &path, effect, remote));
+    }
+    for sub in command.get_subcommands() {
+        if sub.get_name() != "help" {
+            let path = format!("{} {}", path, sub.get_name());
+            collect(sub, path, effect, remote, tools);
+        }
+    }
+}
+
+/// Arguments that belong in a schema: not help/version and not hidden
+fn exported(arg: &Arg) -> bool {
+    !arg.is_hide_set()
+        && !matches!(
+            arg.get_action(),
+            ArgAction::Help | ArgAction::HelpShort | ArgAction::HelpLong | ArgAction::Version
+        )
+}
+
+fn describe(command: &Command, path: &str, effect: Effect, remote: bool) -> Tool {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for arg in command.get_arguments().filter(|arg| exported(arg)) {
+        let name = property_name(arg);
+        if arg.is_required_set() {
+            required.push(Value::String(name.clone()));
+        }
+        properties.insert(name, arg_schema(arg));
+    }
+
+    let mut schema = json!({"type": "object", "properties": properties});
+    if !required.is_empty() {
+        schema["required"] = Value::Array(required);
+    }
+
+    let mut tool = Tool {
+        path: path.to_string(),
+        name: path.replace([' ', '-'], "_"),
+        description: String::new(),
+        schema,
+        effect,
+        remote,
+    };
+    let about = command
+        .get_about()
+        .or_else(|| command.get_long_about())
+        .map(|about| about.to_string())
+        .unwrap_or_else(|| format!("Run {}", path));
+    tool.description = format!(
+        "{}. Runs `safe-ai-util {}`. {}",
+        about.trim_end_matches('.'),
+        path,
+        safety_note(&tool)
+    );
+    tool
+}
+
+fn safety_note(tool: &Tool) -> String {
+    let effect = if tool.read_only() {
+        "Read-only."
+    } else if tool.destructive() {
+        "Can overwrite or delete data; confirm with the user before calling."
+    } else {
+        "May create or modify files in the workspace."
+    };
+    let remote = if tool.remote() {
+        " Contacts remote services."
+    } else {
+        ""
+    };
+    format!(
+        "{}{} Arguments are validated against the command allowlist, policy rules and workspace roots before anything runs.",
+        effect, remote
+    )
+}
+
+/// The property a tool call uses for an argument: its long name, or its id
+fn property_name(arg: &Arg) -> String {
+    arg.get_long()
+        .map(str::to_string)
+        .unwrap_or_else(|| arg.get_id().to_string())
+}
+
+/// JSON Schema for the values of one argument
+fn arg_schema(arg: &Arg) -> Value {
+    let mut schema = match arg.get_action() {
+        ArgAction::SetTrue | ArgAction::SetFalse => json!({"type": "boolean"}),
+        ArgAction::Count => json!({"type": "integer", "minimum": 0}),
+        action => {
+            let possible: Vec<String> = arg
+                .get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect();
+            let item = if !possible.is_empty() {
+                json!({"type": "string", "enum": possible})
+            } else {
+                json!({"type": value_type(arg)})
+            };
+            let multiple = matches!(action, ArgAction::Append)
+                || arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+            if multiple {
+                json!({"type": "array", "items": item})
+            } else {
+                item
+            }
+        }
+    };
+
+    let mut description = arg
+        .get_help()
+        .or_else(|| arg.get_long_help())
+        .map(|help| help.to_string())
+        .unwrap_or_default();
+    if arg.is_positional() {
+        if !description.is_empty() {
+            description.push(' ');
+        }
+        description.push_str("(positional)");
+    }
+    if !description.is_empty() {
+        schema["description"] = Value::String(description);
+    }
+
+    let defaults = arg.get_default_values();
+    if defaults.len() == 1 && schema["type"] != "array" && schema["type"] != "boolean" {
+        let raw = defaults[0].to_string_lossy().into_owned();
+        schema["default"] = match schema["type"].as_str() {
+            Some("integer") => raw
+                .parse::<i64>()
+                .map(Value::from)
+                .unwrap_or(Value::String(raw)),
+            Some("number") => raw
+                .parse::<f64>()
+                .map(Value::from)
+                .unwrap_or(Value::String(raw)),
+            _ => Value::String(raw),
+        };
+    }
+    schema
+}
+
+/// The JSON Schema type of an argument's parsed value
+fn value_type(arg: &Arg) -> &'static str {
+    let id = arg.get_value_parser().type_id();
+    let integers = [
+        TypeId::of::<u8>(),
+        TypeId::of::<u16>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<usize>(),
+        TypeId::of::<i8>(),
+        TypeId::of::<i16>(),
+        TypeId::of::<i32>(),
+        TypeId::of::<i64>(),
+        TypeId::of::<isize>(),
+    ];
+    if integers.iter().any(|t| id == *t) {
+        "integer"
+    } else if id == TypeId::of::<f32>() || id == TypeId::of::<f64>() {
+        "number"
+    } else if id == TypeId::of::<bool>() {
+        "boolean"
+    } else {
+        "string"
+    }
+}
+
+fn openai_spec(tools: &[Tool]) -> Value {
+    Value::Array(
+        tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.schema,
+                    }
+                })
+            })
+            .collect(),
+    )
+}
+
+fn anthropic_spec(tools: &[Tool]) -> Value {
+    Value::Array(
+        tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.schema,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// A `tools/list` result for the Model Context Protocol
+fn mcp_spec(tools: &[Tool]) -> Value {
+    let tools: Vec<Value> = tools
+        .iter()
+        .map(|tool| {
+            json!({
+                "name": tool.name,
+                "title": format!("safe-ai-util {}", tool.path),
+                "description": tool.description,
+                "inputSchema": tool.schema,
+                "annotations": {
+                    "readOnlyHint": tool.read_only(),
+                    "destructiveHint": tool.destructive(),
+                    "openWorldHint": tool.remote(),
+                },
+            })
+        })
+        .collect();
+    json!({ "tools": tools })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli() -> Command {
+        Command::new("safe-ai-util")
+            .subcommand(
+                Command::new("git")
+                    .about("Git operations")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("push")
+                            .add(Effect::Destructive)
+                            .add(Remote)
+                            .about("Update remote refs")
+                            .arg(Arg::new("remote").help("Remote name"))
+                            .arg(
+                                Arg::new("force-with-lease")
+                                    .long("force-with-lease")
+                                    .action(ArgAction::SetTrue),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("stats")
+                    .add(Effect::ReadOnly)
+                    .about("Show metrics")
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["table", "json"])
+                            .default_value("table"),
+                    )
+                    .subcommand(Command::new("serve").about("Serve metrics")),
+            )
+            .subcommand(
+                Command::new("file").subcommand(
+                    Command::new("dedupe")
+                        .arg(
+                            Arg::new("min-size")
+                                .long("min-size")
+                                .required(true)
+                                .value_parser(clap::value_parser!(u64)),
+                        )
+                        .arg(
+                            Arg::new("exclude")
+                                .long("exclude")
+                                .action(ArgAction::Append),
+                        ),
+                ),
+            )
+            .subcommand(Command::new("editor").arg(Arg::new("file")))
+    }
+
+    #[test]
+    fn test_collect_tools() {
+        let tools = collect_tools(&cli());
+        let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["git_push", "stats", "file_dedupe"]);
+
+        let push = &tools[0];
+        assert!(push.destructive() && push.remote() && !push.read_only());
+        assert!(push
+            .description
+            .starts_with("Update remote refs. Runs `safe-ai-util git push`."));
+        assert_eq!(
+            push.schema["properties"]["force-with-lease"]["type"],
+            "boolean"
+        );
+        assert_eq!(
+            push.schema["properties"]["remote"]["description"],
+            "Remote name (positional)"
+        );
+
+        let stats = &tools[1];
+        assert!(stats.read_only());
+        assert_eq!(
+            stats.schema["properties"]["format"],
+            json!({"type": "string", "enum": ["table", "json"], "default": "table"})
+        );
+
+        let dedupe = &tools[2];
+        assert_eq!(dedupe.schema["properties"]["min-size"]["type"], "integer");
+        assert_eq!(dedupe.schema["properties"]["exclude"]["type"], "array");
+        assert_eq!(dedupe.schema["required"], json!(["min-size"]));
+    }
+
+    #[test]
+    fn test_formats() {
+        let tools = collect_tools(&cli());
+        assert_eq!(openai_spec(&tools)[0]["function"]["name"], "git_push");
+        assert_eq!(anthropic_spec(&tools)[2]["input_schema"]["type"], "object");
+        let mcp = mcp_spec(&tools);
+        assert_eq!(mcp["tools"][0]["annotations"]["destructiveHint"], true);
+        assert_eq!(mcp["tools"][1]["annotations"]["readOnlyHint"], true);
+    }
+
+    #[test]
+    fn test_hints_come_from_the_command_definitions() {
+        let cli = Command::new("safe-ai-util")
+            .subcommand(crate::commands::file::build_command())
+            .subcommand(crate::commands::uutils::build_command())
+            .subcommand(crate::commands::awk::build_command())
+            .subcommand(crate::commands::db::build_command())
+            .subcommand(crate::commands::cloud::build_command())
+            .subcommand(crate::commands::http::build_command())
+            .subcommand(crate::commands::service::build_command());
+        let tools = collect_tools(&cli);
+        let tool = |name: &str| tools.iter().find(|t| t.name == name).unwrap();
+
+        for name in ["file_dedupe", "uutils_sort", "awk"] {
+            assert!(!tool(name).read_only(), "{name}");
+            assert!(tool(name).destructive(), "{name}");
+        }
+        for name in [
+            "db_query",
+            "http_post",
+            "http_put",
+            "service_stop",
+            "service_restart",
+        ] {
+            assert!(tool(name).destructive(), "{name}");
+        }
+        assert!(tool("cloud_aws").destructive() && tool("cloud_aws").remote());
+        assert!(tool("http_get").read_only() && tool("http_get").remote());
+        assert!(tool("file_diff").read_only() && !tool("file_diff").remote());
+    }
+}
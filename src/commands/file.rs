@@ -1,20 +1,38 @@
 // file: src/commands/file.rs
-// version: 1.0.0
+// version: 1.6.0
 // guid: fbdd6298-852d-4041-a846-83781ff68a50
 
+use super::{file_dedupe, file_diff, file_move, file_sync, file_tail, file_write};
 use crate::executor::Executor;
 use anyhow::Result;
 use clap::{ArgMatches, Command};
 
 /// Build the file command
 pub fn build_command() -> Command {
-    Command::new("file").about("File operations")
-    // Add subcommands here
+    Command::new("file")
+        .about("File operations")
+        .subcommand(file_move::build_rename_command())
+        .subcommand(file_move::build_move_dir_command())
+        .subcommand(file_dedupe::build_command())
+        .subcommand(file_write::build_command())
+        .subcommand(file_tail::build_command())
+        .subcommand(file_diff::build_command())
+        .subcommand(file_sync::build_command())
 }
 
 /// Execute file commands
-pub async fn execute(_matches: &ArgMatches, _executor: &Executor) -> Result<()> {
-    // Implementation will be added in later phases
-    println!("File command execution not yet implemented");
-    Ok(())
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("rename", sub_matches)) => file_move::execute_rename(sub_matches, executor).await,
+        Some(("move-dir", sub_matches)) => file_move::execute_move_dir(sub_matches, executor).await,
+        Some(("dedupe", sub_matches)) => file_dedupe::execute(sub_matches, executor).await,
+        Some(("write", sub_matches)) => file_write::execute(sub_matches, executor).await,
+        Some(("tail", sub_matches)) => file_tail::execute(sub_matches, executor).await,
+        Some(("diff", sub_matches)) => file_diff::execute(sub_matches, executor).await,
+        Some(("sync", sub_matches)) => file_sync::execute(sub_matches, executor).await,
+        _ => {
+            println!("No file subcommand specified. Use 'file --help' for usage information.");
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,521 @@
+// file: src/commands/file_dedupe.rs
+// version: 1.2.2
+// guid: 73cb65e8-7c9e-4d27-89bc-97f76d524e90
+
+//! Duplicate and near-duplicate detection for `file dedupe`
+//!
+//! Exact duplicates are found by grouping files by size and then by SHA-256. Near-duplicates
+//! are found with a 64-bit SimHash over word shingles (text) or byte shingles (binary files):
+//! files whose fingerprints differ in at most `--threshold` bits are grouped together.
+//!
+//! Exact duplicates can be replaced with hard links to the first copy or moved to a
+//! quarantine directory under the state directory; near-duplicates are only reported.
+
+use super::export::Effect;
+use super::file_move::walk_files;
+use crate::executor::Executor;
+use crate::guardrails::{self, Estimate};
+use crate::utils;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Files larger than this are skipped by the near-duplicate pass
+const MAX_SIMHASH_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Files with fewer shingles than this give unreliable fingerprints
+const MIN_SHINGLES: usize = 8;
+
+/// Identical files
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub sha256: String,
+    /// Sorted paths; the first one is kept when duplicates are resolved
+    pub files: Vec<PathBuf>,
+}
+
+/// Files with similar content
+#[derive(Debug, Clone, Serialize)]
+pub struct NearDuplicateGroup {
+    /// Lowest pairwise similarity in the group (0.0 - 1.0)
+    pub similarity: f64,
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    duplicates: Vec<DuplicateGroup>,
+    near_duplicates: Vec<NearDuplicateGroup>,
+    reclaimable_bytes: u64,
+}
+
+/// Build the `file dedupe` subcommand
+pub fn build_command() -> Command {
+    Command::new("dedupe")
+        .add(Effect::Destructive)
+        .about("Find duplicate and near-duplicate files")
+        .arg(
+            Arg::new("path")
+                .value_name("PATH")
+                .default_value(".")
+                .help("Directory to scan (respects .gitignore)"),
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("action")
+                .help("Only report duplicates (the default when no --action is given)"),
+        )
+        .arg(
+            Arg::new("near")
+                .long("near")
+                .action(ArgAction::SetTrue)
+                .help("Also report near-duplicates using similarity hashing"),
+        )
+        .arg(
+            Arg::new("threshold")
+                .long("threshold")
+                .value_name("BITS")
+                .value_parser(clap::value_parser!(u32).range(0..=32))
+                .default_value("6")
+                .help("Maximum differing SimHash bits (out of 64) for near-duplicates"),
+        )
+        .arg(
+            Arg::new("min-size")
+                .long("min-size")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("1")
+                .help("Ignore files smaller than this"),
+        )
+        .arg(
+            Arg::new("action")
+                .long("action")
+                .value_parser(["hardlink", "quarantine"])
+                .help("Replace exact duplicates with hard links, or move them to quarantine"),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .action(ArgAction::SetTrue)
+                .help("Do not ask for confirmation before --action or before scanning a large tree"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Report format"),
+        )
+}
+
+/// Execute `file dedupe`
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let root = PathBuf::from(matches.get_one::<String>("path").unwrap());
+    executor.check_path(&root)?;
+    let min_size = *matches.get_one::<u64>("min-size").unwrap();
+    let threshold = *matches.get_one::<u32>("threshold").unwrap();
+
+    #[cfg(unix)]
+    let mut seen_inodes = std::collections::HashSet::new();
+    let files: Vec<(PathBuf, u64)> = walk_files(&root)?
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = fs::symlink_metadata(&path).ok()?;
+            if !metadata.is_file() || metadata.len() < min_size {
+                return None;
+            }
+            // Files that are already hard links of each other are not duplicates
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                if !seen_inodes.insert((metadata.dev(), metadata.ino())) {
+                    return None;
+                }
+            }
+            Some((path, metadata.len()))
+        })
+        .collect();
+    info!("Scanning {} files for duplicates", files.len());
+    let mut estimate = Estimate::default();
+    for (_, size) in &files {
+        estimate.add(*size);
+    }
+    guardrails::check(
+        executor.config(),
+        "file dedupe",
+        &estimate,
+        matches.get_flag("yes"),
+    )?;
+
+    let duplicates = find_duplicates(&files)?;
+    let near_duplicates = if matches.get_flag("near") {
+        find_near_duplicates(&files, &duplicates, threshold)?
+    } else {
+        Vec::new()
+    };
+    let report = Report {
+        reclaimable_bytes: duplicates
+            .iter()
+            .map(|g| g.size * (g.files.len() as u64 - 1))
+            .sum(),
+        duplicates,
+        near_duplicates,
+    };
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    let Some(action) = matches.get_one::<String>("action") else {
+        return Ok(());
+    };
+    let redundant: usize = report.duplicates.iter().map(|g| g.files.len() - 1).sum();
+    if redundant == 0 {
+        return Ok(());
+    }
+    if executor.config().safety.dry_run {
+        println!("Dry run; no files were changed");
+        return Ok(());
+    }
+
+    let prompt = match action.as_str() {
+        "hardlink" => format!("Replace {} duplicate files with hard links?", redundant),
+        _ => format!("Move {} duplicate files to quarantine?", redundant),
+    };
+    if !matches.get_flag("yes")
+        && executor.config().safety.confirm_destructive
+        && !utils::confirm(&prompt)?
+    {
+        println!("Aborted; no files were changed");
+        return Ok(());
+    }
+
+    match action.as_str() {
+        "hardlink" => {
+            for group in &report.duplicates {
+                link_duplicates(group)?;
+            }
+            println!("Replaced {} files with hard links", redundant);
+        }
+        _ => {
+            let dir = quarantine_dir();
+            for group in &report.duplicates {
+                quarantine_duplicates(group, &dir)?;
+            }
+            println!("Moved {} files to {}", redundant, dir.display());
+        }
+    }
+    Ok(())
+}
+
+fn print_report(report: &Report) {
+    if report.duplicates.is_empty() {
+        println!("No exact duplicates found");
+    } else {
+        println!(
+            "Exact duplicates ({} groups, {} bytes reclaimable):",
+            report.duplicates.len(),
+            report.reclaimable_bytes
+        );
+        for group in &report.duplicates {
+            println!("  {} bytes, sha256 {}", group.size, &group.sha256[..12]);
+            for (index, file) in group.files.iter().enumerate() {
+                let marker = if index == 0 { "keep" } else { "dup " };
+                println!("    {} {}", marker, file.display());
+            }
+        }
+    }
+
+    if !report.near_duplicates.is_empty() {
+        println!("Near-duplicates ({} groups):", report.near_duplicates.len());
+        for group in &report.near_duplicates {
+            println!("  {:.0}% similar", group.similarity * 100.0);
+            for file in &group.files {
+                println!("    {}", file.display());
+            }
+        }
+    }
+}
+
+/// Group files by size, then by content hash
+fn find_duplicates(files: &[(PathBuf, u64)]) -> Result<Vec<DuplicateGroup>> {
+    let mut by_size: BTreeMap<u64, Vec<&PathBuf>> = BTreeMap::new();
+    for (path, size) in files {
+        by_size.entry(*size).or_default().push(path);
+    }
+
+    let mut groups = Vec::new();
+    for (size, paths) in by_size.into_iter().filter(|(_, p)| p.len() > 1) {
+        let mut by_hash: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for path in paths {
+            match hash_file(path) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(path.clone()),
+                Err(e) => warn!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        for (sha256, mut files) in by_hash.into_iter().filter(|(_, f)| f.len() > 1) {
+            files.sort();
+            groups.push(DuplicateGroup {
+                size,
+                sha256,
+                files,
+            });
+        }
+    }
+
+    groups.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.files.cmp(&b.files)));
+    Ok(groups)
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Cluster files whose SimHash fingerprints are within `threshold` bits
+///
+/// Only one copy of each exact-duplicate group takes part, so near-duplicate groups never
+/// repeat what the exact pass already reported.
+fn find_near_duplicates(
+    files: &[(PathBuf, u64)],
+    duplicates: &[DuplicateGroup],
+    threshold: u32,
+) -> Result<Vec<NearDuplicateGroup>> {
+    let redundant: Vec<&PathBuf> = duplicates.iter().flat_map(|g| &g.files[1..]).collect();
+    let mut fingerprints: Vec<(&PathBuf, u64)> = Vec::new();
+    for (path, size) in files {
+        if *size > MAX_SIMHASH_BYTES || redundant.contains(&path) {
+            continue;
+        }
+        match fs::read(path) {
+            Ok(content) => {
+                if let Some(hash) = simhash(&content) {
+                    fingerprints.push((path, hash));
+                }
+            }
+            Err(e) => warn!("Skipping {}: {}", path.display(), e),
+        }
+    }
+
+    // Union-find over all pairs within the threshold
+    let mut parent: Vec<usize> = (0..fingerprints.len()).collect();
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        parent[i] = root;
+        root
+    }
+    for i in 0..fingerprints.len() {
+        for j in i + 1..fingerprints.len() {
+            if (fingerprints[i].1 ^ fingerprints[j].1).count_ones() <= threshold {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[b] = a;
+            }
+        }
+    }
+
+    let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..fingerprints.len() {
+        let root = find(&mut parent, i);
+        clusters.entry(root).or_default().push(i);
+    }
+
+    let mut groups: Vec<NearDuplicateGroup> = clusters
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let max_distance = members
+                .iter()
+                .flat_map(|&i| members.iter().map(move |&j| (i, j)))
+                .map(|(i, j)| (fingerprints[i].1 ^ fingerprints[j].1).count_ones())
+                .max()
+                .unwrap_or(0);
+            let mut files: Vec<PathBuf> =
+                members.iter().map(|&i| fingerprints[i].0.clone()).collect();
+            files.sort();
+            NearDuplicateGroup {
+                similarity: 1.0 - f64::from(max_distance) / 64.0,
+                files,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.files.cmp(&b.files));
+    Ok(groups)
+}
+
+/// 64-bit SimHash of a file's content, or `None` if there is too little content
+fn simhash(content: &[u8]) -> Option<u64> {
+    let shingles: Vec<u64> = match std::str::from_utf8(content) {
+        Ok(text) => {
+            let words: Vec<&str> = text.split_whitespace().collect();
+            words
+                .windows(3)
+                .map(|w| fnv1a(w.join(" ").as_bytes()))
+                .collect()
+        }
+        Err(_) => content.windows(8).step_by(4).map(fnv1a).collect(),
+    };
+    if shingles.len() < MIN_SHINGLES {
+        return None;
+    }
+
+    let mut weights = [0i64; 64];
+    for shingle in shingles {
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if shingle & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    Some(
+        weights
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| **w > 0)
+            .fold(0u64, |hash, (bit, _)| hash | (1 << bit)),
+    )
+}
+
+/// FNV-1a, used because its output is stable across Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Replace every copy after the first with a hard link to the first
+fn link_duplicates(group: &DuplicateGroup) -> Result<()> {
+    let original = &group.files[0];
+    for duplicate in &group.files[1..] {
+        // Link under a temporary name first so the duplicate is never missing
+        let tmp = duplicate.with_file_name(format!(
+            ".{}.dedupe",
+            duplicate.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        fs::hard_link(original, &tmp).map_err(|e| {
+            anyhow!(
+                "Failed to link {} to {}: {}",
+                duplicate.display(),
+                original.display(),
+                e
+            )
+        })?;
+        if let Err(e) = fs::rename(&tmp, duplicate) {
+            let _ = fs::remove_file(&tmp);
+            return Err(anyhow!("Failed to replace {}: {}", duplicate.display(), e));
+        }
+        info!("Linked {} -> {}", duplicate.display(), original.display());
+    }
+    Ok(())
+}
+
+fn quarantine_dir() -> PathBuf {
+    utils::state_dir()
+        .join("quarantine")
+        .join(chrono::Local::now().format("%Y%m%d-%H%M%S").to_string())
+}
+
+/// Move every copy after the first into the quarantine directory, keeping relative paths
+fn quarantine_duplicates(group: &DuplicateGroup, dir: &Path) -> Result<()> {
+    for duplicate in &group.files[1..] {
+        let relative: PathBuf = duplicate
+            .components()
+            .filter(|c| matches!(c, std::path::Component::Normal(_)))
+            .collect();
+        let target = dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::rename(duplicate, &target).is_err() {
+            // Quarantine may be on another filesystem
+            fs::copy(duplicate, &target)?;
+            fs::remove_file(duplicate)?;
+        }
+        info!(
+            "Quarantined {} -> {}",
+            duplicate.display(),
+            target.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sized(dir: &TempDir, name: &str, content: &str) -> (PathBuf, u64) {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        (path, content.len() as u64)
+    }
+
+    #[test]
+    fn test_find_duplicates_groups_identical_content() {
+        let dir = TempDir::new().unwrap();
+        let files = vec![
+            sized(&dir, "b.txt", "same content"),
+            sized(&dir, "a.txt", "same content"),
+            sized(&dir, "c.txt", "other conten"),
+        ];
+
+        let groups = find_duplicates(&files).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            groups[0].files,
+            vec![files[1].0.clone(), files[0].0.clone()]
+        );
+    }
+
+    #[test]
+    fn test_simhash_similarity() {
+        let base =
+            "the quick brown fox jumps over the lazy dog while the cat sleeps on the warm mat \
+                    and the bird sings a song in the old oak tree near the quiet river bank today";
+        let edited = base.replace("today", "tonight");
+        let unrelated = "lorem ipsum dolor sit amet consectetur adipiscing elit sed do eiusmod \
+                         tempor incididunt ut labore et dolore magna aliqua ut enim ad minim veniam";
+
+        let a = simhash(base.as_bytes()).unwrap();
+        let b = simhash(edited.as_bytes()).unwrap();
+        let c = simhash(unrelated.as_bytes()).unwrap();
+        assert!((a ^ b).count_ones() < (a ^ c).count_ones());
+        assert!((a ^ b).count_ones() <= 6);
+        assert_eq!(simhash(b"too short"), None);
+    }
+
+    #[test]
+    fn test_link_duplicates() {
+        let dir = TempDir::new().unwrap();
+        let (a, _) = sized(&dir, "a.bin", "payload");
+        let (b, _) = sized(&dir, "b.bin", "payload");
+        let group = DuplicateGroup {
+            size: 7,
+            sha256: hash_file(&a).unwrap(),
+            files: vec![a.clone(), b.clone()],
+        };
+
+        link_duplicates(&group).unwrap();
+        fs::write(&a, "changed").unwrap();
+        assert_eq!(fs::read_to_string(&b).unwrap(), "changed");
+    }
+}
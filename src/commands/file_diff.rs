@@ -0,0 +1,143 @@
+// file: src/commands/file_diff.rs
+// version: 1.0.1
+// guid: e5a7c1f9-3d84-4b2e-9f06-8c1d4b7a2e93
+
+//! `file diff`: compare two files with the native diff (see [`crate::diff`])
+//!
+//! Unlike `uutils diff`, which runs the system `diff`, this offers the patience and histogram
+//! algorithms and word or character spans within changed lines, as text or JSON.
+
+use super::export::Effect;
+use crate::diff::{Algorithm, DiffOptions, Inline, TextDiff};
+use crate::executor::Executor;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+/// Build the `file diff` subcommand
+pub fn build_command() -> Command {
+    Command::new("diff")
+        .add(Effect::ReadOnly)
+        .about("Show the differences between two files")
+        .arg(
+            Arg::new("old")
+                .value_name("OLD")
+                .required(true)
+                .help("Original file"),
+        )
+        .arg(
+            Arg::new("new")
+                .value_name("NEW")
+                .required(true)
+                .help("Changed file"),
+        )
+        .arg(
+            Arg::new("algorithm")
+                .long("algorithm")
+                .short('a')
+                .value_name("ALGORITHM")
+                .value_parser(Algorithm::NAMES.to_vec())
+                .default_value("myers")
+                .help("Line diff algorithm"),
+        )
+        .arg(
+            Arg::new("inline")
+                .long("inline")
+                .value_name("GRANULARITY")
+                .value_parser(Inline::NAMES.to_vec())
+                .default_value("none")
+                .help("Also diff changed lines word by word or character by character"),
+        )
+        .arg(
+            Arg::new("context")
+                .long("context")
+                .short('U')
+                .value_name("LINES")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("3")
+                .help("Unchanged lines shown around each change"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+        .arg(
+            Arg::new("exit-code")
+                .long("exit-code")
+                .action(ArgAction::SetTrue)
+                .help("Fail when the files differ"),
+        )
+}
+
+/// Execute `file diff`
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let old_name = matches.get_one::<String>("old").unwrap();
+    let new_name = matches.get_one::<String>("new").unwrap();
+    let old = read(&executor.check_path(old_name)?)?;
+    let new = read(&executor.check_path(new_name)?)?;
+
+    let options = DiffOptions {
+        algorithm: matches
+            .get_one::<String>("algorithm")
+            .unwrap()
+            .parse()
+            .map_err(|e: String| anyhow!(e))?,
+        inline: matches
+            .get_one::<String>("inline")
+            .unwrap()
+            .parse()
+            .map_err(|e: String| anyhow!(e))?,
+        context: *matches.get_one::<usize>("context").unwrap(),
+    };
+    let diff = TextDiff::new(&old, &new, options);
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let report = json!({
+            "old": old_name,
+            "new": new_name,
+            "diff": diff,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", diff.unified(old_name, new_name));
+    }
+
+    if matches.get_flag("exit-code") && !diff.is_empty() {
+        return Err(anyhow!(
+            "{} and {} differ: {} insertions, {} deletions",
+            old_name,
+            new_name,
+            diff.insertions,
+            diff.deletions
+        ));
+    }
+    Ok(())
+}
+
+/// Print what writing `new` to `path` would change, for dry runs
+pub fn print_preview(path: &Path, new: &[u8]) {
+    let Ok(new) = std::str::from_utf8(new) else {
+        return;
+    };
+    let old = fs::read_to_string(path).unwrap_or_default();
+    let options = DiffOptions {
+        inline: Inline::Word,
+        ..DiffOptions::default()
+    };
+    let name = path.display().to_string();
+    print!(
+        "{}",
+        TextDiff::new(&old, new, options).unified(&name, &name)
+    );
+}
+
+fn read(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).map_err(|e| anyhow!("Cannot read {}: {}", path.display(), e))?;
+    String::from_utf8(bytes).map_err(|_| anyhow!("{} is not a text file", path.display()))
+}
@@ -0,0 +1,714 @@
+// file: src/commands/file_move.rs
+// version: 1.5.2
+// guid: 84d62627-1491-4889-b75a-b77ed31a934b
+
+//! Bulk rename and directory move support for `file rename` and `file move-dir`
+//!
+//! Both commands build a plan first and only touch the tree with `--apply`. Tracked files
+//! are moved with `git mv` so the index follows the rename; untracked files are renamed
+//! directly. A move onto a path another move vacates runs after that move, and plans whose
+//! moves form a cycle (such as a swap) are refused. Text files that mention an old path are
+//! reported; with `--fix-references` imports in supported languages are rewritten as well
+//! (see [`super::file_refs`]). If a move or a rewrite fails, the moves already made are undone.
+
+use super::export::Effect;
+use super::file_refs::{self, FileFix};
+use crate::executor::Executor;
+use crate::security::sanitizer;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use ignore::WalkBuilder;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tracing::{info, warn};
+
+/// Files larger than this are not scanned for references
+const MAX_SCAN_BYTES: u64 = 1024 * 1024;
+
+/// A single planned move
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Rename rules file (`--rules`)
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    #[serde(rename = "rule")]
+    rules: Vec<RuleSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleSpec {
+    pattern: String,
+    replace: String,
+}
+
+/// A compiled rename rule
+struct RenameRule {
+    pattern: Regex,
+    replace: String,
+}
+
+/// A line that mentions a path that is being moved
+#[derive(Debug)]
+struct Reference {
+    file: PathBuf,
+    line: usize,
+    needle: String,
+}
+
+/// Build the `file rename` subcommand
+pub fn build_rename_command() -> Command {
+    Command::new("rename")
+        .add(Effect::Destructive)
+        .about("Rename files matching a regex across the tree (ignore-aware)")
+        .arg(
+            Arg::new("path")
+                .value_name("PATH")
+                .help("Root of the tree to rename in")
+                .default_value("."),
+        )
+        .arg(
+            Arg::new("pattern")
+                .long("pattern")
+                .short('p')
+                .value_name("REGEX")
+                .requires("replace")
+                .conflicts_with("rules")
+                .help("Regex matched against each file name"),
+        )
+        .arg(
+            Arg::new("replace")
+                .long("replace")
+                .short('r')
+                .value_name("REPLACEMENT")
+                .help("Replacement, may use capture groups like $1 or ${name}"),
+        )
+        .arg(
+            Arg::new("rules")
+                .long("rules")
+                .value_name("FILE")
+                .help("TOML file with [[rule]] pattern/replace entries applied in order"),
+        )
+        .arg(
+            Arg::new("full-path")
+                .long("full-path")
+                .action(ArgAction::SetTrue)
+                .help("Match against the path relative to PATH instead of the file name"),
+        )
+        .arg(
+            Arg::new("apply")
+                .long("apply")
+                .action(ArgAction::SetTrue)
+                .help("Perform the renames (default is preview only)"),
+        )
+        .arg(
+            Arg::new("fix-references")
+                .long("fix-references")
+                .action(ArgAction::SetTrue)
+                .help("Rewrite Rust, Python and TypeScript imports of the moved paths"),
+        )
+}
+
+/// Build the `file move-dir` subcommand
+pub fn build_move_dir_command() -> Command {
+    Command::new("move-dir")
+        .add(Effect::Destructive)
+        .about("Move a directory, keeping the git index in sync")
+        .arg(
+            Arg::new("source")
+                .value_name("SOURCE")
+                .help("Directory to move")
+                .required(true),
+        )
+        .arg(
+            Arg::new("dest")
+                .value_name("DEST")
+                .help("New location of the directory")
+                .required(true),
+        )
+        .arg(
+            Arg::new("apply")
+                .long("apply")
+                .action(ArgAction::SetTrue)
+                .help("Perform the move (default is preview only)"),
+        )
+        .arg(
+            Arg::new("fix-references")
+                .long("fix-references")
+                .action(ArgAction::SetTrue)
+                .help("Rewrite Rust, Python and TypeScript imports of the moved paths"),
+        )
+}
+
+/// Execute `file rename`
+pub async fn execute_rename(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let root = PathBuf::from(matches.get_one::<String>("path").unwrap());
+    executor.check_path(&root)?;
+    let rules = match matches.get_one::<String>("rules") {
+        Some(rules_file) => load_rules(&executor.check_path(rules_file)?)?,
+        None => match (
+            matches.get_one::<String>("pattern"),
+            matches.get_one::<String>("replace"),
+        ) {
+            (Some(pattern), Some(replace)) => vec![RenameRule {
+                pattern: Regex::new(pattern)?,
+                replace: replace.clone(),
+            }],
+            _ => return Err(anyhow!("Specify --pattern and --replace, or --rules")),
+        },
+    };
+
+    let files = walk_files(&root)?;
+    let moves = plan_renames(&root, &files, &rules, matches.get_flag("full-path"))?;
+    if moves.is_empty() {
+        println!("No files matched");
+        return Ok(());
+    }
+
+    check_conflicts(&moves)?;
+    let moves = order_moves(moves)?;
+    run_plan(
+        &moves,
+        matches.get_flag("apply"),
+        matches.get_flag("fix-references"),
+        executor,
+    )
+    .await
+}
+
+/// Execute `file move-dir`
+pub async fn execute_move_dir(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let source = normalize(Path::new(matches.get_one::<String>("source").unwrap()));
+    let dest = normalize(Path::new(matches.get_one::<String>("dest").unwrap()));
+    executor.check_path(&source)?;
+    executor.check_path(&dest)?;
+
+    if !source.is_dir() {
+        return Err(anyhow!("Not a directory: {}", source.display()));
+    }
+    if dest.starts_with(&source) {
+        return Err(anyhow!("Cannot move {} into itself", source.display()));
+    }
+
+    let moves = vec![PlannedMove {
+        from: source,
+        to: dest,
+    }];
+    check_conflicts(&moves)?;
+    run_plan(
+        &moves,
+        matches.get_flag("apply"),
+        matches.get_flag("fix-references"),
+        executor,
+    )
+    .await
+}
+
+/// Preview the plan and its references, then apply it if requested
+async fn run_plan(
+    moves: &[PlannedMove],
+    apply: bool,
+    fix_references: bool,
+    executor: &Executor,
+) -> Result<()> {
+    println!("Planned moves ({}):", moves.len());
+    for planned in moves {
+        println!("  {} -> {}", planned.from.display(), planned.to.display());
+    }
+
+    // Planned before anything moves so invalid fixes abort the whole operation
+    let fixes = if fix_references {
+        file_refs::plan_fixes(moves)?
+    } else {
+        Vec::new()
+    };
+    if !fixes.is_empty() {
+        let count: usize = fixes.iter().map(|fix| fix.edits.len()).sum();
+        println!("Reference fixes ({}):", count);
+        for fix in &fixes {
+            fix.print_preview();
+        }
+    }
+
+    let references = find_references(Path::new("."), moves)?;
+    if !references.is_empty() {
+        println!("References that may break ({}):", references.len());
+        for reference in &references {
+            println!(
+                "  {}:{}: {}",
+                reference.file.display(),
+                reference.line,
+                reference.needle
+            );
+        }
+    }
+
+    if !apply || executor.config().safety.dry_run {
+        println!("Preview only; re-run with --apply to perform the moves");
+        return Ok(());
+    }
+
+    // Rewritten files pass the content policy before anything moves
+    file_refs::check_fixes(&fixes, executor)?;
+    apply_moves(moves, &fixes, executor).await?;
+    println!("Moved {} paths", moves.len());
+    if !fixes.is_empty() {
+        println!("Updated references in {} files", fixes.len());
+    }
+    Ok(())
+}
+
+/// Perform the moves, using `git mv` for tracked paths, then write the reference fixes.
+/// When a move or a fix fails, the moves already made are undone so the tree is left as
+/// it was.
+pub async fn apply_moves(
+    moves: &[PlannedMove],
+    fixes: &[FileFix],
+    executor: &Executor,
+) -> Result<()> {
+    let tracked = tracked_files(executor).await;
+
+    let mut done = 0;
+    let mut result = Ok(());
+    for planned in moves {
+        let git = is_tracked(&planned.from, &tracked);
+        result = move_path(&planned.from, &planned.to, git, executor).await;
+        if result.is_err() {
+            break;
+        }
+        info!(
+            "Moved {} -> {}",
+            planned.from.display(),
+            planned.to.display()
+        );
+        done += 1;
+    }
+    // write_fixes restores the files it wrote before returning an error
+    if result.is_ok() && !fixes.is_empty() {
+        result = file_refs::write_fixes(fixes, moves, executor);
+    }
+
+    if let Err(e) = result {
+        for planned in moves[..done].iter().rev() {
+            let git = is_tracked(&planned.from, &tracked);
+            if let Err(undo_error) = move_path(&planned.to, &planned.from, git, executor).await {
+                warn!(
+                    "Failed to move {} back to {}: {}",
+                    planned.to.display(),
+                    planned.from.display(),
+                    undo_error
+                );
+            }
+        }
+        return Err(e.context(format!("{} completed moves were undone", done)));
+    }
+    Ok(())
+}
+
+/// Move one path, with `git mv` when `git` is set, and remove the directories it leaves empty
+async fn move_path(from: &Path, to: &Path, git: bool, executor: &Executor) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    if git {
+        let from = from.to_string_lossy().to_string();
+        let to = to.to_string_lossy().to_string();
+        ensure_git_safe(&[&from, &to])?;
+        executor
+            .execute_secure("git", &["mv", "--", &from, &to])
+            .await?;
+    } else {
+        fs::rename(from, to)?;
+    }
+
+    remove_empty_parents(from);
+    Ok(())
+}
+
+fn load_rules(path: &Path) -> Result<Vec<RenameRule>> {
+    let content = fs::read_to_string(path)?;
+    let parsed: RulesFile = toml::from_str(&content)
+        .map_err(|e| anyhow!("Invalid rules file {}: {}", path.display(), e))?;
+    parsed
+        .rules
+        .into_iter()
+        .map(|rule| {
+            Ok(RenameRule {
+                pattern: Regex::new(&rule.pattern)?,
+                replace: rule.replace,
+            })
+        })
+        .collect()
+}
+
+/// Collect files under `root`, honouring .gitignore and skipping the .git directory
+pub(crate) fn walk_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_some_and(|t| t.is_file()) {
+            files.push(normalize(entry.path()));
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Apply the rename rules to each file, returning only the files whose path changes; a
+/// renamed path that climbs out of `root` or is absolute is an error
+fn plan_renames(
+    root: &Path,
+    files: &[PathBuf],
+    rules: &[RenameRule],
+    full_path: bool,
+) -> Result<Vec<PlannedMove>> {
+    let root = normalize(root);
+    let mut moves = Vec::new();
+
+    for file in files {
+        let relative = file.strip_prefix(&root).unwrap_or(file);
+        let subject = if full_path {
+            relative.to_string_lossy().to_string()
+        } else {
+            match file.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            }
+        };
+
+        let renamed = rules.iter().fold(subject.clone(), |current, rule| {
+            rule.pattern
+                .replace_all(&current, rule.replace.as_str())
+                .to_string()
+        });
+        if renamed == subject || renamed.is_empty() {
+            continue;
+        }
+        let escapes = Path::new(&renamed).components().any(|c| {
+            matches!(
+                c,
+                Component::ParentDir | Component::RootDir | Component::Prefix(_)
+            )
+        });
+        if escapes {
+            return Err(anyhow!(
+                "{} would be renamed to {}, outside {}",
+                file.display(),
+                renamed,
+                root.display()
+            ));
+        }
+
+        let to = if full_path {
+            root.join(&renamed)
+        } else {
+            file.with_file_name(&renamed)
+        };
+        moves.push(PlannedMove {
+            from: file.clone(),
+            to: normalize(&to),
+        });
+    }
+
+    Ok(moves)
+}
+
+/// Reject plans with duplicate targets, existing targets, or targets outside the tree
+fn check_conflicts(moves: &[PlannedMove]) -> Result<()> {
+    let sources: HashSet<&PathBuf> = moves.iter().map(|m| &m.from).collect();
+    let mut targets = HashSet::new();
+
+    for planned in moves {
+        if planned.to.components().any(|c| c == Component::ParentDir) {
+            return Err(anyhow!(
+                "Target escapes the working tree: {}",
+                planned.to.display()
+            ));
+        }
+        if !targets.insert(&planned.to) {
+            return Err(anyhow!(
+                "Multiple paths would be moved to {}",
+                planned.to.display()
+            ));
+        }
+        if planned.to.exists() && !sources.contains(&planned.to) {
+            return Err(anyhow!("Target already exists: {}", planned.to.display()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Order the moves so that a move onto another move's source runs after it, e.g. `b -> c`
+/// before `a -> b`; moves that form a cycle, like the swap `a -> b, b -> a`, are refused
+fn order_moves(moves: Vec<PlannedMove>) -> Result<Vec<PlannedMove>> {
+    let by_source: HashMap<&PathBuf, usize> = moves
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (&m.from, i))
+        .collect();
+    let mut ordered = Vec::with_capacity(moves.len());
+    let mut placed = vec![false; moves.len()];
+    for start in 0..moves.len() {
+        // Follow the chain of moves vacating each target, then run it from its far end
+        let mut chain = vec![start];
+        let mut current = start;
+        while let Some(&next) = by_source.get(&moves[current].to) {
+            if placed[next] {
+                break;
+            }
+            if chain.contains(&next) {
+                return Err(anyhow!(
+                    "Moves {} -> {} and {} -> {} form a cycle; rename through a temporary name in \
+                     two steps",
+                    moves[current].from.display(),
+                    moves[current].to.display(),
+                    moves[next].from.display(),
+                    moves[next].to.display()
+                ));
+            }
+            chain.push(next);
+            current = next;
+        }
+        for &i in chain.iter().rev() {
+            if !placed[i] {
+                placed[i] = true;
+                ordered.push(moves[i].clone());
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+/// Strings that identify a path in source files: the path itself, the path without its
+/// extension (JS/TS imports), and the dotted module form (Python imports)
+fn reference_needles(path: &Path) -> Vec<String> {
+    let mut needles = BTreeSet::new();
+    let full = path.to_string_lossy().replace('\\', "/");
+    needles.insert(full.clone());
+
+    let without_ext = path.with_extension("").to_string_lossy().replace('\\', "/");
+    needles.insert(without_ext.clone());
+    if without_ext.contains('/') {
+        needles.insert(without_ext.replace('/', "."));
+    }
+
+    needles.into_iter().filter(|n| n.len() > 2).collect()
+}
+
+/// Scan text files under `root` for mentions of the moved paths
+fn find_references(root: &Path, moves: &[PlannedMove]) -> Result<Vec<Reference>> {
+    let needles: Vec<String> = moves
+        .iter()
+        .flat_map(|planned| reference_needles(&planned.from))
+        .collect();
+    let moved: HashSet<&PathBuf> = moves.iter().map(|m| &m.from).collect();
+    let mut references = Vec::new();
+
+    for file in walk_files(root)? {
+        if moved.contains(&file) {
+            continue;
+        }
+        let too_large = fs::metadata(&file)
+            .map(|m| m.len() > MAX_SCAN_BYTES)
+            .unwrap_or(true);
+        if too_large {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+
+        for (index, line) in content.lines().enumerate() {
+            if let Some(needle) = needles.iter().find(|n| line.contains(n.as_str())) {
+                references.push(Reference {
+                    file: file.clone(),
+                    line: index + 1,
+                    needle: needle.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(references)
+}
+
+/// Files tracked by git, relative to the current directory (empty outside a repository)
+async fn tracked_files(executor: &Executor) -> HashSet<PathBuf> {
+    match executor
+        .execute_secure_capture("git", &["ls-files", "-z"])
+        .await
+    {
+        Ok(output) if output.success() => output
+            .stdout
+            .split('\0')
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        Ok(_) => HashSet::new(),
+        Err(e) => {
+            warn!(
+                "Unable to list tracked files, falling back to plain renames: {}",
+                e
+            );
+            HashSet::new()
+        }
+    }
+}
+
+/// Whether a file, or any file inside a directory, is tracked
+fn is_tracked(path: &Path, tracked: &HashSet<PathBuf>) -> bool {
+    tracked.contains(path) || tracked.iter().any(|t| t.starts_with(path))
+}
+
+/// Make sure argument sanitization would not alter the paths passed to git
+fn ensure_git_safe(paths: &[&str]) -> Result<()> {
+    let args: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+    let sanitized = sanitizer::sanitize_arguments("git", &args)?;
+    if sanitized != args {
+        return Err(anyhow!(
+            "Path contains characters that cannot be passed to git safely: {:?}",
+            paths
+        ));
+    }
+    Ok(())
+}
+
+/// Remove directories left empty by a move, walking up towards the current directory
+fn remove_empty_parents(path: &Path) {
+    let mut current = path.parent();
+    while let Some(dir) = current {
+        if dir.as_os_str().is_empty() || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
+
+/// Strip a leading `./` so paths compare equal to `git ls-files` output
+fn normalize(path: &Path) -> PathBuf {
+    path.strip_prefix(".").unwrap_or(path).to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replace: &str) -> RenameRule {
+        RenameRule {
+            pattern: Regex::new(pattern).unwrap(),
+            replace: replace.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plan_renames_file_name_and_full_path() {
+        let files = vec![
+            PathBuf::from("src/old_name.rs"),
+            PathBuf::from("src/other.rs"),
+        ];
+
+        let moves = plan_renames(Path::new("."), &files, &[rule("^old_", "new_")], false).unwrap();
+        assert_eq!(
+            moves,
+            vec![PlannedMove {
+                from: PathBuf::from("src/old_name.rs"),
+                to: PathBuf::from("src/new_name.rs"),
+            }]
+        );
+
+        let moves = plan_renames(
+            Path::new("."),
+            &files,
+            &[rule(r"^src/(\w+)\.rs$", "lib/$1.rs")],
+            true,
+        )
+        .unwrap();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[1].to, PathBuf::from("lib/other.rs"));
+    }
+
+    #[test]
+    fn test_check_conflicts_detects_duplicate_targets() {
+        let moves = vec![
+            PlannedMove {
+                from: "a_1.txt".into(),
+                to: "a.txt".into(),
+            },
+            PlannedMove {
+                from: "a_2.txt".into(),
+                to: "a.txt".into(),
+            },
+        ];
+        assert!(check_conflicts(&moves).is_err());
+
+        let escape = vec![PlannedMove {
+            from: "a.txt".into(),
+            to: "../a.txt".into(),
+        }];
+        assert!(check_conflicts(&escape).is_err());
+
+        let dotted = vec![PlannedMove {
+            from: "a.txt".into(),
+            to: "a..b.txt".into(),
+        }];
+        assert!(check_conflicts(&dotted).is_ok());
+    }
+
+    #[test]
+    fn test_plan_renames_rejects_escaping_names() {
+        let files = vec![PathBuf::from("src/old.rs")];
+        assert!(plan_renames(Path::new("."), &files, &[rule("^old", "../old")], false).is_err());
+        assert!(plan_renames(Path::new("."), &files, &[rule("^src", "/tmp")], true).is_err());
+        let moves =
+            plan_renames(Path::new("."), &files, &[rule("^old", "old..v2")], false).unwrap();
+        assert_eq!(moves[0].to, PathBuf::from("src/old..v2.rs"));
+    }
+
+    #[test]
+    fn test_order_moves_runs_chains_backwards_and_rejects_swaps() {
+        let planned = |from: &str, to: &str| PlannedMove {
+            from: from.into(),
+            to: to.into(),
+        };
+
+        let chain = vec![planned("a", "b"), planned("b", "c"), planned("c", "d")];
+        check_conflicts(&chain).unwrap();
+        let ordered = order_moves(chain).unwrap();
+        assert_eq!(
+            ordered,
+            vec![planned("c", "d"), planned("b", "c"), planned("a", "b")]
+        );
+
+        let swap = vec![planned("a", "b"), planned("b", "a")];
+        check_conflicts(&swap).unwrap();
+        let err = order_moves(swap).unwrap_err();
+        assert!(err.to_string().contains("cycle"));
+
+        let rotation = vec![planned("a", "b"), planned("b", "c"), planned("c", "a")];
+        assert!(order_moves(rotation).is_err());
+    }
+
+    #[test]
+    fn test_reference_needles() {
+        let needles = reference_needles(Path::new("pkg/utils/helper.py"));
+        assert!(needles.contains(&"pkg/utils/helper.py".to_string()));
+        assert!(needles.contains(&"pkg/utils/helper".to_string()));
+        assert!(needles.contains(&"pkg.utils.helper".to_string()));
+    }
+}
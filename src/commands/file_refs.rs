@@ -0,0 +1,965 @@
+// file: src/commands/file_refs.rs
+// version: 1.0.1
+// guid: c574d2b6-7dbe-484c-a797-6fd75495a220
+
+//! Reference fixing for `file rename` and `file move-dir` (`--fix-references`)
+//!
+//! Source files are parsed with tree-sitter and references to moved paths are rewritten:
+//!
+//! - Rust: `mod` declarations in the parent module and `crate::` paths
+//! - Python: absolute `import` and `from ... import` module paths
+//! - TypeScript/JavaScript: relative import, export, `require()` and `import()` specifiers,
+//!   including specifiers in files that move themselves
+//!
+//! All edits are planned up front and every edited file is re-parsed. If an edit would turn
+//! a file that parsed cleanly into one with syntax errors, the whole plan is rejected before
+//! anything on disk changes; a failed write rolls back the files already rewritten.
+
+use super::file_move::{walk_files, PlannedMove};
+use crate::executor::Executor;
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+use tree_sitter::{Node, Parser, Tree};
+
+/// Extensions tried when resolving extensionless script imports
+const SCRIPT_EXTENSIONS: [&str; 9] = ["ts", "tsx", "d.ts", "js", "jsx", "mts", "cts", "mjs", "cjs"];
+
+/// Languages with reference support
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lang {
+    Rust,
+    Python,
+    TypeScript,
+    Tsx,
+}
+
+impl Lang {
+    fn detect(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Self::Rust),
+            "py" => Some(Self::Python),
+            "ts" | "mts" | "cts" => Some(Self::TypeScript),
+            "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(Self::Tsx),
+            _ => None,
+        }
+    }
+
+    fn parse(self, source: &str) -> Result<Tree> {
+        let language: tree_sitter::Language = match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+        };
+        let mut parser = Parser::new();
+        parser.set_language(&language)?;
+        parser
+            .parse(source, None)
+            .ok_or_else(|| anyhow!("Failed to parse source"))
+    }
+}
+
+/// Replacement of a byte range (an insertion when `start == end`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+/// Validated reference fixes for one file
+#[derive(Debug)]
+pub struct FileFix {
+    /// Location of the file before the moves are applied
+    pub path: PathBuf,
+    pub original: String,
+    pub updated: String,
+    pub edits: Vec<Edit>,
+}
+
+impl FileFix {
+    /// Print one line per edit for the preview
+    pub fn print_preview(&self) {
+        for edit in &self.edits {
+            let line = self.original[..edit.start].matches('\n').count() + 1;
+            let old = &self.original[edit.start..edit.end];
+            if old.is_empty() {
+                println!(
+                    "  {}:{}: + {}",
+                    self.path.display(),
+                    line,
+                    edit.replacement.trim()
+                );
+            } else if edit.replacement.is_empty() {
+                println!("  {}:{}: - {}", self.path.display(), line, old.trim());
+            } else {
+                println!(
+                    "  {}:{}: {} -> {}",
+                    self.path.display(),
+                    line,
+                    old,
+                    edit.replacement
+                );
+            }
+        }
+    }
+}
+
+/// Plan and validate reference fixes for the given moves
+pub fn plan_fixes(moves: &[PlannedMove]) -> Result<Vec<FileFix>> {
+    let files = walk_files(Path::new("."))?;
+    let known: HashSet<PathBuf> = files.iter().cloned().collect();
+    let python_moves = python_module_moves(moves);
+    let rust_moves = rust_module_moves(moves, &files);
+
+    let mut sources = SourceCache::default();
+    let mut edits: BTreeMap<PathBuf, Vec<Edit>> = BTreeMap::new();
+
+    for file in &files {
+        let Some(lang) = Lang::detect(file) else {
+            continue;
+        };
+        let Some((source, tree)) = sources.get(file, lang)? else {
+            continue;
+        };
+        let file_edits = match lang {
+            Lang::Python => python_edits(source, tree, &python_moves),
+            Lang::TypeScript | Lang::Tsx => script_edits(file, source, tree, moves, &known),
+            Lang::Rust => rust_path_edits(file, source, tree, &rust_moves),
+        };
+        if !file_edits.is_empty() {
+            edits.entry(file.clone()).or_default().extend(file_edits);
+        }
+    }
+
+    rust_mod_edits(&rust_moves, &files, moves, &mut sources, &mut edits)?;
+
+    edits
+        .into_iter()
+        .map(|(path, file_edits)| {
+            let lang = Lang::detect(&path)
+                .ok_or_else(|| anyhow!("Unsupported file {}", path.display()))?;
+            let original = fs::read_to_string(&path)?;
+            build_fix(path, lang, original, file_edits)
+        })
+        .collect()
+}
+
+/// Check the fixed files against the content policy
+pub fn check_fixes(fixes: &[FileFix], executor: &Executor) -> Result<()> {
+    for fix in fixes {
+        executor.check_content(&fix.path, fix.updated.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Write the fixed files at their post-move locations once they pass the content policy,
+/// restoring them if any write fails
+pub fn write_fixes(fixes: &[FileFix], moves: &[PlannedMove], executor: &Executor) -> Result<()> {
+    check_fixes(fixes, executor)?;
+    let mut written: Vec<(PathBuf, &str)> = Vec::new();
+
+    for fix in fixes {
+        let target = final_location(&fix.path, moves);
+        if let Err(e) = fs::write(&target, &fix.updated) {
+            for (path, original) in &written {
+                if let Err(restore_error) = fs::write(path, original) {
+                    warn!("Failed to restore {}: {}", path.display(), restore_error);
+                }
+            }
+            return Err(anyhow!(
+                "Failed to update {}: {}; reference fixes were rolled back",
+                target.display(),
+                e
+            ));
+        }
+        written.push((target, &fix.original));
+    }
+
+    Ok(())
+}
+
+/// Where a path ends up once the moves are applied
+pub fn final_location(path: &Path, moves: &[PlannedMove]) -> PathBuf {
+    for planned in moves {
+        if path == planned.from {
+            return planned.to.clone();
+        }
+        if let Ok(rest) = path.strip_prefix(&planned.from) {
+            return planned.to.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Parsed sources, read on demand
+#[derive(Default)]
+struct SourceCache {
+    entries: BTreeMap<PathBuf, Option<(String, Tree)>>,
+}
+
+impl SourceCache {
+    fn get(&mut self, path: &Path, lang: Lang) -> Result<Option<(&str, &Tree)>> {
+        if !self.entries.contains_key(path) {
+            let entry = match fs::read_to_string(path) {
+                Ok(source) => {
+                    let tree = lang.parse(&source)?;
+                    Some((source, tree))
+                }
+                Err(_) => None,
+            };
+            self.entries.insert(path.to_path_buf(), entry);
+        }
+        Ok(self.entries[path].as_ref().map(|(s, t)| (s.as_str(), t)))
+    }
+}
+
+/// Sort, deduplicate and apply edits, then make sure the result still parses
+fn build_fix(path: PathBuf, lang: Lang, original: String, mut edits: Vec<Edit>) -> Result<FileFix> {
+    edits.sort_by_key(|e| (e.start, e.end));
+    edits.dedup();
+    for pair in edits.windows(2) {
+        if pair[0].end > pair[1].start {
+            return Err(anyhow!("Conflicting reference edits in {}", path.display()));
+        }
+    }
+
+    let mut updated = original.clone();
+    for edit in edits.iter().rev() {
+        updated.replace_range(edit.start..edit.end, &edit.replacement);
+    }
+
+    let was_valid = !lang.parse(&original)?.root_node().has_error();
+    if was_valid && lang.parse(&updated)?.root_node().has_error() {
+        return Err(anyhow!(
+            "Reference fixes for {} would produce invalid syntax; no changes were made",
+            path.display()
+        ));
+    }
+
+    Ok(FileFix {
+        path,
+        original,
+        updated,
+        edits,
+    })
+}
+
+/// Visit every node of a tree in document order
+fn visit<'a>(node: Node<'a>, f: &mut impl FnMut(Node<'a>)) {
+    f(node);
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        visit(child, f);
+    }
+}
+
+fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    &source[node.byte_range()]
+}
+
+fn replace(node: Node, replacement: impl Into<String>) -> Edit {
+    Edit {
+        start: node.start_byte(),
+        end: node.end_byte(),
+        replacement: replacement.into(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Python
+// ---------------------------------------------------------------------------
+
+/// Dotted module names before and after each move
+fn python_module_moves(moves: &[PlannedMove]) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    for planned in moves {
+        let is_module = planned.from.extension().map_or(true, |ext| ext == "py");
+        if !is_module {
+            continue;
+        }
+        // Projects using a `src/` layout import without the `src` prefix
+        for prefix in ["", "src"] {
+            let (Ok(from), Ok(to)) = (
+                planned.from.strip_prefix(prefix),
+                planned.to.strip_prefix(prefix),
+            ) else {
+                continue;
+            };
+            if let (Some(old), Some(new)) = (python_module(from), python_module(to)) {
+                if old != new {
+                    pairs.push((old, new));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+fn python_module(path: &Path) -> Option<String> {
+    let mut segments: Vec<String> = path
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if segments.last().is_some_and(|s| s == "__init__") {
+        segments.pop();
+    }
+    if segments.is_empty() || segments.iter().any(|s| !is_identifier(s)) {
+        return None;
+    }
+    Some(segments.join("."))
+}
+
+fn python_edits(source: &str, tree: &Tree, pairs: &[(String, String)]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    if pairs.is_empty() {
+        return edits;
+    }
+
+    visit(tree.root_node(), &mut |node| match node.kind() {
+        "import_statement" => {
+            let mut cursor = node.walk();
+            for name in node.children_by_field_name("name", &mut cursor) {
+                let dotted = if name.kind() == "aliased_import" {
+                    name.child_by_field_name("name")
+                } else {
+                    Some(name)
+                };
+                if let Some(edit) = dotted.and_then(|d| rewrite_dotted(d, source, pairs)) {
+                    edits.push(edit);
+                }
+            }
+        }
+        "import_from_statement" => {
+            let Some(module) = node.child_by_field_name("module_name") else {
+                return;
+            };
+            if module.kind() != "dotted_name" {
+                // Relative imports are resolved by the interpreter, not the project layout
+                return;
+            }
+            if let Some(edit) = rewrite_dotted(module, source, pairs) {
+                edits.push(edit);
+                return;
+            }
+            edits.extend(rewrite_from_names(node, module, source, pairs));
+        }
+        _ => {}
+    });
+
+    edits
+}
+
+/// Rewrite a dotted module name that is, or is inside, a moved module
+fn rewrite_dotted(node: Node, source: &str, pairs: &[(String, String)]) -> Option<Edit> {
+    let current = text(node, source);
+    pairs.iter().find_map(|(old, new)| {
+        let rest = current.strip_prefix(old.as_str())?;
+        (rest.is_empty() || rest.starts_with('.'))
+            .then(|| replace(node, format!("{}{}", new, rest)))
+    })
+}
+
+/// Handle `from package import moved_module`
+fn rewrite_from_names(
+    statement: Node,
+    module: Node,
+    source: &str,
+    pairs: &[(String, String)],
+) -> Vec<Edit> {
+    let module_text = text(module, source);
+    let mut cursor = statement.walk();
+    let names: Vec<Node> = statement
+        .children_by_field_name("name", &mut cursor)
+        .map(|name| {
+            if name.kind() == "aliased_import" {
+                name.child_by_field_name("name").unwrap_or(name)
+            } else {
+                name
+            }
+        })
+        .collect();
+
+    for (old, new) in pairs {
+        let Some((old_parent, old_last)) = old.rsplit_once('.') else {
+            continue;
+        };
+        if old_parent != module_text {
+            continue;
+        }
+        let Some(name) = names.iter().find(|n| text(**n, source) == old_last) else {
+            continue;
+        };
+
+        let (new_parent, new_last) = new.rsplit_once('.').unwrap_or(("", new.as_str()));
+        if new_parent == old_parent {
+            return vec![replace(*name, new_last)];
+        }
+        if names.len() == 1 && !new_parent.is_empty() {
+            return vec![replace(module, new_parent), replace(*name, new_last)];
+        }
+        warn!(
+            "Cannot rewrite 'from {} import {}' automatically; update it to use {}",
+            module_text, old_last, new
+        );
+    }
+
+    Vec::new()
+}
+
+// ---------------------------------------------------------------------------
+// TypeScript / JavaScript
+// ---------------------------------------------------------------------------
+
+/// How a relative specifier resolved to a file
+enum Resolution {
+    Exact,
+    Extension(&'static str),
+    Index(&'static str),
+}
+
+fn script_edits(
+    file: &Path,
+    source: &str,
+    tree: &Tree,
+    moves: &[PlannedMove],
+    known: &HashSet<PathBuf>,
+) -> Vec<Edit> {
+    let importer_dir = file.parent().unwrap_or(Path::new(""));
+    let importer_final = final_location(file, moves);
+    let final_dir = importer_final
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+    let mut edits = Vec::new();
+
+    visit(tree.root_node(), &mut |node| {
+        let Some(string) = specifier_string(node, source) else {
+            return;
+        };
+        let mut cursor = string.walk();
+        let Some(fragment) = string
+            .named_children(&mut cursor)
+            .find(|c| c.kind() == "string_fragment")
+        else {
+            return;
+        };
+
+        let specifier = text(fragment, source);
+        if !specifier.starts_with("./") && !specifier.starts_with("../") {
+            return;
+        }
+        let Some(target) = normalize_lexical(&importer_dir.join(specifier)) else {
+            return;
+        };
+        let Some((resolved, how)) = resolve_script(&target, known) else {
+            return;
+        };
+
+        let target_final = final_location(&resolved, moves);
+        if target_final == resolved && importer_final == file {
+            return;
+        }
+
+        let relative = relative_path(&final_dir, &target_final);
+        let stripped = match how {
+            Resolution::Exact => relative,
+            Resolution::Extension(ext) => relative
+                .strip_suffix(&format!(".{}", ext))
+                .map(str::to_string)
+                .unwrap_or(relative),
+            Resolution::Index(ext) => relative
+                .strip_suffix(&format!("index.{}", ext))
+                .map(|s| s.trim_end_matches('/').to_string())
+                .unwrap_or(relative),
+        };
+        let updated = if stripped.is_empty() {
+            ".".to_string()
+        } else if stripped.starts_with("../") {
+            stripped
+        } else {
+            format!("./{}", stripped)
+        };
+
+        if updated != specifier {
+            edits.push(replace(fragment, updated));
+        }
+    });
+
+    edits
+}
+
+/// The string node holding a module specifier, if `node` is an import-like construct
+fn specifier_string<'a>(node: Node<'a>, source: &str) -> Option<Node<'a>> {
+    match node.kind() {
+        "import_statement" | "export_statement" => node.child_by_field_name("source"),
+        "call_expression" => {
+            let function = node.child_by_field_name("function")?;
+            let is_loader = function.kind() == "import"
+                || (function.kind() == "identifier" && text(function, source) == "require");
+            if !is_loader {
+                return None;
+            }
+            let arguments = node.child_by_field_name("arguments")?;
+            let first = arguments.named_child(0)?;
+            (first.kind() == "string").then_some(first)
+        }
+        _ => None,
+    }
+}
+
+fn resolve_script(target: &Path, known: &HashSet<PathBuf>) -> Option<(PathBuf, Resolution)> {
+    if known.contains(target) {
+        return Some((target.to_path_buf(), Resolution::Exact));
+    }
+    for ext in SCRIPT_EXTENSIONS {
+        let candidate = PathBuf::from(format!("{}.{}", target.display(), ext));
+        if known.contains(&candidate) {
+            return Some((candidate, Resolution::Extension(ext)));
+        }
+    }
+    for ext in SCRIPT_EXTENSIONS {
+        let candidate = target.join(format!("index.{}", ext));
+        if known.contains(&candidate) {
+            return Some((candidate, Resolution::Index(ext)));
+        }
+    }
+    None
+}
+
+/// Resolve `.` and `..` without touching the filesystem; `None` if the path escapes the root
+fn normalize_lexical(path: &Path) -> Option<PathBuf> {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    return None;
+                }
+            }
+            Component::Normal(part) => result.push(part),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+/// Relative path from a directory to a file, using `/` separators
+fn relative_path(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<_> = from_dir.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().to_string()),
+    );
+    parts.join("/")
+}
+
+// ---------------------------------------------------------------------------
+// Rust
+// ---------------------------------------------------------------------------
+
+/// A Rust module that moves within one crate
+#[derive(Debug)]
+struct RustMove {
+    root: PathBuf,
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+impl RustMove {
+    fn path(segments: &[String]) -> String {
+        std::iter::once("crate".to_string())
+            .chain(segments.iter().cloned())
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+}
+
+/// Module path of a Rust file or directory relative to the nearest `src` directory
+fn rust_module(path: &Path) -> Option<(PathBuf, Vec<String>)> {
+    let components: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let src = components.iter().rposition(|c| c == "src")?;
+    let root: PathBuf = components[..=src].iter().collect();
+
+    let mut segments = components[src + 1..].to_vec();
+    if let Some(last) = segments.last_mut() {
+        if let Some(stem) = last.strip_suffix(".rs") {
+            *last = stem.to_string();
+        }
+    }
+    if matches!(segments.as_slice(), [only] if only == "lib" || only == "main") {
+        segments.clear();
+    } else if segments.last().is_some_and(|s| s == "mod") {
+        segments.pop();
+    }
+
+    segments
+        .iter()
+        .all(|s| is_identifier(s))
+        .then_some((root, segments))
+}
+
+fn rust_module_moves(moves: &[PlannedMove], files: &[PathBuf]) -> Vec<RustMove> {
+    let mut result = Vec::new();
+    for planned in moves {
+        let is_rust = match planned.from.extension() {
+            Some(ext) => ext == "rs",
+            None => files
+                .iter()
+                .any(|f| f.starts_with(&planned.from) && f.extension().is_some_and(|e| e == "rs")),
+        };
+        if !is_rust {
+            continue;
+        }
+
+        let (root, old, new) = match (rust_module(&planned.from), rust_module(&planned.to)) {
+            (Some((old_root, old)), Some((new_root, new))) if old_root == new_root => {
+                (old_root, old, new)
+            }
+            (Some(_), _) => {
+                warn!(
+                    "Not updating Rust references for {}: it leaves its crate",
+                    planned.from.display()
+                );
+                continue;
+            }
+            _ => continue,
+        };
+        if !old.is_empty() && !new.is_empty() && old != new {
+            result.push(RustMove { root, old, new });
+        }
+    }
+    result
+}
+
+/// Rewrite `crate::` paths that refer to moved modules
+fn rust_path_edits(file: &Path, source: &str, tree: &Tree, moves: &[RustMove]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+
+    for rust_move in moves.iter().filter(|m| file.starts_with(&m.root)) {
+        let old_path = RustMove::path(&rust_move.old);
+        let new_path = RustMove::path(&rust_move.new);
+        let old_parent = &rust_move.old[..rust_move.old.len() - 1];
+        let new_parent = &rust_move.new[..rust_move.new.len() - 1];
+        let old_last = rust_move.old.last().unwrap();
+        let new_last = rust_move.new.last().unwrap();
+        let parent_path = RustMove::path(old_parent);
+
+        visit(tree.root_node(), &mut |node| match node.kind() {
+            "scoped_identifier" if compact(text(node, source)) == old_path => {
+                edits.push(replace(node, new_path.clone()));
+            }
+            "scoped_use_list" => {
+                let Some(path) = node.child_by_field_name("path") else {
+                    return;
+                };
+                let Some(list) = node.child_by_field_name("list") else {
+                    return;
+                };
+                if compact(text(path, source)) != parent_path {
+                    return;
+                }
+                let mut cursor = list.walk();
+                for item in list.named_children(&mut cursor) {
+                    let Some(ident) = leftmost_identifier(item) else {
+                        continue;
+                    };
+                    if text(ident, source) != old_last {
+                        continue;
+                    }
+                    if old_parent == new_parent {
+                        edits.push(replace(ident, new_last.clone()));
+                    } else {
+                        warn!(
+                            "Cannot rewrite grouped import of {} automatically; update it to {}",
+                            old_path, new_path
+                        );
+                    }
+                }
+            }
+            _ => {}
+        });
+    }
+
+    edits
+}
+
+/// Move or rename the `mod` declaration of each moved module
+fn rust_mod_edits(
+    rust_moves: &[RustMove],
+    files: &[PathBuf],
+    moves: &[PlannedMove],
+    sources: &mut SourceCache,
+    edits: &mut BTreeMap<PathBuf, Vec<Edit>>,
+) -> Result<()> {
+    for rust_move in rust_moves {
+        let old_parent = &rust_move.old[..rust_move.old.len() - 1];
+        let new_parent = &rust_move.new[..rust_move.new.len() - 1];
+        let old_last = rust_move.old.last().unwrap();
+        let new_last = rust_move.new.last().unwrap();
+
+        let Some(parent_file) =
+            module_owner(files, &rust_move.root, old_parent, |f| f.to_path_buf())
+        else {
+            warn!(
+                "No parent module found for {}",
+                RustMove::path(&rust_move.old)
+            );
+            continue;
+        };
+        let Some((source, tree)) = sources.get(&parent_file, Lang::Rust)? else {
+            continue;
+        };
+        let Some(item) = find_mod_item(tree.root_node(), source, old_last) else {
+            warn!(
+                "No `mod {};` declaration found in {}",
+                old_last,
+                parent_file.display()
+            );
+            continue;
+        };
+        let name = item.child_by_field_name("name").unwrap();
+
+        if old_parent == new_parent {
+            edits
+                .entry(parent_file)
+                .or_default()
+                .push(replace(name, new_last.clone()));
+            continue;
+        }
+
+        let declaration = format!(
+            "{}{}{}",
+            &source[item.start_byte()..name.start_byte()],
+            new_last,
+            &source[name.end_byte()..item.end_byte()]
+        );
+        let mut removal_end = item.end_byte();
+        if source[removal_end..].starts_with('\n') {
+            removal_end += 1;
+        }
+        let removal = Edit {
+            start: item.start_byte(),
+            end: removal_end,
+            replacement: String::new(),
+        };
+
+        let Some(new_parent_file) = module_owner(files, &rust_move.root, new_parent, |f| {
+            final_location(f, moves)
+        }) else {
+            warn!(
+                "No module file for {}; add `mod {};` by hand",
+                RustMove::path(new_parent),
+                new_last
+            );
+            continue;
+        };
+        let Some((target_source, target_tree)) = sources.get(&new_parent_file, Lang::Rust)? else {
+            continue;
+        };
+        let insertion = last_mod_item_end(target_tree.root_node())
+            .map(|end| Edit {
+                start: end,
+                end,
+                replacement: format!("\n{}", declaration),
+            })
+            .unwrap_or_else(|| Edit {
+                start: 0,
+                end: 0,
+                replacement: format!("{}\n", declaration),
+            });
+        let _ = target_source;
+
+        edits.entry(parent_file).or_default().push(removal);
+        edits.entry(new_parent_file).or_default().push(insertion);
+    }
+
+    Ok(())
+}
+
+/// The file that declares the children of a module (`lib.rs`/`main.rs`, `x.rs` or `x/mod.rs`)
+fn module_owner(
+    files: &[PathBuf],
+    root: &Path,
+    segments: &[String],
+    location: impl Fn(&Path) -> PathBuf,
+) -> Option<PathBuf> {
+    let mut owners: Vec<&PathBuf> = files
+        .iter()
+        .filter(|f| f.extension().is_some_and(|e| e == "rs"))
+        .filter(|f| rust_module(&location(f)).is_some_and(|(r, s)| r == root && s == segments))
+        .collect();
+    // Prefer the library root when a crate has both lib.rs and main.rs
+    owners.sort_by_key(|f| f.file_name().map_or(true, |n| n != "lib.rs"));
+    owners.first().map(|f| f.to_path_buf())
+}
+
+fn find_mod_item<'a>(root: Node<'a>, source: &str, name: &str) -> Option<Node<'a>> {
+    let mut cursor = root.walk();
+    let found = root.named_children(&mut cursor).find(|item| {
+        item.kind() == "mod_item"
+            && item.child_by_field_name("body").is_none()
+            && item
+                .child_by_field_name("name")
+                .is_some_and(|n| text(n, source) == name)
+    });
+    found
+}
+
+fn last_mod_item_end(root: Node) -> Option<usize> {
+    let mut cursor = root.walk();
+    let end = root
+        .named_children(&mut cursor)
+        .filter(|item| item.kind() == "mod_item")
+        .map(|item| item.end_byte())
+        .last();
+    end
+}
+
+/// First identifier of a (possibly scoped) path inside a use list
+fn leftmost_identifier(mut node: Node) -> Option<Node> {
+    loop {
+        match node.kind() {
+            "identifier" => return Some(node),
+            "scoped_identifier" | "scoped_use_list" | "use_as_clause" => {
+                node = node.child_by_field_name("path")?;
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn compact(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn is_identifier(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planned(from: &str, to: &str) -> PlannedMove {
+        PlannedMove {
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+        }
+    }
+
+    fn apply(source: &str, edits: Vec<Edit>, lang: Lang) -> String {
+        build_fix(PathBuf::from("test"), lang, source.to_string(), edits)
+            .unwrap()
+            .updated
+    }
+
+    #[test]
+    fn test_python_imports() {
+        let source = "import pkg.utils.helper\nfrom pkg.utils.helper import run\nfrom pkg.utils import helper, other\n";
+        let pairs = python_module_moves(&[planned("pkg/utils/helper.py", "pkg/utils/tools.py")]);
+        let tree = Lang::Python.parse(source).unwrap();
+        let edits = python_edits(source, &tree, &pairs);
+
+        assert_eq!(
+            apply(source, edits, Lang::Python),
+            "import pkg.utils.tools\nfrom pkg.utils.tools import run\nfrom pkg.utils import tools, other\n"
+        );
+    }
+
+    #[test]
+    fn test_script_imports_follow_both_sides_of_a_move() {
+        let known: HashSet<PathBuf> = ["src/app.ts", "src/lib/util.ts", "src/lib/index.ts"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let moves = [planned("src/lib", "src/shared/lib")];
+
+        let source = "import { a } from './lib/util';\nimport lib from \"./lib\";\nconst x = require('./missing');\n";
+        let tree = Lang::TypeScript.parse(source).unwrap();
+        let edits = script_edits(Path::new("src/app.ts"), source, &tree, &moves, &known);
+        assert_eq!(
+            apply(source, edits, Lang::TypeScript),
+            "import { a } from './shared/lib/util';\nimport lib from \"./shared/lib\";\nconst x = require('./missing');\n"
+        );
+
+        let source = "export * from '../app';\n";
+        let tree = Lang::TypeScript.parse(source).unwrap();
+        let edits = script_edits(Path::new("src/lib/index.ts"), source, &tree, &moves, &known);
+        assert_eq!(
+            apply(source, edits, Lang::TypeScript),
+            "export * from '../../app';\n"
+        );
+    }
+
+    #[test]
+    fn test_rust_paths_and_use_lists() {
+        let moves = vec![RustMove {
+            root: PathBuf::from("src"),
+            old: vec!["commands".to_string(), "old".to_string()],
+            new: vec!["commands".to_string(), "new".to_string()],
+        }];
+        let source = "use crate::commands::old::run;\nuse crate::commands::{old, other};\nfn f() { crate::commands::old::go(); }\n";
+        let tree = Lang::Rust.parse(source).unwrap();
+        let edits = rust_path_edits(Path::new("src/main.rs"), source, &tree, &moves);
+
+        assert_eq!(
+            apply(source, edits, Lang::Rust),
+            "use crate::commands::new::run;\nuse crate::commands::{new, other};\nfn f() { crate::commands::new::go(); }\n"
+        );
+    }
+
+    #[test]
+    fn test_module_paths() {
+        assert_eq!(
+            rust_module(Path::new("src/commands/mod.rs")),
+            Some((PathBuf::from("src"), vec!["commands".to_string()]))
+        );
+        assert_eq!(
+            rust_module(Path::new("crates/a/src/lib.rs"))
+                .unwrap()
+                .1
+                .len(),
+            0
+        );
+        assert_eq!(
+            python_module(Path::new("pkg/__init__.py")),
+            Some("pkg".to_string())
+        );
+        assert_eq!(
+            relative_path(Path::new("a/b"), Path::new("a/c/d.ts")),
+            "../c/d.ts"
+        );
+    }
+
+    #[test]
+    fn test_invalid_result_is_rejected() {
+        let edits = vec![Edit {
+            start: 4,
+            end: 7,
+            replacement: "(".to_string(),
+        }];
+        assert!(build_fix(
+            PathBuf::from("a.py"),
+            Lang::Python,
+            "x = foo\n".to_string(),
+            edits
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,496 @@
+// file: src/commands/file_sync.rs
+// version: 1.0.3
+// guid: 9d4b2e7a-61c3-4f58-b0a9-3e8c5f17d264
+
+//! `file sync`: make a directory a copy of another, natively
+//!
+//! Files are compared by size and modification time, or by SHA-256 with `--checksum`, and only
+//! those that differ are copied. Copies go to a temporary name first and keep the source's
+//! modification time, so the next sync sees them as unchanged. `--include` and `--exclude` take
+//! gitignore-style globs relative to either directory; excluded files are neither copied nor
+//! deleted. `--delete` removes destination files the source lacks, after confirmation.
+//! `--dry-run` only lists what would be added (`+`), updated (`~`) and deleted (`-`).
+//! Symbolic links are skipped, since their targets may lie outside the workspace.
+
+use super::export::Effect;
+use crate::executor::Executor;
+use crate::guardrails::{self, Estimate};
+use crate::utils;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use filetime::FileTime;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// Build the `file sync` subcommand
+pub fn build_command() -> Command {
+    Command::new("sync")
+        .add(Effect::Destructive)
+        .about("Copy the files of a directory that differ to another directory")
+        .arg(
+            Arg::new("source")
+                .value_name("SRC")
+                .required(true)
+                .help("Directory to copy from"),
+        )
+        .arg(
+            Arg::new("destination")
+                .value_name("DST")
+                .required(true)
+                .help("Directory to copy to; created if missing"),
+        )
+        .arg(
+            Arg::new("checksum")
+                .long("checksum")
+                .short('c')
+                .action(ArgAction::SetTrue)
+                .help("Compare files by SHA-256 instead of size and modification time"),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .help("Only sync files matching GLOB; repeat for several"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .help("Neither copy nor delete files matching GLOB; repeat for several"),
+        )
+        .arg(
+            Arg::new("delete")
+                .long("delete")
+                .action(ArgAction::SetTrue)
+                .help("Delete destination files that are not in the source"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .short('n')
+                .action(ArgAction::SetTrue)
+                .help("Only list the changes"),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .action(ArgAction::SetTrue)
+                .help("Delete without asking, and sync a large tree without asking"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Format of the list of changes"),
+        )
+}
+
+/// What a sync does to one file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    Add,
+    Update,
+    Delete,
+}
+
+impl Action {
+    fn symbol(self) -> char {
+        match self {
+            Action::Add => '+',
+            Action::Update => '~',
+            Action::Delete => '-',
+        }
+    }
+}
+
+/// A file a sync changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Change {
+    action: Action,
+    /// Path relative to both directories
+    path: PathBuf,
+    /// Bytes copied; 0 for deletions
+    bytes: u64,
+}
+
+/// Which files of a directory take part in a sync
+struct Filter {
+    include: Option<Gitignore>,
+    exclude: Gitignore,
+}
+
+impl Filter {
+    fn new(root: &Path, include: &[String], exclude: &[String]) -> Result<Self> {
+        let build = |globs: &[String]| -> Result<Gitignore> {
+            let mut builder = GitignoreBuilder::new(root);
+            for glob in globs {
+                builder
+                    .add_line(None, glob)
+                    .with_context(|| format!("Invalid glob '{}'", glob))?;
+            }
+            Ok(builder.build()?)
+        };
+        Ok(Self {
+            include: if include.is_empty() {
+                None
+            } else {
+                Some(build(include)?)
+            },
+            exclude: build(exclude)?,
+        })
+    }
+
+    /// Whether the file at `path`, under the filter's root, is synced
+    fn accepts(&self, path: &Path) -> bool {
+        let included = self.include.as_ref().map_or(true, |include| {
+            include.matched_path_or_any_parents(path, false).is_ignore()
+        });
+        included
+            && !self
+                .exclude
+                .matched_path_or_any_parents(path, false)
+                .is_ignore()
+    }
+}
+
+/// Execute `file sync`
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let source = executor.check_path(matches.get_one::<String>("source").unwrap())?;
+    let destination = executor.check_path(matches.get_one::<String>("destination").unwrap())?;
+    if !source.is_dir() {
+        bail!("{} is not a directory", source.display());
+    }
+    if destination.exists() && !destination.is_dir() {
+        bail!("{} is not a directory", destination.display());
+    }
+    if source.starts_with(&destination) || destination.starts_with(&source) {
+        bail!(
+            "{} and {} overlap; sync between separate directories",
+            source.display(),
+            destination.display()
+        );
+    }
+    let globs = |id: &str| -> Vec<String> {
+        matches
+            .get_many::<String>(id)
+            .unwrap_or_default()
+            .cloned()
+            .collect()
+    };
+    let (include, exclude) = (globs("include"), globs("exclude"));
+
+    let changes = plan(
+        &source,
+        &destination,
+        &Filter::new(&source, &include, &exclude)?,
+        &Filter::new(&destination, &include, &exclude)?,
+        matches.get_flag("checksum"),
+        matches.get_flag("delete"),
+    )?;
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    } else {
+        print_changes(&changes);
+    }
+    if changes.is_empty() || matches.get_flag("dry-run") {
+        return Ok(());
+    }
+
+    let mut estimate = Estimate::default();
+    for change in &changes {
+        estimate.add(change.bytes);
+    }
+    guardrails::check(
+        executor.config(),
+        "file sync",
+        &estimate,
+        matches.get_flag("yes"),
+    )?;
+    // Copied files pass the content policy before anything changes
+    for change in &changes {
+        if change.action != Action::Delete {
+            let from = source.join(&change.path);
+            let content =
+                fs::read(&from).with_context(|| format!("Failed to read {}", from.display()))?;
+            executor.check_content(destination.join(&change.path), &content)?;
+        }
+    }
+    if executor.config().safety.dry_run {
+        println!("Dry run; no files were changed");
+        return Ok(());
+    }
+
+    let deletions = changes
+        .iter()
+        .filter(|change| change.action == Action::Delete)
+        .count();
+    if deletions > 0
+        && !matches.get_flag("yes")
+        && executor.config().safety.confirm_destructive
+        && !utils::confirm(&format!(
+            "Delete {} files from {} that are not in {}?",
+            deletions,
+            destination.display(),
+            source.display()
+        ))?
+    {
+        println!("Aborted; no files were changed");
+        return Ok(());
+    }
+
+    apply(&source, &destination, &changes)?;
+    let count = |action: Action| changes.iter().filter(|c| c.action == action).count();
+    println!(
+        "Synced {}: {} added, {} updated, {} deleted",
+        destination.display(),
+        count(Action::Add),
+        count(Action::Update),
+        count(Action::Delete)
+    );
+    Ok(())
+}
+
+/// The changes that make `destination` match `source`, sorted by path
+fn plan(
+    source: &Path,
+    destination: &Path,
+    source_filter: &Filter,
+    destination_filter: &Filter,
+    checksum: bool,
+    delete: bool,
+) -> Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    let mut synced = BTreeSet::new();
+    for path in files(source, source_filter)? {
+        let from = source.join(&path);
+        let to = destination.join(&path);
+        let metadata = fs::metadata(&from)?;
+        let action = match fs::symlink_metadata(&to) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Some(Action::Add),
+            Err(e) => return Err(e.into()),
+            Ok(existing) if !existing.is_file() => {
+                bail!(
+                    "{} is in the way of {}; it is not a regular file",
+                    to.display(),
+                    from.display()
+                )
+            }
+            Ok(existing) => {
+                let same = if checksum {
+                    existing.len() == metadata.len() && hash_file(&from)? == hash_file(&to)?
+                } else {
+                    existing.len() == metadata.len()
+                        && seconds(existing.modified()?) == seconds(metadata.modified()?)
+                };
+                (!same).then_some(Action::Update)
+            }
+        };
+        if let Some(action) = action {
+            changes.push(Change {
+                action,
+                path: path.clone(),
+                bytes: metadata.len(),
+            });
+        }
+        synced.insert(path);
+    }
+
+    if delete && destination.is_dir() {
+        for path in files(destination, destination_filter)? {
+            if !synced.contains(&path) {
+                changes.push(Change {
+                    action: Action::Delete,
+                    path,
+                    bytes: 0,
+                });
+            }
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+/// The regular files under `root` that `filter` accepts, relative to `root`
+fn files(root: &Path, filter: &Filter) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_symlink() {
+            warn!("Skipping symbolic link {}", entry.path().display());
+            continue;
+        }
+        if entry.file_type().is_file() && filter.accepts(entry.path()) {
+            files.push(entry.path().strip_prefix(root)?.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+/// Modification times are compared to the second, which every filesystem keeps
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn print_changes(changes: &[Change]) {
+    if changes.is_empty() {
+        println!("Already in sync");
+        return;
+    }
+    for change in changes {
+        println!("{} {}", change.action.symbol(), change.path.display());
+    }
+}
+
+/// Copy the added and updated files and delete the deleted ones, then remove directories
+/// that deletions left empty
+fn apply(source: &Path, destination: &Path, changes: &[Change]) -> Result<()> {
+    let mut emptied = BTreeSet::new();
+    for change in changes {
+        let to = destination.join(&change.path);
+        match change.action {
+            Action::Add | Action::Update => copy(&source.join(&change.path), &to)?,
+            Action::Delete => {
+                fs::remove_file(&to)
+                    .with_context(|| format!("Failed to delete {}", to.display()))?;
+                emptied.extend(to.ancestors().skip(1).map(Path::to_path_buf));
+            }
+        }
+    }
+    // Deepest first, stopping at the destination itself
+    for dir in emptied.iter().rev() {
+        if dir.starts_with(destination)
+            && dir != destination
+            && !source.join(dir.strip_prefix(destination)?).is_dir()
+        {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+    Ok(())
+}
+
+/// Copy `from` to `to` through a temporary file, keeping the modification time
+fn copy(from: &Path, to: &Path) -> Result<()> {
+    let dir = to
+        .parent()
+        .ok_or_else(|| anyhow!("{} has no parent directory", to.display()))?;
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let tmp = dir.join(format!(
+        ".{}.sync",
+        to.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let result = (|| -> Result<()> {
+        fs::copy(from, &tmp)?;
+        let modified = fs::metadata(from)?.modified()?;
+        filetime::set_file_mtime(&tmp, FileTime::from_system_time(modified))?;
+        fs::rename(&tmp, to)?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(anyhow!(
+            "Failed to copy {} to {}: {}",
+            from.display(),
+            to.display(),
+            e
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_plan_and_apply() {
+        let dir = TempDir::new().unwrap();
+        let (source, destination) = (dir.path().join("src"), dir.path().join("dst"));
+        write(&source, "index.html", "<html>");
+        write(&source, "img/logo.png", "png");
+        write(&source, "img/logo.png.map", "map");
+        write(&destination, "old/stale.css", "css");
+        write(&destination, "keep.map", "map");
+
+        let filter = |root: &Path| Filter::new(root, &[], &["*.map".to_string()]).unwrap();
+        let plan_all = |checksum| {
+            plan(
+                &source,
+                &destination,
+                &filter(&source),
+                &filter(&destination),
+                checksum,
+                true,
+            )
+            .unwrap()
+        };
+        let changes = plan_all(false);
+        let summary: Vec<(Action, &str)> = changes
+            .iter()
+            .map(|c| (c.action, c.path.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Action::Add, "img/logo.png"),
+                (Action::Add, "index.html"),
+                (Action::Delete, "old/stale.css"),
+            ]
+        );
+
+        apply(&source, &destination, &changes).unwrap();
+        assert_eq!(
+            fs::read_to_string(destination.join("index.html")).unwrap(),
+            "<html>"
+        );
+        assert!(!destination.join("old").exists());
+        assert!(destination.join("keep.map").exists());
+        assert!(plan_all(false).is_empty());
+
+        // Same size and second, different content: only a checksum notices
+        fs::write(destination.join("index.html"), "<HTML>").unwrap();
+        let modified = fs::metadata(source.join("index.html"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        filetime::set_file_mtime(
+            destination.join("index.html"),
+            FileTime::from_system_time(modified),
+        )
+        .unwrap();
+        assert!(plan_all(false).is_empty());
+        assert_eq!(plan_all(true)[0].action, Action::Update);
+
+        let only_images = Filter::new(&source, &["img/".to_string()], &[]).unwrap();
+        assert!(only_images.accepts(&source.join("img/logo.png")));
+        assert!(!only_images.accepts(&source.join("index.html")));
+    }
+}
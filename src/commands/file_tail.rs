@@ -0,0 +1,480 @@
+// file: src/commands/file_tail.rs
+// version: 1.0.1
+// guid: 8d3f6a1c-2e7b-4c95-b0a4-6f1e9d2c7b58
+
+//! `file tail`: print the end of files and follow what is appended, within a time limit
+//!
+//! Unlike `tail -f`, following always ends: after `--max-duration`, or as soon as a line
+//! matches `--until`, so an agent can wait for "Listening on" without blocking forever.
+//! Files are polled rather than watched. A file that is replaced (log rotation) is read to
+//! its end and then followed under its name again, a truncated file is read from its start,
+//! and a file that does not exist yet is waited for.
+
+use super::export::Effect;
+use crate::executor::Executor;
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use colored::Colorize;
+use regex::Regex;
+use std::fs::{self, File, Metadata};
+use std::io::{self, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Interval at which followed files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Bytes read from the end of a file to find its last lines
+const TAIL_WINDOW: u64 = 1024 * 1024;
+
+/// Build the `file tail` subcommand
+pub fn build_command() -> Command {
+    Command::new("tail")
+        .add(Effect::ReadOnly)
+        .about("Print the last lines of files and optionally follow them")
+        .arg(
+            Arg::new("paths")
+                .value_name("PATH")
+                .num_args(1..)
+                .required(true)
+                .help(
+                    "Files to read; lines are prefixed with the file name when there are several",
+                ),
+        )
+        .arg(
+            Arg::new("lines")
+                .long("lines")
+                .short('n')
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10")
+                .help("Number of lines to print from the end of each file"),
+        )
+        .arg(
+            Arg::new("follow")
+                .long("follow")
+                .short('f')
+                .action(ArgAction::SetTrue)
+                .help("Keep printing appended lines, following rotated and truncated files"),
+        )
+        .arg(
+            Arg::new("max-duration")
+                .long("max-duration")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("300")
+                .help("Stop following after this many seconds"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .value_name("REGEX")
+                .help("Stop following once a line matches; fail if none does in time"),
+        )
+        .arg(
+            Arg::new("grep")
+                .long("grep")
+                .short('g')
+                .value_name("REGEX")
+                .help("Only print lines matching this pattern"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .short('v')
+                .value_name("REGEX")
+                .help("Do not print lines matching this pattern"),
+        )
+        .arg(
+            Arg::new("highlight")
+                .long("highlight")
+                .value_name("REGEX")
+                .action(ArgAction::Append)
+                .help("Highlight matches of this pattern; may be repeated"),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .help("Whether to highlight matches"),
+        )
+}
+
+/// Execute `file tail`
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let paths: Vec<PathBuf> = matches
+        .get_many::<String>("paths")
+        .unwrap_or_default()
+        .map(PathBuf::from)
+        .collect();
+    for path in &paths {
+        executor.check_path(path)?;
+    }
+    let follow = matches.get_flag("follow");
+    let until = matches
+        .get_one::<String>("until")
+        .map(|p| Regex::new(p))
+        .transpose()?;
+    let filter = LineFilter {
+        grep: matches
+            .get_one::<String>("grep")
+            .map(|p| Regex::new(p))
+            .transpose()?,
+        exclude: matches
+            .get_one::<String>("exclude")
+            .map(|p| Regex::new(p))
+            .transpose()?,
+        highlight: matches
+            .get_many::<String>("highlight")
+            .unwrap_or_default()
+            .map(|p| Regex::new(p))
+            .collect::<Result<_, _>>()?,
+    };
+    let color = match matches.get_one::<String>("color").map(String::as_str) {
+        Some("always") => true,
+        Some("never") => false,
+        _ => io::stdout().is_terminal(),
+    };
+    colored::control::set_override(color);
+
+    let lines = *matches.get_one::<usize>("lines").unwrap();
+    let prefixed = paths.len() > 1;
+    let mut out = io::stdout().lock();
+    let mut files = Vec::with_capacity(paths.len());
+    let mut seen = false;
+    for path in &paths {
+        let mut tailed = Tailed::new(path, prefixed);
+        match tailed.open_at_tail(lines, !follow) {
+            Ok(initial) => {
+                for line in initial {
+                    seen |= tailed.emit(&mut out, &line, &filter, until.as_ref())?;
+                }
+            }
+            Err(e) if follow && e.kind() == io::ErrorKind::NotFound => {
+                eprintln!("{}: does not exist yet, waiting for it", path.display());
+            }
+            Err(e) => bail!("Cannot read {}: {}", path.display(), e),
+        }
+        files.push(tailed);
+    }
+    out.flush()?;
+
+    if seen {
+        return Ok(());
+    }
+    if !follow {
+        if until.is_some() {
+            bail!("No line matching --until");
+        }
+        return Ok(());
+    }
+
+    let max_duration = Duration::from_secs(*matches.get_one::<u64>("max-duration").unwrap());
+    let started = Instant::now();
+    while started.elapsed() < max_duration {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        for tailed in &mut files {
+            for event in tailed.poll()? {
+                match event {
+                    Event::Line(line) => {
+                        if tailed.emit(&mut out, &line, &filter, until.as_ref())? {
+                            out.flush()?;
+                            return Ok(());
+                        }
+                    }
+                    Event::Notice(notice) => eprintln!("{}: {}", tailed.path.display(), notice),
+                }
+            }
+        }
+        out.flush()?;
+    }
+
+    // A final line without a newline is complete once following stops
+    for tailed in &mut files {
+        if let Some(line) = tailed.take_partial() {
+            if tailed.emit(&mut out, &line, &filter, until.as_ref())? {
+                return Ok(());
+            }
+        }
+    }
+    out.flush()?;
+    if until.is_some() {
+        bail!(
+            "No line matching --until within {}s",
+            max_duration.as_secs()
+        );
+    }
+    eprintln!(
+        "Stopped following after {}s (--max-duration)",
+        max_duration.as_secs()
+    );
+    Ok(())
+}
+
+/// Which lines are printed and what is highlighted in them
+struct LineFilter {
+    grep: Option<Regex>,
+    exclude: Option<Regex>,
+    highlight: Vec<Regex>,
+}
+
+impl LineFilter {
+    /// The line as printed, or `None` if it is filtered out
+    fn render(&self, line: &str) -> Option<String> {
+        if self.grep.as_ref().is_some_and(|r| !r.is_match(line))
+            || self.exclude.as_ref().is_some_and(|r| r.is_match(line))
+        {
+            return None;
+        }
+
+        // Matches of all patterns, merged, so highlighting never splits an escape sequence
+        let mut ranges: Vec<(usize, usize)> = self
+            .highlight
+            .iter()
+            .flat_map(|r| r.find_iter(line).map(|m| (m.start(), m.end())))
+            .filter(|(start, end)| start < end)
+            .collect();
+        ranges.sort_unstable();
+        let mut rendered = String::with_capacity(line.len());
+        let mut position = 0;
+        for (start, end) in ranges {
+            if end <= position {
+                continue;
+            }
+            let start = start.max(position);
+            rendered.push_str(&line[position..start]);
+            rendered.push_str(&line[start..end].red().bold().to_string());
+            position = end;
+        }
+        rendered.push_str(&line[position..]);
+        Some(rendered)
+    }
+}
+
+/// Something observed while following a file
+#[derive(Debug, PartialEq)]
+enum Event {
+    Line(String),
+    /// Rotation or truncation, reported on stderr
+    Notice(&'static str),
+}
+
+/// A file being tailed
+struct Tailed {
+    path: PathBuf,
+    /// Prefix of printed lines, when several files are tailed
+    prefix: Option<String>,
+    file: Option<File>,
+    identity: Option<Identity>,
+    position: u64,
+    /// Bytes read after the last newline
+    partial: Vec<u8>,
+}
+
+impl Tailed {
+    fn new(path: &Path, prefixed: bool) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            prefix: prefixed.then(|| format!("[{}] ", path.display())),
+            file: None,
+            identity: None,
+            position: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Open the file and return its last `count` lines; a final line without a newline is only
+    /// included with `complete`, otherwise it waits for the rest of the line to be appended
+    fn open_at_tail(&mut self, count: usize, complete: bool) -> io::Result<Vec<String>> {
+        let mut file = File::open(&self.path)?;
+        let metadata = file.metadata()?;
+        let length = metadata.len();
+        let start = length.saturating_sub(TAIL_WINDOW);
+        file.seek(SeekFrom::Start(start))?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        self.identity = identity(&metadata);
+        self.position = start + content.len() as u64;
+        self.file = Some(file);
+
+        let mut lines = self.split_lines(&content);
+        if complete {
+            lines.extend(self.take_partial());
+        }
+        // The first line may have been cut by the window
+        let skip = usize::from(start > 0 && !lines.is_empty());
+        let lines = &lines[skip..];
+        Ok(lines[lines.len().saturating_sub(count)..].to_vec())
+    }
+
+    /// Read what was appended since the last poll, following rotation and truncation
+    fn poll(&mut self) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        let current = fs::metadata(&self.path).ok();
+        let replaced = match (&current, &self.file) {
+            (Some(metadata), Some(_)) => identity(metadata) != self.identity,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if replaced {
+            if self.file.is_some() {
+                // Lines written to the old file before it was rotated away
+                events.extend(self.read_appended()?.into_iter().map(Event::Line));
+                events.extend(self.take_partial().map(Event::Line));
+                events.push(Event::Notice("file was replaced; following the new file"));
+            }
+            let Ok(file) = File::open(&self.path) else {
+                return Ok(events);
+            };
+            self.identity = identity(&file.metadata()?);
+            self.file = Some(file);
+            self.position = 0;
+        } else if current.as_ref().is_some_and(|m| m.len() < self.position) {
+            events.extend(self.take_partial().map(Event::Line));
+            events.push(Event::Notice("file was truncated; reading from its start"));
+            self.position = 0;
+        }
+
+        events.extend(self.read_appended()?.into_iter().map(Event::Line));
+        Ok(events)
+    }
+
+    /// Complete lines appended since the last read
+    fn read_appended(&mut self) -> Result<Vec<String>> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(Vec::new());
+        };
+        file.seek(SeekFrom::Start(self.position))?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        self.position += content.len() as u64;
+        Ok(self.split_lines(&content))
+    }
+
+    /// Split off complete lines, keeping a trailing partial line for the next read
+    fn split_lines(&mut self, content: &[u8]) -> Vec<String> {
+        self.partial.extend_from_slice(content);
+        let Some(last_newline) = self.partial.iter().rposition(|b| *b == b'\n') else {
+            return Vec::new();
+        };
+        let rest = self.partial.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        String::from_utf8_lossy(&complete)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn take_partial(&mut self) -> Option<String> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        let partial = std::mem::take(&mut self.partial);
+        Some(String::from_utf8_lossy(&partial).to_string())
+    }
+
+    /// Print a line if it passes the filter; returns whether it matches `until`
+    fn emit(
+        &self,
+        out: &mut impl Write,
+        line: &str,
+        filter: &LineFilter,
+        until: Option<&Regex>,
+    ) -> Result<bool> {
+        if let Some(rendered) = filter.render(line) {
+            writeln!(out, "{}{}", self.prefix.as_deref().unwrap_or(""), rendered)?;
+        }
+        Ok(until.is_some_and(|r| r.is_match(line)))
+    }
+}
+
+/// What tells a replaced file from the same file grown or truncated
+#[cfg(unix)]
+type Identity = (u64, u64);
+#[cfg(not(unix))]
+type Identity = std::time::SystemTime;
+
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> Option<Identity> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(metadata: &Metadata) -> Option<Identity> {
+    metadata.created().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follow_rotation_truncation_and_partial_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "one\ntwo\nthree\npart").unwrap();
+
+        let mut tailed = Tailed::new(&path, false);
+        assert_eq!(tailed.open_at_tail(2, false).unwrap(), vec!["two", "three"]);
+        assert_eq!(
+            Tailed::new(&path, false).open_at_tail(2, true).unwrap(),
+            vec!["three", "part"]
+        );
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"ial\nfour\n").unwrap();
+        let lines = |events: Vec<Event>| -> Vec<String> {
+            events
+                .into_iter()
+                .filter_map(|e| match e {
+                    Event::Line(line) => Some(line),
+                    Event::Notice(_) => None,
+                })
+                .collect()
+        };
+        assert_eq!(lines(tailed.poll().unwrap()), vec!["partial", "four"]);
+
+        // Rotation: the old file keeps its late lines, the new one is read from its start
+        file.write_all(b"late\n").unwrap();
+        fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        fs::write(&path, "fresh\n").unwrap();
+        let events = tailed.poll().unwrap();
+        assert!(events.contains(&Event::Notice("file was replaced; following the new file")));
+        assert_eq!(lines(events), vec!["late", "fresh"]);
+
+        fs::write(&path, "").unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"new\n")
+            .unwrap();
+        assert_eq!(lines(tailed.poll().unwrap()), vec!["new"]);
+    }
+
+    #[test]
+    fn test_filter_and_highlight() {
+        colored::control::set_override(false);
+        let filter = LineFilter {
+            grep: Some(Regex::new("ERROR|WARN").unwrap()),
+            exclude: Some(Regex::new("ignored").unwrap()),
+            highlight: vec![Regex::new("ERROR").unwrap(), Regex::new("RR").unwrap()],
+        };
+        assert_eq!(filter.render("INFO ready"), None);
+        assert_eq!(filter.render("WARN ignored"), None);
+        assert_eq!(
+            filter.render("ERROR failed").as_deref(),
+            Some("ERROR failed")
+        );
+
+        colored::control::set_override(true);
+        let rendered = filter.render("x ERROR y").unwrap();
+        assert!(rendered.starts_with("x \u{1b}["));
+        assert!(rendered.ends_with(" y"));
+        assert_eq!(rendered.matches("\u{1b}[0m").count(), 1);
+        colored::control::unset_override();
+    }
+}
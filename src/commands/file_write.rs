@@ -0,0 +1,151 @@
+// file: src/commands/file_write.rs
+// version: 1.2.1
+// guid: c4f1a8e2-6d3b-4b9e-8f7a-2e5d0c9b1a36
+
+//! `file write`: write content given on the command line or stdin to a file
+//!
+//! Agents use this instead of shell redirection. The target must lie inside the workspace
+//! roots and the content must pass the content policy; in dry-run mode nothing is written and
+//! the change is shown as a diff instead.
+
+use super::export::Effect;
+use super::file_diff;
+use crate::executor::Executor;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use tracing::info;
+
+/// Build the `file write` subcommand
+pub fn build_command() -> Command {
+    Command::new("write")
+        .add(Effect::Destructive)
+        .about("Write content from an argument or stdin to a file")
+        .arg(
+            Arg::new("path")
+                .value_name("PATH")
+                .required(true)
+                .help("File to write"),
+        )
+        .arg(
+            Arg::new("content")
+                .long("content")
+                .value_name("TEXT")
+                .help("Content to write (default: read from stdin)"),
+        )
+        .arg(
+            Arg::new("append")
+                .long("append")
+                .short('a')
+                .action(ArgAction::SetTrue)
+                .help("Append instead of replacing the file"),
+        )
+        .arg(
+            Arg::new("create-dirs")
+                .long("create-dirs")
+                .action(ArgAction::SetTrue)
+                .help("Create missing parent directories"),
+        )
+}
+
+/// Execute `file write`
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let path = Path::new(matches.get_one::<String>("path").unwrap());
+    executor.check_path(path)?;
+
+    let content = match matches.get_one::<String>("content") {
+        Some(text) => text.clone().into_bytes(),
+        None => {
+            let mut buffer = Vec::new();
+            io::stdin().read_to_end(&mut buffer)?;
+            buffer
+        }
+    };
+    let append = matches.get_flag("append");
+
+    // Appended content is checked together with what it joins, since a match can span both
+    let checked = if append && path.is_file() {
+        let mut existing = fs::read(path)?;
+        existing.extend_from_slice(&content);
+        existing
+    } else {
+        content.clone()
+    };
+    executor.check_content(path, &checked)?;
+
+    if executor.config().safety.dry_run {
+        println!(
+            "DRY RUN: Would {} {} bytes to {}",
+            if append { "append" } else { "write" },
+            content.len(),
+            path.display()
+        );
+        let mut result = if append { fs::read(path).unwrap_or_default() } else { Vec::new() };
+        result.extend_from_slice(&content);
+        file_diff::print_preview(path, &result);
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if !parent.exists() {
+            if !matches.get_flag("create-dirs") {
+                return Err(anyhow!(
+                    "Directory {} does not exist; pass --create-dirs to create it",
+                    parent.display()
+                ));
+            }
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)?;
+    file.write_all(&content)?;
+    println!("Wrote {} bytes to {}", content.len(), path.display());
+    Ok(())
+}
+
+/// Write an edited version of an existing file after the content checks, printing the diff
+/// when `show_diff` is set; in dry-run mode the diff is printed and nothing is written
+pub(crate) fn update(
+    executor: &Executor,
+    path: &Path,
+    content: &[u8],
+    show_diff: bool,
+) -> Result<()> {
+    executor.check_content(path, content)?;
+    if executor.config().safety.dry_run {
+        println!("DRY RUN: Would update {}", path.display());
+        file_diff::print_preview(path, content);
+        return Ok(());
+    }
+    if show_diff {
+        file_diff::print_preview(path, content);
+    }
+    replace_file(path, content)?;
+    info!("Updated {}", path.display());
+    Ok(())
+}
+
+/// Replace an existing file atomically: the content goes to a temporary file in the same
+/// directory, which takes the old file's permissions and is then renamed over it
+pub(crate) fn replace_file(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(content)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(file.path(), metadata.permissions())?;
+    }
+    file.persist(path)
+        .map_err(|e| anyhow!("Failed to replace {}: {}", path.display(), e.error))?;
+    Ok(())
+}
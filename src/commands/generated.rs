@@ -0,0 +1,232 @@
+// file: src/commands/generated.rs
+// version: 1.0.1
+// guid: 9b3d62f0-5c17-4e8a-a4f1-7e20c8d5b693
+
+//! `generated` command: list, check and clean the files recorded in the generated-files
+//! registry (see [`crate::generated`])
+
+use super::export::Effect;
+use crate::executor::Executor;
+use crate::generated::{ArtifactState, Registry, REGISTRY_FILE};
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Build the generated command
+pub fn build_command() -> Command {
+    Command::new("generated")
+        .about("List, check and clean files generated by this utility")
+        .long_about(
+            "Files written by 'buf generate' and 'scaffold new' are recorded in \
+             .safe-ai-util-generated.json at the workspace root with the source they were \
+             generated from and a hash of their content. 'generated check' fails when one was \
+             edited by hand or removed, and 'generated clean' removes only recorded files.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("List generated files with their source and generator")
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("check")
+                .add(Effect::ReadOnly)
+                .about("Fail if a generated file was edited or removed since it was generated")
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("clean")
+                .add(Effect::Destructive)
+                .about("Remove generated files that are unchanged since they were generated")
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Also remove generated files that were edited by hand"),
+                ),
+        )
+        .subcommand(
+            Command::new("record")
+                .about("Record files written by another generator")
+                .arg(
+                    Arg::new("files")
+                        .value_name("FILE")
+                        .num_args(1..)
+                        .required(true)
+                        .help("Generated files"),
+                )
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .value_name("SOURCE")
+                        .required(true)
+                        .help("What the files were generated from, e.g. api/openapi.yaml"),
+                )
+                .arg(
+                    Arg::new("generator")
+                        .long("generator")
+                        .value_name("COMMAND")
+                        .required(true)
+                        .help("Command that generated the files, e.g. 'openapi-generator generate'"),
+                ),
+        )
+}
+
+fn format_arg() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .short('f')
+        .value_parser(["text", "json"])
+        .default_value("text")
+        .help("Output format")
+}
+
+/// Execute the generated command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let base = executor.watch_scope(&[])?.base;
+    let mut registry = Registry::load(&base)?;
+    match matches.subcommand() {
+        Some(("list", sub_matches)) => list(&registry, is_json(sub_matches)),
+        Some(("check", sub_matches)) => check(&registry, is_json(sub_matches)),
+        Some(("clean", sub_matches)) => clean(&mut registry, sub_matches, executor),
+        Some(("record", sub_matches)) => record(&mut registry, sub_matches, executor),
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+fn is_json(matches: &ArgMatches) -> bool {
+    matches.get_one::<String>("format").map(String::as_str) == Some("json")
+}
+
+fn list(registry: &Registry, json: bool) -> Result<()> {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({ "artifacts": registry.artifacts() }))?
+        );
+        return Ok(());
+    }
+    if registry.artifacts().is_empty() {
+        println!("No generated files recorded in {}", REGISTRY_FILE);
+    }
+    for (path, artifact) in registry.artifacts() {
+        println!(
+            "{}  (from {} by '{}')",
+            path, artifact.source, artifact.generator
+        );
+    }
+    Ok(())
+}
+
+fn check(registry: &Registry, json: bool) -> Result<()> {
+    let states = registry.check();
+    let changed: Vec<&(String, ArtifactState)> = states
+        .iter()
+        .filter(|(_, state)| *state != ArtifactState::Unchanged)
+        .collect();
+    if json {
+        let files: Vec<_> = states
+            .iter()
+            .map(|(path, state)| json!({ "path": path, "state": state }))
+            .collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({ "ok": changed.is_empty(), "files": files }))?
+        );
+    } else {
+        for (path, state) in &changed {
+            let state = match state {
+                ArtifactState::Modified => "modified",
+                _ => "missing",
+            };
+            println!("  {:<9}{}", state, path);
+        }
+    }
+    if !changed.is_empty() {
+        bail!(
+            "{} of {} generated files were edited or removed since they were generated; \
+             regenerate them or record the edit with 'generated record'",
+            changed.len(),
+            states.len()
+        );
+    }
+    if !json {
+        println!("All {} generated files are unchanged", states.len());
+    }
+    Ok(())
+}
+
+fn clean(registry: &mut Registry, matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let force = matches.get_flag("force");
+    let dry_run = executor.config().safety.dry_run;
+    let base = executor.watch_scope(&[])?.base;
+    let mut removed = 0;
+    let mut kept = Vec::new();
+    for (path, state) in registry.check() {
+        if state == ArtifactState::Modified && !force {
+            kept.push(path);
+            continue;
+        }
+        if dry_run {
+            println!("DRY RUN: Would remove {}", path);
+            continue;
+        }
+        if state != ArtifactState::Missing {
+            let file = executor.check_path(registry.resolve(&path))?;
+            fs::remove_file(&file)?;
+            remove_empty_parents(&file, &base);
+            println!("Removed {}", path);
+            removed += 1;
+        }
+        registry.forget(&path);
+    }
+    if !dry_run {
+        registry.save()?;
+        println!("Removed {} generated files", removed);
+    }
+    if !kept.is_empty() {
+        println!(
+            "Kept {} generated files edited by hand (use --force to remove them):",
+            kept.len()
+        );
+        for path in &kept {
+            println!("  {}", path);
+        }
+    }
+    Ok(())
+}
+
+fn record(registry: &mut Registry, matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let source = matches.get_one::<String>("source").unwrap();
+    let generator = matches.get_one::<String>("generator").unwrap();
+    let files: Vec<PathBuf> = matches
+        .get_many::<String>("files")
+        .unwrap_or_default()
+        .map(|file| executor.check_path(file))
+        .collect::<Result<_>>()?;
+    if executor.config().safety.dry_run {
+        println!("DRY RUN: Would record {} generated files", files.len());
+        return Ok(());
+    }
+    for file in &files {
+        let path = registry.record(file, source, generator)?;
+        println!("Recorded {}", path);
+    }
+    registry.save()?;
+    Ok(())
+}
+
+/// Remove the directories above `file` that are left empty, stopping at `base`
+fn remove_empty_parents(file: &Path, base: &Path) {
+    let mut dir = file.parent();
+    while let Some(current) = dir {
+        if current == base || !current.starts_with(base) || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
@@ -0,0 +1,503 @@
+// file: src/commands/gh.rs
+// version: 1.0.2
+// guid: 6f2b9d84-3c1e-4a57-b0d6-e95a7c21f438
+
+//! `gh` command: pull requests, issues, workflow runs and release assets with the GitHub CLI
+//!
+//! `pr create`, `pr view` and `pr checkout`, `issue list` and `issue create`, `run watch` and
+//! `release upload` run gh without prompts: bodies are handed to gh on standard input, since
+//! they span lines, and body files and uploaded assets must lie inside the workspace roots.
+//! `pr checkout` asks first while the work tree has uncommitted changes, and
+//! `release upload --clobber` before it replaces assets.
+//!
+//! Deleting a repository or changing its visibility is refused for every gh invocation, not
+//! just these (see [`crate::security::validator`]). GitHub tokens in arguments are redacted
+//! before they are logged (see [`audit::redact_tokens`]), and so are those in the errors of
+//! `pr create` and `issue create`, whose output is captured.
+
+use super::export::{Effect, Remote};
+use crate::batch::{CommandSpec, InputSource};
+use crate::executor::Executor;
+use crate::security::audit;
+use crate::utils;
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Build the gh command
+pub fn build_command() -> Command {
+    Command::new("gh")
+        .add(Remote)
+        .about("Work with GitHub pull requests, issues, runs and releases")
+        .long_about(
+            "Run common GitHub CLI workflows without prompts. pr checkout asks first while the \
+             work tree has uncommitted changes and release upload --clobber before it replaces \
+             assets. Deleting repositories and changing their visibility is never allowed.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("repo")
+                .long("repo")
+                .short('R')
+                .value_name("OWNER/REPO")
+                .global(true)
+                .help("Repository to use (default: the one of the current directory)"),
+        )
+        .subcommand(
+            Command::new("pr")
+                .about("Create, view and check out pull requests")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Open a pull request for the current branch")
+                        .arg(title().required_unless_present("fill"))
+                        .arg(body())
+                        .arg(body_file())
+                        .arg(
+                            Arg::new("base")
+                                .long("base")
+                                .short('B')
+                                .value_name("BRANCH")
+                                .help("Branch to merge into (default: the default branch)"),
+                        )
+                        .arg(
+                            Arg::new("head")
+                                .long("head")
+                                .short('H')
+                                .value_name("BRANCH")
+                                .help("Branch with the changes (default: the current branch)"),
+                        )
+                        .arg(
+                            Arg::new("draft")
+                                .long("draft")
+                                .short('d')
+                                .action(ArgAction::SetTrue)
+                                .help("Open the pull request as a draft"),
+                        )
+                        .arg(
+                            Arg::new("fill")
+                                .long("fill")
+                                .action(ArgAction::SetTrue)
+                                .help("Take the title and body from the commits"),
+                        )
+                        .arg(labels())
+                        .arg(
+                            Arg::new("reviewer")
+                                .long("reviewer")
+                                .short('r')
+                                .value_name("LOGIN")
+                                .action(ArgAction::Append)
+                                .help("Request a review; repeat for several"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("view")
+                        .add(Effect::ReadOnly)
+                        .about("Show a pull request")
+                        .arg(
+                            Arg::new("pr")
+                                .value_name("NUMBER|BRANCH")
+                                .help("Pull request (default: the one of the current branch)"),
+                        )
+                        .arg(
+                            Arg::new("comments")
+                                .long("comments")
+                                .short('c')
+                                .action(ArgAction::SetTrue)
+                                .help("Include the comments"),
+                        )
+                        .arg(json()),
+                )
+                .subcommand(
+                    Command::new("checkout")
+                        .add(Effect::Destructive)
+                        .about("Check out the branch of a pull request")
+                        .arg(
+                            Arg::new("pr")
+                                .value_name("NUMBER|BRANCH")
+                                .required(true)
+                                .help("Pull request to check out"),
+                        )
+                        .arg(
+                            Arg::new("branch")
+                                .long("branch")
+                                .short('b')
+                                .value_name("NAME")
+                                .help("Local branch name (default: the head branch)"),
+                        )
+                        .arg(yes("Check out while the work tree has uncommitted changes")),
+                ),
+        )
+        .subcommand(
+            Command::new("issue")
+                .about("List and open issues")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .add(Effect::ReadOnly)
+                        .about("List issues")
+                        .arg(
+                            Arg::new("state")
+                                .long("state")
+                                .short('s')
+                                .value_parser(["open", "closed", "all"])
+                                .help("Only issues in this state (default: open)"),
+                        )
+                        .arg(labels())
+                        .arg(
+                            Arg::new("assignee")
+                                .long("assignee")
+                                .short('a')
+                                .value_name("LOGIN")
+                                .help("Only issues assigned to LOGIN"),
+                        )
+                        .arg(
+                            Arg::new("author")
+                                .long("author")
+                                .short('A')
+                                .value_name("LOGIN")
+                                .help("Only issues opened by LOGIN"),
+                        )
+                        .arg(
+                            Arg::new("search")
+                                .long("search")
+                                .short('S')
+                                .value_name("QUERY")
+                                .help("Only issues matching a GitHub search query"),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .long("limit")
+                                .short('L')
+                                .value_name("N")
+                                .value_parser(clap::value_parser!(u32))
+                                .help("At most N issues (default: 30)"),
+                        )
+                        .arg(json()),
+                )
+                .subcommand(
+                    Command::new("create")
+                        .about("Open an issue")
+                        .arg(title().required(true))
+                        .arg(body())
+                        .arg(body_file())
+                        .arg(labels())
+                        .arg(
+                            Arg::new("assignee")
+                                .long("assignee")
+                                .short('a')
+                                .value_name("LOGIN")
+                                .action(ArgAction::Append)
+                                .help("Assign the issue; repeat for several"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Follow workflow runs")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("watch")
+                        .add(Effect::ReadOnly)
+                        .about("Wait for a workflow run to finish, showing its progress")
+                        .arg(
+                            Arg::new("run")
+                                .value_name("RUN_ID")
+                                .required(true)
+                                .help("ID of the run, as shown in its URL"),
+                        )
+                        .arg(
+                            Arg::new("exit-status")
+                                .long("exit-status")
+                                .action(ArgAction::SetTrue)
+                                .help("Fail if the run fails"),
+                        )
+                        .arg(
+                            Arg::new("interval")
+                                .long("interval")
+                                .short('i')
+                                .value_name("SECONDS")
+                                .value_parser(clap::value_parser!(u32))
+                                .help("Seconds between refreshes (default: 3)"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("release")
+                .about("Manage release assets")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("upload")
+                        .add(Effect::Destructive)
+                        .about("Upload files as assets of a release")
+                        .arg(
+                            Arg::new("tag")
+                                .value_name("TAG")
+                                .required(true)
+                                .help("Tag of the release"),
+                        )
+                        .arg(
+                            Arg::new("files")
+                                .value_name("FILE")
+                                .action(ArgAction::Append)
+                                .required(true)
+                                .help("Files to upload, optionally FILE#LABEL"),
+                        )
+                        .arg(
+                            Arg::new("clobber")
+                                .long("clobber")
+                                .action(ArgAction::SetTrue)
+                                .help("Replace assets with the same name"),
+                        )
+                        .arg(yes("Replace assets without asking")),
+                ),
+        )
+}
+
+fn title() -> Arg {
+    Arg::new("title")
+        .long("title")
+        .short('t')
+        .value_name("TEXT")
+        .help("Title")
+}
+
+fn body() -> Arg {
+    Arg::new("body")
+        .long("body")
+        .short('b')
+        .value_name("TEXT")
+        .conflicts_with("body-file")
+        .help("Body text")
+}
+
+fn body_file() -> Arg {
+    Arg::new("body-file")
+        .long("body-file")
+        .short('F')
+        .value_name("FILE")
+        .help("Read the body from FILE")
+}
+
+fn labels() -> Arg {
+    Arg::new("label")
+        .long("label")
+        .short('l')
+        .value_name("NAME")
+        .action(ArgAction::Append)
+        .help("Label; repeat for several")
+}
+
+fn json() -> Arg {
+    Arg::new("json")
+        .long("json")
+        .value_name("FIELDS")
+        .help("Print these comma-separated fields as JSON")
+}
+
+fn yes(help: &'static str) -> Arg {
+    Arg::new("yes")
+        .long("yes")
+        .short('y')
+        .action(ArgAction::SetTrue)
+        .help(help)
+}
+
+/// Execute the gh command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let (group, group_matches) = matches.subcommand().expect("subcommand required");
+    let (action, sub_matches) = group_matches.subcommand().expect("subcommand required");
+    let mut args = vec![group.to_string(), action.to_string()];
+    args.extend(gh_args(sub_matches));
+    if let Some(repo) = matches.get_one::<String>("repo") {
+        args.push("--repo".to_string());
+        args.push(repo.clone());
+    }
+
+    match (group, action) {
+        ("pr", "create") | ("issue", "create") => return create(sub_matches, args, executor).await,
+        ("pr", "checkout") if !confirm_checkout(sub_matches, executor).await? => {
+            println!("Aborted; nothing was checked out");
+            return Ok(());
+        }
+        ("release", "upload") => {
+            for file in sub_matches.get_many::<String>("files").unwrap_or_default() {
+                // gh takes FILE#LABEL to give the asset a display label
+                check_file(executor, file.split('#').next().unwrap_or(file))?;
+            }
+            let tag = sub_matches.get_one::<String>("tag").unwrap();
+            if sub_matches.get_flag("clobber")
+                && !sub_matches.get_flag("yes")
+                && executor.config().safety.confirm_destructive
+                && !utils::confirm(&format!(
+                    "Replace assets of release {} that have the same name?",
+                    tag
+                ))?
+            {
+                println!("Aborted; nothing was uploaded");
+                return Ok(());
+            }
+        }
+        _ => {}
+    }
+    executor.execute_secure("gh", &args).await
+}
+
+/// Open a pull request or issue, handing gh the body on standard input since it spans lines
+async fn create(matches: &ArgMatches, mut args: Vec<String>, executor: &Executor) -> Result<()> {
+    let fill = matches.try_get_one::<bool>("fill").ok().flatten() == Some(&true);
+    let stdin = if let Some(file) = matches.get_one::<String>("body-file") {
+        Some(InputSource::File { file: file.into() })
+    } else if let Some(text) = matches.get_one::<String>("body") {
+        Some(InputSource::Text { text: text.clone() })
+    } else if !fill {
+        // Without a body gh would open an editor for it
+        Some(InputSource::Text {
+            text: String::new(),
+        })
+    } else {
+        None
+    };
+    if stdin.is_some() {
+        args.extend(["--body-file".to_string(), "-".to_string()]);
+    }
+
+    let output = executor
+        .execute_spec_capture(&CommandSpec {
+            command: "gh".to_string(),
+            args,
+            cwd: None,
+            env: BTreeMap::new(),
+            stdin,
+        })
+        .await?;
+    print!("{}", output.stdout);
+    if !output.success() {
+        // gh repeats tokens it was given, e.g. in authentication errors
+        bail!("gh failed: {}", audit::redact_tokens(output.stderr.trim()));
+    }
+    eprint!("{}", audit::redact_tokens(&output.stderr));
+    Ok(())
+}
+
+/// Positional arguments and options of a gh subcommand, in gh's own spelling
+fn gh_args(matches: &ArgMatches) -> Vec<String> {
+    let mut args = Vec::new();
+    for id in ["pr", "run", "tag", "files"] {
+        if let Some(values) = matches.try_get_many::<String>(id).ok().flatten() {
+            args.extend(values.cloned());
+        }
+    }
+    let options = [
+        "title", "base", "head", "branch", "state", "assignee", "author", "search", "json",
+    ];
+    for id in options.iter().chain(&["label", "reviewer"]) {
+        if let Some(values) = matches.try_get_many::<String>(id).ok().flatten() {
+            for value in values {
+                args.push(format!("--{}", id));
+                args.push(value.clone());
+            }
+        }
+    }
+    for id in ["limit", "interval"] {
+        if let Some(value) = matches.try_get_one::<u32>(id).ok().flatten() {
+            args.push(format!("--{}", id));
+            args.push(value.to_string());
+        }
+    }
+    for id in ["draft", "fill", "comments", "exit-status", "clobber"] {
+        if matches.try_get_one::<bool>(id).ok().flatten() == Some(&true) {
+            args.push(format!("--{}", id));
+        }
+    }
+    args
+}
+
+/// Refuse files gh would upload from outside the workspace, or that are not files
+fn check_file(executor: &Executor, file: &str) -> Result<()> {
+    executor.sandbox().check_path(Path::new(file))?;
+    if !Path::new(file).is_file() {
+        bail!("{} is not a file", file);
+    }
+    Ok(())
+}
+
+/// Whether `gh pr checkout` may run: at once unless the work tree has uncommitted changes,
+/// which the checkout would carry over to the pull request's branch or fail on
+async fn confirm_checkout(matches: &ArgMatches, executor: &Executor) -> Result<bool> {
+    if matches.get_flag("yes") || !executor.config().safety.confirm_destructive {
+        return Ok(true);
+    }
+    let status = executor
+        .execute_secure_capture("git", &["status", "--porcelain", "--untracked-files=no"])
+        .await;
+    let dirty = status.is_ok_and(|status| status.success() && !status.stdout.trim().is_empty());
+    if !dirty {
+        return Ok(true);
+    }
+    Ok(utils::confirm(
+        "The work tree has uncommitted changes; check out the pull request anyway?",
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command_line: &[&str]) -> Vec<String> {
+        let matches = build_command()
+            .try_get_matches_from(std::iter::once("gh").chain(command_line.iter().copied()))
+            .unwrap();
+        let (_, group_matches) = matches.subcommand().unwrap();
+        let (_, sub_matches) = group_matches.subcommand().unwrap();
+        gh_args(sub_matches)
+    }
+
+    #[test]
+    fn test_gh_args() {
+        assert_eq!(
+            args(&[
+                "pr", "create", "--title", "Fix", "--draft", "-l", "bug", "-l", "ci", "-B", "main"
+            ]),
+            ["--title", "Fix", "--base", "main", "--label", "bug", "--label", "ci", "--draft"]
+        );
+        assert_eq!(
+            args(&[
+                "issue",
+                "list",
+                "--state",
+                "all",
+                "-L",
+                "5",
+                "--json",
+                "number,title"
+            ]),
+            ["--state", "all", "--json", "number,title", "--limit", "5"]
+        );
+        assert_eq!(
+            args(&[
+                "release",
+                "upload",
+                "v1.2.0",
+                "a.tgz",
+                "b.zip#Windows",
+                "--clobber"
+            ]),
+            ["v1.2.0", "a.tgz", "b.zip#Windows", "--clobber"]
+        );
+        assert_eq!(
+            args(&["run", "watch", "42", "--exit-status"]),
+            ["42", "--exit-status"]
+        );
+
+        // A pull request needs a title unless it is taken from the commits
+        assert!(build_command()
+            .try_get_matches_from(["gh", "pr", "create"])
+            .is_err());
+        assert!(build_command()
+            .try_get_matches_from(["gh", "pr", "create", "--fill", "-R", "o/r"])
+            .is_ok());
+    }
+}
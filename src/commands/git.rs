@@ -1,13 +1,15 @@
 // file: src/commands/git.rs
-// version: 2.1.0
+// version: 2.4.1
 // guid: be0736f7-2054-4b57-82f1-b7985d18c552
 
+use super::export::{Effect, Remote};
+use super::{git_merge, git_stack};
 use crate::executor::Executor;
+use crate::intent;
 use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches, Command};
 use std::env;
 
-
 /// Build the git command with comprehensive subcommands
 pub fn build_command() -> Command {
     Command::new("git")
@@ -61,6 +63,8 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("push")
+                .add(Effect::Destructive)
+                .add(Remote)
                 .about("Update remote refs along with associated objects")
                 .arg(Arg::new("remote")
                     .help("Remote name")
@@ -84,6 +88,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("pull")
+                .add(Remote)
                 .about("Fetch from and integrate with another repository or branch")
                 .arg(Arg::new("remote")
                     .help("Remote name")
@@ -102,6 +107,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("status")
+                .add(Effect::ReadOnly)
                 .about("Show the working tree status")
                 .arg(Arg::new("short")
                     .short('s')
@@ -146,6 +152,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("checkout")
+                .add(Effect::Destructive)
                 .about("Switch branches or restore working tree files")
                 .arg(Arg::new("branch")
                     .help("Branch or commit to checkout")
@@ -178,6 +185,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("rebase")
+                .add(Effect::Destructive)
                 .about("Reapply commits on top of another base tip")
                 .arg(Arg::new("upstream")
                     .help("Upstream branch"))
@@ -197,6 +205,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("reset")
+                .add(Effect::Destructive)
                 .about("Reset current HEAD to the specified state")
                 .arg(Arg::new("commit")
                     .help("Commit to reset to"))
@@ -215,6 +224,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("log")
+                .add(Effect::ReadOnly)
                 .about("Show commit logs")
                 .arg(Arg::new("oneline")
                     .long("oneline")
@@ -236,6 +246,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("diff")
+                .add(Effect::ReadOnly)
                 .about("Show changes between commits, commit and working tree, etc")
                 .arg(Arg::new("cached")
                     .long("cached")
@@ -256,6 +267,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("stash")
+                .add(Effect::Destructive)
                 .about("Stash changes in a dirty working directory")
                 .subcommand(Command::new("push")
                     .about("Save current changes")
@@ -266,6 +278,7 @@ pub fn build_command() -> Command {
                 .subcommand(Command::new("pop")
                     .about("Apply and remove stash"))
                 .subcommand(Command::new("list")
+                    .add(Effect::ReadOnly)
                     .about("List all stashes"))
                 .subcommand(Command::new("drop")
                     .about("Delete a stash")
@@ -291,6 +304,7 @@ pub fn build_command() -> Command {
                         .help("Remote name")
                         .required(true)))
                 .subcommand(Command::new("list")
+                    .add(Effect::ReadOnly)
                     .about("List remotes")
                     .arg(Arg::new("verbose")
                         .short('v')
@@ -322,6 +336,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("clone")
+                .add(Remote)
                 .about("Clone a repository into a new directory")
                 .arg(Arg::new("url")
                     .help("Repository URL")
@@ -340,6 +355,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("fetch")
+                .add(Remote)
                 .about("Download objects and refs from another repository")
                 .arg(Arg::new("remote")
                     .help("Remote name")
@@ -364,6 +380,8 @@ pub fn build_command() -> Command {
                     .help("Create bare repository")
                     .action(clap::ArgAction::SetTrue))
         )
+        .subcommand(git_stack::build_command())
+        .subcommand(git_merge::build_command())
 }
 
 /// Execute git commands with comprehensive subcommand support
@@ -387,6 +405,8 @@ pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
         Some(("clone", sub_matches)) => execute_clone(sub_matches, executor).await,
         Some(("fetch", sub_matches)) => execute_fetch(sub_matches, executor).await,
         Some(("init", sub_matches)) => execute_init(sub_matches, executor).await,
+        Some(("stack", sub_matches)) => git_stack::execute(sub_matches, executor).await,
+        Some(("merge-driver", sub_matches)) => git_merge::execute(sub_matches, executor).await,
         _ => Err(anyhow!("Unknown git subcommand")),
     }
 }
@@ -455,6 +475,9 @@ async fn execute_commit(matches: &ArgMatches, executor: &Executor) -> Result<()>
         args.push("-s".to_string());
     }
 
+    // Record why the commit was made for whoever reviews it
+    args.extend(intent::commit_trailer_args());
+
     executor.execute_secure("git", &args).await
 }
 
@@ -730,10 +753,8 @@ async fn execute_remote(matches: &ArgMatches, executor: &Executor) -> Result<()>
                 args.push(name.clone());
             }
         }
-        Some(("list", sub_matches)) => {
-            if sub_matches.get_flag("verbose") {
-                args.push("-v".to_string());
-            }
+        Some(("list", sub_matches)) if sub_matches.get_flag("verbose") => {
+            args.push("-v".to_string());
         }
         _ => {
             // Default remote list
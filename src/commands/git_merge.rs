@@ -0,0 +1,216 @@
+// file: src/commands/git_merge.rs
+// version: 1.0.0
+// guid: 0a6e4d2b-93c5-4f18-b7e1-5d8c2f9a3b64
+
+//! Merge drivers for `git merge-driver`
+//!
+//! Git hands a merge driver three temporary files, the common ancestor (`%O`), ours (`%A`)
+//! and theirs (`%B`), and expects the result in ours, exiting non-zero when conflicts are
+//! left. `git merge-driver install` registers the drivers in `.git/config` and assigns them to
+//! files in `.gitattributes`:
+//!
+//! ```text
+//! Cargo.lock merge=safe-ai-util-cargo-lock
+//! package-lock.json merge=safe-ai-util-package-lock
+//! gen/api/v1/api.pb.go merge=safe-ai-util-generated
+//! ```
+//!
+//! Lockfiles are merged entry by entry (see [`crate::merge`]). Generated files are
+//! regenerated with the command recorded for them in the generated-files registry. When a
+//! driver cannot resolve the conflict, it falls back to `git merge-file`, leaving the usual
+//! conflict markers.
+
+use crate::executor::Executor;
+use crate::generated::Registry;
+use crate::merge;
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// File kinds with a merge driver
+const KINDS: &[(&str, &str)] = &[
+    ("cargo-lock", "Merge Cargo.lock packages"),
+    ("package-lock", "Merge package-lock.json entries"),
+    ("generated", "Regenerate generated files with their recorded generator"),
+];
+
+/// Build the `git merge-driver` subcommand
+pub fn build_command() -> Command {
+    let mut command = Command::new("merge-driver")
+        .about("Content-aware merge drivers for lockfiles and generated code")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("install")
+                .about("Register the merge drivers in .git/config and .gitattributes")
+                .arg(Arg::new("kind")
+                    .long("kind")
+                    .value_name("KIND")
+                    .value_delimiter(',')
+                    .action(ArgAction::Append)
+                    .value_parser(KINDS.iter().map(|(kind, _)| *kind).collect::<Vec<_>>())
+                    .help("Drivers to install (default: all)"))
+        );
+    for (kind, about) in KINDS {
+        command = command.subcommand(
+            Command::new(*kind)
+                .about(*about)
+                .arg(Arg::new("base")
+                    .value_name("BASE")
+                    .required(true)
+                    .help("Common ancestor (%O)"))
+                .arg(Arg::new("ours")
+                    .value_name("OURS")
+                    .required(true)
+                    .help("Our version, replaced by the result (%A)"))
+                .arg(Arg::new("theirs")
+                    .value_name("THEIRS")
+                    .required(true)
+                    .help("Their version (%B)"))
+                .arg(Arg::new("path")
+                    .value_name("PATH")
+                    .required(*kind == "generated")
+                    .help("Path of the file being merged (%P)"))
+        );
+    }
+    command
+}
+
+/// Execute `git merge-driver` subcommands
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("install", sub_matches)) => execute_install(sub_matches, executor).await,
+        Some((kind, sub_matches)) => execute_driver(kind, sub_matches, executor).await,
+        _ => Err(anyhow!("Unknown git merge-driver subcommand")),
+    }
+}
+
+/// Merge one file; on failure leave conflict markers in ours and fail
+async fn execute_driver(kind: &str, matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let base = executor.check_path(matches.get_one::<String>("base").unwrap())?;
+    let ours = executor.check_path(matches.get_one::<String>("ours").unwrap())?;
+    let theirs = executor.check_path(matches.get_one::<String>("theirs").unwrap())?;
+    let path = matches.get_one::<String>("path").cloned();
+    let file = path.clone().unwrap_or_else(|| ours.display().to_string());
+
+    let merged = match kind {
+        "cargo-lock" => read_and_merge(&base, &ours, &theirs, merge::merge_cargo_lock),
+        "package-lock" => read_and_merge(&base, &ours, &theirs, merge::merge_package_lock),
+        "generated" => regenerate(path.as_deref().unwrap(), executor).await,
+        _ => Err(anyhow!("Unknown merge driver '{}'", kind)),
+    };
+    match merged {
+        Ok(content) => {
+            executor.check_content(&ours, content.as_bytes())?;
+            fs::write(&ours, content)?;
+            info!("Merged {} with the {} driver", file, kind);
+            Ok(())
+        }
+        Err(e) => {
+            warn!("The {} driver could not merge {}: {}", kind, file, e);
+            let ours_arg = ours.to_string_lossy().to_string();
+            let base_arg = base.to_string_lossy().to_string();
+            let theirs_arg = theirs.to_string_lossy().to_string();
+            let fallback = executor
+                .execute_secure_capture(
+                    "git",
+                    &["merge-file", "-L", "ours", "-L", "base", "-L", "theirs", &ours_arg, &base_arg, &theirs_arg],
+                )
+                .await?;
+            if fallback.success() {
+                return Ok(());
+            }
+            bail!("Conflicts left in {}: {}", file, e)
+        }
+    }
+}
+
+fn read_and_merge(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    merge: fn(&str, &str, &str) -> crate::error::Result<String>,
+) -> Result<String> {
+    let read = |path: &Path| fs::read_to_string(path);
+    Ok(merge(&read(base)?, &read(ours)?, &read(theirs)?)?)
+}
+
+/// Run the generator recorded for `path` and return what it wrote there
+async fn regenerate(path: &str, executor: &Executor) -> Result<String> {
+    let workspace = executor.watch_scope(&[])?.base;
+    let mut registry = Registry::load(&workspace)?;
+    let artifact = registry
+        .get(Path::new(path))
+        .cloned()
+        .ok_or_else(|| anyhow!("{} is not recorded as a generated file", path))?;
+    let words = shell_words::split(&artifact.generator)?;
+    let (command, args) = words
+        .split_first()
+        .ok_or_else(|| anyhow!("No generator recorded for {}", path))?;
+
+    info!("Regenerating {} with '{}'", path, artifact.generator);
+    let output = executor.execute_secure_capture(command, args).await?;
+    if !output.success() {
+        bail!("'{}' failed: {}", artifact.generator, output.stderr.trim());
+    }
+    let content = fs::read_to_string(workspace.join(path))
+        .map_err(|e| anyhow!("'{}' did not write {}: {}", artifact.generator, path, e))?;
+    registry.record(Path::new(path), &artifact.source, &artifact.generator)?;
+    registry.save()?;
+    Ok(content)
+}
+
+/// Register the drivers with git and assign them to files in `.gitattributes`
+async fn execute_install(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let kinds: Vec<&str> = match matches.get_many::<String>("kind") {
+        Some(kinds) => kinds.map(String::as_str).collect(),
+        None => KINDS.iter().map(|(kind, _)| *kind).collect(),
+    };
+
+    let mut patterns = Vec::new();
+    for (kind, about) in KINDS.iter().filter(|(kind, _)| kinds.contains(kind)) {
+        let name = format!("merge.safe-ai-util-{}", kind);
+        let driver = format!("safe-ai-util git merge-driver {} %O %A %B %P", kind);
+        executor.execute_secure("git", &["config", &format!("{}.name", name), about]).await?;
+        executor.execute_secure("git", &["config", &format!("{}.driver", name), &driver]).await?;
+
+        let files = match *kind {
+            "cargo-lock" => vec!["Cargo.lock".to_string()],
+            "package-lock" => vec!["package-lock.json".to_string()],
+            _ => {
+                let workspace = executor.watch_scope(&[])?.base;
+                Registry::load(&workspace)?.artifacts().keys().cloned().collect()
+            }
+        };
+        patterns.extend(files.into_iter().map(|file| format!("{} merge=safe-ai-util-{}", file, kind)));
+    }
+
+    let attributes = executor.check_path(".gitattributes")?;
+    let existing = fs::read_to_string(&attributes).unwrap_or_default();
+    let missing: Vec<&String> = patterns
+        .iter()
+        .filter(|pattern| !existing.lines().any(|line| line.trim() == pattern.as_str()))
+        .collect();
+    if missing.is_empty() {
+        println!(".gitattributes already assigns the merge drivers");
+        return Ok(());
+    }
+    if executor.config().safety.dry_run {
+        println!("DRY RUN: Would add {} lines to .gitattributes", missing.len());
+        return Ok(());
+    }
+
+    let mut content = existing.clone();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for pattern in &missing {
+        content.push_str(pattern);
+        content.push('\n');
+    }
+    executor.check_content(&attributes, content.as_bytes())?;
+    fs::write(&attributes, content)?;
+    println!("Added {} lines to .gitattributes", missing.len());
+    Ok(())
+}
@@ -0,0 +1,379 @@
+// file: src/commands/git_stack.rs
+// version: 1.1.1
+// guid: 765c7ad5-82f3-4e20-8739-6c3c2cb23753
+
+//! Stacked branch support for `git stack`
+//!
+//! A stack is a chain of dependent branches where each branch is based on its parent.
+//! The stack metadata (parent branch and the parent commit each branch was last based on)
+//! is stored inside the git directory so restacking can use `git rebase --onto` with the
+//! correct old base even after the parent has been rewritten.
+
+use super::export::{Effect, Remote};
+use crate::executor::Executor;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Persistent stack metadata for a repository
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StackState {
+    branches: BTreeMap<String, StackEntry>,
+}
+
+/// Metadata for a single branch in a stack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StackEntry {
+    /// Branch this branch is stacked on
+    parent: String,
+    /// Commit of the parent the branch was last based on
+    base: String,
+}
+
+/// Build the `git stack` subcommand
+pub fn build_command() -> Command {
+    Command::new("stack")
+        .about("Manage stacked (dependent) branches")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("create")
+                .about("Create a new branch stacked on the current (or given) branch")
+                .arg(Arg::new("name")
+                    .help("Name of the new branch")
+                    .required(true))
+                .arg(Arg::new("parent")
+                    .long("parent")
+                    .short('p')
+                    .value_name("BRANCH")
+                    .help("Parent branch (defaults to the current branch)"))
+        )
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("Show tracked stacks")
+        )
+        .subcommand(
+            Command::new("update")
+                .add(Effect::Destructive)
+                .about("Restack every tracked branch onto the latest commit of its parent")
+        )
+        .subcommand(
+            Command::new("submit")
+                .add(Effect::Destructive)
+                .add(Remote)
+                .about("Push every branch in the stack and report the base each one targets")
+                .arg(Arg::new("remote")
+                    .long("remote")
+                    .value_name("REMOTE")
+                    .default_value("origin")
+                    .help("Remote to push to"))
+                .arg(Arg::new("no-restack")
+                    .long("no-restack")
+                    .action(ArgAction::SetTrue)
+                    .help("Push without restacking first"))
+        )
+        .subcommand(
+            Command::new("untrack")
+                .about("Stop tracking a branch (children are re-parented to its parent)")
+                .arg(Arg::new("name")
+                    .help("Branch to untrack")
+                    .required(true))
+        )
+}
+
+/// Execute `git stack` subcommands
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("create", sub_matches)) => execute_create(sub_matches, executor).await,
+        Some(("list", _)) => execute_list(executor).await,
+        Some(("update", _)) => execute_update(executor).await,
+        Some(("submit", sub_matches)) => execute_submit(sub_matches, executor).await,
+        Some(("untrack", sub_matches)) => execute_untrack(sub_matches, executor).await,
+        _ => Err(anyhow!("Unknown git stack subcommand")),
+    }
+}
+
+/// Create a new stacked branch
+async fn execute_create(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+    validate_branch_name(name)?;
+
+    let parent = match matches.get_one::<String>("parent") {
+        Some(parent) => parent.clone(),
+        None => current_branch(executor).await?,
+    };
+    validate_branch_name(&parent)?;
+
+    let mut state = load_state(executor).await?;
+    if state.branches.contains_key(name) {
+        return Err(anyhow!("Branch '{}' is already part of a stack", name));
+    }
+
+    let base = rev_parse(executor, &parent).await?;
+    executor
+        .execute_secure("git", &["checkout", "-b", name.as_str(), parent.as_str()])
+        .await?;
+
+    state.branches.insert(name.clone(), StackEntry { parent: parent.clone(), base });
+    save_state(executor, &state).await?;
+
+    info!("Created stacked branch '{}' on top of '{}'", name, parent);
+    println!("Created '{}' stacked on '{}'", name, parent);
+    Ok(())
+}
+
+/// Print the tracked stacks as trees
+async fn execute_list(executor: &Executor) -> Result<()> {
+    let state = load_state(executor).await?;
+    if state.branches.is_empty() {
+        println!("No stacked branches tracked");
+        return Ok(());
+    }
+
+    for root in stack_roots(&state) {
+        println!("{}", root);
+        print_children(&state, &root, 1);
+    }
+    Ok(())
+}
+
+fn print_children(state: &StackState, parent: &str, depth: usize) {
+    for child in children_of(state, parent) {
+        println!("{}└─ {}", "   ".repeat(depth - 1), child);
+        print_children(state, &child, depth + 1);
+    }
+}
+
+/// Restack all tracked branches onto their parents
+async fn execute_update(executor: &Executor) -> Result<()> {
+    let mut state = load_state(executor).await?;
+    let original = current_branch(executor).await?;
+
+    restack(executor, &mut state).await?;
+
+    executor.execute_secure("git", &["checkout", original.as_str()]).await?;
+    Ok(())
+}
+
+/// Rebase each branch (parents first) whose parent moved since it was last based on it
+async fn restack(executor: &Executor, state: &mut StackState) -> Result<()> {
+    for branch in stack_order(state) {
+        let entry = state.branches[&branch].clone();
+        let new_base = rev_parse(executor, &entry.parent).await?;
+
+        if new_base == entry.base {
+            info!("'{}' is already up to date with '{}'", branch, entry.parent);
+            continue;
+        }
+
+        println!("Restacking '{}' onto '{}'", branch, entry.parent);
+        let result = executor
+            .execute_secure(
+                "git",
+                &["rebase", "--onto", entry.parent.as_str(), entry.base.as_str(), branch.as_str()],
+            )
+            .await;
+
+        if let Err(e) = result {
+            // Keep the progress made so far so a re-run resumes at the failed branch
+            save_state(executor, state).await?;
+            return Err(anyhow!(
+                "Restack of '{}' stopped: {}. Resolve the conflict, run 'git rebase --continue', then re-run 'git stack update'",
+                branch,
+                e
+            ));
+        }
+
+        if let Some(entry) = state.branches.get_mut(&branch) {
+            entry.base = new_base;
+        }
+        save_state(executor, state).await?;
+    }
+    Ok(())
+}
+
+/// Push every branch in stack order
+async fn execute_submit(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let remote = matches.get_one::<String>("remote").unwrap();
+    let mut state = load_state(executor).await?;
+
+    if !matches.get_flag("no-restack") {
+        let original = current_branch(executor).await?;
+        restack(executor, &mut state).await?;
+        executor.execute_secure("git", &["checkout", original.as_str()]).await?;
+    }
+
+    for branch in stack_order(&state) {
+        let parent = &state.branches[&branch].parent;
+        executor
+            .execute_secure(
+                "git",
+                &["push", "--force-with-lease", "-u", remote.as_str(), branch.as_str()],
+            )
+            .await?;
+        println!("Pushed '{}' (base: '{}')", branch, parent);
+    }
+    Ok(())
+}
+
+/// Stop tracking a branch, re-parenting its children
+async fn execute_untrack(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+    let mut state = load_state(executor).await?;
+
+    let removed = state
+        .branches
+        .remove(name)
+        .ok_or_else(|| anyhow!("Branch '{}' is not tracked", name))?;
+
+    for entry in state.branches.values_mut() {
+        if entry.parent == *name {
+            entry.parent = removed.parent.clone();
+        }
+    }
+
+    save_state(executor, &state).await?;
+    println!("Untracked '{}'", name);
+    Ok(())
+}
+
+/// Branches whose parent is not itself tracked, sorted by name
+fn stack_roots(state: &StackState) -> Vec<String> {
+    let mut roots: Vec<String> = state
+        .branches
+        .values()
+        .filter(|entry| !state.branches.contains_key(&entry.parent))
+        .map(|entry| entry.parent.clone())
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Tracked branches stacked directly on `parent`, sorted by name
+fn children_of(state: &StackState, parent: &str) -> Vec<String> {
+    state
+        .branches
+        .iter()
+        .filter(|(_, entry)| entry.parent == parent)
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// All tracked branches ordered so that every parent precedes its children
+fn stack_order(state: &StackState) -> Vec<String> {
+    let mut order = Vec::new();
+    let mut pending: Vec<String> = stack_roots(state).into_iter().rev().collect();
+
+    while let Some(parent) = pending.pop() {
+        for child in children_of(state, &parent).into_iter().rev() {
+            pending.push(child.clone());
+            order.push(child);
+        }
+    }
+
+    // Any leftover branches are part of a parent cycle; keep them out of the order
+    if order.len() != state.branches.len() {
+        warn!("Stack metadata contains a cycle; some branches were skipped");
+    }
+    order
+}
+
+fn validate_branch_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('-') || name.contains("..") {
+        return Err(anyhow!("Invalid branch name: '{}'", name));
+    }
+    Ok(())
+}
+
+async fn current_branch(executor: &Executor) -> Result<String> {
+    let output = executor
+        .execute_secure_capture("git", &["rev-parse", "--abbrev-ref", "HEAD"])
+        .await?;
+    let branch = output.stdout.trim().to_string();
+    // A dry run only plans the query, so there is no output to read the branch from
+    if branch.is_empty() && executor.config().safety.dry_run {
+        return Ok("HEAD".to_string());
+    }
+    if !output.success() || branch.is_empty() || branch == "HEAD" {
+        return Err(anyhow!("Unable to determine the current branch (detached HEAD?)"));
+    }
+    Ok(branch)
+}
+
+async fn rev_parse(executor: &Executor, rev: &str) -> Result<String> {
+    let output = executor.execute_secure_capture("git", &["rev-parse", rev]).await?;
+    if !output.success() {
+        return Err(anyhow!("Unknown revision '{}': {}", rev, output.stderr.trim()));
+    }
+    Ok(output.stdout.trim().to_string())
+}
+
+async fn state_path(executor: &Executor) -> Result<PathBuf> {
+    let output = executor
+        .execute_secure_capture("git", &["rev-parse", "--git-dir"])
+        .await?;
+    let git_dir = output.stdout.trim();
+    let git_dir = if git_dir.is_empty() { ".git" } else { git_dir };
+    Ok(PathBuf::from(git_dir).join("safe-ai-util").join("stack.json"))
+}
+
+async fn load_state(executor: &Executor) -> Result<StackState> {
+    let path = state_path(executor).await?;
+    if !path.exists() {
+        return Ok(StackState::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+async fn save_state(executor: &Executor, state: &StackState) -> Result<()> {
+    // Dry runs and simulations leave the stack metadata as it was
+    if executor.config().safety.dry_run {
+        info!("DRY RUN: not saving the stack metadata");
+        return Ok(());
+    }
+    let path = state_path(executor).await?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(state)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(parent: &str) -> StackEntry {
+        StackEntry {
+            parent: parent.to_string(),
+            base: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_stack_order_parents_first() {
+        let mut state = StackState::default();
+        state.branches.insert("feature-c".to_string(), entry("feature-b"));
+        state.branches.insert("feature-a".to_string(), entry("main"));
+        state.branches.insert("feature-b".to_string(), entry("feature-a"));
+
+        assert_eq!(stack_roots(&state), vec!["main".to_string()]);
+        assert_eq!(
+            stack_order(&state),
+            vec!["feature-a".to_string(), "feature-b".to_string(), "feature-c".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_validate_branch_name() {
+        assert!(validate_branch_name("feature/stack-1").is_ok());
+        assert!(validate_branch_name("--upload-pack").is_err());
+        assert!(validate_branch_name("a..b").is_err());
+    }
+}
@@ -0,0 +1,312 @@
+// file: src/commands/go.rs
+// version: 1.0.2
+// guid: a84e1c37-5d92-4f06-b8e3-2c71f9d05b68
+
+//! `go` command: build, test, vet, mod tidy and generate with the go toolchain
+//!
+//! Build flags from `go.flags`, such as `-mod=readonly`, are added to every build, test, vet
+//! and generate. Flags listed in `go.deny_flags` (by default `-toolexec`, `-exec` and
+//! `-overlay`, which make go run another program or compile files other than the ones in the
+//! tree) are refused whether they come from the command line or from `GOFLAGS`, which go reads
+//! from the environment of every agent that inherited it.
+//!
+//! `test --format json` runs `go test -json` and prints failed tests and compile errors as
+//! diagnostics (see [`crate::parsers::go_test`]) together with the number of tests that passed,
+//! failed and were skipped. `mod tidy` asks before it runs while `go.sum` has uncommitted
+//! changes, which tidying would mix with its own.
+
+use super::export::{Effect, Remote};
+use crate::executor::Executor;
+use crate::parsers::{self, go_test, Diagnostic, Tool};
+use crate::security::audit;
+use crate::utils;
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+use std::env;
+
+/// Build the go command
+pub fn build_command() -> Command {
+    Command::new("go")
+        .about("Build, test, vet, tidy and generate Go code")
+        .long_about(
+            "Run the go toolchain with the build flags of go.flags, refusing the flags of \
+             go.deny_flags in arguments and GOFLAGS. test --format json summarizes failed tests \
+             and compile errors; mod tidy asks first while go.sum has uncommitted changes.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("build")
+                .about("Compile packages")
+                .arg(packages())
+                .arg(race())
+                .arg(extra_args()),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Test packages")
+                .arg(packages())
+                .arg(run())
+                .arg(race())
+                .arg(
+                    Arg::new("short")
+                        .long("short")
+                        .action(ArgAction::SetTrue)
+                        .help("Tell long-running tests to shorten their run time"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format: go's own output, or a JSON summary"),
+                )
+                .arg(extra_args()),
+        )
+        .subcommand(
+            Command::new("vet")
+                .add(Effect::ReadOnly)
+                .about("Report suspicious constructs in packages")
+                .arg(packages())
+                .arg(extra_args()),
+        )
+        .subcommand(
+            Command::new("mod")
+                .about("Maintain go.mod and go.sum")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("tidy")
+                        .add(Remote)
+                        .about("Add missing and remove unused modules")
+                        .arg(
+                            Arg::new("yes")
+                                .long("yes")
+                                .short('y')
+                                .action(ArgAction::SetTrue)
+                                .help("Tidy without asking while go.sum has uncommitted changes"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("generate")
+                .about("Run the //go:generate directives of packages")
+                .arg(packages())
+                .arg(run())
+                .arg(extra_args()),
+        )
+}
+
+fn packages() -> Arg {
+    Arg::new("packages")
+        .value_name("PACKAGE")
+        .action(ArgAction::Append)
+        .help("Packages to work on (default: ./...)")
+}
+
+fn race() -> Arg {
+    Arg::new("race")
+        .long("race")
+        .action(ArgAction::SetTrue)
+        .help("Enable the data race detector")
+}
+
+fn run() -> Arg {
+    Arg::new("run")
+        .long("run")
+        .value_name("REGEX")
+        .help("Only tests, or generate directives, matching REGEX")
+}
+
+fn extra_args() -> Arg {
+    Arg::new("args")
+        .value_name("ARGS")
+        .num_args(0..)
+        .last(true)
+        .allow_hyphen_values(true)
+        .help("Further flags for go after --, e.g. -- -count=1")
+}
+
+/// Execute the go command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let config = &executor.config().go;
+    if let Ok(goflags) = env::var("GOFLAGS") {
+        let flags: Vec<String> = goflags.split_whitespace().map(String::from).collect();
+        check_flags(&flags, &config.deny_flags, "GOFLAGS")?;
+    }
+    let args = match matches.subcommand() {
+        Some(("mod", sub_matches)) => match sub_matches.subcommand() {
+            Some(("tidy", tidy_matches)) => {
+                if !confirm_tidy(tidy_matches, executor).await? {
+                    println!("Aborted; go.sum was not changed");
+                    return Ok(());
+                }
+                vec!["mod".to_string(), "tidy".to_string()]
+            }
+            _ => unreachable!("subcommand required"),
+        },
+        Some((subcommand, sub_matches)) => go_args(subcommand, sub_matches, &config.flags),
+        None => unreachable!("subcommand required"),
+    };
+    check_flags(&args, &config.deny_flags, "the command line")?;
+
+    let json = matches
+        .subcommand_matches("test")
+        .and_then(|test| test.get_one::<String>("format"))
+        .map(String::as_str)
+        == Some("json");
+    if !json {
+        return executor.execute_secure("go", &args).await;
+    }
+
+    let mut args = args;
+    args.insert(1, "-json".to_string());
+    let output = executor.execute_secure_capture("go", &args).await?;
+    let diagnostics: Vec<Diagnostic> = Tool::GoTest
+        .parse(&output.stdout, &output.stderr)
+        .into_iter()
+        .map(|diagnostic| diagnostic.relative_to(executor.sandbox().base()))
+        .collect();
+    let mut report = json!({
+        "command": "test",
+        "args": args,
+        "exit_code": output.exit_code,
+        "diagnostics": diagnostics,
+        "summary": parsers::summary(&diagnostics),
+        "tests": go_test::test_counts(&output.stdout),
+    });
+    // Without findings, go's own errors are the only explanation of a failure
+    if !output.success() && diagnostics.is_empty() {
+        report["stderr"] = json!(output.stderr);
+    }
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if !output.success() {
+        bail!(
+            "go test failed with {}",
+            output
+                .exit_code
+                .map(|code| format!("exit code {}", code))
+                .unwrap_or_else(|| "a signal".to_string())
+        );
+    }
+    Ok(())
+}
+
+/// Arguments for `go <subcommand>` with the build flags of `go.flags`; the packages come
+/// last, since go stops reading flags at the first package
+fn go_args(subcommand: &str, matches: &ArgMatches, flags: &[String]) -> Vec<String> {
+    let flag = |id: &str| matches.try_get_one::<bool>(id).ok().flatten() == Some(&true);
+    let mut args = vec![subcommand.to_string()];
+    args.extend_from_slice(flags);
+    for id in ["race", "short"] {
+        if flag(id) {
+            args.push(format!("-{}", id));
+        }
+    }
+    if let Some(run) = matches.try_get_one::<String>("run").ok().flatten() {
+        args.push("-run".to_string());
+        args.push(run.clone());
+    }
+    args.extend(
+        matches
+            .get_many::<String>("args")
+            .unwrap_or_default()
+            .cloned(),
+    );
+    match matches.get_many::<String>("packages") {
+        Some(packages) => args.extend(packages.cloned()),
+        None => args.push("./...".to_string()),
+    }
+    args
+}
+
+/// Refuse the flags of `deny` in `args`, which came from `source`
+fn check_flags(args: &[String], deny: &[String], source: &str) -> Result<()> {
+    let name = |flag: &str| {
+        flag.trim_start_matches('-')
+            .split('=')
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let denied = args
+        .iter()
+        .filter(|arg| arg.starts_with('-'))
+        .find(|arg| deny.iter().any(|flag| name(flag) == name(arg)));
+    if let Some(flag) = denied {
+        let message = format!(
+            "go flag '{}' in {} is not allowed (go.deny_flags)",
+            flag, source
+        );
+        audit::log_access_denied("go", &message);
+        bail!(message);
+    }
+    Ok(())
+}
+
+/// Whether `go mod tidy` may run: at once unless `go.sum` has uncommitted changes, which
+/// tidying would mix with its own
+async fn confirm_tidy(matches: &ArgMatches, executor: &Executor) -> Result<bool> {
+    if matches.get_flag("yes") || !executor.config().safety.confirm_destructive {
+        return Ok(true);
+    }
+    let status = executor
+        .execute_secure_capture("git", &["status", "--porcelain", "--", "go.sum"])
+        .await;
+    // Outside a git repository there is nothing to lose track of
+    let dirty = status.is_ok_and(|status| status.success() && !status.stdout.trim().is_empty());
+    if !dirty {
+        return Ok(true);
+    }
+    Ok(utils::confirm(
+        "go.sum has uncommitted changes that go mod tidy would mix with its own; tidy anyway?",
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command_line: &[&str], flags: &[&str]) -> Vec<String> {
+        let matches = build_command()
+            .try_get_matches_from(std::iter::once("go").chain(command_line.iter().copied()))
+            .unwrap();
+        let (subcommand, matches) = matches.subcommand().unwrap();
+        let flags: Vec<String> = flags.iter().map(|f| f.to_string()).collect();
+        go_args(subcommand, matches, &flags)
+    }
+
+    #[test]
+    fn test_go_args() {
+        assert_eq!(
+            args(
+                &["test", "--race", "--run", "TestAdd", "./calc", "--", "-count=1"],
+                &["-mod=readonly"]
+            ),
+            [
+                "test",
+                "-mod=readonly",
+                "-race",
+                "-run",
+                "TestAdd",
+                "-count=1",
+                "./calc"
+            ]
+        );
+        assert_eq!(args(&["vet"], &[]), ["vet", "./..."]);
+    }
+
+    #[test]
+    fn test_denied_flags() {
+        let deny = crate::config::GoConfig::default().deny_flags;
+        let flags = |flags: &[&str]| flags.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert!(check_flags(&flags(&["build", "-trimpath", "./..."]), &deny, "GOFLAGS").is_ok());
+        let error = check_flags(&flags(&["-toolexec=/tmp/x"]), &deny, "GOFLAGS").unwrap_err();
+        assert!(error.to_string().contains("'-toolexec=/tmp/x' in GOFLAGS"));
+        assert!(check_flags(&flags(&["--overlay", "o.json"]), &deny, "GOFLAGS").is_err());
+        // Packages are not flags
+        assert!(check_flags(&flags(&["exec"]), &deny, "GOFLAGS").is_ok());
+    }
+}
@@ -0,0 +1,516 @@
+// file: src/commands/grep.rs
+// version: 1.0.1
+// guid: 2e9a6c14-7f3b-4d81-b5c0-8a1d39e7f625
+
+//! `grep` command: recursive regex search without an external binary
+//!
+//! Searches with ripgrep's own libraries. Directories are walked in order, respecting
+//! `.gitignore`, `.ignore` and the global git excludes unless `--no-ignore` is given and
+//! skipping hidden files unless `--hidden` is; binary files are left out. Matches are printed
+//! as `path:line:text` with `-A`/`-B`/`-C` context lines, or as JSON with the context of each
+//! match attached to it.
+//!
+//! Files larger than `--max-filesize` (16 MiB by default) are skipped and reported. The files
+//! that would be searched are counted before the search starts and checked against the usage
+//! guardrails (see [`crate::guardrails`]), so a search of a huge tree asks first.
+
+use super::export::Effect;
+use crate::executor::Executor;
+use crate::guardrails::{self, Estimate};
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use grep_matcher::Matcher;
+use grep_regex::{RegexMatcher, RegexMatcherBuilder};
+use grep_searcher::{
+    BinaryDetection, Searcher, SearcherBuilder, Sink, SinkContext, SinkContextKind, SinkMatch,
+};
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// A matching line with the lines around it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Match {
+    pub path: PathBuf,
+    pub line: u64,
+    /// 1-based byte column of the first match on the line
+    pub column: u64,
+    pub text: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    matches: Vec<Match>,
+    files_searched: usize,
+    files_matched: usize,
+    /// Files over `--max-filesize`, which were not searched
+    skipped_large: Vec<PathBuf>,
+    /// Files with binary content, which were not reported
+    skipped_binary: usize,
+}
+
+/// Build the grep command
+pub fn build_command() -> Command {
+    Command::new("grep")
+        .add(Effect::ReadOnly)
+        .about("Search files for a regex, respecting .gitignore")
+        .long_about(
+            "Search files recursively for a regex without running an external grep. Ignored, \
+             hidden and binary files are skipped; matches are printed with context lines or as \
+             JSON. Searches of trees above the guardrail limits ask first.",
+        )
+        .arg(
+            Arg::new("pattern")
+                .value_name("PATTERN")
+                .required(true)
+                .help("Regex to search for (Rust regex syntax)"),
+        )
+        .arg(
+            Arg::new("paths")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .help("Files and directories to search (default: .)"),
+        )
+        .arg(
+            Arg::new("ignore-case")
+                .long("ignore-case")
+                .short('i')
+                .action(ArgAction::SetTrue)
+                .help("Match case-insensitively"),
+        )
+        .arg(
+            Arg::new("word-regexp")
+                .long("word-regexp")
+                .short('w')
+                .action(ArgAction::SetTrue)
+                .help("Only match whole words"),
+        )
+        .arg(
+            Arg::new("fixed-strings")
+                .long("fixed-strings")
+                .short('F')
+                .action(ArgAction::SetTrue)
+                .help("Treat the pattern as literal text"),
+        )
+        .arg(context(
+            "after-context",
+            'A',
+            "Lines to show after each match",
+        ))
+        .arg(context(
+            "before-context",
+            'B',
+            "Lines to show before each match",
+        ))
+        .arg(context(
+            "context",
+            'C',
+            "Lines to show before and after each match",
+        ))
+        .arg(
+            Arg::new("glob")
+                .long("glob")
+                .short('g')
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .help("Only search files matching GLOB, or not matching !GLOB; may be repeated"),
+        )
+        .arg(
+            Arg::new("hidden")
+                .long("hidden")
+                .action(ArgAction::SetTrue)
+                .help("Search hidden files and directories too"),
+        )
+        .arg(
+            Arg::new("no-ignore")
+                .long("no-ignore")
+                .action(ArgAction::SetTrue)
+                .help("Search files that .gitignore and .ignore exclude too"),
+        )
+        .arg(
+            Arg::new("files-with-matches")
+                .long("files-with-matches")
+                .short('l')
+                .action(ArgAction::SetTrue)
+                .help("Only print the paths of files with matches"),
+        )
+        .arg(
+            Arg::new("max-count")
+                .long("max-count")
+                .short('m')
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Stop searching a file after N matching lines"),
+        )
+        .arg(
+            Arg::new("max-filesize")
+                .long("max-filesize")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("16777216")
+                .help("Skip files larger than this"),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .action(ArgAction::SetTrue)
+                .help("Search a tree above the guardrail limits without asking"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+}
+
+fn context(id: &'static str, short: char, help: &'static str) -> Arg {
+    Arg::new(id)
+        .long(id)
+        .short(short)
+        .value_name("N")
+        .value_parser(clap::value_parser!(usize))
+        .help(help)
+}
+
+/// Execute the grep command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let pattern = matches.get_one::<String>("pattern").unwrap();
+    let matcher = RegexMatcherBuilder::new()
+        .case_insensitive(matches.get_flag("ignore-case"))
+        .word(matches.get_flag("word-regexp"))
+        .fixed_strings(matches.get_flag("fixed-strings"))
+        .build(pattern)
+        .with_context(|| format!("Invalid pattern '{}'", pattern))?;
+
+    let paths: Vec<PathBuf> = match matches.get_many::<String>("paths") {
+        Some(paths) => paths.map(PathBuf::from).collect(),
+        None => vec![PathBuf::from(".")],
+    };
+    for path in &paths {
+        executor.check_path(path)?;
+        if !path.exists() {
+            bail!("{} does not exist", path.display());
+        }
+    }
+
+    let max_filesize = *matches.get_one::<u64>("max-filesize").unwrap();
+    let globs: Vec<String> = matches
+        .get_many::<String>("glob")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let walk = Walk {
+        hidden: matches.get_flag("hidden"),
+        no_ignore: matches.get_flag("no-ignore"),
+        globs: &globs,
+    };
+    let mut report = Report::default();
+    let mut files = Vec::new();
+    let mut estimate = Estimate::default();
+    for (path, size) in walk.files(&paths)? {
+        if size > max_filesize {
+            report.skipped_large.push(path);
+        } else {
+            estimate.add(size);
+            files.push(path);
+        }
+    }
+    guardrails::check(
+        executor.config(),
+        "grep",
+        &estimate,
+        matches.get_flag("yes"),
+    )?;
+    if executor.config().safety.dry_run {
+        return Ok(());
+    }
+    info!("Searching {} files for '{}'", files.len(), pattern);
+
+    let context = |id: &str| matches.get_one::<usize>(id).copied();
+    let around = context("context").unwrap_or(0);
+    let mut searcher = SearcherBuilder::new()
+        .line_number(true)
+        .binary_detection(BinaryDetection::quit(b'\x00'))
+        .before_context(context("before-context").unwrap_or(around))
+        .after_context(context("after-context").unwrap_or(around))
+        .max_matches(matches.get_one::<u64>("max-count").copied())
+        .build();
+    let mut lines = Vec::new();
+    for path in &files {
+        let mut sink = Collect::new(&matcher);
+        searcher
+            .search_path(&matcher, path, &mut sink)
+            .with_context(|| format!("Failed to search {}", path.display()))?;
+        report.files_searched += 1;
+        if sink.binary {
+            report.skipped_binary += 1;
+            continue;
+        }
+        if sink.matched {
+            report.files_matched += 1;
+            report.matches.extend(sink.matches(path));
+            lines.push((path, sink.lines));
+        }
+    }
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    for (path, lines) in &lines {
+        if matches.get_flag("files-with-matches") {
+            println!("{}", path.display());
+            continue;
+        }
+        for line in lines {
+            match line {
+                Line::Break => println!("--"),
+                Line::Match { number, text, .. } => {
+                    println!("{}:{}:{}", path.display(), number, text)
+                }
+                Line::Before { number, text } | Line::After { number, text } => {
+                    println!("{}-{}-{}", path.display(), number, text)
+                }
+            }
+        }
+    }
+    for path in &report.skipped_large {
+        eprintln!(
+            "Skipped {}: larger than --max-filesize ({} bytes)",
+            path.display(),
+            max_filesize
+        );
+    }
+    Ok(())
+}
+
+/// Which files a search visits
+struct Walk<'a> {
+    hidden: bool,
+    no_ignore: bool,
+    globs: &'a [String],
+}
+
+impl Walk<'_> {
+    /// The files under `paths` with their sizes, each directory's entries sorted by name
+    fn files(&self, paths: &[PathBuf]) -> Result<Vec<(PathBuf, u64)>> {
+        let mut files = Vec::new();
+        for path in paths {
+            let mut overrides = OverrideBuilder::new(path);
+            for glob in self.globs {
+                overrides
+                    .add(glob)
+                    .with_context(|| format!("Invalid glob '{}'", glob))?;
+            }
+            let walker = WalkBuilder::new(path)
+                .hidden(!self.hidden)
+                .ignore(!self.no_ignore)
+                .git_ignore(!self.no_ignore)
+                .git_global(!self.no_ignore)
+                .git_exclude(!self.no_ignore)
+                .overrides(overrides.build()?)
+                .filter_entry(|entry| entry.file_name() != ".git")
+                .sort_by_file_name(|a, b| a.cmp(b))
+                .build();
+            for entry in walker {
+                let entry = entry?;
+                if entry.file_type().is_some_and(|t| t.is_file()) {
+                    let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                    files.push((entry.into_path(), size));
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// A line reported for a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    Match {
+        number: u64,
+        column: u64,
+        text: String,
+    },
+    Before {
+        number: u64,
+        text: String,
+    },
+    After {
+        number: u64,
+        text: String,
+    },
+    /// Lines were left out between the ones before and after
+    Break,
+}
+
+/// Sink collecting the lines of one file
+struct Collect<'a> {
+    matcher: &'a RegexMatcher,
+    lines: Vec<Line>,
+    matched: bool,
+    binary: bool,
+}
+
+impl<'a> Collect<'a> {
+    fn new(matcher: &'a RegexMatcher) -> Self {
+        Self {
+            matcher,
+            lines: Vec::new(),
+            matched: false,
+            binary: false,
+        }
+    }
+
+    /// The matches of the file at `path`, each with the context lines around it
+    fn matches(&self, path: &Path) -> Vec<Match> {
+        let mut matches: Vec<Match> = Vec::new();
+        let mut before = Vec::new();
+        for line in &self.lines {
+            match line {
+                Line::Before { text, .. } => before.push(text.clone()),
+                Line::After { text, .. } => {
+                    if let Some(last) = matches.last_mut() {
+                        last.after.push(text.clone());
+                    }
+                }
+                Line::Match {
+                    number,
+                    column,
+                    text,
+                } => matches.push(Match {
+                    path: path.to_path_buf(),
+                    line: *number,
+                    column: *column,
+                    text: text.clone(),
+                    before: std::mem::take(&mut before),
+                    after: Vec::new(),
+                }),
+                Line::Break => before.clear(),
+            }
+        }
+        matches
+    }
+}
+
+/// Text of a line without its terminator
+fn line_text(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    text.trim_end_matches(['\n', '\r']).to_string()
+}
+
+impl Sink for Collect<'_> {
+    type Error = io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, io::Error> {
+        let column = self
+            .matcher
+            .find(mat.bytes())
+            .ok()
+            .flatten()
+            .map(|m| m.start() as u64 + 1)
+            .unwrap_or(1);
+        self.matched = true;
+        self.lines.push(Line::Match {
+            number: mat.line_number().unwrap_or_default(),
+            column,
+            text: line_text(mat.bytes()),
+        });
+        Ok(true)
+    }
+
+    fn context(
+        &mut self,
+        _searcher: &Searcher,
+        context: &SinkContext<'_>,
+    ) -> Result<bool, io::Error> {
+        let number = context.line_number().unwrap_or_default();
+        let text = line_text(context.bytes());
+        self.lines.push(match context.kind() {
+            SinkContextKind::Before => Line::Before { number, text },
+            _ => Line::After { number, text },
+        });
+        Ok(true)
+    }
+
+    fn context_break(&mut self, _searcher: &Searcher) -> Result<bool, io::Error> {
+        self.lines.push(Line::Break);
+        Ok(true)
+    }
+
+    fn binary_data(&mut self, _searcher: &Searcher, _offset: u64) -> Result<bool, io::Error> {
+        self.binary = true;
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn search(dir: &Path, pattern: &str, around: usize) -> Vec<Match> {
+        let matcher = RegexMatcher::new(pattern).unwrap();
+        let mut searcher = SearcherBuilder::new()
+            .line_number(true)
+            .binary_detection(BinaryDetection::quit(b'\x00'))
+            .before_context(around)
+            .after_context(around)
+            .build();
+        let walk = Walk {
+            hidden: false,
+            no_ignore: false,
+            globs: &[],
+        };
+        let mut matches = Vec::new();
+        for (path, _) in walk.files(&[dir.to_path_buf()]).unwrap() {
+            let mut sink = Collect::new(&matcher);
+            searcher.search_path(&matcher, &path, &mut sink).unwrap();
+            if !sink.binary {
+                matches.extend(sink.matches(path.strip_prefix(dir).unwrap()));
+            }
+        }
+        matches
+    }
+
+    #[test]
+    fn test_search_respects_ignores_and_collects_context() {
+        let dir = TempDir::new().unwrap();
+        // The walker only reads .gitignore inside git repositories
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("target/out.rs"), "fn todo() {}\n").unwrap();
+        fs::write(dir.path().join(".hidden.rs"), "fn todo() {}\n").unwrap();
+        fs::write(dir.path().join("blob.bin"), b"todo\x00\x01").unwrap();
+        fs::write(
+            dir.path().join("lib.rs"),
+            "use std::io;\n\nfn todo() {}\nfn done() {}\n",
+        )
+        .unwrap();
+
+        let matches = search(dir.path(), r"\btodo\b", 1);
+        assert_eq!(
+            matches,
+            [Match {
+                path: PathBuf::from("lib.rs"),
+                line: 3,
+                column: 4,
+                text: "fn todo() {}".to_string(),
+                before: vec![String::new()],
+                after: vec!["fn done() {}".to_string()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,581 @@
+// file: src/commands/helm.rs
+// version: 1.0.1
+// guid: 8c3f1b6e-5d27-4a90-b4e8-2f7a9d06c1e5
+
+//! `helm` command: lint, render, diff and install charts, reviewing every upgrade first
+//!
+//! `template` and `diff` show what an upgrade would change and record that it was reviewed:
+//! the release, the chart and its files, the values files, `--set` values, context and
+//! namespace. `upgrade` always installs a missing release and, outside the contexts matched by
+//! `helm.dev_contexts`, only runs once exactly that upgrade was reviewed within
+//! `helm.review_max_age_minutes`. A review covers one upgrade; it is removed once applied.
+//! `status`, and `upgrade` when it finishes, report the release as text or JSON.
+
+use super::export::{Effect, Remote};
+use crate::commands::kubectl::resolve_target;
+use crate::executor::Executor;
+use crate::utils;
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// Build the helm command
+pub fn build_command() -> Command {
+    Command::new("helm")
+        .about("Lint, render, diff and upgrade Helm charts")
+        .long_about(
+            "Run common Helm workflows. upgrade always installs a missing release and, outside \
+             helm.dev_contexts, only runs once the same upgrade was reviewed with helm diff or \
+             helm template within helm.review_max_age_minutes.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            values(
+                Command::new("lint")
+                    .add(Effect::ReadOnly)
+                    .about("Check a chart for problems"),
+            )
+            .arg(chart())
+            .arg(
+                Arg::new("strict")
+                    .long("strict")
+                    .action(ArgAction::SetTrue)
+                    .help("Fail on warnings"),
+            ),
+        )
+        .subcommand(release(
+            Command::new("template")
+                .add(Effect::ReadOnly)
+                .about("Render a release's manifests and record the review"),
+        ))
+        .subcommand(release(
+            Command::new("diff")
+                .add(Effect::ReadOnly)
+                .add(Remote)
+                .about("Show what an upgrade would change and record the review (needs helm-diff)"),
+        ))
+        .subcommand(
+            release(
+                Command::new("upgrade")
+                    .add(Effect::Destructive)
+                    .add(Remote)
+                    .about("Install or upgrade a release once reviewed"),
+            )
+            .arg(
+                Arg::new("wait")
+                    .long("wait")
+                    .action(ArgAction::SetTrue)
+                    .help("Wait until the release's resources are ready"),
+            )
+            .arg(
+                Arg::new("atomic")
+                    .long("atomic")
+                    .action(ArgAction::SetTrue)
+                    .help("Roll back if the upgrade fails"),
+            )
+            .arg(
+                Arg::new("timeout")
+                    .long("timeout")
+                    .value_name("DURATION")
+                    .help("Give up waiting after DURATION, e.g. 5m"),
+            )
+            .arg(format()),
+        )
+        .subcommand(
+            target(
+                Command::new("status")
+                    .add(Effect::ReadOnly)
+                    .add(Remote)
+                    .about("Show the status of a release"),
+            )
+            .arg(name())
+            .arg(format()),
+        )
+}
+
+/// Add `--kube-context` and `--namespace`
+fn target(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("context")
+                .long("kube-context")
+                .value_name("NAME")
+                .help("kubeconfig context to use (default: the current context)"),
+        )
+        .arg(
+            Arg::new("namespace")
+                .long("namespace")
+                .short('n')
+                .value_name("NAME")
+                .help("Namespace to use (default: the namespace of the context)"),
+        )
+}
+
+/// Add the release, chart, values and target of `template`, `diff` and `upgrade`
+fn release(command: Command) -> Command {
+    values(target(command)).arg(name()).arg(chart()).arg(
+        Arg::new("version")
+            .long("version")
+            .value_name("VERSION")
+            .help("Chart version, for charts from a repository"),
+    )
+}
+
+/// Add `--values` and `--set`
+fn values(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("values")
+                .long("values")
+                .short('f')
+                .value_name("FILE")
+                .action(ArgAction::Append)
+                .help("Values file; repeat for several"),
+        )
+        .arg(
+            Arg::new("set")
+                .long("set")
+                .value_name("KEY=VALUE")
+                .action(ArgAction::Append)
+                .help("Value to set; repeat for several"),
+        )
+}
+
+fn name() -> Arg {
+    Arg::new("release")
+        .value_name("RELEASE")
+        .required(true)
+        .help("Release name")
+}
+
+fn chart() -> Arg {
+    Arg::new("chart")
+        .value_name("CHART")
+        .required(true)
+        .help("Chart directory, packaged chart, or REPO/NAME")
+}
+
+fn format() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .value_parser(["text", "json"])
+        .default_value("text")
+        .help("Output format of the release status")
+}
+
+/// A release as `helm status` reports it
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct ReleaseStatus {
+    name: String,
+    namespace: String,
+    revision: u64,
+    /// `deployed`, `failed`, `pending-upgrade` and so on
+    status: String,
+    /// Chart name and version, e.g. `web-1.2.0`
+    chart: String,
+    app_version: Option<String>,
+    updated: Option<String>,
+    description: Option<String>,
+}
+
+impl ReleaseStatus {
+    /// Parse the output of `helm status RELEASE --output json`
+    fn from_json(release: &Value) -> Option<Self> {
+        let text = |value: &Value| value.as_str().filter(|s| !s.is_empty()).map(str::to_string);
+        let metadata = &release["chart"]["metadata"];
+        Some(Self {
+            name: text(&release["name"])?,
+            namespace: text(&release["namespace"]).unwrap_or_else(|| "default".to_string()),
+            revision: release["version"].as_u64()?,
+            status: text(&release["info"]["status"])?,
+            chart: match (text(&metadata["name"]), text(&metadata["version"])) {
+                (Some(name), Some(version)) => format!("{}-{}", name, version),
+                (name, _) => name.unwrap_or_default(),
+            },
+            app_version: text(&metadata["appVersion"]),
+            updated: text(&release["info"]["last_deployed"]),
+            description: text(&release["info"]["description"]),
+        })
+    }
+
+    fn print(&self) {
+        println!(
+            "Release {} in namespace {}: {} (revision {})",
+            self.name, self.namespace, self.status, self.revision
+        );
+        if !self.chart.is_empty() {
+            match &self.app_version {
+                Some(app_version) => println!("  chart: {} (app {})", self.chart, app_version),
+                None => println!("  chart: {}", self.chart),
+            }
+        }
+        if let Some(updated) = &self.updated {
+            println!("  updated: {}", updated);
+        }
+        if let Some(description) = &self.description {
+            println!("  description: {}", description);
+        }
+    }
+}
+
+/// Execute the helm command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("lint", sub_matches)) => {
+            let mut args = vec![
+                "lint".to_string(),
+                sub_matches.get_one::<String>("chart").unwrap().clone(),
+            ];
+            push_values(&mut args, sub_matches);
+            if sub_matches.get_flag("strict") {
+                args.push("--strict".to_string());
+            }
+            executor.execute_secure("helm", &args).await
+        }
+        Some(("template", sub_matches)) => {
+            let (context, namespace) = resolve_target(sub_matches, executor).await?;
+            let mut args = vec!["template".to_string()];
+            args.extend(release_args(sub_matches));
+            args.extend(["--namespace".to_string(), namespace.clone()]);
+            executor.execute_secure("helm", &args).await?;
+            record_review(sub_matches, executor, &context, &namespace)
+        }
+        Some(("diff", sub_matches)) => {
+            let (context, namespace) = resolve_target(sub_matches, executor).await?;
+            let plugins = executor
+                .execute_secure_capture("helm", &["plugin", "list"])
+                .await?;
+            if !plugins.stdout.lines().any(|line| line.starts_with("diff")) {
+                bail!(
+                    "The helm-diff plugin is not installed; install it with `helm plugin install \
+                     https://github.com/databus23/helm-diff`, or review with helm template instead"
+                );
+            }
+            let mut args = vec!["diff".to_string(), "upgrade".to_string()];
+            args.extend(release_args(sub_matches));
+            args.extend([
+                "--allow-unreleased".to_string(),
+                "--kube-context".to_string(),
+                context.clone(),
+                "--namespace".to_string(),
+                namespace.clone(),
+            ]);
+            executor.execute_secure("helm", &args).await?;
+            record_review(sub_matches, executor, &context, &namespace)
+        }
+        Some(("upgrade", sub_matches)) => upgrade(sub_matches, executor).await,
+        Some(("status", sub_matches)) => {
+            let (context, namespace) = resolve_target(sub_matches, executor).await?;
+            let release = sub_matches.get_one::<String>("release").unwrap();
+            let status = status(executor, release, &context, &namespace).await?;
+            print_status(&status, sub_matches)
+        }
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+/// Check the review outside dev contexts, upgrade, and report the release
+async fn upgrade(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let (context, namespace) = resolve_target(matches, executor).await?;
+    let config = &executor.config().helm;
+    let dev = is_dev(&context, &config.dev_contexts)?;
+    println!(
+        "Context: {}{}, namespace: {}",
+        context,
+        if dev { " (dev)" } else { "" },
+        namespace
+    );
+
+    let key = review_key(matches, executor.sandbox().base(), &context, &namespace)?;
+    let review = reviews_dir().join(&key);
+    if !dev {
+        let max_age = Duration::from_secs(config.review_max_age_minutes * 60);
+        check_review(&review, &context, max_age, SystemTime::now())?;
+    }
+
+    let mut args = vec!["upgrade".to_string(), "--install".to_string()];
+    args.extend(release_args(matches));
+    for id in ["wait", "atomic"] {
+        if matches.get_flag(id) {
+            args.push(format!("--{}", id));
+        }
+    }
+    if let Some(timeout) = matches.get_one::<String>("timeout") {
+        args.extend(["--timeout".to_string(), timeout.clone()]);
+    }
+    args.extend([
+        "--kube-context".to_string(),
+        context.clone(),
+        "--namespace".to_string(),
+        namespace.clone(),
+    ]);
+    executor.execute_secure("helm", &args).await?;
+    if executor.config().safety.dry_run {
+        return Ok(());
+    }
+    // The review was of the release as it was before this upgrade
+    if review.exists() {
+        fs::remove_file(&review)
+            .with_context(|| format!("Failed to remove applied review {}", review.display()))?;
+    }
+
+    let release = matches.get_one::<String>("release").unwrap();
+    let status = status(executor, release, &context, &namespace).await?;
+    print_status(&status, matches)
+}
+
+/// `RELEASE CHART` followed by the chart version, values files and `--set` values
+fn release_args(matches: &ArgMatches) -> Vec<String> {
+    let mut args = vec![
+        matches.get_one::<String>("release").unwrap().clone(),
+        matches.get_one::<String>("chart").unwrap().clone(),
+    ];
+    if let Some(version) = matches.get_one::<String>("version") {
+        args.extend(["--version".to_string(), version.clone()]);
+    }
+    push_values(&mut args, matches);
+    args
+}
+
+fn push_values(args: &mut Vec<String>, matches: &ArgMatches) {
+    for id in ["values", "set"] {
+        for value in matches.get_many::<String>(id).unwrap_or_default() {
+            args.push(format!("--{}", id));
+            args.push(value.clone());
+        }
+    }
+}
+
+/// Fetch the status of `release`
+async fn status(
+    executor: &Executor,
+    release: &str,
+    context: &str,
+    namespace: &str,
+) -> Result<ReleaseStatus> {
+    let args = [
+        "status",
+        release,
+        "--output",
+        "json",
+        "--kube-context",
+        context,
+        "--namespace",
+        namespace,
+    ];
+    let output = executor.execute_secure_capture("helm", &args).await?;
+    if !output.success() {
+        bail!("helm status failed: {}", output.stderr.trim());
+    }
+    let value: Value = serde_json::from_str(&output.stdout)
+        .context("Failed to parse helm status --output json")?;
+    ReleaseStatus::from_json(&value).context("helm status reported no release")
+}
+
+fn print_status(status: &ReleaseStatus, matches: &ArgMatches) -> Result<()> {
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(status)?);
+    } else {
+        status.print();
+    }
+    Ok(())
+}
+
+fn reviews_dir() -> PathBuf {
+    utils::state_dir().join("helm").join("reviews")
+}
+
+/// Record that the upgrade `matches` describes was reviewed in `context` and `namespace`
+fn record_review(
+    matches: &ArgMatches,
+    executor: &Executor,
+    context: &str,
+    namespace: &str,
+) -> Result<()> {
+    if executor.config().safety.dry_run {
+        return Ok(());
+    }
+    let key = review_key(matches, executor.sandbox().base(), context, namespace)?;
+    let dir = reviews_dir();
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let release = matches.get_one::<String>("release").unwrap();
+    fs::write(
+        dir.join(key),
+        format!("{} {} {}\n", release, context, namespace),
+    )?;
+    eprintln!(
+        "Reviewed: helm upgrade of {} in {}/{} may run for {} minutes",
+        release,
+        context,
+        namespace,
+        executor.config().helm.review_max_age_minutes
+    );
+    Ok(())
+}
+
+/// Hash of everything that decides what an upgrade does: its arguments, target, values files
+/// and, for a local chart, the chart's files; paths are relative to `base`
+fn review_key(matches: &ArgMatches, base: &Path, context: &str, namespace: &str) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for part in [context, namespace] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    for arg in release_args(matches) {
+        hasher.update(arg.as_bytes());
+        hasher.update([0]);
+    }
+    for file in matches.get_many::<String>("values").unwrap_or_default() {
+        let path = base.join(file);
+        let contents = fs::read(&path)
+            .with_context(|| format!("Failed to read values file {}", path.display()))?;
+        hasher.update(&contents);
+        hasher.update([0]);
+    }
+    let chart = base.join(matches.get_one::<String>("chart").unwrap());
+    if chart.exists() {
+        for file in chart_files(&chart) {
+            hasher.update(
+                file.strip_prefix(&chart)
+                    .unwrap_or(&file)
+                    .to_string_lossy()
+                    .as_bytes(),
+            );
+            hasher.update([0]);
+            hasher.update(fs::read(&file)?);
+            hasher.update([0]);
+        }
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The files of a chart directory, in a stable order, or the packaged chart itself
+fn chart_files(chart: &Path) -> Vec<PathBuf> {
+    WalkDir::new(chart)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Refuse an upgrade whose review at `review` is missing or older than `max_age`
+fn check_review(review: &Path, context: &str, max_age: Duration, now: SystemTime) -> Result<()> {
+    let Ok(reviewed) = fs::metadata(review).and_then(|m| m.modified()) else {
+        bail!(
+            "{} is not a dev context (helm.dev_contexts); review this upgrade first with helm diff \
+             or helm template, using the same release, chart, values and target",
+            context
+        );
+    };
+    let age = now.duration_since(reviewed).unwrap_or_default();
+    if age > max_age {
+        bail!(
+            "The review of this upgrade is {} minutes old (helm.review_max_age_minutes = {}); run \
+             helm diff or helm template again",
+            age.as_secs() / 60,
+            max_age.as_secs() / 60
+        );
+    }
+    Ok(())
+}
+
+/// Whether `context` matches one of the `helm.dev_contexts` patterns
+fn is_dev(context: &str, patterns: &[String]) -> Result<bool> {
+    for pattern in patterns {
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid helm.dev_contexts pattern '{}'", pattern))?;
+        if regex.is_match(context) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_release_status() {
+        let release = json!({
+            "name": "web",
+            "namespace": "shop",
+            "version": 3,
+            "info": {
+                "first_deployed": "2026-01-05T10:00:00Z",
+                "last_deployed": "2026-02-01T09:30:00Z",
+                "status": "deployed",
+                "description": "Upgrade complete",
+            },
+            "chart": { "metadata": { "name": "web", "version": "1.2.0", "appVersion": "2.1.0" } },
+            "config": {},
+        });
+        assert_eq!(
+            ReleaseStatus::from_json(&release),
+            Some(ReleaseStatus {
+                name: "web".to_string(),
+                namespace: "shop".to_string(),
+                revision: 3,
+                status: "deployed".to_string(),
+                chart: "web-1.2.0".to_string(),
+                app_version: Some("2.1.0".to_string()),
+                updated: Some("2026-02-01T09:30:00Z".to_string()),
+                description: Some("Upgrade complete".to_string()),
+            })
+        );
+        assert_eq!(ReleaseStatus::from_json(&json!({ "name": "web" })), None);
+    }
+
+    #[test]
+    fn test_reviews() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("chart")).unwrap();
+        fs::write(dir.path().join("chart/Chart.yaml"), "name: web\n").unwrap();
+        fs::write(dir.path().join("prod.yaml"), "replicas: 3\n").unwrap();
+        let command = build_command();
+        let key = || {
+            let matches = command.clone().get_matches_from([
+                "helm",
+                "upgrade",
+                "web",
+                "chart",
+                "-f",
+                "prod.yaml",
+                "--set",
+                "a={x,y}",
+            ]);
+            let (_, matches) = matches.subcommand().unwrap();
+            review_key(matches, dir.path(), "gke_prod", "shop").unwrap()
+        };
+        let first = key();
+        assert_eq!(key(), first);
+        fs::write(dir.path().join("prod.yaml"), "replicas: 5\n").unwrap();
+        let second = key();
+        assert_ne!(second, first);
+        fs::write(dir.path().join("chart/Chart.yaml"), "name: api\n").unwrap();
+        assert_ne!(key(), second);
+
+        let review = dir.path().join("review");
+        let hour = Duration::from_secs(3600);
+        assert!(check_review(&review, "gke_prod", hour, SystemTime::now()).is_err());
+        fs::write(&review, "").unwrap();
+        assert!(check_review(&review, "gke_prod", hour, SystemTime::now()).is_ok());
+        assert!(check_review(&review, "gke_prod", hour, SystemTime::now() + 2 * hour).is_err());
+
+        let patterns = crate::config::HelmConfig::default().dev_contexts;
+        assert!(is_dev("kind-local", &patterns).unwrap());
+        assert!(is_dev("minikube", &patterns).unwrap());
+        assert!(!is_dev("gke_acme_europe-west1_prod-eu", &patterns).unwrap());
+    }
+}
@@ -0,0 +1,227 @@
+// file: src/commands/history.rs
+// version: 1.3.1
+// guid: 6d2b9e41-7a85-4c3f-b0d6-19e4f8a2c573
+
+//! `history` command: list, search and inspect the commands run through this utility
+
+use super::export::Effect;
+use crate::activity::short_id;
+use crate::executor::Executor;
+use crate::history::{self, format_duration, Filter, History, Run};
+use anyhow::{anyhow, Result};
+use chrono::{Local, Utc};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+/// Build the history command
+pub fn build_command() -> Command {
+    Command::new("history")
+        .add(Effect::ReadOnly)
+        .about("List, search and inspect the commands run through this utility")
+        .long_about(
+            "Every command the utility runs is recorded with its arguments, working directory, \
+             exit code, duration and captured output, so what an agent did can be audited \
+             afterwards. Runs are kept for history.retention_days.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(filter_args(
+            Command::new("list").about("List recorded runs, newest first"),
+        ))
+        .subcommand(filter_args(
+            Command::new("search")
+                .about("List runs whose command line or working directory contains TEXT")
+                .arg(
+                    Arg::new("text")
+                        .value_name("TEXT")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("Text to look for, e.g. 'git push'"),
+                ),
+        ))
+        .subcommand(
+            Command::new("show")
+                .about("Show a run and its captured output")
+                .arg(
+                    Arg::new("id")
+                        .value_name("ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(i64))
+                        .help("Run ID from 'history list'"),
+                )
+                .arg(format_arg(["text", "json"], "text")),
+        )
+}
+
+/// Arguments shared by `list` and `search`
+fn filter_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .value_name("TIME")
+                .help("Only runs started since TIME: an age like 2h or 3d, a date like 2024-05-01, today or yesterday"),
+        )
+        .arg(
+            Arg::new("failed-only")
+                .long("failed-only")
+                .action(ArgAction::SetTrue)
+                .help("Only runs that failed, timed out or did not start"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .short('n')
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("50")
+                .help("Show at most N runs (0 for all)"),
+        )
+        .arg(format_arg(["table", "json"], "table"))
+}
+
+fn format_arg(formats: [&'static str; 2], default: &'static str) -> Arg {
+    Arg::new("format")
+        .long("format")
+        .short('f')
+        .value_parser(formats)
+        .default_value(default)
+        .help("Output format")
+}
+
+/// Execute the history command
+pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
+    let history = History::open()?;
+    match matches.subcommand() {
+        Some(("list", sub_matches)) => list(&history, sub_matches, None),
+        Some(("search", sub_matches)) => list(
+            &history,
+            sub_matches,
+            sub_matches.get_one::<String>("text").cloned(),
+        ),
+        Some(("show", sub_matches)) => show(&history, sub_matches),
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+fn list(history: &History, matches: &ArgMatches, text: Option<String>) -> Result<()> {
+    let filter = Filter {
+        since: matches
+            .get_one::<String>("since")
+            .map(|since| history::parse_since(since, Local::now()))
+            .transpose()?,
+        failed_only: matches.get_flag("failed-only"),
+        text,
+        session_id: None,
+        limit: *matches.get_one::<usize>("limit").unwrap(),
+    };
+    let runs = history.list(&filter)?;
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&runs)?);
+        return Ok(());
+    }
+    if runs.is_empty() {
+        println!("No runs recorded");
+        return Ok(());
+    }
+
+    println!(
+        "{:>6} {:<19} {:>5} {:>9} {:<8}  COMMAND",
+        "ID", "STARTED", "EXIT", "DURATION", "SESSION"
+    );
+    for run in &runs {
+        println!(
+            "{:>6} {:<19} {:>5} {:>9} {:<8}  {}",
+            run.id,
+            run.started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            exit_code(run),
+            format_duration(run.duration_ms),
+            short_id(&run.session_id),
+            run.command_line()
+        );
+    }
+    Ok(())
+}
+
+fn show(history: &History, matches: &ArgMatches) -> Result<()> {
+    let id = *matches.get_one::<i64>("id").unwrap();
+    let run = history
+        .get(id)?
+        .ok_or_else(|| anyhow!("No run {} in history; see `history list`", id))?;
+    // Output may have been removed with the state directory's other files
+    let read = |path: &Option<PathBuf>| {
+        path.as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .map(|output| String::from_utf8_lossy(&output).to_string())
+    };
+    let output = read(&run.output_path);
+    let verbose_output = read(&run.verbose_output_path);
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let mut report = serde_json::to_value(&run)?;
+        report["output"] = json!(output);
+        if run.verbose_output_path.is_some() {
+            report["verbose_output"] = json!(verbose_output);
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let started = run.started_at.with_timezone(&Local);
+    println!("Run:        {}", run.id);
+    println!("Command:    {}", run.command_line());
+    println!("Directory:  {}", run.cwd.display());
+    println!(
+        "Started:    {} ({} ago)",
+        started.format("%Y-%m-%d %H:%M:%S"),
+        format_duration((Utc::now() - run.started_at).num_milliseconds().max(0) as u64)
+    );
+    println!("Duration:   {}", format_duration(run.duration_ms));
+    println!("Exit code:  {}", exit_code(&run));
+    if let Some(error) = &run.error {
+        println!("Error:      {}", error);
+    }
+    if let Some(reason) = &run.reason {
+        println!("Reason:     {}", reason);
+    }
+    if !run.labels.is_empty() {
+        println!("Labels:     {}", run.labels.join(", "));
+    }
+    println!("Session:    {}", run.session_id);
+    println!("Invocation: {}", run.execution_id);
+    match output {
+        Some(output) => {
+            println!();
+            print!("{}", output);
+            if !output.ends_with('\n') {
+                println!();
+            }
+        }
+        None if run.output_path.is_some() => println!("Output:     no longer available"),
+        None => println!("Output:     not captured (written to the terminal)"),
+    }
+    match verbose_output {
+        Some(output) => {
+            println!();
+            println!("Verbose re-run:");
+            print!("{}", output);
+            if !output.ends_with('\n') {
+                println!();
+            }
+        }
+        None if run.verbose_output_path.is_some() => println!("Verbose re-run: no longer available"),
+        None => {}
+    }
+    Ok(())
+}
+
+fn exit_code(run: &Run) -> String {
+    run.exit_code
+        .map(|code| code.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
@@ -0,0 +1,316 @@
+// file: src/commands/jobs.rs
+// version: 1.0.1
+// guid: 0e7c4a92-3f1b-4d58-9a6e-b2c8d5f17e03
+
+//! `jobs` command: start long-running commands in the background and manage them
+
+use super::export::Effect;
+use crate::activity::format_age;
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use crate::jobs::{self, JobOptions, JobRecord, JobRegistry};
+use anyhow::{bail, Result};
+use chrono::Utc;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Interval at which a followed log is checked for new output
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Build the jobs command
+pub fn build_command() -> Command {
+    Command::new("jobs")
+        .about("Run long-running commands in the background and manage them")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("start")
+                .add(Effect::Destructive)
+                .about("Start a command in the background and print its job ID")
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .short('n')
+                        .value_name("NAME")
+                        .help("Name to refer to the job by instead of its ID"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help("Stop the command after this many seconds (default: never)"),
+                )
+                .arg(
+                    Arg::new("cwd")
+                        .long("cwd")
+                        .value_name("DIR")
+                        .help("Working directory of the command"),
+                )
+                .arg(
+                    Arg::new("command")
+                        .value_name("COMMAND")
+                        .num_args(1..)
+                        .required(true)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                        .help("Command and its arguments, e.g. npm run dev"),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("List jobs started in the last 24 hours")
+                .arg(
+                    Arg::new("running")
+                        .long("running")
+                        .short('r')
+                        .action(ArgAction::SetTrue)
+                        .help("Only jobs that are still running"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("logs")
+                .add(Effect::ReadOnly)
+                .about("Print the output of a job")
+                .arg(job_arg())
+                .arg(
+                    Arg::new("lines")
+                        .long("lines")
+                        .short('n')
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Only the last N lines"),
+                )
+                .arg(
+                    Arg::new("follow")
+                        .long("follow")
+                        .short('f')
+                        .action(ArgAction::SetTrue)
+                        .help("Keep printing new output until the job ends"),
+                ),
+        )
+        .subcommand(
+            Command::new("attach")
+                .add(Effect::ReadOnly)
+                .about("Follow the output of a job until it ends and exit with its status")
+                .long_about(
+                    "Print the last lines of a job's output and follow it until the job ends, \
+                     then fail if the job did not exit successfully. Ctrl-C detaches without \
+                     stopping the job.",
+                )
+                .arg(job_arg()),
+        )
+        .subcommand(
+            Command::new("kill")
+                .add(Effect::Destructive)
+                .about("Stop a job and everything it started")
+                .arg(job_arg())
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Send SIGKILL right away instead of SIGTERM first"),
+                ),
+        )
+        .subcommand(
+            Command::new("supervise")
+                .hide(true)
+                .about("Run a job's command; started by 'jobs start'")
+                .arg(Arg::new("id").required(true)),
+        )
+}
+
+fn job_arg() -> Arg {
+    Arg::new("job")
+        .value_name("JOB")
+        .required(true)
+        .help("Job ID, unique ID prefix or name")
+}
+
+/// Execute the jobs command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("start", sub_matches)) => start(sub_matches, executor).await,
+        Some(("list", sub_matches)) => list(sub_matches),
+        Some(("logs", sub_matches)) => logs(sub_matches).await,
+        Some(("attach", sub_matches)) => attach(sub_matches).await,
+        Some(("kill", sub_matches)) => {
+            let job = jobs::kill(
+                sub_matches.get_one::<String>("job").unwrap(),
+                sub_matches.get_flag("force"),
+            )
+            .await?;
+            println!("Stopped job {}", job.describe());
+            Ok(())
+        }
+        Some(("supervise", sub_matches)) => {
+            Ok(jobs::supervise(sub_matches.get_one::<String>("id").unwrap()).await?)
+        }
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+async fn start(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let mut words = matches
+        .get_many::<String>("command")
+        .unwrap_or_default()
+        .cloned();
+    let spec = CommandSpec {
+        command: words.next().unwrap_or_default(),
+        args: words.collect(),
+        cwd: matches.get_one::<String>("cwd").map(PathBuf::from),
+        env: BTreeMap::new(),
+        stdin: None,
+    };
+    let options = JobOptions {
+        name: matches.get_one::<String>("name").cloned(),
+        timeout: matches
+            .get_one::<u64>("timeout")
+            .map(|seconds| Duration::from_secs(*seconds)),
+    };
+
+    let Some(job) = executor.execute_detached(&spec, &options).await? else {
+        return Ok(());
+    };
+    if let Some(error) = &job.error {
+        bail!("Job {} failed to start: {}", job.describe(), error);
+    }
+    println!("{}", job.id);
+    eprintln!(
+        "Started job {} ({}); see `jobs logs {}` and stop it with `jobs kill {}`",
+        job.describe(),
+        job.status.as_str(),
+        job.id,
+        job.id
+    );
+    Ok(())
+}
+
+fn list(matches: &ArgMatches) -> Result<()> {
+    let registry = JobRegistry::load()?;
+    let mut jobs: Vec<&JobRecord> = registry
+        .jobs
+        .values()
+        .filter(|job| !matches.get_flag("running") || job.status.is_active())
+        .collect();
+    jobs.sort_by_key(|job| std::cmp::Reverse(job.started_at));
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&jobs)?);
+        return Ok(());
+    }
+    if jobs.is_empty() {
+        println!("No jobs");
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    println!(
+        "{:<10} {:<16} {:<10} {:>5} {:>8} {:>8}  COMMAND",
+        "JOB", "NAME", "STATUS", "EXIT", "PID", "STARTED"
+    );
+    for job in jobs {
+        println!(
+            "{:<10} {:<16} {:<10} {:>5} {:>8} {:>8}  {}",
+            job.id,
+            job.name.as_deref().unwrap_or("-"),
+            job.status.as_str(),
+            job.exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            job.pid
+                .map(|pid| pid.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            format_age(now - job.started_at),
+            job.command
+        );
+    }
+    Ok(())
+}
+
+async fn logs(matches: &ArgMatches) -> Result<()> {
+    let job = JobRegistry::load()?
+        .find(matches.get_one::<String>("job").unwrap())?
+        .clone();
+    print_log(
+        &job,
+        matches.get_one::<usize>("lines").copied(),
+        matches.get_flag("follow"),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn attach(matches: &ArgMatches) -> Result<()> {
+    let job = JobRegistry::load()?
+        .find(matches.get_one::<String>("job").unwrap())?
+        .clone();
+    let job = print_log(&job, Some(10), true).await?;
+    match job.exit_code {
+        Some(0) => Ok(()),
+        Some(code) => bail!("Job {} exited with code {}", job.describe(), code),
+        None => bail!("Job {} ended: {}", job.describe(), job.status.as_str()),
+    }
+}
+
+/// Print a job's log, optionally only its last lines, and with `follow` keep printing what is
+/// appended until the job ends; returns the job as last seen
+async fn print_log(job: &JobRecord, lines: Option<usize>, follow: bool) -> Result<JobRecord> {
+    let mut file = match File::open(&job.log) {
+        Ok(file) => file,
+        Err(_) if !job.is_finished() => {
+            // The supervisor has not created the log yet
+            tokio::time::sleep(FOLLOW_INTERVAL).await;
+            File::open(&job.log)?
+        }
+        Err(e) => bail!("No output for job {}: {}", job.describe(), e),
+    };
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    let text = String::from_utf8_lossy(&content);
+    let mut stdout = std::io::stdout();
+    match lines {
+        Some(count) => {
+            let all: Vec<&str> = text.split_inclusive('\n').collect();
+            for line in &all[all.len().saturating_sub(count)..] {
+                stdout.write_all(line.as_bytes())?;
+            }
+        }
+        None => stdout.write_all(text.as_bytes())?,
+    }
+    stdout.flush()?;
+
+    let mut current = job.clone();
+    if !follow {
+        return Ok(current);
+    }
+    let mut position = content.len() as u64;
+    loop {
+        // Output written after the job was seen finishing is printed before stopping
+        let finished = current.is_finished();
+        file.seek(SeekFrom::Start(position))?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+        position += appended.len() as u64;
+        stdout.write_all(&appended)?;
+        stdout.flush()?;
+        if finished {
+            return Ok(current);
+        }
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+        current = JobRegistry::load()?.find(&job.id)?.clone();
+    }
+}
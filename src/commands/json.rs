@@ -0,0 +1,777 @@
+// file: src/commands/json.rs
+// version: 1.1.2
+// guid: 5c3e8a71-2d94-4b6f-9e08-b17f4a2c6d39
+
+//! `json` command: read and patch JSON documents without jq
+//!
+//! Paths use a small JSONPath-like syntax: `$.compilerOptions.paths`, `.scripts.build`,
+//! `dependencies["@types/node"]`, `.items[0]`, `.items[-1]`, `.items[*].name` and `$..version`
+//! (the leading `$` is optional). `get` and `set` need a path that names exactly one value;
+//! `query` accepts wildcards and recursive descent and prints every match.
+//!
+//! Documents are read from a file or from stdin (`-`). Comments and trailing commas, as found
+//! in `tsconfig.json`, are accepted when reading; rewriting such a file in place is refused
+//! because the comments would be lost. `set` and `merge` print the patched document unless
+//! `--in-place` is given, in which case the file is written back with its original indentation
+//! and key order (see [`crate::json_order`]) after the sandbox and content checks (or
+//! previewed as a diff in dry-run mode).
+
+use super::export::Effect;
+use super::file_write;
+use crate::executor::Executor;
+use crate::json_order::{KeyOrder, Ordered};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// One step of a parsed path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// An object member, `.name` or `["name"]`
+    Key(String),
+    /// An array element; negative indexes count from the end
+    Index(i64),
+    /// Every member or element, `.*` or `[*]`
+    Wildcard,
+    /// The current value and all of its descendants, `..`
+    Descend,
+}
+
+/// Build the json command
+pub fn build_command() -> Command {
+    Command::new("json")
+        .about("Query and patch JSON files with JSONPath-like paths")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("get")
+                .add(Effect::ReadOnly)
+                .about("Print the value at a path")
+                .arg(path_arg())
+                .arg(file_arg())
+                .arg(raw_arg())
+                .arg(
+                    Arg::new("default")
+                        .long("default")
+                        .value_name("JSON")
+                        .help("Print this value instead of failing when the path is missing"),
+                ),
+        )
+        .subcommand(
+            Command::new("query")
+                .add(Effect::ReadOnly)
+                .about("Print every value matching a path, one per line")
+                .arg(path_arg())
+                .arg(file_arg())
+                .arg(raw_arg())
+                .arg(
+                    Arg::new("with-paths")
+                        .long("with-paths")
+                        .action(ArgAction::SetTrue)
+                        .help("Prefix each value with its concrete path and a tab"),
+                ),
+        )
+        .subcommand(
+            Command::new("set")
+                .add(Effect::Destructive)
+                .about("Set the value at a path, creating missing objects on the way")
+                .arg(path_arg())
+                .arg(
+                    Arg::new("value").required(true).help(
+                        "New value as JSON; text that is not valid JSON is taken as a string",
+                    ),
+                )
+                .arg(file_arg())
+                .arg(
+                    Arg::new("string")
+                        .long("string")
+                        .action(ArgAction::SetTrue)
+                        .help("Always take VALUE as a string"),
+                )
+                .arg(in_place_arg()),
+        )
+        .subcommand(
+            Command::new("merge")
+                .add(Effect::Destructive)
+                .about("Deep-merge a JSON merge patch (RFC 7386) into the document")
+                .arg(file_arg())
+                .arg(
+                    Arg::new("patch")
+                        .long("patch")
+                        .value_name("JSON")
+                        .help("Merge patch given inline"),
+                )
+                .arg(
+                    Arg::new("patch-file")
+                        .long("patch-file")
+                        .value_name("FILE")
+                        .help("Merge patch read from a file"),
+                )
+                .group(
+                    ArgGroup::new("source")
+                        .args(["patch", "patch-file"])
+                        .required(true),
+                )
+                .arg(in_place_arg()),
+        )
+}
+
+pub(crate) fn path_arg() -> Arg {
+    Arg::new("path")
+        .required(true)
+        .value_name("PATH")
+        .help("Path such as $.scripts.build or .items[0].name")
+}
+
+fn file_arg() -> Arg {
+    Arg::new("file")
+        .value_name("FILE")
+        .help("JSON file to read; stdin when omitted or '-'")
+}
+
+pub(crate) fn raw_arg() -> Arg {
+    Arg::new("raw")
+        .short('r')
+        .long("raw")
+        .action(ArgAction::SetTrue)
+        .help("Print strings without quotes")
+}
+
+fn in_place_arg() -> Arg {
+    Arg::new("in-place")
+        .short('i')
+        .long("in-place")
+        .action(ArgAction::SetTrue)
+        .help("Write the result back to FILE instead of printing it")
+}
+
+/// Execute json subcommands
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("get", sub)) => {
+            let path = parse_path(string(sub, "path"))?;
+            if !is_singular(&path) {
+                bail!(
+                    "Path '{}' can match several values; use `json query` instead",
+                    string(sub, "path")
+                );
+            }
+            let document = load(sub, executor)?;
+            let default = sub
+                .get_one::<String>("default")
+                .map(|default| parse_value(default, false));
+            print_value(&document.value, sub, default)
+        }
+        Some(("query", sub)) => {
+            let path = parse_path(string(sub, "path"))?;
+            let document = load(sub, executor)?;
+            let raw = sub.get_flag("raw");
+            for (location, value) in select(&document.value, &path) {
+                let value = render_value(value, raw, false)?;
+                if sub.get_flag("with-paths") {
+                    println!("{}\t{}", location, value);
+                } else {
+                    println!("{}", value);
+                }
+            }
+            Ok(())
+        }
+        Some(("set", sub)) => {
+            let path = parse_path(string(sub, "path"))?;
+            let value = parse_value(string(sub, "value"), sub.get_flag("string"));
+            let mut document = load(sub, executor)?;
+            set(&mut document.value, &path, value)
+                .with_context(|| format!("Cannot set '{}'", string(sub, "path")))?;
+            finish(sub, executor, &document)
+        }
+        Some(("merge", sub)) => {
+            let patch = match sub.get_one::<String>("patch") {
+                Some(patch) => parse_document(patch).context("Invalid --patch")?.0,
+                None => {
+                    let file = string(sub, "patch-file");
+                    let file = executor.check_path(file)?;
+                    let text = fs::read_to_string(&file)
+                        .with_context(|| format!("Failed to read {}", file.display()))?;
+                    parse_document(&text)
+                        .with_context(|| format!("Invalid JSON in {}", file.display()))?
+                        .0
+                }
+            };
+            let mut document = load(sub, executor)?;
+            merge_patch(&mut document.value, &patch);
+            finish(sub, executor, &document)
+        }
+        _ => Err(anyhow!("Unknown json subcommand")),
+    }
+}
+
+pub(crate) fn string<'a>(matches: &'a ArgMatches, name: &str) -> &'a str {
+    matches
+        .get_one::<String>(name)
+        .map(String::as_str)
+        .unwrap_or_default()
+}
+
+/// A parsed input document with what is needed to write it back the same way
+struct Document {
+    file: Option<String>,
+    value: Value,
+    /// Key order of the file, kept when it is written back
+    order: KeyOrder,
+    indent: String,
+    trailing_newline: bool,
+    has_comments: bool,
+}
+
+fn load(matches: &ArgMatches, executor: &Executor) -> Result<Document> {
+    let file = matches
+        .get_one::<String>("file")
+        .filter(|file| file.as_str() != "-")
+        .cloned();
+    let text = match &file {
+        Some(file) => {
+            let path = executor.check_path(file)?;
+            fs::read_to_string(&path).with_context(|| format!("Failed to read {}", file))?
+        }
+        None => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read stdin")?;
+            text
+        }
+    };
+    let source = file.as_deref().unwrap_or("stdin");
+    let (value, has_comments) =
+        parse_document(&text).with_context(|| format!("Invalid JSON in {}", source))?;
+    let order = if has_comments {
+        KeyOrder::of(&strip_jsonc(&text))?
+    } else {
+        KeyOrder::of(&text)?
+    };
+    Ok(Document {
+        file,
+        value,
+        order,
+        indent: detect_indent(&text),
+        trailing_newline: text.is_empty() || text.ends_with('\n'),
+        has_comments,
+    })
+}
+
+/// Print the patched document, or write it back with `--in-place`
+fn finish(matches: &ArgMatches, executor: &Executor, document: &Document) -> Result<()> {
+    let mut output = render_document(&document.value, &document.order, &document.indent)?;
+    if document.trailing_newline {
+        output.push('\n');
+    }
+    if !matches.get_flag("in-place") {
+        print!("{}", output);
+        return Ok(());
+    }
+    let Some(file) = &document.file else {
+        bail!("--in-place needs a FILE, not stdin");
+    };
+    if document.has_comments {
+        bail!(
+            "{} contains comments or trailing commas, which would be lost by rewriting it",
+            file
+        );
+    }
+    file_write::update(executor, Path::new(file), output.as_bytes(), false)
+}
+
+/// Print the value at the singular path `path` of `matches` as `get` does, honouring `--raw`
+pub(crate) fn print_value(
+    document: &Value,
+    matches: &ArgMatches,
+    default: Option<Value>,
+) -> Result<()> {
+    let text = string(matches, "path");
+    let path = parse_path(text)?;
+    if !is_singular(&path) {
+        bail!(
+            "Path '{}' can match several values; use a path to one value",
+            text
+        );
+    }
+    let value = match select(document, &path).into_iter().next() {
+        Some((_, value)) => value.clone(),
+        None => default.ok_or_else(|| anyhow!("No value at path '{}'", text))?,
+    };
+    println!("{}", render_value(&value, matches.get_flag("raw"), true)?);
+    Ok(())
+}
+
+/// Parse a path such as `$.a.b[0]`, `a["b.c"][*]` or `$..name`
+pub fn parse_path(text: &str) -> Result<Vec<Segment>> {
+    let trimmed = text.trim();
+    let rest = trimmed.strip_prefix('$').unwrap_or(trimmed);
+    if rest.is_empty() || rest == "." {
+        return Ok(Vec::new());
+    }
+    let chars: Vec<char> = rest.chars().collect();
+    let mut segments = Vec::new();
+    let mut i = 0;
+    if chars[0] != '.' && chars[0] != '[' {
+        // A bare first name, as in `scripts.build`
+        let (name, next) = read_name(&chars, 0);
+        segments.push(name_segment(name));
+        i = next;
+    }
+    while i < chars.len() {
+        match chars[i] {
+            '.' if chars.get(i + 1) == Some(&'.') => {
+                segments.push(Segment::Descend);
+                i += 2;
+                if chars.get(i) != Some(&'[') {
+                    let (name, next) = read_name(&chars, i);
+                    if name.is_empty() {
+                        bail!("Invalid path '{}': expected a name after '..'", text);
+                    }
+                    segments.push(name_segment(name));
+                    i = next;
+                }
+            }
+            '.' => {
+                let (name, next) = read_name(&chars, i + 1);
+                if name.is_empty() {
+                    bail!("Invalid path '{}': expected a name after '.'", text);
+                }
+                segments.push(name_segment(name));
+                i = next;
+            }
+            '[' => {
+                let (segment, next) = read_bracket(&chars, i + 1)
+                    .ok_or_else(|| anyhow!("Invalid path '{}': malformed [...]", text))?;
+                segments.push(segment);
+                i = next;
+            }
+            other => bail!("Invalid path '{}': unexpected '{}'", text, other),
+        }
+    }
+    if segments.last() == Some(&Segment::Descend) {
+        bail!("Invalid path '{}': '..' must be followed by a name", text);
+    }
+    Ok(segments)
+}
+
+fn read_name(chars: &[char], start: usize) -> (String, usize) {
+    let end = chars[start..]
+        .iter()
+        .position(|c| *c == '.' || *c == '[')
+        .map_or(chars.len(), |offset| start + offset);
+    (chars[start..end].iter().collect(), end)
+}
+
+fn name_segment(name: String) -> Segment {
+    if name == "*" {
+        Segment::Wildcard
+    } else {
+        Segment::Key(name)
+    }
+}
+
+/// Parse the inside of `[...]` starting after the `[`; returns the index after the `]`
+fn read_bracket(chars: &[char], start: usize) -> Option<(Segment, usize)> {
+    let quote = *chars.get(start)?;
+    if quote == '"' || quote == '\'' {
+        let mut key = String::new();
+        let mut i = start + 1;
+        loop {
+            match *chars.get(i)? {
+                '\\' => {
+                    key.push(*chars.get(i + 1)?);
+                    i += 2;
+                }
+                c if c == quote => break,
+                c => {
+                    key.push(c);
+                    i += 1;
+                }
+            }
+        }
+        return (chars.get(i + 1) == Some(&']')).then_some((Segment::Key(key), i + 2));
+    }
+    let end = start + chars[start..].iter().position(|c| *c == ']')?;
+    let inner: String = chars[start..end].iter().collect();
+    let segment = match inner.trim() {
+        "*" => Segment::Wildcard,
+        index => Segment::Index(index.parse().ok()?),
+    };
+    Some((segment, end + 1))
+}
+
+/// Whether a path names at most one value
+pub fn is_singular(path: &[Segment]) -> bool {
+    path.iter()
+        .all(|segment| matches!(segment, Segment::Key(_) | Segment::Index(_)))
+}
+
+/// Every value matching `path`, in document order, with its concrete path
+pub fn select<'a>(root: &'a Value, path: &[Segment]) -> Vec<(String, &'a Value)> {
+    let mut current = vec![("$".to_string(), root)];
+    for segment in path {
+        let mut next = Vec::new();
+        for (location, value) in current {
+            match segment {
+                Segment::Key(key) => {
+                    if let Some(child) = value.as_object().and_then(|map| map.get(key)) {
+                        next.push((format!("{}{}", location, format_key(key)), child));
+                    }
+                }
+                Segment::Index(index) => {
+                    if let Some(items) = value.as_array() {
+                        if let Some(position) = resolve_index(*index, items.len()) {
+                            next.push((format!("{}[{}]", location, position), &items[position]));
+                        }
+                    }
+                }
+                Segment::Wildcard => children(&location, value, &mut next),
+                Segment::Descend => descendants(location, value, &mut next),
+            }
+        }
+        current = next;
+    }
+    current
+}
+
+fn children<'a>(location: &str, value: &'a Value, out: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                out.push((format!("{}{}", location, format_key(key)), child));
+            }
+        }
+        Value::Array(items) => {
+            for (position, child) in items.iter().enumerate() {
+                out.push((format!("{}[{}]", location, position), child));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn descendants<'a>(location: String, value: &'a Value, out: &mut Vec<(String, &'a Value)>) {
+    let mut direct = Vec::new();
+    children(&location, value, &mut direct);
+    out.push((location, value));
+    for (location, child) in direct {
+        descendants(location, child, out);
+    }
+}
+
+pub(crate) fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let position = if index < 0 {
+        len.checked_sub(usize::try_from(index.unsigned_abs()).ok()?)?
+    } else {
+        usize::try_from(index).ok()?
+    };
+    (position < len).then_some(position)
+}
+
+fn format_key(key: &str) -> String {
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if plain {
+        format!(".{}", key)
+    } else {
+        format!("[{}]", Value::String(key.to_string()))
+    }
+}
+
+/// Set the value at a singular path, creating missing object members on the way
+///
+/// An index equal to the array length appends; missing containers are created as objects
+/// for names and arrays for indexes.
+pub fn set(root: &mut Value, path: &[Segment], value: Value) -> Result<()> {
+    let Some((segment, rest)) = path.split_first() else {
+        *root = value;
+        return Ok(());
+    };
+    match segment {
+        Segment::Key(key) => {
+            if root.is_null() {
+                *root = Value::Object(Map::new());
+            }
+            let Value::Object(map) = root else {
+                bail!("cannot set member '{}' of {}", key, kind(root));
+            };
+            set(map.entry(key.clone()).or_insert(Value::Null), rest, value)
+        }
+        Segment::Index(index) => {
+            if root.is_null() {
+                *root = Value::Array(Vec::new());
+            }
+            let Value::Array(items) = root else {
+                bail!("cannot set element {} of {}", index, kind(root));
+            };
+            if usize::try_from(*index).ok() == Some(items.len()) {
+                items.push(Value::Null);
+            }
+            let position = resolve_index(*index, items.len()).ok_or_else(|| {
+                anyhow!(
+                    "index {} is out of range for {} elements",
+                    index,
+                    items.len()
+                )
+            })?;
+            set(&mut items[position], rest, value)
+        }
+        Segment::Wildcard | Segment::Descend => {
+            bail!("wildcards and '..' cannot be used to set values")
+        }
+    }
+}
+
+/// Remove the value at a singular path
+pub fn delete(root: &mut Value, path: &[Segment]) -> Result<()> {
+    let Some((last, parents)) = path.split_last() else {
+        bail!("cannot delete the whole document");
+    };
+    let mut parent = root;
+    for segment in parents {
+        parent = match (segment, parent) {
+            (Segment::Key(key), Value::Object(map)) => map.get_mut(key),
+            (Segment::Index(index), Value::Array(items)) => {
+                resolve_index(*index, items.len()).map(move |position| &mut items[position])
+            }
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("no value at this path"))?;
+    }
+    let removed = match (last, parent) {
+        (Segment::Key(key), Value::Object(map)) => map.remove(key),
+        (Segment::Index(index), Value::Array(items)) => {
+            resolve_index(*index, items.len()).map(|position| items.remove(position))
+        }
+        _ => None,
+    };
+    removed
+        .map(|_| ())
+        .ok_or_else(|| anyhow!("no value at this path"))
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7386): objects merge recursively, `null` removes a member
+/// and anything else, arrays included, replaces the target
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(map) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                map.remove(key);
+            } else {
+                merge_patch(map.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Parse a value argument as JSON, falling back to a string
+fn parse_value(text: &str, force_string: bool) -> Value {
+    if force_string {
+        return Value::String(text.to_string());
+    }
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+/// Parse JSON, retrying without comments and trailing commas; reports whether that was needed
+pub fn parse_document(text: &str) -> Result<(Value, bool)> {
+    match serde_json::from_str(text) {
+        Ok(value) => Ok((value, false)),
+        Err(error) => {
+            let stripped = strip_jsonc(text);
+            if stripped == text {
+                return Err(error.into());
+            }
+            Ok((serde_json::from_str(&stripped)?, true))
+        }
+    }
+}
+
+/// Remove `//` and `/* */` comments and trailing commas outside of strings
+fn strip_jsonc(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    let mut in_string = false;
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            out.push(c);
+            if c == '\\' {
+                if let Some(next) = chars.get(i + 1) {
+                    out.push(*next);
+                    i += 1;
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            i += 1;
+            continue;
+        }
+        match (c, chars.get(i + 1)) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+                i += 1;
+            }
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            (',', _) => {
+                if !matches!(next_significant(&chars, i + 1), Some('}' | ']')) {
+                    out.push(c);
+                }
+                i += 1;
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// The next character from `start` that is neither whitespace nor inside a comment
+fn next_significant(chars: &[char], start: usize) -> Option<char> {
+    let mut i = start;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            (c, _) if c.is_whitespace() => i += 1,
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            (c, _) => return Some(c),
+        }
+    }
+    None
+}
+
+/// The indentation of the first indented line, two spaces if there is none
+fn detect_indent(text: &str) -> String {
+    text.lines()
+        .skip(1)
+        .map(|line| {
+            line.chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect::<String>()
+        })
+        .find(|indent| !indent.is_empty())
+        .unwrap_or_else(|| "  ".to_string())
+}
+
+fn render_document(value: &Value, order: &KeyOrder, indent: &str) -> Result<String> {
+    let mut out = Vec::new();
+    let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    Ordered::new(value, order).serialize(&mut serializer)?;
+    Ok(String::from_utf8(out)?)
+}
+
+fn render_value(value: &Value, raw: bool, pretty: bool) -> Result<String> {
+    Ok(match value {
+        Value::String(text) if raw => text.clone(),
+        _ if pretty => serde_json::to_string_pretty(value)?,
+        _ => serde_json::to_string(value)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_paths_select_set_and_merge() {
+        assert_eq!(
+            parse_path("$.a[\"b.c\"][-1]").unwrap(),
+            vec![
+                Segment::Key("a".into()),
+                Segment::Key("b.c".into()),
+                Segment::Index(-1)
+            ]
+        );
+        assert_eq!(
+            parse_path("scripts.build").unwrap(),
+            parse_path(".scripts.build").unwrap()
+        );
+        assert!(parse_path("$..").is_err());
+        assert!(parse_path(".a[x]").is_err());
+
+        let doc = json!({"a": {"v": 1, "list": [{"v": 2}, {"v": 3}]}, "@types/node": "20"});
+        let found: Vec<_> = select(&doc, &parse_path("$..v").unwrap())
+            .into_iter()
+            .map(|(path, value)| format!("{}={}", path, value))
+            .collect();
+        assert_eq!(found, vec!["$.a.v=1", "$.a.list[0].v=2", "$.a.list[1].v=3"]);
+        let found = select(&doc, &parse_path("['@types/node']").unwrap());
+        assert_eq!(found[0].0, "$[\"@types/node\"]");
+
+        let mut doc = json!({"list": [1]});
+        set(&mut doc, &parse_path(".a.b").unwrap(), json!(true)).unwrap();
+        set(&mut doc, &parse_path(".list[1]").unwrap(), json!(2)).unwrap();
+        assert!(set(&mut doc, &parse_path(".list[5]").unwrap(), json!(0)).is_err());
+        assert!(set(&mut doc, &parse_path(".a.b.c").unwrap(), json!(0)).is_err());
+        assert_eq!(doc, json!({"list": [1, 2], "a": {"b": true}}));
+
+        merge_patch(&mut doc, &json!({"a": {"b": null, "c": [3]}, "list": null}));
+        assert_eq!(doc, json!({"a": {"c": [3]}}));
+    }
+
+    #[test]
+    fn test_jsonc_and_indent() {
+        let text = "{\n    // comment\n    \"a\": \"x//y\", /* more */\n    \"b\": [1, 2,],\n}\n";
+        let (value, has_comments) = parse_document(text).unwrap();
+        assert!(has_comments);
+        assert_eq!(value, json!({"a": "x//y", "b": [1, 2]}));
+        assert_eq!(detect_indent(text), "    ");
+        assert!(!parse_document("{\"a\": 1}").unwrap().1);
+        assert!(parse_document("{\"a\": }").is_err());
+    }
+}
@@ -0,0 +1,620 @@
+// file: src/commands/jvm.rs
+// version: 1.0.2
+// guid: 8a3d6f15-c27e-4b90-9e41-5f0b7c2d83a6
+
+//! `jvm` command: build, test, list dependencies and format with Gradle or Maven
+//!
+//! The project directory decides the build tool: Gradle when it has a `gradlew` or a Gradle
+//! build file, Maven when it has a `pom.xml`; `--tool` picks one for projects with both. Gradle
+//! runs through the project's `gradlew` when there is one, but only once
+//! `gradle/wrapper/gradle-wrapper.jar` checks out: the wrapper jar is code committed to the
+//! repository that runs on every build, so its SHA-256 must be one of `jvm.gradle_wrapper_sha256`
+//! (the checksums Gradle publishes) or have been approved with `jvm approve-wrapper`, and the
+//! distribution it downloads must come over https.
+//!
+//! `--offline` or `jvm.offline` builds from the dependency cache. `test` prints how many tests
+//! passed, failed and were skipped, read from the JUnit XML reports the run wrote, and with
+//! `--format json` also the failed tests and compile errors in the build output (see
+//! [`crate::parsers::gradle`] and [`crate::parsers::maven`]).
+
+use super::export::{Effect, Remote};
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use crate::integrity::{self, short_hash, Approvals};
+use crate::parsers::{self, junit, Diagnostic, TestCounts, Tool};
+use crate::security::audit;
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// Where the Gradle wrapper keeps its jar and properties, relative to the project
+const WRAPPER_JAR: &str = "gradle/wrapper/gradle-wrapper.jar";
+const WRAPPER_PROPERTIES: &str = "gradle/wrapper/gradle-wrapper.properties";
+
+/// Build the jvm command
+pub fn build_command() -> Command {
+    Command::new("jvm")
+        .about("Build, test, list dependencies and format JVM projects with Gradle or Maven")
+        .long_about(
+            "Run Gradle, through a verified gradlew when the project has one, or Maven. The SHA-256 \
+             of gradle-wrapper.jar must be in jvm.gradle_wrapper_sha256 or approved with jvm \
+             approve-wrapper. test summarizes the JUnit reports of the run.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            build_args(
+                Command::new("build")
+                    .add(Remote)
+                    .about("Compile, test and package the project"),
+            )
+            .arg(
+                Arg::new("skip-tests")
+                    .long("skip-tests")
+                    .action(ArgAction::SetTrue)
+                    .help("Build without running the tests"),
+            ),
+        )
+        .subcommand(
+            build_args(
+                Command::new("test")
+                    .add(Remote)
+                    .about("Run the tests and summarize the results"),
+            )
+                .arg(
+                    Arg::new("tests")
+                        .long("tests")
+                        .value_name("PATTERN")
+                        .action(ArgAction::Append)
+                        .help("Only run tests matching PATTERN, e.g. app.CalcTest or CalcTest#adds*"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format: the build's own output, or a JSON summary"),
+                ),
+        )
+        .subcommand(
+            build_args(
+                Command::new("dependencies")
+                    .add(Effect::ReadOnly)
+                    .add(Remote)
+                    .about("Print the dependency tree"),
+            )
+            .arg(
+                Arg::new("configuration")
+                    .long("configuration")
+                    .value_name("NAME")
+                    .help("Gradle configuration to print, e.g. runtimeClasspath"),
+            ),
+        )
+        .subcommand(build_args(
+            Command::new("spotless-apply")
+                .add(Effect::Destructive)
+                .about("Format the sources with the Spotless plugin"),
+        ))
+        .subcommand(
+            Command::new("wrapper")
+                .add(Effect::ReadOnly)
+                .about("Check gradle-wrapper.jar and print its SHA-256")
+                .arg(dir()),
+        )
+        .subcommand(
+            Command::new("approve-wrapper")
+                .about("Approve the current gradle-wrapper.jar after checking it against Gradle's checksums")
+                .arg(dir()),
+        )
+}
+
+fn dir() -> Arg {
+    Arg::new("dir")
+        .long("dir")
+        .short('C')
+        .value_name("DIR")
+        .default_value(".")
+        .help("Project directory")
+}
+
+/// Add the arguments every build shares
+fn build_args(command: Command) -> Command {
+    command
+        .arg(dir())
+        .arg(
+            Arg::new("tool")
+                .long("tool")
+                .value_parser(["gradle", "maven"])
+                .help("Build tool, for projects with both Gradle and Maven builds"),
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .action(ArgAction::SetTrue)
+                .help("Build with the dependencies already downloaded (also jvm.offline)"),
+        )
+        .arg(
+            Arg::new("args")
+                .value_name("ARGS")
+                .num_args(0..)
+                .last(true)
+                .allow_hyphen_values(true)
+                .help("Further arguments for Gradle or Maven after --, e.g. -- --info"),
+        )
+}
+
+/// The build tool of a project
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BuildTool {
+    Gradle,
+    Maven,
+}
+
+impl BuildTool {
+    /// The tool chosen with `--tool`, or the one whose build files `dir` has
+    fn detect(dir: &Path, choice: Option<&str>) -> Result<Self> {
+        let has = |names: &[&str]| names.iter().any(|name| dir.join(name).is_file());
+        let gradle = has(&[
+            "gradlew",
+            "build.gradle",
+            "build.gradle.kts",
+            "settings.gradle",
+            "settings.gradle.kts",
+        ]);
+        let maven = has(&["pom.xml"]);
+        match choice {
+            Some("gradle") if gradle => Ok(Self::Gradle),
+            Some("maven") if maven => Ok(Self::Maven),
+            Some(tool) => bail!("{} has no {} build", dir.display(), tool),
+            None if gradle && maven => bail!(
+                "{} has both a Gradle and a Maven build; choose one with --tool",
+                dir.display()
+            ),
+            None if gradle => Ok(Self::Gradle),
+            None if maven => Ok(Self::Maven),
+            None => bail!("{} has neither a Gradle build nor a pom.xml", dir.display()),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gradle => "gradle",
+            Self::Maven => "maven",
+        }
+    }
+
+    fn parser(self) -> Tool {
+        match self {
+            Self::Gradle => Tool::Gradle,
+            Self::Maven => Tool::Maven,
+        }
+    }
+}
+
+/// Execute the jvm command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let Some((subcommand, sub_matches)) = matches.subcommand() else {
+        unreachable!("subcommand required");
+    };
+    let dir = executor.check_path(sub_matches.get_one::<String>("dir").unwrap())?;
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    match subcommand {
+        "wrapper" => {
+            let wrapper = Wrapper::read(&dir)?;
+            println!("{}  {}", wrapper.sha256, wrapper.jar.display());
+            println!("distributionUrl={}", wrapper.distribution_url);
+            match wrapper.verdict(executor)? {
+                Some(source) => println!("Accepted ({})", source),
+                None => bail!("{}", wrapper.refusal()),
+            }
+            Ok(())
+        }
+        "approve-wrapper" => {
+            let wrapper = Wrapper::read(&dir)?;
+            if executor.config().safety.dry_run {
+                println!(
+                    "DRY RUN: would approve {} ({})",
+                    wrapper.jar.display(),
+                    wrapper.sha256
+                );
+                return Ok(());
+            }
+            Approvals::update(|approvals| {
+                approvals.approve("gradlew", &wrapper.jar, &wrapper.sha256)
+            })?;
+            println!(
+                "Approved {} with SHA-256 {}",
+                wrapper.jar.display(),
+                short_hash(&wrapper.sha256)
+            );
+            Ok(())
+        }
+        _ => build(subcommand, sub_matches, &dir, executor).await,
+    }
+}
+
+/// Run `subcommand` with the project's build tool
+async fn build(
+    subcommand: &str,
+    matches: &ArgMatches,
+    dir: &Path,
+    executor: &Executor,
+) -> Result<()> {
+    let tool = BuildTool::detect(dir, matches.get_one::<String>("tool").map(String::as_str))?;
+    let offline = matches.get_flag("offline") || executor.config().jvm.offline;
+    let args = build_tool_args(tool, subcommand, matches, offline);
+
+    let program = match tool {
+        BuildTool::Gradle if dir.join("gradlew").is_file() => {
+            let wrapper = Wrapper::read(dir)?;
+            if wrapper.verdict(executor)?.is_none() {
+                let message = wrapper.refusal();
+                audit::log_security_violation("gradlew", &args, &message);
+                bail!(message);
+            }
+            executor.use_binary("gradlew", dir.join("gradlew"));
+            "gradlew"
+        }
+        BuildTool::Gradle => "gradle",
+        BuildTool::Maven => "mvn",
+    };
+    let spec = CommandSpec {
+        command: program.to_string(),
+        args,
+        cwd: Some(dir.to_path_buf()),
+        env: BTreeMap::new(),
+        stdin: None,
+    };
+
+    let started = SystemTime::now();
+    let output = executor.execute_spec_capture_full(&spec).await?;
+    let failure = || {
+        format!(
+            "{} failed with {}",
+            spec,
+            output
+                .exit_code
+                .map(|code| format!("exit code {}", code))
+                .unwrap_or_else(|| "a signal".to_string())
+        )
+    };
+    if subcommand != "test" {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+        if !output.success() {
+            bail!(failure());
+        }
+        return Ok(());
+    }
+
+    let tests = test_counts(dir, started);
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let diagnostics: Vec<Diagnostic> = tool
+            .parser()
+            .parse(&output.stdout, &output.stderr)
+            .into_iter()
+            .map(|diagnostic| diagnostic.relative_to(dir))
+            .collect();
+        let mut report = json!({
+            "tool": tool.name(),
+            "command": subcommand,
+            "args": spec.args,
+            "exit_code": output.exit_code,
+            "diagnostics": diagnostics,
+            "summary": parsers::summary(&diagnostics),
+            "tests": tests,
+        });
+        // Without findings, the build's own errors are the only explanation of a failure
+        if !output.success() && diagnostics.is_empty() {
+            report["stderr"] = json!(output.stderr);
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+        match tests {
+            Some(tests) => eprintln!(
+                "Tests: {} passed, {} failed, {} skipped",
+                tests.passed, tests.failed, tests.ignored
+            ),
+            None => eprintln!("Tests: no test reports were written"),
+        }
+    }
+    if !output.success() {
+        bail!(failure());
+    }
+    Ok(())
+}
+
+/// Arguments for `tool` that run `subcommand`
+fn build_tool_args(
+    tool: BuildTool,
+    subcommand: &str,
+    matches: &ArgMatches,
+    offline: bool,
+) -> Vec<String> {
+    let flag = |id: &str| matches.try_get_one::<bool>(id).ok().flatten() == Some(&true);
+    let values = |id: &str| -> Vec<String> {
+        matches
+            .try_get_many::<String>(id)
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .cloned()
+            .collect()
+    };
+    let mut args: Vec<String> = Vec::new();
+    match tool {
+        BuildTool::Gradle => {
+            args.push("--console=plain".to_string());
+            if offline {
+                args.push("--offline".to_string());
+            }
+            match subcommand {
+                "build" => {
+                    args.push("build".to_string());
+                    if flag("skip-tests") {
+                        args.extend(["-x".to_string(), "test".to_string()]);
+                    }
+                }
+                "test" => {
+                    args.push("test".to_string());
+                    for pattern in values("tests") {
+                        args.extend(["--tests".to_string(), pattern]);
+                    }
+                }
+                "dependencies" => {
+                    args.push("dependencies".to_string());
+                    for configuration in values("configuration") {
+                        args.extend(["--configuration".to_string(), configuration]);
+                    }
+                }
+                _ => args.push("spotlessApply".to_string()),
+            }
+        }
+        BuildTool::Maven => {
+            args.push("-B".to_string());
+            if offline {
+                args.push("-o".to_string());
+            }
+            match subcommand {
+                "build" => {
+                    args.push("package".to_string());
+                    if flag("skip-tests") {
+                        args.push("-DskipTests".to_string());
+                    }
+                }
+                "test" => {
+                    args.push("test".to_string());
+                    let patterns = values("tests");
+                    if !patterns.is_empty() {
+                        args.push(format!("-Dtest={}", patterns.join(",")));
+                    }
+                }
+                "dependencies" => args.push("dependency:tree".to_string()),
+                _ => args.push("spotless:apply".to_string()),
+            }
+        }
+    }
+    args.extend(values("args"));
+    args
+}
+
+/// The Gradle wrapper of a project
+struct Wrapper {
+    /// Canonical path of `gradle-wrapper.jar`
+    jar: PathBuf,
+    sha256: String,
+    distribution_url: String,
+}
+
+impl Wrapper {
+    fn read(dir: &Path) -> Result<Self> {
+        let jar = dir.join(WRAPPER_JAR);
+        if !jar.is_file() {
+            bail!(
+                "{} has no {}; without it gradlew cannot be verified",
+                dir.display(),
+                WRAPPER_JAR
+            );
+        }
+        let (jar, sha256) = integrity::fingerprint(&jar)?;
+        let properties = fs::read_to_string(dir.join(WRAPPER_PROPERTIES))
+            .with_context(|| format!("Failed to read {}", WRAPPER_PROPERTIES))?;
+        let Some(distribution_url) = property(&properties, "distributionUrl") else {
+            bail!("{} sets no distributionUrl", WRAPPER_PROPERTIES);
+        };
+        if !distribution_url.starts_with("https://") {
+            bail!(
+                "{} downloads Gradle from {}, which is not https",
+                WRAPPER_PROPERTIES,
+                distribution_url
+            );
+        }
+        if property(&properties, "distributionSha256Sum").is_none() {
+            warn!(
+                "{} sets no distributionSha256Sum; the Gradle distribution is not verified",
+                WRAPPER_PROPERTIES
+            );
+        }
+        Ok(Self {
+            jar,
+            sha256,
+            distribution_url,
+        })
+    }
+
+    /// Why the jar is accepted: `jvm.gradle_wrapper_sha256` or an approval; `None` if it is not
+    fn verdict(&self, executor: &Executor) -> Result<Option<&'static str>> {
+        if executor
+            .config()
+            .jvm
+            .gradle_wrapper_sha256
+            .iter()
+            .any(|sha256| sha256.eq_ignore_ascii_case(&self.sha256))
+        {
+            return Ok(Some("jvm.gradle_wrapper_sha256"));
+        }
+        let approved = Approvals::load()?
+            .find(&self.jar)
+            .is_some_and(|approved| approved.sha256 == self.sha256);
+        Ok(approved.then_some("approved"))
+    }
+
+    fn refusal(&self) -> String {
+        format!(
+            "{} has SHA-256 {}, which is neither in jvm.gradle_wrapper_sha256 nor approved; \
+             compare it with https://gradle.org/release-checksums/, then add it to \
+             jvm.gradle_wrapper_sha256 or run `safe-ai-util jvm approve-wrapper`",
+            self.jar.display(),
+            self.sha256
+        )
+    }
+}
+
+/// The value of `key` in a Java properties file, with its escapes removed
+fn property(properties: &str, key: &str) -> Option<String> {
+    properties.lines().find_map(|line| {
+        let (name, value) = line.trim_start().split_once(['=', ':'])?;
+        (name.trim() == key).then(|| value.trim().replace('\\', ""))
+    })
+}
+
+/// Tests counted by the JUnit XML reports under `dir` written since `started`; `None` if
+/// the run wrote none, as when Gradle found the tests up to date
+fn test_counts(dir: &Path, started: SystemTime) -> Option<TestCounts> {
+    // Report times are compared to the second, which every filesystem keeps
+    let seconds = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default()
+    };
+    let started = seconds(started);
+    let mut counts: Option<TestCounts> = None;
+    let walk = WalkDir::new(dir).into_iter().filter_entry(|entry| {
+        let name = entry.file_name().to_string_lossy();
+        !matches!(
+            name.as_ref(),
+            ".git" | ".gradle" | ".idea" | "node_modules" | "src"
+        )
+    });
+    for entry in walk.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy();
+        let in_reports = entry.path().ancestors().any(|ancestor| {
+            ancestor.file_name().is_some_and(|name| {
+                matches!(
+                    name.to_str(),
+                    Some("test-results" | "surefire-reports" | "failsafe-reports")
+                )
+            })
+        });
+        if !(in_reports && name.starts_with("TEST-") && name.ends_with(".xml")) {
+            continue;
+        }
+        let fresh = entry
+            .metadata()
+            .ok()
+            .and_then(|metadata| metadata.modified().ok())
+            .is_some_and(|modified| seconds(modified) >= started);
+        let Some(report) = fresh
+            .then(|| fs::read_to_string(entry.path()).ok())
+            .flatten()
+        else {
+            continue;
+        };
+        if let Some(found) = junit::test_counts(&report) {
+            let total = counts.get_or_insert_with(TestCounts::default);
+            total.passed += found.passed;
+            total.failed += found.failed;
+            total.ignored += found.ignored;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(tool: BuildTool, command_line: &[&str], offline: bool) -> Vec<String> {
+        let matches = build_command()
+            .try_get_matches_from(std::iter::once("jvm").chain(command_line.iter().copied()))
+            .unwrap();
+        let (subcommand, matches) = matches.subcommand().unwrap();
+        build_tool_args(tool, subcommand, matches, offline)
+    }
+
+    #[test]
+    fn test_build_tool_args() {
+        let test = [
+            "test",
+            "--tests",
+            "app.CalcTest",
+            "--tests",
+            "IoTest#reads*",
+        ];
+        assert_eq!(
+            args(BuildTool::Gradle, &test, true),
+            [
+                "--console=plain",
+                "--offline",
+                "test",
+                "--tests",
+                "app.CalcTest",
+                "--tests",
+                "IoTest#reads*"
+            ]
+        );
+        assert_eq!(
+            args(BuildTool::Maven, &test, false),
+            ["-B", "test", "-Dtest=app.CalcTest,IoTest#reads*"]
+        );
+        assert_eq!(
+            args(
+                BuildTool::Maven,
+                &["build", "--skip-tests", "--", "-pl", "core"],
+                true
+            ),
+            ["-B", "-o", "package", "-DskipTests", "-pl", "core"]
+        );
+        assert_eq!(
+            args(BuildTool::Gradle, &["spotless-apply"], false),
+            ["--console=plain", "spotlessApply"]
+        );
+    }
+
+    #[test]
+    fn test_detect_and_properties() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(BuildTool::detect(dir.path(), None).is_err());
+        fs::write(dir.path().join("pom.xml"), "<project/>").unwrap();
+        assert_eq!(
+            BuildTool::detect(dir.path(), None).unwrap(),
+            BuildTool::Maven
+        );
+        assert!(BuildTool::detect(dir.path(), Some("gradle")).is_err());
+        fs::write(dir.path().join("settings.gradle.kts"), "").unwrap();
+        assert!(BuildTool::detect(dir.path(), None).is_err());
+        assert_eq!(
+            BuildTool::detect(dir.path(), Some("gradle")).unwrap(),
+            BuildTool::Gradle
+        );
+
+        let properties = "distributionBase=GRADLE_USER_HOME\n\
+                          distributionUrl=https\\://services.gradle.org/distributions/gradle-8.10-bin.zip\n";
+        assert_eq!(
+            property(properties, "distributionUrl").unwrap(),
+            "https://services.gradle.org/distributions/gradle-8.10-bin.zip"
+        );
+        assert_eq!(property(properties, "distributionSha256Sum"), None);
+    }
+}
@@ -0,0 +1,393 @@
+// file: src/commands/kubectl.rs
+// version: 1.0.3
+// guid: 0d7e4a92-6b15-4c38-9f2e-a83c51b7d640
+
+//! `kubectl` command: inspect a cluster and apply manifests with context guards
+//!
+//! `get`, `describe`, `logs` and `rollout status` only read. `apply` first prints the context
+//! and namespace it is about to change and runs a server-side dry run, then applies with that
+//! context and namespace pinned, so switching the current context in between cannot redirect
+//! it. Contexts matching `kubectl.production_contexts` (by default any name containing `prod`)
+//! are only applied to once confirmed, interactively or with `--yes`;
+//! `safety.confirm_destructive` does not lift this.
+
+use super::export::{Effect, Remote};
+use crate::executor::Executor;
+use crate::utils;
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use serde_json::Value;
+
+/// Build the kubectl command
+pub fn build_command() -> Command {
+    Command::new("kubectl")
+        .add(Remote)
+        .about("Inspect Kubernetes resources and apply manifests")
+        .long_about(
+            "Run common kubectl workflows. apply prints the target context and namespace, runs a \
+             server-side dry run first and asks before changing a context that \
+             kubectl.production_contexts flags as production.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            target(
+                Command::new("get")
+                    .add(Effect::ReadOnly)
+                    .about("List resources"),
+            )
+            .arg(resource())
+            .arg(names())
+            .arg(
+                Arg::new("all-namespaces")
+                    .long("all-namespaces")
+                    .short('A')
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("namespace")
+                    .help("List resources of every namespace"),
+            )
+            .arg(selector())
+            .arg(
+                Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .value_name("FORMAT")
+                    .help("Output format, e.g. wide, yaml or json"),
+            ),
+        )
+        .subcommand(
+            target(
+                Command::new("describe")
+                    .add(Effect::ReadOnly)
+                    .about("Show resources in detail"),
+            )
+            .arg(resource())
+            .arg(names())
+            .arg(selector()),
+        )
+        .subcommand(
+            target(
+                Command::new("logs")
+                    .add(Effect::ReadOnly)
+                    .about("Show the output of a pod"),
+            )
+            .arg(
+                Arg::new("pod")
+                    .value_name("POD")
+                    .required(true)
+                    .help("Pod, or TYPE/NAME such as deployment/web"),
+            )
+            .arg(
+                Arg::new("container")
+                    .long("container")
+                    .short('c')
+                    .value_name("NAME")
+                    .help("Container of the pod"),
+            )
+            .arg(
+                Arg::new("tail")
+                    .long("tail")
+                    .value_name("LINES")
+                    .value_parser(clap::value_parser!(u32))
+                    .help("Only the last LINES lines"),
+            )
+            .arg(
+                Arg::new("since")
+                    .long("since")
+                    .value_name("DURATION")
+                    .help("Only lines newer than DURATION, e.g. 10m"),
+            )
+            .arg(
+                Arg::new("previous")
+                    .long("previous")
+                    .short('p')
+                    .action(ArgAction::SetTrue)
+                    .help("Output of the previous, crashed instance of the container"),
+            )
+            .arg(
+                Arg::new("follow")
+                    .long("follow")
+                    .short('f')
+                    .action(ArgAction::SetTrue)
+                    .help("Keep printing new output until interrupted"),
+            ),
+        )
+        .subcommand(
+            target(
+                Command::new("apply")
+                    .add(Effect::Destructive)
+                    .about("Apply manifests after a server-side dry run"),
+            )
+            .arg(
+                Arg::new("filename")
+                    .long("filename")
+                    .short('f')
+                    .value_name("FILE")
+                    .action(ArgAction::Append)
+                    .required(true)
+                    .help("Manifest file or directory; repeat for several"),
+            )
+            .arg(
+                Arg::new("dry-run")
+                    .long("dry-run")
+                    .action(ArgAction::SetTrue)
+                    .help("Stop after the dry run"),
+            )
+            .arg(
+                Arg::new("yes")
+                    .long("yes")
+                    .short('y')
+                    .action(ArgAction::SetTrue)
+                    .help("Apply to a production context without asking"),
+            ),
+        )
+        .subcommand(
+            Command::new("rollout")
+                .about("Follow the rollout of workloads")
+                .subcommand_required(true)
+                .subcommand(
+                    target(
+                        Command::new("status")
+                            .add(Effect::ReadOnly)
+                            .about("Wait until a rollout finishes"),
+                    )
+                    .arg(
+                        Arg::new("workload")
+                            .value_name("TYPE/NAME")
+                            .required(true)
+                            .help("Workload, e.g. deployment/web"),
+                    )
+                    .arg(
+                        Arg::new("timeout")
+                            .long("timeout")
+                            .value_name("DURATION")
+                            .help("Give up after DURATION, e.g. 5m"),
+                    ),
+                ),
+        )
+}
+
+/// Add `--context` and `--namespace`
+fn target(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("context")
+                .long("context")
+                .value_name("NAME")
+                .help("kubeconfig context to use (default: the current context)"),
+        )
+        .arg(
+            Arg::new("namespace")
+                .long("namespace")
+                .short('n')
+                .value_name("NAME")
+                .help("Namespace to use (default: the namespace of the context)"),
+        )
+}
+
+fn resource() -> Arg {
+    Arg::new("resource")
+        .value_name("TYPE")
+        .required(true)
+        .help("Resource type, e.g. pods, or TYPE/NAME")
+}
+
+fn names() -> Arg {
+    Arg::new("names")
+        .value_name("NAME")
+        .action(ArgAction::Append)
+}
+
+fn selector() -> Arg {
+    Arg::new("selector")
+        .long("selector")
+        .short('l')
+        .value_name("LABEL=VALUE")
+        .help("Only resources with matching labels")
+}
+
+/// Execute the kubectl command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("apply", sub_matches)) => apply(sub_matches, executor).await,
+        Some(("rollout", sub_matches)) => match sub_matches.subcommand() {
+            Some(("status", status_matches)) => {
+                let mut args = vec!["rollout".to_string(), "status".to_string()];
+                args.push(
+                    status_matches
+                        .get_one::<String>("workload")
+                        .unwrap()
+                        .clone(),
+                );
+                push_options(&mut args, status_matches, &["timeout"]);
+                push_target(&mut args, status_matches);
+                executor.execute_secure("kubectl", &args).await
+            }
+            _ => unreachable!("subcommand required"),
+        },
+        Some((subcommand, sub_matches)) => {
+            let mut args = vec![subcommand.to_string()];
+            for id in ["resource", "pod"] {
+                if let Some(value) = sub_matches.try_get_one::<String>(id).ok().flatten() {
+                    args.push(value.clone());
+                }
+            }
+            if let Some(names) = sub_matches.try_get_many::<String>("names").ok().flatten() {
+                args.extend(names.cloned());
+            }
+            push_options(
+                &mut args,
+                sub_matches,
+                &["selector", "output", "container", "tail", "since"],
+            );
+            for id in ["all-namespaces", "previous", "follow"] {
+                if sub_matches.try_get_one::<bool>(id).ok().flatten() == Some(&true) {
+                    args.push(format!("--{}", id));
+                }
+            }
+            push_target(&mut args, sub_matches);
+            executor.execute_secure("kubectl", &args).await
+        }
+        None => unreachable!("subcommand required"),
+    }
+}
+
+/// Print the target, dry-run the manifests, confirm production contexts, then apply
+async fn apply(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let (context, namespace) = resolve_target(matches, executor).await?;
+    let production = is_production(&context, &executor.config().kubectl.production_contexts)?;
+    println!(
+        "Context: {}{}, namespace: {}",
+        context,
+        if production { " (production)" } else { "" },
+        namespace
+    );
+
+    let mut args = vec!["apply".to_string()];
+    for file in matches.get_many::<String>("filename").unwrap_or_default() {
+        args.push("--filename".to_string());
+        args.push(file.clone());
+    }
+    args.extend([
+        "--context".to_string(),
+        context.clone(),
+        "--namespace".to_string(),
+        namespace,
+    ]);
+    let mut dry_run = args.clone();
+    dry_run.push("--dry-run=server".to_string());
+    executor
+        .execute_secure("kubectl", &dry_run)
+        .await
+        .context("Dry run failed; nothing was applied")?;
+    if matches.get_flag("dry-run") {
+        return Ok(());
+    }
+
+    if production
+        && !matches.get_flag("yes")
+        && !utils::confirm(&format!(
+            "Context {} is a production context (kubectl.production_contexts); apply?",
+            context
+        ))?
+    {
+        println!("Aborted; nothing was applied");
+        return Ok(());
+    }
+    executor.execute_secure("kubectl", &args).await
+}
+
+/// The context and namespace a command would use: the ones given, or those of the kubeconfig
+pub(crate) async fn resolve_target(matches: &ArgMatches, executor: &Executor) -> Result<(String, String)> {
+    let mut args = vec!["config", "view", "--minify", "--output", "json"];
+    if let Some(context) = matches.get_one::<String>("context") {
+        args.extend(["--context", context.as_str()]);
+    }
+    let output = executor.execute_secure_capture("kubectl", &args).await?;
+    if !output.success() {
+        bail!(
+            "Cannot tell which context kubectl would use; pass --context: {}",
+            output.stderr.trim()
+        );
+    }
+    let view: Value =
+        serde_json::from_str(&output.stdout).context("Failed to parse kubectl config view")?;
+    let (context, namespace) = context_of(&view).context(
+        "kubectl has no current context; pass --context or run kubectl config use-context",
+    )?;
+    let namespace = matches
+        .get_one::<String>("namespace")
+        .cloned()
+        .unwrap_or(namespace);
+    Ok((context, namespace))
+}
+
+/// The context a minified kubeconfig describes and its namespace, `default` when unset
+fn context_of(view: &Value) -> Option<(String, String)> {
+    let context = view["contexts"].get(0)?;
+    let name = context["name"].as_str().filter(|name| !name.is_empty())?;
+    let namespace = context["context"]["namespace"]
+        .as_str()
+        .filter(|namespace| !namespace.is_empty())
+        .unwrap_or("default");
+    Some((name.to_string(), namespace.to_string()))
+}
+
+/// Whether `context` matches one of the `kubectl.production_contexts` patterns
+fn is_production(context: &str, patterns: &[String]) -> Result<bool> {
+    for pattern in patterns {
+        let regex = Regex::new(pattern).with_context(|| {
+            format!("Invalid kubectl.production_contexts pattern '{}'", pattern)
+        })?;
+        if regex.is_match(context) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Append `--id value` for each of the options `ids` that is given
+fn push_options(args: &mut Vec<String>, matches: &ArgMatches, ids: &[&str]) {
+    for id in ids {
+        if let Some(value) = matches.try_get_raw(id).ok().flatten() {
+            for value in value {
+                args.push(format!("--{}", id));
+                args.push(value.to_string_lossy().into_owned());
+            }
+        }
+    }
+}
+
+fn push_target(args: &mut Vec<String>, matches: &ArgMatches) {
+    push_options(args, matches, &["context", "namespace"]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_target_and_production_contexts() {
+        let view = json!({
+            "current-context": "staging",
+            "contexts": [{ "name": "staging", "context": { "cluster": "eu", "namespace": "web" } }],
+        });
+        assert_eq!(
+            context_of(&view),
+            Some(("staging".to_string(), "web".to_string()))
+        );
+        let view = json!({ "contexts": [{ "name": "kind", "context": { "cluster": "kind" } }] });
+        assert_eq!(
+            context_of(&view),
+            Some(("kind".to_string(), "default".to_string()))
+        );
+        assert_eq!(context_of(&json!({ "contexts": null })), None);
+
+        let patterns = crate::config::KubectlConfig::default().production_contexts;
+        assert!(is_production("gke_acme_europe-west1_prod-eu", &patterns).unwrap());
+        assert!(!is_production("staging", &patterns).unwrap());
+        assert!(is_production("x", &["(".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,457 @@
+// file: src/commands/make.rs
+// version: 1.0.1
+// guid: 9a3f61d8-2c47-4e0b-b5d9-76e18c4a0f23
+
+//! `make` and `just` commands: list and run the targets of a Makefile or justfile
+//!
+//! `list` reads the targets from the file itself rather than asking the tool, so listing never
+//! runs a recipe. A target's description is a trailing `## comment` on its line, the
+//! self-documenting Makefile convention, or else the comment line right above it; special
+//! targets such as `.PHONY`, pattern rules and private just recipes (`_name` or
+//! `[private]`) are left out.
+//!
+//! `run` runs a target with variables given by `--env` through the executor, which refuses
+//! variables such as `LD_PRELOAD` like it does for every command. `--dry-run` prints the
+//! recipe instead of running it (`make -n`, `just --dry-run`).
+
+use super::export::Effect;
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A task runner and its conventions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Runner {
+    Make,
+    Just,
+}
+
+impl Runner {
+    fn program(self) -> &'static str {
+        match self {
+            Self::Make => "make",
+            Self::Just => "just",
+        }
+    }
+
+    /// Files the tool reads when none is named, in the order it tries them
+    fn files(self) -> &'static [&'static str] {
+        match self {
+            Self::Make => &["GNUmakefile", "makefile", "Makefile"],
+            Self::Just => &["justfile", "Justfile", ".justfile"],
+        }
+    }
+
+    fn file_flag(self) -> &'static str {
+        match self {
+            Self::Make => "--file",
+            Self::Just => "--justfile",
+        }
+    }
+
+    fn dry_run_flag(self) -> &'static str {
+        match self {
+            Self::Make => "-n",
+            Self::Just => "--dry-run",
+        }
+    }
+
+    fn parse(self, text: &str) -> Vec<Target> {
+        match self {
+            Self::Make => make_targets(text),
+            Self::Just => just_recipes(text),
+        }
+    }
+}
+
+/// A target of a Makefile or recipe of a justfile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Target {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Parameters of a just recipe
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    params: Vec<String>,
+}
+
+/// Build the make command
+pub fn build_command() -> Command {
+    runner_command(Runner::Make)
+        .about("List and run the targets of a Makefile")
+        .long_about(
+            "List the targets of a Makefile with their descriptions, and run them with variables \
+             checked by the executor. run --dry-run prints the recipes make would run (make -n).",
+        )
+}
+
+/// Build the just command
+pub fn build_just_command() -> Command {
+    runner_command(Runner::Just)
+        .about("List and run the recipes of a justfile")
+        .long_about(
+            "List the recipes of a justfile with their descriptions and parameters, and run them \
+             with variables checked by the executor. run --dry-run prints the commands just \
+             would run.",
+        )
+}
+
+fn runner_command(runner: Runner) -> Command {
+    let (target, help) = match runner {
+        Runner::Make => ("TARGET", "Targets to make, and VAR=VALUE overrides"),
+        Runner::Just => ("RECIPE", "Recipe to run, followed by its arguments"),
+    };
+    let file = Arg::new("file")
+        .long("file")
+        .short('f')
+        .value_name("FILE")
+        .help(match runner {
+            Runner::Make => "Makefile to read (default: GNUmakefile, makefile or Makefile)",
+            Runner::Just => "justfile to read (default: justfile)",
+        });
+    Command::new(runner.program())
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("List the targets and their descriptions")
+                .arg(file.clone())
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .add(Effect::Destructive)
+                .about("Run targets")
+                .arg(
+                    Arg::new("targets")
+                        .value_name(target)
+                        .required(true)
+                        .num_args(1..)
+                        .help(help),
+                )
+                .arg(file)
+                .arg(
+                    Arg::new("env")
+                        .long("env")
+                        .short('e')
+                        .value_name("KEY=VALUE")
+                        .action(ArgAction::Append)
+                        .help("Environment variable for the recipes; repeat for several"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .short('n')
+                        .action(ArgAction::SetTrue)
+                        .help("Print the recipes instead of running them"),
+                ),
+        )
+}
+
+/// Execute the make command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    run(Runner::Make, matches, executor).await
+}
+
+/// Execute the just command
+pub async fn execute_just(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    run(Runner::Just, matches, executor).await
+}
+
+async fn run(runner: Runner, matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("list", sub_matches)) => {
+            let path = task_file(runner, sub_matches, executor)?;
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let targets = runner.parse(&text);
+            if sub_matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+                let report = json!({
+                    "runner": runner.program(),
+                    "file": path,
+                    "targets": targets,
+                });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            if targets.is_empty() {
+                println!("No targets in {}", path.display());
+            }
+            let names: Vec<String> = targets
+                .iter()
+                .map(|target| {
+                    std::iter::once(target.name.as_str())
+                        .chain(target.params.iter().map(String::as_str))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect();
+            let width = names.iter().map(String::len).max().unwrap_or(0);
+            for (name, target) in names.iter().zip(&targets) {
+                match &target.description {
+                    Some(description) => println!("{:<width$}  {}", name, description),
+                    None => println!("{}", name),
+                }
+            }
+            Ok(())
+        }
+        Some(("run", sub_matches)) => {
+            let mut args = Vec::new();
+            if let Some(file) = sub_matches.get_one::<String>("file") {
+                args.push(runner.file_flag().to_string());
+                args.push(file.clone());
+            }
+            if sub_matches.get_flag("dry-run") {
+                args.push(runner.dry_run_flag().to_string());
+            }
+            args.extend(
+                sub_matches
+                    .get_many::<String>("targets")
+                    .unwrap_or_default()
+                    .cloned(),
+            );
+            let mut env = BTreeMap::new();
+            for pair in sub_matches.get_many::<String>("env").unwrap_or_default() {
+                let Some((key, value)) = pair.split_once('=') else {
+                    bail!("Invalid --env '{}'; expected KEY=VALUE", pair);
+                };
+                env.insert(key.to_string(), value.to_string());
+            }
+
+            let spec = CommandSpec {
+                command: runner.program().to_string(),
+                args,
+                cwd: None,
+                env,
+                stdin: None,
+            };
+            let output = executor.execute_spec_capture(&spec).await?;
+            print!("{}", output.stdout);
+            eprint!("{}", output.stderr);
+            if !output.success() {
+                bail!(
+                    "{} failed with {}",
+                    spec,
+                    output
+                        .exit_code
+                        .map(|code| format!("exit code {}", code))
+                        .unwrap_or_else(|| "a signal".to_string())
+                );
+            }
+            Ok(())
+        }
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+/// The file named by `--file`, or the first of the runner's default files that exists
+fn task_file(runner: Runner, matches: &ArgMatches, executor: &Executor) -> Result<PathBuf> {
+    if let Some(file) = matches.get_one::<String>("file") {
+        return Ok(executor.sandbox().check_path(Path::new(file))?);
+    }
+    let base = executor.sandbox().base();
+    match runner
+        .files()
+        .iter()
+        .map(|name| base.join(name))
+        .find(|path| path.is_file())
+    {
+        Some(path) => Ok(path),
+        None => bail!("No {} in {}", runner.files().join(" or "), base.display()),
+    }
+}
+
+/// The targets of a Makefile, in the order they are defined
+fn make_targets(text: &str) -> Vec<Target> {
+    let rule = Regex::new(r"^([^\s:=#][^:=#]*?)\s*::?(?:[^=]|$)(.*)$").unwrap();
+    let mut targets: Vec<Target> = Vec::new();
+    let mut comment: Option<String> = None;
+    let mut in_define = false;
+    for line in text.lines() {
+        if in_define {
+            in_define = !line.trim_start().starts_with("endef");
+            continue;
+        }
+        if line.starts_with("define ") || line.starts_with("define\t") {
+            in_define = true;
+            continue;
+        }
+        // Recipe lines
+        if line.starts_with('\t') {
+            continue;
+        }
+        if let Some(text) = line.strip_prefix('#') {
+            comment = Some(text.trim_start_matches('#').trim().to_string());
+            continue;
+        }
+        let Some(captures) = rule.captures(line) else {
+            comment = None;
+            continue;
+        };
+        // A trailing `## text` is the description; a plain `#` comment after the rule is not
+        let rest = &captures[2];
+        let description = rest
+            .find("##")
+            .map(|at| rest[at + 2..].trim().to_string())
+            .or_else(|| comment.take())
+            .filter(|description| !description.is_empty());
+        for name in captures[1].split_whitespace() {
+            if name.starts_with('.') || name.contains('%') || name.contains('$') {
+                continue;
+            }
+            if targets.iter().any(|target| target.name == name) {
+                continue;
+            }
+            targets.push(Target {
+                name: name.to_string(),
+                description: description.clone(),
+                params: Vec::new(),
+            });
+        }
+        comment = None;
+    }
+    targets
+}
+
+/// The public recipes of a justfile, in the order they are defined
+fn just_recipes(text: &str) -> Vec<Target> {
+    let recipe = Regex::new(r"^@?([A-Za-z_][A-Za-z0-9_-]*)((?:\s+[^:]*?)?)\s*:(?:[^=]|$)").unwrap();
+    let keyword = Regex::new(r"^(alias|export|import|mod|set)\s").unwrap();
+    let mut recipes = Vec::new();
+    let mut comment: Option<String> = None;
+    let mut private = false;
+    for line in text.lines() {
+        if line.starts_with(char::is_whitespace) || line.is_empty() {
+            if line.is_empty() {
+                comment = None;
+                private = false;
+            }
+            continue;
+        }
+        if let Some(text) = line.strip_prefix('#') {
+            // `#!` starts a shebang recipe body, never at column 0 before a recipe
+            comment = Some(text.trim().to_string());
+            continue;
+        }
+        if line.starts_with('[') {
+            private |= line.contains("private");
+            continue;
+        }
+        if keyword.is_match(line) {
+            comment = None;
+            continue;
+        }
+        let Some(captures) = recipe.captures(line) else {
+            comment = None;
+            private = false;
+            continue;
+        };
+        let name = &captures[1];
+        if !private && !name.starts_with('_') {
+            recipes.push(Target {
+                name: name.to_string(),
+                description: comment.take().filter(|comment| !comment.is_empty()),
+                params: captures[2].split_whitespace().map(String::from).collect(),
+            });
+        }
+        comment = None;
+        private = false;
+    }
+    recipes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(targets: &[Target]) -> Vec<(&str, Option<&str>)> {
+        targets
+            .iter()
+            .map(|t| (t.name.as_str(), t.description.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_make_targets() {
+        let makefile = "\
+VERSION := 1.0
+CFLAGS ?= -O2
+.PHONY: build test
+
+# Compile the binary
+build: main.o
+\tcc -o app main.o
+
+test: build ## Run the tests
+\t./app --test
+
+%.o: %.c
+\tcc -c $<
+
+clean dist:: # not a description
+\trm -rf out
+
+define HELP
+notes: not a target
+endef
+";
+        assert_eq!(
+            summary(&make_targets(makefile)),
+            [
+                ("build", Some("Compile the binary")),
+                ("test", Some("Run the tests")),
+                ("clean", None),
+                ("dist", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_just_recipes() {
+        let justfile = "\
+set shell := [\"bash\", \"-c\"]
+version := \"1.0\"
+
+# Build the project
+build:
+    cargo build
+
+# Run one test
+[no-cd]
+test name filter='': build
+    cargo test {{name}}
+
+[private]
+helper:
+    true
+
+_setup:
+    true
+
+alias b := build
+";
+        let recipes = just_recipes(justfile);
+        assert_eq!(
+            summary(&recipes),
+            [
+                ("build", Some("Build the project")),
+                ("test", Some("Run one test")),
+            ]
+        );
+        assert_eq!(recipes[1].params, ["name", "filter=''"]);
+    }
+}
@@ -1,5 +1,5 @@
 // file: src/commands/mod.rs
-// version: 2.0.0
+// version: 2.62.0
 // guid: b3c95817-32f1-4e1c-8b34-78f6e85029dc
 
 //! Command module for the Copilot Agent Utility
@@ -7,16 +7,79 @@
 //! This module provides command execution functionality for various tools and operations.
 
 pub mod awk;
+pub mod batch;
+pub mod binaries;
 pub mod buf;
+pub mod cargo;
+pub mod cmake;
+pub mod cloud;
+pub mod completions;
+pub mod config;
+pub mod db;
+pub mod deps;
+pub mod deps_graph;
+pub mod deps_upgrade;
+pub mod diagnostics;
+pub mod docker;
+pub mod doctor;
 pub mod editor;
+pub mod env;
+pub mod errors;
+pub mod export;
 pub mod file;
+pub mod file_dedupe;
+pub mod file_diff;
+pub mod file_move;
+pub mod file_refs;
+pub mod file_sync;
+pub mod file_tail;
+pub mod file_write;
+pub mod generated;
+pub mod gh;
 pub mod git;
+pub mod git_merge;
+pub mod git_stack;
+pub mod go;
+pub mod grep;
+pub mod helm;
+pub mod history;
+pub mod http;
+pub mod jobs;
+pub mod json;
+pub mod jvm;
+pub mod kubectl;
 pub mod linter;
+pub mod make;
+pub mod node;
 pub mod prettier;
+pub mod process;
+pub mod publish;
+pub mod pipe;
+pub mod pkg;
+pub mod pre_commit;
 pub mod python;
+pub mod repl;
+pub mod replay;
+pub mod run;
+pub mod scaffold;
+pub mod scripts;
 pub mod sed;
+pub mod serve;
+pub mod service;
+pub mod sessions;
+pub mod setup;
+pub mod shell;
+pub mod snapshot;
+pub mod stats;
 pub mod system;
+pub mod terraform;
+pub mod todos;
+pub mod toml;
+pub mod toolchain;
+pub mod tools;
+pub mod transaction;
 pub mod uutils;
+pub mod yaml;
 
 use crate::executor::Executor;
 use anyhow::Result;
@@ -0,0 +1,529 @@
+// file: src/commands/node.rs
+// version: 1.0.1
+// guid: 6f2b8d41-93ae-4c57-8e16-d0a47b3c9e52
+
+//! `node` command: install, ci, run and audit with the package manager the project uses
+//!
+//! The package manager is detected from the `packageManager` field of `package.json` or the
+//! lock file next to it (`pnpm-lock.yaml`, `yarn.lock`, `package-lock.json`), looking upwards
+//! from the working directory to the repository root, so a package of a workspace uses the
+//! workspace's manager. npm is used when nothing says otherwise.
+//!
+//! Installs respect the lock file: `install` without packages installs exactly what the lock
+//! file records (`npm ci`, `pnpm install --frozen-lockfile`, `yarn install --immutable`) unless
+//! `--update-lock` is given, and `ci` fails without one. Lifecycle scripts such as
+//! `postinstall`, which run code shipped by every dependency, are skipped unless
+//! `--allow-scripts` is given and `policy.allow_scripts` is set; `run` refuses to run them
+//! directly under the same condition.
+
+use super::export::{Effect, Remote};
+use crate::executor::Executor;
+use crate::security::audit;
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Scripts npm, pnpm and yarn run by themselves around installing, packing and publishing
+const LIFECYCLE_SCRIPTS: &[&str] = &[
+    "preinstall",
+    "install",
+    "postinstall",
+    "preprepare",
+    "prepare",
+    "postprepare",
+    "prepublish",
+    "prepublishOnly",
+    "prepack",
+    "postpack",
+    "publish",
+    "postpublish",
+    "dependencies",
+];
+
+/// Package managers the node command drives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Manager {
+    Npm,
+    Pnpm,
+    Yarn,
+}
+
+impl Manager {
+    /// In the order their lock files are looked for
+    const ALL: [Manager; 3] = [Self::Pnpm, Self::Yarn, Self::Npm];
+
+    pub fn program(self) -> &'static str {
+        match self {
+            Self::Npm => "npm",
+            Self::Pnpm => "pnpm",
+            Self::Yarn => "yarn",
+        }
+    }
+
+    fn lock_files(self) -> &'static [&'static str] {
+        match self {
+            Self::Npm => &["package-lock.json", "npm-shrinkwrap.json"],
+            Self::Pnpm => &["pnpm-lock.yaml"],
+            Self::Yarn => &["yarn.lock"],
+        }
+    }
+}
+
+/// The package manager a project uses, and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Detection {
+    pub manager: Manager,
+    /// Yarn 2 or later, whose flags differ from yarn 1's
+    pub berry: bool,
+    /// Lock file of the project, if it has one
+    pub lock_file: Option<PathBuf>,
+    pub reason: String,
+}
+
+/// Detect the package manager of the project `dir` belongs to
+pub fn detect(dir: &Path) -> Detection {
+    for dir in dir.ancestors() {
+        let lock_file = |manager: Manager| {
+            manager
+                .lock_files()
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.is_file())
+        };
+        let berry = |version: Option<&str>| match version {
+            Some(version) => !version.starts_with("1."),
+            None => dir.join(".yarnrc.yml").is_file(),
+        };
+
+        let manifest = dir.join("package.json");
+        let declared = read_manifest(&manifest).and_then(|manifest| {
+            let field = manifest.get("packageManager")?.as_str()?.to_string();
+            let (name, version) = match field.split_once('@') {
+                Some((name, version)) => (name.to_string(), Some(version.to_string())),
+                None => (field.clone(), None),
+            };
+            let manager = Manager::ALL
+                .into_iter()
+                .find(|manager| manager.program() == name)?;
+            Some((manager, version, field))
+        });
+        if let Some((manager, version, field)) = declared {
+            return Detection {
+                manager,
+                berry: manager == Manager::Yarn && berry(version.as_deref()),
+                lock_file: lock_file(manager),
+                reason: format!("packageManager \"{}\" in {}", field, manifest.display()),
+            };
+        }
+        let found = Manager::ALL
+            .into_iter()
+            .find_map(|manager| lock_file(manager).map(|path| (manager, path)));
+        if let Some((manager, path)) = found {
+            return Detection {
+                manager,
+                berry: manager == Manager::Yarn && berry(None),
+                reason: format!("{} found", path.display()),
+                lock_file: Some(path),
+            };
+        }
+        // A package never shares a lock file with a project outside its repository
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    Detection {
+        manager: Manager::Npm,
+        berry: false,
+        lock_file: None,
+        reason: "no packageManager field or lock file; npm is the default".to_string(),
+    }
+}
+
+fn read_manifest(path: &Path) -> Option<Value> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Build the node command
+pub fn build_command() -> Command {
+    Command::new("node")
+        .about("Install, run and audit Node.js packages with the project's package manager")
+        .long_about(
+            "Detect whether the project uses npm, pnpm or yarn and run it with lock-file \
+             respecting defaults. Lifecycle scripts of installed packages are skipped unless \
+             --allow-scripts is given and policy.allow_scripts is set.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("detect")
+                .add(Effect::ReadOnly)
+                .about("Show which package manager the project uses and why")
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("install")
+                .add(Remote)
+                .about("Install the locked dependencies, or add packages")
+                .arg(
+                    Arg::new("packages")
+                        .value_name("PACKAGE")
+                        .action(ArgAction::Append)
+                        .help("Packages to add to package.json, e.g. lodash@^4"),
+                )
+                .arg(
+                    Arg::new("dev")
+                        .long("dev")
+                        .short('D')
+                        .action(ArgAction::SetTrue)
+                        .requires("packages")
+                        .help("Add the packages as development dependencies"),
+                )
+                .arg(
+                    Arg::new("update-lock")
+                        .long("update-lock")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("packages")
+                        .help("Let the lock file change to match package.json"),
+                )
+                .arg(allow_scripts()),
+        )
+        .subcommand(
+            Command::new("ci")
+                .add(Remote)
+                .about("Install exactly what the lock file records, failing if it is out of date")
+                .arg(allow_scripts()),
+        )
+        .subcommand(
+            Command::new("run")
+                .visible_alias("run-script")
+                .about("Run a script from package.json")
+                .arg(
+                    Arg::new("script")
+                        .value_name("SCRIPT")
+                        .required(true)
+                        .help("Script to run"),
+                )
+                .arg(
+                    Arg::new("args")
+                        .value_name("ARGS")
+                        .num_args(0..)
+                        .last(true)
+                        .allow_hyphen_values(true)
+                        .help("Arguments for the script, after --"),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .add(Effect::ReadOnly)
+                .add(Remote)
+                .about("Report known vulnerabilities in the installed dependencies")
+                .arg(
+                    Arg::new("level")
+                        .long("level")
+                        .value_parser(["low", "moderate", "high", "critical"])
+                        .default_value("low")
+                        .help("Lowest severity that fails the audit"),
+                )
+                .arg(
+                    Arg::new("production")
+                        .long("production")
+                        .action(ArgAction::SetTrue)
+                        .help("Skip development dependencies"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the package manager's JSON report"),
+                ),
+        )
+}
+
+fn format_arg() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .short('f')
+        .value_parser(["text", "json"])
+        .default_value("text")
+        .help("Output format")
+}
+
+fn allow_scripts() -> Arg {
+    Arg::new("allow-scripts")
+        .long("allow-scripts")
+        .action(ArgAction::SetTrue)
+        .help("Run the lifecycle scripts of installed packages (needs policy.allow_scripts)")
+}
+
+/// Execute the node command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let dir = executor.sandbox().base().to_path_buf();
+    let detection = detect(&dir);
+    let program = detection.manager.program();
+    let args = match matches.subcommand() {
+        Some(("detect", sub_matches)) => return print_detection(&detection, sub_matches),
+        Some(("install", sub_matches)) => {
+            let packages: Vec<String> = sub_matches
+                .get_many::<String>("packages")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            let frozen = detection.lock_file.is_some() && !sub_matches.get_flag("update-lock");
+            let scripts = scripts_allowed(sub_matches, executor, program)?;
+            install_args(
+                &detection,
+                &packages,
+                sub_matches.get_flag("dev"),
+                frozen,
+                scripts,
+            )
+        }
+        Some(("ci", sub_matches)) => {
+            if detection.lock_file.is_none() {
+                bail!(
+                    "{} ci needs a lock file; create one with `safe-ai-util node install --update-lock`",
+                    program
+                );
+            }
+            let scripts = scripts_allowed(sub_matches, executor, program)?;
+            install_args(&detection, &[], false, true, scripts)
+        }
+        Some(("run", sub_matches)) => {
+            let script = sub_matches.get_one::<String>("script").unwrap();
+            check_script(&dir, script, executor, program)?;
+            let args: Vec<String> = sub_matches
+                .get_many::<String>("args")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            run_args(&detection, script, &args)
+        }
+        Some(("audit", sub_matches)) => audit_args(
+            &detection,
+            sub_matches.get_one::<String>("level").unwrap(),
+            sub_matches.get_flag("production"),
+            sub_matches.get_flag("json"),
+        ),
+        _ => unreachable!("subcommand required"),
+    };
+    info!("Running {} ({})", program, detection.reason);
+    executor.execute_secure(program, &args).await
+}
+
+fn print_detection(detection: &Detection, matches: &ArgMatches) -> Result<()> {
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(detection)?);
+        return Ok(());
+    }
+    let name = match (detection.manager, detection.berry) {
+        (Manager::Yarn, true) => "yarn (2 or later)",
+        (Manager::Yarn, false) => "yarn (1)",
+        (manager, _) => manager.program(),
+    };
+    println!("{}: {}", name, detection.reason);
+    Ok(())
+}
+
+/// Whether lifecycle scripts may run: only when asked for and allowed by the policy
+fn scripts_allowed(matches: &ArgMatches, executor: &Executor, program: &str) -> Result<bool> {
+    if !matches.get_flag("allow-scripts") {
+        info!(
+            "Skipping lifecycle scripts; packages that build native code may need --allow-scripts"
+        );
+        return Ok(false);
+    }
+    if let Err(e) = executor.policy().evaluate_scripts(program) {
+        audit::log_access_denied(program, &e.to_string());
+        return Err(e.into());
+    }
+    Ok(true)
+}
+
+/// Refuse scripts `package.json` in `dir` does not define, and lifecycle scripts the policy
+/// does not allow
+fn check_script(dir: &Path, script: &str, executor: &Executor, program: &str) -> Result<()> {
+    let path = dir.join("package.json");
+    let manifest = read_manifest(&path).ok_or_else(|| anyhow!("Cannot read {}", path.display()))?;
+    let scripts = manifest.get("scripts").and_then(Value::as_object);
+    if !scripts.is_some_and(|scripts| scripts.contains_key(script)) {
+        let mut names: Vec<&str> = scripts
+            .map(|scripts| scripts.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+        names.sort_unstable();
+        bail!(
+            "{} has no script '{}' (available: {})",
+            path.display(),
+            script,
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        );
+    }
+    if LIFECYCLE_SCRIPTS.contains(&script) {
+        if let Err(e) = executor.policy().evaluate_scripts(program) {
+            audit::log_access_denied(program, &e.to_string());
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// Arguments installing the locked dependencies, or adding `packages`
+fn install_args(
+    detection: &Detection,
+    packages: &[String],
+    dev: bool,
+    frozen: bool,
+    scripts: bool,
+) -> Vec<String> {
+    let manager = detection.manager;
+    let mut args: Vec<String> = match (manager, packages.is_empty(), frozen) {
+        (Manager::Npm, true, true) => vec!["ci".into()],
+        (Manager::Npm, false, _) => vec!["install".into()],
+        (_, false, _) => vec!["add".into()],
+        (_, true, false) => vec!["install".into()],
+        (Manager::Yarn, true, true) if detection.berry => {
+            vec!["install".into(), "--immutable".into()]
+        }
+        (_, true, true) => vec!["install".into(), "--frozen-lockfile".into()],
+    };
+    if dev {
+        args.push(
+            if manager == Manager::Yarn {
+                "--dev"
+            } else {
+                "--save-dev"
+            }
+            .into(),
+        );
+    }
+    if !scripts {
+        args.push(
+            if detection.berry {
+                "--mode=skip-build"
+            } else {
+                "--ignore-scripts"
+            }
+            .into(),
+        );
+    }
+    args.extend_from_slice(packages);
+    args
+}
+
+fn run_args(detection: &Detection, script: &str, args: &[String]) -> Vec<String> {
+    let mut full = vec!["run".to_string(), script.to_string()];
+    // npm takes options of its own before --
+    if detection.manager == Manager::Npm && !args.is_empty() {
+        full.push("--".to_string());
+    }
+    full.extend_from_slice(args);
+    full
+}
+
+fn audit_args(detection: &Detection, level: &str, production: bool, json: bool) -> Vec<String> {
+    let mut args: Vec<String> = match (detection.manager, detection.berry) {
+        (Manager::Npm, _) => vec!["audit".into(), format!("--audit-level={}", level)],
+        (Manager::Pnpm, _) => vec!["audit".into(), "--audit-level".into(), level.into()],
+        (Manager::Yarn, false) => vec!["audit".into(), "--level".into(), level.into()],
+        (Manager::Yarn, true) => {
+            vec![
+                "npm".into(),
+                "audit".into(),
+                "--severity".into(),
+                level.into(),
+            ]
+        }
+    };
+    if production {
+        args.extend(match (detection.manager, detection.berry) {
+            (Manager::Npm, _) => vec!["--omit=dev".to_string()],
+            (Manager::Pnpm, _) => vec!["--prod".to_string()],
+            (Manager::Yarn, false) => vec!["--groups".to_string(), "dependencies".to_string()],
+            (Manager::Yarn, true) => vec!["--environment".to_string(), "production".to_string()],
+        });
+    }
+    if json {
+        args.push("--json".to_string());
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("packages/web")).unwrap();
+        fs::write(root.join("packages/web/package.json"), "{}").unwrap();
+
+        let detection = detect(&root.join("packages/web"));
+        assert_eq!(
+            (detection.manager, detection.lock_file),
+            (Manager::Npm, None)
+        );
+
+        fs::write(root.join("pnpm-lock.yaml"), "").unwrap();
+        let detection = detect(&root.join("packages/web"));
+        assert_eq!(detection.manager, Manager::Pnpm);
+        assert_eq!(detection.lock_file, Some(root.join("pnpm-lock.yaml")));
+
+        fs::write(
+            root.join("packages/web/package.json"),
+            r#"{"packageManager": "yarn@4.1.0"}"#,
+        )
+        .unwrap();
+        let detection = detect(&root.join("packages/web"));
+        assert_eq!((detection.manager, detection.berry), (Manager::Yarn, true));
+        assert!(detection
+            .reason
+            .starts_with("packageManager \"yarn@4.1.0\""));
+    }
+
+    #[test]
+    fn test_install_args_respect_lock_file() {
+        let detection = |manager, berry| Detection {
+            manager,
+            berry,
+            lock_file: None,
+            reason: String::new(),
+        };
+        let npm = detection(Manager::Npm, false);
+        assert_eq!(
+            install_args(&npm, &[], false, true, false),
+            ["ci", "--ignore-scripts"]
+        );
+        assert_eq!(install_args(&npm, &[], false, false, true), ["install"]);
+        assert_eq!(
+            install_args(&npm, &["lodash".to_string()], true, true, false),
+            ["install", "--save-dev", "--ignore-scripts", "lodash"]
+        );
+        assert_eq!(
+            install_args(&detection(Manager::Pnpm, false), &[], false, true, false),
+            ["install", "--frozen-lockfile", "--ignore-scripts"]
+        );
+        assert_eq!(
+            install_args(&detection(Manager::Yarn, true), &[], false, true, false),
+            ["install", "--immutable", "--mode=skip-build"]
+        );
+        assert_eq!(
+            run_args(&npm, "test", &["--watch".to_string()]),
+            ["run", "test", "--", "--watch"]
+        );
+        assert_eq!(
+            audit_args(&detection(Manager::Yarn, false), "high", true, false),
+            ["audit", "--level", "high", "--groups", "dependencies"]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+// file: src/commands/pipe.rs
+// version: 1.1.2
+// guid: 1d6f3b8a-9e2c-4a7d-b5f0-3c8e1a4d7b92
+
+//! `pipe` command: connect commands stdout to stdin without a shell
+
+use super::export::Effect;
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgMatches, Command};
+use serde_json::json;
+
+/// Build the pipe command
+pub fn build_command() -> Command {
+    Command::new("pipe")
+        .add(Effect::Destructive)
+        .about("Run commands as a pipeline without invoking a shell")
+        .long_about(
+            "Run commands as a pipeline, each command's output feeding the next command's \
+             input, without invoking a shell. Each command is a single argument split into \
+             words like a shell would; '|', redirections and substitutions have no special \
+             meaning. The pipeline fails if any command fails.",
+        )
+        .arg(
+            Arg::new("commands")
+                .value_name("COMMAND")
+                .num_args(1..)
+                .required(true)
+                .help("Command lines in pipeline order, e.g. 'git diff' 'grep TODO' 'wc -l'"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+}
+
+/// Execute the pipe command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let mut stages = matches
+        .get_many::<String>("commands")
+        .unwrap_or_default()
+        .map(|line| CommandSpec::parse(line).map_err(|e| anyhow!(e)))
+        .collect::<Result<Vec<_>>>()?;
+    // The first command reads the file given with --stdin-file
+    stages[0].stdin = executor.stdin().cloned();
+
+    let output = executor.execute_pipeline(&stages).await?;
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let stage_results: Vec<_> = stages
+            .iter()
+            .zip(&output.exit_codes)
+            .map(|(spec, code)| json!({"command": spec.to_string(), "exit_code": code}))
+            .collect();
+        let mut report = json!({
+            "stages": stage_results,
+            "exit_code": output.exit_code(),
+            "stdout": output.stdout,
+            "stderr": output.stderr,
+        });
+        if !output.truncated.is_empty() {
+            report["truncated"] = json!(output.truncated);
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+    }
+
+    if let Some(stage) = output.failed_stage() {
+        bail!(
+            "Pipeline failed: '{}' exited with {}",
+            stages[stage],
+            output.exit_codes[stage]
+                .map(|code| format!("code {}", code))
+                .unwrap_or_else(|| "a signal".to_string())
+        );
+    }
+    Ok(())
+}
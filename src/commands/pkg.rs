@@ -0,0 +1,472 @@
+// file: src/commands/pkg.rs
+// version: 1.0.2
+// guid: 4e9b2d7a-6c13-4f58-a0e2-b71c5d8f3a96
+
+//! `pkg` command: query and install system packages with brew, apt-get or dnf
+//!
+//! `pkg list` shows what is installed: brew's formulae and casks, the dpkg status database
+//! behind apt-get, or the rpm database behind dnf. Given names, it reports each of them and
+//! fails when any is missing, so a bootstrap script can tell what is left to install.
+//!
+//! `pkg install` sets up developer tools on a workstation. It runs only when
+//! `policy.allow_packages` is set and the install is confirmed at the terminal, or with
+//! `--yes` when nobody is there to ask. Packages already installed are skipped. Every request
+//! is written to the audit log with its outcome, whether it was refused, declined, failed or
+//! done. apt-get and dnf need root and are not run through sudo.
+
+use super::export::{Effect, Remote};
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use crate::security::audit::{self, ExecutionResult};
+use crate::utils;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Database of the packages dpkg knows about
+const DPKG_STATUS: &str = "/var/lib/dpkg/status";
+
+/// Architectures rpm appends to package names
+const RPM_ARCHES: &[&str] = &[
+    "x86_64", "aarch64", "noarch", "i686", "armv7hl", "ppc64le", "s390x",
+];
+
+/// A system package manager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Manager {
+    Brew,
+    Apt,
+    Dnf,
+}
+
+impl Manager {
+    const ALL: [Manager; 3] = [Self::Brew, Self::Apt, Self::Dnf];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Brew => "brew",
+            Self::Apt => "apt",
+            Self::Dnf => "dnf",
+        }
+    }
+
+    /// Program that installs packages
+    fn program(self) -> &'static str {
+        match self {
+            Self::Brew => "brew",
+            Self::Apt => "apt-get",
+            Self::Dnf => "dnf",
+        }
+    }
+
+    /// The manager of this system: brew on macOS, otherwise the first of apt-get, dnf and
+    /// brew on PATH
+    fn detect() -> Option<Self> {
+        let order = if cfg!(target_os = "macos") {
+            [Self::Brew, Self::Apt, Self::Dnf]
+        } else {
+            [Self::Apt, Self::Dnf, Self::Brew]
+        };
+        order
+            .into_iter()
+            .find(|manager| which::which(manager.program()).is_ok())
+    }
+
+    fn install_args(self, packages: &[String]) -> Vec<String> {
+        let mut args = vec!["install".to_string()];
+        if self != Self::Brew {
+            args.push("-y".to_string());
+        }
+        args.extend_from_slice(packages);
+        args
+    }
+}
+
+/// Build the pkg command
+pub fn build_command() -> Command {
+    Command::new("pkg")
+        .about("Query installed system packages and install developer tools with brew, apt or dnf")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("List installed packages, or report whether the given ones are installed")
+                .arg(
+                    Arg::new("packages")
+                        .value_name("PACKAGE")
+                        .num_args(0..)
+                        .help("Packages to look for; fails if any is missing"),
+                )
+                .arg(manager_arg())
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("install")
+                .add(Effect::Destructive)
+                .add(Remote)
+                .about("Install packages after confirmation; needs policy.allow_packages")
+                .arg(
+                    Arg::new("packages")
+                        .value_name("PACKAGE")
+                        .num_args(1..)
+                        .required(true)
+                        .help("Packages to install, e.g. jq or python@3.12"),
+                )
+                .arg(manager_arg())
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .help("Confirm the install without asking, for scripts"),
+                ),
+        )
+}
+
+fn manager_arg() -> Arg {
+    Arg::new("manager")
+        .long("manager")
+        .short('m')
+        .value_parser(Manager::ALL.map(Manager::name))
+        .help("Package manager (default: brew on macOS, else apt, dnf or brew, whichever is installed)")
+}
+
+/// Execute the pkg command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("list", sub)) => list(sub, executor).await,
+        Some(("install", sub)) => install(sub, executor).await,
+        _ => Err(anyhow!("Unknown pkg subcommand")),
+    }
+}
+
+async fn list(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let manager = manager(matches)?;
+    let installed = installed(executor, manager).await?;
+    let requested = packages(matches)?;
+    let json = matches.get_one::<String>("format").map(String::as_str) == Some("json");
+
+    if requested.is_empty() {
+        if json {
+            let packages: Vec<_> = installed
+                .iter()
+                .map(|(name, version)| json!({"name": name, "version": version}))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&packages)?);
+        } else {
+            for (name, version) in &installed {
+                println!("{:<32} {}", name, version);
+            }
+        }
+        return Ok(());
+    }
+
+    let found: Vec<(&String, Option<&String>)> = requested
+        .iter()
+        .map(|name| (name, lookup(&installed, name)))
+        .collect();
+    if json {
+        let packages: Vec<_> = found
+            .iter()
+            .map(|(name, version)| {
+                json!({"name": name, "installed": version.is_some(), "version": version})
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&packages)?);
+    } else {
+        for (name, version) in &found {
+            match version {
+                Some(version) => println!("{:<32} {}", name, version),
+                None => println!("{:<32} not installed", name),
+            }
+        }
+    }
+    let missing = found
+        .iter()
+        .filter(|(_, version)| version.is_none())
+        .count();
+    if missing > 0 {
+        bail!(
+            "{} of {} package(s) are not installed",
+            missing,
+            found.len()
+        );
+    }
+    Ok(())
+}
+
+async fn install(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let manager = manager(matches)?;
+    let program = manager.program();
+    let requested = packages(matches)?;
+    if let Err(e) = executor.policy().evaluate_packages(program) {
+        audit::log_package_install(
+            program,
+            &requested,
+            ExecutionResult::Blocked,
+            vec![e.to_string()],
+        );
+        return Err(e.into());
+    }
+
+    let installed = installed(executor, manager).await?;
+    let mut missing = Vec::new();
+    let mut notes = Vec::new();
+    for name in requested {
+        match lookup(&installed, &name) {
+            Some(version) => {
+                println!("{} {} is already installed", name, version);
+                notes.push(format!("{} {} already installed", name, version));
+            }
+            None => missing.push(name),
+        }
+    }
+    if missing.is_empty() {
+        println!("Nothing to install");
+        return Ok(());
+    }
+
+    let dry_run = executor.config().safety.dry_run;
+    #[cfg(unix)]
+    if manager != Manager::Brew && !dry_run && unsafe { libc::geteuid() } != 0 {
+        bail!(
+            "{} installs packages as root; run this command as root to install {}",
+            program,
+            missing.join(" ")
+        );
+    }
+    let confirmed_by = if dry_run {
+        "dry run"
+    } else if matches.get_flag("yes") {
+        "--yes"
+    } else {
+        let prompt = format!("Install {} with {}?", missing.join(", "), program);
+        match utils::confirm(&prompt) {
+            Ok(true) => {}
+            Ok(false) => {
+                notes.push("declined at the terminal".to_string());
+                audit::log_package_install(program, &missing, ExecutionResult::Blocked, notes);
+                println!("Aborted; nothing was installed");
+                return Ok(());
+            }
+            Err(e) => {
+                notes.push(e.to_string());
+                audit::log_package_install(program, &missing, ExecutionResult::Blocked, notes);
+                return Err(e.into());
+            }
+        }
+        "the terminal"
+    };
+    notes.push(format!("confirmed by {}", confirmed_by));
+
+    let mut env = BTreeMap::new();
+    if manager == Manager::Apt {
+        env.insert("DEBIAN_FRONTEND".to_string(), "noninteractive".to_string());
+    }
+    let spec = CommandSpec {
+        command: program.to_string(),
+        args: manager.install_args(&missing),
+        cwd: None,
+        env,
+        stdin: None,
+    };
+    let output = match executor.execute_spec_capture(&spec).await {
+        Ok(output) => output,
+        Err(e) => {
+            audit::log_package_install(
+                program,
+                &missing,
+                ExecutionResult::Error(e.to_string()),
+                notes,
+            );
+            return Err(e);
+        }
+    };
+    print!("{}", output.stdout);
+    eprint!("{}", output.stderr);
+    if !output.success() {
+        let error = format!(
+            "{} failed with {}",
+            spec,
+            output
+                .exit_code
+                .map(|code| format!("exit code {}", code))
+                .unwrap_or_else(|| "a signal".to_string())
+        );
+        audit::log_package_install(
+            program,
+            &missing,
+            ExecutionResult::Error(error.clone()),
+            notes,
+        );
+        bail!(error);
+    }
+    audit::log_package_install(program, &missing, ExecutionResult::Allowed, notes);
+    println!("Installed {}", missing.join(", "));
+    Ok(())
+}
+
+fn manager(matches: &ArgMatches) -> Result<Manager> {
+    match matches.get_one::<String>("manager") {
+        Some(name) => Ok(Manager::ALL
+            .into_iter()
+            .find(|manager| manager.name() == name)
+            .unwrap()),
+        None => Manager::detect().ok_or_else(|| {
+            anyhow!("No package manager found; install brew, apt-get or dnf, or pass --manager")
+        }),
+    }
+}
+
+/// Package names from the command line, refusing anything that could be read as an option
+fn packages(matches: &ArgMatches) -> Result<Vec<String>> {
+    let packages: Vec<String> = matches
+        .get_many::<String>("packages")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    if let Some(invalid) = packages.iter().find(|name| {
+        !name.starts_with(|c: char| c.is_ascii_alphanumeric())
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.+@/".contains(c))
+    }) {
+        bail!("Invalid package name '{}'", invalid);
+    }
+    Ok(packages)
+}
+
+/// Version of `name` if it is installed; brew formulae from taps (`owner/tap/name`) are
+/// listed by their last component
+fn lookup<'a>(installed: &'a BTreeMap<String, String>, name: &str) -> Option<&'a String> {
+    installed
+        .get(name)
+        .or_else(|| installed.get(name.rsplit('/').next()?))
+}
+
+/// Installed packages and their versions
+async fn installed(executor: &Executor, manager: Manager) -> Result<BTreeMap<String, String>> {
+    match manager {
+        Manager::Apt => {
+            let status = fs::read_to_string(DPKG_STATUS)
+                .with_context(|| format!("Cannot read {}", DPKG_STATUS))?;
+            Ok(parse_dpkg_status(&status))
+        }
+        Manager::Brew | Manager::Dnf => {
+            let (program, args): (&str, &[&str]) = match manager {
+                Manager::Brew => ("brew", &["list", "--versions"]),
+                _ => ("rpm", &["-qa"]),
+            };
+            let output = executor.execute_secure_capture(program, args).await?;
+            if !output.success() {
+                bail!(
+                    "{} {} failed: {}",
+                    program,
+                    args.join(" "),
+                    output.stderr.trim()
+                );
+            }
+            Ok(match manager {
+                Manager::Brew => parse_brew_list(&output.stdout),
+                _ => parse_rpm_list(&output.stdout),
+            })
+        }
+    }
+}
+
+/// `brew list --versions`: a name and its installed versions per line; the last is current
+fn parse_brew_list(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let name = words.next()?;
+            Some((
+                name.to_string(),
+                words.last().unwrap_or_default().to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Stanzas of the dpkg status database whose status is `installed`
+fn parse_dpkg_status(status: &str) -> BTreeMap<String, String> {
+    status
+        .split("\n\n")
+        .filter_map(|stanza| {
+            let field = |name: &str| {
+                stanza
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                    .map(str::trim)
+            };
+            if !field("Status")?.ends_with(" installed") {
+                return None;
+            }
+            Some((field("Package")?.to_string(), field("Version")?.to_string()))
+        })
+        .collect()
+}
+
+/// `rpm -qa`: `name-version-release.arch` per line
+fn parse_rpm_list(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let line = match line.rsplit_once('.') {
+                Some((rest, arch)) if RPM_ARCHES.contains(&arch) => rest,
+                _ => line,
+            };
+            let (rest, release) = line.rsplit_once('-')?;
+            let (name, version) = rest.rsplit_once('-')?;
+            Some((name.to_string(), format!("{}-{}", version, release)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_installed_packages() {
+        let brew =
+            parse_brew_list("git 2.44.0\npython@3.12 3.12.2 3.12.3\nvisual-studio-code 1.88.0\n");
+        assert_eq!(brew["python@3.12"], "3.12.3");
+        assert_eq!(brew.len(), 3);
+
+        let dpkg = parse_dpkg_status(
+            "Package: jq\nStatus: install ok installed\nPriority: optional\nVersion: 1.7.1-3\n\n\
+             Package: vim\nStatus: deinstall ok config-files\nVersion: 2:9.1.0016-1\n\n\
+             Package: libc6\nStatus: install ok installed\nMulti-Arch: same\nVersion: 2.39-0ubuntu8\nDescription: GNU C Library\n libc6 contains the standard libraries\n",
+        );
+        assert_eq!(dpkg.keys().collect::<Vec<_>>(), ["jq", "libc6"]);
+        assert_eq!(dpkg["libc6"], "2.39-0ubuntu8");
+
+        let rpm = parse_rpm_list("bash-5.2.26-3.fc40.x86_64\ngpg-pubkey-a15b79cc-63d04c2c\npython3-pip-23.3.2-1.fc40.noarch\n");
+        assert_eq!(rpm["bash"], "5.2.26-3.fc40");
+        assert_eq!(rpm["python3-pip"], "23.3.2-1.fc40");
+        assert_eq!(rpm["gpg-pubkey"], "a15b79cc-63d04c2c");
+
+        let installed = BTreeMap::from([("wget".to_string(), "1.24.5".to_string())]);
+        assert_eq!(
+            lookup(&installed, "homebrew/core/wget").map(String::as_str),
+            Some("1.24.5")
+        );
+        assert_eq!(lookup(&installed, "curl"), None);
+        assert_eq!(
+            Manager::Apt.install_args(&["jq".to_string()]),
+            ["install", "-y", "jq"]
+        );
+    }
+}
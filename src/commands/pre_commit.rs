@@ -0,0 +1,927 @@
+// file: src/commands/pre_commit.rs
+// version: 1.1.1
+// guid: 7c5a1e3d-8b4f-4d2a-9e6b-1f0c3d8a5e27
+
+//! `pre-commit` adapter: run the hooks of `.pre-commit-config.yaml` through the Executor
+//!
+//! The pre-commit tool runs hooks with its own runner, outside the sandbox, allowlist and
+//! timeouts. This adapter reads the same configuration and runs each hook's entry as a
+//! validated command on the files it selects (`files`, `exclude`, `types`), so a hook whose
+//! tool is not allowed fails with an error instead of running.
+//!
+//! Hooks of `repo: local` are defined in the configuration itself. Hooks of remote
+//! repositories are looked up in the clones pre-commit keeps in its cache
+//! (`$PRE_COMMIT_HOME`, or `~/.cache/pre-commit`), so `pre-commit install-hooks` must have run
+//! once; their tools are taken from `PATH` rather than pre-commit's isolated environments.
+//! The `fail` and `pygrep` languages are evaluated natively.
+//!
+//! Like pre-commit, a hook fails when its command exits non-zero or modifies files.
+//!
+//! `pre-commit install` writes a git hook that runs the hooks this way at every commit.
+//! `pre-commit autoupdate` moves the `rev` of each remote repository to its latest release tag,
+//! found with `git ls-remote`, and edits the configuration in place so its comments are kept.
+
+use super::export::{Effect, Remote};
+use super::file_write;
+use super::json::Segment;
+use super::setup::HOOK_MARKER;
+use super::yaml;
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{debug, info};
+
+/// Configuration file read by default
+pub const CONFIG_FILE: &str = ".pre-commit-config.yaml";
+
+/// Most filenames passed to one invocation of a hook
+const FILES_PER_RUN: usize = 200;
+
+/// `.pre-commit-config.yaml`
+#[derive(Debug, Clone, Deserialize)]
+struct PreCommitConfig {
+    repos: Vec<Repo>,
+    #[serde(default)]
+    files: Option<String>,
+    #[serde(default)]
+    exclude: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Repo {
+    repo: String,
+    #[serde(default)]
+    hooks: Vec<Hook>,
+}
+
+/// A hook as configured or as defined by its repository's `.pre-commit-hooks.yaml`; unset
+/// fields fall back to the definition and then to pre-commit's defaults
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Hook {
+    id: String,
+    name: Option<String>,
+    entry: Option<String>,
+    language: Option<String>,
+    files: Option<String>,
+    exclude: Option<String>,
+    types: Option<Vec<String>>,
+    types_or: Option<Vec<String>>,
+    exclude_types: Option<Vec<String>>,
+    args: Option<Vec<String>>,
+    pass_filenames: Option<bool>,
+    always_run: Option<bool>,
+    stages: Option<Vec<String>>,
+}
+
+impl Hook {
+    /// Configured values over the repository's definition
+    fn merged_with(self, definition: Hook) -> Hook {
+        Hook {
+            id: self.id,
+            name: self.name.or(definition.name),
+            entry: self.entry.or(definition.entry),
+            language: self.language.or(definition.language),
+            files: self.files.or(definition.files),
+            exclude: self.exclude.or(definition.exclude),
+            types: self.types.or(definition.types),
+            types_or: self.types_or.or(definition.types_or),
+            exclude_types: self.exclude_types.or(definition.exclude_types),
+            args: self.args.or(definition.args),
+            pass_filenames: self.pass_filenames.or(definition.pass_filenames),
+            always_run: self.always_run.or(definition.always_run),
+            stages: self.stages.or(definition.stages),
+        }
+    }
+}
+
+/// Outcome of one hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStatus {
+    Passed,
+    /// Exited non-zero or modified files
+    Failed,
+    /// No files to check
+    Skipped,
+    /// Could not be run: unknown definition, refused command, unsupported settings
+    Error,
+}
+
+impl HookStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Result of one hook
+#[derive(Debug, Clone, Serialize)]
+pub struct HookResult {
+    pub id: String,
+    pub name: String,
+    pub status: HookStatus,
+    /// Files the hook ran on
+    pub files: usize,
+    /// Files the hook changed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modified: Vec<PathBuf>,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    pub output: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HookResult {
+    fn new(hook: &Hook, status: HookStatus) -> Self {
+        Self {
+            id: hook.id.clone(),
+            name: hook.name.clone().unwrap_or_else(|| hook.id.clone()),
+            status,
+            files: 0,
+            modified: Vec::new(),
+            exit_code: None,
+            duration_ms: 0,
+            output: String::new(),
+            error: None,
+        }
+    }
+
+    fn error(hook: &Hook, error: impl ToString) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            ..Self::new(hook, HookStatus::Error)
+        }
+    }
+}
+
+/// Build the pre-commit command
+pub fn build_command() -> Command {
+    Command::new("pre-commit")
+        .about("Run pre-commit hooks through the safe executor")
+        .subcommand(
+            Command::new("run")
+                .add(Effect::Destructive)
+                .about("Run the hooks of .pre-commit-config.yaml on staged or given files")
+                .arg(
+                    Arg::new("hooks")
+                        .value_name("HOOK")
+                        .num_args(0..)
+                        .help("Hook ids to run (default: all)"),
+                )
+                .arg(
+                    Arg::new("all-files")
+                        .long("all-files")
+                        .short('a')
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["files", "from-ref"])
+                        .help("Run on every tracked file instead of the staged files"),
+                )
+                .arg(
+                    Arg::new("files")
+                        .long("files")
+                        .value_name("FILE")
+                        .num_args(1..)
+                        .conflicts_with("from-ref")
+                        .help("Run on these files instead of the staged files"),
+                )
+                .arg(
+                    Arg::new("from-ref")
+                        .long("from-ref")
+                        .value_name("REF")
+                        .help("Run on the files changed since REF, e.g. origin/main"),
+                )
+                .arg(
+                    Arg::new("to-ref")
+                        .long("to-ref")
+                        .value_name("REF")
+                        .default_value("HEAD")
+                        .requires("from-ref")
+                        .help("End of the range --from-ref starts"),
+                )
+                .arg(
+                    Arg::new("config")
+                        .long("config")
+                        .short('c')
+                        .value_name("FILE")
+                        .default_value(CONFIG_FILE)
+                        .help("pre-commit configuration file"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("install")
+                .add(Effect::Destructive)
+                .about("Install a git pre-commit hook that runs the hooks through the executor")
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Replace a hook written by pre-commit or by hand"),
+                ),
+        )
+        .subcommand(
+            Command::new("autoupdate")
+                .add(Effect::Destructive)
+                .add(Remote)
+                .about("Move the rev of each remote repository to its latest release tag")
+                .arg(
+                    Arg::new("repo")
+                        .long("repo")
+                        .value_name("URL")
+                        .action(ArgAction::Append)
+                        .help("Only update this repository (repeatable)"),
+                )
+                .arg(
+                    Arg::new("config")
+                        .long("config")
+                        .short('c')
+                        .value_name("FILE")
+                        .default_value(CONFIG_FILE)
+                        .help("pre-commit configuration file"),
+                )
+                .arg(
+                    Arg::new("diff")
+                        .long("diff")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the changes to the configuration"),
+                ),
+        )
+}
+
+/// The git hook `pre-commit install` and `setup` write
+pub(crate) fn git_hook(exe: &Path) -> String {
+    format!(
+        "#!/bin/sh\n{}: run the hooks of {}\nexec '{}' pre-commit run\n",
+        HOOK_MARKER,
+        CONFIG_FILE,
+        exe.display()
+    )
+}
+
+/// Execute the pre-commit command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("run", sub_matches)) => execute_run(sub_matches, executor).await,
+        Some(("install", sub_matches)) => execute_install(sub_matches, executor).await,
+        Some(("autoupdate", sub_matches)) => execute_autoupdate(sub_matches, executor).await,
+        _ => {
+            println!("No pre-commit subcommand specified. Use 'pre-commit --help' for usage information.");
+            Ok(())
+        }
+    }
+}
+
+async fn execute_run(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let config_path = Path::new(matches.get_one::<String>("config").unwrap());
+    executor.check_path(config_path)?;
+    let config: PreCommitConfig = serde_yaml::from_str(
+        &fs::read_to_string(config_path)
+            .with_context(|| format!("Cannot read {}", config_path.display()))?,
+    )
+    .with_context(|| format!("Invalid {}", config_path.display()))?;
+
+    let files: Vec<PathBuf> = if let Some(files) = matches.get_many::<String>("files") {
+        files.map(PathBuf::from).collect()
+    } else {
+        let args: Vec<String> = if matches.get_flag("all-files") {
+            vec!["ls-files".to_string()]
+        } else if let Some(from) = matches.get_one::<String>("from-ref") {
+            let to = matches.get_one::<String>("to-ref").unwrap();
+            if let Some(invalid) = [from, to].into_iter().find(|r| r.starts_with('-')) {
+                bail!("Invalid git reference '{}'", invalid);
+            }
+            ["diff", "--name-only", "--diff-filter=ACMR"]
+                .into_iter()
+                .map(String::from)
+                .chain([format!("{}...{}", from, to)])
+                .collect()
+        } else {
+            ["diff", "--cached", "--name-only", "--diff-filter=ACMR"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        };
+        let output = executor.execute_secure_capture("git", &args).await?;
+        if !output.success() {
+            bail!("git {} failed: {}", args.join(" "), output.stderr.trim());
+        }
+        output
+            .stdout
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    };
+    let files = filter_files(&files, config.files.as_deref(), config.exclude.as_deref())?;
+    info!("Running pre-commit hooks on {} files", files.len());
+
+    let selected: Vec<String> = matches
+        .get_many::<String>("hooks")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let cache = pre_commit_cache();
+    let mut results = Vec::new();
+    for repo in &config.repos {
+        for hook in &repo.hooks {
+            if !selected.is_empty() && !selected.contains(&hook.id) {
+                continue;
+            }
+            let hook = match resolve_hook(repo, hook, cache.as_deref()) {
+                Ok(hook) => hook,
+                Err(e) => {
+                    results.push(HookResult::error(hook, e));
+                    continue;
+                }
+            };
+            if hook
+                .stages
+                .as_ref()
+                .is_some_and(|s| !s.iter().any(|s| s == "pre-commit" || s == "commit"))
+            {
+                debug!("Hook {} does not run at the pre-commit stage", hook.id);
+                continue;
+            }
+            let result = run_hook(executor, &hook, &files)
+                .await
+                .unwrap_or_else(|e| HookResult::error(&hook, e));
+            results.push(result);
+        }
+    }
+    if let Some(unknown) = selected
+        .iter()
+        .find(|id| !results.iter().any(|r| &r.id == *id))
+    {
+        bail!("No hook '{}' in {}", unknown, config_path.display());
+    }
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let report = json!({
+            "hooks": results,
+            "summary": {
+                "total": results.len(),
+                "passed": count(HookStatus::Passed),
+                "failed": count(HookStatus::Failed),
+                "skipped": count(HookStatus::Skipped),
+                "error": count(HookStatus::Error),
+            },
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for result in &results {
+            println!(
+                "{:.<60}{}",
+                format!("{} ", result.name),
+                result.status.as_str()
+            );
+            if !result.modified.is_empty() {
+                println!("    files modified by this hook:");
+                for path in &result.modified {
+                    println!("      {}", path.display());
+                }
+            }
+            if let Some(error) = &result.error {
+                println!("    {}", error);
+            }
+            if result.status == HookStatus::Failed && !result.output.is_empty() {
+                print!("{}", textwrap_output(&result.output));
+            }
+        }
+        println!(
+            "{} passed, {} failed, {} skipped, {} could not run",
+            count(HookStatus::Passed),
+            count(HookStatus::Failed),
+            count(HookStatus::Skipped),
+            count(HookStatus::Error)
+        );
+    }
+
+    let failed = results
+        .iter()
+        .filter(|r| matches!(r.status, HookStatus::Failed | HookStatus::Error))
+        .count();
+    if failed > 0 {
+        bail!("{} of {} hooks did not pass", failed, results.len());
+    }
+    Ok(())
+}
+
+async fn execute_install(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    if !Path::new(CONFIG_FILE).exists() {
+        bail!("No {} to run from the hook", CONFIG_FILE);
+    }
+    if executor.config().safety.dry_run {
+        println!("DRY RUN: Would install a git pre-commit hook running 'pre-commit run'");
+        return Ok(());
+    }
+    let output = executor
+        .execute_secure_capture("git", &["rev-parse", "--git-path", "hooks"])
+        .await?;
+    if !output.success() {
+        bail!("Not in a git repository: {}", output.stderr.trim());
+    }
+    let dir = PathBuf::from(output.stdout.trim());
+    let path = dir.join("pre-commit");
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if !existing.contains(HOOK_MARKER) && !matches.get_flag("force") {
+            bail!(
+                "{} already exists and was not written by safe-ai-util; use --force to replace it",
+                path.display()
+            );
+        }
+    }
+
+    fs::create_dir_all(&dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+    file_write::replace_file(&path, git_hook(&std::env::current_exe()?).as_bytes())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    println!("Installed {}", path.display());
+    Ok(())
+}
+
+async fn execute_autoupdate(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let config_path = Path::new(matches.get_one::<String>("config").unwrap());
+    executor.check_path(config_path)?;
+    let mut text = fs::read_to_string(config_path)
+        .with_context(|| format!("Cannot read {}", config_path.display()))?;
+    let document: Value = serde_yaml::from_str(&text)
+        .with_context(|| format!("Invalid {}", config_path.display()))?;
+    let selected: Vec<&String> = matches
+        .get_many::<String>("repo")
+        .unwrap_or_default()
+        .collect();
+    let repos: Vec<(usize, &str, &str)> = document["repos"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(index, repo)| Some((index, repo["repo"].as_str()?, repo["rev"].as_str()?)))
+        .filter(|(_, url, _)| selected.is_empty() || selected.iter().any(|s| s == url))
+        .collect();
+    if let Some(unknown) = selected
+        .iter()
+        .find(|url| !repos.iter().any(|(_, repo, _)| *repo == url.as_str()))
+    {
+        bail!("No repository {} in {}", unknown, config_path.display());
+    }
+    if executor.config().safety.dry_run {
+        println!(
+            "DRY RUN: Would look up the latest release of {} repositories",
+            repos.len()
+        );
+        return Ok(());
+    }
+
+    let mut updated = 0;
+    let mut failed = 0;
+    for (index, url, rev) in repos {
+        if url.starts_with('-') {
+            println!("[{}] invalid repository url", url);
+            failed += 1;
+            continue;
+        }
+        let output = executor
+            .execute_secure_capture("git", &["ls-remote", "--tags", "--refs", url])
+            .await?;
+        if !output.success() {
+            println!("[{}] cannot list tags: {}", url, output.stderr.trim());
+            failed += 1;
+            continue;
+        }
+        let Some(latest) = latest_release(&output.stdout) else {
+            println!("[{}] has no release tags", url);
+            continue;
+        };
+        let newer = match (release_version(rev), release_version(&latest)) {
+            (Some(current), Some(latest)) => latest > current,
+            _ => rev != latest,
+        };
+        if !newer {
+            println!("[{}] already up to date!", url);
+            continue;
+        }
+        let path = [
+            Segment::Key("repos".to_string()),
+            Segment::Index(index as i64),
+            Segment::Key("rev".to_string()),
+        ];
+        text =
+            yaml::set_in_place(&text, &path, &Value::String(latest.clone()))?.ok_or_else(|| {
+                anyhow!(
+                    "Cannot update the rev of {} in {} without reformatting it",
+                    url,
+                    config_path.display()
+                )
+            })?;
+        println!("[{}] updating {} -> {}", url, rev, latest);
+        updated += 1;
+    }
+
+    if updated > 0 {
+        file_write::update(
+            executor,
+            config_path,
+            text.as_bytes(),
+            matches.get_flag("diff"),
+        )?;
+    }
+    if failed > 0 {
+        bail!("Could not update {} repositories", failed);
+    }
+    Ok(())
+}
+
+/// The highest release tag in `git ls-remote --tags --refs` output
+fn latest_release(ls_remote: &str) -> Option<String> {
+    ls_remote
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1)?.strip_prefix("refs/tags/"))
+        .filter_map(|tag| Some((release_version(tag)?, tag)))
+        .max()
+        .map(|(_, tag)| tag.to_string())
+}
+
+/// `v1.2.3` and `24.4.2` as numbers; pre-releases and other tags are `None`
+fn release_version(tag: &str) -> Option<Vec<u64>> {
+    tag.strip_prefix('v')
+        .unwrap_or(tag)
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Indent hook output under its status line
+fn textwrap_output(output: &str) -> String {
+    output
+        .lines()
+        .map(|line| format!("    {}\n", line))
+        .collect()
+}
+
+/// Complete a configured hook with its repository's definition
+fn resolve_hook(repo: &Repo, hook: &Hook, cache: Option<&Path>) -> Result<Hook> {
+    match repo.repo.as_str() {
+        "local" => Ok(hook.clone()),
+        "meta" => bail!("meta hooks are not supported"),
+        url => {
+            let cache = cache.ok_or_else(|| {
+                anyhow!("pre-commit cache not found; run 'pre-commit install-hooks' once")
+            })?;
+            let definition = find_definition(cache, url, &hook.id)?
+                .ok_or_else(|| anyhow!("hook {} of {} is not in the pre-commit cache; run 'pre-commit install-hooks' once", hook.id, url))?;
+            Ok(hook.clone().merged_with(definition))
+        }
+    }
+}
+
+/// pre-commit's cache directory, if it exists
+fn pre_commit_cache() -> Option<PathBuf> {
+    let dir = std::env::var_os("PRE_COMMIT_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("XDG_CACHE_HOME").map(|d| PathBuf::from(d).join("pre-commit")))
+        .or_else(|| dirs::home_dir().map(|h| h.join(".cache").join("pre-commit")))?;
+    dir.is_dir().then_some(dir)
+}
+
+/// Definition of `id` in a cached clone of `url`
+fn find_definition(cache: &Path, url: &str, id: &str) -> Result<Option<Hook>> {
+    for entry in fs::read_dir(cache)?.flatten() {
+        let clone = entry.path();
+        let remote = fs::read_to_string(clone.join(".git").join("config")).unwrap_or_default();
+        if !remote.lines().any(|l| l.trim() == format!("url = {}", url)) {
+            continue;
+        }
+        let Ok(hooks) = fs::read_to_string(clone.join(".pre-commit-hooks.yaml")) else {
+            continue;
+        };
+        let hooks: Vec<Hook> = serde_yaml::from_str(&hooks)?;
+        if let Some(hook) = hooks.into_iter().find(|h| h.id == id) {
+            return Ok(Some(hook));
+        }
+    }
+    Ok(None)
+}
+
+/// Run one hook on the files it selects
+async fn run_hook(executor: &Executor, hook: &Hook, files: &[PathBuf]) -> Result<HookResult> {
+    let entry = hook
+        .entry
+        .as_deref()
+        .ok_or_else(|| anyhow!("hook {} has no entry", hook.id))?;
+    let mut files = filter_files(files, hook.files.as_deref(), hook.exclude.as_deref())?;
+    files.retain(|f| matches_types(f, hook));
+    if files.is_empty() && !hook.always_run.unwrap_or(false) {
+        return Ok(HookResult::new(hook, HookStatus::Skipped));
+    }
+    let args = hook.args.clone().unwrap_or_default();
+    let started = Instant::now();
+    let mut result = HookResult {
+        files: files.len(),
+        ..HookResult::new(hook, HookStatus::Passed)
+    };
+
+    match hook.language.as_deref().unwrap_or("system") {
+        "fail" => {
+            result.status = HookStatus::Failed;
+            result.output = files
+                .iter()
+                .map(|f| format!("{}: {}\n", f.display(), entry))
+                .collect();
+        }
+        "pygrep" => {
+            let findings = pygrep(entry, &args, &files)?;
+            if !findings.is_empty() {
+                result.status = HookStatus::Failed;
+                result.output = findings;
+            }
+        }
+        language => {
+            if language == "docker" || language == "docker_image" {
+                bail!("language '{}' is not supported", language);
+            }
+            let words = shell_words::split(entry)
+                .map_err(|e| anyhow!("Invalid entry '{}': {}", entry, e))?;
+            let (command, entry_args) = words
+                .split_first()
+                .ok_or_else(|| anyhow!("hook {} has an empty entry", hook.id))?;
+            let before = fingerprints(&files);
+
+            let chunks: Vec<&[PathBuf]> =
+                if hook.pass_filenames.unwrap_or(true) && !files.is_empty() {
+                    files.chunks(FILES_PER_RUN).collect()
+                } else {
+                    vec![&[]]
+                };
+            for chunk in chunks {
+                let spec = CommandSpec {
+                    command: command.clone(),
+                    args: entry_args
+                        .iter()
+                        .chain(&args)
+                        .cloned()
+                        .chain(chunk.iter().map(|f| f.display().to_string()))
+                        .collect(),
+                    cwd: None,
+                    env: BTreeMap::new(),
+                    stdin: None,
+                };
+                let output = executor.execute_spec_capture(&spec).await?;
+                result.output.push_str(&output.stdout);
+                result.output.push_str(&output.stderr);
+                if !output.success() {
+                    result.status = HookStatus::Failed;
+                    result.exit_code = output.exit_code;
+                } else if result.exit_code.is_none() {
+                    result.exit_code = Some(0);
+                }
+            }
+
+            result.modified = files
+                .iter()
+                .zip(before)
+                .filter(|(file, before)| fingerprint(file) != *before)
+                .map(|(file, _)| file.clone())
+                .collect();
+            if !result.modified.is_empty() {
+                result.status = HookStatus::Failed;
+            }
+        }
+    }
+    result.duration_ms = started.elapsed().as_millis() as u64;
+    Ok(result)
+}
+
+/// Files matching `files` (default: all) and not `exclude` (default: none)
+fn filter_files(
+    files: &[PathBuf],
+    include: Option<&str>,
+    exclude: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let include = include
+        .filter(|p| !p.is_empty())
+        .map(Regex::new)
+        .transpose()?;
+    let exclude = exclude
+        .filter(|p| !p.is_empty() && *p != "^$")
+        .map(Regex::new)
+        .transpose()?;
+    Ok(files
+        .iter()
+        .filter(|f| {
+            let name = f.to_string_lossy();
+            include.as_ref().map_or(true, |r| r.is_match(&name))
+                && !exclude.as_ref().is_some_and(|r| r.is_match(&name))
+        })
+        .cloned()
+        .collect())
+}
+
+/// File type tags of a path, like pre-commit's `identify` for the common cases
+fn file_tags(path: &Path) -> Vec<&'static str> {
+    let mut tags = vec!["file"];
+    if path.is_symlink() {
+        tags.push("symlink");
+    }
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let text: &[&str] = match extension.as_str() {
+        "py" | "pyi" => &["python"],
+        "rs" => &["rust"],
+        "go" => &["go"],
+        "js" | "mjs" | "cjs" => &["javascript"],
+        "jsx" => &["javascript", "jsx"],
+        "ts" => &["ts"],
+        "tsx" => &["ts", "tsx"],
+        "json" => &["json"],
+        "yaml" | "yml" => &["yaml"],
+        "toml" => &["toml"],
+        "md" | "markdown" => &["markdown"],
+        "sh" | "bash" => &["shell", "bash"],
+        "proto" => &["proto"],
+        "html" | "htm" => &["html"],
+        "css" => &["css"],
+        "xml" => &["xml"],
+        "txt" | "cfg" | "ini" | "lock" => &[],
+        _ if name == "Dockerfile" => &["dockerfile"],
+        _ if name == "Makefile" => &["makefile"],
+        _ => {
+            // Unknown extensions: text unless the content looks binary
+            let binary = fs::read(path)
+                .map(|c| c.iter().take(8000).any(|b| *b == 0))
+                .unwrap_or(false);
+            tags.push(if binary { "binary" } else { "text" });
+            return tags;
+        }
+    };
+    tags.push("text");
+    tags.extend(text);
+    tags
+}
+
+/// Whether a file satisfies the hook's `types`, `types_or` and `exclude_types`
+fn matches_types(path: &Path, hook: &Hook) -> bool {
+    let tags = file_tags(path);
+    let has = |tag: &String| tags.contains(&tag.as_str());
+    hook.types
+        .as_ref()
+        .map_or(true, |types| types.iter().all(has))
+        && hook
+            .types_or
+            .as_ref()
+            .map_or(true, |types| types.is_empty() || types.iter().any(has))
+        && !hook
+            .exclude_types
+            .as_ref()
+            .is_some_and(|types| types.iter().any(has))
+}
+
+/// The `pygrep` language: report lines matching `pattern`, or files not matching it with
+/// `--negate`
+fn pygrep(pattern: &str, args: &[String], files: &[PathBuf]) -> Result<String> {
+    let has = |flag: &str| args.iter().any(|a| a == flag);
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(has("--ignore-case") || has("-i"))
+        .multi_line(true)
+        .dot_matches_new_line(has("--multiline"))
+        .build()?;
+    let mut findings = String::new();
+    for file in files {
+        let content = String::from_utf8_lossy(&fs::read(file)?).to_string();
+        if has("--negate") {
+            if !regex.is_match(&content) {
+                findings.push_str(&format!("{}\n", file.display()));
+            }
+        } else if has("--multiline") {
+            if let Some(found) = regex.find(&content) {
+                let line = content[..found.start()].matches('\n').count() + 1;
+                findings.push_str(&format!("{}:{}:{}\n", file.display(), line, found.as_str()));
+            }
+        } else {
+            for (number, line) in content.lines().enumerate() {
+                if regex.is_match(line) {
+                    findings.push_str(&format!("{}:{}:{}\n", file.display(), number + 1, line));
+                }
+            }
+        }
+    }
+    Ok(findings)
+}
+
+fn fingerprints(files: &[PathBuf]) -> Vec<Option<Vec<u8>>> {
+    files.iter().map(|f| fingerprint(f)).collect()
+}
+
+/// SHA-256 of a file's content, `None` if it cannot be read
+fn fingerprint(path: &Path) -> Option<Vec<u8>> {
+    fs::read(path)
+        .ok()
+        .map(|content| Sha256::digest(content).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_and_select_files() {
+        let config: PreCommitConfig = serde_yaml::from_str(
+            "exclude: ^vendor/\nrepos:\n  - repo: local\n    hooks:\n      - id: no-todo\n        name: No TODO\n        entry: TODO\n        language: pygrep\n        types: [python]\n  - repo: https://github.com/pre-commit/pre-commit-hooks\n    rev: v4.6.0\n    hooks:\n      - id: trailing-whitespace\n        args: [--markdown-linebreak-ext=md]\n",
+        )
+        .unwrap();
+        assert_eq!(config.repos.len(), 2);
+        assert_eq!(
+            config.repos[1].hooks[0].args.as_deref(),
+            Some(&["--markdown-linebreak-ext=md".to_string()][..])
+        );
+
+        let files = [
+            PathBuf::from("src/app.py"),
+            PathBuf::from("vendor/lib.py"),
+            PathBuf::from("README.md"),
+        ];
+        let files =
+            filter_files(&files, config.files.as_deref(), config.exclude.as_deref()).unwrap();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("src/app.py"), PathBuf::from("README.md")]
+        );
+
+        let hook = &config.repos[0].hooks[0];
+        let typed: Vec<_> = files.iter().filter(|f| matches_types(f, hook)).collect();
+        assert_eq!(typed, vec![&PathBuf::from("src/app.py")]);
+    }
+
+    #[test]
+    fn test_merge_with_definition_and_pygrep() {
+        let configured = Hook {
+            id: "check".to_string(),
+            args: Some(vec!["--fix".to_string()]),
+            ..Default::default()
+        };
+        let definition = Hook {
+            id: "check".to_string(),
+            entry: Some("check-tool".to_string()),
+            args: Some(vec!["--strict".to_string()]),
+            types: Some(vec!["python".to_string()]),
+            ..Default::default()
+        };
+        let hook = configured.merged_with(definition);
+        assert_eq!(hook.entry.as_deref(), Some("check-tool"));
+        assert_eq!(hook.args, Some(vec!["--fix".to_string()]));
+        assert_eq!(hook.types, Some(vec!["python".to_string()]));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("a.py");
+        fs::write(&file, "x = 1\n# todo: later\n").unwrap();
+        let findings = pygrep("todo", &["--ignore-case".to_string()], std::slice::from_ref(&file)).unwrap();
+        assert!(findings.ends_with(":2:# todo: later\n"));
+        assert!(pygrep("^import", &["--negate".to_string()], &[file])
+            .unwrap()
+            .contains("a.py"));
+    }
+
+    #[test]
+    fn test_latest_release_skips_prereleases() {
+        let ls_remote = "1111\trefs/tags/v4.5.0\n2222\trefs/tags/v4.10.0\n3333\trefs/tags/v5.0.0-rc1\n4444\trefs/tags/nightly\n";
+        assert_eq!(latest_release(ls_remote).as_deref(), Some("v4.10.0"));
+        assert_eq!(
+            latest_release("5555\trefs/tags/24.4.2\n").as_deref(),
+            Some("24.4.2")
+        );
+        assert_eq!(latest_release(""), None);
+        assert!(release_version("v4.10.0") > release_version("v4.9.1"));
+        assert_eq!(release_version("v5.0.0-rc1"), None);
+    }
+}
@@ -0,0 +1,149 @@
+// file: src/commands/process.rs
+// version: 1.0.0
+// guid: 71f1d17e-80b8-4b12-88eb-9b2ba3f51fd7
+
+//! `process` command: validate, format and lint files with the handlers their type calls for
+//!
+//! Agents asked to "fix this file" run `process FILE` instead of guessing the tool. Each file
+//! goes through its validate, format and lint handlers in that order, as routed by
+//! [`handlers::plan`]; a failing step skips the rest of that file's steps.
+
+use super::file_diff;
+use crate::config::FileAction;
+use crate::executor::Executor;
+use crate::handlers::{self, Route, Step};
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs;
+use std::path::Path;
+use tracing::{error, info};
+
+/// Build the process command
+pub fn build_command() -> Command {
+    Command::new("process")
+        .about("Validate, format and lint files with the tools their type calls for")
+        .long_about(
+            "Route each file by extension or MIME type to its handlers and run them: validate, \
+             then format, then lint. Handlers configured under [[process.handlers]] take \
+             precedence over the built-in ones (JSON, YAML and TOML parsed in-process, JSON \
+             reformatted, buf for protobuf, the usual formatter and linter for source files).",
+        )
+        .arg(
+            Arg::new("files")
+                .value_name("FILE")
+                .num_args(1..)
+                .required(true)
+                .help("Files to process"),
+        )
+        .arg(
+            Arg::new("action")
+                .long("action")
+                .short('a')
+                .value_parser(["validate", "format", "lint"])
+                .action(ArgAction::Append)
+                .help("Only run this action; repeat for several (default: all)"),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .action(ArgAction::SetTrue)
+                .help("Show the handler of each action without running it"),
+        )
+}
+
+/// Execute the process command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let actions: Vec<FileAction> = match matches.get_many::<String>("action") {
+        Some(names) => FileAction::ALL
+            .into_iter()
+            .filter(|action| names.clone().any(|name| name == action.as_str()))
+            .collect(),
+        None => FileAction::ALL.to_vec(),
+    };
+    let explain = matches.get_flag("explain");
+
+    let files: Vec<&String> = matches.get_many::<String>("files").unwrap().collect();
+    let mut errors = Vec::new();
+    for file in &files {
+        let path = Path::new(file.as_str());
+        let result = if explain {
+            explain_file(path, &actions, executor)
+        } else {
+            process_file(path, &actions, executor).await
+        };
+        if let Err(e) = result {
+            if files.len() > 1 {
+                error!("{}: {}", file, e);
+            }
+            errors.push(e);
+        }
+    }
+
+    // A single failure is returned as is, so a missing tool still ends the run with its
+    // capability request
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        n => bail!("{} of {} files failed", n, files.len()),
+    }
+}
+
+/// The steps for a file that exists and may be touched, or an error naming its type if
+/// nothing handles it
+fn plan(path: &Path, actions: &[FileAction], executor: &Executor) -> Result<Vec<Step>> {
+    if !path.is_file() {
+        bail!("No such file: {}", path.display());
+    }
+    executor.check_path(path)?;
+    let steps = handlers::plan(path, &executor.config().process, actions)?;
+    if steps.is_empty() {
+        bail!(
+            "No handler for {} files; add one under [[process.handlers]]",
+            handlers::mime_type(path)
+        );
+    }
+    Ok(steps)
+}
+
+fn explain_file(path: &Path, actions: &[FileAction], executor: &Executor) -> Result<()> {
+    let steps = plan(path, actions, executor)?;
+    println!("{} ({})", path.display(), handlers::mime_type(path));
+    for step in steps {
+        let origin = if step.configured {
+            "configured"
+        } else {
+            "built-in"
+        };
+        println!("  {:<9} {:<11} {}", step.action, origin, step);
+    }
+    Ok(())
+}
+
+async fn process_file(path: &Path, actions: &[FileAction], executor: &Executor) -> Result<()> {
+    for step in plan(path, actions, executor)? {
+        info!("{} {} with {}", step.action, path.display(), step);
+        match &step.route {
+            Route::Parse(format) => {
+                format
+                    .check(&fs::read_to_string(path)?)
+                    .map_err(|e| anyhow!("Invalid {}: {}", format, e))?;
+                println!("{}: valid {}", path.display(), format);
+            }
+            Route::Reformat(format) => {
+                let content = fs::read_to_string(path)?;
+                let formatted = format.reformat(&content).map_err(|e| anyhow!(e))?;
+                if formatted == content {
+                    println!("{}: already formatted", path.display());
+                } else if executor.config().safety.dry_run {
+                    println!("DRY RUN: Would reformat {}", path.display());
+                    file_diff::print_preview(path, formatted.as_bytes());
+                } else {
+                    fs::write(path, formatted)?;
+                    println!("{}: formatted", path.display());
+                }
+            }
+            Route::Run(words) => executor.execute_secure(&words[0], &words[1..]).await?,
+        }
+    }
+    Ok(())
+}
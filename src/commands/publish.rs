@@ -0,0 +1,561 @@
+// file: src/commands/publish.rs
+// version: 1.0.1
+// guid: a65730e8-11d8-427b-8274-6d5bd9c517fe
+
+//! `publish` command: package and upload crates, npm packages and Python distributions
+//!
+//! A run builds the artifacts into its directory in the state directory, checks them with the
+//! registry's dry run and, only with `--execute` and after confirmation, uploads exactly those
+//! files. Both commands are validated like any other, so policy rules can forbid publishing
+//! (the `restricted` profile does). Every run, dry or not, is recorded with a provenance
+//! document; see [`crate::publish`].
+
+use crate::activity::format_age;
+use crate::executor::Executor;
+use crate::publish::{
+    ArtifactFile, Ecosystem, PublishLog, PublishRecord, SourceInfo, PROVENANCE_FILE,
+};
+use crate::{session, tools, utils};
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Build the publish command
+pub fn build_command() -> Command {
+    Command::new("publish")
+        .about("Package and publish crates, npm packages and Python distributions with provenance")
+        .long_about(
+            "Build a package's artifacts, check them with the registry's dry run and record \
+             them with a SLSA provenance document. Nothing is uploaded without --execute, which \
+             asks for confirmation unless --yes is given or safety.confirm_destructive is off.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            with_upload_args(Command::new("cargo").about("Publish a crate to crates.io"))
+                .arg(
+                    Arg::new("package")
+                        .long("package")
+                        .short('p')
+                        .value_name("NAME")
+                        .help("Workspace member to publish (default: the package in the current directory)"),
+                )
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .value_name("NAME")
+                        .help("Registry to publish to, as named in .cargo/config.toml"),
+                )
+                .arg(
+                    Arg::new("allow-dirty")
+                        .long("allow-dirty")
+                        .action(ArgAction::SetTrue)
+                        .help("Package even with uncommitted changes"),
+                ),
+        )
+        .subcommand(
+            with_upload_args(Command::new("npm").about("Publish a package to the npm registry"))
+                .arg(
+                    Arg::new("path")
+                        .value_name("DIR")
+                        .default_value(".")
+                        .help("Package directory"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .help("Distribution tag (default: latest)"),
+                )
+                .arg(
+                    Arg::new("access")
+                        .long("access")
+                        .value_parser(["public", "restricted"])
+                        .help("Access level of a scoped package"),
+                )
+                .arg(
+                    Arg::new("registry")
+                        .long("registry")
+                        .value_name("URL")
+                        .help("Registry to publish to"),
+                ),
+        )
+        .subcommand(
+            with_upload_args(
+                Command::new("pypi").about("Upload built distributions to PyPI with twine"),
+            )
+            .arg(
+                Arg::new("dist")
+                    .long("dist")
+                    .value_name("DIR")
+                    .default_value("dist")
+                    .help("Directory holding the wheels and sdists, e.g. built by python -m build"),
+            )
+            .arg(
+                Arg::new("repository")
+                    .long("repository")
+                    .short('r')
+                    .value_name("NAME")
+                    .help("Repository from ~/.pypirc, e.g. testpypi (default: pypi)"),
+            ),
+        )
+        .subcommand(
+            Command::new("list").about("List publish runs").arg(
+                Arg::new("format")
+                    .long("format")
+                    .short('f')
+                    .value_parser(["table", "json"])
+                    .default_value("table")
+                    .help("Output format"),
+            ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Print the provenance document of a publish run")
+                .arg(
+                    Arg::new("id")
+                        .value_name("ID")
+                        .required(true)
+                        .help("Run ID or ID prefix"),
+                ),
+        )
+}
+
+fn with_upload_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("execute")
+                .long("execute")
+                .action(ArgAction::SetTrue)
+                .help("Upload after the checks pass (default: dry run)"),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .action(ArgAction::SetTrue)
+                .help("Upload without asking for confirmation"),
+        )
+}
+
+/// Execute the publish command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("cargo", sub_matches)) => publish(Ecosystem::Cargo, sub_matches, executor).await,
+        Some(("npm", sub_matches)) => publish(Ecosystem::Npm, sub_matches, executor).await,
+        Some(("pypi", sub_matches)) => publish(Ecosystem::Pypi, sub_matches, executor).await,
+        Some(("list", sub_matches)) => list(sub_matches),
+        Some(("show", sub_matches)) => {
+            let id = sub_matches.get_one::<String>("id").unwrap();
+            let log = PublishLog::load()?;
+            let record = log
+                .find(id)
+                .ok_or_else(|| anyhow!("No publish run with ID {}", id))?;
+            println!(
+                "{}",
+                fs::read_to_string(record.dir().join(PROVENANCE_FILE))?
+            );
+            Ok(())
+        }
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+/// Artifacts built for a package and the commands checking and uploading them
+struct Package {
+    name: String,
+    version: String,
+    registry: String,
+    /// Artifacts in the run's directory
+    artifacts: Vec<PathBuf>,
+    /// Files the check builds, copied into the run's directory once it passes
+    built: Vec<(PathBuf, PathBuf)>,
+    check: Vec<String>,
+    upload: Vec<String>,
+    /// Tools whose versions go into the provenance
+    tools: &'static [&'static str],
+}
+
+async fn publish(ecosystem: Ecosystem, matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    if executor.config().safety.dry_run {
+        println!(
+            "DRY RUN: Would package and check the {} package; nothing is built or uploaded",
+            ecosystem
+        );
+        return Ok(());
+    }
+
+    let id = session::current().execution_id().to_string();
+    let dir = PublishRecord::dir_for(&id);
+    fs::create_dir_all(&dir)?;
+    // Runs that fail before they are recorded leave nothing behind
+    let result = run(ecosystem, matches, executor, id, &dir).await;
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&dir);
+    }
+    result
+}
+
+async fn run(
+    ecosystem: Ecosystem,
+    matches: &ArgMatches,
+    executor: &Executor,
+    id: String,
+    dir: &Path,
+) -> Result<()> {
+    let started_at = Utc::now();
+    let package = match ecosystem {
+        Ecosystem::Cargo => package_crate(matches, executor, dir).await?,
+        Ecosystem::Npm => package_npm(matches, executor, dir).await?,
+        Ecosystem::Pypi => package_dists(matches, dir)?,
+    };
+    executor
+        .execute_secure(&package.check[0], &package.check[1..])
+        .await?;
+    for (built, kept) in &package.built {
+        fs::copy(built, kept).map_err(|e| anyhow!("Cannot keep {}: {}", built.display(), e))?;
+    }
+
+    let artifacts = package
+        .artifacts
+        .iter()
+        .map(|path| ArtifactFile::from_path(path))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    println!(
+        "{} {} for {}:",
+        package.name, package.version, package.registry
+    );
+    for artifact in &artifacts {
+        println!(
+            "  {}  sha256:{}  {} bytes",
+            artifact.name, artifact.sha256, artifact.size
+        );
+    }
+
+    let uploaded = if matches.get_flag("execute") {
+        upload(&package, matches, executor).await?
+    } else {
+        println!("Dry run; nothing was uploaded (pass --execute to publish)");
+        false
+    };
+
+    let record = PublishRecord {
+        id,
+        session_id: session::current().session_id().to_string(),
+        ecosystem,
+        package: package.name,
+        version: package.version,
+        registry: package.registry,
+        uploaded,
+        artifacts,
+        command: package.upload,
+        tools: tool_versions(package.tools, executor),
+        source: source_info(executor).await,
+        started_at,
+        finished_at: Utc::now(),
+    };
+    let provenance = record.write_provenance()?;
+    println!("Provenance: {}", provenance.display());
+    PublishLog::update(|log| log.records.push(record))?;
+    Ok(())
+}
+
+/// Upload the checked artifacts once policy allows it and the user agrees; whether they were
+async fn upload(package: &Package, matches: &ArgMatches, executor: &Executor) -> Result<bool> {
+    let (command, args) = package.upload.split_first().unwrap();
+    // A denial is reported before anyone is asked to confirm an upload that cannot happen
+    executor.policy().evaluate(command, args)?;
+    let prompt = format!(
+        "Publish {} {} to {}?",
+        package.name, package.version, package.registry
+    );
+    if !matches.get_flag("yes")
+        && executor.config().safety.confirm_destructive
+        && !utils::confirm(&prompt)?
+    {
+        println!("Aborted; nothing was uploaded");
+        return Ok(false);
+    }
+    executor.execute_secure(command, args).await?;
+    println!("Published {} {}", package.name, package.version);
+    Ok(true)
+}
+
+async fn package_crate(matches: &ArgMatches, executor: &Executor, dir: &Path) -> Result<Package> {
+    let output = executor
+        .execute_secure_capture("cargo", &["metadata", "--no-deps", "--format-version", "1"])
+        .await?;
+    if !output.success() {
+        bail!("cargo metadata failed: {}", output.stderr.trim());
+    }
+    let metadata: Value = serde_json::from_str(&output.stdout)?;
+    let selected = matches.get_one::<String>("package");
+    let manifest = std::env::current_dir()?.join("Cargo.toml");
+    let package = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|package| match selected {
+            Some(name) => package["name"] == name.as_str(),
+            None => package["manifest_path"].as_str().map(Path::new) == Some(manifest.as_path()),
+        })
+        .ok_or_else(|| match selected {
+            Some(name) => anyhow!("No package {} in this workspace", name),
+            None => anyhow!("No package in the current directory; choose one with --package"),
+        })?;
+    let name = package["name"].as_str().unwrap_or_default().to_string();
+    let version = package["version"].as_str().unwrap_or_default().to_string();
+
+    let mut options = vec!["--package".to_string(), name.clone()];
+    if let Some(registry) = matches.get_one::<String>("registry") {
+        options.extend(["--registry".to_string(), registry.clone()]);
+    }
+    if matches.get_flag("allow-dirty") {
+        options.push("--allow-dirty".to_string());
+    }
+    // `cargo publish --dry-run` packages and verifies the crate; the upload packages it again
+    // from the same sources, which gives the same file
+    let crate_file = format!("{}-{}.crate", name, version);
+    let target = PathBuf::from(metadata["target_directory"].as_str().unwrap_or("target"));
+    let artifact = dir.join(&crate_file);
+
+    let mut check = vec![
+        "cargo".to_string(),
+        "publish".to_string(),
+        "--dry-run".to_string(),
+    ];
+    check.extend(options.iter().cloned());
+    let mut upload = vec!["cargo".to_string(), "publish".to_string()];
+    upload.extend(options);
+
+    Ok(Package {
+        registry: matches
+            .get_one::<String>("registry")
+            .cloned()
+            .unwrap_or_else(|| Ecosystem::Cargo.default_registry().to_string()),
+        name,
+        version,
+        artifacts: vec![artifact.clone()],
+        built: vec![(target.join("package").join(&crate_file), artifact)],
+        check,
+        upload,
+        tools: &["cargo", "rustc"],
+    })
+}
+
+async fn package_npm(matches: &ArgMatches, executor: &Executor, dir: &Path) -> Result<Package> {
+    let path = matches.get_one::<String>("path").unwrap();
+    executor.check_path(path)?;
+    let dir_arg = dir.to_string_lossy().into_owned();
+    let output = executor
+        .execute_secure_capture(
+            "npm",
+            &[
+                "pack",
+                path.as_str(),
+                "--json",
+                "--pack-destination",
+                &dir_arg,
+            ],
+        )
+        .await?;
+    if !output.success() {
+        bail!("npm pack failed: {}", output.stderr.trim());
+    }
+    let packed: Value = serde_json::from_str(&output.stdout)
+        .map_err(|e| anyhow!("Unexpected npm pack output: {}", e))?;
+    let packed = &packed[0];
+    let tarball = dir.join(
+        packed["filename"]
+            .as_str()
+            .ok_or_else(|| anyhow!("npm pack did not name the tarball"))?,
+    );
+
+    let tarball_arg = tarball.to_string_lossy().into_owned();
+    let mut options = Vec::new();
+    for flag in ["tag", "access", "registry"] {
+        if let Some(value) = matches.get_one::<String>(flag) {
+            options.extend([format!("--{}", flag), value.clone()]);
+        }
+    }
+    let mut check = vec![
+        "npm".to_string(),
+        "publish".to_string(),
+        tarball_arg.clone(),
+        "--dry-run".to_string(),
+    ];
+    check.extend(options.iter().cloned());
+    let mut upload = vec!["npm".to_string(), "publish".to_string(), tarball_arg];
+    upload.extend(options);
+
+    Ok(Package {
+        name: packed["name"].as_str().unwrap_or_default().to_string(),
+        version: packed["version"].as_str().unwrap_or_default().to_string(),
+        registry: matches
+            .get_one::<String>("registry")
+            .cloned()
+            .unwrap_or_else(|| Ecosystem::Npm.default_registry().to_string()),
+        artifacts: vec![tarball],
+        built: Vec::new(),
+        check,
+        upload,
+        tools: &["npm", "node"],
+    })
+}
+
+fn package_dists(matches: &ArgMatches, dir: &Path) -> Result<Package> {
+    let dist = Path::new(matches.get_one::<String>("dist").unwrap());
+    let mut built: Vec<PathBuf> = fs::read_dir(dist)
+        .map_err(|e| {
+            anyhow!(
+                "Cannot read {}: {}; build the package first, e.g. with python -m build",
+                dist.display(),
+                e
+            )
+        })?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.to_string_lossy();
+            name.ends_with(".whl") || name.ends_with(".tar.gz")
+        })
+        .collect();
+    built.sort();
+    let Some((name, version)) = built.first().and_then(|path| dist_name(path)) else {
+        bail!(
+            "No wheels or sdists in {}; build the package first, e.g. with python -m build",
+            dist.display()
+        );
+    };
+    if let Some(other) = built
+        .iter()
+        .find(|path| dist_name(path).map(|(_, v)| v) != Some(version.clone()))
+    {
+        bail!(
+            "{} holds more than one version ({} and {}); remove the old distributions first",
+            dist.display(),
+            version,
+            other.display()
+        );
+    }
+
+    // Upload the copies that are hashed and kept, not files that could change meanwhile
+    let mut artifacts = Vec::new();
+    for path in &built {
+        let kept = dir.join(path.file_name().unwrap());
+        fs::copy(path, &kept)?;
+        artifacts.push(kept);
+    }
+    let files: Vec<String> = artifacts
+        .iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+
+    let mut check = vec![
+        "twine".to_string(),
+        "check".to_string(),
+        "--strict".to_string(),
+    ];
+    check.extend(files.iter().cloned());
+    let mut upload = vec![
+        "twine".to_string(),
+        "upload".to_string(),
+        "--non-interactive".to_string(),
+    ];
+    let repository = matches.get_one::<String>("repository");
+    if let Some(repository) = repository {
+        upload.extend(["--repository".to_string(), repository.clone()]);
+    }
+    upload.extend(files);
+
+    Ok(Package {
+        name,
+        version,
+        registry: repository
+            .cloned()
+            .unwrap_or_else(|| Ecosystem::Pypi.default_registry().to_string()),
+        artifacts,
+        built: Vec::new(),
+        check,
+        upload,
+        tools: &["twine", "python3"],
+    })
+}
+
+/// Name and version from a wheel (`name-1.0-py3-none-any.whl`) or sdist (`name-1.0.tar.gz`)
+fn dist_name(path: &Path) -> Option<(String, String)> {
+    let file = path.file_name()?.to_str()?;
+    let (name, version) = match file.strip_suffix(".tar.gz") {
+        Some(stem) => stem.rsplit_once('-')?,
+        None => {
+            let mut parts = file.strip_suffix(".whl")?.splitn(3, '-');
+            (parts.next()?, parts.next()?)
+        }
+    };
+    Some((name.to_string(), version.to_string()))
+}
+
+/// Versions of the tools that are installed, by name
+fn tool_versions(names: &[&str], executor: &Executor) -> BTreeMap<String, String> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let path = tools::resolve(name, executor.config().tools.get(*name)).ok()?;
+            let version = tools::tool_version(&path).ok()?;
+            Some((name.to_string(), tools::join_version(&version)))
+        })
+        .collect()
+}
+
+/// Commit and remote of the repository in the current directory, if it is one
+async fn source_info(executor: &Executor) -> SourceInfo {
+    let git = |args: &'static [&'static str]| async move {
+        executor
+            .execute_secure_capture("git", args)
+            .await
+            .ok()
+            .filter(|output| output.success())
+            .map(|output| output.stdout.trim().to_string())
+    };
+    SourceInfo {
+        commit: git(&["rev-parse", "HEAD"]).await,
+        repository: git(&["remote", "get-url", "origin"]).await,
+        dirty: git(&["status", "--porcelain"])
+            .await
+            .is_some_and(|status| !status.is_empty()),
+    }
+}
+
+fn list(matches: &ArgMatches) -> Result<()> {
+    let log = PublishLog::load()?;
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&log.records)?);
+        return Ok(());
+    }
+    if log.records.is_empty() {
+        println!("No publish runs");
+        return Ok(());
+    }
+    let now = Utc::now();
+    println!(
+        "{:<8} {:<6} {:<24} {:<12} {:<9} {:>8}",
+        "ID", "KIND", "PACKAGE", "VERSION", "UPLOADED", "AGE"
+    );
+    for record in &log.records {
+        println!(
+            "{:<8} {:<6} {:<24} {:<12} {:<9} {:>8}",
+            &record.id[..record.id.len().min(8)],
+            record.ecosystem,
+            record.package,
+            record.version,
+            if record.uploaded { "yes" } else { "dry run" },
+            format_age(now - record.finished_at)
+        );
+    }
+    Ok(())
+}
+
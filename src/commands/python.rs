@@ -1,7 +1,8 @@
 // file: src/commands/python.rs
-// version: 1.2.0
+// version: 1.2.1
 // guid: 38a24a1a-5d79-4344-acac-f99e390fe1ac
 
+use super::export::Remote;
 use crate::executor::Executor;
 use anyhow::Result;
 use clap::{Arg, ArgAction, ArgMatches, Command};
@@ -85,6 +86,7 @@ Common flows:
         )
         .subcommand(
             Command::new("pip")
+                .add(Remote)
                 .about("Pip operations scoped to a virtual environment (never global by default)")
                 .subcommand(
                     Command::new("install")
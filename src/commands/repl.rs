@@ -0,0 +1,36 @@
+// file: src/commands/repl.rs
+// version: 1.0.0
+// guid: 1b7f4c9e-8d20-4e6a-a5c3-2f9e0d6b84a7
+
+//! `repl` command: an interactive prompt running commands of this utility (see
+//! [`crate::repl`])
+
+use crate::executor::Executor;
+use crate::repl::{self, ReplOptions};
+use anyhow::Result;
+use clap::{Arg, ArgAction, Command};
+
+/// Build the repl command
+pub fn build_command() -> Command {
+    Command::new("repl")
+        .about("Run commands at an interactive prompt with history and completion")
+        .long_about(
+            "Read command lines such as `git status` at a prompt and run each as a command of \
+             this utility. `cd`, `profile`, `set` and `unset` change the working directory, \
+             profile and environment of the commands that follow; `help` lists them. Lines are \
+             edited with the usual readline keys, Tab completes commands, options and values, \
+             and the history is kept in the state directory. Without a terminal, lines are read \
+             from stdin.",
+        )
+        .arg(
+            Arg::new("no-history")
+                .long("no-history")
+                .action(ArgAction::SetTrue)
+                .help("Neither read nor save the history file"),
+        )
+}
+
+/// Execute the repl command
+pub async fn execute(executor: &Executor, options: ReplOptions) -> Result<()> {
+    Ok(repl::run(executor, options).await?)
+}
@@ -0,0 +1,141 @@
+// file: src/commands/replay.rs
+// version: 1.0.2
+// guid: 3a9c6e02-5d71-4f8b-b2e4-8c07d1f45a93
+
+//! `replay` command: run a command from history again
+//!
+//! The recorded command is re-executed with the same arguments, working directory and
+//! environment variables, through the same validation and policy checks as a new command, so
+//! a replay can be refused where the original run was allowed. Runs whose record had
+//! credentials redacted are refused too, since their arguments are no longer the ones that
+//! ran. `--dry-run` shows what would run without running it.
+
+use super::export::Effect;
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use crate::history::{History, Run};
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+
+/// Build the replay command
+pub fn build_command() -> Command {
+    Command::new("replay")
+        .add(Effect::Destructive)
+        .about("Run a command from history again")
+        .long_about(
+            "Re-executes a recorded run with the same arguments, working directory and \
+             environment variables. The command is validated again against the current \
+             configuration and policy.",
+        )
+        .arg(
+            Arg::new("id")
+                .value_name("ID")
+                .required(true)
+                .value_parser(clap::value_parser!(i64))
+                .help("Run ID from 'history list'"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Show what would run without running it"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+}
+
+/// Execute the replay command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let id = *matches.get_one::<i64>("id").unwrap();
+    let json = matches.get_one::<String>("format").map(String::as_str) == Some("json");
+    let run = History::open()?
+        .get(id)?
+        .ok_or_else(|| anyhow!("No run {} in history; see `history list`", id))?;
+
+    if matches.get_flag("dry-run") || executor.config().safety.dry_run {
+        if json {
+            let mut report = serde_json::to_value(&run)?;
+            report["dry_run"] = json!(true);
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_preview(&run);
+        }
+        return Ok(());
+    }
+    if run.redacted() {
+        bail!(
+            "Run {} had credentials redacted from its record and cannot be replayed; run `{}` again \
+             with them",
+            run.id,
+            run.command_line()
+        );
+    }
+
+    let spec = CommandSpec {
+        command: run.command.clone(),
+        args: run.args.clone(),
+        cwd: Some(run.cwd.clone()),
+        env: run.env.clone(),
+        stdin: None,
+    };
+    let output = executor.execute_spec_capture(&spec).await?;
+
+    if json {
+        let report = json!({
+            "replayed_from": run.id,
+            "command_line": run.command_line(),
+            "exit_code": output.exit_code,
+            "original_exit_code": run.exit_code,
+            "stdout": output.stdout,
+            "stderr": output.stderr,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+        eprintln!(
+            "Run {} exited with {} originally; the replay exited with {}",
+            run.id,
+            exit_code(run.exit_code),
+            exit_code(output.exit_code)
+        );
+    }
+    if !output.success() {
+        bail!(
+            "Replay of run {} failed with exit code {}",
+            run.id,
+            exit_code(output.exit_code)
+        );
+    }
+    Ok(())
+}
+
+fn print_preview(run: &Run) {
+    println!("Would replay run {}:", run.id);
+    println!("  Command:    {}", run.command_line());
+    println!("  Directory:  {}", run.cwd.display());
+    if !run.env.is_empty() {
+        let names: Vec<&str> = run.env.keys().map(String::as_str).collect();
+        println!("  Env:        {}", names.join(", "));
+    }
+    println!(
+        "  Originally: exit code {} at {}",
+        exit_code(run.exit_code),
+        run.started_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+    );
+}
+
+fn exit_code(code: Option<i32>) -> String {
+    code.map(|code| code.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
@@ -0,0 +1,206 @@
+// file: src/commands/run.rs
+// version: 1.3.1
+// guid: 2f8b6d1e-4c7a-4e9b-a3d5-1b0e9f7c2a64
+
+//! `run --manifest`: execute the named steps of a task manifest as a dependency graph
+
+use super::export::Effect;
+use crate::batch::{self, BatchStatus};
+use crate::commands::completions;
+use crate::executor::Executor;
+use crate::manifest::{Manifest, MatrixReport, RunOptions, StepResult};
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use clap_complete::engine::ArgValueCandidates;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// Build the run command
+pub fn build_command() -> Command {
+    Command::new("run")
+        .add(Effect::Destructive)
+        .about("Run the steps of a task manifest in dependency order")
+        .long_about(
+            "Run the steps of a task manifest (YAML, or TOML for .toml files) in dependency \
+             order. Each step names a command, its arguments, working directory, environment \
+             and the steps it depends on; independent steps run concurrently. A step with a \
+             matrix runs once per combination of its values, and the run ends with a pass/fail \
+             table for each matrix step.",
+        )
+        .arg(
+            Arg::new("manifest")
+                .long("manifest")
+                .short('m')
+                .value_name("FILE")
+                .required(true)
+                .help("Task manifest, e.g. tasks.yaml"),
+        )
+        .arg(
+            Arg::new("steps")
+                .value_name("STEP")
+                .num_args(0..)
+                .help("Steps to run with their dependencies (default: all)")
+                .add(ArgValueCandidates::new(completions::step_candidates)),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .value_name("N")
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .help("Steps running at the same time (default: manifest jobs, or number of CPUs)"),
+        )
+        .arg(
+            Arg::new("keep-going")
+                .long("keep-going")
+                .short('k')
+                .action(ArgAction::SetTrue)
+                .help("Start independent steps after a failure instead of stopping"),
+        )
+        .arg(
+            Arg::new("log-dir")
+                .long("log-dir")
+                .value_name("DIR")
+                .help("Write each step's output to DIR/<step>.log"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+}
+
+/// Execute the run command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let path = Path::new(matches.get_one::<String>("manifest").unwrap());
+    executor.check_path(path)?;
+    let mut manifest = Manifest::load(path)?;
+    let targets: Vec<String> = matches
+        .get_many::<String>("steps")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    manifest.select(&targets)?;
+    if let Some(budget) = &manifest.budget {
+        executor.budget().narrow(budget);
+    }
+
+    let log_dir = matches.get_one::<String>("log-dir").map(PathBuf::from);
+    if let Some(dir) = &log_dir {
+        executor.check_path(dir)?;
+    }
+    let options = RunOptions {
+        jobs: matches
+            .get_one::<u16>("jobs")
+            .map(|jobs| *jobs as usize)
+            .or(manifest.jobs)
+            .unwrap_or_else(batch::default_jobs),
+        keep_going: matches.get_flag("keep-going"),
+        log_dir,
+    };
+
+    let results = manifest.run(executor, &options).await;
+    let batch_results: Vec<_> = results.iter().map(|r| r.result.clone()).collect();
+    let matrices = manifest.matrix_report(&results);
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let mut report = json!({"steps": results, "summary": batch::summary(&batch_results)});
+        if !matrices.is_empty() {
+            report["matrix"] = json!(matrices);
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_text(&results);
+        for matrix in &matrices {
+            print_matrix(matrix);
+        }
+    }
+
+    let unsuccessful = results
+        .iter()
+        .filter(|r| r.result.status != BatchStatus::Succeeded)
+        .filter(|r| {
+            let tolerated = manifest
+                .steps
+                .iter()
+                .any(|s| s.name == r.name && s.continue_on_error);
+            !(tolerated && r.result.status == BatchStatus::Failed)
+        })
+        .count();
+    if unsuccessful > 0 {
+        bail!(
+            "{} of {} steps did not succeed",
+            unsuccessful,
+            results.len()
+        );
+    }
+    Ok(())
+}
+
+/// Print each step's output under a header, then a summary line
+fn print_text(results: &[StepResult]) {
+    for StepResult { name, result } in results {
+        let detail = match (result.status, result.exit_code) {
+            (BatchStatus::Skipped, _) => String::new(),
+            (BatchStatus::Failed, Some(code)) => {
+                format!(" (exit {}, {} ms)", code, result.duration_ms)
+            }
+            _ => format!(" ({} ms)", result.duration_ms),
+        };
+        println!(
+            "==> [{}] {}: {}{}",
+            result.status.as_str(),
+            name,
+            result.spec,
+            detail
+        );
+        print!("{}", result.stdout);
+        eprint!("{}", result.stderr);
+        if let Some(error) = &result.error {
+            println!("    {}", error);
+        }
+    }
+    println!("{} steps", results.len());
+}
+
+/// Print a matrix step's variants as a table of their values and outcomes
+fn print_matrix(report: &MatrixReport) {
+    println!(
+        "matrix {}: {} passed, {} failed",
+        report.step, report.passed, report.failed
+    );
+    let mut rows = vec![report
+        .keys
+        .iter()
+        .cloned()
+        .chain(["status".to_string()])
+        .collect::<Vec<_>>()];
+    for variant in &report.variants {
+        let outcome = match variant.exit_code {
+            Some(code) if code != 0 => format!("{} (exit {})", variant.status.as_str(), code),
+            _ => variant.status.as_str().to_string(),
+        };
+        rows.push(
+            report
+                .keys
+                .iter()
+                .map(|key| variant.values[key].clone())
+                .chain([outcome])
+                .collect(),
+        );
+    }
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap_or(0))
+        .collect();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("  {}", cells.join("  ").trim_end());
+    }
+}
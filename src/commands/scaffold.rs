@@ -0,0 +1,533 @@
+// file: src/commands/scaffold.rs
+// version: 1.3.2
+// guid: 9957ce9e-e153-47ca-8151-523181b2cef6
+
+//! Project template instantiation for `scaffold new`
+//!
+//! A template is a directory tree, either local or cloned from an allowlisted git host.
+//! `{{ variable }}` placeholders in file contents and paths are substituted, and an optional
+//! `template.toml` at the template root declares variables and tool versions to pin:
+//!
+//! ```toml
+//! [variables.description]
+//! description = "One-line project description"
+//! default = "A new project"
+//!
+//! [tools]
+//! rust = "1.78.0"
+//! ```
+//!
+//! The generated files are recorded in the new project's generated-files registry (see
+//! [`crate::generated`]), which is part of the initial commit.
+
+use super::export::Remote;
+use crate::config::ScaffoldConfig;
+use crate::executor::Executor;
+use crate::generated::Registry;
+use crate::intent;
+use anyhow::{anyhow, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+use walkdir::WalkDir;
+
+/// Name of the optional manifest at the template root
+const MANIFEST_FILE: &str = "template.toml";
+
+/// File used to pin tool versions in generated projects
+const TOOL_VERSIONS_FILE: &str = ".tool-versions";
+
+/// Template manifest (`template.toml`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TemplateManifest {
+    variables: BTreeMap<String, VariableSpec>,
+    tools: BTreeMap<String, String>,
+}
+
+/// Declaration of a template variable
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct VariableSpec {
+    description: Option<String>,
+    default: Option<String>,
+}
+
+/// A file that will be created from the template
+#[derive(Debug)]
+struct PlannedFile {
+    source: PathBuf,
+    target: PathBuf,
+}
+
+/// Build the scaffold command
+pub fn build_command() -> Command {
+    Command::new("scaffold")
+        .about("Create new projects from templates")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("new")
+                .add(Remote)
+                .about("Instantiate a project template")
+                .arg(
+                    Arg::new("template")
+                        .value_name("TEMPLATE")
+                        .help("Template directory, template name, or allowlisted git URL")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("dest")
+                        .long("dest")
+                        .short('d')
+                        .value_name("DIR")
+                        .help("Directory to create (defaults to the template name)"),
+                )
+                .arg(
+                    Arg::new("var")
+                        .long("var")
+                        .value_name("KEY=VALUE")
+                        .action(ArgAction::Append)
+                        .help("Set a template variable"),
+                )
+                .arg(
+                    Arg::new("ref")
+                        .long("ref")
+                        .value_name("REF")
+                        .help("Branch or tag to use for git-hosted templates"),
+                )
+                .arg(
+                    Arg::new("no-git")
+                        .long("no-git")
+                        .action(ArgAction::SetTrue)
+                        .help("Do not initialize a git repository"),
+                )
+                .arg(
+                    Arg::new("no-commit")
+                        .long("no-commit")
+                        .action(ArgAction::SetTrue)
+                        .help("Initialize git but do not create the first commit"),
+                )
+                .arg(
+                    Arg::new("no-pin")
+                        .long("no-pin")
+                        .action(ArgAction::SetTrue)
+                        .help("Do not write pinned tool versions"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("List the files that would be created without writing anything"),
+                ),
+        )
+}
+
+/// Execute scaffold commands
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("new", sub_matches)) => execute_new(sub_matches, executor).await,
+        _ => Err(anyhow!("No scaffold subcommand specified")),
+    }
+}
+
+async fn execute_new(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let template = matches.get_one::<String>("template").unwrap();
+    let dry_run = matches.get_flag("dry-run") || executor.config().safety.dry_run;
+    let settings = &executor.config().scaffold;
+
+    // Keep the clone alive until generation is complete
+    let mut _checkout = None;
+    let root = if is_remote(template) {
+        if !is_allowed_source(template, settings) {
+            return Err(anyhow!(
+                "Template source '{}' is not in the scaffold allowlist",
+                template
+            ));
+        }
+        if executor.config().safety.dry_run {
+            println!("DRY RUN: Would clone template {}", template);
+            return Ok(());
+        }
+        let checkout = tempfile::tempdir()?;
+        let clone_dir = checkout.path().join("template");
+        let clone_dir_arg = clone_dir.to_string_lossy().to_string();
+        let mut args = vec!["clone", "--depth", "1"];
+        if let Some(reference) = matches.get_one::<String>("ref") {
+            args.extend(["--branch", reference.as_str()]);
+        }
+        args.extend([template.as_str(), clone_dir_arg.as_str()]);
+        info!("Cloning template from {}", template);
+        executor.execute_secure("git", &args).await?;
+        _checkout = Some(checkout);
+        clone_dir
+    } else {
+        resolve_local_template(template, settings)?
+    };
+
+    let manifest = load_manifest(&root)?;
+    let dest = match matches.get_one::<String>("dest") {
+        Some(dest) => PathBuf::from(dest),
+        None => PathBuf::from(template_name(template)),
+    };
+
+    let provided = matches
+        .get_many::<String>("var")
+        .map(|values| values.map(|v| parse_var(v)).collect::<Result<Vec<_>>>())
+        .transpose()?
+        .unwrap_or_default();
+    let vars = resolve_variables(&manifest, &dest, provided)?;
+
+    let files = plan_files(&root, &vars)?;
+    let pin_tools = !matches.get_flag("no-pin") && !manifest.tools.is_empty();
+    let init_git = !matches.get_flag("no-git");
+    let commit = init_git && !matches.get_flag("no-commit");
+
+    if dry_run {
+        println!("DRY RUN: Would create {} with {} files:", dest.display(), files.len());
+        for file in &files {
+            println!("  {}", file.target.display());
+        }
+        if pin_tools {
+            println!("  {}", TOOL_VERSIONS_FILE);
+        }
+        if init_git {
+            println!("DRY RUN: Would initialize a git repository");
+        }
+        if commit {
+            println!("DRY RUN: Would create the initial commit");
+        }
+        return Ok(());
+    }
+
+    ensure_empty_destination(&dest)?;
+    let mut registry = Registry::load(&dest)?;
+    let generator = format!("scaffold new {}", template);
+    for file in &files {
+        write_file(executor, &file.source, &dest.join(&file.target), &vars)?;
+        registry.record(&file.target, template, &generator)?;
+    }
+    println!("Created {} files in {}", files.len(), dest.display());
+
+    if pin_tools {
+        let pins: String = manifest
+            .tools
+            .iter()
+            .map(|(tool, version)| format!("{} {}\n", tool, version))
+            .collect();
+        let path = dest.join(TOOL_VERSIONS_FILE);
+        executor.check_content(&path, pins.as_bytes())?;
+        fs::write(path, pins)?;
+        registry.record(Path::new(TOOL_VERSIONS_FILE), template, &generator)?;
+        println!("Pinned {} tool versions in {}", manifest.tools.len(), TOOL_VERSIONS_FILE);
+    }
+
+    registry.save()?;
+
+    if init_git {
+        let dest_arg = dest.to_string_lossy().to_string();
+        executor.execute_secure("git", &["-C", &dest_arg, "init"]).await?;
+        if commit {
+            let message = format!("Initial commit from template {}", template_name(template));
+            executor.execute_secure("git", &["-C", &dest_arg, "add", "-A"]).await?;
+            let mut args = vec!["-C".to_string(), dest_arg, "commit".to_string(), "-m".to_string(), message];
+            args.extend(intent::commit_trailer_args());
+            executor
+                .execute_secure("git", &args)
+                .await
+                .context("Project files were generated but the initial commit failed")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the template refers to a git-hosted repository
+fn is_remote(template: &str) -> bool {
+    template.contains("://") || template.starts_with("git@")
+}
+
+/// Whether a git-hosted template matches one of the configured sources: same scheme, host
+/// and port, and the source's path segments as whole leading segments of the template's
+fn is_allowed_source(url: &str, settings: &ScaffoldConfig) -> bool {
+    let Some(template) = parse_source(url) else {
+        return false;
+    };
+    settings.allowed_sources.iter().any(|source| {
+        let Some(source) = parse_source(source) else {
+            return false;
+        };
+        let segments = |url: &Url| -> Vec<String> {
+            url.path_segments()
+                .map(|segments| segments.filter(|s| !s.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default()
+        };
+        let (allowed, requested) = (segments(&source), segments(&template));
+        source.scheme() == template.scheme()
+            && source.host_str().is_some_and(|host| Some(host) == template.host_str())
+            && source.port_or_known_default() == template.port_or_known_default()
+            && source.username() == template.username()
+            && allowed.len() <= requested.len()
+            && allowed.iter().zip(&requested).all(|(allowed, requested)| {
+                allowed == requested || requested.strip_suffix(".git") == Some(allowed.as_str())
+            })
+    })
+}
+
+/// Parse a template source, reading scp-like `git@host:org/repo` as `ssh://git@host/org/repo`
+fn parse_source(source: &str) -> Option<Url> {
+    if !source.contains("://") {
+        let (user_host, path) = source.split_once(':')?;
+        return Url::parse(&format!("ssh://{}/{}", user_host, path)).ok();
+    }
+    Url::parse(source).ok()
+}
+
+/// Resolve a local template path, falling back to the configured templates directory
+fn resolve_local_template(template: &str, settings: &ScaffoldConfig) -> Result<PathBuf> {
+    let direct = PathBuf::from(template);
+    if direct.is_dir() {
+        return Ok(direct);
+    }
+
+    let templates_dir = settings
+        .templates_dir
+        .as_ref()
+        .map(|dir| PathBuf::from(shellexpand::tilde(&dir.to_string_lossy()).as_ref()))
+        .or_else(|| dirs::config_dir().map(|dir| dir.join("safe-ai-util").join("templates")));
+    if let Some(dir) = templates_dir {
+        let named = dir.join(template);
+        if named.is_dir() {
+            return Ok(named);
+        }
+    }
+
+    Err(anyhow!("Template not found: {}", template))
+}
+
+/// Derive a default project name from the template reference
+fn template_name(template: &str) -> String {
+    template
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or(template)
+        .trim_end_matches(".git")
+        .to_string()
+}
+
+fn load_manifest(root: &Path) -> Result<TemplateManifest> {
+    let path = root.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(TemplateManifest::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    toml::from_str(&content).with_context(|| format!("Invalid {}", path.display()))
+}
+
+fn parse_var(raw: &str) -> Result<(String, String)> {
+    match raw.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(anyhow!("Invalid variable '{}', expected KEY=VALUE", raw)),
+    }
+}
+
+/// Combine provided values, manifest defaults and built-ins, failing on missing variables
+fn resolve_variables(
+    manifest: &TemplateManifest,
+    dest: &Path,
+    provided: Vec<(String, String)>,
+) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::new();
+    let project_name = dest
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    vars.insert("project_name".to_string(), project_name);
+
+    for (name, spec) in &manifest.variables {
+        if let Some(default) = &spec.default {
+            vars.insert(name.clone(), default.clone());
+        }
+    }
+    vars.extend(provided);
+
+    let missing: Vec<String> = manifest
+        .variables
+        .iter()
+        .filter(|(name, _)| !vars.contains_key(*name))
+        .map(|(name, spec)| match &spec.description {
+            Some(description) => format!("{} ({})", name, description),
+            None => name.clone(),
+        })
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "Missing template variables: {} (set them with --var KEY=VALUE)",
+            missing.join(", ")
+        ));
+    }
+
+    Ok(vars)
+}
+
+/// Substitute `{{ name }}` placeholders, leaving unknown placeholders untouched
+fn render(text: &str, vars: &BTreeMap<String, String>) -> String {
+    let placeholder = Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap();
+    placeholder
+        .replace_all(text, |caps: &regex::Captures| match vars.get(&caps[1]) {
+            Some(value) => value.clone(),
+            None => caps[0].to_string(),
+        })
+        .to_string()
+}
+
+/// List the template files and the rendered paths they will be written to
+fn plan_files(root: &Path, vars: &BTreeMap<String, String>) -> Result<Vec<PlannedFile>> {
+    let mut files = Vec::new();
+    let walker = WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git");
+
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(root)?;
+        if relative == Path::new(MANIFEST_FILE) {
+            continue;
+        }
+
+        let target = PathBuf::from(render(&relative.to_string_lossy(), vars));
+        if target
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(anyhow!(
+                "Template path '{}' renders outside the project: {}",
+                relative.display(),
+                target.display()
+            ));
+        }
+
+        files.push(PlannedFile {
+            source: entry.path().to_path_buf(),
+            target,
+        });
+    }
+
+    Ok(files)
+}
+
+fn ensure_empty_destination(dest: &Path) -> Result<()> {
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(anyhow!(
+            "Destination {} already exists and is not empty",
+            dest.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Write a single file, rendering text files and copying binary files verbatim
+fn write_file(
+    executor: &Executor,
+    source: &Path,
+    target: &Path,
+    vars: &BTreeMap<String, String>,
+) -> Result<()> {
+    let content = match String::from_utf8(fs::read(source)?) {
+        Ok(text) => render(&text, vars).into_bytes(),
+        Err(binary) => binary.into_bytes(),
+    };
+    executor.check_content(target, &content)?;
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(target, content)?;
+    fs::set_permissions(target, fs::metadata(source)?.permissions())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_substitutes_known_variables() {
+        let vars = vars(&[("project_name", "demo")]);
+        assert_eq!(render("name = \"{{ project_name }}\"", &vars), "name = \"demo\"");
+        assert_eq!(render("{{project_name}}/{{ other }}", &vars), "demo/{{ other }}");
+    }
+
+    #[test]
+    fn test_allowlist_and_names() {
+        let settings = ScaffoldConfig::default();
+        assert!(is_allowed_source("https://github.com/jdfalk/template-rust", &settings));
+        assert!(!is_allowed_source("https://github.com/someone/template", &settings));
+        assert!(!is_allowed_source("https://github.com/jdfalk-evil/template", &settings));
+        assert!(!is_allowed_source("https://github.com@evil.com/jdfalk/template", &settings));
+        assert!(!is_allowed_source("http://github.com/jdfalk/template", &settings));
+        let settings = ScaffoldConfig {
+            allowed_sources: vec![
+                "https://github.com/org/templates".to_string(),
+                "git@github.com:org".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert!(is_allowed_source("https://github.com/org/templates.git", &settings));
+        assert!(!is_allowed_source("https://github.com/org/templates-evil", &settings));
+        assert!(is_allowed_source("git@github.com:org/rust.git", &settings));
+        assert!(!is_allowed_source("git@github.com:org-evil/rust.git", &settings));
+        assert_eq!(template_name("https://github.com/jdfalk/template-rust.git"), "template-rust");
+        assert_eq!(template_name("templates/go-service/"), "go-service");
+    }
+
+    #[test]
+    fn test_plan_files_renders_paths_and_skips_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/{{ project_name }}")).unwrap();
+        fs::write(dir.path().join("src/{{ project_name }}/lib.rs"), "").unwrap();
+        fs::write(dir.path().join(MANIFEST_FILE), "").unwrap();
+
+        let files = plan_files(dir.path(), &vars(&[("project_name", "demo")])).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].target, PathBuf::from("src/demo/lib.rs"));
+
+        let escape = plan_files(dir.path(), &vars(&[("project_name", "..")]));
+        assert!(escape.is_err());
+    }
+
+    #[test]
+    fn test_missing_variables_are_reported() {
+        let manifest: TemplateManifest =
+            toml::from_str("[variables.owner]\ndescription = \"Code owner\"\n").unwrap();
+        let err = resolve_variables(&manifest, Path::new("demo"), Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("owner (Code owner)"));
+
+        let vars = resolve_variables(
+            &manifest,
+            Path::new("demo"),
+            vec![parse_var("owner=me").unwrap()],
+        )
+        .unwrap();
+        assert_eq!(vars["project_name"], "demo");
+        assert_eq!(vars["owner"], "me");
+    }
+}
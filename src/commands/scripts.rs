@@ -0,0 +1,452 @@
+// file: src/commands/scripts.rs
+// version: 1.0.1
+// guid: 8d41c6f2-5a3e-4b97-b0d8-2e7f93a1c654
+
+//! `scripts` command: lint and format the shell scripts of a workspace
+//!
+//! `scripts check` runs shellcheck over the scripts and reports its comments as diagnostics
+//! (see [`crate::parsers::shellcheck`]) as text, JSON or SARIF; `--fail-on` sets the lowest
+//! severity that fails the command. `scripts fmt` runs shfmt and lists the scripts it would
+//! reformat, or reformats them with `--write`.
+//!
+//! Scripts are files named `*.sh`, `*.bash`, `*.dash` or `*.ksh`, and files without an
+//! extension whose `#!` line runs sh, bash, dash or ksh. Directories are walked honoring
+//! `.gitignore`. With `--changed-only` only the scripts that differ from `--base` (default
+//! `HEAD`) or are untracked are taken, so a large repository checks just what a change touches.
+
+use super::export::Effect;
+use crate::commands::diagnostics::describe;
+use crate::executor::Executor;
+use crate::guardrails::{self, Estimate};
+use crate::parsers::{self, Diagnostic, Severity, Tool};
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use ignore::WalkBuilder;
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+
+/// Extensions of shell scripts
+const EXTENSIONS: &[&str] = &["sh", "bash", "dash", "ksh"];
+
+/// Shells whose scripts shellcheck and shfmt understand
+const SHELLS: &[&str] = &["sh", "bash", "dash", "ksh"];
+
+/// Scripts passed to one run of a tool, to stay below the argument length limit
+const CHUNK: usize = 200;
+
+/// Build the scripts command
+pub fn build_command() -> Command {
+    Command::new("scripts")
+        .about("Lint shell scripts with shellcheck and format them with shfmt")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("check")
+                .add(Effect::ReadOnly)
+                .about("Run shellcheck and report its findings as diagnostics")
+                .arg(paths_arg())
+                .arg(changed_only_arg())
+                .arg(base_arg())
+                .arg(
+                    Arg::new("severity")
+                        .long("severity")
+                        .short('S')
+                        .value_parser(["error", "warning", "info", "style"])
+                        .default_value("style")
+                        .help("Lowest severity shellcheck reports"),
+                )
+                .arg(
+                    Arg::new("fail-on")
+                        .long("fail-on")
+                        .value_parser(["error", "warning", "info", "none"])
+                        .default_value("warning")
+                        .help(
+                            "Lowest severity that fails the command; style findings count as info",
+                        ),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["text", "json", "sarif"])
+                        .default_value("text")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("List the scripts shfmt would reformat, or reformat them")
+                .arg(paths_arg())
+                .arg(changed_only_arg())
+                .arg(base_arg())
+                .arg(
+                    Arg::new("write")
+                        .long("write")
+                        .short('w')
+                        .action(ArgAction::SetTrue)
+                        .help("Reformat the scripts instead of failing when they need it"),
+                )
+                .arg(
+                    Arg::new("diff")
+                        .long("diff")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("write")
+                        .help("Show the changes shfmt would make"),
+                )
+                .arg(
+                    Arg::new("indent")
+                        .long("indent")
+                        .short('i')
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u8))
+                        .help(
+                            "Indent with N spaces, 0 for tabs (default: .editorconfig, then tabs)",
+                        ),
+                ),
+        )
+}
+
+fn paths_arg() -> Arg {
+    Arg::new("paths")
+        .value_name("PATH")
+        .num_args(1..)
+        .default_value(".")
+        .help("Scripts and directories to search for scripts")
+}
+
+fn changed_only_arg() -> Arg {
+    Arg::new("changed-only")
+        .long("changed-only")
+        .action(ArgAction::SetTrue)
+        .help("Only take scripts that differ from --base or are untracked")
+}
+
+fn base_arg() -> Arg {
+    Arg::new("base")
+        .long("base")
+        .value_name("REF")
+        .default_value("HEAD")
+        .requires("changed-only")
+        .help("What --changed-only compares with, e.g. origin/main")
+}
+
+/// Execute the scripts command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("check", sub)) => check(sub, executor).await,
+        Some(("fmt", sub)) => format(sub, executor).await,
+        _ => Err(anyhow!("Unknown scripts subcommand")),
+    }
+}
+
+async fn check(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let scripts = select(matches, executor).await?;
+    let severity = matches.get_one::<String>("severity").unwrap();
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for chunk in scripts.chunks(CHUNK) {
+        let mut args = vec!["--severity".to_string(), severity.clone()];
+        args.extend_from_slice(chunk);
+        let (program, args) = Tool::Shellcheck.invocation(&args);
+        let output = executor.execute_secure_capture(program, &args).await?;
+        let found = Tool::Shellcheck.parse(&output.stdout, &output.stderr);
+        // shellcheck exits with 1 when it has comments and above that when it cannot run
+        if found.is_empty() && output.exit_code.is_some_and(|code| code > 1) {
+            bail!("shellcheck failed: {}", output.stderr.trim());
+        }
+        diagnostics.extend(found);
+    }
+
+    match matches.get_one::<String>("format").map(String::as_str) {
+        Some("json") => {
+            let report = json!({
+                "tool": Tool::Shellcheck,
+                "scripts": scripts,
+                "diagnostics": diagnostics,
+                "summary": parsers::summary(&diagnostics),
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Some("sarif") => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&parsers::sarif(&diagnostics))?
+            );
+        }
+        _ => {
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+            eprintln!(
+                "{} script(s) checked: {}",
+                scripts.len(),
+                describe(&diagnostics)
+            );
+        }
+    }
+
+    let fail_on = match matches.get_one::<String>("fail-on").map(String::as_str) {
+        Some("error") => Severity::Error,
+        Some("warning") => Severity::Warning,
+        Some("info") => Severity::Info,
+        _ => return Ok(()),
+    };
+    let failing = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity <= fail_on)
+        .count();
+    if failing > 0 {
+        bail!(
+            "shellcheck reported {} finding(s) of severity {} or higher",
+            failing,
+            fail_on.as_str()
+        );
+    }
+    Ok(())
+}
+
+async fn format(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let scripts = select(matches, executor).await?;
+    let write = matches.get_flag("write");
+    let mut options = Vec::new();
+    if let Some(indent) = matches.get_one::<u8>("indent") {
+        options.extend(["-i".to_string(), indent.to_string()]);
+    }
+    if write {
+        guardrails::check(
+            executor.config(),
+            "scripts fmt --write",
+            &Estimate::of_paths(&scripts, false),
+            false,
+        )?;
+    }
+
+    let mut unformatted = Vec::new();
+    for chunk in scripts.chunks(CHUNK) {
+        let mut args = vec!["-l".to_string()];
+        if write {
+            args.push("-w".to_string());
+        }
+        args.extend_from_slice(&options);
+        args.extend_from_slice(chunk);
+        let output = executor.execute_secure_capture("shfmt", &args).await?;
+        if !output.success() && !output.stderr.trim().is_empty() {
+            bail!("shfmt failed: {}", output.stderr.trim());
+        }
+        unformatted.extend(output.stdout.lines().map(str::to_string));
+    }
+
+    if write {
+        for script in &unformatted {
+            println!("Formatted {}", script);
+        }
+        info!(
+            "shfmt reformatted {} of {} script(s)",
+            unformatted.len(),
+            scripts.len()
+        );
+        return Ok(());
+    }
+    for script in &unformatted {
+        println!("{}", script);
+    }
+    if unformatted.is_empty() {
+        eprintln!("{} script(s) formatted", scripts.len());
+        return Ok(());
+    }
+    if matches.get_flag("diff") {
+        let mut args = vec!["-d".to_string()];
+        args.extend(options);
+        args.extend(unformatted.iter().cloned());
+        let output = executor.execute_secure_capture("shfmt", &args).await?;
+        print!("{}", output.stdout);
+    }
+    bail!(
+        "{} of {} script(s) need formatting; `scripts fmt --write` reformats them",
+        unformatted.len(),
+        scripts.len()
+    );
+}
+
+/// The scripts under the given paths, or those of them git reports as changed, in a stable
+/// order and relative to the working directory where they were given relative
+async fn select(matches: &ArgMatches, executor: &Executor) -> Result<Vec<String>> {
+    let roots: Vec<PathBuf> = matches
+        .get_many::<String>("paths")
+        .unwrap_or_default()
+        .map(PathBuf::from)
+        .collect();
+    for root in &roots {
+        executor.check_path(root)?;
+    }
+
+    let scripts: BTreeSet<PathBuf> = if matches.get_flag("changed-only") {
+        let base = matches.get_one::<String>("base").unwrap();
+        changed_files(executor, base)
+            .await?
+            .into_iter()
+            .filter(|file| {
+                roots
+                    .iter()
+                    .any(|root| normalize(file).starts_with(normalize(root)))
+            })
+            .filter(|file| file.is_file() && is_script(file))
+            .collect()
+    } else {
+        let mut scripts = BTreeSet::new();
+        for root in &roots {
+            if root.is_file() {
+                // A script named explicitly is taken whatever it is called
+                scripts.insert(normalize(root));
+            } else if root.is_dir() {
+                scripts.extend(walk(root)?);
+            } else {
+                bail!("{} does not exist", root.display());
+            }
+        }
+        scripts
+    };
+    if scripts.is_empty() {
+        eprintln!("No shell scripts found");
+    }
+    Ok(scripts
+        .into_iter()
+        .map(|script| script.display().to_string())
+        .collect())
+}
+
+/// The scripts under `root`, skipping ignored files
+fn walk(root: &Path) -> Result<Vec<PathBuf>> {
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    let mut scripts = Vec::new();
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_some_and(|t| t.is_file()) && is_script(entry.path()) {
+            scripts.push(normalize(entry.path()));
+        }
+    }
+    Ok(scripts)
+}
+
+/// Files that differ from `base` in the working tree, and untracked files that are not
+/// ignored, relative to the working directory
+async fn changed_files(executor: &Executor, base: &str) -> Result<Vec<PathBuf>> {
+    if base.starts_with('-') {
+        bail!("Invalid git reference '{}'", base);
+    }
+    let diff = executor
+        .execute_secure_capture(
+            "git",
+            &[
+                "diff",
+                "--name-only",
+                "-z",
+                "--relative",
+                "--diff-filter=d",
+                base,
+                "--",
+            ],
+        )
+        .await?;
+    if !diff.success() {
+        bail!(
+            "--changed-only needs a git repository: {}",
+            diff.stderr.trim()
+        );
+    }
+    let untracked = executor
+        .execute_secure_capture("git", &["ls-files", "-z", "--others", "--exclude-standard"])
+        .await?;
+    if !untracked.success() {
+        bail!("Cannot list untracked files: {}", untracked.stderr.trim());
+    }
+    Ok(diff
+        .stdout
+        .split('\0')
+        .chain(untracked.stdout.split('\0'))
+        .filter(|name| !name.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Whether `path` is a script shellcheck and shfmt understand, by its extension or, without
+/// one, by its `#!` line
+fn is_script(path: &Path) -> bool {
+    if let Some(extension) = path.extension() {
+        return EXTENSIONS.iter().any(|known| extension == *known);
+    }
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    let mut line = String::new();
+    // Binary files fail as invalid UTF-8
+    BufReader::new(file.take(256))
+        .read_line(&mut line)
+        .is_ok_and(|_| shebang_shell(&line).is_some())
+}
+
+/// The shell a `#!` line runs, looking through `env`
+fn shebang_shell(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    SHELLS.contains(&program).then_some(program)
+}
+
+/// `path` without `.` components, so `./bin/x.sh` and `bin/x.sh` compare equal
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scripts_are_found_by_extension_and_shebang() {
+        assert_eq!(shebang_shell("#!/bin/sh\n"), Some("sh"));
+        assert_eq!(shebang_shell("#! /usr/bin/env -S bash -e\n"), Some("bash"));
+        assert_eq!(shebang_shell("#!/usr/bin/env python3\n"), None);
+        assert_eq!(shebang_shell("echo hi\n"), None);
+
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".github/scripts")).unwrap();
+        fs::create_dir_all(root.join("vendor")).unwrap();
+        fs::write(root.join(".gitignore"), "vendor/\n").unwrap();
+        fs::write(root.join(".github/scripts/release.sh"), "echo release\n").unwrap();
+        fs::write(root.join("configure"), "#!/bin/bash\nset -e\n").unwrap();
+        fs::write(root.join("tool.py"), "#!/bin/sh\n").unwrap();
+        fs::write(root.join("data"), [0xff, 0xfe, 0x00]).unwrap();
+        fs::write(root.join("vendor/lib.sh"), "echo vendored\n").unwrap();
+        // The ignore crate only reads .gitignore inside a git repository
+        fs::create_dir(root.join(".git")).unwrap();
+
+        let found: BTreeSet<PathBuf> = walk(root)
+            .unwrap()
+            .into_iter()
+            .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        let expected: BTreeSet<PathBuf> = [".github/scripts/release.sh", "configure"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(found, expected);
+        assert_eq!(
+            normalize(Path::new("./bin/./x.sh")),
+            PathBuf::from("bin/x.sh")
+        );
+        assert!(normalize(Path::new("bin/x.sh")).starts_with(normalize(Path::new("."))));
+    }
+}
@@ -1,8 +1,11 @@
 // file: src/commands/sed.rs
-// version: 1.0.0
+// version: 1.5.1
 // guid: 8a1b2c3d-4e5f-6a7b-8c9d-0e1f2a3b4c5d
 
+use super::export::Effect;
+use super::file_diff;
 use crate::executor::Executor;
+use crate::guardrails::{self, Estimate};
 use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches, Command};
 use regex::Regex;
@@ -13,6 +16,7 @@ use std::path::Path;
 /// Build the sed command with comprehensive options
 pub fn build_command() -> Command {
     Command::new("sed")
+        .add(Effect::Destructive)
         .about("Stream editor for filtering and transforming text (Rust implementation)")
         .arg(Arg::new("expression")
             .help("Sed expression/script")
@@ -59,7 +63,7 @@ pub fn build_command() -> Command {
 }
 
 /// Execute sed commands with Rust-native implementation
-pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
     let expressions: Vec<_> = matches.get_many::<String>("expression")
         .map(|vals| vals.cloned().collect())
         .unwrap_or_default();
@@ -87,9 +91,13 @@ pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
     }
 
     if files.is_empty() {
-        // Read from stdin
+        // Read from stdin, or the file given with --stdin-file
+        let reader: Box<dyn BufRead> = match executor.stdin() {
+            Some(input) => input.reader()?,
+            None => Box::new(io::stdin().lock()),
+        };
         process_input(
-            Box::new(io::stdin().lock()),
+            reader,
             &sed_operations,
             None,
             quiet,
@@ -97,6 +105,18 @@ pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
             unbuffered,
         )?;
     } else {
+        for file_path in &files {
+            executor.check_path(file_path)?;
+        }
+        let operation = if in_place { "sed --in-place" } else { "sed" };
+        guardrails::check(
+            executor.config(),
+            operation,
+            &Estimate::of_paths(&files, false),
+            false,
+        )?;
+        let dry_run = executor.config().safety.dry_run;
+
         // Process files
         for file_path in &files {
             let path = Path::new(file_path);
@@ -109,12 +129,6 @@ pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
             let reader = Box::new(BufReader::new(file));
 
             if in_place {
-                // Create backup if requested
-                if let Some(suffix) = backup_suffix {
-                    let backup_path = format!("{}{}", file_path, suffix);
-                    fs::copy(path, &backup_path)?;
-                }
-
                 // Process to memory first, then write back
                 let mut output = Vec::new();
                 {
@@ -129,6 +143,18 @@ pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
                     )?;
                 }
 
+                executor.check_content(path, &output)?;
+                if dry_run {
+                    println!("DRY RUN: Would edit {} in place", file_path);
+                    file_diff::print_preview(path, &output);
+                    continue;
+                }
+
+                // Create backup if requested
+                if let Some(suffix) = backup_suffix {
+                    let backup_path = format!("{}{}", file_path, suffix);
+                    fs::copy(path, &backup_path)?;
+                }
                 fs::write(path, output)?;
             } else {
                 process_input(
@@ -357,7 +383,7 @@ fn process_input_to_writer<W: Write>(
                             if count == n {
                                 replacement.clone()
                             } else {
-                                return format!("{}", &line_str); // This is wrong, need to fix
+                                line_str.to_string() // This is wrong, need to fix
                             }
                         }).to_string();
                     } else {
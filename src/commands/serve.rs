@@ -0,0 +1,149 @@
+// file: src/commands/serve.rs
+// version: 1.2.0
+// guid: 6d2a8f41-c7e3-4b95-a1d0-93e5b8c27f16
+
+//! `serve` command: run commands for an agent in one long-lived process (see
+//! [`crate::serve`]), for editor extensions over local HTTP (see [`crate::http`]), or for
+//! remote clients over gRPC (see `crate::grpc`)
+
+use crate::executor::Executor;
+use crate::ide::CommandInfo;
+use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use std::future::Future;
+
+/// Variable holding the bearer token of `serve grpc` unless `--token-env` names another
+pub const TOKEN_ENV: &str = "SAFE_AI_UTIL_GRPC_TOKEN";
+
+/// Build the serve command
+pub fn build_command() -> Command {
+    Command::new("serve")
+        .about("Run commands sent as JSON lines without starting a process for each")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("stdio")
+                .about("Read requests such as {\"cmd\":\"git\",\"args\":[\"status\"]} from stdin")
+                .long_about(
+                    "Read one JSON request per line from stdin, such as \
+                     {\"id\":1,\"cmd\":\"git\",\"args\":[\"status\"]}, and answer with JSON \
+                     lines on stdout: 'output' events with the lines the command writes, then \
+                     an 'exit' event with its exit code. {\"cmd\":\"shutdown\"} or the end of \
+                     input ends the session. The global options given before 'serve' apply to \
+                     every request.",
+                ),
+        )
+        .subcommand(
+            Command::new("http")
+                .about("Serve a local REST API with server-sent events for editor extensions")
+                .long_about(
+                    "Serve a REST API on a loopback address: POST /v1/runs starts a command line \
+                     such as [\"git\", \"status\"], GET /v1/runs/{id}/events streams its output \
+                     as server-sent events, DELETE /v1/runs/{id} cancels it and GET /v1/history \
+                     lists recorded runs. Every request needs the bearer token written to the \
+                     token file at startup. When started by systemd socket activation without \
+                     --bind, the passed socket is served.",
+                )
+                .arg(
+                    Arg::new("bind")
+                        .long("bind")
+                        .value_name("ADDRESS")
+                        .help("Loopback address to listen on (default: the systemd socket, or 127.0.0.1:8787); port 0 picks a free port"),
+                )
+                .arg(
+                    Arg::new("token-file")
+                        .long("token-file")
+                        .value_name("FILE")
+                        .help("File receiving the bearer token (default: <state dir>/http/token)"),
+                ),
+        )
+        .subcommand(
+            Command::new("grpc")
+                .about("Serve the ExecutionService gRPC API (requires the grpc feature)")
+                .long_about(
+                    "Serve the ExecutionService API of proto/safe_ai_util/v1/execution.proto so \
+                     CI runners and containers can submit commands, stream their output, cancel \
+                     them and query the history. Every command goes through this server's \
+                     validation and policy. Listening on anything but a loopback address \
+                     requires a bearer token. Only available when built with --features grpc.",
+                )
+                .arg(
+                    Arg::new("listen")
+                        .long("listen")
+                        .value_name("ADDRESS")
+                        .default_value("127.0.0.1:50051")
+                        .help("Address to listen on; port 0 picks a free port"),
+                )
+                .arg(
+                    Arg::new("token-env")
+                        .long("token-env")
+                        .value_name("VAR")
+                        .default_value(TOKEN_ENV)
+                        .help("Variable holding the bearer token clients must send"),
+                ),
+        )
+}
+
+/// Execute `serve http`; `commands` are the commands runs may start and `forward_args`
+/// the global options they are run with
+pub async fn execute_http(
+    matches: &ArgMatches,
+    executor: Executor,
+    commands: Vec<CommandInfo>,
+    forward_args: Vec<String>,
+) -> Result<()> {
+    let bind = match matches.get_one::<String>("bind") {
+        Some(bind) => Some(
+            bind.parse()
+                .map_err(|_| anyhow!("Invalid bind address '{}'", bind))?,
+        ),
+        None => None,
+    };
+    let options = crate::http::Options {
+        bind,
+        token_file: matches
+            .get_one::<String>("token-file")
+            .map(PathBuf::from)
+            .unwrap_or_else(crate::http::default_token_file),
+        commands,
+        forward_args,
+    };
+    Ok(crate::http::serve(executor, options).await?)
+}
+
+/// Execute `serve grpc`, which runs commands itself rather than through a dispatcher
+pub async fn execute_grpc(matches: &ArgMatches, executor: Executor) -> Result<()> {
+    let listen = matches.get_one::<String>("listen").unwrap();
+    let token_env = matches.get_one::<String>("token-env").unwrap();
+    #[cfg(feature = "grpc")]
+    {
+        let options = crate::grpc::Options {
+            listen: listen
+                .parse()
+                .map_err(|_| anyhow!("Invalid listen address '{}'", listen))?,
+            token: std::env::var(token_env).ok().filter(|token| !token.is_empty()),
+        };
+        Ok(crate::grpc::serve(executor, options).await?)
+    }
+    #[cfg(not(feature = "grpc"))]
+    {
+        let _ = (listen, token_env, executor);
+        Err(anyhow!(
+            "serve grpc is not available: this binary was built without the grpc feature"
+        ))
+    }
+}
+
+/// Execute serve subcommands; `dispatch` runs one command line in this process
+pub async fn execute<D, F>(matches: &ArgMatches, dispatch: D) -> Result<()>
+where
+    D: FnMut(Vec<String>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    match matches.subcommand() {
+        Some(("stdio", _)) => Ok(crate::serve::stdio(dispatch).await?),
+        Some(("http", _)) => Err(anyhow!("serve http is started with execute_http")),
+        Some(("grpc", _)) => Err(anyhow!("serve grpc is started with execute_grpc")),
+        _ => Err(anyhow!("Unknown serve subcommand")),
+    }
+}
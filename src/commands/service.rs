@@ -0,0 +1,419 @@
+// file: src/commands/service.rs
+// version: 1.0.1
+// guid: 8c2f6e41-9d37-4b05-a6e8-3f1b7d94c520
+
+//! `service` command: check, start, stop and restart the user's development services
+//!
+//! On Linux the services are systemd user units, managed with `systemctl --user` and read with
+//! `journalctl --user`; on macOS they are launchd agents in the user's `gui/<uid>` domain,
+//! managed with `launchctl`. Only the services listed in `policy.services` can be named, so an
+//! agent can bounce its dev server but never touch a unit it was not given. The same list
+//! applies to systemctl, journalctl and launchctl run any other way, and system units are out
+//! of reach altogether.
+
+use super::export::Effect;
+use crate::executor::Executor;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+
+/// Properties `systemctl show` reports for a unit
+const PROPERTIES: &str =
+    "--property=Id,Description,LoadState,ActiveState,SubState,MainPID,ActiveEnterTimestamp";
+
+/// The service manager of this system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Manager {
+    Systemd,
+    Launchd,
+}
+
+impl Manager {
+    fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::Launchd
+        } else {
+            Self::Systemd
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Self::Systemd => "systemctl",
+            Self::Launchd => "launchctl",
+        }
+    }
+
+    /// Arguments that start, stop or restart `service`
+    fn action_args(self, action: &str, service: &str) -> Vec<String> {
+        let args: Vec<&str> = match (self, action) {
+            (Self::Systemd, _) => vec!["--user", action],
+            (Self::Launchd, "start") => vec!["kickstart"],
+            (Self::Launchd, "stop") => vec!["kill", "SIGTERM"],
+            (Self::Launchd, _) => vec!["kickstart", "-k"],
+        };
+        let target = match self {
+            Self::Systemd => service.to_string(),
+            Self::Launchd => launchd_target(service),
+        };
+        args.into_iter()
+            .map(str::to_string)
+            .chain([target])
+            .collect()
+    }
+}
+
+/// State of a service
+#[derive(Debug, Default, Serialize, PartialEq)]
+struct Status {
+    service: String,
+    description: String,
+    /// `active`, `inactive`, `failed`, or another systemd active state
+    state: String,
+    /// What the manager reports in more detail, e.g. `running` or `exited`
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    since: Option<String>,
+}
+
+/// Build the service command
+pub fn build_command() -> Command {
+    Command::new("service")
+        .about("Check, start, stop and restart user services listed in policy.services")
+        .long_about(
+            "Manage the user's development services: systemd user units on Linux (systemctl \
+             --user, journalctl --user) and launchd agents on macOS. Only services listed in \
+             policy.services can be named; system units cannot be managed at all.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("status")
+                .add(Effect::ReadOnly)
+                .about("Show whether a service is running")
+                .arg(service())
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("start")
+                .about("Start a service")
+                .arg(service()),
+        )
+        .subcommand(
+            Command::new("stop")
+                .add(Effect::Destructive)
+                .about("Stop a service")
+                .arg(service()),
+        )
+        .subcommand(
+            Command::new("restart")
+                .add(Effect::Destructive)
+                .about("Restart a service, starting it if it is stopped")
+                .arg(service()),
+        )
+        .subcommand(
+            Command::new("logs")
+                .add(Effect::ReadOnly)
+                .about("Show the output of a service")
+                .arg(service())
+                .arg(
+                    Arg::new("lines")
+                        .long("lines")
+                        .short('n')
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32))
+                        .default_value("100")
+                        .help("Show the last N lines"),
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .value_name("TIME")
+                        .help("Only lines newer than TIME, e.g. \"10 min ago\" (systemd only)"),
+                )
+                .arg(
+                    Arg::new("follow")
+                        .long("follow")
+                        .short('F')
+                        .action(ArgAction::SetTrue)
+                        .help("Keep printing new lines until interrupted (systemd only)"),
+                ),
+        )
+}
+
+fn service() -> Arg {
+    Arg::new("service")
+        .value_name("SERVICE")
+        .required(true)
+        .help("Unit name, e.g. api-dev or api-dev.service, or launchd label")
+}
+
+/// Execute the service command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let manager = Manager::current();
+    let (action, sub_matches) = matches.subcommand().expect("subcommand required");
+    let service = sub_matches.get_one::<String>("service").unwrap();
+    check_name(service)?;
+
+    match action {
+        "status" => {
+            let status = status(executor, manager, service).await?;
+            if sub_matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                print_status(&status);
+            }
+            Ok(())
+        }
+        "logs" => logs(sub_matches, executor, manager, service).await,
+        _ => {
+            executor
+                .execute_secure(manager.program(), &manager.action_args(action, service))
+                .await?;
+            if executor.config().safety.dry_run {
+                return Ok(());
+            }
+            print_status(&status(executor, manager, service).await?);
+            Ok(())
+        }
+    }
+}
+
+/// Refuse names that systemctl would read as an option or expand as a pattern
+fn check_name(service: &str) -> Result<()> {
+    let valid = service.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && service
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.:@".contains(c));
+    if !valid {
+        bail!("Invalid service name '{}'", service);
+    }
+    Ok(())
+}
+
+fn print_status(status: &Status) {
+    if status.description.is_empty() {
+        println!("{}", status.service);
+    } else {
+        println!("{} - {}", status.service, status.description);
+    }
+    print!("  State: {} ({})", status.state, status.detail);
+    match &status.since {
+        Some(since) => println!(" since {}", since),
+        None => println!(),
+    }
+    if let Some(pid) = status.pid {
+        println!("  PID: {}", pid);
+    }
+}
+
+async fn status(executor: &Executor, manager: Manager, service: &str) -> Result<Status> {
+    let args = match manager {
+        Manager::Systemd => vec![
+            "--user".to_string(),
+            "show".to_string(),
+            service.to_string(),
+            PROPERTIES.to_string(),
+        ],
+        Manager::Launchd => vec!["print".to_string(), launchd_target(service)],
+    };
+    let output = executor
+        .execute_secure_capture(manager.program(), &args)
+        .await?;
+    if !output.success() {
+        bail!(
+            "Cannot read the status of {}: {}",
+            service,
+            output.stderr.trim()
+        );
+    }
+    let status = match manager {
+        Manager::Systemd => parse_systemd_show(&output.stdout),
+        Manager::Launchd => parse_launchd_print(service, &output.stdout),
+    };
+    status.ok_or_else(|| anyhow!("Service {} not found", service))
+}
+
+async fn logs(
+    matches: &ArgMatches,
+    executor: &Executor,
+    manager: Manager,
+    service: &str,
+) -> Result<()> {
+    let lines = *matches.get_one::<u32>("lines").unwrap();
+    if manager == Manager::Systemd {
+        let mut args = vec![
+            "--user".to_string(),
+            "--unit".to_string(),
+            service.to_string(),
+            "--no-pager".to_string(),
+            format!("--lines={}", lines),
+        ];
+        if let Some(since) = matches.get_one::<String>("since") {
+            args.push(format!("--since={}", since));
+        }
+        if matches.get_flag("follow") {
+            args.push("--follow".to_string());
+        }
+        return executor.execute_secure("journalctl", &args).await;
+    }
+
+    if matches.get_flag("follow") || matches.contains_id("since") {
+        bail!(
+            "--follow and --since need journald; launchd only keeps the files a service writes to"
+        );
+    }
+    let output = executor
+        .execute_secure_capture("launchctl", &["print".to_string(), launchd_target(service)])
+        .await?;
+    if !output.success() {
+        bail!("Cannot read {}: {}", service, output.stderr.trim());
+    }
+    let paths = launchd_log_paths(&output.stdout);
+    if paths.is_empty() {
+        bail!(
+            "{} writes its output nowhere; set StandardOutPath in its plist",
+            service
+        );
+    }
+    for path in paths {
+        let text = fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path))?;
+        println!("==> {} <==", path);
+        let all: Vec<&str> = text.lines().collect();
+        for line in &all[all.len().saturating_sub(lines as usize)..] {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// The launchctl target of the user agent `label`
+fn launchd_target(label: &str) -> String {
+    #[cfg(unix)]
+    // SAFETY: plain libc query
+    let uid = unsafe { libc::getuid() };
+    #[cfg(not(unix))]
+    let uid = 0;
+    format!("gui/{}/{}", uid, label)
+}
+
+/// `systemctl show`: `Key=value` per line; `None` for units systemd does not know
+fn parse_systemd_show(output: &str) -> Option<Status> {
+    let property = |name: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+            .filter(|value| !value.is_empty())
+    };
+    if property("LoadState") == Some("not-found") {
+        return None;
+    }
+    Some(Status {
+        service: property("Id")?.to_string(),
+        description: property("Description").unwrap_or_default().to_string(),
+        state: property("ActiveState").unwrap_or("unknown").to_string(),
+        detail: property("SubState").unwrap_or("unknown").to_string(),
+        pid: property("MainPID")
+            .and_then(|pid| pid.parse().ok())
+            .filter(|pid| *pid != 0),
+        since: property("ActiveEnterTimestamp").map(str::to_string),
+    })
+}
+
+/// Top-level `key = value` lines of `launchctl print`, which are indented by one tab
+fn launchd_properties(output: &str) -> impl Iterator<Item = (&str, &str)> {
+    output.lines().filter_map(|line| {
+        let line = line.strip_prefix('\t')?;
+        if line.starts_with('\t') {
+            return None;
+        }
+        line.split_once(" = ")
+    })
+}
+
+/// `launchctl print gui/<uid>/<label>`: running agents have a `pid`, and agents that exited
+/// with an error are `failed`
+fn parse_launchd_print(label: &str, output: &str) -> Option<Status> {
+    let mut status = Status {
+        service: label.to_string(),
+        ..Default::default()
+    };
+    let mut exit_code = None;
+    for (key, value) in launchd_properties(output) {
+        match key {
+            "program" => status.description = value.to_string(),
+            "state" => status.detail = value.to_string(),
+            "pid" => status.pid = value.parse().ok(),
+            "last exit code" => exit_code = Some(value),
+            _ => {}
+        }
+    }
+    if status.detail.is_empty() {
+        return None;
+    }
+    status.state = if status.detail == "running" {
+        "active"
+    } else if exit_code.is_some_and(|code| code != "0" && !code.starts_with('(')) {
+        "failed"
+    } else {
+        "inactive"
+    }
+    .to_string();
+    Some(status)
+}
+
+/// The files launchd sends an agent's stdout and stderr to
+fn launchd_log_paths(output: &str) -> Vec<String> {
+    let paths: BTreeSet<&str> = launchd_properties(output)
+        .filter(|(key, _)| matches!(*key, "stdout path" | "stderr path"))
+        .map(|(_, path)| path)
+        .filter(|path| *path != "/dev/null")
+        .collect();
+    paths.into_iter().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_service_status() {
+        let show = "Id=api-dev.service\nDescription=API dev server\nLoadState=loaded\nActiveState=active\nSubState=running\nMainPID=4242\nActiveEnterTimestamp=Thu 2024-05-02 10:14:03 UTC\n";
+        let status = parse_systemd_show(show).unwrap();
+        assert_eq!(status.service, "api-dev.service");
+        assert_eq!((status.state.as_str(), status.pid), ("active", Some(4242)));
+
+        let stopped = "Id=worker.service\nDescription=\nLoadState=loaded\nActiveState=failed\nSubState=failed\nMainPID=0\nActiveEnterTimestamp=\n";
+        let status = parse_systemd_show(stopped).unwrap();
+        assert_eq!((status.pid, status.since), (None, None));
+        assert!(parse_systemd_show("Id=nope.service\nLoadState=not-found\n").is_none());
+
+        let print = "gui/501/dev.api = {\n\tactive count = 1\n\tpath = /Users/dev/Library/LaunchAgents/dev.api.plist\n\ttype = LaunchAgent\n\tstate = running\n\n\tprogram = /usr/local/bin/api\n\targuments = {\n\t\t/usr/local/bin/api\n\t}\n\n\tstdout path = /tmp/api.log\n\tstderr path = /tmp/api.log\n\tpid = 812\n\tlast exit code = (never exited)\n}\n";
+        let status = parse_launchd_print("dev.api", print).unwrap();
+        assert_eq!(
+            (status.state.as_str(), status.detail.as_str(), status.pid),
+            ("active", "running", Some(812))
+        );
+        assert_eq!(launchd_log_paths(print), ["/tmp/api.log"]);
+        let crashed = "gui/501/dev.api = {\n\tstate = not running\n\tlast exit code = 1\n}\n";
+        assert_eq!(
+            parse_launchd_print("dev.api", crashed).unwrap().state,
+            "failed"
+        );
+
+        assert!(check_name("worker@1.service").is_ok());
+        assert!(check_name("--all").is_err());
+        assert!(check_name("api-*").is_err());
+    }
+}
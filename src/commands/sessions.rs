@@ -0,0 +1,384 @@
+// file: src/commands/sessions.rs
+// version: 1.1.1
+// guid: fe677083-3de1-40cf-b31f-04530dc238b8
+
+//! `sessions` command: show which other sessions and agents are working alongside this one,
+//! and record and export transcripts of what a session did
+
+use super::export::Effect;
+use crate::activity::{format_age, short_id, SessionRecord, SessionRegistry};
+use crate::batch::CommandSpec;
+use crate::executor::Executor;
+use crate::history::{Filter, History};
+use crate::security::sandbox::canonicalize_lenient;
+use crate::session::{self, SESSION_ID_ENV};
+use crate::transcript::{self, Recording, Transcript, TranscriptCommand};
+use crate::utils;
+use anyhow::{bail, Result};
+use chrono::Utc;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Build the sessions command
+pub fn build_command() -> Command {
+    Command::new("sessions")
+        .about("Show sessions and agents active in this or other workspaces")
+        .visible_alias("session")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("List active sessions, their agents and the files they modified")
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .short('a')
+                        .action(ArgAction::SetTrue)
+                        .help("Include sessions that have been idle for more than 15 minutes"),
+                )
+                .arg(
+                    Arg::new("workspace")
+                        .long("workspace")
+                        .short('w')
+                        .action(ArgAction::SetTrue)
+                        .help("Only sessions sharing this workspace"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("record")
+                .about("Start recording the session, so its transcript includes file diffs")
+                .long_about(
+                    "Saves the state of the git work tree for the session, including uncommitted \
+                     changes; `sessions export` diffs the work tree against it. Recording again \
+                     starts over. Set SAFE_AI_SESSION_ID so later commands belong to the same \
+                     session.",
+                )
+                .arg(session_arg()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export a transcript of the commands a session ran and the files it changed")
+                .arg(session_arg())
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["markdown", "json"])
+                        .default_value("markdown")
+                        .help("Output format"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Write the transcript to FILE instead of stdout"),
+                ),
+        )
+}
+
+fn session_arg() -> Arg {
+    Arg::new("session")
+        .long("session")
+        .value_name("ID")
+        .help("Session ID (default: this session, from SAFE_AI_SESSION_ID)")
+}
+
+/// Execute the sessions command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("list", sub_matches)) => list(sub_matches, executor),
+        Some(("record", sub_matches)) => record(sub_matches, executor).await,
+        Some(("export", sub_matches)) => export(sub_matches, executor).await,
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+fn list(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let now = Utc::now();
+    let current = session::current().session_id();
+    let workspace = executor.activity().workspace();
+    let registry = SessionRegistry::load()?;
+
+    let mut sessions: Vec<&SessionRecord> = registry
+        .sessions
+        .values()
+        .filter(|record| matches.get_flag("all") || record.is_active(now))
+        .filter(|record| !matches.get_flag("workspace") || record.shares_workspace(workspace))
+        .collect();
+    sessions.sort_by_key(|record| std::cmp::Reverse(record.last_seen));
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        let sessions: Vec<_> = sessions
+            .iter()
+            .map(|record| {
+                let mut value = serde_json::to_value(record).unwrap_or_default();
+                value["current"] = json!(record.session_id == current);
+                value["active"] = json!(record.is_active(now));
+                value["same_workspace"] = json!(record.shares_workspace(workspace));
+                value
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+        return Ok(());
+    }
+
+    if sessions.is_empty() {
+        println!("No active sessions");
+        return Ok(());
+    }
+
+    println!(
+        "  {:<10} {:<16} {:>8} {:>9} {:>9}  {:<32} LAST COMMAND",
+        "SESSION", "AGENT", "PID", "LAST SEEN", "MODIFIED", "WORKSPACE"
+    );
+    for record in sessions {
+        let marker = if record.session_id == current {
+            '*'
+        } else if record.shares_workspace(workspace) {
+            '!'
+        } else {
+            ' '
+        };
+        let agent = record
+            .agent
+            .as_deref()
+            .or(record.user.as_deref())
+            .unwrap_or("-");
+        println!(
+            "{} {:<10} {:<16} {:>8} {:>9} {:>9}  {:<32} {}",
+            marker,
+            short_id(&record.session_id),
+            agent,
+            record.pid,
+            format_age(now - record.last_seen),
+            record.modified.len(),
+            record.workspace.display(),
+            record.last_command.as_deref().unwrap_or("-")
+        );
+    }
+    println!();
+    println!("* this session   ! another session in this workspace");
+    Ok(())
+}
+
+async fn record(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let session_id = selected_session(matches);
+    if executor.config().safety.dry_run {
+        println!("DRY RUN: Would start recording session {}", session_id);
+        return Ok(());
+    }
+    let workspace = PathBuf::from(
+        git(executor, None, &["rev-parse", "--show-toplevel"])
+            .await?
+            .trim(),
+    );
+    // A clean tree has no stash; a repository without commits has neither a stash nor HEAD
+    let stash = git(executor, Some(&workspace), &["stash", "create"])
+        .await
+        .unwrap_or_default();
+    let base = match stash.trim() {
+        "" => git(
+            executor,
+            Some(&workspace),
+            &["rev-parse", "--verify", "HEAD"],
+        )
+        .await
+        .ok()
+        .map(|head| head.trim().to_string()),
+        stash => Some(stash.to_string()),
+    };
+    let untracked = git(
+        executor,
+        Some(&workspace),
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+    )
+    .await?
+    .split('\0')
+    .filter(|path| !path.is_empty())
+    .map(str::to_string)
+    .collect();
+
+    let recording = Recording {
+        session_id: session_id.clone(),
+        started_at: Utc::now(),
+        workspace,
+        base,
+        untracked,
+        execution_id: session::current().execution_id().to_string(),
+    };
+    recording.save()?;
+
+    println!(
+        "Recording session {} in {}",
+        session_id,
+        recording.workspace.display()
+    );
+    if recording.base.is_none() {
+        println!("The repository has no commits yet; only new files will be diffed");
+    }
+    if std::env::var(SESSION_ID_ENV).is_err() {
+        println!(
+            "Run later commands with {}={} so they belong to this session",
+            SESSION_ID_ENV, session_id
+        );
+    }
+    Ok(())
+}
+
+async fn export(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let session_id = selected_session(matches);
+    let output = matches.get_one::<String>("output").map(Path::new);
+    if let Some(path) = output {
+        executor.check_path(path)?;
+    }
+    if executor.config().safety.dry_run {
+        println!(
+            "DRY RUN: Would export the transcript of session {}{}",
+            session_id,
+            output.map_or(String::new(), |path| format!(" to {}", path.display()))
+        );
+        return Ok(());
+    }
+    let recording = Recording::load(&session_id)?;
+    // The git commands of recording and exporting are not part of what the session did
+    let own: Vec<&str> = std::iter::once(session::current().execution_id())
+        .chain(recording.as_ref().map(|r| r.execution_id.as_str()))
+        .collect();
+
+    let history = History::open()?;
+    let mut runs = history.list(&Filter {
+        since: recording.as_ref().map(|r| r.started_at),
+        session_id: Some(session_id.clone()),
+        ..Filter::default()
+    })?;
+    runs.retain(|run| !own.contains(&run.execution_id.as_str()));
+    runs.reverse();
+    let commands = runs
+        .into_iter()
+        .map(|run| TranscriptCommand {
+            output: run
+                .output_path
+                .as_ref()
+                .and_then(|path| fs::read(path).ok())
+                .map(|output| String::from_utf8_lossy(&output).to_string()),
+            run,
+        })
+        .collect();
+
+    let changes = match &recording {
+        Some(recording) => Some(changes_since(executor, recording).await?),
+        None => None,
+    };
+    let transcript = Transcript {
+        session_id,
+        workspace: recording.as_ref().map(|r| r.workspace.clone()),
+        recorded_since: recording.as_ref().map(|r| r.started_at),
+        exported_at: Utc::now(),
+        commands,
+        changes,
+    };
+    let content = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("json") => serde_json::to_string_pretty(&transcript)? + "\n",
+        _ => transcript.to_markdown(),
+    };
+
+    match output {
+        Some(path) => {
+            executor.check_content(path, content.as_bytes())?;
+            fs::write(path, &content)?;
+            println!("Transcript written to {}", path.display());
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+/// Files changed in the work tree since `recording` started
+async fn changes_since(
+    executor: &Executor,
+    recording: &Recording,
+) -> Result<Vec<transcript::FileChange>> {
+    let workspace = Some(recording.workspace.as_path());
+    let mut changes = match &recording.base {
+        Some(base) => {
+            let diff = ["diff", "--no-color", "--no-ext-diff", "--no-renames"];
+            let name_status = [&diff[..], &["--name-status", "-z", base]].concat();
+            let patch = [&diff[..], &[base.as_str()]].concat();
+            transcript::parse_changes(
+                &git(executor, workspace, &name_status).await?,
+                &git(executor, workspace, &patch).await?,
+            )
+        }
+        None => Vec::new(),
+    };
+
+    let untracked = git(
+        executor,
+        workspace,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+    )
+    .await?;
+    // The utility's own logs and state are not changes the session made
+    let own_dirs = [
+        std::env::current_dir().unwrap_or_default().join("logs"),
+        utils::state_dir(),
+    ]
+    .map(|dir| canonicalize_lenient(&dir));
+    for path in untracked.split('\0').filter(|path| !path.is_empty()) {
+        let absolute = canonicalize_lenient(&recording.workspace.join(path));
+        if own_dirs.iter().any(|dir| absolute.starts_with(dir)) {
+            continue;
+        }
+        if !recording.untracked.iter().any(|known| known == path) {
+            changes.push(transcript::added_file(&recording.workspace, path));
+        }
+    }
+    for path in &recording.untracked {
+        if !recording.workspace.join(path).exists() {
+            changes.push(transcript::FileChange {
+                path: path.clone(),
+                status: transcript::ChangeStatus::Deleted,
+                diff: String::new(),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+/// Session given with `--session`, or this one
+fn selected_session(matches: &ArgMatches) -> String {
+    matches
+        .get_one::<String>("session")
+        .cloned()
+        .unwrap_or_else(|| session::current().session_id().to_string())
+}
+
+/// Run git in `cwd` and return its output, failing if it does
+async fn git(executor: &Executor, cwd: Option<&Path>, args: &[&str]) -> Result<String> {
+    let spec = CommandSpec {
+        command: "git".to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+        cwd: cwd.map(Path::to_path_buf),
+        env: BTreeMap::new(),
+        stdin: None,
+    };
+    let output = executor.execute_spec_capture_full(&spec).await?;
+    if !output.success() {
+        bail!("git {} failed: {}", args.join(" "), output.stderr.trim());
+    }
+    Ok(output.stdout)
+}
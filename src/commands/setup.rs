@@ -0,0 +1,757 @@
+// file: src/commands/setup.rs
+// version: 1.1.1
+// guid: 7c2e9a41-5d8b-4f36-b1e0-3a9f6d2c8e57
+
+//! `setup` command: first-run wizard writing an initial configuration
+//!
+//! The wizard detects the installed tools, proposes a safety profile (a strict one for agents
+//! or a relaxed one for development), asks which branches to protect and which directories
+//! commands may touch, then writes a configuration file explaining each setting. It also
+//! installs shell completions and git hooks: a pre-push hook guarding the protected branches
+//! even from plain `git push`, and a pre-commit hook running `.pre-commit-config.yaml` through
+//! the executor when the project has one.
+//!
+//! Every question comes with a proposed answer, which `--yes` accepts without asking.
+
+use crate::commands::completions;
+use crate::commands::config::{target_path, with_target};
+use crate::commands::pre_commit;
+use crate::config::validate_toml;
+use crate::executor::Executor;
+use crate::tools;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command as Process, Stdio};
+
+/// Development tools looked for on PATH; the strict profile allows only the ones found
+const TOOLS: &[&str] = &[
+    "git", "cargo", "rustc", "rustup", "go", "node", "npm", "npx", "pnpm", "yarn", "python3",
+    "python", "uv", "pytest", "ruff", "buf", "protoc", "prettier", "eslint", "make", "mise",
+];
+
+/// Read-only utilities the strict profile allows besides the tools, for `uutils` and `pipe`
+const READ_ONLY_UTILITIES: &[&str] = &[
+    "cat", "ls", "find", "grep", "head", "tail", "wc", "sort", "uniq", "cut", "diff",
+];
+
+/// First line after the shebang of the hooks setup writes; other hooks are left alone
+pub(crate) const HOOK_MARKER: &str = "# Installed by safe-ai-util setup";
+
+/// Safety profile written by the wizard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Profile {
+    /// Strict, for AI agents: allowlisted tools, a path jail and read-only protected branches
+    Agent,
+    /// Relaxed, for people: the built-in dev profile, with protected branches that cannot be
+    /// rewritten or deleted
+    Dev,
+}
+
+impl Profile {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "agent" => Some(Profile::Agent),
+            "dev" => Some(Profile::Dev),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Profile::Agent => "agent",
+            Profile::Dev => "dev",
+        }
+    }
+}
+
+/// What the configuration is written from
+#[derive(Debug, Clone)]
+struct Answers {
+    profile: Profile,
+    /// Names of the tools found
+    tools: Vec<String>,
+    branches: Vec<String>,
+    roots: Vec<String>,
+}
+
+/// Build the setup command
+pub fn build_command() -> Command {
+    with_target(Command::new("setup").about(
+        "First-run wizard: detect tools, choose a safety profile and write the initial config",
+    ))
+    .arg(
+        Arg::new("preset")
+            .long("preset")
+            .value_name("PRESET")
+            .value_parser(["agent", "dev"])
+            .help("Safety profile to write: agent (strict) or dev (relaxed)"),
+    )
+    .arg(
+        Arg::new("branches")
+            .long("branches")
+            .value_name("BRANCHES")
+            .help("Protected branches, comma-separated (default: the repository's default branch)"),
+    )
+    .arg(
+        Arg::new("roots").long("roots").value_name("DIRS").help(
+            "Directories commands may read and write, comma-separated; empty for unrestricted",
+        ),
+    )
+    .arg(
+        Arg::new("shell")
+            .long("shell")
+            .value_name("SHELL")
+            .value_parser(["bash", "zsh", "fish"])
+            .help("Shell to install completions for (default: from $SHELL)"),
+    )
+    .arg(
+        Arg::new("no-completions")
+            .long("no-completions")
+            .action(ArgAction::SetTrue)
+            .help("Do not install shell completions"),
+    )
+    .arg(
+        Arg::new("no-hooks")
+            .long("no-hooks")
+            .action(ArgAction::SetTrue)
+            .help("Do not install git hooks"),
+    )
+    .arg(
+        Arg::new("force")
+            .long("force")
+            .action(ArgAction::SetTrue)
+            .help("Overwrite an existing configuration file and git hooks setup did not write"),
+    )
+    .arg(
+        Arg::new("yes")
+            .long("yes")
+            .short('y')
+            .action(ArgAction::SetTrue)
+            .help("Accept the proposed answers without asking"),
+    )
+}
+
+/// Execute the setup command; `cli` is the whole command line interface, for completions
+pub async fn execute(matches: &ArgMatches, executor: &Executor, cli: Command) -> Result<()> {
+    let prompt = Prompt {
+        assume_yes: matches.get_flag("yes"),
+    };
+    if !prompt.assume_yes && !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "setup asks questions; re-run it in a terminal or with --yes to accept the proposed answers"
+        ));
+    }
+    let dry_run = executor.config().safety.dry_run;
+    let force = matches.get_flag("force");
+    let config_path = target_path(matches, false)?;
+    if config_path.exists() && !force {
+        return Err(anyhow!(
+            "{} already exists; use --force to overwrite it or `config set` to change it",
+            config_path.display()
+        ));
+    }
+
+    println!("Installed tools:");
+    let mut found = Vec::new();
+    for name in TOOLS {
+        match tools::resolve(name, executor.config().tools.get(*name)) {
+            Ok(path) => {
+                let version = tools::tool_version(&path)
+                    .map(|version| tools::join_version(&version))
+                    .unwrap_or_else(|_| "?".to_string());
+                println!("  {:<9} {:<10} {}", name, version, path.display());
+                found.push(name.to_string());
+            }
+            Err(_) => println!("  {:<9} -", name),
+        }
+    }
+    println!();
+
+    let proposed = matches
+        .get_one::<String>("preset")
+        .map(String::as_str)
+        .unwrap_or("agent");
+    let profile = loop {
+        let answer = prompt.ask(
+            "Safety profile: agent (strict: only the tools above, inside the workspace, protected branches read-only) or dev (relaxed)",
+            proposed,
+        )?;
+        match Profile::parse(&answer) {
+            Some(profile) => break profile,
+            None => println!("Answer agent or dev"),
+        }
+    };
+
+    let proposed = match matches.get_one::<String>("branches") {
+        Some(branches) => branches.clone(),
+        None => default_branch().unwrap_or_else(|| "none".to_string()),
+    };
+    let branches = loop {
+        let answer = prompt.ask("Protected branches (comma-separated, or none)", &proposed)?;
+        let branches = split_list(&answer);
+        match branches.iter().find(|branch| !is_branch_name(branch)) {
+            Some(invalid) if prompt.assume_yes => {
+                return Err(anyhow!("'{}' is not a branch name", invalid))
+            }
+            Some(invalid) => println!("'{}' is not a branch name", invalid),
+            None => break branches,
+        }
+    };
+
+    let proposed = match (matches.get_one::<String>("roots"), profile) {
+        (Some(roots), _) => roots.clone(),
+        (None, Profile::Agent) => ".".to_string(),
+        (None, Profile::Dev) => "none".to_string(),
+    };
+    let roots = split_list(&prompt.ask(
+        "Directories commands may read and write (comma-separated, or none for unrestricted)",
+        &proposed,
+    )?);
+
+    let answers = Answers {
+        profile,
+        tools: found,
+        branches,
+        roots,
+    };
+    let content = render_config(&answers);
+    let problems = validate_toml(&content);
+    if !problems.is_empty() {
+        return Err(anyhow!(
+            "Generated an invalid configuration: {}",
+            problems.join("; ")
+        ));
+    }
+    println!();
+    if dry_run {
+        println!("Would write {}:\n{}", config_path.display(), content);
+    } else {
+        write_file(&config_path, &content)?;
+        println!(
+            "Wrote {} ({} profile)",
+            config_path.display(),
+            profile.name()
+        );
+    }
+
+    if !matches.get_flag("no-completions") {
+        let shell = matches
+            .get_one::<String>("shell")
+            .cloned()
+            .or_else(login_shell);
+        match shell.filter(|name| ["bash", "zsh", "fish"].contains(&name.as_str())) {
+            Some(shell) if prompt.confirm(&format!("Install {} completions?", shell))? => {
+                install_completions(&shell, &cli, dry_run)?;
+            }
+            Some(_) => {}
+            None => println!("Skipped completions: use --shell bash, zsh or fish"),
+        }
+    }
+
+    if !matches.get_flag("no-hooks") {
+        match hooks_dir() {
+            Some(dir) if prompt.confirm("Install git hooks?")? => {
+                install_hooks(&dir, &answers, force, dry_run)?;
+            }
+            Some(_) => {}
+            None => println!("Skipped git hooks: not in a git repository"),
+        }
+    }
+
+    println!();
+    println!(
+        "Done. Check the result with `safe-ai-util config validate` and `safe-ai-util config show`."
+    );
+    Ok(())
+}
+
+/// Questions on the terminal, or their proposed answers with `--yes`
+struct Prompt {
+    assume_yes: bool,
+}
+
+impl Prompt {
+    /// Ask a question, returning the proposed answer when the reply is empty
+    fn ask(&self, question: &str, proposed: &str) -> Result<String> {
+        if self.assume_yes {
+            println!("{} [{}]: {}", question, proposed, proposed);
+            return Ok(proposed.to_string());
+        }
+        print!("{} [{}]: ", question, proposed);
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer)?;
+        match answer.trim() {
+            "" => Ok(proposed.to_string()),
+            answer => Ok(answer.to_string()),
+        }
+    }
+
+    /// Ask a yes/no question whose proposed answer is yes
+    fn confirm(&self, question: &str) -> Result<bool> {
+        let answer = self.ask(&format!("{} (y/n)", question), "y")?;
+        Ok(!matches!(answer.as_str(), "n" | "N" | "no"))
+    }
+}
+
+/// Items of a comma-separated answer; `none` is the empty list
+fn split_list(text: &str) -> Vec<String> {
+    if text.trim() == "none" {
+        return Vec::new();
+    }
+    text.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Branch names that need no quoting in hooks and no escaping beyond regex metacharacters
+fn is_branch_name(name: &str) -> bool {
+    !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._/-".contains(c))
+}
+
+/// Run git and return its trimmed output if it succeeds
+fn git(args: &[&str]) -> Option<String> {
+    let output = Process::new("git")
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The branch `origin/HEAD` points at, or `main` or `master` when one exists locally
+fn default_branch() -> Option<String> {
+    if let Some(head) = git(&[
+        "symbolic-ref",
+        "--quiet",
+        "--short",
+        "refs/remotes/origin/HEAD",
+    ]) {
+        if let Some(branch) = head.strip_prefix("origin/") {
+            return Some(branch.to_string());
+        }
+    }
+    ["main", "master"]
+        .into_iter()
+        .find(|branch| {
+            git(&[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("refs/heads/{}", branch),
+            ])
+            .is_some()
+        })
+        .map(str::to_string)
+}
+
+fn hooks_dir() -> Option<PathBuf> {
+    git(&["rev-parse", "--git-path", "hooks"]).map(PathBuf::from)
+}
+
+fn login_shell() -> Option<String> {
+    let shell = std::env::var("SHELL").ok()?;
+    Path::new(&shell)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// A TOML array of strings
+fn toml_list(items: &[String]) -> String {
+    toml::Value::from(items.to_vec()).to_string()
+}
+
+/// Policy rules keeping the protected branches from being rewritten or deleted, and for the
+/// agent profile from being pushed to at all, as (argument regex, reason) pairs
+fn branch_rules(profile: Profile, branches: &[String]) -> Vec<(String, String)> {
+    if branches.is_empty() {
+        return Vec::new();
+    }
+    let names = branches
+        .iter()
+        .map(|branch| regex::escape(branch))
+        .collect::<Vec<_>>()
+        .join("|");
+    let branch = format!("({})(\\s|$)", names);
+    let listed = match branches {
+        [branch] => format!("{} is", branch),
+        _ => format!("{} are", branches.join(", ")),
+    };
+
+    let mut rules = Vec::new();
+    match profile {
+        Profile::Agent => rules.push((
+            format!("^push\\b.*\\s[+:]?(\\S+:)?{}", branch),
+            format!(
+                "{} protected; push to another branch and open a pull request",
+                listed
+            ),
+        )),
+        Profile::Dev => {
+            rules.push((
+                format!(
+                    "^push\\b.*\\s(--force\\S*|-f)\\s(.*\\s)?\\+?(\\S+:)?{b}|^push\\b.*\\s\\+(\\S+:)?{b}",
+                    b = branch
+                ),
+                format!("{} protected and cannot be force-pushed", listed),
+            ));
+            rules.push((
+                format!(
+                    "^push\\b.*\\s(--delete|-d)\\s(.*\\s)?{b}|^push\\b.*\\s:{b}",
+                    b = branch
+                ),
+                format!("{} protected and cannot be deleted", listed),
+            ));
+        }
+    }
+    rules.push((
+        format!("^branch\\b.*\\s(-d|-D|--delete)\\s(.*\\s)?{}", branch),
+        format!("{} protected and cannot be deleted", listed),
+    ));
+    rules
+}
+
+/// The configuration file, with a comment explaining every setting
+fn render_config(answers: &Answers) -> String {
+    let mut out = String::from(
+        "# Safe AI Utility configuration, written by `safe-ai-util setup`\n\
+         #\n",
+    );
+    match answers.profile {
+        Profile::Agent => out.push_str(
+            "# Strict agent profile: commands are limited to the tools setup found, run inside the\n\
+             # workspace roots, and cannot push to the protected branches.\n",
+        ),
+        Profile::Dev => out.push_str(
+            "# Relaxed dev profile: any allowlisted command may run, but the protected branches\n\
+             # cannot be force-pushed or deleted.\n",
+        ),
+    }
+    out.push_str(
+        "# `safe-ai-util config init --file FILE` writes a file listing every key with its\n\
+         # default; run `safe-ai-util config validate` after editing.\n",
+    );
+
+    if answers.profile == Profile::Dev {
+        out.push_str(
+            "\n# The built-in dev profile: long timeouts, debug logging, confirmation before\n\
+             # destructive operations\n\
+             profile = \"dev\"\n",
+        );
+    } else {
+        out.push_str(
+            "\n[general]\n\
+             # Seconds a command may run before it is stopped\n\
+             timeout_seconds = 300\n\
+             # Failed commands are reported to the agent instead of retried\n\
+             max_retries = 0\n",
+        );
+    }
+
+    out.push_str("\n[safety]\n");
+    if answers.roots.is_empty() {
+        out.push_str("# Commands may read and write anywhere; list directories to confine them\n");
+    } else {
+        out.push_str("# Commands may only read and write inside these directories (the path jail),\n# relative to the working directory\n");
+    }
+    out.push_str(&format!(
+        "workspace_roots = {}\n",
+        toml_list(&answers.roots)
+    ));
+    if answers.profile == Profile::Agent {
+        out.push_str(
+            "# Reject path traversal such as `../` in arguments\n\
+             validate_paths = true\n\
+             # Ask before destructive operations; without a terminal they are refused\n\
+             confirm_destructive = true\n",
+        );
+    }
+
+    out.push_str(
+        "\n[git]\n\
+         # --force becomes --force-with-lease, which refuses to overwrite commits not fetched yet\n\
+         safe_force_push = true\n",
+    );
+
+    let mut rules = branch_rules(answers.profile, &answers.branches);
+    if answers.profile == Profile::Agent {
+        rules.push((
+            "^(reset\\b.*--hard|clean\\b.*-[a-z]*f)".to_string(),
+            "discarding work is not allowed for agents".to_string(),
+        ));
+    }
+    if answers.profile == Profile::Agent || !rules.is_empty() {
+        out.push_str("\n[policy]\n");
+    }
+    if answers.profile == Profile::Agent {
+        let mut allowed = answers.tools.clone();
+        allowed.extend(READ_ONLY_UTILITIES.iter().map(|name| name.to_string()));
+        out.push_str(&format!(
+            "# Only these commands may run: the tools setup found and read-only utilities\n\
+             allow_commands = {}\n",
+            toml_list(&allowed)
+        ));
+    }
+    if !rules.is_empty() {
+        out.push_str(
+            "# The first rule whose regex matches the space-joined arguments decides\nrules = [\n",
+        );
+        for (args, reason) in rules {
+            out.push_str(&format!(
+                "    {{ command = \"git\", args = '{}', action = \"deny\", reason = \"{}\" }},\n",
+                args, reason
+            ));
+        }
+        out.push_str("]\n");
+    }
+
+    if answers.profile == Profile::Agent {
+        out.push_str(
+            "\n[content_policy]\n\
+             # Refuse to write credentials and known malware patterns to files\n\
+             enabled = true\n\
+             \n\
+             [budget]\n\
+             # Subprocesses one agent session may spawn, nested invocations included\n\
+             max_subprocesses = 500\n",
+        );
+    }
+    out
+}
+
+/// Install completions where the shell finds them without further setup, except for zsh
+fn install_completions(shell: &str, cli: &Command, dry_run: bool) -> Result<()> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Cannot determine the home directory"))?;
+    let xdg = |var: &str, default: &str| {
+        std::env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .unwrap_or_else(|| home.join(default))
+    };
+    let name = "safe-ai-util";
+    let path = match shell {
+        "bash" => xdg("XDG_DATA_HOME", ".local/share")
+            .join("bash-completion/completions")
+            .join(name),
+        "zsh" => xdg("XDG_DATA_HOME", ".local/share")
+            .join(name)
+            .join("completions.zsh"),
+        "fish" => xdg("XDG_CONFIG_HOME", ".config")
+            .join("fish/completions")
+            .join(format!("{}.fish", name)),
+        other => return Err(anyhow!("Completions for {} are not supported", other)),
+    };
+    if dry_run {
+        println!("Would write {}", path.display());
+        return Ok(());
+    }
+
+    write_file(&path, &completions::script(shell, cli)?)?;
+    println!("Wrote {}", path.display());
+    if shell == "zsh" {
+        println!(
+            "  add `source {}` after `compinit` in ~/.zshrc to load it",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Install the pre-push hook guarding the protected branches and, when the project has a
+/// `.pre-commit-config.yaml`, a pre-commit hook running it through the executor
+fn install_hooks(dir: &Path, answers: &Answers, force: bool, dry_run: bool) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut hooks = Vec::new();
+    if !answers.branches.is_empty() {
+        hooks.push((
+            "pre-push",
+            pre_push_hook(answers.profile, &answers.branches),
+        ));
+    }
+    if Path::new(pre_commit::CONFIG_FILE).exists() {
+        hooks.push(("pre-commit", pre_commit::git_hook(&exe)));
+    }
+    if hooks.is_empty() {
+        println!("No git hooks to install: no protected branches and no .pre-commit-config.yaml");
+    }
+
+    for (name, script) in hooks {
+        let path = dir.join(name);
+        let ours =
+            fs::read_to_string(&path).map_or(true, |existing| existing.contains(HOOK_MARKER));
+        if !ours && !force {
+            println!(
+                "Skipped {}: an existing hook is in place; use --force to replace it",
+                path.display()
+            );
+            continue;
+        }
+        if dry_run {
+            println!("Would write {}", path.display());
+            continue;
+        }
+        write_file(&path, &script)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        }
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// A pre-push hook refusing what the policy rules refuse, for pushes made with plain git
+fn pre_push_hook(profile: Profile, branches: &[String]) -> String {
+    let check = match profile {
+        Profile::Agent => {
+            "        echo \"safe-ai-util: $name is protected; push to another branch and open a pull request\" >&2\n\
+             \x20       exit 1\n"
+        }
+        Profile::Dev => {
+            "        case $remote_sha in\n\
+             \x20           *[!0]*)\n\
+             \x20               if ! git merge-base --is-ancestor \"$remote_sha\" \"$local_sha\" 2>/dev/null; then\n\
+             \x20                   echo \"safe-ai-util: $name is protected and cannot be force-pushed\" >&2\n\
+             \x20                   exit 1\n\
+             \x20               fi\n\
+             \x20               ;;\n\
+             \x20       esac\n"
+        }
+    };
+    format!(
+        "#!/bin/sh\n\
+         {marker}: guard the protected branches\n\
+         protected='{branches}'\n\
+         while read -r local_ref local_sha remote_ref remote_sha; do\n\
+         \x20   for name in $protected; do\n\
+         \x20       [ \"${{remote_ref#refs/heads/}}\" = \"$name\" ] || continue\n\
+         \x20       case $local_sha in\n\
+         \x20           *[!0]*) ;;\n\
+         \x20           *)\n\
+         \x20               echo \"safe-ai-util: $name is protected and cannot be deleted\" >&2\n\
+         \x20               exit 1\n\
+         \x20               ;;\n\
+         \x20       esac\n\
+         {check}\
+         \x20   done\n\
+         done\n\
+         exit 0\n",
+        marker = HOOK_MARKER,
+        branches = branches.join(" "),
+        check = check,
+    )
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ContentPolicyConfig, PolicyConfig};
+    use crate::security::policy::Policy;
+
+    fn answers(profile: Profile) -> Answers {
+        Answers {
+            profile,
+            tools: vec!["git".to_string(), "cargo".to_string()],
+            branches: vec!["main".to_string(), "release/1.x".to_string()],
+            roots: vec![".".to_string()],
+        }
+    }
+
+    fn policy(answers: &Answers) -> Policy {
+        let content = render_config(answers);
+        assert_eq!(validate_toml(&content), Vec::<String>::new(), "{}", content);
+        Policy::from_config(&section::<PolicyConfig>(&content, "policy")).unwrap()
+    }
+
+    fn section<T: serde::de::DeserializeOwned>(content: &str, name: &str) -> T {
+        let value: toml::Value = toml::from_str(content).unwrap();
+        value[name].clone().try_into().unwrap()
+    }
+
+    fn allowed(policy: &Policy, command: &str, args: &str) -> bool {
+        let args: Vec<String> = args.split(' ').map(String::from).collect();
+        policy.evaluate(command, &args).is_ok()
+    }
+
+    #[test]
+    fn test_agent_config() {
+        let answers = answers(Profile::Agent);
+        let policy = policy(&answers);
+        assert!(allowed(&policy, "git", "push origin feature"));
+        assert!(allowed(&policy, "git", "push origin feature/main-fix"));
+        assert!(!allowed(&policy, "git", "push origin main"));
+        assert!(!allowed(&policy, "git", "push -u origin HEAD:release/1.x"));
+        assert!(!allowed(&policy, "git", "push origin :main"));
+        assert!(!allowed(&policy, "git", "branch -D main"));
+        assert!(!allowed(&policy, "git", "reset --hard HEAD~1"));
+        assert!(allowed(&policy, "cargo", "test"));
+        assert!(allowed(&policy, "cat", "notes.txt"));
+        assert!(!allowed(&policy, "curl", "https://example.com"));
+
+        let content = render_config(&answers);
+        let safety: toml::Value = section(&content, "safety");
+        assert_eq!(safety["workspace_roots"], toml::Value::from(vec!["."]));
+        assert!(section::<ContentPolicyConfig>(&content, "content_policy").enabled);
+    }
+
+    #[test]
+    fn test_dev_config() {
+        let answers = Answers {
+            roots: Vec::new(),
+            ..answers(Profile::Dev)
+        };
+        let content = render_config(&answers);
+        assert!(content.contains("profile = \"dev\""));
+        let policy = policy(&answers);
+        assert!(allowed(&policy, "git", "push origin main"));
+        assert!(allowed(&policy, "git", "push --force origin feature"));
+        assert!(!allowed(
+            &policy,
+            "git",
+            "push --force-with-lease origin main"
+        ));
+        assert!(!allowed(&policy, "git", "push origin +main"));
+        assert!(!allowed(&policy, "git", "push --delete origin release/1.x"));
+        assert!(!allowed(&policy, "git", "branch --delete main"));
+        assert!(allowed(&policy, "git", "branch -D feature"));
+        assert!(allowed(&policy, "curl", "https://example.com"));
+
+        let safety: toml::Value = section(&content, "safety");
+        assert_eq!(safety["workspace_roots"], toml::Value::Array(Vec::new()));
+    }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(split_list(" main, ,master "), vec!["main", "master"]);
+        assert!(split_list("none").is_empty());
+        assert!(is_branch_name("release/1.x"));
+        assert!(!is_branch_name("main;rm"));
+        assert!(!is_branch_name("--force"));
+
+        let hook = pre_push_hook(Profile::Dev, &["main".to_string(), "master".to_string()]);
+        assert!(hook.starts_with("#!/bin/sh\n# Installed by safe-ai-util setup"));
+        assert!(hook.contains("protected='main master'"));
+        assert!(hook.contains("merge-base --is-ancestor"));
+        assert!(!pre_push_hook(Profile::Agent, &["main".to_string()]).contains("merge-base"));
+    }
+}
@@ -0,0 +1,248 @@
+// file: src/commands/shell.rs
+// version: 1.1.0
+// guid: 5a0c8e3d-71f4-4b2a-9d6e-3c8f1b7a2e94
+
+//! `shell` command: an interactive shell for the rare cases a human needs raw access
+//!
+//! The shell runs whatever is typed, past the allowlist and the command policy, so it is
+//! refused unless `policy.allow_shell` is set. Every session is written to the security audit
+//! log when it starts and ends, and its keystrokes and output are saved as an asciicast
+//! recording next to the audit log. A session ends when the shell exits, after
+//! `--idle-timeout` without keystrokes or output, or after `--max-duration`.
+
+use crate::executor::Executor;
+use crate::security::audit;
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Build the shell command
+pub fn build_command() -> Command {
+    Command::new("shell")
+        .about("Open an interactive shell whose keystrokes and output are recorded for audit")
+        .long_about(
+            "Open an interactive shell in a pseudo-terminal. Everything typed and printed is \
+             recorded in the audit store, including input typed at password prompts. Disabled \
+             unless policy.allow_shell is set.",
+        )
+        .arg(
+            Arg::new("shell")
+                .long("shell")
+                .value_name("PROGRAM")
+                .help("Shell to run (default: execution.shell, then $SHELL, then sh)"),
+        )
+        .arg(
+            Arg::new("idle-timeout")
+                .long("idle-timeout")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("300")
+                .help("End the session after this many seconds without keystrokes or output"),
+        )
+        .arg(
+            Arg::new("max-duration")
+                .long("max-duration")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("3600")
+                .help("End the session after this many seconds"),
+        )
+        .arg(
+            Arg::new("no-network")
+                .long("no-network")
+                .action(ArgAction::SetTrue)
+                .help("Run the shell without network access (Linux)"),
+        )
+}
+
+/// How a shell session is run
+struct ShellOptions {
+    program: PathBuf,
+    idle_timeout: Duration,
+    max_duration: Duration,
+    no_network: bool,
+}
+
+/// Execute the shell command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    if let Err(e) = executor.policy().evaluate_shell() {
+        audit::log_access_denied("shell", &e.to_string());
+        return Err(e.into());
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        bail!("The shell needs an interactive terminal");
+    }
+
+    let name = matches
+        .get_one::<String>("shell")
+        .cloned()
+        .or_else(|| executor.config().execution.shell.clone())
+        .or_else(|| {
+            std::env::var("SHELL")
+                .ok()
+                .filter(|shell| !shell.is_empty())
+        })
+        .unwrap_or_else(|| "sh".to_string());
+    let Ok(program) = which::which(&name) else {
+        bail!("Shell '{}' not found", name);
+    };
+    let mut max_duration = Duration::from_secs(*matches.get_one::<u64>("max-duration").unwrap());
+    if let Some(remaining) = executor.budget().remaining_time() {
+        max_duration = max_duration.min(remaining);
+    }
+    let options = ShellOptions {
+        program,
+        idle_timeout: Duration::from_secs(*matches.get_one::<u64>("idle-timeout").unwrap()),
+        max_duration,
+        no_network: matches.get_flag("no-network") || executor.config().safety.no_network,
+    };
+
+    if executor.config().safety.dry_run {
+        println!(
+            "Would open a recorded shell session: {} ({})",
+            options.program.display(),
+            describe(&options)
+        );
+        return Ok(());
+    }
+    run(executor, &options).await
+}
+
+/// Limits of a session, as shown to the user and written to the audit log
+fn describe(options: &ShellOptions) -> String {
+    format!(
+        "idle timeout {}s, max duration {}s, network {}",
+        options.idle_timeout.as_secs(),
+        options.max_duration.as_secs(),
+        if options.no_network {
+            "denied"
+        } else {
+            "allowed"
+        }
+    )
+}
+
+#[cfg(unix)]
+async fn run(executor: &Executor, options: &ShellOptions) -> Result<()> {
+    use crate::activity::short_id;
+    use crate::process;
+    use crate::pty::{Pty, SessionOptions};
+    use crate::session;
+    use chrono::Utc;
+    use serde_json::{json, Map};
+    use std::collections::BTreeMap;
+    use std::time::Instant;
+
+    let shell = options.program.display().to_string();
+    let terminal = Pty::open()?;
+    let (rows, cols) = terminal.size();
+    let mut cmd = executor.prepare_command(&options.program, &[], None, &BTreeMap::new())?;
+    terminal.attach(&mut cmd)?;
+    // With safety.no_network the executor has already isolated the shell
+    if options.no_network && !executor.config().safety.no_network {
+        process::deny_network(&mut cmd)?;
+    }
+    executor.budget().spawn()?;
+
+    let started_at = Utc::now();
+    audit::log_shell_session(&shell, &[], vec![format!("started: {}", describe(options))]);
+    eprintln!(
+        "Recorded shell session ({}); everything typed is recorded. Exit the shell to end it.",
+        describe(options)
+    );
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) if options.no_network => {
+            bail!(
+                "Failed to start {} without network access (needs unprivileged user namespaces): {}",
+                shell,
+                e
+            )
+        }
+        Err(e) => bail!("Failed to start {}: {}", shell, e),
+    };
+    drop(cmd);
+    let session = terminal.start(SessionOptions {
+        echo: true,
+        forward_terminal: true,
+        record_input: true,
+        input: None,
+    })?;
+
+    let cancel = executor.cancellation_token();
+    let started = Instant::now();
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    let ended = loop {
+        tokio::select! {
+            status = child.wait() => {
+                break match status {
+                    Ok(status) => format!("shell exited ({})", status),
+                    Err(e) => format!("could not wait for the shell: {}", e),
+                };
+            }
+            _ = cancel.cancelled() => {
+                process::terminate(&mut child).await;
+                break "cancelled".to_string();
+            }
+            _ = tick.tick() => {
+                if session.idle() >= options.idle_timeout {
+                    process::terminate(&mut child).await;
+                    break format!("idle for {}s", options.idle_timeout.as_secs());
+                }
+                if started.elapsed() >= options.max_duration {
+                    process::terminate(&mut child).await;
+                    let limit = options.max_duration.as_secs();
+                    break format!("reached the maximum duration of {}s", limit);
+                }
+            }
+        }
+    };
+    // Restores the terminal
+    let recording = session.finish().await;
+
+    let mut header = Map::new();
+    header.insert("width".to_string(), json!(cols));
+    header.insert("height".to_string(), json!(rows));
+    header.insert("timestamp".to_string(), json!(started_at.timestamp()));
+    header.insert("command".to_string(), json!(shell));
+    header.insert(
+        "title".to_string(),
+        json!(format!("shell session {}", session::current().session_id())),
+    );
+    let name = format!(
+        "shell-{}-{}.cast",
+        started_at.format("%Y%m%dT%H%M%S"),
+        short_id(session::current().session_id())
+    );
+    let mut notes = vec![
+        format!("ended: {}", ended),
+        format!("duration: {}s", started.elapsed().as_secs()),
+    ];
+    let saved = audit::save_recording(&name, recording.to_asciicast(header).as_bytes());
+    match &saved {
+        Ok(path) => notes.push(format!("recording: {}", path.display())),
+        Err(e) => notes.push(format!("recording failed: {}", e)),
+    }
+    audit::log_shell_session(&shell, &[], notes);
+
+    match saved {
+        Ok(path) => eprintln!(
+            "Shell session ended: {}; recorded in {}",
+            ended,
+            path.display()
+        ),
+        Err(e) => bail!(
+            "Shell session ended: {}; the recording could not be saved: {}",
+            ended,
+            e
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn run(_executor: &Executor, _options: &ShellOptions) -> Result<()> {
+    bail!("The shell is only supported on Unix")
+}
@@ -0,0 +1,403 @@
+// file: src/commands/snapshot.rs
+// version: 1.0.1
+// guid: 1c7e4b92-3f58-4a0d-b6e2-85d9a3f1c047
+
+//! `snapshot test`: golden-file tests of command output
+//!
+//! The command runs through the executor like any other, and its output is normalized before it
+//! is compared: ANSI escapes and trailing whitespace are removed, line endings become `\n`, and
+//! volatile text (the workspace path, the temporary directory, timestamps, UUIDs and durations)
+//! is replaced by placeholders such as `[TIMESTAMP]`. JSON output is pretty-printed with the
+//! fields named by `--redact-field` replaced. Files given with `--file`, such as the output of a
+//! code generator, are part of the snapshot as well.
+//!
+//! Snapshots are stored as `<dir>/<name>.snap`. A mismatch fails with a diff and leaves the new
+//! output next to the snapshot as `<name>.snap.new`; `--update` records it instead.
+
+use crate::diff::{DiffOptions, Inline, TextDiff};
+use crate::executor::Executor;
+use crate::json_order::{KeyOrder, Ordered};
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use serde_json::Value;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Placeholder for the values of fields named by `--redact-field`
+const REDACTED: &str = "[REDACTED]";
+
+/// Volatile text replaced unless `--no-default-redactions` is given
+const DEFAULT_REDACTIONS: &[(&str, &str)] = &[
+    (
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?",
+        "[TIMESTAMP]",
+    ),
+    (
+        r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+        "[UUID]",
+    ),
+    (r"\b\d+(\.\d+)?(ns|µs|us|ms|s)\b", "[DURATION]"),
+];
+
+/// Build the snapshot command
+pub fn build_command() -> Command {
+    Command::new("snapshot")
+        .about("Compare command output with stored snapshots")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("test")
+                .about("Run a command and compare its normalized output with a snapshot")
+                .arg(
+                    Arg::new("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("Snapshot name, e.g. codegen/api (stored as <dir>/<name>.snap)"),
+                )
+                .arg(
+                    Arg::new("dir")
+                        .long("dir")
+                        .value_name("DIR")
+                        .default_value(".snapshots")
+                        .help("Directory holding the snapshots"),
+                )
+                .arg(
+                    Arg::new("update")
+                        .long("update")
+                        .short('u')
+                        .action(ArgAction::SetTrue)
+                        .help("Record the output as the snapshot instead of comparing"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .value_name("PATH")
+                        .action(ArgAction::Append)
+                        .help("Also snapshot the content of this file after the command ran; may be repeated"),
+                )
+                .arg(
+                    Arg::new("stderr")
+                        .long("stderr")
+                        .action(ArgAction::SetTrue)
+                        .help("Also snapshot standard error"),
+                )
+                .arg(
+                    Arg::new("redact")
+                        .long("redact")
+                        .value_name("REGEX[=TEXT]")
+                        .action(ArgAction::Append)
+                        .help("Replace matches of REGEX with TEXT (default: [REDACTED]), split at the last '='; may be repeated"),
+                )
+                .arg(
+                    Arg::new("redact-field")
+                        .long("redact-field")
+                        .value_name("KEY")
+                        .action(ArgAction::Append)
+                        .help("Replace the values of this field in JSON output; may be repeated"),
+                )
+                .arg(
+                    Arg::new("no-default-redactions")
+                        .long("no-default-redactions")
+                        .action(ArgAction::SetTrue)
+                        .help("Keep paths, timestamps, UUIDs and durations as they are"),
+                )
+                .arg(
+                    Arg::new("command")
+                        .value_name("COMMAND")
+                        .num_args(1..)
+                        .required(true)
+                        .last(true)
+                        .help("Command and its arguments, after --"),
+                ),
+        )
+}
+
+/// Execute snapshot subcommands
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("test", sub_matches)) => execute_test(sub_matches, executor).await,
+        _ => Err(anyhow!("Unknown snapshot subcommand")),
+    }
+}
+
+async fn execute_test(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+    let dir = Path::new(matches.get_one::<String>("dir").unwrap());
+    let path = executor.check_path(snapshot_path(dir, name)?)?;
+    let pending = path.with_extension("snap.new");
+
+    let words: Vec<&String> = matches.get_many::<String>("command").unwrap().collect();
+    let (command, args) = words.split_first().unwrap();
+    let workspace = executor.watch_scope(&[])?.base;
+    let redactor = Redactor::new(
+        matches.get_many::<String>("redact").unwrap_or_default(),
+        matches
+            .get_many::<String>("redact-field")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        (!matches.get_flag("no-default-redactions")).then_some(workspace.as_path()),
+    )?;
+
+    let output = executor.execute_secure_capture(command, args).await?;
+    let mut sections = vec![
+        format!(
+            "# command: {}\n",
+            shell_words::join(words.iter().map(|word| word.as_str()))
+        ),
+        format!(
+            "exit: {}\n",
+            output
+                .exit_code
+                .map_or("signal".to_string(), |code| code.to_string())
+        ),
+        section("stdout", &redactor.apply(&output.stdout)),
+    ];
+    if matches.get_flag("stderr") {
+        sections.push(section("stderr", &redactor.apply(&output.stderr)));
+    }
+    for file in matches.get_many::<String>("file").unwrap_or_default() {
+        let content = fs::read_to_string(executor.check_path(file)?)
+            .map_err(|e| anyhow!("Cannot read {} for the snapshot: {}", file, e))?;
+        sections.push(section(
+            &format!("file {}", file),
+            &redactor.apply(&content),
+        ));
+    }
+    let actual = sections.concat();
+
+    let dry_run = executor.config().safety.dry_run;
+    if matches.get_flag("update") {
+        if dry_run {
+            println!(
+                "DRY RUN: Would record snapshot {} in {}",
+                name,
+                path.display()
+            );
+            return Ok(());
+        }
+        write(&path, &actual)?;
+        let _ = fs::remove_file(&pending);
+        println!("Recorded snapshot {} in {}", name, path.display());
+        return Ok(());
+    }
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(_) => {
+            if !dry_run {
+                write(&pending, &actual)?;
+            }
+            bail!(
+                "No snapshot {} at {}; review {} and run again with --update to record it",
+                name,
+                path.display(),
+                pending.display()
+            );
+        }
+    };
+    if expected == actual {
+        let _ = fs::remove_file(&pending);
+        println!("Snapshot {} matches", name);
+        return Ok(());
+    }
+
+    let options = DiffOptions {
+        inline: Inline::Word,
+        ..DiffOptions::default()
+    };
+    print!(
+        "{}",
+        TextDiff::new(&expected, &actual, options).unified(&path.display().to_string(), "actual")
+    );
+    if !dry_run {
+        write(&pending, &actual)?;
+    }
+    bail!(
+        "Snapshot {} does not match; the new output is in {}, run with --update to accept it",
+        name,
+        pending.display()
+    )
+}
+
+/// `<dir>/<name>.snap`; the name may contain `/` but must stay inside `dir`
+fn snapshot_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    if name.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!("Invalid snapshot name '{}'", name);
+    }
+    Ok(dir.join(format!("{}.snap", name)))
+}
+
+fn section(title: &str, content: &str) -> String {
+    format!("--- {}\n{}", title, content)
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Normalizes output and replaces volatile text
+struct Redactor {
+    /// Literal paths, replaced first so the patterns do not see them
+    paths: Vec<(String, &'static str)>,
+    patterns: Vec<(Regex, String)>,
+    fields: Vec<String>,
+}
+
+impl Redactor {
+    /// `workspace` enables the default redactions
+    fn new<'a>(
+        rules: impl Iterator<Item = &'a String>,
+        fields: Vec<String>,
+        workspace: Option<&Path>,
+    ) -> Result<Self> {
+        let mut paths = Vec::new();
+        let mut patterns = Vec::new();
+        if let Some(workspace) = workspace {
+            paths.push((workspace.display().to_string(), "[WORKSPACE]"));
+            let temp = std::env::temp_dir();
+            let temp = temp.display().to_string();
+            paths.push((temp.trim_end_matches('/').to_string(), "[TMP]"));
+            for (pattern, replacement) in DEFAULT_REDACTIONS {
+                patterns.push((Regex::new(pattern)?, replacement.to_string()));
+            }
+        }
+        // User rules come first so they can redact text the defaults would partly replace
+        let mut custom = Vec::new();
+        for rule in rules {
+            let (pattern, replacement) = rule.rsplit_once('=').unwrap_or((rule.as_str(), REDACTED));
+            let regex = Regex::new(pattern)
+                .map_err(|e| anyhow!("Invalid --redact pattern '{}': {}", pattern, e))?;
+            custom.push((regex, replacement.to_string()));
+        }
+        custom.extend(patterns);
+        // Longer paths first, so a workspace inside the temporary directory stays [WORKSPACE]
+        paths.retain(|(path, _)| !path.is_empty());
+        paths.sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+        Ok(Self {
+            paths,
+            patterns: custom,
+            fields,
+        })
+    }
+
+    fn apply(&self, text: &str) -> String {
+        let mut text = normalize(text);
+        if !self.fields.is_empty() {
+            text = self.redact_json(&text).unwrap_or(text);
+        }
+        for (path, placeholder) in &self.paths {
+            text = text.replace(path.as_str(), placeholder);
+        }
+        for (regex, replacement) in &self.patterns {
+            text = regex.replace_all(&text, replacement.as_str()).into_owned();
+        }
+        text
+    }
+
+    /// Pretty-printed JSON, or JSON lines, with the named fields replaced; `None` if the text is
+    /// not JSON
+    fn redact_json(&self, text: &str) -> Option<String> {
+        if let Ok(mut value) = serde_json::from_str::<Value>(text) {
+            let order = KeyOrder::of(text).ok()?;
+            self.redact_value(&mut value);
+            return serde_json::to_string_pretty(&Ordered::new(&value, &order))
+                .ok()
+                .map(|json| json + "\n");
+        }
+        let mut out = String::new();
+        for line in text.lines() {
+            let mut value = serde_json::from_str::<Value>(line).ok()?;
+            let order = KeyOrder::of(line).ok()?;
+            self.redact_value(&mut value);
+            out.push_str(&serde_json::to_string(&Ordered::new(&value, &order)).ok()?);
+            out.push('\n');
+        }
+        Some(out)
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.fields.iter().any(|field| field == key) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_value(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            _ => {}
+        }
+    }
+}
+
+/// Without ANSI escapes, carriage returns and trailing whitespace, ending with a newline
+fn normalize(text: &str) -> String {
+    static ANSI: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+    let ansi = ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap());
+    let text = ansi.replace_all(text, "");
+    let mut out = String::with_capacity(text.len());
+    for line in text.lines() {
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_redact() {
+        assert_eq!(normalize("\x1b[32mok\x1b[0m  \r\nnext"), "ok\nnext\n");
+
+        let workspace = Path::new("/work/repo");
+        let rules = ["abc[0-9]+=[TOKEN]".to_string()];
+        let redactor = Redactor::new(rules.iter(), Vec::new(), Some(workspace)).unwrap();
+        assert_eq!(
+            redactor.apply(
+                "built /work/repo/gen/a.go at 2026-10-17T08:15:02.123Z in 1.52s\n\
+                 run 0f8fad5b-d9cb-469f-a165-70867728950e token=abc123\n"
+            ),
+            "built [WORKSPACE]/gen/a.go at [TIMESTAMP] in [DURATION]\n\
+             run [UUID] token=[TOKEN]\n"
+        );
+
+        let redactor =
+            Redactor::new(std::iter::empty(), vec!["duration_ms".to_string()], None).unwrap();
+        assert_eq!(
+            redactor.apply(r#"{"steps": [{"name": "a", "duration_ms": 12}]}"#),
+            "{\n  \"steps\": [\n    {\n      \"name\": \"a\",\n      \"duration_ms\": \"[REDACTED]\"\n    }\n  ]\n}\n"
+        );
+        assert_eq!(
+            redactor.apply("{\"duration_ms\": 1}\n{\"duration_ms\": 2}\n"),
+            "{\"duration_ms\":\"[REDACTED]\"}\n{\"duration_ms\":\"[REDACTED]\"}\n"
+        );
+        // Not JSON: left as it is
+        assert_eq!(redactor.apply("duration_ms: 12"), "duration_ms: 12\n");
+    }
+
+    #[test]
+    fn test_snapshot_path() {
+        let dir = Path::new(".snapshots");
+        assert_eq!(
+            snapshot_path(dir, "codegen/api").unwrap(),
+            Path::new(".snapshots/codegen/api.snap")
+        );
+        assert!(snapshot_path(dir, "../escape").is_err());
+        assert!(snapshot_path(dir, "/abs").is_err());
+        assert!(snapshot_path(dir, "").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+// file: src/commands/stats.rs
+// version: 1.0.1
+// guid: 683cdd24-7857-4fd8-9e62-49a9bd8cf776
+
+//! `stats` command: show execution metrics or serve them to Prometheus
+
+use super::export::Effect;
+use crate::executor::Executor;
+use crate::metrics::MetricsRegistry;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+/// Build the stats command
+pub fn build_command() -> Command {
+    Command::new("stats")
+        .add(Effect::ReadOnly)
+        .about("Show execution metrics (commands, failures, durations)")
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["table", "prometheus", "json"])
+                .default_value("table")
+                .help("Output format"),
+        )
+        .arg(
+            Arg::new("reset")
+                .long("reset")
+                .action(ArgAction::SetTrue)
+                .help("Clear all recorded metrics"),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve metrics on http://127.0.0.1:<port>/metrics until interrupted")
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .short('p')
+                        .value_name("PORT")
+                        .value_parser(clap::value_parser!(u16))
+                        .default_value("9464")
+                        .help("Port to listen on (localhost only)"),
+                ),
+        )
+}
+
+/// Execute the stats command
+pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
+    if let Some(("serve", sub_matches)) = matches.subcommand() {
+        let port = *sub_matches.get_one::<u16>("port").unwrap();
+        return serve(port).await;
+    }
+
+    if matches.get_flag("reset") {
+        let path = MetricsRegistry::path();
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        println!("Metrics reset");
+        return Ok(());
+    }
+
+    let registry = MetricsRegistry::load()?;
+    match matches.get_one::<String>("format").map(String::as_str) {
+        Some("prometheus") => print!("{}", registry.to_prometheus()),
+        Some("json") => println!("{}", serde_json::to_string_pretty(&registry)?),
+        _ => print_table(&registry),
+    }
+    Ok(())
+}
+
+fn print_table(registry: &MetricsRegistry) {
+    if registry.series.is_empty() {
+        println!("No commands recorded yet");
+        return;
+    }
+
+    println!(
+        "{:<28} {:>9} {:>9} {:>10} {:>10}",
+        "COMMAND", "EXECUTED", "FAILED", "MEAN (s)", "TOTAL (s)"
+    );
+    for metrics in registry.series.values() {
+        let name = format!("{} {}", metrics.command, metrics.subcommand);
+        println!(
+            "{:<28} {:>9} {:>9} {:>10.3} {:>10.3}",
+            name.trim(),
+            metrics.executed,
+            metrics.failures,
+            metrics.duration.mean(),
+            metrics.duration.sum
+        );
+    }
+}
+
+/// Serve `/metrics` on localhost until Ctrl-C
+async fn serve(port: u16) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    println!("Serving metrics on http://127.0.0.1:{}/metrics", port);
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = accepted?;
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream).await {
+                        warn!("Metrics request from {} failed: {}", peer, e);
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Metrics server stopped");
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream) -> Result<()> {
+    let mut buffer = vec![0u8; 8192];
+    let mut read = 0;
+    while !buffer[..read].windows(4).any(|w| w == b"\r\n\r\n") {
+        if read == buffer.len() {
+            return Err(anyhow!("Request header too large"));
+        }
+        let n = stream.read(&mut buffer[read..]).await?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let response = match request_target(&request) {
+        Some(("GET", "/metrics")) => {
+            let body = MetricsRegistry::load()?.to_prometheus();
+            http_response("200 OK", "text/plain; version=0.0.4", &body)
+        }
+        Some(("GET", _)) => http_response("404 Not Found", "text/plain", "Not Found\n"),
+        _ => http_response(
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method Not Allowed\n",
+        ),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Method and path from the request line
+fn request_target(request: &str) -> Option<(&str, &str)> {
+    let mut parts = request.lines().next()?.split_whitespace();
+    let method = parts.next()?;
+    let path = parts.next()?;
+    Some((method, path.split('?').next().unwrap_or(path)))
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_target() {
+        assert_eq!(
+            request_target("GET /metrics?x=1 HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Some(("GET", "/metrics"))
+        );
+        assert_eq!(request_target(""), None);
+    }
+}
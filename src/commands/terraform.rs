@@ -0,0 +1,504 @@
+// file: src/commands/terraform.rs
+// version: 1.0.2
+// guid: 4b9e2f71-c853-4d0a-96e7-1a5d38c2b6f9
+
+//! `terraform` command: init, validate, plan, show and apply with terraform or OpenTofu
+//!
+//! `apply` only applies a saved plan, never the configuration directly, and only one that is
+//! fresh: newer than every `.tf`, `.tfvars` and lock file of the configuration, and younger than
+//! `terraform.plan_max_age_minutes`. The plan is summarized before it is applied, asking first
+//! if it destroys anything, and removed afterwards so it cannot be applied twice.
+//!
+//! `plan` saves the plan (`tfplan` by default) and `show` summarizes a saved one from
+//! `show -json`: the resources to create, update, replace, destroy or read, as text or JSON.
+//! `terraform.binary` picks `terraform` or `tofu`; by default the first one installed.
+
+use super::export::{Effect, Remote};
+use crate::config::Config;
+use crate::executor::Executor;
+use crate::tools;
+use crate::utils;
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// Binaries tried, in order, when `terraform.binary` is not set
+const BINARIES: [&str; 2] = ["terraform", "tofu"];
+
+/// Plan file used when none is named
+const DEFAULT_PLAN: &str = "tfplan";
+
+/// Build the terraform command
+pub fn build_command() -> Command {
+    Command::new("terraform")
+        .about("Plan and apply infrastructure changes with terraform or OpenTofu")
+        .long_about(
+            "Run terraform or OpenTofu. apply only applies a saved plan that is newer than the \
+             configuration and younger than terraform.plan_max_age_minutes, and summarizes it \
+             first; plan and show summarize the changes a plan makes.",
+        )
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("chdir")
+                .long("chdir")
+                .short('C')
+                .value_name("DIR")
+                .help("Directory of the configuration (default: the working directory)"),
+        )
+        .subcommand(
+            Command::new("init")
+                .add(Remote)
+                .about("Install providers and modules and set up the backend")
+                .arg(
+                    Arg::new("upgrade")
+                        .long("upgrade")
+                        .action(ArgAction::SetTrue)
+                        .help("Upgrade providers and modules within their constraints"),
+                )
+                .arg(extra_args()),
+        )
+        .subcommand(
+            Command::new("validate")
+                .add(Effect::ReadOnly)
+                .about("Check the configuration for errors")
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the diagnostics as JSON"),
+                ),
+        )
+        .subcommand(
+            Command::new("plan")
+                .add(Remote)
+                .about("Save the changes that would be made, and summarize them")
+                .arg(plan_file("out"))
+                .arg(
+                    Arg::new("destroy")
+                        .long("destroy")
+                        .action(ArgAction::SetTrue)
+                        .help("Plan to destroy every resource"),
+                )
+                .arg(
+                    Arg::new("var-file")
+                        .long("var-file")
+                        .value_name("FILE")
+                        .action(ArgAction::Append)
+                        .help("Variable definitions file; repeat for several"),
+                )
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_name("ADDRESS")
+                        .action(ArgAction::Append)
+                        .help("Only plan this resource and its dependencies"),
+                )
+                .arg(format())
+                .arg(extra_args()),
+        )
+        .subcommand(
+            Command::new("show")
+                .add(Effect::ReadOnly)
+                .about("Summarize a saved plan")
+                .arg(plan_file("plan"))
+                .arg(format()),
+        )
+        .subcommand(
+            Command::new("apply")
+                .add(Effect::Destructive)
+                .add(Remote)
+                .about("Apply a fresh saved plan")
+                .arg(plan_file("plan"))
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .help("Apply a plan that destroys resources without asking"),
+                ),
+        )
+}
+
+fn plan_file(id: &'static str) -> Arg {
+    let arg = Arg::new(id)
+        .value_name("PLAN")
+        .default_value(DEFAULT_PLAN)
+        .help("Saved plan file, relative to the configuration directory");
+    if id == "out" {
+        arg.long("out").short('o')
+    } else {
+        arg
+    }
+}
+
+fn format() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .short('f')
+        .value_parser(["text", "json"])
+        .default_value("text")
+        .help("Output format of the plan summary")
+}
+
+fn extra_args() -> Arg {
+    Arg::new("args")
+        .value_name("ARGS")
+        .num_args(0..)
+        .last(true)
+        .allow_hyphen_values(true)
+        .help("Further arguments for terraform after --")
+}
+
+/// A resource a plan changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ResourceChange {
+    address: String,
+    /// `create`, `update`, `replace`, `delete` or `read`
+    action: &'static str,
+}
+
+/// What a plan would do, counted the way terraform counts it: a replaced resource is both
+/// added and destroyed
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+struct PlanSummary {
+    add: usize,
+    change: usize,
+    destroy: usize,
+    replace: usize,
+    resources: Vec<ResourceChange>,
+}
+
+impl PlanSummary {
+    /// Summarize the output of `terraform show -json PLAN`
+    fn from_json(plan: &Value) -> Self {
+        let mut summary = Self::default();
+        let changes = plan["resource_changes"].as_array().into_iter().flatten();
+        for change in changes {
+            let actions: Vec<&str> = change["change"]["actions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            let action = match actions.as_slice() {
+                ["create"] => "create",
+                ["update"] => "update",
+                ["delete"] => "delete",
+                ["read"] => "read",
+                actions if actions.contains(&"create") && actions.contains(&"delete") => "replace",
+                _ => continue,
+            };
+            match action {
+                "create" => summary.add += 1,
+                "update" => summary.change += 1,
+                "delete" => summary.destroy += 1,
+                "replace" => {
+                    summary.add += 1;
+                    summary.destroy += 1;
+                    summary.replace += 1;
+                }
+                _ => {}
+            }
+            summary.resources.push(ResourceChange {
+                address: change["address"].as_str().unwrap_or_default().to_string(),
+                action,
+            });
+        }
+        summary
+    }
+
+    fn print(&self, plan: &str) {
+        if self.resources.is_empty() {
+            println!("Plan {}: no changes", plan);
+            return;
+        }
+        println!(
+            "Plan {}: {} to add, {} to change, {} to destroy{}",
+            plan,
+            self.add,
+            self.change,
+            self.destroy,
+            if self.replace > 0 {
+                format!(" ({} replaced)", self.replace)
+            } else {
+                String::new()
+            }
+        );
+        for resource in &self.resources {
+            let symbol = match resource.action {
+                "create" => "+",
+                "update" => "~",
+                "delete" => "-",
+                "replace" => "-/+",
+                _ => "<=",
+            };
+            println!("  {:>3} {}", symbol, resource.address);
+        }
+    }
+}
+
+/// Execute the terraform command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let binary = binary(executor.config());
+    let chdir = matches.get_one::<String>("chdir");
+    let mut args: Vec<String> = chdir
+        .map(|dir| format!("-chdir={}", dir))
+        .into_iter()
+        .collect();
+    let dir = executor
+        .sandbox()
+        .base()
+        .join(chdir.map(String::as_str).unwrap_or("."));
+
+    match matches.subcommand() {
+        Some(("init", sub_matches)) => {
+            args.extend(["init".to_string(), "-input=false".to_string()]);
+            if sub_matches.get_flag("upgrade") {
+                args.push("-upgrade".to_string());
+            }
+            args.extend(extra(sub_matches));
+            executor.execute_secure(&binary, &args).await
+        }
+        Some(("validate", sub_matches)) => {
+            args.push("validate".to_string());
+            if sub_matches.get_flag("json") {
+                args.push("-json".to_string());
+            }
+            executor.execute_secure(&binary, &args).await
+        }
+        Some(("plan", sub_matches)) => {
+            let out = sub_matches.get_one::<String>("out").unwrap();
+            let json = sub_matches.get_one::<String>("format").map(String::as_str) == Some("json");
+            let mut plan = args.clone();
+            plan.extend([
+                "plan".to_string(),
+                "-input=false".to_string(),
+                format!("-out={}", out),
+            ]);
+            if sub_matches.get_flag("destroy") {
+                plan.push("-destroy".to_string());
+            }
+            for file in sub_matches
+                .get_many::<String>("var-file")
+                .unwrap_or_default()
+            {
+                plan.push(format!("-var-file={}", file));
+            }
+            for target in sub_matches.get_many::<String>("target").unwrap_or_default() {
+                plan.push(format!("-target={}", target));
+            }
+            plan.extend(extra(sub_matches));
+            if json {
+                // Only the summary goes to stdout, so terraform's own output is held back
+                let output = executor.execute_secure_capture(&binary, &plan).await?;
+                if !output.success() {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({
+                            "plan": out,
+                            "exit_code": output.exit_code,
+                            "stderr": output.stderr,
+                        }))?
+                    );
+                    bail!("{} plan failed", binary);
+                }
+            } else {
+                executor.execute_secure(&binary, &plan).await?;
+            }
+            let summary = summarize(&binary, &args, out, executor).await?;
+            print_summary(&summary, out, json)
+        }
+        Some(("show", sub_matches)) => {
+            let plan = sub_matches.get_one::<String>("plan").unwrap();
+            let json = sub_matches.get_one::<String>("format").map(String::as_str) == Some("json");
+            let summary = summarize(&binary, &args, plan, executor).await?;
+            print_summary(&summary, plan, json)
+        }
+        Some(("apply", sub_matches)) => {
+            let plan = sub_matches.get_one::<String>("plan").unwrap();
+            let path = dir.join(plan);
+            if !path.is_file() {
+                bail!(
+                    "No saved plan at {}; run `terraform plan --out {}` first",
+                    path.display(),
+                    plan
+                );
+            }
+            let max_age =
+                Duration::from_secs(executor.config().terraform.plan_max_age_minutes * 60);
+            check_fresh(&path, &dir, max_age, SystemTime::now())?;
+
+            let summary = summarize(&binary, &args, plan, executor).await?;
+            summary.print(plan);
+            if summary.resources.is_empty() {
+                println!("Nothing to apply");
+                return Ok(());
+            }
+            if summary.destroy > 0
+                && !sub_matches.get_flag("yes")
+                && executor.config().safety.confirm_destructive
+                && !utils::confirm(&format!(
+                    "The plan destroys {} resources; apply it?",
+                    summary.destroy
+                ))?
+            {
+                println!("Aborted; nothing was applied");
+                return Ok(());
+            }
+
+            args.extend([
+                "apply".to_string(),
+                "-input=false".to_string(),
+                plan.clone(),
+            ]);
+            executor.execute_secure(&binary, &args).await?;
+            // A plan describes changes from the state it was made against, which is gone now
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove applied plan {}", path.display()))?;
+            Ok(())
+        }
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+/// `terraform.binary`, or else the first of terraform and tofu that is installed
+fn binary(config: &Config) -> String {
+    if let Some(binary) = &config.terraform.binary {
+        return binary.clone();
+    }
+    BINARIES
+        .iter()
+        .find(|name| tools::resolve(name, config.tools.get(**name)).is_ok())
+        .unwrap_or(&BINARIES[0])
+        .to_string()
+}
+
+/// Summarize the saved plan `plan`; `args` are the global options, such as `-chdir`
+async fn summarize(
+    binary: &str,
+    args: &[String],
+    plan: &str,
+    executor: &Executor,
+) -> Result<PlanSummary> {
+    let mut show = args.to_vec();
+    show.extend(["show".to_string(), "-json".to_string(), plan.to_string()]);
+    let output = executor.execute_secure_capture(binary, &show).await?;
+    if !output.success() {
+        bail!("{} show failed: {}", binary, output.stderr.trim());
+    }
+    let plan: Value = serde_json::from_str(&output.stdout)
+        .with_context(|| format!("Failed to parse the output of {} show -json", binary))?;
+    Ok(PlanSummary::from_json(&plan))
+}
+
+fn print_summary(summary: &PlanSummary, plan: &str, json: bool) -> Result<()> {
+    if json {
+        let mut report = serde_json::to_value(summary)?;
+        report["plan"] = json!(plan);
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        summary.print(plan);
+    }
+    Ok(())
+}
+
+/// Refuse a plan that is older than a file of the configuration in `dir`, or than `max_age`
+fn check_fresh(plan: &Path, dir: &Path, max_age: Duration, now: SystemTime) -> Result<()> {
+    let planned = fs::metadata(plan)?.modified()?;
+    let age = now.duration_since(planned).unwrap_or_default();
+    if age > max_age {
+        bail!(
+            "Plan {} is {} minutes old (terraform.plan_max_age_minutes = {}); run terraform plan \
+             again",
+            plan.display(),
+            age.as_secs() / 60,
+            max_age.as_secs() / 60
+        );
+    }
+    if let Some(changed) = configuration_files(dir).into_iter().find(|file| {
+        fs::metadata(file)
+            .and_then(|m| m.modified())
+            .is_ok_and(|m| m > planned)
+    }) {
+        bail!(
+            "{} changed after plan {} was made; run terraform plan again",
+            changed.display(),
+            plan.display()
+        );
+    }
+    Ok(())
+}
+
+/// The files of the configuration in `dir` and its modules, outside `.terraform`
+fn configuration_files(dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            name == ".terraform.lock.hcl"
+                || [".tf", ".tf.json", ".tfvars", ".tfvars.json"]
+                    .iter()
+                    .any(|extension| name.ends_with(extension))
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+fn extra(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .get_many::<String>("args")
+        .unwrap_or_default()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_summary() {
+        let plan = json!({
+            "resource_changes": [
+                { "address": "aws_s3_bucket.logs", "change": { "actions": ["create"] } },
+                { "address": "aws_iam_role.ci", "change": { "actions": ["update"] } },
+                { "address": "aws_instance.web", "change": { "actions": ["delete", "create"] } },
+                { "address": "aws_eip.old", "change": { "actions": ["delete"] } },
+                { "address": "aws_vpc.main", "change": { "actions": ["no-op"] } },
+                { "address": "data.aws_ami.base", "change": { "actions": ["read"] } },
+            ]
+        });
+        let summary = PlanSummary::from_json(&plan);
+        assert_eq!(
+            (
+                summary.add,
+                summary.change,
+                summary.destroy,
+                summary.replace
+            ),
+            (2, 1, 2, 1)
+        );
+        assert_eq!(
+            summary
+                .resources
+                .iter()
+                .map(|r| r.action)
+                .collect::<Vec<_>>(),
+            ["create", "update", "replace", "delete", "read"]
+        );
+        assert_eq!(PlanSummary::from_json(&json!({})), PlanSummary::default());
+    }
+}
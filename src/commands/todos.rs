@@ -0,0 +1,837 @@
+// file: src/commands/todos.rs
+// version: 1.0.3
+// guid: 626f24cc-765c-40c8-8992-f22becaff472
+
+//! `todos` command: keep TODO comments and GitHub issues in sync
+//!
+//! `todos sync` scans the whole workspace (the git top level) for comments that start with a
+//! marker such as `TODO` or `FIXME`. The first sync writes a stable id into each comment
+//! (`// TODO[td-1a2b3c4d]: ...`); the same id is embedded in the body of the issue created
+//! for it, so a comment can move between lines and files without losing its issue.
+//!
+//! Issues are managed through `gh`:
+//!
+//! - markers without an issue get one, labeled with `--label`
+//! - issues whose title or location is stale are edited
+//! - closed issues whose marker is still present are reopened
+//! - open issues whose marker disappeared are closed with a comment
+//!
+//! `--dry-run` prints the planned id assignments and issue operations without changing
+//! anything.
+
+use super::export::{Effect, Remote};
+use super::file_move::walk_files;
+use crate::executor::Executor;
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use regex::Regex;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// Prefix of the marker ids written into comments
+const ID_PREFIX: &str = "td-";
+
+/// Hidden tag that links an issue body to a marker id
+const ISSUE_TAG: &str = "safe-ai-util:todo";
+
+/// Files larger than this are not scanned
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Longest issue title generated from a marker
+const MAX_TITLE_LEN: usize = 120;
+
+/// A marker comment found in the workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Marker {
+    /// Path relative to the workspace root, with `/` separators
+    path: String,
+    /// 1-based line number
+    line: usize,
+    kind: String,
+    id: Option<String>,
+    text: String,
+    /// Byte offset in the line just past the marker keyword, where the id is inserted
+    id_offset: usize,
+}
+
+impl Marker {
+    fn location(&self) -> String {
+        format!("{}:{}", self.path, self.line)
+    }
+
+    fn title(&self) -> String {
+        let title = if self.text.is_empty() {
+            format!("{} in {}", self.kind, self.path)
+        } else {
+            format!("{}: {}", self.kind, self.text)
+        };
+        // Titles are passed as arguments, so avoid characters the sanitizer rejects
+        let title = title
+            .replace('`', "'")
+            .replace("$(", "$ (")
+            .replace("${", "$ {");
+        if title.chars().count() <= MAX_TITLE_LEN {
+            return title;
+        }
+        let mut truncated: String = title.chars().take(MAX_TITLE_LEN - 3).collect();
+        truncated.push_str("...");
+        truncated
+    }
+
+    fn body(&self, id: &str) -> String {
+        let text = if self.text.is_empty() {
+            "(no description)"
+        } else {
+            &self.text
+        };
+        format!(
+            "{}\n\n`{}`\n\n<!-- {} {} -->",
+            text,
+            self.location(),
+            ISSUE_TAG,
+            id
+        )
+    }
+}
+
+/// An issue as reported by `gh issue list --json`
+#[derive(Debug, Clone, Deserialize)]
+struct Issue {
+    number: u64,
+    title: String,
+    #[serde(default)]
+    body: String,
+    state: String,
+}
+
+impl Issue {
+    /// Whether the issue was filed for one of the marker `kinds`, going by the title
+    /// [`Marker::title`] gave it
+    fn is_kind_of(&self, kinds: &[String]) -> bool {
+        kinds.iter().any(|kind| {
+            self.title
+                .strip_prefix(kind.as_str())
+                .is_some_and(|rest| rest.starts_with(": ") || rest.starts_with(" in "))
+        })
+    }
+
+    fn is_open(&self) -> bool {
+        self.state.eq_ignore_ascii_case("open")
+    }
+}
+
+/// A planned change to the issue tracker
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operation {
+    Create {
+        id: String,
+        title: String,
+        body: String,
+        location: String,
+    },
+    Update {
+        number: u64,
+        id: String,
+        reopen: bool,
+        old_title: String,
+        title: String,
+        body: Option<String>,
+        location: String,
+    },
+    Close {
+        number: u64,
+        id: String,
+        title: String,
+    },
+}
+
+impl Operation {
+    fn print(&self) {
+        match self {
+            Self::Create {
+                id,
+                title,
+                location,
+                ..
+            } => println!("+ create  {}  \"{}\" ({})", id, title, location),
+            Self::Update {
+                number,
+                id,
+                reopen,
+                old_title,
+                title,
+                body,
+                location,
+            } => {
+                let action = if *reopen { "reopen" } else { "update" };
+                println!("~ {}  #{} {}  ({})", action, number, id, location);
+                if old_title != title {
+                    println!("    - title: \"{}\"", old_title);
+                    println!("    + title: \"{}\"", title);
+                }
+                if body.is_some() {
+                    println!("    ~ body: location or description changed");
+                }
+            }
+            Self::Close { number, id, title } => {
+                println!(
+                    "- close   #{} {}  \"{}\" (marker removed)",
+                    number, id, title
+                )
+            }
+        }
+    }
+}
+
+/// Build the todos command
+pub fn build_command() -> Command {
+    Command::new("todos")
+        .about("Track TODO comments as GitHub issues")
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("List marker comments in the workspace and their ids")
+                .arg(markers_arg()),
+        )
+        .subcommand(
+            Command::new("sync")
+                .add(Effect::Destructive)
+                .add(Remote)
+                .about("Create, update, reopen and close GitHub issues (via gh) to match the TODO comments in the workspace")
+                .arg(markers_arg())
+                .arg(
+                    Arg::new("label")
+                        .long("label")
+                        .value_name("LABEL")
+                        .default_value("todo")
+                        .help("Label that identifies the issues managed by todos sync"),
+                )
+                .arg(
+                    Arg::new("repo")
+                        .long("repo")
+                        .short('R')
+                        .value_name("OWNER/REPO")
+                        .help("Repository to sync with (defaults to the repository gh detects)"),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32))
+                        .default_value("1000")
+                        .help("Maximum number of labeled issues to fetch"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Show the planned id assignments and issue operations without applying them"),
+                ),
+        )
+}
+
+fn markers_arg() -> Arg {
+    Arg::new("markers")
+        .long("markers")
+        .value_name("LIST")
+        .value_delimiter(',')
+        .default_value("TODO,FIXME")
+        .help("Comma-separated marker keywords to track")
+}
+
+/// Execute the todos command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("list", sub_matches)) => execute_list(sub_matches, executor).await,
+        Some(("sync", sub_matches)) => execute_sync(sub_matches, executor).await,
+        _ => {
+            println!("No todos subcommand specified. Use 'todos --help' for usage information.");
+            Ok(())
+        }
+    }
+}
+
+async fn execute_list(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let root = workspace_root(executor).await?;
+    let markers = scan_markers(&root, &marker_kinds(matches))?;
+    for marker in &markers {
+        println!(
+            "{:<12} {:<40} {}: {}",
+            marker.id.as_deref().unwrap_or("-"),
+            marker.location(),
+            marker.kind,
+            marker.text
+        );
+    }
+    println!("{} markers", markers.len());
+    Ok(())
+}
+
+async fn execute_sync(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let dry_run = matches.get_flag("dry-run") || executor.config().safety.dry_run;
+    let label = matches.get_one::<String>("label").unwrap();
+    let repo = matches.get_one::<String>("repo").map(String::as_str);
+    let limit = *matches.get_one::<u32>("limit").unwrap();
+
+    let root = workspace_root(executor).await?;
+    let kinds = marker_kinds(matches);
+    let mut markers = scan_markers(&root, &kinds)?;
+    let assigned = assign_ids(&mut markers);
+    let issues = list_issues(executor, label, repo, limit).await?;
+    info!(
+        "Found {} markers and {} issues labeled '{}'",
+        markers.len(),
+        issues.len(),
+        label
+    );
+
+    let operations = plan_sync(&markers, &kinds, &issues);
+    for &index in &assigned {
+        let marker = &markers[index];
+        println!(
+            "= id      {}  {} -> {}[{}]",
+            marker.location(),
+            marker.kind,
+            marker.kind,
+            marker.id.as_deref().unwrap_or_default()
+        );
+    }
+    for operation in &operations {
+        operation.print();
+    }
+
+    if assigned.is_empty() && operations.is_empty() {
+        println!("TODO issues are in sync ({} markers)", markers.len());
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "Dry run: {} ids to assign, {} issue operations planned",
+            assigned.len(),
+            operations.len()
+        );
+        return Ok(());
+    }
+
+    // Every file passes the content policy before any of them is written
+    let updates = with_ids(&root, &markers, &assigned)?;
+    for (file, content) in &updates {
+        executor.check_content(file, content.as_bytes())?;
+    }
+    for (file, content) in updates {
+        fs::write(&file, content)?;
+        info!("Wrote marker ids to {}", file.display());
+    }
+
+    if operations
+        .iter()
+        .any(|op| matches!(op, Operation::Create { .. }))
+    {
+        let args = vec![
+            "label".to_string(),
+            "create".to_string(),
+            label.clone(),
+            "--force".to_string(),
+            "--description".to_string(),
+            "Tracked TODO comment".to_string(),
+        ];
+        run_gh(executor, with_repo(args, repo)).await?;
+    }
+
+    for operation in &operations {
+        apply_operation(executor, operation, label, repo).await?;
+    }
+    println!(
+        "Synced {} markers: {} ids assigned, {} issue operations applied",
+        markers.len(),
+        assigned.len(),
+        operations.len()
+    );
+    Ok(())
+}
+
+fn marker_kinds(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .get_many::<String>("markers")
+        .unwrap()
+        .map(|kind| kind.trim().to_string())
+        .filter(|kind| !kind.is_empty())
+        .collect()
+}
+
+/// The git top level, falling back to the current directory outside a repository
+async fn workspace_root(executor: &Executor) -> Result<PathBuf> {
+    let output = executor
+        .execute_secure_capture("git", &["rev-parse", "--show-toplevel"])
+        .await?;
+    let root = output.stdout.trim();
+    if output.success() && !root.is_empty() {
+        Ok(PathBuf::from(root))
+    } else {
+        Ok(PathBuf::from("."))
+    }
+}
+
+/// Regex matching a marker at the start of a comment
+fn marker_regex(kinds: &[String]) -> Result<Regex> {
+    if kinds.is_empty() {
+        return Err(anyhow!("At least one marker keyword is required"));
+    }
+    let kinds = kinds
+        .iter()
+        .map(|kind| regex::escape(kind))
+        .collect::<Vec<_>>()
+        .join("|");
+    Ok(Regex::new(&format!(
+        r"(?:^\s*\*|//+|#+|/\*+|<!--|--|;+)\s*(?P<kind>{})\b(?:\[(?P<id>{}[0-9a-f]{{8}})\])?(?:\([^)]*\))?:?\s*(?P<text>.*?)\s*(?:\*/|-->)?\s*$",
+        kinds,
+        regex::escape(ID_PREFIX)
+    ))?)
+}
+
+/// Find every marker comment in the workspace
+fn scan_markers(root: &Path, kinds: &[String]) -> Result<Vec<Marker>> {
+    let regex = marker_regex(kinds)?;
+    let mut markers = Vec::new();
+
+    for file in walk_files(root)? {
+        if fs::metadata(&file).map(|m| m.len()).unwrap_or(0) > MAX_FILE_SIZE {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let path = file
+            .strip_prefix(root)
+            .unwrap_or(&file)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        for (index, line) in content.lines().enumerate() {
+            let Some(captures) = regex.captures(line) else {
+                continue;
+            };
+            let kind = captures.name("kind").unwrap();
+            markers.push(Marker {
+                path: path.clone(),
+                line: index + 1,
+                kind: kind.as_str().to_string(),
+                id: captures.name("id").map(|m| m.as_str().to_string()),
+                text: captures["text"].to_string(),
+                id_offset: kind.end(),
+            });
+        }
+    }
+
+    debug!(
+        "Scanned {} for {:?}: {} markers",
+        root.display(),
+        kinds,
+        markers.len()
+    );
+    Ok(markers)
+}
+
+/// Give every marker without an id a new one, returning the indices of those markers
+fn assign_ids(markers: &mut [Marker]) -> Vec<usize> {
+    let mut used: HashSet<String> = markers.iter().filter_map(|m| m.id.clone()).collect();
+    let mut assigned = Vec::new();
+
+    for (index, marker) in markers.iter_mut().enumerate() {
+        if marker.id.is_some() {
+            continue;
+        }
+        let mut salt = 0usize;
+        let id = loop {
+            let mut hasher = Sha256::new();
+            hasher.update(format!(
+                "{}\n{}\n{}\n{}",
+                marker.path, marker.kind, marker.text, salt
+            ));
+            let id = format!("{}{}", ID_PREFIX, &hex::encode(hasher.finalize())[..8]);
+            if used.insert(id.clone()) {
+                break id;
+            }
+            salt += 1;
+        };
+        marker.id = Some(id);
+        assigned.push(index);
+    }
+
+    assigned
+}
+
+/// Compute the issue operations that bring the tracker in line with the markers
+fn plan_sync(markers: &[Marker], kinds: &[String], issues: &[Issue]) -> Vec<Operation> {
+    let tag = Regex::new(&format!(
+        r"<!--\s*{}\s+({}[0-9a-f]{{8}})\s*-->",
+        regex::escape(ISSUE_TAG),
+        regex::escape(ID_PREFIX)
+    ))
+    .unwrap();
+
+    // The lowest-numbered issue wins if an id was ever filed twice
+    let mut by_id: BTreeMap<String, &Issue> = BTreeMap::new();
+    for issue in issues {
+        if let Some(captures) = tag.captures(&issue.body) {
+            let entry = by_id.entry(captures[1].to_string()).or_insert(issue);
+            if issue.number < entry.number {
+                *entry = issue;
+            }
+        }
+    }
+
+    let mut operations = Vec::new();
+    let mut seen = HashSet::new();
+    for marker in markers {
+        let Some(id) = marker.id.clone() else {
+            continue;
+        };
+        if !seen.insert(id.clone()) {
+            warn!(
+                "Marker id {} at {} is a duplicate; only its first occurrence is tracked",
+                id,
+                marker.location()
+            );
+            continue;
+        }
+
+        let title = marker.title();
+        let body = marker.body(&id);
+        match by_id.get(&id) {
+            None => operations.push(Operation::Create {
+                id,
+                title,
+                body,
+                location: marker.location(),
+            }),
+            Some(issue) => {
+                let body_changed = issue.body.replace("\r\n", "\n").trim() != body.trim();
+                if issue.is_open() && !body_changed && issue.title == title {
+                    continue;
+                }
+                operations.push(Operation::Update {
+                    number: issue.number,
+                    id,
+                    reopen: !issue.is_open(),
+                    old_title: issue.title.clone(),
+                    title,
+                    body: body_changed.then_some(body),
+                    location: marker.location(),
+                });
+            }
+        }
+    }
+
+    // Only issues of the marker kinds scanned for can have lost their marker
+    for (id, issue) in &by_id {
+        if issue.is_open() && !seen.contains(id) && issue.is_kind_of(kinds) {
+            operations.push(Operation::Close {
+                number: issue.number,
+                id: id.clone(),
+                title: issue.title.clone(),
+            });
+        }
+    }
+
+    operations
+}
+
+/// The contents of the files with newly assigned ids, inserted into their marker comments
+fn with_ids(root: &Path, markers: &[Marker], assigned: &[usize]) -> Result<Vec<(PathBuf, String)>> {
+    let mut by_file: BTreeMap<&str, Vec<&Marker>> = BTreeMap::new();
+    for &index in assigned {
+        by_file
+            .entry(markers[index].path.as_str())
+            .or_default()
+            .push(&markers[index]);
+    }
+
+    let mut updates = Vec::new();
+    for (path, file_markers) in by_file {
+        let file = root.join(path);
+        let content = fs::read_to_string(&file)?;
+        let mut lines: Vec<String> = content.split_inclusive('\n').map(String::from).collect();
+        for marker in file_markers {
+            let line = lines
+                .get_mut(marker.line - 1)
+                .ok_or_else(|| anyhow!("{} changed while syncing", marker.location()))?;
+            line.insert_str(
+                marker.id_offset,
+                &format!("[{}]", marker.id.as_deref().unwrap_or_default()),
+            );
+        }
+        updates.push((file, lines.concat()));
+    }
+    Ok(updates)
+}
+
+async fn list_issues(
+    executor: &Executor,
+    label: &str,
+    repo: Option<&str>,
+    limit: u32,
+) -> Result<Vec<Issue>> {
+    let args = vec![
+        "issue".to_string(),
+        "list".to_string(),
+        "--label".to_string(),
+        label.to_string(),
+        "--state".to_string(),
+        "all".to_string(),
+        "--limit".to_string(),
+        limit.to_string(),
+        "--json".to_string(),
+        "number,title,body,state".to_string(),
+    ];
+    let stdout = run_gh(executor, with_repo(args, repo)).await?;
+    if stdout.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&stdout).map_err(|e| anyhow!("Unexpected gh issue list output: {}", e))
+}
+
+async fn apply_operation(
+    executor: &Executor,
+    operation: &Operation,
+    label: &str,
+    repo: Option<&str>,
+) -> Result<()> {
+    match operation {
+        Operation::Create {
+            id, title, body, ..
+        } => {
+            let body_file = write_body(body)?;
+            let args = vec![
+                "issue".to_string(),
+                "create".to_string(),
+                "--title".to_string(),
+                title.clone(),
+                "--body-file".to_string(),
+                body_file.path().to_string_lossy().to_string(),
+                "--label".to_string(),
+                label.to_string(),
+            ];
+            let url = run_gh(executor, with_repo(args, repo)).await?;
+            println!("Created {} for {}", url.trim(), id);
+        }
+        Operation::Update {
+            number,
+            reopen,
+            old_title,
+            title,
+            body,
+            ..
+        } => {
+            let number = number.to_string();
+            if *reopen {
+                let args = vec!["issue".to_string(), "reopen".to_string(), number.clone()];
+                run_gh(executor, with_repo(args, repo)).await?;
+            }
+            if old_title != title || body.is_some() {
+                let mut args = vec![
+                    "issue".to_string(),
+                    "edit".to_string(),
+                    number.clone(),
+                    "--title".to_string(),
+                    title.clone(),
+                ];
+                let body_file = body.as_deref().map(write_body).transpose()?;
+                if let Some(body_file) = &body_file {
+                    args.push("--body-file".to_string());
+                    args.push(body_file.path().to_string_lossy().to_string());
+                }
+                run_gh(executor, with_repo(args, repo)).await?;
+            }
+            println!("Updated #{}", number);
+        }
+        Operation::Close { number, id, .. } => {
+            let args = vec![
+                "issue".to_string(),
+                "close".to_string(),
+                number.to_string(),
+                "--comment".to_string(),
+                format!("The {} comment was removed from the workspace.", id),
+            ];
+            run_gh(executor, with_repo(args, repo)).await?;
+            println!("Closed #{}", number);
+        }
+    }
+    Ok(())
+}
+
+fn with_repo(mut args: Vec<String>, repo: Option<&str>) -> Vec<String> {
+    if let Some(repo) = repo {
+        args.push("--repo".to_string());
+        args.push(repo.to_string());
+    }
+    args
+}
+
+/// Issue bodies are multi-line, so they are handed to gh through a file
+fn write_body(body: &str) -> Result<tempfile::NamedTempFile> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(body.as_bytes())?;
+    file.flush()?;
+    Ok(file)
+}
+
+async fn run_gh(executor: &Executor, args: Vec<String>) -> Result<String> {
+    let output = executor.execute_secure_capture("gh", &args).await?;
+    if !output.success() {
+        return Err(anyhow!(
+            "gh {} failed: {}",
+            args.iter().take(2).cloned().collect::<Vec<_>>().join(" "),
+            output.stderr.trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn kinds() -> Vec<String> {
+        vec!["TODO".to_string(), "FIXME".to_string()]
+    }
+
+    #[test]
+    fn test_scan_and_insert_ids() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("lib.rs"),
+            "fn a() {} // TODO: handle errors\n/* FIXME[td-0000abcd] leaks */\nlet todo = \"TODO: not a comment\";\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("tool.py"), "# TODO(jdfalk): port to rust\n").unwrap();
+
+        let mut markers = scan_markers(dir.path(), &kinds()).unwrap();
+        assert_eq!(markers.len(), 3);
+        assert_eq!(markers[0].text, "handle errors");
+        assert_eq!(markers[1].id.as_deref(), Some("td-0000abcd"));
+        assert_eq!(markers[1].text, "leaks");
+        assert_eq!(markers[2].text, "port to rust");
+
+        let assigned = assign_ids(&mut markers);
+        assert_eq!(assigned, vec![0, 2]);
+        for (file, content) in with_ids(dir.path(), &markers, &assigned).unwrap() {
+            fs::write(file, content).unwrap();
+        }
+
+        let rescanned = scan_markers(dir.path(), &kinds()).unwrap();
+        assert_eq!(rescanned, markers);
+        let python = fs::read_to_string(dir.path().join("tool.py")).unwrap();
+        assert_eq!(
+            python,
+            format!(
+                "# TODO[{}](jdfalk): port to rust\n",
+                markers[2].id.as_deref().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_plan_sync() {
+        let marker = |line: usize, id: &str, text: &str| Marker {
+            path: "src/lib.rs".to_string(),
+            line,
+            kind: "TODO".to_string(),
+            id: Some(id.to_string()),
+            text: text.to_string(),
+            id_offset: 0,
+        };
+        let markers = vec![
+            marker(1, "td-00000001", "new"),
+            marker(2, "td-00000002", "unchanged"),
+            marker(3, "td-00000003", "moved"),
+            marker(4, "td-00000004", "closed but present"),
+        ];
+        let issue = |number: u64, state: &str, title: &str, body: String| Issue {
+            number,
+            title: title.to_string(),
+            body,
+            state: state.to_string(),
+        };
+        let issues = vec![
+            issue(
+                10,
+                "OPEN",
+                "TODO: unchanged",
+                markers[1].body("td-00000002"),
+            ),
+            issue(
+                11,
+                "OPEN",
+                "TODO: moved",
+                marker(30, "td-00000003", "moved").body("td-00000003"),
+            ),
+            issue(
+                12,
+                "CLOSED",
+                "TODO: closed but present",
+                markers[3].body("td-00000004"),
+            ),
+            issue(
+                13,
+                "OPEN",
+                "TODO: gone",
+                marker(5, "td-00000005", "gone").body("td-00000005"),
+            ),
+            issue(
+                14,
+                "CLOSED",
+                "TODO: long gone",
+                marker(6, "td-00000006", "x").body("td-00000006"),
+            ),
+            issue(15, "OPEN", "Unrelated", "no tag".to_string()),
+        ];
+
+        let operations = plan_sync(&markers, &kinds(), &issues);
+        assert_eq!(operations.len(), 4);
+        assert!(matches!(&operations[0], Operation::Create { id, .. } if id == "td-00000001"));
+        assert!(matches!(
+            &operations[1],
+            Operation::Update {
+                number: 11,
+                reopen: false,
+                body: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(
+            &operations[2],
+            Operation::Update {
+                number: 12,
+                reopen: true,
+                body: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &operations[3],
+            Operation::Close { number: 13, .. }
+        ));
+
+        // Issues of marker kinds left out of the scan stay open
+        let operations = plan_sync(&markers, &["FIXME".to_string()], &issues);
+        assert_eq!(operations.len(), 3);
+        assert!(!operations
+            .iter()
+            .any(|operation| matches!(operation, Operation::Close { .. })));
+        let hack = issue(
+            16,
+            "OPEN",
+            "HACK in src/main.rs",
+            marker(7, "td-00000007", "").body("td-00000007"),
+        );
+        assert!(hack.is_kind_of(&["HACK".to_string()]));
+        assert!(!hack.is_kind_of(&["HA".to_string()]));
+    }
+}
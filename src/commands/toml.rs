@@ -0,0 +1,355 @@
+// file: src/commands/toml.rs
+// version: 1.0.1
+// guid: 0b7d4e29-c8a1-4f63-9d52-6e1a3f8b7c04
+
+//! `toml` command: read and edit keys of TOML files such as `Cargo.toml`
+//!
+//! Paths use the syntax of the `json` command (`package.version`,
+//! `dependencies["serde"].features`, `bin[0].name`). Edits go through `toml_edit`, so comments,
+//! key order and the layout of untouched tables are kept, and a replaced value keeps the comment
+//! that followed it. Files are rewritten atomically after the content checks; `--diff` prints
+//! the change and dry-run mode only previews it.
+
+use super::export::Effect;
+use super::file_write;
+use super::json::{self, Segment};
+use crate::executor::Executor;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::Value as Json;
+use std::fs;
+use std::path::Path;
+use toml_edit::{DocumentMut, InlineTable, Item, Value};
+
+/// Build the toml command
+pub fn build_command() -> Command {
+    Command::new("toml")
+        .about("Get, set and delete keys in TOML files, keeping comments and layout")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("get")
+                .add(Effect::ReadOnly)
+                .about("Print the value at a path as JSON")
+                .arg(json::path_arg())
+                .arg(file_arg())
+                .arg(json::raw_arg())
+                .arg(
+                    Arg::new("default")
+                        .long("default")
+                        .value_name("VALUE")
+                        .help("Print this value instead of failing when the path is missing"),
+                ),
+        )
+        .subcommand(
+            Command::new("set")
+                .add(Effect::Destructive)
+                .about("Set the value at a path, creating missing tables on the way")
+                .arg(json::path_arg())
+                .arg(Arg::new("value").required(true).help(
+                    "New value as a TOML value; text that is not valid TOML is taken as a string",
+                ))
+                .arg(file_arg())
+                .arg(
+                    Arg::new("string")
+                        .long("string")
+                        .action(ArgAction::SetTrue)
+                        .help("Always take VALUE as a string"),
+                )
+                .arg(diff_arg()),
+        )
+        .subcommand(
+            Command::new("delete")
+                .add(Effect::Destructive)
+                .about("Remove the key or array element at a path")
+                .arg(json::path_arg())
+                .arg(file_arg())
+                .arg(diff_arg()),
+        )
+}
+
+fn file_arg() -> Arg {
+    Arg::new("file")
+        .value_name("FILE")
+        .required(true)
+        .help("TOML file")
+}
+
+fn diff_arg() -> Arg {
+    Arg::new("diff")
+        .long("diff")
+        .action(ArgAction::SetTrue)
+        .help("Print the change as a diff")
+}
+
+/// Execute toml subcommands
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let (name, sub) = matches
+        .subcommand()
+        .ok_or_else(|| anyhow!("Unknown toml subcommand"))?;
+    let file = json::string(sub, "file");
+    let path = executor.check_path(file)?;
+    let text = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", file))?;
+    let mut document: DocumentMut = text
+        .parse()
+        .map_err(|e| anyhow!("Failed to parse {}: {}", file, e))?;
+    let segments = json::parse_path(json::string(sub, "path"))?;
+    if !json::is_singular(&segments) {
+        bail!("Wildcards and '..' are not supported by the toml command");
+    }
+
+    match name {
+        "get" => {
+            let default = sub
+                .get_one::<String>("default")
+                .map(|raw| to_json(&parse_value(raw, false)))
+                .transpose()?;
+            json::print_value(&to_json_document(&text)?, sub, default)
+        }
+        "set" => {
+            let value = parse_value(json::string(sub, "value"), sub.get_flag("string"));
+            set(document.as_item_mut(), &segments, value)
+                .with_context(|| format!("Cannot set '{}'", json::string(sub, "path")))?;
+            write(executor, Path::new(file), &document, sub)
+        }
+        "delete" => {
+            delete(document.as_item_mut(), &segments)
+                .with_context(|| format!("Cannot delete '{}'", json::string(sub, "path")))?;
+            write(executor, Path::new(file), &document, sub)
+        }
+        _ => Err(anyhow!("Unknown toml subcommand")),
+    }
+}
+
+fn write(
+    executor: &Executor,
+    path: &Path,
+    document: &DocumentMut,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let updated = document.to_string();
+    // Never write a file that no longer parses
+    updated
+        .parse::<toml::Table>()
+        .map_err(|e| anyhow!("Refusing to write {}: {}", path.display(), e))?;
+    file_write::update(executor, path, updated.as_bytes(), matches.get_flag("diff"))
+}
+
+/// Parse a value argument as a TOML value, falling back to a string
+fn parse_value(raw: &str, force_string: bool) -> Value {
+    if !force_string {
+        if let Ok(mut value) = raw.parse::<Value>() {
+            value.decor_mut().clear();
+            return value;
+        }
+    }
+    Value::from(raw)
+}
+
+/// Set the value at a path of keys and indexes below `item`
+fn set(item: &mut Item, path: &[Segment], value: Value) -> Result<()> {
+    match path {
+        [] => bail!("cannot replace the whole document"),
+        [Segment::Key(key)] => {
+            let description = kind(item);
+            let table = item
+                .as_table_like_mut()
+                .ok_or_else(|| anyhow!("cannot set '{}' in {}", key, description))?;
+            let mut value = value;
+            // Keep the comment that followed the old value
+            if let Some(existing) = table.get(key).and_then(Item::as_value) {
+                *value.decor_mut() = existing.decor().clone();
+            }
+            table.insert(key, Item::Value(value));
+            Ok(())
+        }
+        [Segment::Index(index)] => {
+            let description = kind(item);
+            let Some(array) = item.as_array_mut() else {
+                bail!("cannot set element {} of {}", index, description);
+            };
+            if usize::try_from(*index).ok() == Some(array.len()) {
+                array.push(value);
+                return Ok(());
+            }
+            let position = json::resolve_index(*index, array.len()).ok_or_else(|| {
+                anyhow!(
+                    "index {} is out of range for {} elements",
+                    index,
+                    array.len()
+                )
+            })?;
+            let mut value = value;
+            *value.decor_mut() = array.get(position).unwrap().decor().clone();
+            array.replace_formatted(position, value);
+            Ok(())
+        }
+        [first, rest @ ..] => with_child(item, first, true, |child| set(child, rest, value)),
+    }
+}
+
+/// Remove the value at a path of keys and indexes below `item`
+fn delete(item: &mut Item, path: &[Segment]) -> Result<()> {
+    match path {
+        [] => bail!("cannot delete the whole document"),
+        [Segment::Key(key)] => item
+            .as_table_like_mut()
+            .and_then(|table| table.remove(key))
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("no value at this path")),
+        [Segment::Index(index)] => {
+            match item {
+                Item::ArrayOfTables(tables) => {
+                    let position = json::resolve_index(*index, tables.len())
+                        .ok_or_else(|| anyhow!("no value at this path"))?;
+                    tables.remove(position);
+                }
+                Item::Value(Value::Array(array)) => {
+                    let position = json::resolve_index(*index, array.len())
+                        .ok_or_else(|| anyhow!("no value at this path"))?;
+                    array.remove(position);
+                }
+                _ => bail!("no value at this path"),
+            }
+            Ok(())
+        }
+        [first, rest @ ..] => with_child(item, first, false, |child| delete(child, rest)),
+    }
+}
+
+/// Run `f` on the child of `item` named by `segment`, creating a missing table when `create`
+///
+/// Array elements are not items, so an element is moved into a temporary item for `f` and
+/// moved back afterwards.
+fn with_child<R>(
+    item: &mut Item,
+    segment: &Segment,
+    create: bool,
+    f: impl FnOnce(&mut Item) -> Result<R>,
+) -> Result<R> {
+    match segment {
+        Segment::Key(key) => {
+            let inline = item.is_value();
+            let description = kind(item);
+            let table = item
+                .as_table_like_mut()
+                .ok_or_else(|| anyhow!("'{}' is not a key of {}", key, description))?;
+            if table.get(key).is_none() {
+                if !create {
+                    bail!("no value at this path");
+                }
+                let child = if inline {
+                    toml_edit::value(InlineTable::new())
+                } else {
+                    toml_edit::table()
+                };
+                table.insert(key, child);
+            }
+            f(table.get_mut(key).unwrap())
+        }
+        Segment::Index(index) => match item {
+            Item::ArrayOfTables(tables) => {
+                let position = json::resolve_index(*index, tables.len())
+                    .ok_or_else(|| anyhow!("index {} is out of range", index))?;
+                let table = tables.get_mut(position).unwrap();
+                let mut child = Item::Table(std::mem::take(table));
+                let result = f(&mut child);
+                *table = child
+                    .into_table()
+                    .map_err(|_| anyhow!("element {} must stay a table", index))?;
+                result
+            }
+            Item::Value(Value::Array(array)) => {
+                let position = json::resolve_index(*index, array.len())
+                    .ok_or_else(|| anyhow!("index {} is out of range", index))?;
+                let element = array.get_mut(position).unwrap();
+                let mut child = Item::Value(std::mem::replace(element, Value::from(false)));
+                let result = f(&mut child);
+                *element = child
+                    .into_value()
+                    .map_err(|_| anyhow!("element {} must stay a value", index))?;
+                result
+            }
+            _ => bail!("cannot index into {}", kind(item)),
+        },
+        Segment::Wildcard | Segment::Descend => {
+            bail!("wildcards and '..' are not supported")
+        }
+    }
+}
+
+fn kind(item: &Item) -> &'static str {
+    match item {
+        Item::None => "nothing",
+        Item::Table(_) | Item::Value(Value::InlineTable(_)) => "a table",
+        Item::ArrayOfTables(_) | Item::Value(Value::Array(_)) => "an array",
+        Item::Value(Value::String(_)) => "a string",
+        Item::Value(_) => "a scalar",
+    }
+}
+
+fn to_json_document(text: &str) -> Result<Json> {
+    let table: toml::Table = text.parse()?;
+    Ok(convert(toml::Value::Table(table)))
+}
+
+fn to_json(value: &Value) -> Result<Json> {
+    let mut table: toml::Table = format!("v = {}", value).parse()?;
+    Ok(convert(
+        table.remove("v").unwrap_or(toml::Value::Boolean(false)),
+    ))
+}
+
+/// Convert a TOML value to JSON; dates and times become strings
+fn convert(value: toml::Value) -> Json {
+    match value {
+        toml::Value::String(text) => Json::String(text),
+        toml::Value::Integer(n) => Json::from(n),
+        toml::Value::Float(n) => Json::from(n),
+        toml::Value::Boolean(b) => Json::Bool(b),
+        toml::Value::Datetime(datetime) => Json::String(datetime.to_string()),
+        toml::Value::Array(items) => Json::Array(items.into_iter().map(convert).collect()),
+        toml::Value::Table(table) => Json::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, convert(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(text: &str, path: &str, value: Option<&str>) -> Result<String> {
+        let mut document: DocumentMut = text.parse()?;
+        let segments = json::parse_path(path)?;
+        match value {
+            Some(raw) => set(document.as_item_mut(), &segments, parse_value(raw, false))?,
+            None => delete(document.as_item_mut(), &segments)?,
+        }
+        Ok(document.to_string())
+    }
+
+    #[test]
+    fn test_edits_keep_comments_and_layout() {
+        let text = "# manifest\n[package]\nname = \"app\"\nversion = \"0.1.0\" # bump me\n\n[[bin]]\nname = \"a\"\n\n[dependencies]\nserde = { version = \"1\", features = [\"derive\"] }\n";
+        assert_eq!(
+            edit(text, "package.version", Some("0.2.0")).unwrap(),
+            text.replace("\"0.1.0\" # bump me", "\"0.2.0\" # bump me")
+        );
+        let updated = edit(text, "dependencies.serde.features[1]", Some("\"rc\"")).unwrap();
+        assert!(updated.contains("features = [\"derive\", \"rc\"]"));
+        let updated = edit(text, "bin[0].path", Some("src/a.rs")).unwrap();
+        assert!(updated.contains("[[bin]]\nname = \"a\"\npath = \"src/a.rs\"\n"));
+        let updated = edit(text, "profile.release.lto", Some("true")).unwrap();
+        assert!(updated.ends_with("[profile.release]\nlto = true\n"));
+        let updated = edit(text, "dependencies.serde", None).unwrap();
+        assert!(updated.starts_with("# manifest\n") && !updated.contains("serde"));
+        assert!(edit(text, "package.name.first", Some("1")).is_err());
+        assert!(edit(text, "package.missing", None).is_err());
+
+        let json = to_json_document(text).unwrap();
+        assert_eq!(json["dependencies"]["serde"]["features"][0], "derive");
+    }
+}
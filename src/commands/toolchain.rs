@@ -0,0 +1,410 @@
+// file: src/commands/toolchain.rs
+// version: 1.0.1
+// guid: c41e8b27-9f5d-4a63-8e0b-71d2a6f3c958
+
+//! `toolchain` command: download verified tool releases into the managed tools directory
+//!
+//! Downloads run `curl` directly rather than through the executor: the URL comes from the
+//! release catalog or the user, not from an agent's command line, so the allowlist that keeps
+//! `curl` away from agents does not apply. Policy still does, through the same evaluation
+//! the executor makes, so a profile that denies `curl` also denies `toolchain install`.
+
+use super::export::Effect;
+use crate::activity::format_age;
+use crate::capability::CapabilityQueue;
+use crate::error::AgentError;
+use crate::executor::Executor;
+use crate::toolchain::{
+    self, find_checksum, Checksums, InstalledTool, Platform, Release, Toolchain,
+};
+use crate::tools::{self, join_version};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use std::process::{Command as Process, Stdio};
+use tracing::info;
+
+/// Build the toolchain command
+pub fn build_command() -> Command {
+    Command::new("toolchain")
+        .about("Download verified tool releases and run them instead of the ones on PATH")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("install")
+                .about("Download, verify and activate a tool version")
+                .long_about(
+                    "Download a release into the managed tools directory, check its SHA-256 \
+                     against the checksums published with it (or --sha256), and make it the \
+                     version commands run. Known tools: buf, golangci-lint, rg, shfmt; others \
+                     need --url and --sha256.",
+                )
+                .arg(
+                    Arg::new("tool")
+                        .value_name("TOOL@VERSION")
+                        .required(true)
+                        .help("Tool and version, e.g. buf@1.32.0"),
+                )
+                .arg(
+                    Arg::new("url")
+                        .long("url")
+                        .value_name("URL")
+                        .help("Download from this https:// or file:// URL; {version} is replaced"),
+                )
+                .arg(
+                    Arg::new("sha256")
+                        .long("sha256")
+                        .value_name("HASH")
+                        .help("Expected SHA-256 of the download"),
+                )
+                .arg(
+                    Arg::new("binary")
+                        .long("binary")
+                        .value_name("PATH")
+                        .help("Path of the binary inside a downloaded archive"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Download again even if the version is installed"),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .add(Effect::ReadOnly)
+                .about("List the installed tool versions")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove installed versions of a tool")
+                .arg(
+                    Arg::new("tool")
+                        .value_name("TOOL[@VERSION]")
+                        .required(true)
+                        .help("Tool, or one version of it"),
+                ),
+        )
+}
+
+/// Execute the toolchain command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("install", sub_matches)) => install(sub_matches, executor),
+        Some(("list", sub_matches)) => list(sub_matches),
+        Some(("remove", sub_matches)) => remove(sub_matches, executor),
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+fn parse_spec(spec: &str) -> Result<(&str, Option<&str>)> {
+    let (name, version) = match spec.split_once('@') {
+        Some((name, version)) => (name, Some(version.strip_prefix('v').unwrap_or(version))),
+        None => (spec, None),
+    };
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._-+".contains(c))
+    };
+    if !valid(name) || !version.map_or(true, valid) {
+        bail!("Invalid tool '{}': expected TOOL or TOOL@VERSION", spec);
+    }
+    Ok((name, version))
+}
+
+fn install(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let spec = matches.get_one::<String>("tool").unwrap();
+    let (name, Some(version)) = parse_spec(spec)? else {
+        bail!("Give the version to install, e.g. {}@1.2.3", spec);
+    };
+    let expected = matches
+        .get_one::<String>("sha256")
+        .map(|hash| hash.to_ascii_lowercase());
+
+    let release = match matches.get_one::<String>("url") {
+        Some(url) => Release {
+            url: url.replace("{version}", version),
+            checksums: Checksums::Given,
+            binary: matches.get_one::<String>("binary").cloned(),
+        },
+        None => {
+            let mut release =
+                toolchain::release(name, version, Platform::current()).ok_or_else(|| {
+                    anyhow!(
+                        "No known release of {} for this platform (known: {}); give --url and \
+                         --sha256",
+                        name,
+                        toolchain::KNOWN_TOOLS.join(", ")
+                    )
+                })?;
+            if let Some(binary) = matches.get_one::<String>("binary") {
+                release.binary = Some(binary.clone());
+            }
+            release
+        }
+    };
+    if release.checksums == Checksums::Given && expected.is_none() {
+        bail!("--sha256 is required for downloads without published checksums");
+    }
+    let remote = !release.url.starts_with("file://");
+    if remote && !release.url.starts_with("https://") {
+        bail!(
+            "Only https:// and file:// URLs are accepted: {}",
+            release.url
+        );
+    }
+
+    let installed = Toolchain::load()?;
+    if let Some(tool) = installed.find(name, version) {
+        if tool.binary.is_file() && !matches.get_flag("force") {
+            if executor.config().safety.dry_run {
+                println!("DRY RUN: Would activate {} {}", name, version);
+                return Ok(());
+            }
+            toolchain::activate(name, &tool.binary)?;
+            let tool = tool.clone();
+            Toolchain::update(|toolchain| {
+                toolchain.install(InstalledTool {
+                    active: true,
+                    ..tool
+                })
+            })?;
+            println!("{} {} is already installed; now active", name, version);
+            return Ok(());
+        }
+    }
+
+    if remote {
+        // A denial is reported before anything is downloaded
+        executor.policy().evaluate("curl", std::slice::from_ref(&release.url))?;
+    }
+    let target = toolchain::tools_dir().join(name).join(version);
+    if executor.config().safety.dry_run {
+        println!(
+            "DRY RUN: Would download {}, verify its SHA-256 and install {} {} into {}",
+            release.url,
+            name,
+            version,
+            target.display()
+        );
+        return Ok(());
+    }
+
+    let tools_dir = toolchain::tools_dir();
+    fs::create_dir_all(&tools_dir)?;
+    // Downloads are unpacked next to their destination, so nothing half-written is installed
+    let work = tempfile::Builder::new()
+        .prefix(".download-")
+        .tempdir_in(&tools_dir)?;
+    let download = work.path().join(release.file_name());
+    fetch(&release.url, &download)?;
+    let sha256 = hex::encode(Sha256::digest(fs::read(&download)?));
+
+    if let Checksums::File(url) = &release.checksums {
+        let listing = work.path().join("checksums");
+        fetch(url, &listing)?;
+        let published = find_checksum(&fs::read_to_string(&listing)?, release.file_name())
+            .ok_or_else(|| anyhow!("{} lists no checksum for {}", url, release.file_name()))?;
+        verify(&release.url, &sha256, &published)?;
+    }
+    if let Some(expected) = &expected {
+        verify(&release.url, &sha256, expected)?;
+    }
+    println!("Verified {} (sha256 {})", release.file_name(), sha256);
+
+    let source = match &release.binary {
+        None => download.clone(),
+        Some(inner) => {
+            let unpacked = work.path().join("unpacked");
+            fs::create_dir_all(&unpacked)?;
+            unpack(&download, &unpacked)?;
+            let binary = unpacked.join(inner);
+            if !binary.is_file() {
+                bail!("{} has no {}", release.file_name(), inner);
+            }
+            binary
+        }
+    };
+    if target.exists() {
+        fs::remove_dir_all(&target)?;
+    }
+    fs::create_dir_all(&target)?;
+    let binary = target.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    fs::rename(&source, &binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755))?;
+    }
+    let link = toolchain::activate(name, &binary)?;
+
+    Toolchain::update(|toolchain| {
+        toolchain.install(InstalledTool {
+            name: name.to_string(),
+            version: version.to_string(),
+            binary: binary.clone(),
+            url: release.url.clone(),
+            sha256,
+            active: true,
+            installed_at: Utc::now(),
+        })
+    })?;
+    let approved = CapabilityQueue::update(|queue| queue.remove(name))?;
+
+    match tools::tool_version(&binary) {
+        Ok(found) => println!(
+            "Installed {} {} ({} reports {})",
+            name,
+            version,
+            link.display(),
+            join_version(&found)
+        ),
+        Err(_) => println!("Installed {} {} ({})", name, version, link.display()),
+    }
+    if approved > 0 {
+        println!("Approved {} capability request(s) for {}", approved, name);
+    }
+    Ok(())
+}
+
+/// Download a URL to a file; `file://` URLs are copied
+fn fetch(url: &str, dest: &Path) -> Result<()> {
+    if let Some(path) = url.strip_prefix("file://") {
+        fs::copy(path, dest).with_context(|| format!("Failed to read {}", path))?;
+        return Ok(());
+    }
+    info!("Downloading {}", url);
+    let curl = which::which("curl")
+        .map_err(|_| anyhow!("Downloading {} needs curl, which is not installed", url))?;
+    let output = Process::new(curl)
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args([
+            "--proto",
+            "=https",
+            "--proto-redir",
+            "=https",
+            "--retry",
+            "2",
+        ])
+        .arg("--output")
+        .arg(dest)
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to run curl")?;
+    if !output.status.success() {
+        bail!(
+            "Download of {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn verify(url: &str, actual: &str, expected: &str) -> Result<()> {
+    if actual != expected {
+        return Err(AgentError::security(format!(
+            "Checksum mismatch for {}: expected sha256 {}, got {}",
+            url, expected, actual
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Unpack a `.tar.gz`, `.tar.xz` or `.zip` archive with the system `tar`
+fn unpack(archive: &Path, dest: &Path) -> Result<()> {
+    let output = Process::new("tar")
+        .arg("-xf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dest)
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to run tar")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to unpack {}: {}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn list(matches: &ArgMatches) -> Result<()> {
+    let toolchain = Toolchain::load()?;
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&toolchain.tools)?);
+        return Ok(());
+    }
+    if toolchain.tools.is_empty() {
+        println!("No managed tools; install one with `toolchain install TOOL@VERSION`");
+        return Ok(());
+    }
+    let now = Utc::now();
+    println!(
+        "{:<16} {:<12} {:<6} {:>8}  SHA256",
+        "TOOL", "VERSION", "ACTIVE", "AGE"
+    );
+    for tool in &toolchain.tools {
+        println!(
+            "{:<16} {:<12} {:<6} {:>8}  {}",
+            tool.name,
+            tool.version,
+            if tool.active { "yes" } else { "" },
+            format_age(now - tool.installed_at),
+            &tool.sha256[..16.min(tool.sha256.len())]
+        );
+    }
+    Ok(())
+}
+
+fn remove(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let spec = matches.get_one::<String>("tool").unwrap();
+    let (name, version) = parse_spec(spec)?;
+    let matching = |tool: &InstalledTool| {
+        tool.name == name && version.map_or(true, |version| tool.version == version)
+    };
+    let removed: Vec<InstalledTool> = Toolchain::load()?
+        .tools
+        .into_iter()
+        .filter(|tool| matching(tool))
+        .collect();
+    if removed.is_empty() {
+        bail!("{} is not installed", spec);
+    }
+    if executor.config().safety.dry_run {
+        for tool in &removed {
+            println!("DRY RUN: Would remove {} {}", tool.name, tool.version);
+        }
+        return Ok(());
+    }
+
+    Toolchain::update(|toolchain| toolchain.tools.retain(|tool| !matching(tool)))?;
+    for tool in &removed {
+        if tool.active {
+            toolchain::deactivate(name)?;
+        }
+        let dir = toolchain::tools_dir().join(&tool.name).join(&tool.version);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        println!("Removed {} {}", tool.name, tool.version);
+    }
+    Ok(())
+}
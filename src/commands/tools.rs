@@ -0,0 +1,137 @@
+// file: src/commands/tools.rs
+// version: 1.0.0
+// guid: e61d0c4b-2a95-4f7e-8b13-9c5a7f2e0d48
+
+//! `tools` command: install missing tools and review queued capability requests
+
+use crate::activity::format_age;
+use crate::capability::{CapabilityQueue, CapabilityRequest};
+use crate::executor::Executor;
+use anyhow::{bail, Result};
+use chrono::Utc;
+use clap::{Arg, ArgMatches, Command};
+
+/// Build the tools command
+pub fn build_command() -> Command {
+    Command::new("tools")
+        .about("Install missing tools and review capability requests")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("install")
+                .about("Install tools with mise, approving their queued requests")
+                .long_about(
+                    "Install tools globally with `mise use --global`, at the version given after \
+                     @ or the latest one, and remove their queued capability requests.",
+                )
+                .arg(
+                    Arg::new("tools")
+                        .value_name("TOOL[@VERSION]")
+                        .num_args(1..)
+                        .required(true)
+                        .help("Tools to install, e.g. node@20 or protoc"),
+                ),
+        )
+        .subcommand(
+            Command::new("requests")
+                .about("List the capability requests waiting for approval")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("dismiss")
+                .about("Remove the queued capability requests for tools without installing them")
+                .arg(
+                    Arg::new("tools")
+                        .value_name("TOOL")
+                        .num_args(1..)
+                        .required(true)
+                        .help("Tools whose requests to remove"),
+                ),
+        )
+}
+
+/// Execute the tools command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    match matches.subcommand() {
+        Some(("install", sub_matches)) => install(sub_matches, executor).await,
+        Some(("requests", sub_matches)) => requests(sub_matches),
+        Some(("dismiss", sub_matches)) => {
+            for tool in sub_matches.get_many::<String>("tools").unwrap() {
+                let removed = CapabilityQueue::update(|queue| queue.remove(tool))?;
+                println!("Dismissed {} request(s) for {}", removed, tool);
+            }
+            Ok(())
+        }
+        _ => unreachable!("subcommand required"),
+    }
+}
+
+async fn install(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    if which::which("mise").is_err() {
+        bail!("Installing tools needs mise (https://mise.jdx.dev), which is not installed");
+    }
+    for spec in matches.get_many::<String>("tools").unwrap() {
+        let (tool, version) = spec.split_once('@').unwrap_or((spec.as_str(), "latest"));
+        if tool.is_empty() || version.is_empty() {
+            bail!("Invalid tool '{}': expected TOOL or TOOL@VERSION", spec);
+        }
+        executor
+            .execute_secure(
+                "mise",
+                &["use", "--global", &format!("{}@{}", tool, version)],
+            )
+            .await?;
+        if executor.config().safety.dry_run {
+            continue;
+        }
+        let approved = CapabilityQueue::update(|queue| queue.remove(tool))?;
+        match approved {
+            0 => println!("Installed {}@{}", tool, version),
+            n => println!("Installed {}@{}, approving {} request(s)", tool, version, n),
+        }
+    }
+    Ok(())
+}
+
+fn requests(matches: &ArgMatches) -> Result<()> {
+    let queue = CapabilityQueue::load()?;
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&queue.requests)?);
+        return Ok(());
+    }
+    if queue.requests.is_empty() {
+        println!("No capability requests");
+        return Ok(());
+    }
+    let now = Utc::now();
+    println!(
+        "{:<16} {:<10} {:<12} {:>8}  NEEDED BY",
+        "TOOL", "VERSION", "PROBLEM", "AGE"
+    );
+    for request in &queue.requests {
+        println!(
+            "{:<16} {:<10} {:<12} {:>8}  {}",
+            request.tool,
+            request.required_version.as_deref().unwrap_or("-"),
+            problem(request),
+            format_age(now - request.requested_at),
+            request.needed_by.join(" ")
+        );
+    }
+    eprintln!("Approve with `tools install TOOL[@VERSION]`, or remove with `tools dismiss TOOL`");
+    Ok(())
+}
+
+fn problem(request: &CapabilityRequest) -> String {
+    match &request.found_version {
+        Some(found) => format!("has {}", found),
+        None => "missing".to_string(),
+    }
+}
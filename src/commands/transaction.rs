@@ -0,0 +1,244 @@
+// file: src/commands/transaction.rs
+// version: 1.0.1
+// guid: e1a64c93-0b7f-4d25-9c8e-3f52d7a0b816
+
+//! `transaction` command: run file-modifying steps in order and undo them all if one fails
+//!
+//! The files under the watched paths are backed up first (see [`crate::transaction`]). A step
+//! fails when it exits non-zero or cannot run; the remaining steps are skipped and every file
+//! the steps touched is restored. The outcome is recorded in the audit log.
+
+use super::export::Effect;
+use crate::batch::CommandSpec;
+use crate::executor::{CommandOutput, Executor};
+use crate::security::audit::{self, ExecutionResult};
+use crate::snapshot::Changes;
+use crate::transaction::Transaction;
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use serde_json::json;
+use std::path::PathBuf;
+
+/// Build the transaction command
+pub fn build_command() -> Command {
+    Command::new("transaction")
+        .add(Effect::Destructive)
+        .about("Run file-modifying steps in order, restoring every touched file if one fails")
+        .long_about(
+            "Backs up the files in the workspace, or under --path, then runs each step in \
+             order. Each step is a single argument split into words like a shell would, but no \
+             shell is run. If a step fails, the rest are skipped and the files the steps \
+             created, modified or deleted are restored. The outcome is recorded in the audit \
+             log.",
+        )
+        .arg(
+            Arg::new("steps")
+                .value_name("STEP")
+                .num_args(1..)
+                .required(true)
+                .help("Command lines to run in order, e.g. 'buf generate' 'prettier --write gen'"),
+        )
+        .arg(
+            Arg::new("path")
+                .long("path")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .help("Back up and restore only these paths (default: the workspace)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Output format"),
+        )
+}
+
+/// Result of one step
+#[derive(Debug, Serialize)]
+struct Step {
+    command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    stdout: String,
+    stderr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Step {
+    fn failed(&self) -> bool {
+        self.error.is_some() || self.exit_code != Some(0)
+    }
+}
+
+/// Execute the transaction command
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let specs = matches
+        .get_many::<String>("steps")
+        .unwrap_or_default()
+        .map(|line| CommandSpec::parse(line).map_err(|e| anyhow!(e)))
+        .collect::<Result<Vec<_>>>()?;
+    let paths: Vec<PathBuf> = matches
+        .get_many::<String>("path")
+        .unwrap_or_default()
+        .map(PathBuf::from)
+        .collect();
+    let json = matches.get_one::<String>("format").map(String::as_str) == Some("json");
+    let scope = executor.watch_scope(&paths)?;
+
+    // Nothing is written in a dry run, so there is nothing to back up
+    if executor.config().safety.dry_run {
+        for spec in &specs {
+            executor.execute_spec_capture(spec).await?;
+        }
+        println!(
+            "DRY RUN: Would run {} steps as a transaction, restoring {} if one fails",
+            specs.len(),
+            describe_roots(&scope.roots)
+        );
+        return Ok(());
+    }
+
+    let transaction = Transaction::begin(&scope)?;
+    let id = transaction.id().to_string();
+    let lines: Vec<String> = specs.iter().map(ToString::to_string).collect();
+    let mut steps = Vec::new();
+    for spec in &specs {
+        let step = match executor.execute_spec_capture(spec).await {
+            Ok(output) => step_from_output(spec, output),
+            Err(e) => Step {
+                command: spec.to_string(),
+                exit_code: None,
+                stdout: String::new(),
+                stderr: String::new(),
+                error: Some(e.to_string()),
+            },
+        };
+        if !json {
+            print_step(&step);
+        }
+        let failed = step.failed();
+        steps.push(step);
+        if failed {
+            break;
+        }
+    }
+
+    let failed = steps
+        .last()
+        .filter(|step| step.failed())
+        .map(|_| steps.len());
+    let (changes, result) = match failed {
+        None => (transaction.commit()?, ExecutionResult::Allowed),
+        Some(step) => {
+            let changes = transaction.rollback()?;
+            let reason = format!("Step {} failed: {}", step, lines[step - 1]);
+            (changes, ExecutionResult::Error(reason))
+        }
+    };
+    audit::log_transaction(&id, &lines, result, change_notes(&changes));
+
+    if json {
+        let report = json!({
+            "id": id,
+            "status": if failed.is_some() { "rolled_back" } else { "committed" },
+            "failed_step": failed,
+            "steps": steps,
+            "changes": changes,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_outcome(&id, failed, steps.len(), specs.len(), &changes);
+    }
+    if let Some(step) = failed {
+        bail!(
+            "Transaction {} rolled back: step {} ({}) failed",
+            id,
+            step,
+            lines[step - 1]
+        );
+    }
+    Ok(())
+}
+
+fn step_from_output(spec: &CommandSpec, output: CommandOutput) -> Step {
+    Step {
+        command: spec.to_string(),
+        exit_code: output.exit_code,
+        stdout: output.stdout,
+        stderr: output.stderr,
+        error: None,
+    }
+}
+
+fn print_step(step: &Step) {
+    let status = match (&step.error, step.exit_code) {
+        (Some(_), _) => "error".to_string(),
+        (None, Some(0)) => "ok".to_string(),
+        (None, Some(code)) => format!("exit {}", code),
+        (None, None) => "killed".to_string(),
+    };
+    println!("==> [{}] {}", status, step.command);
+    print!("{}", step.stdout);
+    eprint!("{}", step.stderr);
+    if let Some(error) = &step.error {
+        println!("    {}", error);
+    }
+}
+
+fn print_outcome(id: &str, failed: Option<usize>, ran: usize, total: usize, changes: &Changes) {
+    let kinds = [
+        ("created", &changes.created),
+        ("modified", &changes.modified),
+        ("deleted", &changes.deleted),
+    ];
+    match failed {
+        None => println!("Transaction {} committed: {} steps succeeded", id, total),
+        Some(step) => {
+            println!(
+                "Transaction {} rolled back after step {} of {} failed; {} skipped",
+                id,
+                step,
+                total,
+                total - ran
+            );
+            if !changes.is_empty() {
+                println!("Restored:");
+            }
+        }
+    }
+    for (kind, paths) in kinds {
+        for path in paths {
+            println!("  {:<9}{}", kind, path.display());
+        }
+    }
+}
+
+/// Audit notes listing the files a transaction changed, or restored when it was rolled back
+fn change_notes(changes: &Changes) -> Vec<String> {
+    let kinds = [
+        ("created", &changes.created),
+        ("modified", &changes.modified),
+        ("deleted", &changes.deleted),
+    ];
+    kinds
+        .iter()
+        .flat_map(|(kind, paths)| {
+            paths
+                .iter()
+                .map(move |path| format!("{} {}", kind, path.display()))
+        })
+        .collect()
+}
+
+fn describe_roots(roots: &[PathBuf]) -> String {
+    roots
+        .iter()
+        .map(|root| root.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,8 +1,11 @@
 // file: src/commands/uutils.rs
-// version: 1.0.0
+// version: 1.4.1
 // guid: 22b67d94-f0e5-4823-8a59-3d7c8b4f6a2e
 
+use super::export::Effect;
 use crate::executor::Executor;
+use crate::guardrails::{self, Estimate};
+use crate::session;
 use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches, Command};
 use std::process::Command as StdCommand;
@@ -10,6 +13,9 @@ use std::process::Stdio;
 use std::env;
 use tracing::{debug, error, info};
 
+/// Utilities whose arguments never name files, exempt from the workspace root check
+const PATHLESS: &[&str] = &["echo", "printf", "yes", "sleep", "uname", "who", "pwd", "tr"];
+
 /// Helper function to append additional arguments from environment variable
 fn append_additional_args(mut args: Vec<String>) -> Vec<String> {
     if let Ok(additional_args_str) = env::var("COPILOT_AGENT_ADDITIONAL_ARGS") {
@@ -29,6 +35,7 @@ pub fn build_command() -> Command {
         .about("Unix utilities using uutils/coreutils")
         .subcommand(
             Command::new("find")
+                .add(Effect::ReadOnly)
                 .about("Find files and directories")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to find command")
@@ -38,6 +45,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("ls")
+                .add(Effect::ReadOnly)
                 .about("List directory contents (enhanced ls)")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to ls command")
@@ -47,6 +55,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("cat")
+                .add(Effect::ReadOnly)
                 .about("Display file contents")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to cat command")
@@ -56,6 +65,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("grep")
+                .add(Effect::ReadOnly)
                 .about("Search text patterns")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to grep command")
@@ -65,6 +75,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("sort")
+                .add(Effect::Destructive)
                 .about("Sort lines of text")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to sort command")
@@ -74,6 +85,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("wc")
+                .add(Effect::ReadOnly)
                 .about("Word, line, character, and byte count")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to wc command")
@@ -83,6 +95,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("du")
+                .add(Effect::ReadOnly)
                 .about("Display directory space usage")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to du command")
@@ -92,6 +105,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("df")
+                .add(Effect::ReadOnly)
                 .about("Display filesystem disk space usage")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to df command")
@@ -101,6 +115,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("cp")
+                .add(Effect::Destructive)
                 .about("Copy files or directories")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to cp command")
@@ -110,6 +125,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("mv")
+                .add(Effect::Destructive)
                 .about("Move/rename files or directories")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to mv command")
@@ -119,6 +135,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("rm")
+                .add(Effect::Destructive)
                 .about("Remove files or directories")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to rm command")
@@ -137,6 +154,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("rmdir")
+                .add(Effect::Destructive)
                 .about("Remove empty directories")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to rmdir command")
@@ -146,6 +164,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("chmod")
+                .add(Effect::Destructive)
                 .about("Change file permissions")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to chmod command")
@@ -155,6 +174,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("chown")
+                .add(Effect::Destructive)
                 .about("Change file ownership")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to chown command")
@@ -173,6 +193,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("stat")
+                .add(Effect::ReadOnly)
                 .about("Display file or filesystem status")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to stat command")
@@ -182,6 +203,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("pwd")
+                .add(Effect::ReadOnly)
                 .about("Print working directory")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to pwd command")
@@ -191,6 +213,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("who")
+                .add(Effect::ReadOnly)
                 .about("Show who is logged on")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to who command")
@@ -200,6 +223,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("uname")
+                .add(Effect::ReadOnly)
                 .about("Show system information")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to uname command")
@@ -209,6 +233,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("head")
+                .add(Effect::ReadOnly)
                 .about("Show first lines of file")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to head command")
@@ -218,6 +243,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("tail")
+                .add(Effect::ReadOnly)
                 .about("Show last lines of file")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to tail command")
@@ -227,6 +253,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("diff")
+                .add(Effect::ReadOnly)
                 .about("Compare files line by line")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to diff command")
@@ -236,6 +263,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("uniq")
+                .add(Effect::ReadOnly)
                 .about("Extract and list unique lines")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to uniq command")
@@ -245,6 +273,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("cut")
+                .add(Effect::ReadOnly)
                 .about("Cut out selected portions of each line")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to cut command")
@@ -254,6 +283,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("tr")
+                .add(Effect::ReadOnly)
                 .about("Translate or delete characters")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to tr command")
@@ -281,6 +311,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("sleep")
+                .add(Effect::ReadOnly)
                 .about("Delay for a specified amount of time")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to sleep command")
@@ -290,6 +321,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("echo")
+                .add(Effect::ReadOnly)
                 .about("Print text to standard output")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to echo command")
@@ -299,6 +331,7 @@ pub fn build_command() -> Command {
         )
         .subcommand(
             Command::new("printf")
+                .add(Effect::ReadOnly)
                 .about("Print text with formatting")
                 .arg(Arg::new("args")
                     .help("Arguments to pass to printf command")
@@ -308,16 +341,28 @@ pub fn build_command() -> Command {
         )
 }
 
-pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
     info!("Executing uutils command");
 
     match matches.subcommand() {
         Some((command, sub_matches)) => {
-            let args = sub_matches
+            let args: Vec<String> = sub_matches
                 .get_many::<String>("args")
                 .map(|values| values.map(|s| s.to_string()).collect())
                 .unwrap_or_default();
 
+            let args = append_additional_args(args);
+            executor.check_policy(command, &args)?;
+            if !PATHLESS.contains(&command) {
+                executor.check_path_args(command, &args)?;
+            }
+
+            if let Some((paths, recursive)) = search_paths(command, &args) {
+                let estimate = Estimate::of_paths(&paths, recursive);
+                let operation = format!("uutils {}", command);
+                guardrails::check(executor.config(), &operation, &estimate, false)?;
+            }
+
             execute_uutil(command, args).await
         }
         _ => {
@@ -327,11 +372,46 @@ pub async fn execute(matches: &ArgMatches, _executor: &Executor) -> Result<()> {
     }
 }
 
-/// Execute a uutils command with the given arguments
-async fn execute_uutil(command: &str, mut args: Vec<String>) -> Result<()> {
-    // Append additional arguments from environment variable
-    args = append_additional_args(args);
+/// Paths a search command reads and whether it descends into directories, used to
+/// estimate its cost; `None` for commands that are not searches
+fn search_paths(command: &str, args: &[String]) -> Option<(Vec<String>, bool)> {
+    match command {
+        "find" => {
+            // Starting points come before the first expression
+            let paths: Vec<String> = args
+                .iter()
+                .take_while(|a| !a.starts_with('-') && !matches!(a.as_str(), "(" | "!"))
+                .cloned()
+                .collect();
+            Some((if paths.is_empty() { vec![".".to_string()] } else { paths }, true))
+        }
+        "grep" => {
+            let recursive = args.iter().any(|a| {
+                matches!(a.as_str(), "--recursive" | "--dereference-recursive")
+                    || (a.starts_with('-') && !a.starts_with("--") && a.contains(['r', 'R']))
+            });
+            // The first operand is the pattern unless it was given with -e or -f
+            let explicit_pattern = args.iter().any(|a| {
+                matches!(a.as_str(), "-e" | "-f")
+                    || a.starts_with("--regexp")
+                    || a.starts_with("--file")
+            });
+            let mut operands = args.iter().filter(|a| !a.starts_with('-'));
+            if !explicit_pattern {
+                operands.next();
+            }
+            let mut paths: Vec<String> = operands.cloned().collect();
+            if paths.is_empty() && recursive {
+                paths.push(".".to_string());
+            }
+            Some((paths, recursive))
+        }
+        _ => None,
+    }
+}
 
+/// Execute a uutils command with the given arguments
+async fn execute_uutil(command: &str, args: Vec<String>) -> Result<()> {
     debug!("Executing uutil command: {} with args: {:?}", command, args);
 
     // Try to use the uutils multicall binary first
@@ -341,6 +421,7 @@ async fn execute_uutil(command: &str, mut args: Vec<String>) -> Result<()> {
     cmd.stdout(Stdio::inherit());
     cmd.stderr(Stdio::inherit());
     cmd.stdin(Stdio::inherit());
+    cmd.envs(session::current().child_env());
 
     let output = cmd.output().or_else(|_| {
         // Fallback to individual uutils command if multicall binary is not available
@@ -350,6 +431,7 @@ async fn execute_uutil(command: &str, mut args: Vec<String>) -> Result<()> {
         fallback_cmd.stdout(Stdio::inherit());
         fallback_cmd.stderr(Stdio::inherit());
         fallback_cmd.stdin(Stdio::inherit());
+        fallback_cmd.envs(session::current().child_env());
         fallback_cmd.output()
     }).or_else(|_| {
         // Final fallback to system command if uutils is not available
@@ -359,6 +441,7 @@ async fn execute_uutil(command: &str, mut args: Vec<String>) -> Result<()> {
         system_cmd.stdout(Stdio::inherit());
         system_cmd.stderr(Stdio::inherit());
         system_cmd.stdin(Stdio::inherit());
+        system_cmd.envs(session::current().child_env());
         system_cmd.output()
     })?;
 
@@ -393,4 +476,27 @@ mod tests {
         let ls_cmd = command.find_subcommand("ls");
         assert!(ls_cmd.is_some());
     }
+
+    #[test]
+    fn test_search_paths() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            search_paths("grep", &args(&["-rn", "TODO", "src", "tests"])),
+            Some((args(&["src", "tests"]), true))
+        );
+        assert_eq!(
+            search_paths("grep", &args(&["-r", "TODO"])),
+            Some((args(&["."]), true))
+        );
+        assert_eq!(
+            search_paths("grep", &args(&["-e", "TODO", "notes.txt"])),
+            Some((args(&["TODO", "notes.txt"]), false))
+        );
+        assert_eq!(
+            search_paths("find", &args(&["src", "-name", "*.rs"])),
+            Some((args(&["src"]), true))
+        );
+        assert_eq!(search_paths("ls", &args(&["-la"])), None);
+    }
 }
@@ -0,0 +1,687 @@
+// file: src/commands/yaml.rs
+// version: 1.1.1
+// guid: 9a2f6c81-4e37-4b0d-a5d9-13c8e7f2b640
+
+//! `yaml` command: read and edit keys of YAML files such as `buf.yaml` or CI workflows
+//!
+//! Paths use the syntax of the `json` command (`jobs.test.steps[0].uses`). There is no YAML
+//! library that keeps comments, so edits are made to the lines of the file: a value is replaced
+//! on its own line, a new key is added after the last key of its mapping and a deleted key's
+//! lines are removed, all with the indentation found in the file. The result is parsed again and
+//! must equal the document with the change applied; when it does not, or when the change
+//! cannot be expressed as a line edit (flow collections, anchors, multiple keys on one line),
+//! the command fails unless `--reformat` allows rewriting the whole file, which drops comments.
+//!
+//! Files are rewritten atomically after the content checks; `--diff` prints the change and
+//! dry-run mode only previews it.
+
+use super::export::Effect;
+use super::file_write;
+use super::json::{self, Segment};
+use crate::executor::Executor;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde_json::Value as Json;
+use std::fs;
+use std::path::Path;
+
+/// Build the yaml command
+pub fn build_command() -> Command {
+    Command::new("yaml")
+        .about("Get, set and delete keys in YAML files, keeping comments and layout")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("get")
+                .add(Effect::ReadOnly)
+                .about("Print the value at a path as JSON")
+                .arg(json::path_arg())
+                .arg(file_arg())
+                .arg(json::raw_arg())
+                .arg(
+                    Arg::new("default")
+                        .long("default")
+                        .value_name("VALUE")
+                        .help("Print this value instead of failing when the path is missing"),
+                ),
+        )
+        .subcommand(
+            Command::new("set")
+                .add(Effect::Destructive)
+                .about("Set the value at a path, creating missing mappings on the way")
+                .arg(json::path_arg())
+                .arg(
+                    Arg::new("value")
+                        .required(true)
+                        .help("New value as YAML, e.g. 3, true, [a, b] or plain text"),
+                )
+                .arg(file_arg())
+                .arg(
+                    Arg::new("string")
+                        .long("string")
+                        .action(ArgAction::SetTrue)
+                        .help("Always take VALUE as a string"),
+                )
+                .arg(diff_arg())
+                .arg(reformat_arg()),
+        )
+        .subcommand(
+            Command::new("delete")
+                .add(Effect::Destructive)
+                .about("Remove the key or sequence item at a path")
+                .arg(json::path_arg())
+                .arg(file_arg())
+                .arg(diff_arg())
+                .arg(reformat_arg()),
+        )
+}
+
+fn file_arg() -> Arg {
+    Arg::new("file")
+        .value_name("FILE")
+        .required(true)
+        .help("YAML file")
+}
+
+fn diff_arg() -> Arg {
+    Arg::new("diff")
+        .long("diff")
+        .action(ArgAction::SetTrue)
+        .help("Print the change as a diff")
+}
+
+fn reformat_arg() -> Arg {
+    Arg::new("reformat")
+        .long("reformat")
+        .action(ArgAction::SetTrue)
+        .help("Rewrite the whole file when the change cannot be made in place (drops comments)")
+}
+
+/// Execute yaml subcommands
+pub async fn execute(matches: &ArgMatches, executor: &Executor) -> Result<()> {
+    let (name, sub) = matches
+        .subcommand()
+        .ok_or_else(|| anyhow!("Unknown yaml subcommand"))?;
+    let file = json::string(sub, "file");
+    let path = executor.check_path(file)?;
+    let text = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", file))?;
+    let document = parse(&text).with_context(|| format!("Failed to parse {}", file))?;
+    let segments = json::parse_path(json::string(sub, "path"))?;
+    if !json::is_singular(&segments) {
+        bail!("Wildcards and '..' are not supported by the yaml command");
+    }
+
+    let mut expected = document.clone();
+    let edited = match name {
+        "get" => {
+            let default = sub
+                .get_one::<String>("default")
+                .map(|raw| parse_value(raw, false));
+            return json::print_value(&document, sub, default);
+        }
+        "set" => {
+            let value = parse_value(json::string(sub, "value"), sub.get_flag("string"));
+            json::set(&mut expected, &segments, value.clone())
+                .with_context(|| format!("Cannot set '{}'", json::string(sub, "path")))?;
+            Lines::new(&text).set(&segments, &value)
+        }
+        "delete" => {
+            json::delete(&mut expected, &segments)
+                .with_context(|| format!("Cannot delete '{}'", json::string(sub, "path")))?;
+            Lines::new(&text).delete(&segments)
+        }
+        _ => bail!("Unknown yaml subcommand"),
+    };
+
+    // A line edit is only kept when the file still means what the change asked for
+    let updated = match edited.filter(|updated| parse(updated).ok().as_ref() == Some(&expected)) {
+        Some(updated) => updated,
+        None if sub.get_flag("reformat") => serde_yaml::to_string(&expected)?,
+        None => bail!(
+            "Cannot change '{}' in {} without reformatting it; re-run with --reformat to rewrite \
+             the whole file (comments are lost)",
+            json::string(sub, "path"),
+            file
+        ),
+    };
+    file_write::update(
+        executor,
+        Path::new(file),
+        updated.as_bytes(),
+        sub.get_flag("diff"),
+    )
+}
+
+/// Parse a YAML document into its JSON equivalent
+fn parse(text: &str) -> Result<Json> {
+    let value: serde_yaml::Value = serde_yaml::from_str(text)?;
+    Ok(serde_json::to_value(value)?)
+}
+
+/// Parse a value argument as YAML, falling back to a string
+fn parse_value(raw: &str, force_string: bool) -> Json {
+    if force_string {
+        return Json::String(raw.to_string());
+    }
+    parse(raw).unwrap_or_else(|_| Json::String(raw.to_string()))
+}
+
+/// `text` with the value at `path` set by a line edit, or `None` when the change cannot be
+/// made without reformatting the file
+pub(crate) fn set_in_place(text: &str, path: &[Segment], value: &Json) -> Result<Option<String>> {
+    let mut expected = parse(text)?;
+    json::set(&mut expected, path, value.clone())?;
+    Ok(Lines::new(text)
+        .set(path, value)
+        .filter(|updated| parse(updated).ok().as_ref() == Some(&expected)))
+}
+
+/// A run of entries at the same column: the lines of a block mapping or block sequence
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    /// Line of the first entry
+    line: usize,
+    /// Column of the entries; for a block starting after `- ` on an item line this is the
+    /// column after the dash
+    col: usize,
+    /// End of the lines the block may span (exclusive)
+    end: usize,
+}
+
+/// A mapping entry or sequence item, whose value starts at byte `value` of `line`
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    line: usize,
+    value: usize,
+    col: usize,
+    end: usize,
+    item: bool,
+}
+
+/// Where looking up a path stopped
+enum Stop {
+    Found(Slot),
+    /// `path[depth]` is a key missing from the mapping (`None` for an empty document)
+    MissingKey(Option<Block>, usize),
+    /// `path[depth]` is the index one past the end of the sequence
+    Append(Block, usize),
+    /// The value at `path[..depth]` is empty, so `path[depth..]` has to be created in it
+    Empty(Slot, usize),
+}
+
+/// The lines of a YAML file, edited by indentation
+struct Lines {
+    lines: Vec<String>,
+    eol: &'static str,
+    trailing_eol: bool,
+    step: usize,
+}
+
+impl Lines {
+    fn new(text: &str) -> Self {
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        let step = lines
+            .iter()
+            .filter(|line| !is_filler(line))
+            .map(|line| indent(line))
+            .filter(|indent| *indent > 0)
+            .min()
+            .unwrap_or(2);
+        Lines {
+            lines,
+            eol: if text.contains("\r\n") { "\r\n" } else { "\n" },
+            trailing_eol: text.is_empty() || text.ends_with('\n'),
+            step,
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut text = self.lines.join(self.eol);
+        if self.trailing_eol && !text.is_empty() {
+            text.push_str(self.eol);
+        }
+        text
+    }
+
+    /// Set `path` to `value`; `None` when that cannot be done as a line edit
+    fn set(mut self, path: &[Segment], value: &Json) -> Option<String> {
+        match self.locate(path)? {
+            Stop::Found(slot) => self.replace(slot, value)?,
+            Stop::MissingKey(block, depth) => {
+                let Segment::Key(key) = &path[depth] else {
+                    return None;
+                };
+                let value = build(&path[depth + 1..], value)?;
+                self.insert_entry(block, key, &value)?;
+            }
+            Stop::Append(block, depth) => {
+                let value = build(&path[depth + 1..], value)?;
+                self.append_item(block, &value)?;
+            }
+            Stop::Empty(slot, depth) => {
+                let value = build(&path[depth..], value)?;
+                self.replace(slot, &value)?;
+            }
+        }
+        Some(self.render())
+    }
+
+    /// Remove the entry or item at `path`; `None` when that cannot be done as a line edit
+    fn delete(mut self, path: &[Segment]) -> Option<String> {
+        let Stop::Found(slot) = self.locate(path)? else {
+            return None;
+        };
+        // The first key of a `- key: value` item shares its line with the dash
+        if !slot.item && indent(&self.lines[slot.line]) != slot.col {
+            return None;
+        }
+        let end = self.content_end(slot.line, slot.end);
+        self.lines.drain(slot.line..end);
+        Some(self.render())
+    }
+
+    fn locate(&self, path: &[Segment]) -> Option<Stop> {
+        let Some(mut block) = self.root() else {
+            return matches!(path.first(), Some(Segment::Key(_)))
+                .then_some(Stop::MissingKey(None, 0));
+        };
+        for (depth, segment) in path.iter().enumerate() {
+            let sequence = is_item(&self.lines[block.line][block.col..]);
+            let slot = match segment {
+                Segment::Key(key) if !sequence => match self.find_key(block, key) {
+                    Some(slot) => slot,
+                    None => return Some(Stop::MissingKey(Some(block), depth)),
+                },
+                Segment::Index(index) if sequence => {
+                    let items = self.items(block);
+                    if usize::try_from(*index).ok() == Some(items.len()) {
+                        return Some(Stop::Append(block, depth));
+                    }
+                    items[json::resolve_index(*index, items.len())?]
+                }
+                _ => return None,
+            };
+            if depth + 1 == path.len() {
+                return Some(Stop::Found(slot));
+            }
+            match self.child(slot) {
+                Some(child) => block = child,
+                None if self.is_empty(slot) => return Some(Stop::Empty(slot, depth + 1)),
+                None => return None,
+            }
+        }
+        None
+    }
+
+    fn root(&self) -> Option<Block> {
+        let line = (0..self.lines.len()).find(|l| !is_filler(&self.lines[*l]))?;
+        Some(Block {
+            line,
+            col: indent(&self.lines[line]),
+            end: self.lines.len(),
+        })
+    }
+
+    /// Lines where the entries of `block` start
+    fn entries(&self, block: Block) -> Vec<usize> {
+        let sequence = is_item(&self.lines[block.line][block.col..]);
+        let mut entries = vec![block.line];
+        for l in block.line + 1..block.end {
+            let line = &self.lines[l];
+            if is_filler(line) {
+                continue;
+            }
+            if indent(line) < block.col {
+                break;
+            }
+            // A sequence at the column of its key belongs to that key
+            if indent(line) == block.col && is_item(&line[block.col..]) == sequence {
+                entries.push(l);
+            }
+        }
+        entries
+    }
+
+    fn slots(&self, block: Block) -> Vec<(usize, usize)> {
+        let entries = self.entries(block);
+        entries
+            .iter()
+            .enumerate()
+            .map(|(k, line)| (*line, entries.get(k + 1).copied().unwrap_or(block.end)))
+            .collect()
+    }
+
+    fn find_key(&self, block: Block, key: &str) -> Option<Slot> {
+        self.slots(block).into_iter().find_map(|(line, end)| {
+            let (name, after) = parse_key(&self.lines[line][block.col..])?;
+            (name == key).then_some(Slot {
+                line,
+                value: block.col + after,
+                col: block.col,
+                end,
+                item: false,
+            })
+        })
+    }
+
+    fn items(&self, block: Block) -> Vec<Slot> {
+        self.slots(block)
+            .into_iter()
+            .map(|(line, end)| Slot {
+                line,
+                value: block.col + 1,
+                col: block.col,
+                end,
+                item: true,
+            })
+            .collect()
+    }
+
+    /// The block holding the value of `slot`, if it is a block mapping or sequence
+    fn child(&self, slot: Slot) -> Option<Block> {
+        let rest = &self.lines[slot.line][slot.value..];
+        let (value, _) = split_comment(rest);
+        if value.trim().is_empty() {
+            let line = (slot.line + 1..slot.end).find(|l| !is_filler(&self.lines[*l]))?;
+            return Some(Block {
+                line,
+                col: indent(&self.lines[line]),
+                end: slot.end,
+            });
+        }
+        let inline = value.trim_start();
+        (slot.item && (parse_key(inline).is_some() || is_item(inline))).then(|| Block {
+            line: slot.line,
+            col: slot.value + rest.len() - rest.trim_start().len(),
+            end: slot.end,
+        })
+    }
+
+    fn is_empty(&self, slot: Slot) -> bool {
+        let (value, _) = split_comment(&self.lines[slot.line][slot.value..]);
+        matches!(value.trim(), "" | "~" | "null")
+            && (slot.line + 1..slot.end).all(|l| is_filler(&self.lines[l]))
+    }
+
+    /// End of the lines of an entry without the blank and comment lines after it
+    fn content_end(&self, start: usize, end: usize) -> usize {
+        let mut end = end;
+        while end > start + 1 && is_filler(&self.lines[end - 1]) {
+            end -= 1;
+        }
+        end
+    }
+
+    fn replace(&mut self, slot: Slot, value: &Json) -> Option<()> {
+        let line = &self.lines[slot.line];
+        let head = line[..slot.value].to_string();
+        let (_, comment) = split_comment(&line[slot.value..]);
+        let child_col = if slot.item {
+            slot.col + 2
+        } else {
+            slot.col + self.step
+        };
+        let new = entry_lines(head, comment, value, slot.item, child_col)?;
+        let end = self.content_end(slot.line, slot.end);
+        self.lines.splice(slot.line..end, new);
+        Some(())
+    }
+
+    fn insert_entry(&mut self, block: Option<Block>, key: &str, value: &Json) -> Option<()> {
+        let (at, col) = match block {
+            Some(block) => (self.content_end(block.line, block.end), block.col),
+            None => (self.lines.len(), 0),
+        };
+        let head = format!("{}{}:", " ".repeat(col), render_key(key));
+        let new = entry_lines(head, "", value, false, col + self.step)?;
+        self.lines.splice(at..at, new);
+        Some(())
+    }
+
+    fn append_item(&mut self, block: Block, value: &Json) -> Option<()> {
+        let at = self.content_end(block.line, block.end);
+        let head = format!("{}-", " ".repeat(block.col));
+        let new = entry_lines(head, "", value, true, block.col + 2)?;
+        self.lines.splice(at..at, new);
+        Some(())
+    }
+}
+
+/// The lines for an entry: `head` (ending in `:` or `-`), the value and any comment
+///
+/// Non-empty mappings and sequences start on the next line after a key and on the same line
+/// after a dash; their lines are indented to `child_col`.
+fn entry_lines(
+    head: String,
+    comment: &str,
+    value: &Json,
+    inline_first: bool,
+    child_col: usize,
+) -> Option<Vec<String>> {
+    let text = serde_yaml::to_string(value).ok()?;
+    let mut rendered = text.lines();
+    let container = match value {
+        Json::Array(items) => !items.is_empty(),
+        Json::Object(map) => !map.is_empty(),
+        _ => false,
+    };
+    let mut lines = vec![head];
+    if inline_first || !container {
+        let first = rendered.next()?;
+        lines[0] = format!("{} {}{}", lines[0], first, comment);
+    }
+    let prefix = " ".repeat(child_col);
+    lines.extend(rendered.map(|line| {
+        if line.is_empty() {
+            String::new()
+        } else {
+            format!("{}{}", prefix, line)
+        }
+    }));
+    Some(lines)
+}
+
+/// The value to place at the first missing step of `path`
+fn build(path: &[Segment], value: &Json) -> Option<Json> {
+    let mut built = Json::Null;
+    json::set(&mut built, path, value.clone()).ok()?;
+    Some(built)
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Blank lines, comments and document markers
+fn is_filler(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty()
+        || trimmed.starts_with('#')
+        || trimmed.starts_with('%')
+        || trimmed == "---"
+        || trimmed == "..."
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// The key of a `key: value` line and the byte offset after its colon
+fn parse_key(text: &str) -> Option<(String, usize)> {
+    if is_item(text) || text.starts_with(['#', '?', '[', '{', '&', '*', '!', '|', '>']) {
+        return None;
+    }
+    let (key, colon) = match text.chars().next()? {
+        '"' => {
+            let end = closing_quote(text, '"')?;
+            (serde_json::from_str(&text[..=end]).ok()?, end + 1)
+        }
+        '\'' => {
+            let end = closing_quote(text, '\'')?;
+            (text[1..end].replace("''", "'"), end + 1)
+        }
+        _ => {
+            let colon = text
+                .char_indices()
+                .find(|(i, c)| {
+                    *c == ':' && matches!(text[i + 1..].chars().next(), None | Some(' ' | '\t'))
+                })?
+                .0;
+            if text[..colon].contains(" #") {
+                return None;
+            }
+            (text[..colon].trim_end().to_string(), colon)
+        }
+    };
+    let after = text.get(colon..)?.strip_prefix(':')?;
+    matches!(after.chars().next(), None | Some(' ' | '\t')).then_some((key, colon + 1))
+}
+
+fn closing_quote(text: &str, quote: char) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] as char {
+            '\\' if quote == '"' => i += 2,
+            c if c == quote => {
+                if quote == '\'' && bytes.get(i + 1) == Some(&b'\'') {
+                    i += 2;
+                } else {
+                    return Some(i);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Split the text after a key's colon or an item's dash into the value and a trailing comment
+/// (with the whitespace before it)
+fn split_comment(rest: &str) -> (&str, &str) {
+    let start = rest.len() - rest.trim_start().len();
+    let mut skip_to = start;
+    if let Some(quote @ ('"' | '\'')) = rest[start..].chars().next() {
+        if let Some(end) = closing_quote(&rest[start..], quote) {
+            skip_to = start + end + 1;
+        }
+    }
+    let bytes = rest.as_bytes();
+    for i in skip_to..bytes.len() {
+        if bytes[i] == b'#' && (i == 0 || bytes[i - 1] == b' ' || bytes[i - 1] == b'\t') {
+            let value = rest[..i].trim_end();
+            return (value, &rest[value.len()..]);
+        }
+    }
+    (rest, "")
+}
+
+/// A key as written in YAML: plain when it reads back as the same string, quoted otherwise
+fn render_key(key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+        && !key.starts_with(['-', '.'])
+        && serde_yaml::from_str::<serde_yaml::Value>(key).ok()
+            == Some(serde_yaml::Value::String(key.to_string()));
+    if plain {
+        key.to_string()
+    } else {
+        Json::String(key.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKFLOW: &str = "\
+# CI
+name: ci
+on: [push]
+
+jobs:
+  test:
+    runs-on: ubuntu-latest # pinned later
+    steps:
+      - uses: actions/checkout@v4
+      - name: Test
+        run: |
+          cargo test
+    env:
+      RUST_LOG: info
+
+  # lint runs on every push
+  lint:
+    runs-on: ubuntu-latest
+";
+
+    fn set(path: &str, value: &str) -> Option<String> {
+        Lines::new(WORKFLOW).set(&json::parse_path(path).unwrap(), &parse_value(value, false))
+    }
+
+    #[test]
+    fn test_line_edits_keep_comments() {
+        let updated = set("jobs.test.runs-on", "ubuntu-24.04").unwrap();
+        assert_eq!(
+            updated,
+            WORKFLOW.replace("ubuntu-latest # pinned", "ubuntu-24.04 # pinned")
+        );
+
+        let updated = set("jobs.test.steps[1].run", "cargo nextest run").unwrap();
+        assert!(updated.contains("        run: cargo nextest run\n    env:\n"));
+
+        let updated = set(
+            "jobs.test.steps[2]",
+            "{uses: actions/cache@v4, with: {key: k}}",
+        )
+        .unwrap();
+        assert!(updated.contains(
+            "          cargo test\n      - uses: actions/cache@v4\n        with:\n          key: k\n    env:"
+        ));
+
+        let updated = set("jobs.lint.timeout-minutes", "10").unwrap();
+        assert!(updated.ends_with("  lint:\n    runs-on: ubuntu-latest\n    timeout-minutes: 10\n"));
+
+        let updated = set("jobs.test.env.CARGO_TERM_COLOR", "always").unwrap();
+        assert!(
+            updated.contains("      RUST_LOG: info\n      CARGO_TERM_COLOR: always\n\n  # lint")
+        );
+
+        let updated = set("permissions.contents", "read").unwrap();
+        assert!(updated.ends_with("    runs-on: ubuntu-latest\npermissions:\n  contents: read\n"));
+
+        let updated = Lines::new(WORKFLOW)
+            .delete(&json::parse_path("jobs.test").unwrap())
+            .unwrap();
+        assert!(updated.contains("jobs:\n\n  # lint runs on every push\n  lint:\n"));
+        let updated = Lines::new(WORKFLOW)
+            .delete(&json::parse_path("jobs.test.steps[0]").unwrap())
+            .unwrap();
+        assert!(updated.contains("    steps:\n      - name: Test\n"));
+
+        // Flow collections and the first key of an item cannot be edited by line
+        assert!(set("on[1]", "pull_request").is_none());
+        assert!(Lines::new(WORKFLOW)
+            .delete(&json::parse_path("jobs.test.steps[1].name").unwrap())
+            .is_none());
+
+        assert_eq!(
+            Lines::new("").set(&json::parse_path("a.b").unwrap(), &Json::from(1)),
+            Some("a:\n  b: 1\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_keys_and_comments() {
+        assert_eq!(parse_key("a: 1"), Some(("a".to_string(), 2)));
+        assert_eq!(parse_key("\"a b\": 1"), Some(("a b".to_string(), 6)));
+        assert_eq!(parse_key("url: http://x"), Some(("url".to_string(), 4)));
+        assert_eq!(parse_key("http://x"), None);
+        assert_eq!(split_comment(" 'a # b' # c"), (" 'a # b'", " # c"));
+        assert_eq!(split_comment(" a#b"), (" a#b", ""));
+        assert_eq!(render_key("on"), "on");
+        assert_eq!(render_key("true"), "\"true\"");
+        assert_eq!(render_key("a b"), "\"a b\"");
+    }
+}
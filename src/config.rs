@@ -1,21 +1,76 @@
 // file: src/config.rs
-// version: 1.0.0
+// version: 1.44.0
 // guid: 6ea31d79-e2bf-4304-a841-22bf1e595512
 
 use crate::error::{AgentError, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
 use tokio::fs;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Profile applied when none is given with `--profile` or `SAFE_AI_UTIL_PROFILE`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
     pub general: GeneralConfig,
     pub logging: LoggingConfig,
     pub safety: SafetyConfig,
     pub git: GitConfig,
     pub execution: ExecutionConfig,
+    #[serde(default)]
+    pub scaffold: ScaffoldConfig,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    #[serde(default)]
+    pub guardrails: GuardrailsConfig,
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    #[serde(default)]
+    pub content_policy: ContentPolicyConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub lock: LockConfig,
+    #[serde(default)]
+    pub capabilities: CapabilitiesConfig,
+    #[serde(default)]
+    pub process: ProcessConfig,
+    #[serde(default)]
+    pub container: ContainerConfig,
+    #[serde(default)]
+    pub remote: RemoteConfig,
+    #[serde(default)]
+    pub cargo: CargoConfig,
+    #[serde(default)]
+    pub go: GoConfig,
+    #[serde(default)]
+    pub kubectl: KubectlConfig,
+    #[serde(default)]
+    pub terraform: TerraformConfig,
+    #[serde(default)]
+    pub helm: HelmConfig,
+    #[serde(default)]
+    pub db: DbConfig,
+    #[serde(default)]
+    pub jvm: JvmConfig,
+    #[serde(default)]
+    pub cmake: CmakeConfig,
+    #[serde(default)]
+    pub buf: BufConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    /// Pinned binaries and minimum versions of external tools, keyed by command name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tools: BTreeMap<String, ToolPin>,
+    /// User-defined profiles, or overrides of the built-in ones, keyed by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, toml::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,9 +99,21 @@ pub enum LogFormat {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SafetyConfig {
     pub dry_run: bool,
+    /// Return fabricated results instead of spawning anything; implies `dry_run`
+    #[serde(default)]
+    pub simulate: bool,
     pub confirm_destructive: bool,
     pub backup_before_delete: bool,
     pub validate_paths: bool,
+    /// Directories operations may read and write; empty means unrestricted
+    #[serde(default)]
+    pub workspace_roots: Vec<PathBuf>,
+    /// Record the SHA-256 of every external binary run and refuse binaries that change
+    #[serde(default)]
+    pub verify_binaries: bool,
+    /// Run spawned commands without network access
+    #[serde(default)]
+    pub no_network: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +129,9 @@ pub struct ExecutionConfig {
     pub shell: Option<String>,
     pub environment_isolation: bool,
     pub resource_limits: ResourceLimits,
+    /// Re-run a failing command once with verbose flags and attach its output to history
+    #[serde(default)]
+    pub escalate_on_failure: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,6 +141,515 @@ pub struct ResourceLimits {
     pub max_execution_time: Option<u64>,
 }
 
+/// Project template settings used by `scaffold new`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScaffoldConfig {
+    /// Directory searched for templates referenced by name
+    pub templates_dir: Option<PathBuf>,
+    /// Sources git-hosted templates must come from: a template matches a source with the same
+    /// scheme, host and port whose path segments lead its own, e.g. `https://github.com/org/`
+    pub allowed_sources: Vec<String>,
+}
+
+impl Default for ScaffoldConfig {
+    fn default() -> Self {
+        Self {
+            templates_dir: None,
+            allowed_sources: vec!["https://github.com/jdfalk/".to_string()],
+        }
+    }
+}
+
+/// Admin-defined command policy, evaluated before any command is spawned
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    /// If non-empty, only these commands may run
+    pub allow_commands: Vec<String>,
+    /// Commands that may never run
+    pub deny_commands: Vec<String>,
+    /// Argument rules evaluated in order; the first matching rule decides
+    pub rules: Vec<PolicyRule>,
+    /// Allow the recorded interactive `shell`, which runs whatever is typed
+    pub allow_shell: bool,
+    /// Allow package managers to run the lifecycle scripts of the packages they install
+    pub allow_scripts: bool,
+    /// Allow containers that run privileged, with every capability and device of the host
+    pub allow_privileged: bool,
+    /// Allow system package managers to install, upgrade and remove packages
+    pub allow_packages: bool,
+    /// Allow the aws, gcloud and az CLIs to create, change and delete cloud resources
+    pub allow_cloud_mutations: bool,
+    /// User services (systemd units or launchd labels) that may be managed; `dev-*` matches
+    /// every name starting with `dev-`. Empty allows none.
+    pub services: Vec<String>,
+    /// Times outside which matching commands are blocked
+    pub windows: Vec<MaintenanceWindow>,
+}
+
+/// A maintenance window: commands it matches may only run while its schedule does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    /// Name shown when a command is blocked
+    pub name: String,
+    /// Commands the window applies to
+    pub commands: Vec<String>,
+    /// Regex over the space-joined arguments; without it every use of the commands matches
+    #[serde(default)]
+    pub args: Option<String>,
+    /// Cron-style minutes the commands may run: minute hour day-of-month month day-of-week
+    pub schedule: String,
+    /// Read the schedule in UTC instead of local time
+    #[serde(default)]
+    pub utc: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// A policy rule matching a command and a regex over its space-joined arguments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyRule {
+    pub command: String,
+    pub args: String,
+    pub action: PolicyAction,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    Allow,
+    Deny,
+}
+
+/// Thresholds for operations whose cost scales with the number and size of files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuardrailsConfig {
+    pub enabled: bool,
+    /// Log a warning when an operation touches more files than this
+    pub warn_files: u64,
+    /// Log a warning when an operation reads or writes more bytes than this
+    pub warn_bytes: u64,
+    /// Require confirmation when an operation touches more files than this
+    pub confirm_files: u64,
+    /// Require confirmation when an operation reads or writes more bytes than this
+    pub confirm_bytes: u64,
+}
+
+impl Default for GuardrailsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            warn_files: 10_000,
+            warn_bytes: 512 * 1024 * 1024,
+            confirm_files: 100_000,
+            confirm_bytes: 4 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Limits on what a session may spend; unset limits are unlimited
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Wall-clock time the session may run, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_wall_clock_seconds: Option<u64>,
+    /// Subprocesses the session may spawn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_subprocesses: Option<u64>,
+    /// Bytes built-in network operations may transfer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_network_bytes: Option<u64>,
+}
+
+/// Limits on command output captured for structured results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Bytes of each of stdout and stderr kept when output is captured for a result; the
+    /// start and end are kept and the middle dropped. 0 keeps everything
+    pub max_capture_bytes: u64,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            max_capture_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// The execution history database in the state directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Record every command run, with its output when it was captured
+    pub enabled: bool,
+    /// Days runs and their output are kept; 0 keeps them forever
+    pub retention_days: u32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention_days: 30,
+        }
+    }
+}
+
+/// The advisory workspace lock taken by mutating commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LockConfig {
+    /// Take `.safe-ai-util.lock` before mutating commands run
+    pub enabled: bool,
+    /// Seconds to wait for another holder before failing; 0 fails at once
+    pub timeout_seconds: u64,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_seconds: 120,
+        }
+    }
+}
+
+/// What happens when a command needs a tool that is missing or outdated
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapabilitiesConfig {
+    /// Queue a capability request for approval with `tools install`
+    pub queue: bool,
+}
+
+/// How `process` routes files to the tools that validate, format and lint them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessConfig {
+    /// Fall back to the built-in handlers for files no configured handler matches
+    pub builtin: bool,
+    /// Handlers tried before the built-in ones, in order
+    pub handlers: Vec<FileHandler>,
+}
+
+impl Default for ProcessConfig {
+    fn default() -> Self {
+        Self {
+            builtin: true,
+            handlers: Vec::new(),
+        }
+    }
+}
+
+/// Container spawned commands run in instead of on the host (see [`crate::container`])
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContainerConfig {
+    /// Image commands run in; unset runs them on the host
+    pub image: Option<String>,
+    /// `docker` or `podman`; the first of them found when unset
+    pub runtime: Option<String>,
+    /// Mount the workspace read-only
+    pub read_only: bool,
+}
+
+/// Machine spawned commands run on instead of this one (see [`crate::remote`])
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// `ssh://[user@]host[:port][/directory]`; unset runs commands here
+    pub target: Option<String>,
+}
+
+/// How the `cargo` command runs cargo
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CargoConfig {
+    /// Pass `--locked`, so a `Cargo.lock` that needs changes fails the build instead
+    pub locked: bool,
+}
+
+/// How the `go` command runs go
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GoConfig {
+    /// Build flags added to every build, test, vet and generate, e.g. `-mod=readonly`
+    pub flags: Vec<String>,
+    /// Flags refused in `GOFLAGS` and on the command line
+    pub deny_flags: Vec<String>,
+}
+
+impl Default for GoConfig {
+    fn default() -> Self {
+        Self {
+            flags: Vec::new(),
+            // Each makes go run another program or compile files other than the ones in the tree
+            deny_flags: vec![
+                "-toolexec".to_string(),
+                "-exec".to_string(),
+                "-overlay".to_string(),
+            ],
+        }
+    }
+}
+
+/// How the `kubectl` command treats clusters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KubectlConfig {
+    /// Regexes for the names of production contexts, which mutating commands only target
+    /// once confirmed
+    pub production_contexts: Vec<String>,
+}
+
+impl Default for KubectlConfig {
+    fn default() -> Self {
+        Self {
+            production_contexts: vec!["prod".to_string()],
+        }
+    }
+}
+
+/// How the `terraform` command runs terraform or OpenTofu
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerraformConfig {
+    /// `terraform` or `tofu`; the first of them found when unset
+    pub binary: Option<String>,
+    /// Minutes after which a saved plan is too old to apply
+    pub plan_max_age_minutes: u64,
+}
+
+impl Default for TerraformConfig {
+    fn default() -> Self {
+        Self {
+            binary: None,
+            plan_max_age_minutes: 30,
+        }
+    }
+}
+
+/// How the `helm` command guards upgrades
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HelmConfig {
+    /// Regexes for the names of dev contexts, which upgrades target without a prior review
+    pub dev_contexts: Vec<String>,
+    /// Minutes after which a `helm diff` or `helm template` review no longer covers an upgrade
+    pub review_max_age_minutes: u64,
+}
+
+impl Default for HelmConfig {
+    fn default() -> Self {
+        Self {
+            dev_contexts: vec![
+                "^(kind|k3d)-".to_string(),
+                "^(minikube|docker-desktop|rancher-desktop|orbstack)$".to_string(),
+            ],
+            review_max_age_minutes: 60,
+        }
+    }
+}
+
+/// Databases the `db` command queries and how much it prints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DbConfig {
+    /// PostgreSQL databases, queried through psql by name
+    pub connections: Vec<DbConnection>,
+    /// Rows printed at most per query
+    pub max_rows: usize,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            connections: Vec::new(),
+            max_rows: 1000,
+        }
+    }
+}
+
+/// A PostgreSQL database of `db.connections`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbConnection {
+    /// Name given to `db query` instead of a SQLite file
+    pub name: String,
+    /// `postgres://` connection URL
+    pub url: String,
+}
+
+/// How the `jvm` command runs Gradle and Maven
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JvmConfig {
+    /// SHA-256 checksums of `gradle-wrapper.jar` files accepted without approval, as published
+    /// at https://gradle.org/release-checksums/
+    pub gradle_wrapper_sha256: Vec<String>,
+    /// Build offline, with dependencies already in the local cache or repository
+    pub offline: bool,
+}
+
+/// How the `cmake` command configures, builds and tests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CmakeConfig {
+    /// Build directory, relative to the source directory, when neither `-B` nor a preset names one
+    pub build_dir: PathBuf,
+    /// Generator for configures without a preset or `-G`, e.g. `Ninja`; CMake's default when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+    /// Parallel build jobs and concurrent tests; the native build tool's default when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+}
+
+impl Default for CmakeConfig {
+    fn default() -> Self {
+        Self {
+            build_dir: PathBuf::from("build"),
+            generator: None,
+            jobs: None,
+        }
+    }
+}
+
+/// Which servers `buf curl` may call
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BufConfig {
+    /// Host names of the endpoints `buf curl` may invoke, matched like `http.allowed_hosts`.
+    /// Empty allows none.
+    pub curl_allowed_hosts: Vec<String>,
+}
+
+/// Which hosts the built-in `http` client may reach
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Host names requests and their redirects may go to; `*.example.com` matches the
+    /// subdomains of example.com. Empty allows none.
+    pub allowed_hosts: Vec<String>,
+}
+
+/// Command run by `process` for files with one of the given extensions or MIME types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileHandler {
+    /// Extensions matched, without the dot
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// MIME types matched, `text/*` for a whole type
+    #[serde(default)]
+    pub mime: Vec<String>,
+    pub action: FileAction,
+    /// Command line run for the file, with `{file}` replaced by its path
+    pub command: String,
+}
+
+/// What a file handler does to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAction {
+    Validate,
+    Format,
+    Lint,
+}
+
+impl FileAction {
+    /// All actions, in the order `process` runs them
+    pub const ALL: [FileAction; 3] = [FileAction::Validate, FileAction::Format, FileAction::Lint];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Validate => "validate",
+            Self::Format => "format",
+            Self::Lint => "lint",
+        }
+    }
+}
+
+impl fmt::Display for FileAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Patterns that content must not match before the utility writes it to a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentPolicyConfig {
+    pub enabled: bool,
+    /// Built-in rule sets to apply: `credentials`, `malware`
+    pub builtin: Vec<String>,
+    /// Additional deny rules
+    pub deny: Vec<ContentRule>,
+}
+
+impl Default for ContentPolicyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            builtin: vec!["credentials".to_string(), "malware".to_string()],
+            deny: Vec::new(),
+        }
+    }
+}
+
+/// A named regex that written content must not match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentRule {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Binary and minimum version an external tool must match before it is run, and how it is run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolPin {
+    /// Absolute path of the binary to run instead of the one found on PATH
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Lowest version accepted in the tool's `--version` output, e.g. `"2.40"`
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_version"
+    )]
+    pub min_version: Option<String>,
+    /// Run the tool in a pseudo-terminal, for tools that misbehave without one
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub pty: bool,
+    /// Flags that make the tool verbose, for re-runs of failed commands; overrides the
+    /// built-in ones
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub verbose_args: Vec<String>,
+}
+
+/// Accept versions written as strings or whole numbers; `2.40` as a float would lose its
+/// trailing zero, so it must be quoted
+fn deserialize_version<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<toml::Value>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(toml::Value::String(version)) => Ok(Some(version)),
+        Some(toml::Value::Integer(version)) => Ok(Some(version.to_string())),
+        Some(other) => Err(serde::de::Error::custom(format!(
+            "expected a quoted version such as \"2.40\", got {}",
+            other
+        ))),
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -88,9 +667,13 @@ impl Default for Config {
             },
             safety: SafetyConfig {
                 dry_run: false,
+                simulate: false,
                 confirm_destructive: true,
                 backup_before_delete: true,
                 validate_paths: true,
+                workspace_roots: Vec::new(),
+                verify_binaries: false,
+                no_network: false,
             },
             git: GitConfig {
                 auto_stage: false,
@@ -106,49 +689,609 @@ impl Default for Config {
                     max_cpu_percent: Some(80),
                     max_execution_time: Some(600),
                 },
+                escalate_on_failure: false,
             },
+            scaffold: ScaffoldConfig::default(),
+            policy: PolicyConfig::default(),
+            guardrails: GuardrailsConfig::default(),
+            budget: BudgetConfig::default(),
+            content_policy: ContentPolicyConfig::default(),
+            output: OutputConfig::default(),
+            history: HistoryConfig::default(),
+            lock: LockConfig::default(),
+            capabilities: CapabilitiesConfig::default(),
+            process: ProcessConfig::default(),
+            container: ContainerConfig::default(),
+            remote: RemoteConfig::default(),
+            cargo: CargoConfig::default(),
+            go: GoConfig::default(),
+            kubectl: KubectlConfig::default(),
+            terraform: TerraformConfig::default(),
+            helm: HelmConfig::default(),
+            db: DbConfig::default(),
+            jvm: JvmConfig::default(),
+            cmake: CmakeConfig::default(),
+            buf: BufConfig::default(),
+            http: HttpConfig::default(),
+            tools: BTreeMap::new(),
+            profile: None,
+            profiles: BTreeMap::new(),
         }
     }
 }
 
+/// Where a configuration value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    System(PathBuf),
+    User(PathBuf),
+    Project(PathBuf),
+    /// File given with `--config`
+    Explicit(PathBuf),
+    /// Named profile
+    Profile(String),
+    /// Environment variable
+    Env(String),
+}
+
+impl ConfigSource {
+    /// Whether an agent working in the project can write the layer: the project file and
+    /// the environment may only set [`UNTRUSTED_KEYS`] and tighten security settings (see
+    /// [`merge_untrusted`])
+    pub fn is_untrusted(&self) -> bool {
+        matches!(self, Self::Project(_) | Self::Env(_))
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::System(path) => write!(f, "system ({})", path.display()),
+            Self::User(path) => write!(f, "user ({})", path.display()),
+            Self::Project(path) => write!(f, "project ({})", path.display()),
+            Self::Explicit(path) => write!(f, "--config ({})", path.display()),
+            Self::Profile(name) => write!(f, "profile ({})", name),
+            Self::Env(var) => write!(f, "env ({})", var),
+        }
+    }
+}
+
+impl ConfigSource {
+    /// File this source was read from, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::System(path) | Self::User(path) | Self::Project(path) | Self::Explicit(path) => {
+                Some(path)
+            }
+            Self::Default | Self::Profile(_) | Self::Env(_) => None,
+        }
+    }
+}
+
+/// Effective configuration together with the source of every value
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    pub config: Config,
+    /// The merged configuration as TOML
+    pub value: toml::Value,
+    /// Source of each leaf value, keyed by dotted path (e.g. `safety.dry_run`)
+    pub sources: BTreeMap<String, ConfigSource>,
+    /// Configuration files that were found and merged, lowest precedence first
+    pub files: Vec<ConfigSource>,
+    /// Profile that was applied, if any
+    pub profile: Option<String>,
+}
+
+/// Options for loading configuration, usually taken from global command-line flags
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadOptions<'a> {
+    /// File given with `--config`
+    pub file: Option<&'a Path>,
+    /// Profile given with `--profile`
+    pub profile: Option<&'a str>,
+}
+
+/// Prefix of environment variables overriding arbitrary keys, e.g.
+/// `SAFE_AI_UTIL__SAFETY__DRY_RUN=true` sets `safety.dry_run`
+pub const ENV_PREFIX: &str = "SAFE_AI_UTIL__";
+
+/// Environment variable selecting a profile when `--profile` is not given
+pub const PROFILE_ENV: &str = "SAFE_AI_UTIL_PROFILE";
+
+/// Built-in profiles as partial configuration layers; `[profiles.<name>]` tables in
+/// configuration files extend or override them
+pub const BUILTIN_PROFILES: &[(&str, &str)] = &[
+    (
+        "dev",
+        r#"
+[general]
+timeout_seconds = 600
+
+[logging]
+level = "debug"
+format = "Pretty"
+"#,
+    ),
+    (
+        "ci",
+        r#"
+[general]
+timeout_seconds = 1800
+max_retries = 1
+
+[logging]
+level = "info"
+format = "Json"
+
+[cargo]
+locked = true
+"#,
+    ),
+    (
+        "restricted",
+        r#"
+[general]
+timeout_seconds = 120
+max_retries = 0
+
+[logging]
+level = "info"
+format = "Json"
+
+[safety]
+confirm_destructive = true
+validate_paths = true
+workspace_roots = ["."]
+
+[git]
+safe_force_push = true
+
+[policy]
+allow_commands = ["git", "cargo", "rustc", "npm", "npx", "node", "python", "python3", "uv", "pytest", "buf", "prettier", "eslint", "ruff"]
+rules = [
+    { command = "git", args = '^push\b.*\s(--force|-f)(\s|$)', action = "deny", reason = "force pushes are not allowed in the restricted profile" },
+    { command = "git", args = '^(reset\b.*--hard|clean\b.*-[a-z]*f)', action = "deny", reason = "discarding work is not allowed in the restricted profile" },
+    { command = "*", args = '^publish\b', action = "deny", reason = "publishing is not allowed in the restricted profile" },
+]
+"#,
+    ),
+];
+
+/// File name of project-local configuration
+pub const PROJECT_CONFIG_FILE: &str = ".safe-ai-util.toml";
+
+/// A documented configuration key
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigKey {
+    /// Dotted path, e.g. `safety.dry_run`
+    pub key: &'static str,
+    pub description: &'static str,
+    /// Example value for keys without a default
+    pub example: Option<&'static str>,
+}
+
+const fn key(key: &'static str, description: &'static str) -> ConfigKey {
+    ConfigKey {
+        key,
+        description,
+        example: None,
+    }
+}
+
+const fn optional(
+    key: &'static str,
+    description: &'static str,
+    example: &'static str,
+) -> ConfigKey {
+    ConfigKey {
+        key,
+        description,
+        example: Some(example),
+    }
+}
+
+/// Every supported configuration key, in the order `config init` writes them
+pub const CONFIG_KEYS: &[ConfigKey] = &[
+    optional(
+        "profile",
+        "Profile applied when none is given with --profile or SAFE_AI_UTIL_PROFILE (dev, ci, restricted or a [profiles.<name>] table)",
+        "\"dev\"",
+    ),
+    optional(
+        "general.working_directory",
+        "Directory commands run in (defaults to the current directory)",
+        "\"/path/to/project\"",
+    ),
+    key("general.timeout_seconds", "Timeout for each command in seconds"),
+    key("general.max_retries", "Retries for retryable failures"),
+    key("logging.level", "Log level: trace, debug, info, warn or error"),
+    key("logging.format", "Log format: \"Json\", \"Pretty\" or \"Compact\""),
+    key("logging.file_rotation", "Rotate log files daily"),
+    key("logging.max_log_size", "Maximum size of a log file"),
+    key("logging.retention_days", "Days to keep rotated log files"),
+    key("safety.dry_run", "Show what would be done without executing"),
+    key(
+        "safety.simulate",
+        "Return fabricated results instead of executing; implies dry_run",
+    ),
+    key("safety.confirm_destructive", "Ask before destructive operations"),
+    key("safety.backup_before_delete", "Back up files before deleting them"),
+    key("safety.validate_paths", "Reject path traversal in arguments"),
+    key(
+        "safety.verify_binaries",
+        "Record the SHA-256 of external binaries and refuse ones that change until approved",
+    ),
+    key(
+        "safety.no_network",
+        "Run spawned commands with networking disabled (Linux and macOS)",
+    ),
+    key(
+        "safety.workspace_roots",
+        "Directories operations may read and write, relative to the working directory; empty means unrestricted",
+    ),
+    key("git.auto_stage", "Stage changes automatically before commit"),
+    key("git.require_message", "Require a commit message"),
+    key("git.push_hooks", "Run hooks on push"),
+    key("git.safe_force_push", "Use --force-with-lease instead of --force"),
+    optional("execution.shell", "Shell used for shell commands", "\"bash\""),
+    key("execution.environment_isolation", "Run commands with a minimal environment"),
+    key("execution.resource_limits.max_memory_mb", "Memory limit per command in MB"),
+    key("execution.resource_limits.max_cpu_percent", "CPU limit per command in percent"),
+    key("execution.resource_limits.max_execution_time", "Hard time limit per command in seconds"),
+    key("execution.escalate_on_failure", "Re-run failing commands once with verbose flags and keep the log in history"),
+    optional(
+        "scaffold.templates_dir",
+        "Directory searched for templates referenced by name",
+        "\"~/.config/safe-ai-util/templates\"",
+    ),
+    key("scaffold.allowed_sources", "Sources git-hosted templates must come from, matched by host and whole path segments"),
+    key("policy.allow_commands", "If non-empty, only these commands may run"),
+    key("policy.deny_commands", "Commands that may never run"),
+    optional(
+        "policy.rules",
+        "Argument rules; the first rule whose regex matches the arguments decides",
+        "[{ command = \"git\", args = \"^push .*--force\", action = \"deny\", reason = \"no force pushes\" }]",
+    ),
+    key(
+        "policy.allow_shell",
+        "Allow the recorded interactive shell, which bypasses the command allowlist and policy",
+    ),
+    key(
+        "policy.allow_scripts",
+        "Allow npm, pnpm and yarn to run package lifecycle scripts such as postinstall in node install and node ci",
+    ),
+    key(
+        "policy.allow_privileged",
+        "Allow docker compose up to start services that declare privileged: true",
+    ),
+    key(
+        "policy.allow_packages",
+        "Allow pkg install, and brew, apt-get and dnf commands that install, upgrade or remove packages",
+    ),
+    key(
+        "policy.allow_cloud_mutations",
+        "Allow aws, gcloud and az commands other than describe, list, get and show; cloud also needs --allow-mutations",
+    ),
+    key(
+        "policy.services",
+        "User services (systemd units or launchd labels) service may start, stop and read, e.g. [\"api-dev\", \"worker@*\"]; empty allows none",
+    ),
+    optional(
+        "policy.windows",
+        "Maintenance windows; commands a window matches are blocked outside its cron-style schedule",
+        "[{ name = \"deploys\", commands = [\"kubectl\"], args = \"^(apply|delete)\\\\b\", schedule = \"* 9-16 * * mon-thu\" }]",
+    ),
+    key("guardrails.enabled", "Estimate the files and bytes of large operations before running them"),
+    key("guardrails.warn_files", "Warn when an operation touches more files than this"),
+    key("guardrails.warn_bytes", "Warn when an operation touches more bytes than this"),
+    key("guardrails.confirm_files", "Ask for confirmation when an operation touches more files than this"),
+    key("guardrails.confirm_bytes", "Ask for confirmation when an operation touches more bytes than this"),
+    key("content_policy.enabled", "Scan content for denied patterns before writing files"),
+    key("content_policy.builtin", "Built-in rule sets to apply: credentials, malware"),
+    optional(
+        "content_policy.deny",
+        "Additional rules; a write whose content matches a rule's regex is refused",
+        "[{ name = \"agpl\", pattern = \"GNU Affero General Public License\", reason = \"AGPL code is not allowed\" }]",
+    ),
+    key(
+        "output.max_capture_bytes",
+        "Bytes of stdout and of stderr kept in batch, run and pipe results; the middle of longer output is dropped (0 keeps everything)",
+    ),
+    key("history.enabled", "Record every command run in the history database"),
+    key("history.retention_days", "Days runs and their output stay in history (0 keeps them forever)"),
+    key("lock.enabled", "Serialize mutating commands in a workspace with .safe-ai-util.lock"),
+    key("lock.timeout_seconds", "Seconds a mutating command waits for the workspace lock (0 fails at once)"),
+    key(
+        "capabilities.queue",
+        "Queue a request for approval when a command needs a missing or outdated tool (see tools requests)",
+    ),
+    key(
+        "process.builtin",
+        "Use the built-in handlers of process for files no configured handler matches",
+    ),
+    optional(
+        "process.handlers",
+        "Handlers process tries first; the first one matching a file's extension or MIME type runs for its action",
+        "[{ extensions = [\"json\"], action = \"format\", command = \"prettier --write {file}\" }]",
+    ),
+    optional(
+        "container.image",
+        "Image spawned commands run in, with the workspace bind-mounted; unset runs them on the host",
+        "\"rust:1.79\"",
+    ),
+    optional(
+        "container.runtime",
+        "Container runtime, docker or podman; the first found on PATH when unset",
+        "\"podman\"",
+    ),
+    key("container.read_only", "Mount the workspace read-only in the container"),
+    optional(
+        "remote.target",
+        "Machine spawned commands run on over SSH, as ssh://[user@]host[:port][/directory]; unset runs them here",
+        "\"ssh://ci@build01/srv/app\"",
+    ),
+    key(
+        "cargo.locked",
+        "Pass --locked to cargo build, test, clippy and doc, failing when Cargo.lock is out of date",
+    ),
+    key(
+        "go.flags",
+        "Build flags added to every go build, test, vet and generate, e.g. [\"-mod=readonly\"]",
+    ),
+    key(
+        "go.deny_flags",
+        "Flags the go command refuses in GOFLAGS and on its command line",
+    ),
+    key(
+        "kubectl.production_contexts",
+        "Regexes for kubectl contexts that kubectl apply only targets once confirmed",
+    ),
+    optional(
+        "terraform.binary",
+        "terraform or tofu; the first found on PATH when unset",
+        "\"tofu\"",
+    ),
+    key(
+        "terraform.plan_max_age_minutes",
+        "Minutes after which terraform apply refuses a saved plan as stale",
+    ),
+    key(
+        "helm.dev_contexts",
+        "Regexes for kubectl contexts that helm upgrade targets without a prior helm diff or template",
+    ),
+    key(
+        "helm.review_max_age_minutes",
+        "Minutes after which helm upgrade refuses a helm diff or template review as stale",
+    ),
+    optional(
+        "db.connections",
+        "PostgreSQL databases db query can use by name; passwords in URLs are redacted from logs",
+        "[{ name = \"app\", url = \"postgres://readonly@localhost/app\" }]",
+    ),
+    key(
+        "db.max_rows",
+        "Rows db query and db tables print at most, unless --max-rows is given",
+    ),
+    key(
+        "jvm.gradle_wrapper_sha256",
+        "SHA-256 checksums of gradle-wrapper.jar that jvm runs gradlew with without an approval",
+    ),
+    key(
+        "jvm.offline",
+        "Run Gradle with --offline and Maven with -o, unless --offline is given anyway",
+    ),
+    key(
+        "cmake.build_dir",
+        "Build directory cmake configure, build and test use without -B or a preset",
+    ),
+    optional(
+        "cmake.generator",
+        "Generator cmake configure uses without a preset or -G",
+        "\"Ninja\"",
+    ),
+    optional(
+        "cmake.jobs",
+        "Parallel jobs for cmake build and cmake test, unless -j is given",
+        "8",
+    ),
+    key(
+        "buf.curl_allowed_hosts",
+        "Hosts buf curl may call, e.g. [\"localhost\", \"*.staging.example.com\"]; empty allows none",
+    ),
+    key(
+        "http.allowed_hosts",
+        "Hosts the http command may reach, e.g. [\"api.github.com\", \"*.example.com\"]; empty allows none",
+    ),
+    optional(
+        "budget.max_wall_clock_seconds",
+        "Wall-clock seconds a session may run, nested invocations included",
+        "3600",
+    ),
+    optional(
+        "budget.max_subprocesses",
+        "Subprocesses a session may spawn, nested invocations included",
+        "200",
+    ),
+    optional(
+        "budget.max_network_bytes",
+        "Bytes built-in network operations of a session may transfer",
+        "104857600",
+    ),
+];
+
+/// Look up a documented configuration key
+pub fn config_key(key: &str) -> Option<&'static ConfigKey> {
+    CONFIG_KEYS.iter().find(|k| k.key == key)
+}
+
+/// Fields of a `[tools.<name>]` table
+const TOOL_PIN_FIELDS: [&str; 4] = ["path", "min_version", "pty", "verbose_args"];
+
+/// Whether `key` may appear in a configuration file, including inside `[profiles.<name>]`
+/// and `[tools.<name>]`
+pub fn is_known_key(key: &str) -> bool {
+    if let Some((_, inner)) = key
+        .strip_prefix("profiles.")
+        .and_then(|rest| rest.split_once('.'))
+    {
+        return inner != "profile" && !inner.starts_with("profiles.") && is_known_key(inner);
+    }
+    if let Some((_, field)) = key
+        .strip_prefix("tools.")
+        .and_then(|rest| rest.split_once('.'))
+    {
+        return TOOL_PIN_FIELDS.contains(&field);
+    }
+    config_key(key).is_some()
+}
+
 impl Config {
-    /// Load configuration from multiple sources
+    /// The default configuration as TOML
+    pub fn default_value() -> Result<toml::Value> {
+        toml::Value::try_from(Self::default())
+            .map_err(|e| AgentError::config(format!("Failed to serialize defaults: {}", e)))
+    }
+
+    /// Load configuration from all layers
     pub async fn load() -> Result<Self> {
-        let mut config = Self::default();
+        Ok(Self::resolve(LoadOptions::default()).await?.config)
+    }
+
+    /// Merge configuration layers, later layers taking precedence per value:
+    /// defaults, system, user, project (nearest `.safe-ai-util.toml` walking up from the
+    /// current directory), the file given with `--config`, the selected profile, then
+    /// environment variables
+    pub async fn resolve(options: LoadOptions<'_>) -> Result<ResolvedConfig> {
+        let mut value = Self::default_value()?;
+        let mut sources = BTreeMap::new();
+        record_sources(&value, "", &ConfigSource::Default, &mut sources);
 
-        // Try to load from user config directory
-        if let Some(user_config) = Self::user_config_path() {
-            if user_config.exists() {
-                info!("Loading user configuration from: {}", user_config.display());
-                config = Self::load_from_file(&user_config).await?;
+        let mut files = Vec::new();
+        for source in Self::config_layers(options.file) {
+            let Some(path) = source.path().map(Path::to_path_buf) else {
+                continue;
+            };
+            if !path.exists() {
+                if matches!(source, ConfigSource::Explicit(_)) {
+                    return Err(AgentError::config(format!(
+                        "Config file not found: {}",
+                        path.display()
+                    )));
+                }
+                continue;
             }
+
+            info!("Loading configuration from {}", source);
+            let layer = Self::load_value(&path).await?;
+            if source.is_untrusted() {
+                merge_untrusted(&mut value, layer, &source, &mut sources);
+            } else {
+                merge_values(&mut value, layer, "", &source, &mut sources);
+            }
+            files.push(source);
         }
 
-        // Try to load from project config
-        let project_config = Path::new(".safe-ai-util.toml");
-        if project_config.exists() {
-            info!(
-                "Loading project configuration from: {}",
-                project_config.display()
-            );
-            let project_config = Self::load_from_file(project_config).await?;
-            config = Self::merge_configs(config, project_config);
+        let profile = options
+            .profile
+            .map(str::to_string)
+            .or_else(|| std::env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty()))
+            .or_else(|| {
+                value
+                    .get("profile")
+                    .and_then(|p| p.as_str())
+                    .map(str::to_string)
+            });
+        // A profile selected by the project file is held to the same limits as the file
+        let selected_by_project = options.profile.is_none()
+            && std::env::var(PROFILE_ENV).map_or(true, |p| p.is_empty())
+            && sources.get("profile").is_some_and(ConfigSource::is_untrusted);
+        if let Some(name) = &profile {
+            info!("Applying profile '{}'", name);
+            let layer = profile_layer(&value, name)?;
+            let source = ConfigSource::Profile(name.clone());
+            if selected_by_project {
+                merge_untrusted(&mut value, layer, &source, &mut sources);
+            } else {
+                merge_profile(&mut value, layer, &source, &mut sources);
+            }
         }
 
-        // Override with environment variables
-        config = Self::apply_env_overrides(config)?;
+        Self::apply_env_overrides(&mut value, &mut sources);
+
+        let config: Self = value
+            .clone()
+            .try_into()
+            .map_err(|e| AgentError::config(format!("Invalid configuration: {}", e)))?;
 
         debug!("Final configuration: {:#?}", config);
-        Ok(config)
+        Ok(ResolvedConfig {
+            config,
+            value,
+            sources,
+            files,
+            profile,
+        })
+    }
+
+    /// Names of the built-in profiles and those defined in `value`
+    pub fn profile_names(value: &toml::Value) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_PROFILES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        if let Some(defined) = value.get("profiles").and_then(|p| p.as_table()) {
+            names.extend(defined.keys().cloned());
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Candidate configuration files, lowest precedence first
+    pub fn config_layers(explicit: Option<&Path>) -> Vec<ConfigSource> {
+        let mut layers = Vec::new();
+        if let Some(system) = Self::system_config_path() {
+            layers.push(ConfigSource::System(system));
+        }
+        if let Some(user) = Self::user_config_path() {
+            layers.push(ConfigSource::User(user));
+        }
+        if let Some(project) = Self::project_config_path() {
+            layers.push(ConfigSource::Project(project));
+        }
+        if let Some(path) = explicit {
+            layers.push(ConfigSource::Explicit(path.to_path_buf()));
+        }
+        layers
+    }
+
+    /// Get the system-wide configuration file path
+    pub fn system_config_path() -> Option<PathBuf> {
+        if cfg!(windows) {
+            std::env::var_os("ProgramData")
+                .map(|dir| PathBuf::from(dir).join("safe-ai-util").join("config.toml"))
+        } else {
+            Some(PathBuf::from("/etc/safe-ai-util/config.toml"))
+        }
     }
 
     /// Get the user configuration file path
-    fn user_config_path() -> Option<PathBuf> {
+    pub fn user_config_path() -> Option<PathBuf> {
         dirs::config_dir().map(|dir| dir.join("safe-ai-util").join("config.toml"))
     }
 
-    /// Load configuration from a TOML file
-    async fn load_from_file(path: &Path) -> Result<Self> {
+    /// Find the nearest project configuration in the current directory or its ancestors
+    pub fn project_config_path() -> Option<PathBuf> {
+        let cwd = std::env::current_dir().ok()?;
+        cwd.ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .find(|path| path.is_file())
+    }
+
+    /// Load a TOML file as an untyped value so partial files can be merged
+    async fn load_value(path: &Path) -> Result<toml::Value> {
         let content = fs::read_to_string(path).await.map_err(|e| {
             AgentError::config(format!(
                 "Failed to read config file {}: {}",
@@ -166,29 +1309,862 @@ impl Config {
         })
     }
 
-    /// Merge two configurations, with the second taking precedence
-    fn merge_configs(_base: Self, override_config: Self) -> Self {
-        // For now, just return the override config
-        // In a full implementation, we'd merge each field intelligently
-        override_config
-    }
-
     /// Apply environment variable overrides
-    fn apply_env_overrides(mut config: Self) -> Result<Self> {
+    fn apply_env_overrides(value: &mut toml::Value, sources: &mut BTreeMap<String, ConfigSource>) {
+        let mut overrides: Vec<(String, String, toml::Value)> = Vec::new();
+
         if let Ok(level) = std::env::var("COPILOT_AGENT_LOG_LEVEL") {
-            config.logging.level = level;
+            overrides.push((
+                "COPILOT_AGENT_LOG_LEVEL".to_string(),
+                "logging.level".to_string(),
+                toml::Value::String(level),
+            ));
         }
 
         if let Ok(dry_run) = std::env::var("COPILOT_AGENT_DRY_RUN") {
-            config.safety.dry_run = dry_run.parse().unwrap_or(false);
+            overrides.push((
+                "COPILOT_AGENT_DRY_RUN".to_string(),
+                "safety.dry_run".to_string(),
+                toml::Value::Boolean(dry_run.parse().unwrap_or(false)),
+            ));
         }
 
         if let Ok(timeout) = std::env::var("COPILOT_AGENT_TIMEOUT") {
-            if let Ok(timeout_secs) = timeout.parse::<u64>() {
-                config.general.timeout_seconds = timeout_secs;
+            if let Ok(timeout_secs) = timeout.parse::<i64>() {
+                overrides.push((
+                    "COPILOT_AGENT_TIMEOUT".to_string(),
+                    "general.timeout_seconds".to_string(),
+                    toml::Value::Integer(timeout_secs),
+                ));
+            }
+        }
+
+        let mut prefixed: Vec<(String, String)> = std::env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        prefixed.sort();
+        for (name, raw) in prefixed {
+            let key = name[ENV_PREFIX.len()..].to_lowercase().replace("__", ".");
+            if key.is_empty() {
+                continue;
+            }
+            overrides.push((name, key, parse_value(&raw)));
+        }
+
+        for (name, key, new_value) in overrides {
+            if !covered(&UNTRUSTED_KEYS, &key) {
+                if covered(&TIGHTENED_KEYS, &key) {
+                    tighten(value, &key, new_value, &ConfigSource::Env(name), sources);
+                } else {
+                    warn!(
+                        "Ignoring {} from {}: only trusted configuration files can set it",
+                        key, name
+                    );
+                }
+                continue;
             }
+            debug!("Overriding {} from {}", key, name);
+            set_value(value, &key, new_value);
+            sources.retain(|existing, _| !existing.starts_with(&format!("{}.", key)));
+            sources.insert(key, ConfigSource::Env(name));
+        }
+    }
+}
+
+/// The settings of a profile: the built-in definition, if any, extended by the
+/// `[profiles.<name>]` table of the merged configuration files
+fn profile_layer(value: &toml::Value, name: &str) -> Result<toml::Value> {
+    let builtin = BUILTIN_PROFILES.iter().find(|(n, _)| *n == name);
+    let defined = value.get("profiles").and_then(|p| p.get(name)).cloned();
+    if builtin.is_none() && defined.is_none() {
+        return Err(AgentError::config(format!(
+            "Unknown profile '{}' (available: {})",
+            name,
+            Config::profile_names(value).join(", ")
+        )));
+    }
+
+    let mut layer = match builtin {
+        Some((_, text)) => toml::from_str(text).map_err(|e| {
+            AgentError::config(format!("Invalid built-in profile '{}': {}", name, e))
+        })?,
+        None => toml::Value::Table(toml::Table::new()),
+    };
+    if let Some(defined) = defined {
+        merge_values(
+            &mut layer,
+            defined,
+            "",
+            &ConfigSource::Default,
+            &mut BTreeMap::new(),
+        );
+    }
+    Ok(layer)
+}
+
+/// Interpret a command-line or environment value as a TOML value, falling back to a plain string
+pub fn parse_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// Check a configuration file's contents for syntax errors, unknown keys, type errors and
+/// invalid policy rules, returning one message per problem
+pub fn validate_toml(content: &str) -> Vec<String> {
+    let layer: toml::Value = match toml::from_str(content) {
+        Ok(layer) => layer,
+        Err(e) => return vec![format!("syntax error: {}", e)],
+    };
+
+    let mut problems = Vec::new();
+    let mut keys = BTreeMap::new();
+    record_sources(&layer, "", &ConfigSource::Default, &mut keys);
+    for key in keys.keys() {
+        if !is_known_key(key) {
+            problems.push(format!("unknown key '{}'", key));
         }
+    }
+
+    // Each profile must also produce a valid configuration on its own
+    if let Some(profiles) = layer.get("profiles").and_then(|p| p.as_table()) {
+        for (name, profile) in profiles {
+            let Ok(mut merged) = Config::default_value() else {
+                continue;
+            };
+            merge_values(
+                &mut merged,
+                profile.clone(),
+                "",
+                &ConfigSource::Default,
+                &mut BTreeMap::new(),
+            );
+            if let Err(e) = merged.try_into::<Config>() {
+                problems.push(format!(
+                    "profile '{}': invalid value: {}",
+                    name,
+                    e.message()
+                ));
+            }
+        }
+    }
+
+    let mut merged = match Config::default_value() {
+        Ok(value) => value,
+        Err(e) => return vec![e.to_string()],
+    };
+    merge_values(
+        &mut merged,
+        layer,
+        "",
+        &ConfigSource::Default,
+        &mut BTreeMap::new(),
+    );
+    match merged.try_into::<Config>() {
+        Ok(config) => {
+            if let Err(e) = crate::security::policy::Policy::from_config(&config.policy) {
+                problems.push(e.to_string());
+            }
+            if let Err(e) =
+                crate::security::content::ContentPolicy::from_config(&config.content_policy)
+            {
+                problems.push(e.to_string());
+            }
+            for (name, pin) in &config.tools {
+                if let Err(e) = crate::tools::validate_pin(name, pin) {
+                    problems.push(e.to_string());
+                }
+            }
+        }
+        Err(e) => problems.push(format!("invalid value: {}", e.message())),
+    }
+
+    problems
+}
+
+/// Set a dotted key, creating intermediate tables as needed
+fn set_value(root: &mut toml::Value, key: &str, new_value: toml::Value) {
+    let mut current = root;
+    let mut parts = key.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if !current.is_table() {
+            *current = toml::Value::Table(toml::Table::new());
+        }
+        let table = current.as_table_mut().unwrap();
+        if parts.peek().is_none() {
+            table.insert(part.to_string(), new_value);
+            return;
+        }
+        current = table
+            .entry(part.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    }
+}
+
+/// Settings an untrusted layer may change like any other layer. A key covers the keys below
+/// it and `*` matches any one key; everything else is tightened (see [`TIGHTENED_KEYS`]) or
+/// ignored, so a project file cannot pick binaries, handlers, shells, hosts or contexts.
+const UNTRUSTED_KEYS: [&str; 13] = [
+    "profile",
+    "general.timeout_seconds",
+    "general.max_retries",
+    "logging",
+    "git.auto_stage",
+    "git.require_message",
+    "execution.escalate_on_failure",
+    "output",
+    "lock.timeout_seconds",
+    "cmake",
+    "db.max_rows",
+    "tools.*.min_version",
+    "tools.*.pty",
+];
+
+/// Security settings an untrusted layer may only tighten (see [`tightened`])
+const TIGHTENED_KEYS: [&str; 16] = [
+    "policy",
+    "safety",
+    "budget",
+    "guardrails",
+    "content_policy.enabled",
+    "content_policy.builtin",
+    "content_policy.deny",
+    "git.safe_force_push",
+    "cargo.locked",
+    "jvm.offline",
+    "go.deny_flags",
+    "kubectl.production_contexts",
+    "helm.dev_contexts",
+    "http.allowed_hosts",
+    "buf.curl_allowed_hosts",
+    "scaffold.allowed_sources",
+];
+
+/// Policy switches that allow more when on; an untrusted layer may only turn them off
+const POLICY_PERMISSIONS: [&str; 5] = [
+    "allow_shell",
+    "allow_scripts",
+    "allow_privileged",
+    "allow_packages",
+    "allow_cloud_mutations",
+];
+
+/// Switches that protect more when on; an untrusted layer may only turn them on
+const GUARDS: [&str; 12] = [
+    "safety.dry_run",
+    "safety.simulate",
+    "safety.confirm_destructive",
+    "safety.backup_before_delete",
+    "safety.validate_paths",
+    "safety.verify_binaries",
+    "safety.no_network",
+    "guardrails.enabled",
+    "content_policy.enabled",
+    "git.safe_force_push",
+    "cargo.locked",
+    "jvm.offline",
+];
+
+/// Lists whose entries restrict more; an untrusted layer may only add to them
+const DENY_LISTS: [&str; 6] = [
+    "policy.deny_commands",
+    "policy.windows",
+    "content_policy.builtin",
+    "content_policy.deny",
+    "go.deny_flags",
+    "kubectl.production_contexts",
+];
+
+/// Lists whose entries allow more, where empty allows nothing; an untrusted layer may only
+/// remove entries
+const ALLOW_LISTS: [&str; 5] = [
+    "policy.services",
+    "helm.dev_contexts",
+    "http.allowed_hosts",
+    "buf.curl_allowed_hosts",
+    "scaffold.allowed_sources",
+];
+
+/// Whether one of `patterns` covers the dotted `key`: each of its parts is `*` or equal to
+/// the key's part at the same position, and the key has at least as many parts
+fn covered(patterns: &[&str], key: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        let parts: Vec<&str> = key.split('.').collect();
+        let pattern: Vec<&str> = pattern.split('.').collect();
+        pattern.len() <= parts.len()
+            && pattern
+                .iter()
+                .zip(&parts)
+                .all(|(expected, part)| *expected == "*" || expected == part)
+    })
+}
+
+/// Merge a layer an agent can write (see [`ConfigSource::is_untrusted`])
+///
+/// Only the settings in [`UNTRUSTED_KEYS`] merge as usual. Those in [`TIGHTENED_KEYS`] are
+/// applied only as far as they tighten the ones already merged, and anything else is
+/// ignored with a warning. `[profiles.<name>]` tables keep only the settings in
+/// [`UNTRUSTED_KEYS`], since a profile selected with `--profile` is applied as trusted.
+fn merge_untrusted(
+    base: &mut toml::Value,
+    layer: toml::Value,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    let toml::Value::Table(mut layer) = layer else {
+        return;
+    };
+    let mut allowed = toml::Table::new();
+    if let Some(profiles) = layer.remove("profiles") {
+        let mut kept = toml::Table::new();
+        for (name, profile) in profiles.as_table().cloned().unwrap_or_default() {
+            let mut settings = toml::Table::new();
+            if let toml::Value::Table(profile) = profile {
+                filter_untrusted(profile, "", &mut settings, &mut |key, _| {
+                    warn!(
+                        "Ignoring profiles.{}.{} from {}: profiles defined there cannot set it",
+                        name, key, source
+                    );
+                });
+            }
+            kept.insert(name, toml::Value::Table(settings));
+        }
+        allowed.insert("profiles".to_string(), toml::Value::Table(kept));
+    }
+    filter_untrusted(layer, "", &mut allowed, &mut |key, value| {
+        if covered(&TIGHTENED_KEYS, &key) {
+            tighten(base, &key, value, source, sources);
+        } else {
+            warn!(
+                "Ignoring {} from {}: only trusted configuration files can set it",
+                key, source
+            );
+        }
+    });
+    merge_values(base, toml::Value::Table(allowed), "", source, sources);
+}
+
+/// Merge a profile selected with `--profile`, the environment or a trusted file
+///
+/// The `[policy]` lists add to the ones already merged rather than replacing them: the
+/// profile's rules go ahead of the existing rules, its denials, windows and services are
+/// added, and its allow list narrows an existing one. Everything else merges as usual.
+fn merge_profile(
+    base: &mut toml::Value,
+    layer: toml::Value,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    use toml::Value::Array;
+
+    let toml::Value::Table(mut layer) = layer else {
+        return;
+    };
+    let policy = match layer.remove("policy") {
+        Some(toml::Value::Table(policy)) => policy,
+        Some(other) => {
+            layer.insert("policy".to_string(), other);
+            toml::Table::new()
+        }
+        None => toml::Table::new(),
+    };
+    merge_values(base, toml::Value::Table(layer), "", source, sources);
+
+    for (name, new) in policy {
+        let key = join_key("policy", &name);
+        let combined = match (name.as_str(), get_value(base, &key).cloned(), new) {
+            ("rules", Some(Array(current)), Array(new)) => {
+                Array(new.into_iter().chain(current).collect())
+            }
+            ("allow_commands", Some(current @ Array(_)), new @ Array(_)) => {
+                match tightened(&key, current, new) {
+                    Some(kept) => kept,
+                    None => {
+                        warn!(
+                            "Ignoring {} from {}: it shares no command with the configured allow list",
+                            key, source
+                        );
+                        continue;
+                    }
+                }
+            }
+            ("deny_commands" | "windows" | "services", Some(Array(mut current)), Array(new)) => {
+                for item in new {
+                    if !current.contains(&item) {
+                        current.push(item);
+                    }
+                }
+                Array(current)
+            }
+            (_, _, new) => {
+                let mut layer = toml::Value::Table(toml::Table::new());
+                set_value(&mut layer, &key, new);
+                merge_values(base, layer, "", source, sources);
+                continue;
+            }
+        };
+        set_value(base, &key, combined);
+        sources.retain(|existing, _| !existing.starts_with(&format!("{}.", key)));
+        sources.insert(key, source.clone());
+    }
+}
+
+/// Copy the settings of `table` that [`UNTRUSTED_KEYS`] covers into `allowed` and pass the
+/// others, by dotted key, to `rest`
+fn filter_untrusted(
+    table: toml::Table,
+    prefix: &str,
+    allowed: &mut toml::Table,
+    rest: &mut dyn FnMut(String, toml::Value),
+) {
+    for (name, value) in table {
+        let key = join_key(prefix, &name);
+        if covered(&UNTRUSTED_KEYS, &key) {
+            allowed.insert(name, value);
+            continue;
+        }
+        match value {
+            toml::Value::Table(child) => {
+                let mut kept = toml::Table::new();
+                filter_untrusted(child, &key, &mut kept, rest);
+                if !kept.is_empty() {
+                    allowed.insert(name, toml::Value::Table(kept));
+                }
+            }
+            value => rest(key, value),
+        }
+    }
+}
+
+/// Apply `value` to the security setting `key` as far as it tightens the current value:
+/// denials and maintenance windows are added to, deny rules are put ahead of the existing
+/// rules, allow lists and workspace roots can only shrink, limits can only be lowered,
+/// permissions can only be turned off and safety switches only on. Anything that would
+/// loosen the setting is ignored with a warning.
+fn tighten(
+    base: &mut toml::Value,
+    key: &str,
+    value: toml::Value,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    let current = get_value(base, key)
+        .cloned()
+        .unwrap_or_else(|| toml::Value::Array(Vec::new()));
+    let Some(tightened) = tightened(key, current, value) else {
+        warn!(
+            "Ignoring {} from {}: it can only tighten the security settings",
+            key, source
+        );
+        return;
+    };
+    debug!("Tightening {} from {}", key, source);
+    set_value(base, key, tightened);
+    sources.retain(|existing, _| !existing.starts_with(&format!("{}.", key)));
+    sources.insert(key.to_string(), source.clone());
+}
+
+/// The value of the security setting `key` after an untrusted layer sets it to `new`, or
+/// `None` if the layer would loosen it
+fn tightened(key: &str, current: toml::Value, new: toml::Value) -> Option<toml::Value> {
+    use toml::Value::{Array, Boolean, Integer};
+
+    let (section, name) = key.split_once('.')?;
+    match (section, name, current, new) {
+        ("policy", name, Boolean(current), Boolean(new)) if POLICY_PERMISSIONS.contains(&name) => {
+            (current || !new).then_some(Boolean(current && new))
+        }
+        (_, _, Boolean(current), Boolean(new)) if GUARDS.contains(&key) => {
+            (!current || new).then_some(Boolean(current || new))
+        }
+        (_, _, Array(mut current), Array(new)) if DENY_LISTS.contains(&key) => {
+            for item in new {
+                if !current.contains(&item) {
+                    current.push(item);
+                }
+            }
+            Some(Array(current))
+        }
+        ("policy", "rules", Array(current), Array(new)) => {
+            // Deny rules go first so no existing allow rule can shadow them
+            let is_deny = |rule: &toml::Value| {
+                rule.get("action").and_then(toml::Value::as_str) == Some("deny")
+            };
+            if !new.iter().all(is_deny) {
+                return None;
+            }
+            Some(Array(new.into_iter().chain(current).collect()))
+        }
+        // An empty allow list allows every command, an empty service list none
+        ("policy", "allow_commands", Array(current), Array(new)) if current.is_empty() => {
+            Some(Array(new))
+        }
+        ("policy", "allow_commands", Array(current), Array(new)) => {
+            let kept: Vec<_> = new.into_iter().filter(|item| current.contains(item)).collect();
+            (!kept.is_empty()).then_some(Array(kept))
+        }
+        (_, _, Array(current), Array(new)) if ALLOW_LISTS.contains(&key) => Some(Array(
+            new.into_iter()
+                .filter(|item| current.contains(item))
+                .collect(),
+        )),
+        // Lower limits and thresholds are tighter; an unset budget is unlimited
+        ("budget", _, current, Integer(new)) => Some(Integer(
+            current.as_integer().map_or(new, |current| current.min(new)),
+        )),
+        ("guardrails", _, Integer(current), Integer(new)) => Some(Integer(current.min(new))),
+        // Empty roots leave paths unrestricted
+        ("safety", "workspace_roots", Array(current), Array(new)) if current.is_empty() => {
+            Some(Array(new))
+        }
+        ("safety", "workspace_roots", Array(current), Array(new)) => {
+            let roots: Vec<PathBuf> = current
+                .iter()
+                .filter_map(toml::Value::as_str)
+                .filter_map(lexical_path)
+                .collect();
+            let kept: Vec<_> = new
+                .into_iter()
+                .filter(|root| {
+                    root.as_str().and_then(lexical_path).is_some_and(|path| {
+                        roots.iter().any(|existing| {
+                            path.has_root() == existing.has_root() && path.starts_with(existing)
+                        })
+                    })
+                })
+                .collect();
+            (!kept.is_empty()).then_some(Array(kept))
+        }
+        _ => None,
+    }
+}
+
+/// A configured path with `.` components removed; `None` if it uses `..`
+fn lexical_path(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    if path.components().any(|c| c == std::path::Component::ParentDir) {
+        return None;
+    }
+    Some(
+        path.components()
+            .filter(|c| *c != std::path::Component::CurDir)
+            .collect(),
+    )
+}
+
+/// Look up a dotted key
+fn get_value<'a>(root: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.').try_fold(root, |current, part| current.get(part))
+}
+
+/// Merge `layer` into `base` table by table; any non-table value replaces the old one
+fn merge_values(
+    base: &mut toml::Value,
+    layer: toml::Value,
+    prefix: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match (base, layer) {
+        (toml::Value::Table(base_table), toml::Value::Table(layer_table)) => {
+            for (key, layer_value) in layer_table {
+                let path = join_key(prefix, &key);
+                match base_table.get_mut(&key) {
+                    Some(existing) if existing.is_table() && layer_value.is_table() => {
+                        merge_values(existing, layer_value, &path, source, sources);
+                    }
+                    _ => {
+                        sources.retain(|existing, _| !existing.starts_with(&format!("{}.", path)));
+                        record_sources(&layer_value, &path, source, sources);
+                        base_table.insert(key, layer_value);
+                    }
+                }
+            }
+        }
+        (base, layer) => {
+            record_sources(&layer, prefix, source, sources);
+            *base = layer;
+        }
+    }
+}
+
+/// Record `source` for every leaf under `value`
+fn record_sources(
+    value: &toml::Value,
+    prefix: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, child) in table {
+                record_sources(child, &join_key(prefix, key), source, sources);
+            }
+        }
+        _ => {
+            sources.insert(prefix.to_string(), source.clone());
+        }
+    }
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::policy::Policy;
+
+    #[test]
+    fn test_partial_layers_merge_per_value() {
+        let mut value = toml::Value::try_from(Config::default()).unwrap();
+        let mut sources = BTreeMap::new();
+        record_sources(&value, "", &ConfigSource::Default, &mut sources);
+
+        let user = ConfigSource::User(PathBuf::from("user.toml"));
+        let project = ConfigSource::Project(PathBuf::from(".safe-ai-util.toml"));
+        let user_layer: toml::Value =
+            toml::from_str("[safety]\ndry_run = true\n[general]\ntimeout_seconds = 60\n").unwrap();
+        let project_layer: toml::Value =
+            toml::from_str("[general]\ntimeout_seconds = 30\n[policy]\ndeny_commands = [\"rm\"]\n")
+                .unwrap();
+        merge_values(&mut value, user_layer, "", &user, &mut sources);
+        merge_values(&mut value, project_layer, "", &project, &mut sources);
+
+        let config: Config = value.try_into().unwrap();
+        assert!(config.safety.dry_run);
+        assert!(config.safety.confirm_destructive);
+        assert_eq!(config.general.timeout_seconds, 30);
+        assert_eq!(config.policy.deny_commands, vec!["rm".to_string()]);
+        assert_eq!(sources["safety.dry_run"], user);
+        assert_eq!(sources["general.timeout_seconds"], project);
+        assert_eq!(sources["general.max_retries"], ConfigSource::Default);
+    }
+
+    #[test]
+    fn test_untrusted_layers_only_tighten() {
+        let mut value = toml::Value::try_from(Config::default()).unwrap();
+        let mut sources = BTreeMap::new();
+        let user = ConfigSource::User(PathBuf::from("user.toml"));
+        let project = ConfigSource::Project(PathBuf::from(".safe-ai-util.toml"));
+        let user_layer: toml::Value = toml::from_str(
+            "[safety]\ndry_run = true\nworkspace_roots = [\".\"]\n[policy]\ndeny_commands = [\"git\"]\nallow_shell = true\n",
+        )
+        .unwrap();
+        merge_values(&mut value, user_layer, "", &user, &mut sources);
+
+        let project_layer: toml::Value = toml::from_str(
+            "[general]\ntimeout_seconds = 30\n\
+             [safety]\ndry_run = false\nno_network = true\nworkspace_roots = [\"src\", \"/tmp\"]\n\
+             [policy]\ndeny_commands = [\"curl\"]\nallow_shell = false\n\
+             rules = [{ command = \"git\", args = \"^push\", action = \"deny\" }]\n\
+             [profiles.dev.policy]\ndeny_commands = []\n",
+        )
+        .unwrap();
+        merge_untrusted(&mut value, project_layer, &project, &mut sources);
+        let loosening: toml::Value = toml::from_str(
+            "[policy]\ndeny_commands = []\nallow_shell = true\nrules = [{ command = \"*\", args = \".\", action = \"allow\" }]\n",
+        )
+        .unwrap();
+        merge_untrusted(&mut value, loosening, &project, &mut sources);
+
+        assert!(value["profiles"]["dev"].get("policy").is_none());
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.general.timeout_seconds, 30);
+        assert!(config.safety.dry_run);
+        assert!(config.safety.no_network);
+        assert_eq!(config.safety.workspace_roots, vec![PathBuf::from("src")]);
+        assert_eq!(config.policy.deny_commands, vec!["git".to_string(), "curl".to_string()]);
+        assert!(!config.policy.allow_shell);
+        assert_eq!(config.policy.rules.len(), 1);
+        assert_eq!(config.policy.rules[0].action, PolicyAction::Deny);
+        assert_eq!(sources["safety.dry_run"], user);
+        assert_eq!(sources["safety.no_network"], project);
+    }
+
+    #[test]
+    fn test_untrusted_layers_cannot_pick_binaries() {
+        let mut value = toml::Value::try_from(Config::default()).unwrap();
+        let mut sources = BTreeMap::new();
+        let project = ConfigSource::Project(PathBuf::from(".safe-ai-util.toml"));
+        let project_layer: toml::Value = toml::from_str(
+            "[tools.git]\npath = \"/tmp/evil/git\"\nmin_version = \"2.40\"\n\
+             [[process.handlers]]\nextensions = [\"json\"]\naction = \"validate\"\ncommand = \"/tmp/evil/jq\"\n\
+             [execution]\nshell = \"/tmp/evil/sh\"\n\
+             [http]\nallowed_hosts = [\"evil.example\"]\n\
+             [remote]\ntarget = \"ssh://evil.example\"\n\
+             [budget]\nmax_subprocesses = 50\n\
+             [profiles.team]\ngeneral = { timeout_seconds = 5 }\ntools = { cargo = { path = \"/tmp/evil/cargo\" } }\n",
+        )
+        .unwrap();
+        merge_untrusted(&mut value, project_layer, &project, &mut sources);
+
+        assert!(value["profiles"]["team"].get("tools").is_none());
+        assert_eq!(
+            value["profiles"]["team"]["general"]["timeout_seconds"].as_integer(),
+            Some(5)
+        );
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.tools["git"].path, None);
+        assert_eq!(config.tools["git"].min_version.as_deref(), Some("2.40"));
+        assert!(config.process.handlers.is_empty());
+        assert_eq!(config.execution.shell, None);
+        assert!(config.http.allowed_hosts.is_empty());
+        assert_eq!(config.remote.target, None);
+        assert_eq!(config.budget.max_subprocesses, Some(50));
+        assert_eq!(sources["budget.max_subprocesses"], project);
+    }
+
+    #[test]
+    fn test_tightened_allow_lists() {
+        let list = |items: &[&str]| toml::Value::try_from(items).unwrap();
+        assert_eq!(
+            tightened("policy.allow_commands", list(&[]), list(&["git"])),
+            Some(list(&["git"]))
+        );
+        assert_eq!(
+            tightened("policy.allow_commands", list(&["git", "cargo"]), list(&["git", "curl"])),
+            Some(list(&["git"]))
+        );
+        // Nothing in common would empty the list, which allows every command
+        assert_eq!(tightened("policy.allow_commands", list(&["git"]), list(&["curl"])), None);
+        assert_eq!(tightened("policy.services", list(&["api"]), list(&["db"])), Some(list(&[])));
+        assert_eq!(tightened("safety.workspace_roots", list(&["."]), list(&["../other"])), None);
+        assert_eq!(tightened("safety.mystery", list(&[]), list(&[])), None);
+        assert_eq!(
+            tightened("http.allowed_hosts", list(&["a.example"]), list(&["a.example", "b.example"])),
+            Some(list(&["a.example"]))
+        );
+        assert_eq!(
+            tightened("budget.max_subprocesses", toml::Value::Integer(10), toml::Value::Integer(50)),
+            Some(toml::Value::Integer(10))
+        );
+    }
+
+    #[test]
+    fn test_every_default_key_is_documented() {
+        let value = toml::Value::try_from(Config::default()).unwrap();
+        let mut keys = BTreeMap::new();
+        record_sources(&value, "", &ConfigSource::Default, &mut keys);
+        for key in keys.keys() {
+            assert!(config_key(key).is_some(), "{} is not in CONFIG_KEYS", key);
+        }
+    }
+
+    #[test]
+    fn test_validate_toml() {
+        assert!(validate_toml("[safety]\ndry_run = true\n").is_empty());
+        assert!(validate_toml("[safety\n")[0].starts_with("syntax error"));
+
+        let problems = validate_toml(
+            "[safety]\ndry_rn = true\n[general]\ntimeout_seconds = \"soon\"\n[[policy.rules]]\ncommand = \"git\"\nargs = \"(\"\naction = \"deny\"\n",
+        );
+        assert!(problems.contains(&"unknown key 'safety.dry_rn'".to_string()));
+        assert!(problems.iter().any(|p| p.starts_with("invalid value")));
+
+        let problems =
+            validate_toml("[[policy.rules]]\ncommand = \"git\"\nargs = \"(\"\naction = \"deny\"\n");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("Invalid policy pattern"));
+
+        assert!(validate_toml("[tools.git]\npath = \"/usr/bin/git\"\nmin_version = \"2.40\"\n").is_empty());
+        assert!(validate_toml("[tools.buf]\nmin_version = 1\n").is_empty());
+        assert!(validate_toml("[tools.buf]\nmin_version = 1.30\n")[0].contains("quoted version"));
+        let problems = validate_toml("[tools.git]\npath = \"git\"\nversion = \"2\"\n");
+        assert!(problems.contains(&"unknown key 'tools.git.version'".to_string()));
+        assert!(problems.iter().any(|p| p.contains("must be an absolute path")));
+    }
+
+    #[test]
+    fn test_builtin_profiles() {
+        let defaults = toml::Value::try_from(Config::default()).unwrap();
+        for (name, _) in BUILTIN_PROFILES {
+            let mut value = defaults.clone();
+            let layer = profile_layer(&value, name).unwrap();
+            merge_profile(
+                &mut value,
+                layer,
+                &ConfigSource::Default,
+                &mut BTreeMap::new(),
+            );
+            let config: Config = value.try_into().unwrap();
+            let policy = Policy::from_config(&config.policy).unwrap();
+            if *name == "restricted" {
+                let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+                assert!(policy.evaluate("git", &args(&["push", "-f"])).is_err());
+                assert!(policy
+                    .evaluate("git", &args(&["push", "--force", "origin"]))
+                    .is_err());
+                assert!(policy
+                    .evaluate("git", &args(&["push", "--force-with-lease", "origin"]))
+                    .is_ok());
+                assert!(policy.evaluate("git", &args(&["push", "origin"])).is_ok());
+                assert!(policy.evaluate("curl", &[]).is_err());
+            }
+        }
+
+        // A profile adds to the policy of the configuration files instead of replacing it
+        let mut value = defaults.clone();
+        let user: toml::Value = toml::from_str(
+            r#"
+[policy]
+allow_commands = ["git", "make"]
+deny_commands = ["rm"]
+rules = [{ command = "git", args = '^tag\b', action = "deny" }]
+"#,
+        )
+        .unwrap();
+        merge_values(
+            &mut value,
+            user,
+            "",
+            &ConfigSource::Default,
+            &mut BTreeMap::new(),
+        );
+        let layer = profile_layer(&value, "restricted").unwrap();
+        merge_profile(
+            &mut value,
+            layer,
+            &ConfigSource::Profile("restricted".into()),
+            &mut BTreeMap::new(),
+        );
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.policy.allow_commands, vec!["git"]);
+        assert_eq!(config.policy.deny_commands, vec!["rm"]);
+        assert_eq!(config.policy.rules.len(), 4);
+        let policy = Policy::from_config(&config.policy).unwrap();
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(policy.evaluate("git", &args(&["tag", "v1"])).is_err());
+        assert!(policy.evaluate("git", &args(&["push", "-f"])).is_err());
+
+        let custom: toml::Value =
+            toml::from_str("[profiles.team.general]\ntimeout_seconds = 5\n").unwrap();
+        assert!(profile_layer(&custom, "team").is_ok());
+        assert!(profile_layer(&custom, "missing").is_err());
+    }
+
+    #[test]
+    fn test_env_values() {
+        assert_eq!(parse_value("true"), toml::Value::Boolean(true));
+        assert_eq!(parse_value("42"), toml::Value::Integer(42));
+        assert_eq!(
+            parse_value("debug"),
+            toml::Value::String("debug".to_string())
+        );
 
-        Ok(config)
+        let mut value = toml::Value::try_from(Config::default()).unwrap();
+        set_value(
+            &mut value,
+            "execution.shell",
+            toml::Value::String("bash".into()),
+        );
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.execution.shell.as_deref(), Some("bash"));
     }
 }
@@ -0,0 +1,206 @@
+// file: src/container.rs
+// version: 1.1.0
+// guid: c4e7a2d9-3b58-4f16-9e0a-6d81f5b2c937
+
+//! Container execution target
+//!
+//! With `--in-container IMAGE`, or `container.image`, every command the executor spawns runs
+//! in a fresh container of that image (`docker run --rm`, or `podman run --rm`) instead of on
+//! the host, isolating untrusted or experimental agent operations:
+//!
+//! - The workspace, meaning the working directory and every `safety.workspace_roots` entry, is
+//!   bind-mounted at its own path, read-write or, with `--container-read-only` or
+//!   `container.read_only`, read-only. Nothing else of the host is visible.
+//! - The command runs in the directory it would run in on the host, as the calling user, so
+//!   the files it writes keep their owner.
+//! - Tools are the image's: commands are looked up on the container's `PATH`, and tool pins
+//!   and `safety.verify_binaries` do not apply. Binaries inside the workspace run as they are.
+//! - Variables the executor sets for the command are passed in; the rest of the host
+//!   environment is not. With `safety.no_network` the container has no network.
+//!
+//! Operations the utility performs itself, such as the `file` commands, still happen on the
+//! host, inside the workspace roots.
+
+use crate::config::Config;
+use crate::error::{AgentError, Result};
+use crate::tools;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Runtimes tried, in order, when `container.runtime` is not set
+pub const RUNTIMES: [&str; 2] = ["docker", "podman"];
+
+/// How commands are run in a container
+#[derive(Debug, Clone)]
+pub struct Container {
+    runtime: PathBuf,
+    /// Podman keeps the caller's uid with a user namespace rather than `--user`
+    podman: bool,
+    image: String,
+    read_only: bool,
+    network: bool,
+}
+
+impl Container {
+    /// The container configured in `config`, if any
+    ///
+    /// The runtime is looked up now, so a missing one fails before anything runs.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(image) = &config.container.image else {
+            return Ok(None);
+        };
+        if image.is_empty() || image.starts_with('-') || image.contains(char::is_whitespace) {
+            return Err(AgentError::invalid_argument(format!(
+                "Invalid container image: '{}'",
+                image
+            )));
+        }
+        let runtime = match &config.container.runtime {
+            Some(name) => tools::resolve(name, config.tools.get(name))?,
+            None => RUNTIMES
+                .iter()
+                .find_map(|name| tools::resolve(name, config.tools.get(*name)).ok())
+                .ok_or_else(|| {
+                    AgentError::config(format!(
+                        "Running commands in a container needs {} on PATH",
+                        RUNTIMES.join(" or ")
+                    ))
+                })?,
+        };
+        Ok(Some(Self {
+            podman: runtime
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().contains("podman")),
+            runtime,
+            image: image.clone(),
+            read_only: config.container.read_only,
+            network: !config.safety.no_network,
+        }))
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// Runtime and arguments running the prepared `cmd` in a container, with its working
+    /// directory and the variables set on it, and the host directories `mounts` mounted at
+    /// the same path
+    pub fn command_line(&self, cmd: &Command, mounts: &[PathBuf]) -> (PathBuf, Vec<OsString>) {
+        let cmd = cmd.as_std();
+        let mut args: Vec<OsString> = ["run", "--rm", "--interactive"].map(OsString::from).into();
+        #[cfg(unix)]
+        if self.podman {
+            args.push("--userns=keep-id".into());
+        } else {
+            // SAFETY: plain libc queries
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            args.push("--user".into());
+            args.push(format!("{}:{}", uid, gid).into());
+        }
+        if !self.network {
+            args.push("--network".into());
+            args.push("none".into());
+        }
+        for mount in mounts {
+            let mut volume = mount.clone().into_os_string();
+            volume.push(":");
+            volume.push(mount);
+            if self.read_only {
+                volume.push(":ro");
+            }
+            args.push("--volume".into());
+            args.push(volume);
+        }
+        let cwd = match cmd.get_current_dir() {
+            Some(dir) => Some(dir.to_path_buf()),
+            None => std::env::current_dir().ok(),
+        };
+        if let Some(cwd) = cwd {
+            args.push("--workdir".into());
+            args.push(cwd.into_os_string());
+        }
+        for (key, value) in cmd.get_envs() {
+            // The host's PATH means nothing in the image
+            let Some(value) = value.filter(|_| key != "PATH") else {
+                continue;
+            };
+            let mut variable = key.to_os_string();
+            variable.push("=");
+            variable.push(value);
+            args.push("--env".into());
+            args.push(variable);
+        }
+        args.push(self.image.clone().into());
+        args.push(program_in(cmd.get_program(), mounts));
+        args.extend(cmd.get_args().map(OsStr::to_os_string));
+        (self.runtime.clone(), args)
+    }
+}
+
+/// The program as the container sees it: binaries in the mounted workspace by their path,
+/// others by name, to be found on the container's `PATH`
+fn program_in(program: &OsStr, mounts: &[PathBuf]) -> OsString {
+    let path = Path::new(program);
+    match path.file_name() {
+        Some(name) if path.is_absolute() && !mounts.iter().any(|mount| path.starts_with(mount)) => {
+            name.to_os_string()
+        }
+        _ => program.to_os_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(podman: bool, read_only: bool, network: bool) -> Container {
+        Container {
+            runtime: PathBuf::from("/usr/bin/docker"),
+            podman,
+            image: "rust:1.79".to_string(),
+            read_only,
+            network,
+        }
+    }
+
+    fn strings(args: &[OsString]) -> Vec<&str> {
+        args.iter().map(|arg| arg.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_command_line() {
+        let mounts = [PathBuf::from("/work/proj")];
+        let mut cmd = Command::new("/usr/bin/cargo");
+        cmd.args(["test", "--workspace"])
+            .current_dir("/work/proj/crate")
+            .env("PATH", "/usr/bin")
+            .env("SAFE_AI_UTIL_SESSION_ID", "abc");
+
+        let (runtime, args) = container(true, true, false).command_line(&cmd, &mounts);
+        assert_eq!(runtime, Path::new("/usr/bin/docker"));
+        let args = strings(&args);
+        let tail = [
+            "--volume",
+            "/work/proj:/work/proj:ro",
+            "--workdir",
+            "/work/proj/crate",
+            "--env",
+            "SAFE_AI_UTIL_SESSION_ID=abc",
+            "rust:1.79",
+            "cargo",
+            "test",
+            "--workspace",
+        ];
+        assert!(args.ends_with(&tail), "{:?}", args);
+        assert!(args.starts_with(&["run", "--rm"]));
+        assert!(args.windows(2).any(|pair| pair == ["--network", "none"]));
+
+        let cmd = Command::new("/work/proj/target/debug/tool");
+        let (_, args) = container(false, false, true).command_line(&cmd, &mounts);
+        let args = strings(&args);
+        assert!(args.ends_with(&["rust:1.79", "/work/proj/target/debug/tool"]));
+        assert!(args.contains(&"/work/proj:/work/proj"));
+        assert!(!args.contains(&"--network"));
+    }
+}
@@ -0,0 +1,214 @@
+// file: src/credentials.rs
+// version: 1.0.0
+// guid: 9d3f6b21-5e7a-4c80-b2f4-81a6c0e5d397
+
+//! Buf Schema Registry tokens kept in the OS keyring
+//!
+//! `buf registry login` stores the token for a registry host in the platform's credential
+//! store (Keychain, Windows Credential Manager or the Secret Service) instead of the plaintext
+//! `~/.netrc` buf itself writes. Only the names of the hosts logged in to are kept on disk, in
+//! `buf-registry-hosts.json` in the state directory, so commands skip the keyring entirely
+//! until a login happened.
+//!
+//! The Executor hands the tokens to buf, and to no other program, as `BUF_TOKEN` (see
+//! [`buf_child_env`]). They never appear in arguments, history, plans or logs, and are not
+//! passed to commands run with `--in-container` or `--target`, whose environment ends up on a
+//! command line. A `BUF_TOKEN` already in the environment takes precedence.
+
+use crate::error::{AgentError, Result};
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Environment variable buf reads registry tokens from
+pub const BUF_TOKEN_ENV: &str = "BUF_TOKEN";
+
+/// Registry host used when none is given
+pub const DEFAULT_BUF_HOST: &str = "buf.build";
+
+/// Keyring service the tokens are stored under, one entry per host
+const KEYRING_SERVICE: &str = "safe-ai-util-buf";
+
+/// File in the state directory listing the hosts with a stored token
+const HOSTS_FILE: &str = "buf-registry-hosts.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Hosts {
+    hosts: Vec<String>,
+}
+
+/// Hosts with a token in the keyring, in the order they were logged in to
+pub fn buf_hosts() -> Vec<String> {
+    read_hosts(&hosts_path()).hosts
+}
+
+/// Store `token` for `host`, returning the one it replaces
+pub fn store_buf_token(host: &str, token: &str) -> Result<Option<String>> {
+    let (owned, token) = (host.to_string(), token.to_string());
+    let previous = on_own_thread(move || {
+        let entry = entry(&owned)?;
+        let previous = match entry.get_password() {
+            Ok(previous) => Some(previous),
+            Err(keyring::Error::NoEntry) => None,
+            Err(e) => return Err(keyring_error(e)),
+        };
+        entry.set_password(&token).map_err(keyring_error)?;
+        Ok(previous)
+    })?;
+    let path = hosts_path();
+    let mut hosts = read_hosts(&path);
+    if !hosts.hosts.iter().any(|known| known == host) {
+        hosts.hosts.push(host.to_string());
+        write_hosts(&path, &hosts)?;
+    }
+    debug!("Stored the buf token for {} in the keyring", host);
+    Ok(previous)
+}
+
+/// Remove the token of `host`; whether there was one
+pub fn delete_buf_token(host: &str) -> Result<bool> {
+    let owned = host.to_string();
+    let deleted = on_own_thread(move || match entry(&owned)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(keyring_error(e)),
+    })?;
+    let path = hosts_path();
+    let mut hosts = read_hosts(&path);
+    let before = hosts.hosts.len();
+    hosts.hosts.retain(|known| known != host);
+    if hosts.hosts.len() != before {
+        write_hosts(&path, &hosts)?;
+    }
+    Ok(deleted || hosts.hosts.len() != before)
+}
+
+/// `BUF_TOKEN` for a child process running `program`, if it is buf and tokens are stored
+///
+/// Failing to read the keyring is logged and leaves buf to its own credentials.
+pub fn buf_child_env(program: &Path) -> Option<(&'static str, String)> {
+    if program.file_stem().and_then(|stem| stem.to_str()) != Some("buf")
+        || std::env::var_os(BUF_TOKEN_ENV).is_some()
+    {
+        return None;
+    }
+    let hosts = buf_hosts();
+    if hosts.is_empty() {
+        return None;
+    }
+    let tokens = on_own_thread(move || {
+        let mut tokens = Vec::new();
+        for host in hosts {
+            match entry(&host)?.get_password() {
+                Ok(token) => tokens.push((host, token)),
+                Err(keyring::Error::NoEntry) => debug!("No buf token for {} in the keyring", host),
+                Err(e) => return Err(keyring_error(e)),
+            }
+        }
+        Ok(tokens)
+    });
+    match tokens {
+        Ok(tokens) if !tokens.is_empty() => Some((BUF_TOKEN_ENV, buf_token_value(&tokens))),
+        Ok(_) => None,
+        Err(e) => {
+            warn!("Could not read buf tokens from the keyring: {}", e);
+            None
+        }
+    }
+}
+
+/// `BUF_TOKEN` value for tokens by host: a bare token for buf.build alone, otherwise
+/// `token@host` pairs separated by commas
+fn buf_token_value(tokens: &[(String, String)]) -> String {
+    match tokens {
+        [(host, token)] if host == DEFAULT_BUF_HOST => token.clone(),
+        _ => tokens
+            .iter()
+            .map(|(host, token)| format!("{}@{}", token, host))
+            .collect::<Vec<_>>()
+            .join(","),
+    }
+}
+
+fn entry(host: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, host).map_err(keyring_error)
+}
+
+fn keyring_error(e: keyring::Error) -> AgentError {
+    AgentError::system(format!("OS keyring: {}", e))
+}
+
+/// Run a keyring operation on a thread of its own: the Secret Service backend drives its
+/// own runtime, which cannot be blocked on from within the utility's
+fn on_own_thread<T: Send>(operation: impl FnOnce() -> Result<T> + Send) -> Result<T> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(operation)
+            .join()
+            .unwrap_or_else(|_| Err(AgentError::system("OS keyring access panicked")))
+    })
+}
+
+fn hosts_path() -> PathBuf {
+    utils::state_dir().join(HOSTS_FILE)
+}
+
+fn read_hosts(path: &Path) -> Hosts {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn write_hosts(path: &Path, hosts: &Hosts) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(hosts)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(host, token)| (host.to_string(), token.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_buf_token_value() {
+        assert_eq!(buf_token_value(&tokens(&[("buf.build", "t1")])), "t1");
+        assert_eq!(
+            buf_token_value(&tokens(&[("bsr.example.com", "t2")])),
+            "t2@bsr.example.com"
+        );
+        assert_eq!(
+            buf_token_value(&tokens(&[("buf.build", "t1"), ("bsr.example.com", "t2")])),
+            "t1@buf.build,t2@bsr.example.com"
+        );
+    }
+
+    #[test]
+    fn test_buf_child_env_only_for_buf() {
+        assert!(buf_child_env(Path::new("/usr/bin/protoc")).is_none());
+        assert!(buf_child_env(Path::new("/usr/bin/buf-lint")).is_none());
+    }
+
+    #[test]
+    fn test_hosts_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join(HOSTS_FILE);
+        assert!(read_hosts(&path).hosts.is_empty());
+        let hosts = Hosts {
+            hosts: vec!["buf.build".to_string(), "bsr.example.com".to_string()],
+        };
+        write_hosts(&path, &hosts).unwrap();
+        assert_eq!(read_hosts(&path).hosts, hosts.hosts);
+    }
+}
@@ -0,0 +1,998 @@
+// file: src/diff.rs
+// version: 1.0.0
+// guid: 7c3e9a14-5b2d-4f86-a0e1-d94b6f28c571
+
+//! Native line diffs with intra-line token spans
+//!
+//! Previews of edits (`file write` and `sed --in-place` in dry-run mode, `file diff`) are
+//! computed here rather than by shelling out to `diff`. Three line algorithms are available:
+//!
+//! - `myers`: the shortest edit script, as computed by `diff` and git by default
+//! - `patience`: anchors the diff on lines that occur exactly once on both sides, which keeps
+//!   moved functions and blocks of braces from being interleaved
+//! - `histogram`: git's refinement of patience that also anchors on lines occurring a few times
+//!
+//! Paired deleted and inserted lines can be diffed again word by word or character by
+//! character. The resulting spans carry byte offsets into their line, so JSON consumers can
+//! highlight exactly what changed.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::hash::Hash;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Occurrences above which a line is not used as a histogram anchor
+const HISTOGRAM_MAX_OCCURRENCES: usize = 64;
+
+/// Line diff algorithm
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    #[default]
+    Myers,
+    Patience,
+    Histogram,
+}
+
+impl Algorithm {
+    pub const NAMES: &'static [&'static str] = &["myers", "patience", "histogram"];
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "myers" => Ok(Self::Myers),
+            "patience" => Ok(Self::Patience),
+            "histogram" => Ok(Self::Histogram),
+            other => Err(format!("Unknown diff algorithm '{}'", other)),
+        }
+    }
+}
+
+/// Granularity of intra-line diffs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Inline {
+    /// Whole lines only
+    #[default]
+    None,
+    Word,
+    Char,
+}
+
+impl Inline {
+    pub const NAMES: &'static [&'static str] = &["none", "word", "char"];
+}
+
+impl FromStr for Inline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "word" => Ok(Self::Word),
+            "char" => Ok(Self::Char),
+            other => Err(format!("Unknown inline diff granularity '{}'", other)),
+        }
+    }
+}
+
+/// How to diff
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOptions {
+    pub algorithm: Algorithm,
+    pub inline: Inline,
+    /// Unchanged lines shown around each change
+    pub context: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::Myers,
+            inline: Inline::None,
+            context: 3,
+        }
+    }
+}
+
+/// Kind of an edit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A run of elements with the same kind of edit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub op: Op,
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// Edit script turning `old` into `new`
+pub fn diff_slices<T: Hash + Eq>(algorithm: Algorithm, old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut recorder = Recorder::default();
+    match algorithm {
+        Algorithm::Myers => myers(old, 0..old.len(), new, 0..new.len(), &mut recorder),
+        Algorithm::Patience => patience(old, 0..old.len(), new, 0..new.len(), &mut recorder),
+        Algorithm::Histogram => histogram(old, 0..old.len(), new, 0..new.len(), &mut recorder),
+    }
+    recorder.edits
+}
+
+/// A changed part of a line, with byte offsets into it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub op: Op,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// A line of a hunk; numbers are 1-based
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Line {
+    pub op: Op,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<usize>,
+    pub text: String,
+    /// Intra-line changes, for deleted and inserted lines paired with each other
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spans: Vec<Span>,
+}
+
+/// Changed lines with their context
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<Line>,
+}
+
+/// Line diff of two texts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TextDiff {
+    pub algorithm: Algorithm,
+    pub inline: Inline,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub hunks: Vec<Hunk>,
+}
+
+impl TextDiff {
+    /// Diff `old` against `new` line by line
+    pub fn new(old: &str, new: &str, options: DiffOptions) -> Self {
+        let old_lines = split_lines(old);
+        let new_lines = split_lines(new);
+        let edits = diff_slices(options.algorithm, &old_lines, &new_lines);
+
+        let mut insertions = 0;
+        let mut deletions = 0;
+        for edit in &edits {
+            match edit.op {
+                Op::Delete => deletions += edit.old.len(),
+                Op::Insert => insertions += edit.new.len(),
+                Op::Equal => {}
+            }
+        }
+
+        let hunks = group(&edits, options.context)
+            .into_iter()
+            .map(|group| build_hunk(&group, &old_lines, &new_lines, options.inline))
+            .collect();
+        Self {
+            algorithm: options.algorithm,
+            inline: options.inline,
+            insertions,
+            deletions,
+            hunks,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// Unified diff between `old_name` and `new_name`; with an inline granularity, paired
+    /// lines are shown once with `[-deleted-]{+inserted+}` markers after a `~`
+    pub fn unified(&self, old_name: &str, new_name: &str) -> String {
+        let mut out = String::new();
+        if self.is_empty() {
+            return out;
+        }
+        let _ = writeln!(out, "--- {}", old_name);
+        let _ = writeln!(out, "+++ {}", new_name);
+        for hunk in &self.hunks {
+            let _ = writeln!(
+                out,
+                "@@ -{} +{} @@",
+                hunk_range(hunk.old_start, hunk.old_lines),
+                hunk_range(hunk.new_start, hunk.new_lines)
+            );
+            let mut index = 0;
+            while index < hunk.lines.len() {
+                let line = &hunk.lines[index];
+                if line.op == Op::Delete && self.inline != Inline::None {
+                    // Deleted lines are paired with the inserted lines that follow them
+                    let deleted = count_run(&hunk.lines[index..], Op::Delete);
+                    let inserted = count_run(&hunk.lines[index + deleted..], Op::Insert);
+                    let pairs = deleted.min(inserted);
+                    let partners = &hunk.lines[index + deleted..index + deleted + pairs];
+                    for (old, new) in hunk.lines[index..index + pairs].iter().zip(partners) {
+                        let _ = writeln!(out, "~{}", merge_spans(&old.spans, &new.spans));
+                    }
+                    for line in &hunk.lines[index + pairs..index + deleted] {
+                        let _ = writeln!(out, "-{}", line.text);
+                    }
+                    index += deleted + pairs;
+                    continue;
+                }
+                let prefix = match line.op {
+                    Op::Equal => ' ',
+                    Op::Delete => '-',
+                    Op::Insert => '+',
+                };
+                let _ = writeln!(out, "{}{}", prefix, line.text);
+                index += 1;
+            }
+        }
+        out
+    }
+}
+
+fn count_run(lines: &[Line], op: Op) -> usize {
+    lines.iter().take_while(|line| line.op == op).count()
+}
+
+/// `start,count` as in unified diff headers, where an empty range starts before its line
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start.saturating_sub(1)),
+        1 => start.to_string(),
+        _ => format!("{},{}", start, count),
+    }
+}
+
+/// Lines without their terminators
+fn split_lines(text: &str) -> Vec<&str> {
+    text.lines().collect()
+}
+
+/// Split edits into hunks with `context` equal lines around each change
+fn group(edits: &[Edit], context: usize) -> Vec<Vec<Edit>> {
+    let mut groups: Vec<Vec<Edit>> = Vec::new();
+    let mut current: Vec<Edit> = Vec::new();
+    let last = edits.len().saturating_sub(1);
+    for (index, edit) in edits.iter().enumerate() {
+        if edit.op != Op::Equal {
+            current.push(edit.clone());
+            continue;
+        }
+        let len = edit.old.len();
+        let leading = index == 0;
+        let trailing = index == last;
+        if leading {
+            let keep = len.min(context);
+            current.push(Edit {
+                op: Op::Equal,
+                old: edit.old.end - keep..edit.old.end,
+                new: edit.new.end - keep..edit.new.end,
+            });
+        } else if trailing || len > 2 * context {
+            let keep = len.min(context);
+            current.push(Edit {
+                op: Op::Equal,
+                old: edit.old.start..edit.old.start + keep,
+                new: edit.new.start..edit.new.start + keep,
+            });
+            groups.push(std::mem::take(&mut current));
+            if !trailing {
+                current.push(Edit {
+                    op: Op::Equal,
+                    old: edit.old.end - context..edit.old.end,
+                    new: edit.new.end - context..edit.new.end,
+                });
+            }
+        } else {
+            current.push(edit.clone());
+        }
+    }
+    if current.iter().any(|edit| edit.op != Op::Equal) {
+        groups.push(current);
+    }
+    groups.retain(|group| group.iter().any(|edit| edit.op != Op::Equal));
+    groups
+}
+
+fn build_hunk(group: &[Edit], old: &[&str], new: &[&str], inline: Inline) -> Hunk {
+    let first = &group[0];
+    let old_start = first.old.start + 1;
+    let new_start = first.new.start + 1;
+    let old_lines = group.iter().map(|edit| edit.old.len()).sum();
+    let new_lines = group.iter().map(|edit| edit.new.len()).sum();
+
+    let mut lines = Vec::new();
+    let mut index = 0;
+    while index < group.len() {
+        let edit = &group[index];
+        match edit.op {
+            Op::Equal => {
+                for (o, n) in edit.old.clone().zip(edit.new.clone()) {
+                    lines.push(Line {
+                        op: Op::Equal,
+                        old_line: Some(o + 1),
+                        new_line: Some(n + 1),
+                        text: old[o].to_string(),
+                        spans: Vec::new(),
+                    });
+                }
+            }
+            Op::Delete => {
+                let mut deleted: Vec<Line> = edit.old.clone().map(|o| removed(old, o)).collect();
+                let insert = group.get(index + 1).filter(|next| next.op == Op::Insert);
+                let mut inserted: Vec<Line> = insert
+                    .map(|edit| edit.new.clone().map(|n| added(new, n)).collect())
+                    .unwrap_or_default();
+                if inline != Inline::None {
+                    for (old_line, new_line) in deleted.iter_mut().zip(inserted.iter_mut()) {
+                        let (old_spans, new_spans) =
+                            inline_spans(&old_line.text, &new_line.text, inline);
+                        old_line.spans = old_spans;
+                        new_line.spans = new_spans;
+                    }
+                }
+                lines.extend(deleted);
+                lines.extend(inserted);
+                if insert.is_some() {
+                    index += 1;
+                }
+            }
+            Op::Insert => lines.extend(edit.new.clone().map(|n| added(new, n))),
+        }
+        index += 1;
+    }
+    Hunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        lines,
+    }
+}
+
+fn removed(old: &[&str], index: usize) -> Line {
+    Line {
+        op: Op::Delete,
+        old_line: Some(index + 1),
+        new_line: None,
+        text: old[index].to_string(),
+        spans: Vec::new(),
+    }
+}
+
+fn added(new: &[&str], index: usize) -> Line {
+    Line {
+        op: Op::Insert,
+        old_line: None,
+        new_line: Some(index + 1),
+        text: new[index].to_string(),
+        spans: Vec::new(),
+    }
+}
+
+/// Spans of an old and a new line: equal and deleted parts of the old line, equal and
+/// inserted parts of the new one
+pub fn inline_spans(old: &str, new: &str, inline: Inline) -> (Vec<Span>, Vec<Span>) {
+    let old_tokens = tokenize(old, inline);
+    let new_tokens = tokenize(new, inline);
+    let old_texts: Vec<&str> = old_tokens.iter().map(|range| &old[range.clone()]).collect();
+    let new_texts: Vec<&str> = new_tokens.iter().map(|range| &new[range.clone()]).collect();
+
+    let mut old_spans = Vec::new();
+    let mut new_spans = Vec::new();
+    for edit in diff_slices(Algorithm::Myers, &old_texts, &new_texts) {
+        if !edit.old.is_empty() {
+            let bytes = old_tokens[edit.old.start].start..old_tokens[edit.old.end - 1].end;
+            old_spans.push(span(edit.op, old, bytes));
+        }
+        if !edit.new.is_empty() {
+            let bytes = new_tokens[edit.new.start].start..new_tokens[edit.new.end - 1].end;
+            new_spans.push(span(edit.op, new, bytes));
+        }
+    }
+    (old_spans, new_spans)
+}
+
+fn span(op: Op, line: &str, bytes: Range<usize>) -> Span {
+    Span {
+        op,
+        start: bytes.start,
+        end: bytes.end,
+        text: line[bytes].to_string(),
+    }
+}
+
+/// Byte ranges of the tokens of `line`: runs of word characters, runs of whitespace and
+/// single other characters, or single characters
+fn tokenize(line: &str, inline: Inline) -> Vec<Range<usize>> {
+    let mut tokens: Vec<Range<usize>> = Vec::new();
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            1
+        } else if c.is_whitespace() {
+            2
+        } else {
+            0
+        }
+    };
+    let mut previous = None;
+    for (start, c) in line.char_indices() {
+        let end = start + c.len_utf8();
+        let current = class(c);
+        match tokens.last_mut() {
+            Some(last) if inline == Inline::Word && current != 0 && previous == Some(current) => {
+                last.end = end;
+            }
+            _ => tokens.push(start..end),
+        }
+        previous = Some(current);
+    }
+    tokens
+}
+
+/// One line showing both sides of a pair: `[-deleted-]{+inserted+}`
+fn merge_spans(old: &[Span], new: &[Span]) -> String {
+    let mut out = String::new();
+    let mut old = old.iter().peekable();
+    let mut new = new.iter().peekable();
+    loop {
+        while let Some(span) = old.next_if(|span| span.op == Op::Delete) {
+            let _ = write!(out, "[-{}-]", span.text);
+        }
+        while let Some(span) = new.next_if(|span| span.op == Op::Insert) {
+            let _ = write!(out, "{{+{}+}}", span.text);
+        }
+        match (old.next(), new.next()) {
+            (Some(span), Some(_)) => out.push_str(&span.text),
+            (None, None) => break,
+            // Equal spans are the same on both sides
+            _ => unreachable!("unbalanced equal spans"),
+        }
+    }
+    out
+}
+
+/// Collects edits, merging adjacent ones of the same kind
+#[derive(Default)]
+struct Recorder {
+    edits: Vec<Edit>,
+}
+
+impl Recorder {
+    fn push(&mut self, op: Op, old: Range<usize>, new: Range<usize>) {
+        if old.is_empty() && new.is_empty() {
+            return;
+        }
+        if op == Op::Delete && self.edits.last().is_some_and(|last| last.op == Op::Insert) {
+            // Deletions go before the insertions they meet, as in `diff` output
+            let insert = self.edits.pop().unwrap();
+            self.push(Op::Delete, old.clone(), insert.new.start..insert.new.start);
+            self.push(Op::Insert, old.end..old.end, insert.new);
+            return;
+        }
+        if let Some(last) = self.edits.last_mut() {
+            if last.op == op && last.old.end == old.start && last.new.end == new.start {
+                last.old.end = old.end;
+                last.new.end = new.end;
+                return;
+            }
+        }
+        self.edits.push(Edit { op, old, new });
+    }
+
+    fn equal(&mut self, old_start: usize, new_start: usize, len: usize) {
+        self.push(
+            Op::Equal,
+            old_start..old_start + len,
+            new_start..new_start + len,
+        );
+    }
+
+    /// Everything in `old` deleted, then everything in `new` inserted
+    fn replace(&mut self, old: Range<usize>, new: Range<usize>) {
+        self.push(Op::Delete, old.clone(), new.start..new.start);
+        self.push(Op::Insert, old.end..old.end, new);
+    }
+}
+
+fn common_prefix<T: Eq>(
+    old: &[T],
+    old_range: &Range<usize>,
+    new: &[T],
+    new_range: &Range<usize>,
+) -> usize {
+    old[old_range.clone()]
+        .iter()
+        .zip(&new[new_range.clone()])
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+fn common_suffix<T: Eq>(
+    old: &[T],
+    old_range: &Range<usize>,
+    new: &[T],
+    new_range: &Range<usize>,
+) -> usize {
+    old[old_range.clone()]
+        .iter()
+        .rev()
+        .zip(new[new_range.clone()].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// Record the common prefix, run `inner` on what lies between prefix and suffix, then record
+/// the common suffix
+fn with_trimmed<T: Eq>(
+    old: &[T],
+    mut old_range: Range<usize>,
+    new: &[T],
+    mut new_range: Range<usize>,
+    out: &mut Recorder,
+    inner: impl FnOnce(Range<usize>, Range<usize>, &mut Recorder),
+) {
+    let prefix = common_prefix(old, &old_range, new, &new_range);
+    out.equal(old_range.start, new_range.start, prefix);
+    old_range.start += prefix;
+    new_range.start += prefix;
+    let suffix = common_suffix(old, &old_range, new, &new_range);
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    if old_range.is_empty() || new_range.is_empty() {
+        out.replace(old_range.clone(), new_range.clone());
+    } else {
+        inner(old_range.clone(), new_range.clone(), out);
+    }
+    out.equal(old_range.end, new_range.end, suffix);
+}
+
+/// Myers' O(ND) diff in linear space: split at the middle snake and recurse
+fn myers<T: Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    out: &mut Recorder,
+) {
+    with_trimmed(
+        old,
+        old_range,
+        new,
+        new_range,
+        out,
+        |old_range, new_range, out| match middle_snake(old, &old_range, new, &new_range) {
+            Some((x, y))
+                if (x, y) != (old_range.start, new_range.start)
+                    && (x, y) != (old_range.end, new_range.end) =>
+            {
+                myers(old, old_range.start..x, new, new_range.start..y, out);
+                myers(old, x..old_range.end, new, y..new_range.end, out);
+            }
+            _ => out.replace(old_range, new_range),
+        },
+    )
+}
+
+/// A point on an optimal edit path, searching from both ends until the paths overlap
+fn middle_snake<T: Eq>(
+    old: &[T],
+    old_range: &Range<usize>,
+    new: &[T],
+    new_range: &Range<usize>,
+) -> Option<(usize, usize)> {
+    let n = old_range.len() as isize;
+    let m = new_range.len() as isize;
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    let max = (n + m + 1) / 2 + 1;
+    let offset = max;
+    // Furthest x reached on each diagonal k = x - y, forwards and (from the end) backwards
+    let mut forward = vec![0isize; 2 * max as usize + 1];
+    let mut backward = vec![0isize; 2 * max as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+    let old_at = |x: isize| &old[old_range.start + x as usize];
+    let new_at = |y: isize| &new[new_range.start + y as usize];
+
+    for d in 0..max {
+        let mut k = d;
+        while k >= -d {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let start = (x, y);
+            while x < n && y < m && old_at(x) == new_at(y) {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            if odd && (k - delta).abs() < d && x + backward[at(delta - k)] >= n {
+                return Some((
+                    old_range.start + start.0 as usize,
+                    new_range.start + start.1 as usize,
+                ));
+            }
+            k -= 2;
+        }
+
+        let mut k = d;
+        while k >= -d {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old_at(n - x - 1) == new_at(m - y - 1) {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            if !odd && (k - delta).abs() <= d && x + forward[at(delta - k)] >= n {
+                return Some((
+                    old_range.start + (n - x) as usize,
+                    new_range.start + (m - y) as usize,
+                ));
+            }
+            k -= 2;
+        }
+    }
+    None
+}
+
+/// Patience diff: anchor on the longest increasing run of lines unique on both sides
+fn patience<T: Hash + Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    out: &mut Recorder,
+) {
+    with_trimmed(
+        old,
+        old_range,
+        new,
+        new_range,
+        out,
+        |old_range, new_range, out| {
+            let anchors = unique_anchors(old, &old_range, new, &new_range);
+            if anchors.is_empty() {
+                return myers(old, old_range, new, new_range, out);
+            }
+            let (mut old_pos, mut new_pos) = (old_range.start, new_range.start);
+            for (o, n) in anchors {
+                patience(old, old_pos..o, new, new_pos..n, out);
+                out.equal(o, n, 1);
+                old_pos = o + 1;
+                new_pos = n + 1;
+            }
+            patience(
+                old,
+                old_pos..old_range.end,
+                new,
+                new_pos..new_range.end,
+                out,
+            );
+        },
+    )
+}
+
+/// Lines occurring once on each side, reduced to the longest run in the same order on both
+fn unique_anchors<T: Hash + Eq>(
+    old: &[T],
+    old_range: &Range<usize>,
+    new: &[T],
+    new_range: &Range<usize>,
+) -> Vec<(usize, usize)> {
+    // Per line: occurrences in old, position in old, occurrences in new, position in new
+    let mut counts: HashMap<&T, (usize, usize, usize, usize)> = HashMap::new();
+    for i in old_range.clone() {
+        let entry = counts.entry(&old[i]).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for j in new_range.clone() {
+        if let Some(entry) = counts.get_mut(&new[j]) {
+            entry.2 += 1;
+            entry.3 = j;
+        }
+    }
+    let mut matches: Vec<(usize, usize)> = counts
+        .into_values()
+        .filter(|(in_old, _, in_new, _)| *in_old == 1 && *in_new == 1)
+        .map(|(_, o, _, n)| (o, n))
+        .collect();
+    matches.sort_unstable();
+
+    // Patience sorting: longest increasing subsequence of new positions
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; matches.len()];
+    for (index, &(_, n)) in matches.iter().enumerate() {
+        let pile = piles.partition_point(|&top| matches[top].1 < n);
+        if pile > 0 {
+            previous[index] = Some(piles[pile - 1]);
+        }
+        if pile == piles.len() {
+            piles.push(index);
+        } else {
+            piles[pile] = index;
+        }
+    }
+    let mut anchors = Vec::new();
+    let mut cursor = piles.last().copied();
+    while let Some(index) = cursor {
+        anchors.push(matches[index]);
+        cursor = previous[index];
+    }
+    anchors.reverse();
+    anchors
+}
+
+/// Histogram diff: anchor on the longest common region around the rarest line, then recurse
+fn histogram<T: Hash + Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    out: &mut Recorder,
+) {
+    with_trimmed(
+        old,
+        old_range,
+        new,
+        new_range,
+        out,
+        |old_range, new_range, out| {
+            let Some((o, n, len)) = rarest_region(old, &old_range, new, &new_range) else {
+                return myers(old, old_range, new, new_range, out);
+            };
+            histogram(old, old_range.start..o, new, new_range.start..n, out);
+            out.equal(o, n, len);
+            histogram(
+                old,
+                o + len..old_range.end,
+                new,
+                n + len..new_range.end,
+                out,
+            );
+        },
+    )
+}
+
+/// The common region `(old start, new start, length)` containing the line with the fewest
+/// occurrences in old, preferring longer regions among equally rare lines
+fn rarest_region<T: Hash + Eq>(
+    old: &[T],
+    old_range: &Range<usize>,
+    new: &[T],
+    new_range: &Range<usize>,
+) -> Option<(usize, usize, usize)> {
+    let mut positions: HashMap<&T, Vec<usize>> = HashMap::new();
+    for i in old_range.clone() {
+        positions.entry(&old[i]).or_default().push(i);
+    }
+
+    let mut best: Option<(usize, usize, usize, usize)> = None; // (occurrences, o, n, len)
+    let mut j = new_range.start;
+    while j < new_range.end {
+        let Some(candidates) = positions.get(&new[j]) else {
+            j += 1;
+            continue;
+        };
+        if candidates.len() > HISTOGRAM_MAX_OCCURRENCES {
+            j += 1;
+            continue;
+        }
+        let mut next = j + 1;
+        for &i in candidates {
+            let (mut o, mut n) = (i, j);
+            while o > old_range.start && n > new_range.start && old[o - 1] == new[n - 1] {
+                o -= 1;
+                n -= 1;
+            }
+            let mut len = i - o + 1;
+            while o + len < old_range.end && n + len < new_range.end && old[o + len] == new[n + len]
+            {
+                len += 1;
+            }
+            // The rarest line of the region decides how good an anchor it is
+            let occurrences = (0..len)
+                .map(|offset| positions.get(&old[o + offset]).map_or(usize::MAX, Vec::len))
+                .min()
+                .unwrap_or(usize::MAX);
+            let better = match best {
+                None => true,
+                Some((best_occurrences, _, _, best_len)) => {
+                    occurrences < best_occurrences
+                        || (occurrences == best_occurrences && len > best_len)
+                }
+            };
+            if better {
+                best = Some((occurrences, o, n, len));
+            }
+            next = next.max(n + len);
+        }
+        j = next;
+    }
+    best.map(|(_, o, n, len)| (o, n, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply an edit script and check it reproduces `new`
+    fn apply<'a>(edits: &[Edit], old: &[&'a str], new: &[&'a str]) -> Vec<&'a str> {
+        let mut result = Vec::new();
+        let (mut o, mut n) = (0, 0);
+        for edit in edits {
+            assert_eq!(edit.old.start, o, "{:?}", edits);
+            assert_eq!(edit.new.start, n, "{:?}", edits);
+            match edit.op {
+                Op::Equal => {
+                    assert_eq!(old[edit.old.clone()], new[edit.new.clone()]);
+                    result.extend_from_slice(&old[edit.old.clone()]);
+                }
+                Op::Delete => assert!(edit.new.is_empty()),
+                Op::Insert => {
+                    assert!(edit.old.is_empty());
+                    result.extend_from_slice(&new[edit.new.clone()]);
+                }
+            }
+            o = edit.old.end;
+            n = edit.new.end;
+        }
+        assert_eq!((o, n), (old.len(), new.len()));
+        result
+    }
+
+    fn changed(edits: &[Edit]) -> usize {
+        edits
+            .iter()
+            .filter(|edit| edit.op != Op::Equal)
+            .map(|edit| edit.old.len() + edit.new.len())
+            .sum()
+    }
+
+    #[test]
+    fn test_algorithms_produce_valid_scripts() {
+        let cases: &[(&str, &str)] = &[
+            ("", ""),
+            ("a b c", ""),
+            ("", "a b c"),
+            ("a b c a b b a", "c b a b a c"),
+            ("a b c d e f", "a x c d y f"),
+            ("x y z", "p q r"),
+            ("a a a a b", "b a a a a"),
+        ];
+        for (old, new) in cases {
+            let old: Vec<&str> = old.split_whitespace().collect();
+            let new: Vec<&str> = new.split_whitespace().collect();
+            for algorithm in [Algorithm::Myers, Algorithm::Patience, Algorithm::Histogram] {
+                let edits = diff_slices(algorithm, &old, &new);
+                assert_eq!(apply(&edits, &old, &new), new, "{:?}", algorithm);
+            }
+        }
+
+        // Myers finds the shortest script: "abcabba" to "cbabac" takes five edits
+        let old: Vec<&str> = "a b c a b b a".split_whitespace().collect();
+        let new: Vec<&str> = "c b a b a c".split_whitespace().collect();
+        assert_eq!(changed(&diff_slices(Algorithm::Myers, &old, &new)), 5);
+    }
+
+    #[test]
+    fn test_patience_anchors_on_unique_lines() {
+        let old = "fn a() {\n    one\n}\n\nfn b() {\n    two\n}\n";
+        let new = "fn b() {\n    two\n}\n\nfn a() {\n    one\n}\n";
+        let old_lines = split_lines(old);
+        let new_lines = split_lines(new);
+
+        for algorithm in [Algorithm::Patience, Algorithm::Histogram] {
+            let edits = diff_slices(algorithm, &old_lines, &new_lines);
+            assert_eq!(apply(&edits, &old_lines, &new_lines), new_lines);
+            // One function is kept whole and the other moved, instead of matching up braces
+            let kept: Vec<&str> = edits
+                .iter()
+                .filter(|edit| edit.op == Op::Equal)
+                .flat_map(|edit| old_lines[edit.old.clone()].iter().copied())
+                .collect();
+            assert!(
+                kept.windows(3).any(|w| w == ["fn a() {", "    one", "}"])
+                    || kept.windows(3).any(|w| w == ["fn b() {", "    two", "}"]),
+                "{:?}: {:?}",
+                algorithm,
+                kept
+            );
+        }
+    }
+
+    #[test]
+    fn test_unified_and_inline_spans() {
+        let old = "one\ntwo\nlet value = compute(1);\nfour\n";
+        let new = "one\ntwo\nlet value = compute(2);\nfour\nfive\n";
+        let options = DiffOptions {
+            context: 1,
+            ..DiffOptions::default()
+        };
+        let diff = TextDiff::new(old, new, options);
+        assert_eq!((diff.insertions, diff.deletions), (2, 1));
+        assert_eq!(
+            diff.unified("a/file", "b/file"),
+            "--- a/file\n+++ b/file\n@@ -2,3 +2,4 @@\n two\n-let value = compute(1);\n+let value = compute(2);\n four\n+five\n"
+        );
+
+        let diff = TextDiff::new(
+            old,
+            new,
+            DiffOptions {
+                inline: Inline::Word,
+                ..options
+            },
+        );
+        let deleted = &diff.hunks[0].lines[1];
+        assert_eq!(deleted.op, Op::Delete);
+        let spans: Vec<(Op, &str)> = deleted
+            .spans
+            .iter()
+            .map(|s| (s.op, s.text.as_str()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (Op::Equal, "let value = compute("),
+                (Op::Delete, "1"),
+                (Op::Equal, ");")
+            ]
+        );
+        assert_eq!((deleted.spans[1].start, deleted.spans[1].end), (20, 21));
+        assert!(diff
+            .unified("a", "b")
+            .contains("\n~let value = compute([-1-]{+2+});\n"));
+
+        let (old_spans, new_spans) = inline_spans("colour", "color", Inline::Char);
+        assert_eq!(old_spans.iter().filter(|s| s.op == Op::Delete).count(), 1);
+        assert!(new_spans.iter().all(|s| s.op == Op::Equal));
+    }
+
+    #[test]
+    fn test_hunks_are_split_by_distant_changes() {
+        let old: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let new: String = (1..=20)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                18 => "eighteen\n".to_string(),
+                _ => format!("{}\n", i),
+            })
+            .collect();
+        let diff = TextDiff::new(&old, &new, DiffOptions::default());
+        assert_eq!(diff.hunks.len(), 2);
+        assert_eq!((diff.hunks[0].old_start, diff.hunks[0].old_lines), (1, 5));
+        assert_eq!((diff.hunks[1].old_start, diff.hunks[1].old_lines), (15, 6));
+    }
+}
@@ -0,0 +1,336 @@
+// file: src/env_profile.rs
+// version: 1.0.0
+// guid: 1b7e4d92-6c3a-4f58-a0d9-8e25f1c3b746
+
+//! Workspace profiles: a portable bundle of an agent setup
+//!
+//! `env export-profile` captures what a setup proven on one machine depends on: the settings
+//! that differ from the defaults (including policy rules and tool pins), the version of every
+//! pinned or managed tool, task manifests, and the names of the environment variables it
+//! needs. `env import-profile` applies the bundle on another machine, writing the settings
+//! and task manifests and reporting the tools and variables still missing there.
+//!
+//! Settings that describe one machine or one invocation are left out: the working directory,
+//! `tools.<name>.path` and dry-run mode. Variable values are never exported.
+
+use crate::config::{Config, ToolPin};
+use crate::error::{AgentError, Result};
+use crate::manifest::Manifest;
+use crate::toolchain::{self, Toolchain};
+use crate::tools;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Version of the bundle format
+pub const FORMAT: u32 = 1;
+
+/// File written by `env export-profile` unless told otherwise
+pub const DEFAULT_FILE: &str = "safe-ai-util-profile.json";
+
+/// Task manifests exported when none are given
+pub const DEFAULT_TASK_FILES: [&str; 3] = ["tasks.yaml", "tasks.yml", "tasks.toml"];
+
+/// Settings describing one machine or invocation rather than the setup
+const MACHINE_KEYS: [&str; 3] = [
+    "general.working_directory",
+    "safety.dry_run",
+    "safety.simulate",
+];
+
+/// Variables configuring the utility outside the configuration files
+const UTILITY_ENV: [&str; 3] = [
+    "SAFE_AI_UTIL_PATH_MAP",
+    "SAFE_AI_UTIL_GRPC_TOKEN",
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+];
+
+/// An exported agent setup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub format: u32,
+    pub created_at: DateTime<Utc>,
+    /// Version of the utility that exported the bundle
+    pub created_with: String,
+    /// Settings that differ from the defaults, as TOML
+    pub config: String,
+    /// Pinned and managed tools, keyed by command name
+    #[serde(default)]
+    pub tools: BTreeMap<String, ToolRecord>,
+    #[serde(default)]
+    pub tasks: Vec<TaskFile>,
+    /// Environment variables the setup needs
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+/// A tool as found on the exporting machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolRecord {
+    /// Version the tool reported, if it was installed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Release it was installed from with `toolchain install`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub managed: Option<ManagedRelease>,
+}
+
+/// A managed tool version and the download it was installed from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedRelease {
+    pub version: String,
+    pub url: String,
+    pub sha256: String,
+}
+
+impl ManagedRelease {
+    /// Command installing this release; catalog tools are looked up for the platform they
+    /// are installed on, others are downloaded from the same URL
+    pub fn install_command(&self, name: &str) -> String {
+        if toolchain::KNOWN_TOOLS.contains(&name) {
+            format!("safe-ai-util toolchain install {}@{}", name, self.version)
+        } else {
+            format!(
+                "safe-ai-util toolchain install {}@{} --url {} --sha256 {}",
+                name, self.version, self.url, self.sha256
+            )
+        }
+    }
+}
+
+/// A task manifest, by its path relative to the working directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskFile {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// How a tool of a bundle compares with the one installed here
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolMatch {
+    Matches,
+    Missing,
+    Differs { found: String },
+}
+
+impl Bundle {
+    /// Capture the setup running with `config`, with the task manifests in `task_files` and
+    /// the variables in `require_env` on top of the ones the utility itself uses
+    pub fn capture(
+        config: &Config,
+        task_files: &[PathBuf],
+        require_env: &[String],
+    ) -> Result<Self> {
+        let (settings, _) = settings(config)?;
+        let config_text = toml::to_string_pretty(&settings)
+            .map_err(|e| AgentError::config(format!("Failed to serialize settings: {}", e)))?;
+
+        let toolchain = Toolchain::load()?;
+        let mut names: BTreeSet<&str> = config.tools.keys().map(String::as_str).collect();
+        names.extend(
+            toolchain
+                .tools
+                .iter()
+                .filter(|t| t.active)
+                .map(|t| t.name.as_str()),
+        );
+        let tools = names
+            .into_iter()
+            .map(|name| {
+                let record = ToolRecord {
+                    version: installed_version(name, config.tools.get(name)),
+                    managed: toolchain
+                        .tools
+                        .iter()
+                        .find(|tool| tool.active && tool.name == name)
+                        .map(|tool| ManagedRelease {
+                            version: tool.version.clone(),
+                            url: tool.url.clone(),
+                            sha256: tool.sha256.clone(),
+                        }),
+                };
+                (name.to_string(), record)
+            })
+            .collect();
+
+        let mut tasks = Vec::new();
+        for path in task_files {
+            check_relative(path)?;
+            // Only valid manifests are worth carrying to another machine
+            Manifest::load(path)?;
+            tasks.push(TaskFile {
+                path: path.clone(),
+                content: fs::read_to_string(path)?,
+            });
+        }
+
+        let mut env: BTreeSet<String> = require_env.iter().cloned().collect();
+        env.extend(
+            UTILITY_ENV
+                .iter()
+                .filter(|name| std::env::var_os(name).is_some())
+                .map(|name| name.to_string()),
+        );
+
+        Ok(Self {
+            format: FORMAT,
+            created_at: Utc::now(),
+            created_with: crate::VERSION.to_string(),
+            config: config_text,
+            tools,
+            tasks,
+            env: env.into_iter().collect(),
+        })
+    }
+
+    /// Read a bundle, rejecting formats this version does not know and unsafe task paths
+    pub fn load(path: &Path) -> Result<Self> {
+        let bundle: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if bundle.format != FORMAT {
+            return Err(AgentError::validation(format!(
+                "{} has profile format {}, but this version reads format {}",
+                path.display(),
+                bundle.format,
+                FORMAT
+            )));
+        }
+        for task in &bundle.tasks {
+            check_relative(&task.path)?;
+        }
+        Ok(bundle)
+    }
+}
+
+/// Settings of `config` that differ from the defaults, and the machine-specific keys left out
+pub fn settings(config: &Config) -> Result<(toml::Table, Vec<String>)> {
+    let value = toml::Value::try_from(config)
+        .map_err(|e| AgentError::config(format!("Failed to serialize configuration: {}", e)))?;
+    let defaults = Config::default_value()?;
+    let mut dropped = Vec::new();
+    let table = match value.as_table() {
+        Some(table) => non_default(table, defaults.as_table(), "", &mut dropped),
+        None => toml::Table::new(),
+    };
+    Ok((table, dropped))
+}
+
+fn non_default(
+    table: &toml::Table,
+    defaults: Option<&toml::Table>,
+    prefix: &str,
+    dropped: &mut Vec<String>,
+) -> toml::Table {
+    let mut changed = toml::Table::new();
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        let default = defaults.and_then(|defaults| defaults.get(key));
+        if default == Some(value) {
+            continue;
+        }
+        if MACHINE_KEYS.contains(&path.as_str())
+            || (path.starts_with("tools.") && path.ends_with(".path"))
+        {
+            dropped.push(path);
+            continue;
+        }
+        match value {
+            toml::Value::Table(inner) => {
+                let inner = non_default(
+                    inner,
+                    default.and_then(toml::Value::as_table),
+                    &path,
+                    dropped,
+                );
+                if !inner.is_empty() {
+                    changed.insert(key.clone(), toml::Value::Table(inner));
+                }
+            }
+            _ => {
+                changed.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    changed
+}
+
+/// Version of a tool as its resolved binary reports it
+pub fn installed_version(name: &str, pin: Option<&ToolPin>) -> Option<String> {
+    let path = tools::resolve(name, pin).ok()?;
+    tools::tool_version(&path)
+        .ok()
+        .map(|version| tools::join_version(&version))
+}
+
+/// Compare a tool of a bundle with the one installed here
+pub fn match_tool(name: &str, record: &ToolRecord) -> ToolMatch {
+    match (installed_version(name, None), &record.version) {
+        (None, _) => ToolMatch::Missing,
+        (Some(found), Some(expected)) if &found != expected => ToolMatch::Differs { found },
+        _ => ToolMatch::Matches,
+    }
+}
+
+/// Task paths stay inside the directory the bundle is applied in
+fn check_relative(path: &Path) -> Result<()> {
+    if path.is_absolute()
+        || path
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(AgentError::validation(format!(
+            "Task file {} must be a path inside the working directory, without ..",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_keep_only_portable_changes() {
+        let mut config = Config::default();
+        config.general.timeout_seconds = 60;
+        config.general.working_directory = Some(PathBuf::from("/home/me/project"));
+        config.safety.dry_run = true;
+        config.policy.deny_commands = vec!["curl".to_string()];
+        config.tools.insert(
+            "git".to_string(),
+            ToolPin {
+                path: Some(PathBuf::from("/usr/bin/git")),
+                min_version: Some("2.40".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let (table, dropped) = settings(&config).unwrap();
+        assert_eq!(table["general"]["timeout_seconds"].as_integer(), Some(60));
+        assert_eq!(table["policy"]["deny_commands"][0].as_str(), Some("curl"));
+        assert_eq!(table["tools"]["git"]["min_version"].as_str(), Some("2.40"));
+        assert!(table["tools"]["git"].get("path").is_none());
+        assert!(table.get("safety").is_none() && table.get("logging").is_none());
+        assert_eq!(
+            dropped,
+            [
+                "general.working_directory",
+                "safety.dry_run",
+                "tools.git.path"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_task_paths_stay_relative() {
+        assert!(check_relative(Path::new("ci/tasks.yaml")).is_ok());
+        assert!(check_relative(Path::new("../tasks.yaml")).is_err());
+        assert!(check_relative(Path::new("/etc/tasks.yaml")).is_err());
+    }
+}
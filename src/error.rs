@@ -1,7 +1,11 @@
 // file: src/error.rs
-// version: 2.0.0
+// version: 2.7.0
 // guid: 57b83a63-07b6-4534-aa6c-51e8797254e0
 
+use crate::budget::BudgetExceeded;
+use crate::capability::CapabilityRequest;
+use crate::error_codes;
+use crate::security::policy::Denial;
 use thiserror::Error;
 
 /// Result type alias for the application
@@ -34,6 +38,12 @@ pub enum AgentError {
     #[error("Timeout: {0}")]
     Timeout(String),
 
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(BudgetExceeded),
+
     #[error("Network error: {0}")]
     Network(String),
 
@@ -43,9 +53,18 @@ pub enum AgentError {
     #[error("YAML error: {0}")]
     Yaml(#[from] serde_yaml::Error),
 
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
     #[error("Security violation: {0}")]
     Security(String),
 
+    #[error("Policy violation: {0}")]
+    PolicyViolation(Box<Denial>),
+
+    #[error("Tool unavailable: {0}")]
+    ToolUnavailable(Box<CapabilityRequest>),
+
     #[error("System error: {0}")]
     System(String),
 
@@ -54,6 +73,31 @@ pub enum AgentError {
 }
 
 impl AgentError {
+    /// Stable `SAU-xxxx` code of the error's class; see [`error_codes`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Io(e) => error_codes::io_code(e.kind()),
+            Self::Config(_) => error_codes::CONFIG,
+            Self::Execution(_) => error_codes::EXECUTION,
+            Self::Validation(_) => error_codes::VALIDATION,
+            Self::Permission(_) => error_codes::PERMISSION,
+            Self::FileNotFound(_) => error_codes::FILE_NOT_FOUND,
+            Self::InvalidArgument(_) => error_codes::INVALID_ARGUMENT,
+            Self::Timeout(_) => error_codes::TIMEOUT,
+            Self::Cancelled(_) => error_codes::CANCELLED,
+            Self::BudgetExceeded(_) => error_codes::BUDGET_EXCEEDED,
+            Self::Network(_) => error_codes::NETWORK,
+            Self::Serialization(_) => error_codes::SERIALIZATION,
+            Self::Yaml(_) => error_codes::YAML,
+            Self::Database(_) => error_codes::DATABASE,
+            Self::Security(_) => error_codes::SECURITY,
+            Self::PolicyViolation(_) => error_codes::POLICY_VIOLATION,
+            Self::ToolUnavailable(_) => error_codes::TOOL_UNAVAILABLE,
+            Self::System(_) => error_codes::SYSTEM,
+            Self::Other(_) => error_codes::OTHER,
+        }
+    }
+
     /// Create a new configuration error
     pub fn config(msg: impl Into<String>) -> Self {
         Self::Config(msg.into())
@@ -89,6 +133,11 @@ impl AgentError {
         Self::Timeout(msg.into())
     }
 
+    /// Create a new cancellation error
+    pub fn cancelled(msg: impl Into<String>) -> Self {
+        Self::Cancelled(msg.into())
+    }
+
     /// Create a new network error
     pub fn network(msg: impl Into<String>) -> Self {
         Self::Network(msg.into())
@@ -99,6 +148,11 @@ impl AgentError {
         Self::Security(msg.into())
     }
 
+    /// Create a new policy violation error explained only by its message
+    pub fn policy_violation(msg: impl Into<String>) -> Self {
+        Self::PolicyViolation(Box::new(Denial::new(msg)))
+    }
+
     /// Create a new system error
     pub fn system(msg: impl Into<String>) -> Self {
         Self::System(msg.into())
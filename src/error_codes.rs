@@ -0,0 +1,355 @@
+// file: src/error_codes.rs
+// version: 1.1.0
+// guid: 5c0e7a91-3b4d-4f62-a8e1-92d7c6b0f315
+
+//! Stable error codes
+//!
+//! Every failure class has a code of the form `SAU-xxxx` that does not change when its
+//! message is reworded, so agents can branch on the code instead of parsing text. The first
+//! digit groups the codes: 1 files, 2 configuration and input, 3 running commands, 4 safety
+//! controls, 5 other systems, 0 failures nothing more specific covers. Failed commands log
+//! their code, the structured error lines on stderr carry it in `code`, and
+//! `errors explain <code>` prints the entry below.
+
+use crate::error::AgentError;
+use serde::Serialize;
+
+/// A catalog entry: what a code means and how to get past it
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub causes: &'static [&'static str],
+    pub remedies: &'static [&'static str],
+}
+
+pub const UNCLASSIFIED: &str = "SAU-0001";
+pub const IO: &str = "SAU-1001";
+pub const FILE_NOT_FOUND: &str = "SAU-1002";
+pub const PERMISSION: &str = "SAU-1003";
+pub const CONFIG: &str = "SAU-2001";
+pub const INVALID_ARGUMENT: &str = "SAU-2002";
+pub const VALIDATION: &str = "SAU-2003";
+pub const SERIALIZATION: &str = "SAU-2004";
+pub const YAML: &str = "SAU-2005";
+pub const EXECUTION: &str = "SAU-3001";
+pub const TIMEOUT: &str = "SAU-3002";
+pub const CANCELLED: &str = "SAU-3003";
+pub const TOOL_UNAVAILABLE: &str = "SAU-3004";
+pub const SECURITY: &str = "SAU-4001";
+pub const POLICY_VIOLATION: &str = "SAU-4002";
+pub const BUDGET_EXCEEDED: &str = "SAU-4003";
+pub const NETWORK: &str = "SAU-5001";
+pub const DATABASE: &str = "SAU-5002";
+pub const SYSTEM: &str = "SAU-5003";
+pub const OTHER: &str = "SAU-5999";
+
+/// Every code, in order
+pub const CATALOG: &[ErrorCode] = &[
+    ErrorCode {
+        code: UNCLASSIFIED,
+        title: "Command failed",
+        description: "The command reported a failure that has no more specific code.",
+        causes: &[
+            "A check the command performs did not pass, e.g. a lint or a verification step",
+            "An external tool exited with an error the command passed on",
+        ],
+        remedies: &[
+            "Read the message; it names the failing step",
+            "Re-run with --verbose for the full log",
+        ],
+    },
+    ErrorCode {
+        code: IO,
+        title: "I/O error",
+        description: "Reading or writing a file, directory or stream failed.",
+        causes: &[
+            "The disk is full or the file system is read-only",
+            "Another process holds or removed the file",
+        ],
+        remedies: &[
+            "Check free space and mount options",
+            "Retry once the other process has finished",
+        ],
+    },
+    ErrorCode {
+        code: FILE_NOT_FOUND,
+        title: "File not found",
+        description: "A file or directory the command needs does not exist.",
+        causes: &[
+            "The path is misspelled or relative to a different working directory",
+            "An earlier step that creates the file did not run",
+        ],
+        remedies: &[
+            "Check the path against the working directory",
+            "Run the step that produces the file first",
+        ],
+    },
+    ErrorCode {
+        code: PERMISSION,
+        title: "Permission denied",
+        description: "The operating system refused access to a file or resource.",
+        causes: &[
+            "The file belongs to another user or lacks the needed mode bits",
+            "The path is outside safety.workspace_roots",
+        ],
+        remedies: &[
+            "Fix the ownership or mode of the file",
+            "Pass --allow-outside-workspace, or add the directory to safety.workspace_roots",
+        ],
+    },
+    ErrorCode {
+        code: CONFIG,
+        title: "Configuration error",
+        description: "A configuration file or setting is invalid, or a pinned tool does not match its pin.",
+        causes: &[
+            "A key has the wrong type or an unknown name",
+            "tools.<name>.path points to a binary that does not exist",
+            "The output of a tool's --version could not be parsed",
+        ],
+        remedies: &[
+            "Run `safe-ai-util config validate` and fix what it reports",
+            "Run `safe-ai-util doctor --ecosystem tools` to check pinned tools",
+        ],
+    },
+    ErrorCode {
+        code: INVALID_ARGUMENT,
+        title: "Invalid argument",
+        description: "An argument has a value the command cannot use.",
+        causes: &["A value is out of range or in the wrong format"],
+        remedies: &["Run the command with --help to see the accepted values"],
+    },
+    ErrorCode {
+        code: VALIDATION,
+        title: "Validation failed",
+        description: "Input was rejected before anything ran: a path, a command line or file content failed a check.",
+        causes: &[
+            "A path escapes the workspace or contains a traversal",
+            "Content written to a file matches a content policy rule",
+            "A manifest, template or recorded file is malformed",
+        ],
+        remedies: &[
+            "Use a path inside the workspace",
+            "Pass --allow-content <rule> when the content is intended",
+            "Fix the input the message names",
+        ],
+    },
+    ErrorCode {
+        code: SERIALIZATION,
+        title: "JSON error",
+        description: "JSON could not be parsed or produced.",
+        causes: &[
+            "A JSON file in the state directory or the project is corrupt or hand-edited",
+            "A tool printed something other than the JSON it was asked for",
+        ],
+        remedies: &[
+            "Fix or remove the file the message names; state files are recreated when missing",
+        ],
+    },
+    ErrorCode {
+        code: YAML,
+        title: "YAML error",
+        description: "A YAML file could not be parsed.",
+        causes: &["The file has a syntax error, often indentation or an unquoted special character"],
+        remedies: &["Check the file with `safe-ai-util process --action validate FILE`"],
+    },
+    ErrorCode {
+        code: EXECUTION,
+        title: "Command execution failed",
+        description: "An external command could not be started or exited with a non-zero status.",
+        causes: &[
+            "The command itself failed; its output says why",
+            "The command is not in the allowlist",
+            "The working directory does not exist",
+        ],
+        remedies: &[
+            "Read the command's output in the log",
+            "Check the allowed commands with `safe-ai-util config get policy.allow_commands`",
+        ],
+    },
+    ErrorCode {
+        code: TIMEOUT,
+        title: "Timed out",
+        description: "A command or wait ran longer than its time limit and was stopped.",
+        causes: &[
+            "The command is slow or waiting for input that never comes",
+            "general.timeout_seconds is too low for the job",
+        ],
+        remedies: &[
+            "Raise general.timeout_seconds",
+            "Make sure the command does not prompt; use its non-interactive flag",
+        ],
+    },
+    ErrorCode {
+        code: CANCELLED,
+        title: "Cancelled",
+        description: "The operation was interrupted before it finished.",
+        causes: &["Ctrl-C, a signal, or a cancel request from the editor or serve client"],
+        remedies: &["Run the command again"],
+    },
+    ErrorCode {
+        code: TOOL_UNAVAILABLE,
+        title: "Tool unavailable",
+        description: "A command needs an external tool that is not installed, or is older than its [tools.<name>] pin. A capability request is queued for a human to approve.",
+        causes: &[
+            "The tool is not on PATH",
+            "The installed version is below tools.<name>.min_version",
+        ],
+        remedies: &[
+            "Ask for the tool to be installed with the `install` command in the error line",
+            "Review queued requests with `safe-ai-util tools requests`",
+        ],
+    },
+    ErrorCode {
+        code: SECURITY,
+        title: "Security violation",
+        description: "The command line was refused by the executor's security checks.",
+        causes: &[
+            "The command is not allowlisted",
+            "An argument contains shell metacharacters or a dangerous pattern",
+            "With safety.verify_binaries, the tool's binary changed since it was approved",
+        ],
+        remedies: &[
+            "Use the dedicated subcommand for the operation instead of a raw command",
+            "Have a human add the command to policy.allow_commands if it is safe",
+            "Have a human check a changed binary and approve it with `safe-ai-util binaries approve <command>`",
+        ],
+    },
+    ErrorCode {
+        code: POLICY_VIOLATION,
+        title: "Denied by policy",
+        description: "The active profile's policy rules deny the command. The error line lists the matching rules and remedies.",
+        causes: &[
+            "The profile (e.g. restricted) does not allow this command",
+            "A [[policy.rules]] entry denies the arguments",
+        ],
+        remedies: &[
+            "Follow a remedy from the error line",
+            "Check the active rules with `safe-ai-util config get policy.rules`",
+            "Switch profile with --profile if a human allows it",
+        ],
+    },
+    ErrorCode {
+        code: BUDGET_EXCEEDED,
+        title: "Budget exceeded",
+        description: "The session used up one of its budgets: wall-clock time, subprocesses or network bytes.",
+        causes: &["The session did more work than its budget allows"],
+        remedies: &[
+            "Raise the setting named in config_key in the error line",
+            "Start a new session",
+        ],
+    },
+    ErrorCode {
+        code: NETWORK,
+        title: "Network error",
+        description: "A connection to a remote service failed.",
+        causes: &[
+            "No network access, or a proxy or firewall blocks it",
+            "The remote service is down or rejected the credentials",
+        ],
+        remedies: &["Check connectivity and credentials, then retry"],
+    },
+    ErrorCode {
+        code: DATABASE,
+        title: "Database error",
+        description: "The SQLite database in the state directory could not be read or written.",
+        causes: &[
+            "Another process holds a write lock",
+            "The database file is corrupt",
+        ],
+        remedies: &[
+            "Retry once other safe-ai-util processes have finished",
+            "Move the database aside; it is recreated empty",
+        ],
+    },
+    ErrorCode {
+        code: SYSTEM,
+        title: "System error",
+        description: "The environment is not in a state the command can work with.",
+        causes: &[
+            "The state directory cannot be created",
+            "A lock file is held by a process that no longer exists",
+        ],
+        remedies: &[
+            "Check that SAFE_AI_STATE_DIR is writable",
+            "Remove stale .lock files once no safe-ai-util process is running",
+        ],
+    },
+    ErrorCode {
+        code: OTHER,
+        title: "Other error",
+        description: "An internal failure without a category.",
+        causes: &["An unexpected condition in safe-ai-util itself"],
+        remedies: &["Re-run with --verbose and report the log"],
+    },
+];
+
+/// Look up a code, case-insensitively and with or without the `SAU-` prefix
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    let code = code.trim().to_ascii_uppercase();
+    let number = code.strip_prefix("SAU-").unwrap_or(&code);
+    CATALOG
+        .iter()
+        .find(|entry| entry.code.strip_prefix("SAU-") == Some(number))
+}
+
+/// Code of an I/O error of this kind
+pub fn io_code(kind: std::io::ErrorKind) -> &'static str {
+    match kind {
+        std::io::ErrorKind::NotFound => FILE_NOT_FOUND,
+        std::io::ErrorKind::PermissionDenied => PERMISSION,
+        _ => IO,
+    }
+}
+
+/// Code of an error: that of the first [`AgentError`] in its chain, or of a bare I/O or
+/// JSON error, or [`UNCLASSIFIED`]
+pub fn classify(error: &anyhow::Error) -> &'static str {
+    error
+        .chain()
+        .find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<AgentError>() {
+                Some(e.code())
+            } else if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+                Some(io_code(e.kind()))
+            } else if cause.is::<serde_json::Error>() {
+                Some(SERIALIZATION)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(UNCLASSIFIED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        for (i, entry) in CATALOG.iter().enumerate() {
+            assert!(entry.code.starts_with("SAU-") && entry.code.len() == 8);
+            assert!(!entry.causes.is_empty() && !entry.remedies.is_empty());
+            assert!(
+                CATALOG[i + 1..]
+                    .iter()
+                    .all(|other| other.code != entry.code),
+                "{} is used twice",
+                entry.code
+            );
+        }
+        assert_eq!(lookup("sau-3004").unwrap().code, TOOL_UNAVAILABLE);
+        assert_eq!(lookup("4002").unwrap().code, POLICY_VIOLATION);
+        assert!(lookup("SAU-9999").is_none());
+    }
+
+    #[test]
+    fn test_classify() {
+        let denied = anyhow::Error::new(AgentError::policy_violation("no"));
+        assert_eq!(classify(&denied.context("running git")), POLICY_VIOLATION);
+        let missing = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(classify(&missing), FILE_NOT_FOUND);
+        assert_eq!(classify(&anyhow::anyhow!("lint failed")), UNCLASSIFIED);
+    }
+}
@@ -1,67 +1,670 @@
 // file: src/executor.rs
-// version: 2.0.0
+// version: 2.41.1
 // guid: bb371682-35cb-4f34-b318-8bf69ec125bd
 
+use crate::activity::Activity;
+use crate::batch::{BatchOptions, BatchResult, BatchStatus, CommandSpec, FailurePolicy, InputSource};
+use crate::budget::Budget;
+use crate::capability::CapabilityQueue;
 use crate::config::Config;
-use crate::security::{SecurityManager, audit};
+use crate::container::Container;
+use crate::credentials;
+use crate::security::{SecurityManager, audit, content::ContentPolicy, policy::Policy, sandbox::{self, Sandbox}};
+use crate::history::{self, Completed};
+use crate::integrity::{self, Verdict};
+use crate::jobs::{self, JobOptions, JobRecord};
+use crate::metrics;
+use crate::pipeline::PipelineOutput;
+use crate::plan::{Plan, PlanStep, SimulatedResult};
+use crate::process::{self, CancellationToken, Capture, CapturedOutput, OutputSink, OutputStream};
+#[cfg(unix)]
+use crate::pty::{self, Pty, SessionOptions};
+use crate::remote::{self, Remote};
+use crate::session;
+use crate::simulate;
+use crate::snapshot::{ChangeTracker, CommandChanges, Scope, Snapshot};
+use crate::telemetry;
 use crate::error::{AgentError, Result};
-use std::process::Stdio;
-use tokio::process::Command;
-use tracing::{info, warn};
+use crate::tools;
+use crate::verbosity;
+use crate::workspace_lock;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command};
+use tokio::sync::Semaphore;
+use std::time::Instant;
+use serde::Serialize;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+/// Captured output of a command executed through the safe execution path
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    /// Process exit code (`None` if the process was terminated by a signal)
+    pub exit_code: Option<i32>,
+    /// Captured standard output
+    pub stdout: String,
+    /// Captured standard error
+    pub stderr: String,
+    /// Whether the output was fabricated by simulation mode rather than produced by a process
+    pub simulated: bool,
+    /// Bytes dropped from the middle of output longer than `output.max_capture_bytes`
+    pub truncated: Truncation,
+}
+
+impl CommandOutput {
+    /// Whether the command exited successfully
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Bytes dropped from the middle of captured stdout and stderr; a marker line in the output
+/// shows where
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Truncation {
+    pub stdout_bytes: u64,
+    pub stderr_bytes: u64,
+}
+
+impl Truncation {
+    /// Whether no output was dropped
+    pub fn is_empty(&self) -> bool {
+        self.stdout_bytes == 0 && self.stderr_bytes == 0
+    }
+}
+
+/// Environment variables removed from every command and never set for one
+const DANGEROUS_ENV_VARS: &[&str] = &[
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "DYLD_INSERT_LIBRARIES",
+    "PYTHONPATH", // Can be dangerous if not carefully managed
+];
 
 /// Safe command executor with comprehensive security controls
 pub struct Executor {
     config: Config,
     security: SecurityManager,
+    policy: Policy,
+    sandbox: Sandbox,
+    /// Patterns content must not match before it is written
+    content_policy: ContentPolicy,
+    /// Processes recorded instead of spawned in dry-run and simulation mode
+    plan: Plan,
+    /// Binaries already resolved and checked against their pins, keyed by command
+    resolved: Mutex<HashMap<String, PathBuf>>,
+    /// Registration of this session and the files its commands modify
+    activity: Activity,
+    /// Cancels running commands and refuses new ones
+    cancel: CancellationToken,
+    /// Wall-clock, subprocess and network limits of the session
+    budget: Budget,
+    /// Input of commands run with [`Executor::execute_secure`] instead of the terminal
+    stdin: Option<InputSource>,
+    /// Run every command in a pseudo-terminal, not only tools configured with `pty = true`
+    pty: bool,
+    /// Files created, modified and deleted by each command, with `--report-changes`
+    changes: Option<ChangeTracker>,
+    /// Container commands are spawned in instead of on the host, with `--in-container`
+    container: Option<Container>,
+    /// Machine commands are spawned on instead of this one, with `--target`
+    remote: Option<Remote>,
 }
 
 impl Executor {
     /// Create a new executor with the given configuration
-    pub async fn new(config: Config) -> Result<Self> {
+    pub async fn new(mut config: Config) -> Result<Self> {
         // Initialize security audit system
         audit::initialize_audit_system()
             .map_err(|e| AgentError::system(format!("Failed to initialize audit system: {}", e)))?;
 
         let security = SecurityManager::new();
+        let policy = Policy::from_config(&config.policy)?;
+        let content_policy = ContentPolicy::from_config(&config.content_policy)?;
+        for (name, pin) in &config.tools {
+            tools::validate_pin(name, pin)?;
+        }
+        let base = match &config.general.working_directory {
+            Some(dir) => dir.clone(),
+            None => std::env::current_dir()?,
+        };
+        let sandbox = Sandbox::new(&config.safety.workspace_roots, &base)?;
+        let container = Container::from_config(&config)?;
+        let remote = Remote::from_config(&config)?;
+        if let Some(remote) = &remote {
+            if container.is_some() {
+                return Err(AgentError::config("--target cannot be combined with --in-container"));
+            }
+            if config.safety.no_network {
+                return Err(AgentError::config("--no-network cannot be applied on a --target machine"));
+            }
+            remote::set_current(remote.url());
+            info!("Commands run on {}", remote.url());
+        }
+        // A container is cut off by its runtime instead
+        if config.safety.no_network && container.is_none() {
+            process::check_network_isolation()?;
+        }
+        if let Some(container) = &container {
+            info!("Commands run in container image {}", container.image());
+        }
+
+        // Simulation never has side effects, so everything that honors dry-run skips its writes too
+        if config.safety.simulate {
+            config.safety.dry_run = true;
+        }
+        let plan = Plan::new(config.safety.simulate);
+        let activity = Activity::new(&base);
+        let budget = Budget::new(&config.budget);
 
         // Log the security configuration
         info!("Executor initialized with security controls enabled");
         info!("Security stats: {:?}", security.get_allowed_commands().len());
 
-        Ok(Self { config, security })
+        Ok(Self {
+            config,
+            security,
+            policy,
+            sandbox,
+            content_policy,
+            plan,
+            resolved: Mutex::new(HashMap::new()),
+            activity,
+            cancel: CancellationToken::new(),
+            budget,
+            stdin: None,
+            pty: false,
+            changes: None,
+            container,
+            remote,
+        })
     }
 
     /// Execute a command with full security validation
     pub async fn execute_secure<T: AsRef<str>>(&self, command: &str, args: &[T]) -> anyhow::Result<()> {
+        let sanitized_args = self.validate_request(command, args)?;
+
+        if self.config.safety.dry_run {
+            let spec = CommandSpec {
+                command: command.to_string(),
+                args: sanitized_args.clone(),
+                cwd: None,
+                env: BTreeMap::new(),
+                stdin: self.stdin.clone(),
+            };
+            let simulated = self.plan_step(command, &sanitized_args, &spec)?;
+            audit::log_command_execution(command, &sanitized_args);
+            if let Some(output) = simulated {
+                print!("{}", output.stdout);
+            }
+            return Ok(());
+        }
+
+        if let Some(InputSource::File { file: path }) = &self.stdin {
+            self.check_input_file(command, path)?;
+        }
+        let program = self.resolve_command(command, args)?;
+
+        // Execute command with security monitoring
+        let watch = self.activity.before(command, &sanitized_args);
+        let snapshot = self.changes.as_ref().map(ChangeTracker::before);
+        let result = self.execute_command_impl(&program, command, &sanitized_args).await;
+        self.record_changes(snapshot, command, &sanitized_args);
+        self.activity.after(watch);
+        result.map_err(Into::into)
+    }
+
+    /// Execute a command with full security validation and capture its output
+    ///
+    /// Unlike [`Executor::execute_secure`], a non-zero exit status is not treated as an
+    /// error; callers inspect [`CommandOutput::success`] and decide how to proceed. The whole
+    /// output is kept, since callers parse it.
+    pub async fn execute_secure_capture<T: AsRef<str>>(
+        &self,
+        command: &str,
+        args: &[T],
+    ) -> anyhow::Result<CommandOutput> {
+        let spec = CommandSpec {
+            command: command.to_string(),
+            args: args.iter().map(|a| a.as_ref().to_string()).collect(),
+            cwd: None,
+            env: BTreeMap::new(),
+            stdin: None,
+        };
+        self.capture_cancellable(&spec, &self.cancel, 0, None).await
+    }
+
+    /// Execute a command with its own working directory and environment variables and
+    /// capture its output, like [`Executor::execute_secure_capture`]
+    ///
+    /// The working directory and a file given as standard input must lie inside the workspace
+    /// roots, and variables the executor strips from every command (such as `LD_PRELOAD`)
+    /// cannot be set. Output past `output.max_capture_bytes` is truncated (see
+    /// [`CommandOutput::truncated`]).
+    pub async fn execute_spec_capture(&self, spec: &CommandSpec) -> anyhow::Result<CommandOutput> {
+        self.capture_cancellable(spec, &self.cancel, self.capture_limit(), None).await
+    }
+
+    /// [`Executor::execute_spec_capture`] keeping the whole output, for callers that parse it
+    pub async fn execute_spec_capture_full(&self, spec: &CommandSpec) -> anyhow::Result<CommandOutput> {
+        self.capture_cancellable(spec, &self.cancel, 0, None).await
+    }
+
+    /// [`Executor::execute_spec_capture`], sending the output to `sink` as it is written and
+    /// stopped by `cancel` as well as by cancelling the executor
+    ///
+    /// Pass a child of [`Executor::cancellation_token`] to stop one command among several.
+    pub async fn execute_spec_streaming(
+        &self,
+        spec: &CommandSpec,
+        cancel: &CancellationToken,
+        sink: &OutputSink,
+    ) -> anyhow::Result<CommandOutput> {
+        self.capture_cancellable(spec, cancel, self.capture_limit(), Some(sink)).await
+    }
+
+    /// Run commands as a pipeline, each command's stdout feeding the next one's stdin,
+    /// without a shell
+    ///
+    /// Every stage is validated like a single command before any is started. The stages run
+    /// concurrently, each under the timeout; only the first may have its own standard input,
+    /// and the last one's output is captured, truncated like
+    /// [`Executor::execute_spec_capture`]'s.
+    pub async fn execute_pipeline(&self, stages: &[CommandSpec]) -> anyhow::Result<PipelineOutput> {
+        if stages.is_empty() {
+            return Err(AgentError::invalid_argument("A pipeline needs at least one command").into());
+        }
+        if stages.iter().skip(1).any(|spec| spec.stdin.is_some()) {
+            return Err(AgentError::invalid_argument("Only the first command of a pipeline reads its own input").into());
+        }
+        let mut validated = Vec::with_capacity(stages.len());
+        for spec in stages {
+            validated.push(self.validate_request(&spec.command, &spec.args)?);
+            self.validate_overrides(spec)?;
+        }
+
+        if self.config.safety.dry_run {
+            let mut output = PipelineOutput::default();
+            for (spec, args) in stages.iter().zip(&validated) {
+                let simulated = self.plan_step(&spec.command, args, spec)?.unwrap_or(CommandOutput {
+                    exit_code: Some(0),
+                    ..Default::default()
+                });
+                audit::log_command_execution(&spec.command, args);
+                output.exit_codes.push(simulated.exit_code);
+                output.stdout = simulated.stdout;
+                output.stderr.push_str(&simulated.stderr);
+            }
+            return Ok(output);
+        }
+
+        let mut programs = Vec::with_capacity(stages.len());
+        for spec in stages {
+            programs.push(self.resolve_spec(spec)?);
+        }
+
+        let watches: Vec<_> = stages
+            .iter()
+            .zip(&validated)
+            .map(|(spec, args)| self.activity.before(&spec.command, args))
+            .collect();
+        let snapshot = self.changes.as_ref().map(ChangeTracker::before);
+        let result = self.pipeline_impl(stages, &programs, &validated).await;
+        if let (Some(tracker), Some(snapshot)) = (&self.changes, snapshot) {
+            let line = stages.iter().map(CommandSpec::to_string).collect::<Vec<_>>().join(" | ");
+            tracker.after(line, snapshot);
+        }
+        for watch in watches {
+            self.activity.after(watch);
+        }
+        result.map_err(Into::into)
+    }
+
+    /// Start a long-running command in the background as a job (see [`crate::jobs`])
+    ///
+    /// The command is validated like any other before its supervisor is started; its output
+    /// goes to the job's log rather than being captured, and it reads no input. Returns
+    /// `None` in dry-run mode, where the command is only planned.
+    pub async fn execute_detached(
+        &self,
+        spec: &CommandSpec,
+        options: &JobOptions,
+    ) -> anyhow::Result<Option<JobRecord>> {
+        let command = spec.command.as_str();
+        if spec.stdin.is_some() {
+            return Err(AgentError::invalid_argument("Background jobs read no input").into());
+        }
+        let sanitized_args = self.validate_request(command, &spec.args)?;
+        self.validate_overrides(spec)?;
+
+        if self.config.safety.dry_run {
+            self.plan_step(command, &sanitized_args, spec)?;
+            audit::log_command_execution(command, &sanitized_args);
+            return Ok(None);
+        }
+
+        let program = self.resolve_spec(spec)?;
+        self.budget.spawn()?;
+        let id = jobs::new_id();
+        let supervise = ["jobs".to_string(), "supervise".to_string(), id.clone()];
+        let cwd = spec.cwd.as_deref().or(self.config.general.working_directory.as_deref());
+        // The supervisor stays on the host and starts the container or ssh session
+        let job = self.prepare_host_command(&program, &sanitized_args, cwd, &spec.env)?;
+        let (program, job_args) = self
+            .target_command_line(&job)
+            .unwrap_or_else(|| (program, sanitized_args.clone()));
+        let supervisor = self.prepare_host_command(&jobs::supervisor_binary()?, &supervise, cwd, &spec.env)?;
+
+        let watch = self.activity.before(command, &sanitized_args);
+        let job = jobs::launch(supervisor, &spec.to_string(), program, job_args, jobs::job_cwd(cwd), id, options).await;
+        self.activity.after(watch);
+        audit::log_command_execution(command, &sanitized_args);
+        Ok(Some(job?))
+    }
+
+    /// Run independent commands concurrently, at most `options.jobs` at a time
+    ///
+    /// Each command is validated and captured like [`Executor::execute_spec_capture`];
+    /// the results are in the order of `commands`. See [`crate::batch`].
+    pub async fn execute_many(
+        &self,
+        commands: &[CommandSpec],
+        options: BatchOptions,
+    ) -> Vec<BatchResult> {
+        let permits = Semaphore::new(options.jobs.max(1));
+        // Cancels only this batch; cancelling the executor still stops it too
+        let batch = self.cancel.child_token();
+
+        let runs = commands.iter().map(|spec| {
+            let (permits, batch) = (&permits, &batch);
+            async move {
+                let _permit = permits.acquire().await;
+                if batch.is_cancelled() {
+                    return BatchResult::without_output(spec.clone(), BatchStatus::Skipped, None);
+                }
+
+                let started = Instant::now();
+                let outcome = self.capture_cancellable(spec, batch, self.capture_limit(), None).await;
+                let duration_ms = started.elapsed().as_millis() as u64;
+                let result = match outcome {
+                    Ok(output) => BatchResult {
+                        spec: spec.clone(),
+                        status: if output.success() {
+                            BatchStatus::Succeeded
+                        } else {
+                            BatchStatus::Failed
+                        },
+                        exit_code: output.exit_code,
+                        stdout: output.stdout,
+                        stderr: output.stderr,
+                        truncated: output.truncated,
+                        duration_ms,
+                        error: None,
+                    },
+                    Err(e) => {
+                        let cancelled = batch.is_cancelled()
+                            && matches!(e.downcast_ref::<AgentError>(), Some(AgentError::Cancelled(_)));
+                        let status = if cancelled { BatchStatus::Cancelled } else { BatchStatus::Error };
+                        BatchResult {
+                            duration_ms,
+                            ..BatchResult::without_output(spec.clone(), status, Some(e.to_string()))
+                        }
+                    }
+                };
+
+                if result.status != BatchStatus::Succeeded && options.on_failure == FailurePolicy::FailFast {
+                    batch.cancel();
+                }
+                result
+            }
+        });
+        futures::future::join_all(runs).await
+    }
+
+    /// [`Executor::execute_spec_capture`], stopped by `cancel`
+    async fn capture_cancellable(
+        &self,
+        spec: &CommandSpec,
+        cancel: &CancellationToken,
+        limit: usize,
+        sink: Option<&OutputSink>,
+    ) -> anyhow::Result<CommandOutput> {
+        let command = spec.command.as_str();
+        if cancel.is_cancelled() {
+            return Err(AgentError::cancelled(format!("Not running {}: execution was cancelled", command)).into());
+        }
+        let sanitized_args = self.validate_request(command, &spec.args)?;
+        self.validate_overrides(spec)?;
+
+        if self.config.safety.dry_run {
+            let simulated = self.plan_step(command, &sanitized_args, spec)?;
+            audit::log_command_execution(command, &sanitized_args);
+            return Ok(simulated.unwrap_or(CommandOutput {
+                exit_code: Some(0),
+                ..Default::default()
+            }));
+        }
+
+        let program = self.resolve_spec(spec)?;
+
+        let watch = self.activity.before(command, &sanitized_args);
+        let snapshot = self.changes.as_ref().map(ChangeTracker::before);
+        let result = self.capture_command_impl(&program, spec, &sanitized_args, cancel, limit, sink).await;
+        self.record_changes(snapshot, command, &sanitized_args);
+        self.activity.after(watch);
+        result.map_err(Into::into)
+    }
+
+    /// Record what a command changed since `snapshot`, taken when `--report-changes` is on
+    fn record_changes(&self, snapshot: Option<Snapshot>, command: &str, args: &[String]) {
+        if let (Some(tracker), Some(snapshot)) = (&self.changes, snapshot) {
+            let line = std::iter::once(command).chain(args.iter().map(String::as_str)).collect::<Vec<_>>().join(" ");
+            tracker.after(line, snapshot);
+        }
+    }
+
+    /// Check the working directory and environment variables a command asks for
+    fn validate_overrides(&self, spec: &CommandSpec) -> anyhow::Result<()> {
+        if let Some(cwd) = &spec.cwd {
+            let cwd = self.check_path(cwd)?;
+            if !cwd.is_dir() {
+                return Err(AgentError::file_not_found(format!("Working directory {} does not exist", cwd.display())).into());
+            }
+        }
+        if let Some(name) = spec.env.keys().find(|name| DANGEROUS_ENV_VARS.contains(&name.as_str())) {
+            let message = format!("Setting {} for {} is not allowed", name, spec.command);
+            audit::log_security_violation(&spec.command, &spec.args, &message);
+            return Err(AgentError::security(message).into());
+        }
+        if let Some(path) = spec.env.get("PATH") {
+            if let Some(entry) = path.split(':').find(|e| !e.starts_with('/') || e.contains("..")) {
+                let message = format!("PATH entry '{}' for {} must be an absolute path", entry, spec.command);
+                audit::log_security_violation(&spec.command, &spec.args, &message);
+                return Err(AgentError::security(message).into());
+            }
+        }
+        if let Some(InputSource::File { file: path }) = &spec.stdin {
+            self.check_input_file(&spec.command, path)?;
+        }
+        Ok(())
+    }
+
+    /// Check a file a command is to read as standard input
+    fn check_input_file(&self, command: &str, path: &Path) -> anyhow::Result<()> {
+        let path = self.check_path(path)?;
+        if !path.is_file() {
+            return Err(AgentError::file_not_found(format!("Input file {} for {} does not exist", path.display(), command)).into());
+        }
+        Ok(())
+    }
+
+    /// Validate the execution context and sanitize the command arguments
+    fn validate_request<T: AsRef<str>>(&self, command: &str, args: &[T]) -> anyhow::Result<Vec<String>> {
+        if self.cancel.is_cancelled() {
+            return Err(AgentError::cancelled(format!("Not running {}: execution was cancelled", command)).into());
+        }
+        self.budget.check_time()?;
+
         // Validate execution context first
         self.security.validate_execution_context().map_err(|e| anyhow::anyhow!("{}", e))?;
 
         // Convert args to String vector for security validation
         let string_args: Vec<String> = args.iter().map(|s| s.as_ref().to_string()).collect();
 
+        // Admin policy is evaluated on the raw arguments, before any sanitization
+        self.check_policy(command, &string_args)?;
+
+        self.check_path_args(command, &string_args)?;
+
         // Validate and sanitize the command and arguments
         let sanitized_args = self.security.validate_arguments(command, &string_args).map_err(|e| anyhow::anyhow!("{}", e))?;
 
         info!(
             "Executing secure command: {} with sanitized args: {:?}",
-            command, sanitized_args
+            command, audit::redact_args(&sanitized_args)
         );
 
-        if self.config.safety.dry_run {
-            println!("DRY RUN: Would execute: {} {:?}", command, sanitized_args);
-            audit::log_command_execution(command, &sanitized_args);
+        Ok(sanitized_args)
+    }
+
+    /// Evaluate the admin policy for a command, logging a denial as a security violation
+    ///
+    /// Commands that spawn their programs without the Executor (such as `uutils`) call this
+    /// before running anything, so `[policy]` applies to them as well.
+    pub fn check_policy(&self, command: &str, args: &[String]) -> anyhow::Result<()> {
+        if let Err(e) = self.policy.evaluate(command, args) {
+            audit::log_security_violation(command, args, &e.to_string());
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Refuse path arguments outside the workspace roots, logging them as a security violation
+    ///
+    /// Like [`Executor::check_policy`], for commands that spawn their programs themselves.
+    pub fn check_path_args(&self, command: &str, args: &[String]) -> anyhow::Result<()> {
+        if let Err(e) = self.sandbox.check_args(args) {
+            audit::log_security_violation(command, args, &e.to_string());
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Record the process a dry run would spawn, prepared exactly as it would be
+    ///
+    /// In simulation mode the step also gets a fabricated result, which is returned.
+    fn plan_step(&self, command: &str, args: &[String], spec: &CommandSpec) -> Result<Option<CommandOutput>> {
+        // A missing binary does not stop the plan; the step notes why it would fail
+        let search_path = spec.env.get("PATH").map(String::as_str);
+        let (program, note) = match tools::resolve_in(command, self.config.tools.get(command), search_path) {
+            Ok(path) => (path, None),
+            Err(e) => (PathBuf::from(command), Some(e.to_string())),
+        };
+        let prepared = self.prepare_command(&program, args, spec.cwd.as_deref(), &spec.env)?;
+        let mut step = PlanStep::from_command(command, &prepared, note);
+        step.stdin = spec.stdin.clone();
+
+        if !self.config.safety.simulate {
+            self.plan.record(step);
+            info!("DRY RUN: planned {} {:?}", command, audit::redact_args(args));
+            return Ok(None);
+        }
+
+        let output = simulate::fabricate(command, args, &step.cwd);
+        step.simulated = Some(SimulatedResult {
+            exit_code: output.exit_code.unwrap_or_default(),
+            stdout: output.stdout.clone(),
+        });
+        self.plan.record(step);
+        info!("SIMULATED: {} {:?}", command, audit::redact_args(args));
+        Ok(Some(output))
+    }
+
+    /// Resolve the binary for a command, verifying it against its `[tools.<name>]` pin
+    fn resolve_command<T: AsRef<str>>(&self, command: &str, args: &[T]) -> anyhow::Result<PathBuf> {
+        if let Some(path) = self.resolved.lock().unwrap().get(command) {
+            return Ok(path.clone());
+        }
+        // The container's or remote machine's PATH decides which binary runs
+        if self.container.is_some() || self.remote.is_some() {
+            return Ok(PathBuf::from(command));
+        }
+
+        let string_args: Vec<String> = args.iter().map(|s| s.as_ref().to_string()).collect();
+        match tools::resolve(command, self.config.tools.get(command)) {
+            Ok(path) => {
+                self.verify_binary(command, &string_args, &path)?;
+                self.resolved.lock().unwrap().insert(command.to_string(), path.clone());
+                Ok(path)
+            }
+            Err(e) => {
+                audit::log_security_violation(command, &string_args, &e.to_string());
+                Err(self.request_capability(e, command, &string_args).into())
+            }
+        }
+    }
+
+    /// Run `command` from `program` for the rest of the session, for a binary the caller has
+    /// already verified (such as a Gradle wrapper whose jar is approved); ignored when a
+    /// container or remote machine decides which binary runs
+    pub fn use_binary(&self, command: &str, program: PathBuf) {
+        if self.container.is_none() && self.remote.is_none() {
+            self.resolved.lock().unwrap().insert(command.to_string(), program);
+        }
+    }
+
+    /// Check a resolved binary against the hash approved for it, if `safety.verify_binaries`
+    /// is set, recording the hash of binaries not seen before
+    fn verify_binary(&self, command: &str, args: &[String], program: &Path) -> anyhow::Result<()> {
+        if !self.config.safety.verify_binaries {
             return Ok(());
         }
+        match integrity::verify(command, program) {
+            Ok(Verdict::Verified) => Ok(()),
+            Ok(Verdict::Recorded) => {
+                info!("Recorded the SHA-256 of {} as approved for {}", program.display(), command);
+                Ok(())
+            }
+            Err(e) => {
+                audit::log_security_violation(command, args, &e.to_string());
+                Err(e.into())
+            }
+        }
+    }
 
-        // Validate command exists
-        if which::which(command).is_err() {
-            let error_msg = format!("Command not found: {}", command);
-            audit::log_security_violation(command, &string_args, &error_msg);
-            return Err(anyhow::anyhow!("{}", error_msg));
+    /// Record the command line that needed a missing or outdated tool in its capability
+    /// request, and queue the request for approval if `capabilities.queue` is set
+    fn request_capability(&self, error: AgentError, command: &str, args: &[String]) -> AgentError {
+        let AgentError::ToolUnavailable(mut request) = error else {
+            return error;
+        };
+        request.needed_by = std::iter::once(command.to_string()).chain(args.iter().cloned()).collect();
+        if self.config.capabilities.queue {
+            let queued = (*request).clone();
+            match CapabilityQueue::update(|queue| queue.push(queued)) {
+                Ok(()) => request.queued = true,
+                Err(e) => warn!("Failed to queue the capability request for {}: {}", request.tool, e),
+            }
         }
+        AgentError::ToolUnavailable(request)
+    }
 
-        // Execute command with security monitoring
-        self.execute_command_impl(command, &sanitized_args).await.map_err(|e| anyhow::anyhow!("{}", e))
+    /// Resolve the binary for a command spec, searching the `PATH` it sets, if any, instead
+    /// of the inherited one; a binary found there outside the inherited `PATH` and the mise
+    /// installs is refused (see [`tools::is_trusted_location`])
+    fn resolve_spec(&self, spec: &CommandSpec) -> anyhow::Result<PathBuf> {
+        let Some(path) = spec.env.get("PATH").filter(|_| self.container.is_none() && self.remote.is_none()) else {
+            return self.resolve_command(&spec.command, &spec.args);
+        };
+        let program = tools::resolve_in(&spec.command, self.config.tools.get(&spec.command), Some(path)).map_err(|e| {
+            audit::log_security_violation(&spec.command, &spec.args, &e.to_string());
+            anyhow::Error::from(self.request_capability(e, &spec.command, &spec.args))
+        })?;
+        self.verify_binary(&spec.command, &spec.args, &program)?;
+        Ok(program)
     }
 
     /// Execute a raw command with arguments (DEPRECATED - use execute_secure instead)
@@ -78,39 +681,124 @@ impl Executor {
 
         // Route through secure execution
         self.execute_secure(command, &string_args).await
-            .map_err(|e| AgentError::execution(e.to_string()))
+            .map_err(|e| match e.downcast::<AgentError>() {
+                Ok(agent_error) => agent_error,
+                Err(e) => AgentError::execution(e.to_string()),
+            })
     }
 
     /// Internal implementation of command execution
-    async fn execute_command_impl(&self, command: &str, args: &[String]) -> Result<()> {
-        // Create command
-        let mut cmd = Command::new(command);
-        cmd.args(args)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit());
+    async fn execute_command_impl(&self, program: &Path, command: &str, args: &[String]) -> Result<()> {
+        let spec = CommandSpec {
+            command: command.to_string(),
+            args: args.to_vec(),
+            cwd: None,
+            env: BTreeMap::new(),
+            stdin: self.stdin.clone(),
+        };
+        if self.uses_pty(command) {
+            let (status, _) = self.pty_command_impl(program, &spec, args, true, &self.cancel).await?;
+            return self.check_status(command, args, status, None);
+        }
 
-        // Set working directory if specified
-        if let Some(ref wd) = self.config.general.working_directory {
-            cmd.current_dir(wd);
+        let mut cmd = self.prepare_command(program, args, None, &BTreeMap::new())?;
+        let input = match &self.stdin {
+            Some(source) => redirect_stdin(&mut cmd, source)?,
+            None => None,
+        };
+        cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+        // Only a command reading the terminal is given it
+        let _terminal = process::isolate(&mut cmd, self.stdin.is_none());
+        self.budget.spawn()?;
+
+        // Execute with timeout; the whole process tree is stopped on timeout or cancellation
+        let span = execution_span(command, args);
+        let started = Instant::now();
+        let status = async {
+            let mut child = cmd.spawn().map_err(|e| AgentError::execution(format!("Failed to execute command: {}", e)))?;
+            feed_stdin(&mut child, input);
+            process::wait(&mut child, self.timeout(), &self.cancel).await
         }
+        .instrument(span.clone())
+        .await;
+        let status = self.check_budget_timeout(status);
+        let (status, run_id) = self.record_run(&span, &spec, args, started, status);
+        let status = status?;
 
-        // Set environment variables with security filtering
-        self.set_secure_environment(&mut cmd)?;
+        // A command killed by a signal was interrupted, not failing
+        let verbose_log = match run_id {
+            Some(id) if self.config.execution.escalate_on_failure && status.code().is_some_and(|code| code != 0) => {
+                self.escalate_failure(program, &spec, id).await
+            }
+            _ => None,
+        };
+        self.check_status(command, args, status, verbose_log.as_deref())
+    }
+
+    /// Re-run a command that failed with the flags that make its tool verbose, capturing
+    /// its output, and attach the output to its run `id` in history; returns the file the
+    /// output was saved in, or `None` if the tool has no known flags or the re-run failed
+    ///
+    /// The re-run is validated like a new command.
+    async fn escalate_failure(&self, program: &Path, spec: &CommandSpec, id: i64) -> Option<PathBuf> {
+        let command = spec.command.as_str();
+        let escalation = verbosity::escalate(command, &spec.args, self.config.tools.get(command))?;
+        let args = match self.validate_request(command, &escalation.args) {
+            Ok(args) => args,
+            Err(e) => {
+                warn!("Not re-running {} with verbose flags: {}", command, e);
+                return None;
+            }
+        };
+        info!("Re-running {} with verbose flags: {:?}", command, audit::redact_args(&args));
 
-        // Execute with timeout
-        let status = tokio::time::timeout(
-            std::time::Duration::from_secs(self.config.general.timeout_seconds),
-            cmd.status()
-        )
-        .await
-        .map_err(|_| AgentError::timeout("Command execution timed out"))?
-        .map_err(|e| AgentError::execution(format!("Failed to execute command: {}", e)))?;
+        let output = async {
+            let mut cmd = self.prepare_command(program, &args, None, &escalation.env)?;
+            cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+            let input = match &spec.stdin {
+                Some(source) => redirect_stdin(&mut cmd, source)?,
+                None => None,
+            };
+            let _terminal = process::isolate(&mut cmd, false);
+            self.budget.spawn()?;
+            let mut child = cmd.spawn().map_err(|e| AgentError::execution(format!("Failed to execute command: {}", e)))?;
+            feed_stdin(&mut child, input);
+            process::output_to(child, self.timeout(), &self.cancel, self.capture_limit(), None).await
+        }
+        .instrument(execution_span(command, &args))
+        .await;
+        let output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Verbose re-run of {} failed: {}", command, e);
+                return None;
+            }
+        };
+        if output.status.success() {
+            warn!("{} succeeded when re-run with verbose flags; the failure may be intermittent", command);
+        }
+        let path = history::record_verbose_output(
+            id,
+            command,
+            &args,
+            &output.stdout.into_bytes(),
+            &output.stderr.into_bytes(),
+        )?;
+        info!("Verbose output of {} recorded in {}", command, path.display());
+        Some(path)
+    }
 
+    /// Fail, and audit the failure, unless a command run by [`Executor::execute_secure`]
+    /// exited successfully; the error names the output of a verbose re-run, if there was one
+    fn check_status(&self, command: &str, args: &[String], status: ExitStatus, verbose_log: Option<&Path>) -> Result<()> {
         if !status.success() {
-            let error_msg = format!(
+            let mut error_msg = format!(
                 "Command failed with exit code: {:?}",
                 status.code()
             );
+            if let Some(path) = verbose_log {
+                error_msg.push_str(&format!("; verbose re-run output in {}", path.display()));
+            }
             audit::log_security_violation(command, args, &error_msg);
             return Err(AgentError::execution(error_msg));
         }
@@ -120,17 +808,308 @@ impl Executor {
         Ok(())
     }
 
+    /// Internal implementation of command execution with captured output
+    async fn capture_command_impl(
+        &self,
+        program: &Path,
+        spec: &CommandSpec,
+        args: &[String],
+        cancel: &CancellationToken,
+        limit: usize,
+        sink: Option<&OutputSink>,
+    ) -> Result<CommandOutput> {
+        let command = spec.command.as_str();
+        if self.uses_pty(command) {
+            let (status, output) = self.pty_command_impl(program, spec, args, false, cancel).await?;
+            audit::log_command_execution(command, args);
+            if let Some(sink) = sink {
+                let _ = sink.send((OutputStream::Stdout, output.clone().into_bytes()));
+            }
+            let mut stdout = Capture::new(limit);
+            stdout.push(output.as_bytes());
+            // A terminal has a single output stream, so stderr is part of stdout
+            return Ok(CommandOutput {
+                exit_code: status.code(),
+                truncated: Truncation {
+                    stdout_bytes: stdout.omitted(),
+                    stderr_bytes: 0,
+                },
+                stdout: String::from_utf8_lossy(&stdout.into_bytes()).to_string(),
+                stderr: String::new(),
+                simulated: false,
+            });
+        }
+
+        let mut cmd = self.prepare_command(program, args, spec.cwd.as_deref(), &spec.env)?;
+        cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let input = match &spec.stdin {
+            Some(source) => redirect_stdin(&mut cmd, source)?,
+            None => None,
+        };
+        let _terminal = process::isolate(&mut cmd, false);
+        self.budget.spawn()?;
+
+        let span = execution_span(command, args);
+        let started = Instant::now();
+        let output = async {
+            let mut child = cmd.spawn().map_err(|e| AgentError::execution(format!("Failed to execute command: {}", e)))?;
+            feed_stdin(&mut child, input);
+            process::output_to(child, self.timeout(), cancel, limit, sink).await
+        }
+        .instrument(span.clone())
+        .await;
+        let output = self.check_budget_timeout(output);
+        let output = self.record_completion(&span, spec, args, started, output)?;
+
+        audit::log_command_execution(command, args);
+
+        Ok(CommandOutput {
+            exit_code: output.status.code(),
+            truncated: Truncation {
+                stdout_bytes: output.stdout.omitted(),
+                stderr_bytes: output.stderr.omitted(),
+            },
+            stdout: String::from_utf8_lossy(&output.stdout.into_bytes()).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr.into_bytes()).to_string(),
+            simulated: false,
+        })
+    }
+
+    /// Run a command in a new pseudo-terminal and wait for it; returns its exit status and
+    /// what it wrote to the terminal as plain text
+    ///
+    /// With `interactive`, the output is copied to stdout as it is written and recorded in the
+    /// state directory, and keystrokes on this process's terminal are forwarded unless the
+    /// command has input of its own.
+    #[cfg(unix)]
+    async fn pty_command_impl(
+        &self,
+        program: &Path,
+        spec: &CommandSpec,
+        args: &[String],
+        interactive: bool,
+        cancel: &CancellationToken,
+    ) -> Result<(ExitStatus, String)> {
+        use std::io::{IsTerminal, Read};
+
+        let command = spec.command.as_str();
+        let input = match &spec.stdin {
+            Some(source) => {
+                let mut input = Vec::new();
+                source.reader()?.read_to_end(&mut input)?;
+                Some(input)
+            }
+            None => None,
+        };
+        let terminal = Pty::open()?;
+        let mut cmd = self.prepare_command(program, args, spec.cwd.as_deref(), &spec.env)?;
+        terminal.attach(&mut cmd)?;
+        let options = SessionOptions {
+            echo: interactive,
+            forward_terminal: interactive && input.is_none() && std::io::stdin().is_terminal(),
+            input,
+            record_input: false,
+        };
+        self.budget.spawn()?;
+
+        let span = execution_span(command, args);
+        let started = Instant::now();
+        let result = async {
+            let mut child = cmd.spawn().map_err(|e| AgentError::execution(format!("Failed to execute command: {}", e)))?;
+            drop(cmd);
+            let session = terminal.start(options)?;
+            let status = process::wait(&mut child, self.timeout(), cancel).await;
+            let transcript = session.finish().await.output();
+            status.map(|status| (status, transcript))
+        }
+        .instrument(span.clone())
+        .await;
+        let result = self.check_budget_timeout(result);
+        let (status, transcript) = self.record_completion(&span, spec, args, started, result)?;
+
+        if interactive {
+            match pty::save_recording(command, &transcript) {
+                Ok(path) => info!("Terminal output recorded in {}", path.display()),
+                Err(e) => warn!("Could not record terminal output: {}", e),
+            }
+        }
+        Ok((status, pty::clean_transcript(&transcript)))
+    }
+
+    #[cfg(not(unix))]
+    async fn pty_command_impl(
+        &self,
+        _program: &Path,
+        _spec: &CommandSpec,
+        _args: &[String],
+        _interactive: bool,
+        _cancel: &CancellationToken,
+    ) -> Result<(ExitStatus, String)> {
+        Err(AgentError::validation("PTY mode is only supported on Unix"))
+    }
+
+    /// Spawn the stages of a pipeline connected by pipes and wait for all of them
+    async fn pipeline_impl(
+        &self,
+        stages: &[CommandSpec],
+        programs: &[PathBuf],
+        args: &[Vec<String>],
+    ) -> Result<PipelineOutput> {
+        let mut children = Vec::with_capacity(stages.len());
+        let mut spans = Vec::with_capacity(stages.len());
+        let mut input = Some(Stdio::null());
+        for (i, spec) in stages.iter().enumerate() {
+            let spawned = self.spawn_stage(spec, &programs[i], &args[i], input.take());
+            let mut child = match spawned {
+                Ok(child) => child,
+                Err(e) => {
+                    // Stages already started would otherwise wait forever for their reader
+                    for child in &mut children {
+                        process::terminate(child).await;
+                    }
+                    return Err(e);
+                }
+            };
+            spans.push((execution_span(&spec.command, &args[i]), Instant::now()));
+            input = match child.stdout.take() {
+                Some(stdout) if i + 1 < stages.len() => Some(
+                    stdout
+                        .try_into()
+                        .map_err(|e| AgentError::execution(format!("Failed to connect pipeline: {}", e)))?,
+                ),
+                stdout => {
+                    child.stdout = stdout;
+                    None
+                }
+            };
+            children.push(child);
+        }
+
+        let (timeout, limit) = (self.timeout(), self.capture_limit());
+        let waits = children.into_iter().zip(spans).enumerate().map(|(i, (child, (span, started)))| {
+            let (command, args) = (stages[i].command.as_str(), &args[i]);
+            async move {
+                let output = process::output(child, timeout, &self.cancel, limit).instrument(span.clone()).await;
+                let output = self.check_budget_timeout(output);
+                let output = self.record_completion(&span, &stages[i], args, started, output);
+                audit::log_command_execution(command, args);
+                output
+            }
+        });
+
+        let mut result = PipelineOutput::default();
+        for output in futures::future::join_all(waits).await {
+            let output = output?;
+            result.exit_codes.push(output.status.code());
+            result.truncated.stderr_bytes += output.stderr.omitted();
+            result.truncated.stdout_bytes = output.stdout.omitted();
+            result.stderr.push_str(&String::from_utf8_lossy(&output.stderr.into_bytes()));
+            result.stdout = String::from_utf8_lossy(&output.stdout.into_bytes()).to_string();
+        }
+        Ok(result)
+    }
+
+    /// Start one stage of a pipeline reading from `input`, the previous stage's output, or
+    /// for the first stage from its own input
+    fn spawn_stage(
+        &self,
+        spec: &CommandSpec,
+        program: &Path,
+        args: &[String],
+        input: Option<Stdio>,
+    ) -> Result<Child> {
+        let mut cmd = self.prepare_command(program, args, spec.cwd.as_deref(), &spec.env)?;
+        cmd.stdin(input.unwrap_or_else(Stdio::null)).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let text = match &spec.stdin {
+            Some(source) => redirect_stdin(&mut cmd, source)?,
+            None => None,
+        };
+        let _terminal = process::isolate(&mut cmd, false);
+        self.budget.spawn()?;
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| AgentError::execution(format!("Failed to execute {}: {}", spec.command, e)))?;
+        feed_stdin(&mut child, text);
+        Ok(child)
+    }
+
+    /// Build a process command with working directory and environment applied; `cwd` and
+    /// `env` are the command's own, already validated
+    ///
+    /// With `--in-container` or `--target` the command runs in the container or on the
+    /// remote machine.
+    pub(crate) fn prepare_command(
+        &self,
+        program: &Path,
+        args: &[String],
+        cwd: Option<&Path>,
+        env: &BTreeMap<String, String>,
+    ) -> Result<Command> {
+        let cmd = self.prepare_host_command(program, args, cwd, env)?;
+        let Some((program, args)) = self.target_command_line(&cmd) else {
+            return Ok(cmd);
+        };
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        Ok(cmd)
+    }
+
+    /// Container runtime or `ssh` and its arguments running the prepared `cmd` on the
+    /// execution target, or `None` if commands run on the host
+    fn target_command_line(&self, cmd: &Command) -> Option<(PathBuf, Vec<String>)> {
+        if let Some(container) = &self.container {
+            let (runtime, args) = container.command_line(cmd, &self.container_mounts());
+            return Some((runtime, args.iter().map(|arg| arg.to_string_lossy().into_owned()).collect()));
+        }
+        self.remote
+            .as_ref()
+            .map(|remote| remote.command_line(cmd, self.sandbox.base(), DANGEROUS_ENV_VARS))
+    }
+
+    /// [`Executor::prepare_command`], always running on the host
+    fn prepare_host_command(
+        &self,
+        program: &Path,
+        args: &[String],
+        cwd: Option<&Path>,
+        env: &BTreeMap<String, String>,
+    ) -> Result<Command> {
+        let mut cmd = if self.config.safety.no_network && self.container.is_none() {
+            process::offline_command(program, args)?
+        } else {
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        };
+
+        // Set working directory if specified
+        if let Some(wd) = cwd.or(self.config.general.working_directory.as_deref()) {
+            cmd.current_dir(wd);
+        }
+
+        // Set environment variables with security filtering
+        self.set_secure_environment(&mut cmd)?;
+        cmd.envs(env);
+
+        // Propagate session correlation IDs so nested executions can be linked
+        cmd.envs(session::current().child_env());
+        // Nested invocations get what is left of the budget
+        cmd.envs(self.budget.child_env());
+        // and share the workspace lock instead of waiting for it
+        cmd.envs(workspace_lock::child_env());
+        // buf gets the registry tokens from the keyring, only where the environment stays off
+        // command lines and never for a plan
+        if !self.config.safety.dry_run && self.container.is_none() && self.remote.is_none() {
+            cmd.envs(credentials::buf_child_env(program));
+        }
+
+        Ok(cmd)
+    }
+
     /// Set environment variables with security filtering
     fn set_secure_environment(&self, cmd: &mut Command) -> Result<()> {
         // Remove potentially dangerous environment variables
-        let dangerous_vars = [
-            "LD_PRELOAD",
-            "LD_LIBRARY_PATH",
-            "DYLD_INSERT_LIBRARIES",
-            "PYTHONPATH", // Can be dangerous if not carefully managed
-        ];
-
-        for var in &dangerous_vars {
+        for var in DANGEROUS_ENV_VARS {
             if std::env::var(var).is_ok() {
                 warn!("Removing dangerous environment variable: {}", var);
                 cmd.env_remove(var);
@@ -177,6 +1156,233 @@ impl Executor {
         Ok(clean_entries.join(":"))
     }
 
+    /// Token that cancels running commands, stopping their process trees, and refuses new
+    /// ones once cancelled
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Use `token` for cancellation, e.g. a child of a token the caller already has
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancel = token;
+    }
+
+    /// Record the outcome of an execution of `spec` with `args` on its span, in the metrics
+    /// registry and in history
+    ///
+    /// Spawn failures and timeouts count as failed executions.
+    fn record_completion<T: Finished>(
+        &self,
+        span: &tracing::Span,
+        spec: &CommandSpec,
+        args: &[String],
+        started: Instant,
+        result: Result<T>,
+    ) -> Result<T> {
+        self.record_run(span, spec, args, started, result).0
+    }
+
+    /// [`Executor::record_completion`], also returning the ID of the run in history, if it
+    /// was recorded
+    fn record_run<T: Finished>(
+        &self,
+        span: &tracing::Span,
+        spec: &CommandSpec,
+        args: &[String],
+        started: Instant,
+        result: Result<T>,
+    ) -> (Result<T>, Option<i64>) {
+        let elapsed = started.elapsed();
+        let code = result.as_ref().ok().and_then(Finished::exit_code);
+        span.record("exit_code", code.unwrap_or(-1));
+        span.record("duration_ms", elapsed.as_millis() as u64);
+        metrics::record_execution(&spec.command, args, code == Some(0), elapsed);
+
+        let cwd = match spec.cwd.as_deref().or(self.config.general.working_directory.as_deref()) {
+            Some(dir) => std::env::current_dir().map(|current| current.join(dir)).unwrap_or_else(|_| dir.to_path_buf()),
+            None => std::env::current_dir().unwrap_or_default(),
+        };
+        let output = result.as_ref().ok().and_then(Finished::output);
+        let id = history::record_execution(
+            &self.config.history,
+            &Completed {
+                command: &spec.command,
+                args,
+                cwd: &cwd,
+                env: &spec.env,
+                started_at: chrono::Utc::now() - chrono::Duration::from_std(elapsed).unwrap_or_default(),
+                duration: elapsed,
+                exit_code: code,
+                error: result.as_ref().err().map(ToString::to_string),
+                output: output.as_ref().map(|(stdout, stderr)| (stdout.as_slice(), stderr.as_slice())),
+            },
+        );
+        (result, id)
+    }
+
+    /// Per-command timeout from `general.timeout_seconds`, shortened to what is left of the
+    /// wall-clock budget
+    fn timeout(&self) -> std::time::Duration {
+        let timeout = std::time::Duration::from_secs(self.config.general.timeout_seconds);
+        match self.budget.remaining_time() {
+            Some(remaining) => timeout.min(remaining),
+            None => timeout,
+        }
+    }
+
+    /// Bytes of each output stream kept in results, from `output.max_capture_bytes`
+    fn capture_limit(&self) -> usize {
+        usize::try_from(self.config.output.max_capture_bytes).unwrap_or(usize::MAX)
+    }
+
+    /// Report a timeout caused by the wall-clock budget running out as a budget failure
+    fn check_budget_timeout<T>(&self, result: Result<T>) -> Result<T> {
+        match result {
+            Err(AgentError::Timeout(message)) => {
+                self.budget.check_time()?;
+                Err(AgentError::Timeout(message))
+            }
+            result => result,
+        }
+    }
+
+    /// Limits and spending of this session
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    /// Registration of this session among concurrent sessions
+    pub fn activity(&self) -> &Activity {
+        &self.activity
+    }
+
+    /// Get the configuration this executor was created with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Get the processes recorded instead of spawned during a dry run
+    pub fn plan(&self) -> &Plan {
+        &self.plan
+    }
+
+    /// Get the workspace sandbox that path arguments are checked against
+    pub fn sandbox(&self) -> &Sandbox {
+        &self.sandbox
+    }
+
+    /// Get the admin-defined command policy
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Canonicalize a path an operation will read or write, refusing it if it lies outside
+    /// the workspace roots
+    pub fn check_path(&self, path: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
+        let path = path.as_ref();
+        self.sandbox.check_path(path).map_err(|e| {
+            audit::log_security_violation("path", &[path.display().to_string()], &e.to_string());
+            e.into()
+        })
+    }
+
+    /// Refuse to write `content` to `path` if it matches the content policy
+    pub fn check_content(&self, path: impl AsRef<Path>, content: &[u8]) -> anyhow::Result<()> {
+        Ok(self.content_policy.check(path.as_ref(), content)?)
+    }
+
+    /// Get the content policy writes are checked against
+    pub fn content_policy(&self) -> &ContentPolicy {
+        &self.content_policy
+    }
+
+    /// Let writes through that match the named content rules (`--allow-content`)
+    pub fn set_content_overrides(&mut self, rules: Vec<String>) {
+        self.content_policy.set_overrides(rules);
+    }
+
+    /// Feed commands run with [`Executor::execute_secure`] this input instead of the terminal
+    /// (`--stdin-file`)
+    pub fn set_stdin(&mut self, input: Option<InputSource>) {
+        self.stdin = input;
+    }
+
+    /// Input given with [`Executor::set_stdin`], which commands implemented in-process read
+    /// instead of the terminal too
+    pub fn stdin(&self) -> Option<&InputSource> {
+        self.stdin.as_ref()
+    }
+
+    /// Run commands in a pseudo-terminal (`--pty`); pipelines and background jobs never are
+    pub fn set_pty(&mut self, pty: bool) {
+        self.pty = pty;
+    }
+
+    /// Report the files each command creates, modifies and deletes under `roots`, or the whole
+    /// workspace when none are given (`--report-changes`); background jobs are not watched
+    pub fn set_report_changes(&mut self, roots: &[PathBuf]) -> anyhow::Result<()> {
+        self.changes = Some(ChangeTracker::new(self.watch_scope(roots)?));
+        Ok(())
+    }
+
+    /// Files under `roots`, or the whole workspace when none are given, to take snapshots of;
+    /// the roots must lie inside the workspace roots
+    pub fn watch_scope(&self, roots: &[PathBuf]) -> anyhow::Result<Scope> {
+        let base = match &self.config.general.working_directory {
+            Some(dir) => dir.clone(),
+            None => std::env::current_dir()?,
+        };
+        let base = sandbox::canonicalize_lenient(&base);
+        let roots = if roots.is_empty() {
+            vec![base.clone()]
+        } else {
+            roots.iter().map(|root| self.check_path(root)).collect::<anyhow::Result<_>>()?
+        };
+        // The utility's own logs and state are not changes a command made
+        let excluded = [std::env::current_dir()?.join("logs"), crate::utils::state_dir()]
+            .iter()
+            .map(|dir| sandbox::canonicalize_lenient(dir))
+            .collect();
+        Ok(Scope { base, roots, excluded, include_ignored: false })
+    }
+
+    /// What each command changed, when [`Executor::set_report_changes`] was called
+    pub fn changes(&self) -> Option<Vec<CommandChanges>> {
+        self.changes.as_ref().map(ChangeTracker::reports)
+    }
+
+    /// Whether `command` runs in a pseudo-terminal, because of [`Executor::set_pty`] or
+    /// `pty = true` in its `[tools.<command>]` table
+    pub fn uses_pty(&self, command: &str) -> bool {
+        self.pty || self.config.tools.get(command).is_some_and(|tool| tool.pty)
+    }
+
+    /// Permit operations outside the workspace roots (`--allow-outside-workspace`)
+    pub fn set_allow_outside_workspace(&mut self, allow: bool) {
+        self.sandbox.set_allow_outside(allow);
+    }
+
+    /// Replace the workspace roots, e.g. with the folders an IDE session opened; relative
+    /// roots are resolved against `base`
+    pub fn set_workspace_roots(&mut self, roots: &[PathBuf], base: &Path) -> Result<()> {
+        let mut sandbox = Sandbox::new(roots, base)?;
+        sandbox.set_allow_outside(self.sandbox.allows_outside());
+        self.sandbox = sandbox;
+        Ok(())
+    }
+
+    /// Host directories mounted in the container: the working directory and the workspace
+    /// roots
+    fn container_mounts(&self) -> Vec<PathBuf> {
+        let mut mounts = vec![self.sandbox.base().to_path_buf()];
+        for root in self.sandbox.roots() {
+            if !mounts.iter().any(|mount| root.starts_with(mount)) {
+                mounts.push(root.clone());
+            }
+        }
+        mounts
+    }
+
     /// Get security manager for advanced operations
     pub fn security(&self) -> &SecurityManager {
         &self.security
@@ -187,3 +1393,81 @@ impl Executor {
         &mut self.security
     }
 }
+
+/// Connect a command's stdin to `source`; text is returned to be written with
+/// [`feed_stdin`] once the command has started, a file is read by the command directly
+fn redirect_stdin(cmd: &mut Command, source: &InputSource) -> Result<Option<Vec<u8>>> {
+    match source {
+        InputSource::Text { text } => {
+            cmd.stdin(Stdio::piped());
+            Ok(Some(text.clone().into_bytes()))
+        }
+        InputSource::File { file: path } => {
+            let file = std::fs::File::open(path)
+                .map_err(|e| AgentError::file_not_found(format!("Cannot open input file {}: {}", path.display(), e)))?;
+            cmd.stdin(file);
+            Ok(None)
+        }
+    }
+}
+
+/// Write `input` to a started command's stdin in the background, closing it afterwards
+fn feed_stdin(child: &mut Child, input: Option<Vec<u8>>) {
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        tokio::spawn(async move {
+            // A command may exit without reading all of its input
+            if let Err(e) = stdin.write_all(&input).await {
+                debug!("Input not fully written to command: {}", e);
+            }
+        });
+    }
+}
+
+/// Create the telemetry span describing a single command execution
+fn execution_span(command: &str, args: &[String]) -> tracing::Span {
+    info_span!(
+        telemetry::EXECUTION_SPAN,
+        command = %command,
+        args_hash = %telemetry::args_hash(args),
+        exit_code = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+    )
+}
+
+/// What a finished process produced, as recorded in history
+trait Finished {
+    fn exit_code(&self) -> Option<i32>;
+
+    /// Captured stdout and stderr, if the output was not written to the terminal
+    fn output(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        None
+    }
+}
+
+impl Finished for ExitStatus {
+    fn exit_code(&self) -> Option<i32> {
+        self.code()
+    }
+}
+
+impl Finished for CapturedOutput {
+    fn exit_code(&self) -> Option<i32> {
+        self.status.code()
+    }
+
+    fn output(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        Some((self.stdout.to_bytes(), self.stderr.to_bytes()))
+    }
+}
+
+/// A command run in a pseudo-terminal, with what it wrote to the terminal
+#[cfg(unix)]
+impl Finished for (ExitStatus, Vec<u8>) {
+    fn exit_code(&self) -> Option<i32> {
+        self.0.code()
+    }
+
+    fn output(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        Some((pty::clean_transcript(&self.1).into_bytes(), Vec::new()))
+    }
+}
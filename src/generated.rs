@@ -0,0 +1,221 @@
+// file: src/generated.rs
+// version: 1.1.0
+// guid: 5e08b3d7-4a92-4c61-b7f5-2d9a6c13e840
+
+//! Registry of files the utility generated
+//!
+//! Files written by `buf generate`, `scaffold new` and other code generators are recorded in
+//! `.safe-ai-util-generated.json` at the workspace root, with the hash of the content that was
+//! generated, the source it was generated from and the command that generated it. The file is
+//! meant to be committed, so everyone working on the repository can tell which files are
+//! generated, whether one was edited by hand since, and which files are safe to remove.
+//!
+//! Paths are relative to the workspace root and use `/` separators.
+
+use crate::error::{AgentError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the registry at the workspace root
+pub const REGISTRY_FILE: &str = ".safe-ai-util-generated.json";
+
+/// A generated file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Hash of the content as generated
+    pub sha256: String,
+    /// What the file was generated from, such as a proto directory or a template
+    pub source: String,
+    /// Command that generated the file
+    pub generator: String,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Whether a generated file still has the content it was generated with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactState {
+    Unchanged,
+    /// Edited since it was generated
+    Modified,
+    /// Removed since it was generated
+    Missing,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    #[serde(default)]
+    artifacts: BTreeMap<String, Artifact>,
+}
+
+/// The generated files of a workspace
+#[derive(Debug)]
+pub struct Registry {
+    base: PathBuf,
+    artifacts: BTreeMap<String, Artifact>,
+}
+
+impl Registry {
+    /// Load the registry of the workspace at `base`; empty if there is none yet
+    pub fn load(base: &Path) -> Result<Self> {
+        let file = match fs::read_to_string(base.join(REGISTRY_FILE)) {
+            Ok(content) => serde_json::from_str::<RegistryFile>(&content)
+                .map_err(|e| AgentError::config(format!("Invalid {}: {}", REGISTRY_FILE, e)))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => RegistryFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            base: base.to_path_buf(),
+            artifacts: file.artifacts,
+        })
+    }
+
+    /// Write the registry back, removing the file when no artifacts are left
+    pub fn save(&self) -> Result<()> {
+        let path = self.base.join(REGISTRY_FILE);
+        if self.artifacts.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let file = RegistryFile {
+            artifacts: self.artifacts.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&file)? + "\n")?;
+        Ok(())
+    }
+
+    /// Recorded files, by path
+    pub fn artifacts(&self) -> &BTreeMap<String, Artifact> {
+        &self.artifacts
+    }
+
+    /// Record `path`, absolute or relative to the workspace, as generated from `source` by
+    /// `generator` with its current content; returns the path as recorded
+    pub fn record(&mut self, path: &Path, source: &str, generator: &str) -> Result<String> {
+        let key = self.key(path)?;
+        let artifact = Artifact {
+            sha256: hash_file(&self.base.join(&key))?,
+            source: source.to_string(),
+            generator: generator.to_string(),
+            generated_at: Utc::now(),
+        };
+        self.artifacts.insert(key.clone(), artifact);
+        Ok(key)
+    }
+
+    /// The record of `path`, absolute or relative to the workspace, if it is a generated file
+    pub fn get(&self, path: &Path) -> Option<&Artifact> {
+        self.artifacts.get(&self.key(path).ok()?)
+    }
+
+    /// Forget a recorded file
+    pub fn forget(&mut self, key: &str) -> Option<Artifact> {
+        self.artifacts.remove(key)
+    }
+
+    /// Where a recorded file is
+    pub fn resolve(&self, key: &str) -> PathBuf {
+        self.base.join(key)
+    }
+
+    /// Compare every recorded file with the content it was generated with
+    pub fn check(&self) -> Vec<(String, ArtifactState)> {
+        self.artifacts
+            .iter()
+            .map(|(key, artifact)| {
+                let state = match hash_file(&self.resolve(key)) {
+                    Ok(hash) if hash == artifact.sha256 => ArtifactState::Unchanged,
+                    Ok(_) => ArtifactState::Modified,
+                    Err(_) => ArtifactState::Missing,
+                };
+                (key.clone(), state)
+            })
+            .collect()
+    }
+
+    /// `path` relative to the workspace with `/` separators; it must lie inside it
+    fn key(&self, path: &Path) -> Result<String> {
+        let relative = if path.is_absolute() {
+            path.strip_prefix(&self.base).map_err(|_| {
+                AgentError::invalid_argument(format!(
+                    "{} is outside the workspace {}",
+                    path.display(),
+                    self.base.display()
+                ))
+            })?
+        } else {
+            path
+        };
+        let mut parts = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+                Component::CurDir => {}
+                _ => {
+                    return Err(AgentError::invalid_argument(format!(
+                        "{} is not a path inside the workspace",
+                        path.display()
+                    )))
+                }
+            }
+        }
+        if parts.is_empty() {
+            return Err(AgentError::invalid_argument("Empty path"));
+        }
+        Ok(parts.join("/"))
+    }
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    Ok(hex::encode(Sha256::digest(fs::read(path)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_check() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("gen")).unwrap();
+        for name in ["a.pb.go", "b.pb.go", "c.pb.go"] {
+            fs::write(base.join("gen").join(name), name).unwrap();
+        }
+
+        let mut registry = Registry::load(base).unwrap();
+        let generator = "buf generate";
+        registry
+            .record(Path::new("gen/a.pb.go"), "proto", generator)
+            .unwrap();
+        registry
+            .record(&base.join("gen/b.pb.go"), "proto", generator)
+            .unwrap();
+        registry
+            .record(Path::new("./gen/c.pb.go"), "proto", generator)
+            .unwrap();
+        assert!(registry
+            .record(Path::new("../outside"), "proto", generator)
+            .is_err());
+        registry.save().unwrap();
+
+        fs::write(base.join("gen/b.pb.go"), "edited").unwrap();
+        fs::remove_file(base.join("gen/c.pb.go")).unwrap();
+        let registry = Registry::load(base).unwrap();
+        assert_eq!(
+            registry.check(),
+            vec![
+                ("gen/a.pb.go".to_string(), ArtifactState::Unchanged),
+                ("gen/b.pb.go".to_string(), ArtifactState::Modified),
+                ("gen/c.pb.go".to_string(), ArtifactState::Missing),
+            ]
+        );
+    }
+}
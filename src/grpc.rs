@@ -0,0 +1,450 @@
+// file: src/grpc.rs
+// version: 1.0.1
+// guid: 5b8e3f17-2c94-4a6d-9e01-d7a4c6b2f859
+
+//! Remote execution over gRPC (`serve grpc`, built with the `grpc` feature)
+//!
+//! CI runners and containers can hand their commands to one central daemon instead of
+//! running them directly, so every command goes through the daemon's validation, policy,
+//! budgets and history. The API is the `ExecutionService` of
+//! `proto/safe_ai_util/v1/execution.proto`:
+//!
+//! - `Submit` starts a command in the background and returns its execution id.
+//! - `StreamOutput` sends the execution's output as it is written, replaying what came
+//!   before the call, and ends with its exit status.
+//! - `Cancel` stops the command and the processes it started.
+//! - `QueryHistory` lists recorded runs (see [`crate::history`]).
+//!
+//! The server listens on a loopback address unless a token is set; with a token every call
+//! must carry `authorization: Bearer <token>` metadata. The last [`KEEP_FINISHED`]
+//! finished executions stay available to `StreamOutput`.
+
+/// Code generated from the proto by `build.rs`
+pub mod proto {
+    tonic::include_proto!("safe_ai_util.v1");
+}
+
+use crate::batch::{BatchStatus, CommandSpec, InputSource};
+use crate::error::{AgentError, Result};
+use crate::executor::Executor;
+use crate::history::{Filter, History, Run};
+use crate::process::{CancellationToken, OutputStream};
+use crate::utils;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use proto::execution_service_server::{ExecutionService, ExecutionServiceServer};
+use proto::{
+    output_event, CancelRequest, CancelResponse, ExitStatus, HistoryEntry, OutputChunk,
+    OutputEvent, QueryHistoryRequest, QueryHistoryResponse, StreamOutputRequest, SubmitRequest,
+    SubmitResponse,
+};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+/// Finished executions kept for `StreamOutput`; older ones are forgotten
+pub const KEEP_FINISHED: usize = 100;
+
+/// Runs returned by `QueryHistory` when the request sets no limit
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+/// Options for [`serve`]
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Address to listen on; port 0 picks a free port
+    pub listen: SocketAddr,
+    /// Bearer token every call must carry; required unless listening on a loopback address
+    pub token: Option<String>,
+}
+
+/// Serve the ExecutionService until the process is stopped
+///
+/// The address actually listened on is printed to stdout as `listening on <address>` once
+/// the server accepts connections.
+pub async fn serve(executor: Executor, options: Options) -> Result<()> {
+    if options.token.is_none() && !options.listen.ip().is_loopback() {
+        return Err(AgentError::security(format!(
+            "Listening on {} requires a token, since anyone who can connect could run commands",
+            options.listen
+        )));
+    }
+    let incoming = TcpIncoming::bind(options.listen)
+        .map_err(|e| AgentError::network(format!("Cannot listen on {}: {}", options.listen, e)))?;
+    let address = incoming.local_addr()?;
+
+    let service = ExecutionServiceServer::with_interceptor(
+        Service {
+            executor: Arc::new(executor),
+            executions: Arc::new(Mutex::new(Registry::default())),
+        },
+        authorize(options.token),
+    );
+    println!("listening on {}", address);
+    info!("gRPC ExecutionService listening on {}", address);
+    Server::builder()
+        .add_service(service)
+        .serve_with_incoming(incoming)
+        .await
+        .map_err(|e| AgentError::network(format!("gRPC server failed: {}", e)))
+}
+
+/// Interceptor rejecting calls without the bearer token, if there is one
+fn authorize(
+    token: Option<String>,
+) -> impl FnMut(Request<()>) -> std::result::Result<Request<()>, Status> + Clone {
+    let expected = token.map(|token| format!("Bearer {}", token));
+    move |request: Request<()>| {
+        let Some(expected) = &expected else {
+            return Ok(request);
+        };
+        let given = request
+            .metadata()
+            .get("authorization")
+            .map(|value| value.as_bytes())
+            .unwrap_or_default();
+        if utils::constant_time_eq(given, expected.as_bytes()) {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("missing or wrong bearer token"))
+        }
+    }
+}
+
+type EventSender = UnboundedSender<std::result::Result<OutputEvent, Status>>;
+type EventStream = UnboundedReceiver<std::result::Result<OutputEvent, Status>>;
+
+/// One submitted command and the events it produced so far
+struct Execution {
+    cancel: CancellationToken,
+    state: Mutex<Events>,
+}
+
+#[derive(Default)]
+struct Events {
+    events: Vec<OutputEvent>,
+    subscribers: Vec<EventSender>,
+    finished: bool,
+}
+
+impl Execution {
+    fn new(cancel: CancellationToken) -> Self {
+        Self {
+            cancel,
+            state: Mutex::new(Events::default()),
+        }
+    }
+
+    /// Record an event and send it to every subscriber; the exit event ends their streams
+    fn publish(&self, event: OutputEvent) {
+        let mut state = self.state.lock().unwrap();
+        let last = matches!(event.event, Some(output_event::Event::Exit(_)));
+        state
+            .subscribers
+            .retain(|subscriber| subscriber.unbounded_send(Ok(event.clone())).is_ok());
+        state.events.push(event);
+        if last {
+            state.finished = true;
+            state.subscribers.clear();
+        }
+    }
+
+    /// Stream of the events so far followed by the ones still to come
+    fn subscribe(&self) -> EventStream {
+        let (sender, receiver) = unbounded();
+        let mut state = self.state.lock().unwrap();
+        for event in &state.events {
+            let _ = sender.unbounded_send(Ok(event.clone()));
+        }
+        if !state.finished {
+            state.subscribers.push(sender);
+        }
+        receiver
+    }
+
+    fn finished(&self) -> bool {
+        self.state.lock().unwrap().finished
+    }
+}
+
+/// Executions by id, in the order they were submitted
+#[derive(Default)]
+struct Registry {
+    executions: HashMap<String, Arc<Execution>>,
+    order: VecDeque<String>,
+}
+
+impl Registry {
+    /// Add an execution, forgetting the oldest finished ones past [`KEEP_FINISHED`]
+    fn insert(&mut self, id: String, execution: Arc<Execution>) {
+        let finished: Vec<String> = self
+            .order
+            .iter()
+            .filter(|id| self.executions[*id].finished())
+            .cloned()
+            .collect();
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(KEEP_FINISHED))
+        {
+            self.executions.remove(id);
+            self.order.retain(|other| other != id);
+        }
+        self.order.push_back(id.clone());
+        self.executions.insert(id, execution);
+    }
+
+    fn get(&self, id: &str) -> std::result::Result<Arc<Execution>, Status> {
+        self.executions
+            .get(id)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("No execution '{}'", id)))
+    }
+}
+
+struct Service {
+    executor: Arc<Executor>,
+    executions: Arc<Mutex<Registry>>,
+}
+
+#[tonic::async_trait]
+impl ExecutionService for Service {
+    type StreamOutputStream = EventStream;
+
+    async fn submit(
+        &self,
+        request: Request<SubmitRequest>,
+    ) -> std::result::Result<Response<SubmitResponse>, Status> {
+        let request = request.into_inner();
+        if request.command.is_empty() {
+            return Err(Status::invalid_argument("command is empty"));
+        }
+        let stdin = match request.stdin {
+            Some(bytes) => Some(InputSource::Text {
+                text: String::from_utf8(bytes)
+                    .map_err(|_| Status::invalid_argument("stdin must be UTF-8 text"))?,
+            }),
+            None => None,
+        };
+        let spec = CommandSpec {
+            command: request.command,
+            args: request.args,
+            cwd: (!request.cwd.is_empty()).then(|| PathBuf::from(request.cwd)),
+            env: request.env.into_iter().collect(),
+            stdin,
+        };
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let execution = Arc::new(Execution::new(
+            self.executor.cancellation_token().child_token(),
+        ));
+        self.executions
+            .lock()
+            .unwrap()
+            .insert(id.clone(), execution.clone());
+        info!("Execution {} submitted: {}", id, spec);
+        tokio::spawn(run(self.executor.clone(), spec, execution));
+        Ok(Response::new(SubmitResponse { execution_id: id }))
+    }
+
+    async fn stream_output(
+        &self,
+        request: Request<StreamOutputRequest>,
+    ) -> std::result::Result<Response<Self::StreamOutputStream>, Status> {
+        let id = request.into_inner().execution_id;
+        let execution = self.executions.lock().unwrap().get(&id)?;
+        Ok(Response::new(execution.subscribe()))
+    }
+
+    async fn cancel(
+        &self,
+        request: Request<CancelRequest>,
+    ) -> std::result::Result<Response<CancelResponse>, Status> {
+        let id = request.into_inner().execution_id;
+        let execution = self.executions.lock().unwrap().get(&id)?;
+        let cancelled = !execution.finished();
+        if cancelled {
+            info!("Execution {} cancelled", id);
+            execution.cancel.cancel();
+        }
+        Ok(Response::new(CancelResponse { cancelled }))
+    }
+
+    async fn query_history(
+        &self,
+        request: Request<QueryHistoryRequest>,
+    ) -> std::result::Result<Response<QueryHistoryResponse>, Status> {
+        let request = request.into_inner();
+        let filter = Filter {
+            failed_only: request.failed_only,
+            text: (!request.text.is_empty()).then_some(request.text),
+            limit: match request.limit {
+                0 => DEFAULT_HISTORY_LIMIT,
+                limit => limit as usize,
+            },
+            ..Default::default()
+        };
+        let runs = tokio::task::spawn_blocking(move || History::open()?.list(&filter))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(QueryHistoryResponse {
+            entries: runs.into_iter().map(history_entry).collect(),
+        }))
+    }
+}
+
+/// Run a submitted command, publishing its output and then its exit status
+async fn run(executor: Arc<Executor>, spec: CommandSpec, execution: Arc<Execution>) {
+    let (sink, mut chunks) = tokio::sync::mpsc::unbounded_channel();
+    let started = Instant::now();
+    let command = executor.execute_spec_streaming(&spec, &execution.cancel, &sink);
+    tokio::pin!(command);
+    let outcome = loop {
+        tokio::select! {
+            Some((stream, data)) = chunks.recv() => execution.publish(output(stream, data)),
+            outcome = &mut command => break outcome,
+        }
+    };
+    // Output read just before the command finished
+    while let Ok((stream, data)) = chunks.try_recv() {
+        execution.publish(output(stream, data));
+    }
+
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let exit = match outcome {
+        Ok(result) => {
+            // A simulated command produces its output without running
+            if result.simulated && !result.stdout.is_empty() {
+                execution.publish(output(
+                    OutputStream::Stdout,
+                    result.stdout.clone().into_bytes(),
+                ));
+            }
+            let status = if result.success() {
+                BatchStatus::Succeeded
+            } else {
+                BatchStatus::Failed
+            };
+            ExitStatus {
+                status: status.as_str().to_string(),
+                exit_code: result.exit_code,
+                duration_ms,
+                error: String::new(),
+            }
+        }
+        Err(e) => {
+            let status = if execution.cancel.is_cancelled() {
+                BatchStatus::Cancelled
+            } else {
+                BatchStatus::Error
+            };
+            ExitStatus {
+                status: status.as_str().to_string(),
+                exit_code: None,
+                duration_ms,
+                error: e.to_string(),
+            }
+        }
+    };
+    info!(
+        "Execution of {} {} in {} ms",
+        spec, exit.status, duration_ms
+    );
+    execution.publish(OutputEvent {
+        event: Some(output_event::Event::Exit(exit)),
+    });
+}
+
+fn output(stream: OutputStream, data: Vec<u8>) -> OutputEvent {
+    let stream = match stream {
+        OutputStream::Stdout => proto::OutputStream::Stdout,
+        OutputStream::Stderr => proto::OutputStream::Stderr,
+    };
+    OutputEvent {
+        event: Some(output_event::Event::Output(OutputChunk {
+            stream: stream.into(),
+            data,
+        })),
+    }
+}
+
+fn history_entry(run: Run) -> HistoryEntry {
+    HistoryEntry {
+        id: run.id,
+        started_at: run.started_at.to_rfc3339(),
+        command_line: run.command_line(),
+        cwd: run.cwd.display().to_string(),
+        exit_code: run.exit_code,
+        duration_ms: run.duration_ms,
+        error: run.error.unwrap_or_default(),
+        reason: run.reason.unwrap_or_default(),
+        labels: run.labels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exit() -> OutputEvent {
+        OutputEvent {
+            event: Some(output_event::Event::Exit(ExitStatus::default())),
+        }
+    }
+
+    #[test]
+    fn test_late_subscribers_get_earlier_events() {
+        use futures::StreamExt;
+
+        let execution = Execution::new(CancellationToken::new());
+        let early = execution.subscribe();
+        execution.publish(output(OutputStream::Stdout, b"one\n".to_vec()));
+        let late = execution.subscribe();
+        execution.publish(exit());
+        let after = execution.subscribe();
+
+        for stream in [early, late, after] {
+            let events: Vec<_> = futures::executor::block_on(stream.collect::<Vec<_>>());
+            assert_eq!(events.len(), 2);
+            assert!(matches!(
+                events[1].as_ref().unwrap().event,
+                Some(output_event::Event::Exit(_))
+            ));
+        }
+        assert!(execution.finished());
+    }
+
+    #[test]
+    fn test_registry_forgets_oldest_finished() {
+        let mut registry = Registry::default();
+        for i in 0..=KEEP_FINISHED {
+            let execution = Arc::new(Execution::new(CancellationToken::new()));
+            execution.publish(exit());
+            registry.insert(i.to_string(), execution);
+        }
+        let running = Arc::new(Execution::new(CancellationToken::new()));
+        registry.insert("running".to_string(), running);
+
+        assert!(registry.get("0").is_err());
+        assert!(registry.get("1").is_ok());
+        assert!(registry.get("running").is_ok());
+        assert_eq!(registry.order.len(), KEEP_FINISHED + 1);
+    }
+
+    #[test]
+    fn test_authorize_checks_bearer_token() {
+        let mut check = authorize(Some("secret".to_string()));
+        let mut request = Request::new(());
+        assert!(check(Request::new(())).is_err());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(check(request).is_ok());
+        assert!(authorize(None)(Request::new(())).is_ok());
+    }
+}
@@ -0,0 +1,192 @@
+// file: src/guardrails.rs
+// version: 1.0.0
+// guid: 85cc9b52-8ccb-4edd-a957-e2c78e0a8fc5
+
+//! Usage guardrails for operations whose cost scales with the workspace
+//!
+//! Commands that read or rewrite many files (recursive search, in-place sed, hashing)
+//! estimate the number of files and bytes involved before they start. Above the
+//! `guardrails.warn_*` thresholds a warning is logged; above `guardrails.confirm_*` the
+//! operation needs interactive confirmation (or `--yes` where the command offers it).
+//! In dry-run mode the estimate is reported instead.
+
+use crate::config::Config;
+use crate::error::{AgentError, Result};
+use crate::utils::format_bytes;
+use std::fmt;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use tracing::{debug, warn};
+use walkdir::WalkDir;
+
+/// Estimated size of an operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Estimate {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl Estimate {
+    /// Count the files (and their bytes) under the given paths; directories are only
+    /// descended into when `recursive` is set, and paths that do not exist are ignored
+    pub fn of_paths<P: AsRef<Path>>(paths: &[P], recursive: bool) -> Self {
+        let mut estimate = Self::default();
+        for path in paths {
+            let path = path.as_ref();
+            if path.is_file() {
+                estimate.add(path.metadata().map(|m| m.len()).unwrap_or(0));
+            } else if path.is_dir() && recursive {
+                for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+                    if entry.file_type().is_file() {
+                        estimate.add(entry.metadata().map(|m| m.len()).unwrap_or(0));
+                    }
+                }
+            }
+        }
+        estimate
+    }
+
+    /// Account for one more file of the given size
+    pub fn add(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} files ({})", self.files, format_bytes(self.bytes))
+    }
+}
+
+/// How an estimate compares to the configured thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Ok,
+    Warn,
+    Confirm,
+}
+
+/// Classify an estimate against the guardrail thresholds of the configuration
+pub fn level(config: &Config, estimate: &Estimate) -> Level {
+    let limits = &config.guardrails;
+    if !limits.enabled {
+        Level::Ok
+    } else if estimate.files > limits.confirm_files || estimate.bytes > limits.confirm_bytes {
+        Level::Confirm
+    } else if estimate.files > limits.warn_files || estimate.bytes > limits.warn_bytes {
+        Level::Warn
+    } else {
+        Level::Ok
+    }
+}
+
+/// Check an operation against the guardrails before running it
+///
+/// Returns an error if the operation needs confirmation that was refused or cannot be
+/// asked for because stdin is not a terminal.
+pub fn check(
+    config: &Config,
+    operation: &str,
+    estimate: &Estimate,
+    assume_yes: bool,
+) -> Result<()> {
+    let level = level(config, estimate);
+
+    if config.safety.dry_run {
+        let note = match level {
+            Level::Ok => "",
+            Level::Warn => " (above the warning threshold)",
+            Level::Confirm => " (would require confirmation)",
+        };
+        println!("DRY RUN: {} would touch {}{}", operation, estimate, note);
+        return Ok(());
+    }
+
+    match level {
+        Level::Ok => {
+            debug!("{} will touch {}", operation, estimate);
+            Ok(())
+        }
+        Level::Warn => {
+            warn!("{} will touch {}", operation, estimate);
+            Ok(())
+        }
+        Level::Confirm => {
+            warn!(
+                "{} will touch {}, above the guardrail limits",
+                operation, estimate
+            );
+            if assume_yes {
+                return Ok(());
+            }
+            let limits = &config.guardrails;
+            if !io::stdin().is_terminal() {
+                return Err(AgentError::validation(format!(
+                    "{} would touch {}, more than guardrails.confirm_files ({}) or guardrails.confirm_bytes ({}); run it interactively to confirm or raise the limits",
+                    operation,
+                    estimate,
+                    limits.confirm_files,
+                    format_bytes(limits.confirm_bytes)
+                )));
+            }
+
+            print!("{} will touch {}. Continue? [y/N] ", operation, estimate);
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            io::stdin().lock().read_line(&mut answer)?;
+            if matches!(answer.trim(), "y" | "Y" | "yes") {
+                Ok(())
+            } else {
+                Err(AgentError::validation(format!("{} cancelled", operation)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_estimate_of_paths() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.txt"), "12345").unwrap();
+        fs::write(dir.path().join("sub/b.txt"), "123").unwrap();
+
+        let recursive = Estimate::of_paths(&[dir.path()], true);
+        assert_eq!(recursive, Estimate { files: 2, bytes: 8 });
+        assert_eq!(
+            Estimate::of_paths(&[dir.path()], false),
+            Estimate::default()
+        );
+        assert_eq!(
+            Estimate::of_paths(
+                &[dir.path().join("a.txt"), dir.path().join("missing")],
+                false
+            ),
+            Estimate { files: 1, bytes: 5 }
+        );
+    }
+
+    #[test]
+    fn test_level() {
+        let mut config = Config::default();
+        config.guardrails.warn_files = 10;
+        config.guardrails.confirm_files = 100;
+        config.guardrails.warn_bytes = 1000;
+        config.guardrails.confirm_bytes = 10_000;
+
+        let estimate = |files, bytes| Estimate { files, bytes };
+        assert_eq!(level(&config, &estimate(5, 100)), Level::Ok);
+        assert_eq!(level(&config, &estimate(50, 100)), Level::Warn);
+        assert_eq!(level(&config, &estimate(5, 20_000)), Level::Confirm);
+
+        config.guardrails.enabled = false;
+        assert_eq!(level(&config, &estimate(500, 20_000)), Level::Ok);
+        assert!(check(&config, "test", &estimate(500, 20_000), false).is_ok());
+    }
+}
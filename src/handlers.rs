@@ -0,0 +1,460 @@
+// file: src/handlers.rs
+// version: 1.0.1
+// guid: eb239a71-e175-49da-bec8-d2ecd359ddf2
+
+//! Routing files to the tools that handle them
+//!
+//! `process` takes a file and works out what validates, formats and lints it from its
+//! extension, or for scripts without one from the interpreter on their `#!` line. Handlers
+//! configured under `[[process.handlers]]` are tried first, in order; unless
+//! `process.builtin` is turned off, files they leave unhandled fall back to the built-in
+//! table: JSON, YAML and TOML are parsed in-process (JSON is also reformatted), protobuf goes
+//! to buf, and source files to their usual formatter and linter.
+
+use crate::config::{FileAction, FileHandler, ProcessConfig};
+use crate::error::{AgentError, Result};
+use crate::json_order::{KeyOrder, Ordered};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// MIME types of the extensions handlers are usually written for
+const EXTENSIONS: &[(&[&str], &str)] = &[
+    (&["json"], "application/json"),
+    (&["yaml", "yml"], "application/yaml"),
+    (&["toml"], "application/toml"),
+    (&["xml"], "application/xml"),
+    (&["proto"], "text/x-protobuf"),
+    (&["rs"], "text/x-rust"),
+    (&["go"], "text/x-go"),
+    (&["py", "pyi"], "text/x-python"),
+    (&["sh", "bash"], "application/x-sh"),
+    (&["js", "mjs", "cjs", "jsx"], "text/javascript"),
+    (&["ts", "mts", "cts", "tsx"], "application/typescript"),
+    (&["c", "h"], "text/x-c"),
+    (&["cc", "cpp", "cxx", "hpp"], "text/x-c++"),
+    (&["md", "markdown"], "text/markdown"),
+    (&["css"], "text/css"),
+    (&["html", "htm"], "text/html"),
+    (&["txt"], "text/plain"),
+];
+
+/// MIME types of scripts by the interpreter named on their `#!` line
+const INTERPRETERS: &[(&[&str], &str)] = &[
+    (&["sh", "bash", "dash", "zsh", "ksh"], "application/x-sh"),
+    (&["python"], "text/x-python"),
+    (&["node", "deno", "bun"], "text/javascript"),
+];
+
+/// Handlers used when no configured one matches: MIME type, action, what runs
+const BUILTIN: &[(&str, FileAction, Builtin)] = &[
+    (
+        "application/json",
+        FileAction::Validate,
+        Builtin::Parse(DataFormat::Json),
+    ),
+    (
+        "application/json",
+        FileAction::Format,
+        Builtin::Reformat(DataFormat::Json),
+    ),
+    (
+        "application/yaml",
+        FileAction::Validate,
+        Builtin::Parse(DataFormat::Yaml),
+    ),
+    (
+        "application/toml",
+        FileAction::Validate,
+        Builtin::Parse(DataFormat::Toml),
+    ),
+    (
+        "text/x-protobuf",
+        FileAction::Format,
+        Builtin::Run("buf format -w {file}"),
+    ),
+    (
+        "text/x-protobuf",
+        FileAction::Lint,
+        Builtin::Run("buf lint {file}"),
+    ),
+    (
+        "text/x-rust",
+        FileAction::Format,
+        Builtin::Run("rustfmt --edition 2021 {file}"),
+    ),
+    (
+        "text/x-go",
+        FileAction::Format,
+        Builtin::Run("gofmt -w {file}"),
+    ),
+    ("text/x-go", FileAction::Lint, Builtin::Run("go vet {file}")),
+    (
+        "text/x-python",
+        FileAction::Format,
+        Builtin::Run("black -q {file}"),
+    ),
+    (
+        "text/x-python",
+        FileAction::Lint,
+        Builtin::Run("flake8 {file}"),
+    ),
+    (
+        "application/x-sh",
+        FileAction::Format,
+        Builtin::Run("shfmt -w {file}"),
+    ),
+    (
+        "application/x-sh",
+        FileAction::Lint,
+        Builtin::Run("shellcheck {file}"),
+    ),
+    (
+        "text/javascript",
+        FileAction::Format,
+        Builtin::Run("prettier --write {file}"),
+    ),
+    (
+        "text/javascript",
+        FileAction::Lint,
+        Builtin::Run("eslint {file}"),
+    ),
+    (
+        "application/typescript",
+        FileAction::Format,
+        Builtin::Run("prettier --write {file}"),
+    ),
+    (
+        "application/typescript",
+        FileAction::Lint,
+        Builtin::Run("eslint {file}"),
+    ),
+    (
+        "text/x-c",
+        FileAction::Format,
+        Builtin::Run("clang-format -i {file}"),
+    ),
+    (
+        "text/x-c++",
+        FileAction::Format,
+        Builtin::Run("clang-format -i {file}"),
+    ),
+    (
+        "text/markdown",
+        FileAction::Format,
+        Builtin::Run("prettier --write {file}"),
+    ),
+    (
+        "text/css",
+        FileAction::Format,
+        Builtin::Run("prettier --write {file}"),
+    ),
+    (
+        "text/html",
+        FileAction::Format,
+        Builtin::Run("prettier --write {file}"),
+    ),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Builtin {
+    Parse(DataFormat),
+    Reformat(DataFormat),
+    Run(&'static str),
+}
+
+/// Data formats the utility parses itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl DataFormat {
+    /// Check that `content` parses, returning the parser's message if it does not
+    pub fn check(self, content: &str) -> std::result::Result<(), String> {
+        match self {
+            Self::Json => serde_json::from_str::<serde_json::Value>(content)
+                .map(drop)
+                .map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::from_str::<serde_yaml::Value>(content)
+                .map(drop)
+                .map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str::<toml::Value>(content)
+                .map(drop)
+                .map_err(|e| e.to_string().trim_end().to_string()),
+        }
+    }
+
+    /// `content` in the canonical layout, keys in their original order; JSON only
+    pub fn reformat(self, content: &str) -> std::result::Result<String, String> {
+        match self {
+            Self::Json => {
+                let value: serde_json::Value =
+                    serde_json::from_str(content).map_err(|e| e.to_string())?;
+                let order = KeyOrder::of(content).map_err(|e| e.to_string())?;
+                let mut formatted = serde_json::to_string_pretty(&Ordered::new(&value, &order))
+                    .map_err(|e| e.to_string())?;
+                formatted.push('\n');
+                Ok(formatted)
+            }
+            Self::Yaml | Self::Toml => Err(format!("{} files cannot be reformatted", self)),
+        }
+    }
+}
+
+impl fmt::Display for DataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+        })
+    }
+}
+
+/// What handles one action for a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// Parse the file in-process
+    Parse(DataFormat),
+    /// Rewrite the file in-process in its canonical layout
+    Reformat(DataFormat),
+    /// Run a command, given as its words with the file path filled in
+    Run(Vec<String>),
+}
+
+/// One action of a file's plan and the handler carrying it out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub action: FileAction,
+    pub route: Route,
+    /// Whether the handler comes from `[[process.handlers]]` rather than the built-in table
+    pub configured: bool,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.route {
+            Route::Parse(format) => write!(f, "built-in {} parser", format),
+            Route::Reformat(format) => write!(f, "built-in {} formatter", format),
+            Route::Run(words) => write!(f, "{}", shell_words::join(words)),
+        }
+    }
+}
+
+/// MIME type of a file, from its extension or, for scripts without one, its `#!` line;
+/// `text/plain` or `application/octet-stream` when neither tells
+pub fn mime_type(path: &Path) -> &'static str {
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        let ext = ext.to_ascii_lowercase();
+        if let Some((_, mime)) = EXTENSIONS
+            .iter()
+            .find(|(exts, _)| exts.contains(&ext.as_str()))
+        {
+            return mime;
+        }
+    }
+
+    let mut head = Vec::new();
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(8192).read_to_end(&mut head);
+    }
+    if let Some(interpreter) = interpreter(&head) {
+        if let Some((_, mime)) = INTERPRETERS
+            .iter()
+            .find(|(names, _)| names.contains(&interpreter))
+        {
+            return mime;
+        }
+    }
+    if head.contains(&0) {
+        "application/octet-stream"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Name of the interpreter on a `#!` line, without its directory or version suffix
+fn interpreter(head: &[u8]) -> Option<&str> {
+    let line = head.strip_prefix(b"#!")?.split(|&b| b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    Some(program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.'))
+        .filter(|name| !name.is_empty())
+}
+
+/// The steps handling `actions` for `path`, in the order given: for each action the first
+/// configured handler matching the file, or else the built-in one. Actions nothing handles
+/// are left out.
+pub fn plan(path: &Path, config: &ProcessConfig, actions: &[FileAction]) -> Result<Vec<Step>> {
+    let mime = mime_type(path);
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let file = path.to_string_lossy();
+
+    let mut steps = Vec::new();
+    for &action in actions {
+        let configured = config
+            .handlers
+            .iter()
+            .find(|handler| handler.action == action && matches(handler, ext.as_deref(), mime));
+        if let Some(handler) = configured {
+            steps.push(Step {
+                action,
+                route: Route::Run(command_words(&handler.command, &file)?),
+                configured: true,
+            });
+            continue;
+        }
+        if !config.builtin {
+            continue;
+        }
+        let builtin = BUILTIN.iter().find(|(builtin_mime, builtin_action, _)| {
+            *builtin_mime == mime && *builtin_action == action
+        });
+        if let Some((_, _, builtin)) = builtin {
+            let route = match builtin {
+                Builtin::Parse(format) => Route::Parse(*format),
+                Builtin::Reformat(format) => Route::Reformat(*format),
+                Builtin::Run(command) => Route::Run(command_words(command, &file)?),
+            };
+            steps.push(Step {
+                action,
+                route,
+                configured: false,
+            });
+        }
+    }
+    Ok(steps)
+}
+
+/// Whether a handler applies to a file with the given extension and MIME type
+fn matches(handler: &FileHandler, ext: Option<&str>, mime: &str) -> bool {
+    let by_ext = ext.is_some_and(|ext| {
+        handler
+            .extensions
+            .iter()
+            .any(|wanted| wanted.trim_start_matches('.').eq_ignore_ascii_case(ext))
+    });
+    let by_mime = handler
+        .mime
+        .iter()
+        .any(|wanted| match wanted.strip_suffix("/*") {
+            Some(kind) => mime.split('/').next() == Some(kind),
+            None => wanted.eq_ignore_ascii_case(mime),
+        });
+    by_ext || by_mime
+}
+
+/// Split a handler's command line into words, then put the file path in place of `{file}`,
+/// so paths with spaces stay one argument
+fn command_words(command: &str, file: &str) -> Result<Vec<String>> {
+    let words = shell_words::split(command)
+        .map_err(|e| AgentError::config(format!("Invalid handler command '{}': {}", command, e)))?;
+    if words.is_empty() {
+        return Err(AgentError::config("Handler command is empty"));
+    }
+    Ok(words
+        .into_iter()
+        .map(|word| word.replace("{file}", file))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mime_type_from_extension_and_shebang() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let script = dir.path().join("deploy");
+        std::fs::write(&script, "#!/usr/bin/env -S python3.12 -u\nprint(1)\n").unwrap();
+        let binary = dir.path().join("blob");
+        std::fs::write(&binary, [0x7f, b'E', b'L', b'F', 0, 0]).unwrap();
+
+        assert_eq!(
+            mime_type(Path::new("api/v1/service.PROTO")),
+            "text/x-protobuf"
+        );
+        assert_eq!(mime_type(Path::new(".github/ci.yml")), "application/yaml");
+        assert_eq!(mime_type(&script), "text/x-python");
+        assert_eq!(mime_type(&binary), "application/octet-stream");
+        assert_eq!(mime_type(Path::new("missing")), "text/plain");
+    }
+
+    #[test]
+    fn test_plan_prefers_configured_handlers() {
+        let mut config = ProcessConfig::default();
+        let path = Path::new("my dir/data.json");
+        let steps = plan(path, &config, &FileAction::ALL).unwrap();
+        let routes: Vec<&Route> = steps.iter().map(|step| &step.route).collect();
+        assert_eq!(
+            routes,
+            [
+                &Route::Parse(DataFormat::Json),
+                &Route::Reformat(DataFormat::Json)
+            ]
+        );
+
+        config.handlers.push(FileHandler {
+            extensions: vec![".JSON".to_string()],
+            mime: Vec::new(),
+            action: FileAction::Lint,
+            command: "npx jsonlint --quiet {file}".to_string(),
+        });
+        config.handlers.push(FileHandler {
+            extensions: Vec::new(),
+            mime: vec!["application/*".to_string()],
+            action: FileAction::Format,
+            command: "prettier --write {file}".to_string(),
+        });
+        config.builtin = false;
+        let steps = plan(
+            path,
+            &config,
+            &[FileAction::Lint, FileAction::Format, FileAction::Validate],
+        )
+        .unwrap();
+        assert_eq!(steps.len(), 2);
+        assert!(steps.iter().all(|step| step.configured));
+        assert_eq!(
+            steps[0].route,
+            Route::Run(vec![
+                "npx".into(),
+                "jsonlint".into(),
+                "--quiet".into(),
+                "my dir/data.json".into()
+            ])
+        );
+        assert_eq!(steps[1].to_string(), "prettier --write 'my dir/data.json'");
+
+        assert!(plan(Path::new("notes.txt"), &config, &FileAction::ALL)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_data_formats() {
+        assert_eq!(
+            DataFormat::Json.reformat("{\"b\":1,\"a\":[true]}").unwrap(),
+            "{\n  \"b\": 1,\n  \"a\": [\n    true\n  ]\n}\n"
+        );
+        assert!(DataFormat::Json
+            .check("{\"a\": }")
+            .unwrap_err()
+            .contains("line 1"));
+        assert!(DataFormat::Yaml.check("a: [1, 2\n").is_err());
+        assert!(DataFormat::Toml
+            .check("[tools]\ngit = { min_version = \"2\" }\n")
+            .is_ok());
+    }
+}
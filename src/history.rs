@@ -0,0 +1,708 @@
+// file: src/history.rs
+// version: 1.6.0
+// guid: 0c4f7a2e-9b31-4d85-a6e0-3f18c2d7b954
+
+//! Execution history
+//!
+//! Every command the Executor runs is recorded in a SQLite database, `history/history.db` in
+//! the state directory, with its arguments, working directory, environment variables of its
+//! own, exit code, duration, the session that ran it and the reason and labels given for it
+//! (see [`crate::intent`]), so what an agent did can be audited and replayed afterwards. Output captured for a result is saved next to the database as
+//! `history/<id>.log`: stdout, then stderr after a `--- stderr ---` line. Output of commands
+//! attached to the terminal is not captured, but when such a command fails and is re-run with
+//! verbose flags (see [`crate::verbosity`]) the output of the re-run is saved as
+//! `history/<id>.verbose.log`, after a `$ <command line>` line.
+//!
+//! Credentials are redacted before anything is stored, as in the audit log (see
+//! [`audit::redact_tokens`]): tokens and connection URL passwords in arguments, environment
+//! values and output, verbose re-runs included, and the whole value of variables named like
+//! `*TOKEN*` or `*PASSWORD*`. A run recorded with redactions cannot be replayed as recorded
+//! (see [`Run::redacted`]).
+//!
+//! Runs older than `history.retention_days` are removed, with their output, when a new run is
+//! recorded. Like metrics, history is best effort and never fails the command it records.
+
+use crate::config::HistoryConfig;
+use crate::error::{AgentError, Result};
+use crate::intent;
+use crate::plan;
+use crate::security::audit;
+use crate::session;
+use crate::utils;
+use chrono::{DateTime, Duration, Local, NaiveDate, SecondsFormat, TimeZone, Utc};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Line separating stdout from stderr in a saved output file
+pub const STDERR_SEPARATOR: &str = "--- stderr ---";
+
+/// Time another process may hold the database locked before recording gives up
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    execution_id TEXT NOT NULL,
+    command TEXT NOT NULL,
+    args TEXT NOT NULL,
+    command_line TEXT NOT NULL,
+    cwd TEXT NOT NULL,
+    exit_code INTEGER,
+    duration_ms INTEGER NOT NULL,
+    error TEXT,
+    output_path TEXT
+);
+CREATE INDEX IF NOT EXISTS runs_started_at ON runs (started_at);
+";
+
+/// Changes to [`SCHEMA`], applied in order to databases whose `user_version` is lower than
+/// their position plus one
+const MIGRATIONS: &[&str] = &[
+    "ALTER TABLE runs ADD COLUMN env TEXT",
+    "ALTER TABLE runs ADD COLUMN reason TEXT; ALTER TABLE runs ADD COLUMN labels TEXT",
+    "ALTER TABLE runs ADD COLUMN verbose_output_path TEXT",
+];
+
+/// A recorded run of a command
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Run {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub session_id: String,
+    /// Invocation of this utility that ran the command
+    pub execution_id: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    /// Environment variables set for this command, on top of the executor's environment
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// `None` if the command was killed by a signal, timed out or did not start
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    /// Why the command did not finish, e.g. a timeout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// File with the captured output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_path: Option<PathBuf>,
+    /// File with the output of the verbose re-run of the failed command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbose_output_path: Option<PathBuf>,
+    /// Why the command was run, from `--reason`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Labels given with `--label`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+}
+
+impl Run {
+    /// Whether the command did not exit successfully
+    pub fn failed(&self) -> bool {
+        self.exit_code != Some(0)
+    }
+
+    /// The command and its arguments, quoted for a shell
+    pub fn command_line(&self) -> String {
+        command_line(&self.command, &self.args)
+    }
+
+    /// Whether credentials were redacted from the recorded arguments or environment
+    pub fn redacted(&self) -> bool {
+        self.args
+            .iter()
+            .chain(self.env.values())
+            .any(|value| value.contains(audit::REDACTED))
+    }
+}
+
+/// A finished command to record
+#[derive(Debug, Clone)]
+pub struct Completed<'a> {
+    pub command: &'a str,
+    pub args: &'a [String],
+    pub cwd: &'a Path,
+    pub env: &'a BTreeMap<String, String>,
+    pub started_at: DateTime<Utc>,
+    pub duration: std::time::Duration,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    /// Captured stdout and stderr, or `None` if the command wrote to the terminal
+    pub output: Option<(&'a [u8], &'a [u8])>,
+}
+
+/// Which runs to list; the newest come first
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Only runs started at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only runs that did not exit successfully
+    pub failed_only: bool,
+    /// Only runs whose command line or working directory contains this text
+    pub text: Option<String>,
+    /// Only runs of this session
+    pub session_id: Option<String>,
+    /// At most this many runs; 0 for all
+    pub limit: usize,
+}
+
+/// The history database
+pub struct History {
+    conn: Connection,
+    dir: PathBuf,
+}
+
+impl History {
+    /// Directory of the database and saved output
+    pub fn dir() -> PathBuf {
+        utils::state_dir().join("history")
+    }
+
+    /// Open the database in the state directory, creating it if needed
+    pub fn open() -> Result<Self> {
+        Self::open_in(&Self::dir())
+    }
+
+    /// Open the database in `dir`, creating it if needed
+    pub fn open_in(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let conn = Connection::open(dir.join("history.db"))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(SCHEMA)?;
+        let version = |conn: &Connection| -> rusqlite::Result<usize> {
+            conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        };
+        if version(&conn)? < MIGRATIONS.len() {
+            // Another process may be migrating the same database
+            conn.execute_batch("BEGIN IMMEDIATE")?;
+            for migration in &MIGRATIONS[version(&conn)?..] {
+                conn.execute_batch(migration)?;
+            }
+            conn.execute_batch(&format!(
+                "PRAGMA user_version = {}; COMMIT",
+                MIGRATIONS.len()
+            ))?;
+        }
+        Ok(Self {
+            conn,
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Record a run, saving its output if it has any; returns its ID
+    pub fn record(&self, run: &Completed) -> Result<i64> {
+        let args = audit::redact_args(run.args);
+        let env = audit::redact_env(run.env);
+        self.conn.execute(
+            "INSERT INTO runs (started_at, session_id, execution_id, command, args, command_line, \
+             cwd, env, exit_code, duration_ms, error, reason, labels) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                timestamp(run.started_at),
+                session::current().session_id(),
+                session::current().execution_id(),
+                run.command,
+                serde_json::to_string(&args)?,
+                command_line(run.command, &args),
+                run.cwd.display().to_string(),
+                serde_json::to_string(&env)?,
+                run.exit_code,
+                run.duration.as_millis() as i64,
+                run.error,
+                intent::current().reason,
+                serde_json::to_string(&intent::current().labels)?,
+            ],
+        )?;
+        let id = self.conn.last_insert_rowid();
+
+        if let Some((stdout, stderr)) = run
+            .output
+            .filter(|(out, err)| !out.is_empty() || !err.is_empty())
+        {
+            let path = self.dir.join(format!("{}.log", id));
+            let (stdout, stderr) = (audit::redact_output(stdout), audit::redact_output(stderr));
+            fs::write(&path, output_content(Vec::new(), &stdout, &stderr))?;
+            self.conn.execute(
+                "UPDATE runs SET output_path = ?1 WHERE id = ?2",
+                params![path.display().to_string(), id],
+            )?;
+        }
+        Ok(id)
+    }
+
+    /// Save the output of the verbose re-run of run `id`, run as `command` with `args`;
+    /// returns the file it was saved in
+    pub fn attach_verbose_output(
+        &self,
+        id: i64,
+        command: &str,
+        args: &[String],
+        stdout: &[u8],
+        stderr: &[u8],
+    ) -> Result<PathBuf> {
+        let path = self.dir.join(format!("{}.verbose.log", id));
+        let header = format!("$ {}\n", command_line(command, &audit::redact_args(args)));
+        let (stdout, stderr) = (audit::redact_output(stdout), audit::redact_output(stderr));
+        fs::write(&path, output_content(header.into_bytes(), &stdout, &stderr))?;
+        let updated = self.conn.execute(
+            "UPDATE runs SET verbose_output_path = ?1 WHERE id = ?2",
+            params![path.display().to_string(), id],
+        )?;
+        if updated == 0 {
+            let _ = fs::remove_file(&path);
+            return Err(AgentError::validation(format!("No run {} in history", id)));
+        }
+        Ok(path)
+    }
+
+    /// Runs matching `filter`, newest first
+    pub fn list(&self, filter: &Filter) -> Result<Vec<Run>> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(since) = filter.since {
+            conditions.push("started_at >= ?");
+            values.push(timestamp(since));
+        }
+        if filter.failed_only {
+            conditions.push("(exit_code IS NULL OR exit_code != 0)");
+        }
+        if let Some(text) = &filter.text {
+            conditions.push("(instr(command_line, ?) > 0 OR instr(cwd, ?) > 0)");
+            values.push(text.clone());
+            values.push(text.clone());
+        }
+        if let Some(session_id) = &filter.session_id {
+            conditions.push("session_id = ?");
+            values.push(session_id.clone());
+        }
+
+        let mut sql = "SELECT * FROM runs".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY id DESC");
+        if filter.limit > 0 {
+            sql.push_str(&format!(" LIMIT {}", filter.limit));
+        }
+
+        let mut statement = self.conn.prepare(&sql)?;
+        let runs = statement
+            .query_map(params_from_iter(values), run_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(runs)
+    }
+
+    /// The run with `id`
+    pub fn get(&self, id: i64) -> Result<Option<Run>> {
+        Ok(self
+            .conn
+            .query_row("SELECT * FROM runs WHERE id = ?1", [id], run_from_row)
+            .optional()?)
+    }
+
+    /// Remove runs started before `cutoff` and their output; returns how many were removed
+    pub fn prune(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let cutoff = timestamp(cutoff);
+        let mut statement = self.conn.prepare(
+            "SELECT output_path, verbose_output_path FROM runs WHERE started_at < ?1",
+        )?;
+        let paths: Vec<(Option<String>, Option<String>)> = statement
+            .query_map([&cutoff], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        for path in paths.into_iter().flat_map(|(output, verbose)| [output, verbose]).flatten() {
+            let _ = fs::remove_file(path);
+        }
+        Ok(self
+            .conn
+            .execute("DELETE FROM runs WHERE started_at < ?1", [&cutoff])?)
+    }
+}
+
+/// Record a finished command in the history database, removing runs past their retention;
+/// returns the ID of the run
+///
+/// History is best effort: failures to open or write the database are logged and never
+/// affect the command that was executed.
+pub fn record_execution(config: &HistoryConfig, run: &Completed) -> Option<i64> {
+    if !config.enabled {
+        return None;
+    }
+    let result = History::open().and_then(|history| {
+        let id = history.record(run)?;
+        if config.retention_days > 0 {
+            history.prune(Utc::now() - Duration::days(i64::from(config.retention_days)))?;
+        }
+        Ok(id)
+    });
+    match result {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("Failed to record history: {}", e);
+            None
+        }
+    }
+}
+
+/// Attach the output of a verbose re-run to recorded run `id`, best effort like
+/// [`record_execution`]; returns the file it was saved in
+pub fn record_verbose_output(
+    id: i64,
+    command: &str,
+    args: &[String],
+    stdout: &[u8],
+    stderr: &[u8],
+) -> Option<PathBuf> {
+    match History::open()
+        .and_then(|history| history.attach_verbose_output(id, command, args, stdout, stderr))
+    {
+        Ok(path) => Some(path),
+        Err(e) => {
+            warn!("Failed to record verbose output: {}", e);
+            None
+        }
+    }
+}
+
+/// Start of the period given to `--since`: an age such as `30m`, `12h`, `2d` or `1w`, a date
+/// (`2024-05-01`, from local midnight), `today`, `yesterday`, or an RFC 3339 time
+pub fn parse_since(text: &str, now: DateTime<Local>) -> Result<DateTime<Utc>> {
+    let text = text.trim();
+    let midnight = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+    };
+    let since = match text {
+        "today" => midnight(now.date_naive()),
+        "yesterday" => now.date_naive().pred_opt().and_then(midnight),
+        _ => {
+            let age = text
+                .char_indices()
+                .last()
+                .and_then(|(i, unit)| Some((text[..i].parse::<i64>().ok()?, unit)))
+                .and_then(|(n, unit)| match unit {
+                    's' => Some(Duration::seconds(n)),
+                    'm' => Some(Duration::minutes(n)),
+                    'h' => Some(Duration::hours(n)),
+                    'd' => Some(Duration::days(n)),
+                    'w' => Some(Duration::weeks(n)),
+                    _ => None,
+                });
+            match age {
+                Some(age) => Some(now.with_timezone(&Utc) - age),
+                None => DateTime::parse_from_rfc3339(text)
+                    .map(|time| time.with_timezone(&Utc))
+                    .ok()
+                    .or_else(|| {
+                        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                            .ok()
+                            .and_then(midnight)
+                    }),
+            }
+        }
+    };
+    since.ok_or_else(|| {
+        AgentError::invalid_argument(format!(
+            "Invalid time '{}': use an age like 2h or 3d, a date like 2024-05-01, today or yesterday",
+            text
+        ))
+    })
+}
+
+/// `850ms`, `12.4s`, `3m05s` or `2h10m`
+pub fn format_duration(ms: u64) -> String {
+    let seconds = ms / 1000;
+    match ms {
+        0..=999 => format!("{}ms", ms),
+        1000..=59_999 => format!("{:.1}s", ms as f64 / 1000.0),
+        60_000..=3_599_999 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+fn run_from_row(row: &Row) -> rusqlite::Result<Run> {
+    let started_at: String = row.get("started_at")?;
+    let args: String = row.get("args")?;
+    let cwd: String = row.get("cwd")?;
+    let env: Option<String> = row.get("env")?;
+    let output_path: Option<String> = row.get("output_path")?;
+    let verbose_output_path: Option<String> = row.get("verbose_output_path")?;
+    let labels: Option<String> = row.get("labels")?;
+    Ok(Run {
+        id: row.get("id")?,
+        started_at: DateTime::parse_from_rfc3339(&started_at)
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_default(),
+        session_id: row.get("session_id")?,
+        execution_id: row.get("execution_id")?,
+        command: row.get("command")?,
+        args: serde_json::from_str(&args).unwrap_or_default(),
+        cwd: PathBuf::from(cwd),
+        env: env
+            .and_then(|env| serde_json::from_str(&env).ok())
+            .unwrap_or_default(),
+        exit_code: row.get("exit_code")?,
+        duration_ms: row.get::<_, i64>("duration_ms")? as u64,
+        error: row.get("error")?,
+        output_path: output_path.map(PathBuf::from),
+        verbose_output_path: verbose_output_path.map(PathBuf::from),
+        reason: row.get("reason")?,
+        labels: labels
+            .and_then(|labels| serde_json::from_str(&labels).ok())
+            .unwrap_or_default(),
+    })
+}
+
+/// Times are stored in UTC with a fixed width, so they compare as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Saved output: `content`, then stdout, then stderr after [`STDERR_SEPARATOR`]
+fn output_content(mut content: Vec<u8>, stdout: &[u8], stderr: &[u8]) -> Vec<u8> {
+    content.extend_from_slice(stdout);
+    if !stderr.is_empty() {
+        if !content.is_empty() && !content.ends_with(b"\n") {
+            content.push(b'\n');
+        }
+        content.extend_from_slice(STDERR_SEPARATOR.as_bytes());
+        content.push(b'\n');
+        content.extend_from_slice(stderr);
+    }
+    content
+}
+
+fn command_line(command: &str, args: &[String]) -> String {
+    std::iter::once(command)
+        .chain(args.iter().map(String::as_str))
+        .map(plan::shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static ENV: BTreeMap<String, String> = BTreeMap::new();
+
+    fn completed<'a>(
+        command: &'a str,
+        args: &'a [String],
+        exit_code: Option<i32>,
+    ) -> Completed<'a> {
+        Completed {
+            command,
+            args,
+            cwd: Path::new("/work/project"),
+            env: &ENV,
+            started_at: Utc::now(),
+            duration: std::time::Duration::from_millis(1500),
+            exit_code,
+            error: None,
+            output: None,
+        }
+    }
+
+    #[test]
+    fn test_record_list_and_search() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let history = History::open_in(dir.path()).unwrap();
+        let status = vec!["status".to_string()];
+        let push = vec!["push".to_string(), "origin main".to_string()];
+
+        let first = history.record(&completed("git", &status, Some(0))).unwrap();
+        let mut failed = completed("git", &push, Some(1));
+        failed.output = Some((b"", b"rejected\n"));
+        let second = history.record(&failed).unwrap();
+
+        let runs = history.list(&Filter::default()).unwrap();
+        assert_eq!(
+            runs.iter().map(|run| run.id).collect::<Vec<_>>(),
+            vec![second, first]
+        );
+        assert_eq!(runs[0].command_line(), "git push 'origin main'");
+        assert_eq!(runs[0].duration_ms, 1500);
+        assert_eq!(runs[1].output_path, None);
+        let output = fs::read_to_string(runs[0].output_path.as_ref().unwrap()).unwrap();
+        assert_eq!(output, "--- stderr ---\nrejected\n");
+
+        let failed_only = Filter {
+            failed_only: true,
+            ..Filter::default()
+        };
+        assert_eq!(history.list(&failed_only).unwrap().len(), 1);
+        let search = Filter {
+            text: Some("git push".to_string()),
+            ..Filter::default()
+        };
+        assert_eq!(history.list(&search).unwrap()[0].id, second);
+        assert_eq!(history.get(first).unwrap().unwrap().args, status);
+        assert_eq!(history.get(42).unwrap(), None);
+    }
+
+    #[test]
+    fn test_credentials_are_not_stored() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let history = History::open_in(dir.path()).unwrap();
+        let args = vec![
+            "postgres://u:secret@h/db".to_string(),
+            "-c".to_string(),
+            "select 1".to_string(),
+        ];
+        let env = BTreeMap::from([("PGPASSWORD".to_string(), "secret".to_string())]);
+        let mut run = completed("psql", &args, Some(1));
+        run.env = &env;
+        run.output = Some((b"", b"connection to postgres://u:secret@h/db failed\n"));
+        let id = history.record(&run).unwrap();
+
+        let stored = history.get(id).unwrap().unwrap();
+        assert_eq!(stored.args[0], "postgres://u:[REDACTED]@h/db");
+        assert_eq!(stored.env["PGPASSWORD"], "[REDACTED]");
+        let database = fs::read(dir.path().join("history.db")).unwrap();
+        assert!(!String::from_utf8_lossy(&database).contains("secret"));
+        let output = fs::read_to_string(stored.output_path.unwrap()).unwrap();
+        assert!(!output.contains("secret"));
+    }
+
+    #[test]
+    fn test_verbose_output_and_replays_are_redacted() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let history = History::open_in(dir.path()).unwrap();
+        let token = format!("ghp_{}", "a".repeat(36));
+        let args = vec![
+            "api".to_string(),
+            "-H".to_string(),
+            format!("Authorization: Bearer {}", token),
+        ];
+        let id = history.record(&completed("gh", &args, Some(1))).unwrap();
+        assert!(history.get(id).unwrap().unwrap().redacted());
+
+        let mut verbose = args.clone();
+        verbose.push("--verbose".to_string());
+        let stderr = format!("* GH_TOKEN={}\n", token);
+        let path = history
+            .attach_verbose_output(id, "gh", &verbose, b"", stderr.as_bytes())
+            .unwrap();
+        let log = fs::read_to_string(path).unwrap();
+        assert!(log.contains("Authorization: Bearer [REDACTED]"));
+        assert!(!log.contains(&token));
+
+        let plain = vec!["status".to_string()];
+        let id = history.record(&completed("git", &plain, Some(0))).unwrap();
+        assert!(!history.get(id).unwrap().unwrap().redacted());
+    }
+
+    #[test]
+    fn test_env_and_migration() {
+        let dir = tempfile::TempDir::new().unwrap();
+        // A database created before the env column existed
+        let conn = Connection::open(dir.path().join("history.db")).unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute(
+            "INSERT INTO runs (started_at, session_id, execution_id, command, args, \
+             command_line, cwd, duration_ms) VALUES ('2024-05-01T12:00:00.000Z', 's', 'e', \
+             'ls', '[]', 'ls', '/work', 5)",
+            [],
+        )
+        .unwrap();
+        drop(conn);
+
+        let history = History::open_in(dir.path()).unwrap();
+        assert!(history.get(1).unwrap().unwrap().env.is_empty());
+        let env = BTreeMap::from([("RUST_LOG".to_string(), "debug".to_string())]);
+        let args = vec!["test".to_string()];
+        let mut run = completed("cargo", &args, Some(0));
+        run.env = &env;
+        let id = history.record(&run).unwrap();
+        assert_eq!(history.get(id).unwrap().unwrap().env, env);
+
+        // Opening again leaves the migrated database alone
+        drop(history);
+        assert_eq!(
+            History::open_in(dir.path())
+                .unwrap()
+                .get(id)
+                .unwrap()
+                .unwrap()
+                .env,
+            env
+        );
+    }
+
+    #[test]
+    fn test_prune_removes_output() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let history = History::open_in(dir.path()).unwrap();
+        let args = vec!["test".to_string()];
+        let mut old = completed("cargo", &args, Some(0));
+        old.started_at = Utc::now() - Duration::days(40);
+        old.output = Some((b"ok\n", b""));
+        history.record(&old).unwrap();
+        history.record(&completed("cargo", &args, Some(0))).unwrap();
+
+        assert_eq!(history.prune(Utc::now() - Duration::days(30)).unwrap(), 1);
+        assert_eq!(history.list(&Filter::default()).unwrap().len(), 1);
+        assert!(!dir.path().join("1.log").exists());
+    }
+
+    #[test]
+    fn test_attach_verbose_output() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let history = History::open_in(dir.path()).unwrap();
+        let args = vec!["build".to_string()];
+        let mut failed = completed("buf", &args, Some(1));
+        failed.started_at = Utc::now() - Duration::days(40);
+        let id = history.record(&failed).unwrap();
+
+        let verbose = vec!["build".to_string(), "--debug".to_string()];
+        let path = history
+            .attach_verbose_output(id, "buf", &verbose, b"", b"DEBUG loading\n")
+            .unwrap();
+        assert_eq!(
+            history.get(id).unwrap().unwrap().verbose_output_path,
+            Some(path.clone())
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "$ buf build --debug\n--- stderr ---\nDEBUG loading\n"
+        );
+        assert!(history
+            .attach_verbose_output(42, "buf", &verbose, b"", b"")
+            .is_err());
+
+        history.prune(Utc::now() - Duration::days(30)).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = Local.with_ymd_and_hms(2024, 5, 10, 15, 30, 0).unwrap();
+        assert_eq!(
+            parse_since("2h", now).unwrap(),
+            now.with_timezone(&Utc) - Duration::hours(2)
+        );
+        assert_eq!(
+            parse_since("yesterday", now).unwrap(),
+            Local.with_ymd_and_hms(2024, 5, 9, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_since("2024-05-01", now).unwrap(),
+            Local.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_since("2024-05-01T12:00:00Z", now).unwrap(),
+            Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+        );
+        assert!(parse_since("last week", now).is_err());
+    }
+}
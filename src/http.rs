@@ -0,0 +1,687 @@
+// file: src/http.rs
+// version: 1.0.0
+// guid: a3c7e1f4-9b26-4d58-8e0a-6f2d4b9c7e15
+
+//! Local REST API (`serve http`)
+//!
+//! For editor extensions that would rather talk HTTP than spawn a process per command. Like
+//! `--ide` (see [`crate::ide`]), every run is a separate invocation of this binary with the
+//! server's global options, so commands keep their own validation, audit logging and
+//! output handling.
+//!
+//! Endpoints, all under `/v1` and all requiring `Authorization: Bearer <token>`:
+//!
+//! - `GET /v1/capabilities`: version and the commands `runs` accepts
+//! - `POST /v1/runs` `{"args": ["git", "status"], "cwd"?, "dry_run"?, "simulate"?}` starts a
+//!   command line and answers `202` with its `id`
+//! - `GET /v1/runs/{id}`: whether it is still running, its exit code and its output
+//! - `GET /v1/runs/{id}/events`: server-sent events, `output` for every line written
+//!   (replayed from the start) and a final `exit`
+//! - `DELETE /v1/runs/{id}`: stop the command and the processes it started
+//! - `GET /v1/history?limit=&failed=&text=`: recorded runs, newest first (see
+//!   [`crate::history`])
+//!
+//! The server only listens on loopback addresses. A new random token is written to a file
+//! readable only by the user (`<state dir>/http/token` by default) each time it starts, so
+//! only processes of that user can call it. Started by systemd socket activation
+//! (`LISTEN_FDS`), it serves the socket it was given instead of binding one.
+
+use crate::error::{AgentError, Result};
+use crate::executor::Executor;
+use crate::history::{Filter, History};
+use crate::ide::{self, CommandInfo};
+use crate::{process, utils};
+use axum::extract::{Path as UrlPath, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+/// Address listened on when neither `--bind` nor socket activation gives one
+pub const DEFAULT_BIND: &str = "127.0.0.1:8787";
+
+/// Finished runs kept for `GET /v1/runs/{id}`; older ones are forgotten
+pub const KEEP_FINISHED: usize = 100;
+
+/// Output kept per run for replay; later lines are still streamed
+const MAX_KEPT_OUTPUT: usize = 1024 * 1024;
+
+/// Runs returned by `GET /v1/history` without a limit
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+/// Options for [`serve`]
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Address to listen on; `None` uses the socket from systemd, or [`DEFAULT_BIND`]
+    pub bind: Option<SocketAddr>,
+    /// File receiving the bearer token
+    pub token_file: PathBuf,
+    /// Commands `POST /v1/runs` accepts
+    pub commands: Vec<CommandInfo>,
+    /// Global options every command is run with
+    pub forward_args: Vec<String>,
+}
+
+/// Default token file, `<state dir>/http/token`
+pub fn default_token_file() -> PathBuf {
+    utils::state_dir().join("http").join("token")
+}
+
+/// Serve the REST API until the process is stopped
+///
+/// The URL listened on is printed to stdout as `listening on http://<address>` once the
+/// token file is written.
+pub async fn serve(executor: Executor, options: Options) -> Result<()> {
+    let listener = match (options.bind, activated_listener()?) {
+        (None, Some(listener)) => {
+            info!("Serving the socket passed by systemd");
+            listener
+        }
+        (bind, _) => {
+            let bind = bind.unwrap_or_else(|| DEFAULT_BIND.parse().unwrap());
+            std::net::TcpListener::bind(bind)
+                .map_err(|e| AgentError::network(format!("Cannot listen on {}: {}", bind, e)))?
+        }
+    };
+    let address = listener.local_addr()?;
+    if !address.ip().is_loopback() {
+        return Err(AgentError::security(format!(
+            "serve http only listens on loopback addresses, not {}",
+            address
+        )));
+    }
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+
+    let token = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    write_token(&options.token_file, &token)?;
+
+    let mut commands = options.commands;
+    commands.retain(|command| command.name != "serve");
+    let state = Arc::new(Server {
+        executor,
+        exe: std::env::current_exe()?,
+        forward_args: options.forward_args,
+        commands,
+        token,
+        runs: Mutex::new(Registry::default()),
+    });
+    let app = Router::new()
+        .route("/v1/capabilities", get(capabilities))
+        .route("/v1/runs", post(start_run))
+        .route("/v1/runs/{id}", get(run_status).delete(cancel_run))
+        .route("/v1/runs/{id}/events", get(run_events))
+        .route("/v1/history", get(history))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state);
+
+    println!("listening on http://{}", address);
+    info!(
+        "REST API listening on http://{} (token in {})",
+        address,
+        options.token_file.display()
+    );
+    axum::serve(listener, app)
+        .await
+        .map_err(|e| AgentError::network(format!("HTTP server failed: {}", e)))
+}
+
+/// The listening socket passed by systemd socket activation, if any
+#[cfg(unix)]
+fn activated_listener() -> Result<Option<std::net::TcpListener>> {
+    use std::os::unix::io::FromRawFd;
+
+    // The first passed descriptor, after stdin, stdout and stderr
+    const FIRST_FD: i32 = 3;
+    let for_us = std::env::var("LISTEN_PID").ok() == Some(std::process::id().to_string());
+    let count: u32 = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0);
+    if !for_us || count == 0 {
+        return Ok(None);
+    }
+    // Commands started by the server must not take the socket for their own
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    if count > 1 {
+        warn!("systemd passed {} sockets; serving the first", count);
+    }
+    // SAFETY: systemd passes the descriptors starting at 3 and they belong to this process
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(FIRST_FD) };
+    listener.local_addr().map_err(|e| {
+        AgentError::config(format!(
+            "The socket passed by systemd is not a TCP socket: {}",
+            e
+        ))
+    })?;
+    Ok(Some(listener))
+}
+
+#[cfg(not(unix))]
+fn activated_listener() -> Result<Option<std::net::TcpListener>> {
+    Ok(None)
+}
+
+/// Write the token to a file only its owner can read
+fn write_token(path: &Path, token: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // An existing file keeps its mode when opened
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    std::io::Write::write_all(&mut options.open(path)?, token.as_bytes())?;
+    Ok(())
+}
+
+struct Server {
+    executor: Executor,
+    exe: PathBuf,
+    forward_args: Vec<String>,
+    commands: Vec<CommandInfo>,
+    token: String,
+    runs: Mutex<Registry>,
+}
+
+/// Something that happened to a run, as sent to `events` subscribers
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    /// A line the command wrote, with its line ending
+    Output { stream: &'static str, data: String },
+    Exit {
+        /// `None` if the command was killed, cancelled or did not start
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Output { .. } => "output",
+            Self::Exit { .. } => "exit",
+        }
+    }
+}
+
+/// A started command line and what it produced so far
+struct Run {
+    args: Vec<String>,
+    cwd: PathBuf,
+    events: Vec<Event>,
+    kept_bytes: usize,
+    truncated: bool,
+    subscribers: Vec<UnboundedSender<Event>>,
+    finished: bool,
+    cancel: Option<oneshot::Sender<()>>,
+}
+
+impl Run {
+    /// Record an event and send it to every subscriber; the exit event ends their streams
+    fn publish(&mut self, event: Event) {
+        self.subscribers
+            .retain(|subscriber| subscriber.unbounded_send(event.clone()).is_ok());
+        match &event {
+            Event::Output { data, .. } if self.kept_bytes + data.len() > MAX_KEPT_OUTPUT => {
+                self.truncated = true;
+                return;
+            }
+            Event::Output { data, .. } => self.kept_bytes += data.len(),
+            Event::Exit { .. } => {
+                self.finished = true;
+                self.subscribers.clear();
+                self.cancel = None;
+            }
+        }
+        self.events.push(event);
+    }
+
+    /// Stream of the kept events followed by the ones still to come
+    fn subscribe(&mut self) -> UnboundedReceiver<Event> {
+        let (sender, receiver) = unbounded();
+        for event in &self.events {
+            let _ = sender.unbounded_send(event.clone());
+        }
+        if !self.finished {
+            self.subscribers.push(sender);
+        }
+        receiver
+    }
+
+    fn to_json(&self, id: &str) -> Value {
+        let mut output = HashMap::new();
+        let mut exit = json!(null);
+        for event in &self.events {
+            match event {
+                Event::Output { stream, data } => output
+                    .entry(*stream)
+                    .or_insert_with(String::new)
+                    .push_str(data),
+                Event::Exit { .. } => exit = json!(event),
+            }
+        }
+        json!({
+            "id": id,
+            "args": self.args,
+            "cwd": self.cwd,
+            "running": !self.finished,
+            "stdout": output.remove("stdout").unwrap_or_default(),
+            "stderr": output.remove("stderr").unwrap_or_default(),
+            "truncated": self.truncated,
+            "exit": exit,
+        })
+    }
+}
+
+/// Runs by id, in the order they were started
+#[derive(Default)]
+struct Registry {
+    runs: HashMap<String, Run>,
+    order: VecDeque<String>,
+}
+
+impl Registry {
+    /// Add a run, forgetting the oldest finished ones past [`KEEP_FINISHED`]
+    fn insert(&mut self, id: String, run: Run) {
+        let finished: Vec<String> = self
+            .order
+            .iter()
+            .filter(|id| self.runs[*id].finished)
+            .cloned()
+            .collect();
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(KEEP_FINISHED))
+        {
+            self.runs.remove(id);
+            self.order.retain(|other| other != id);
+        }
+        self.order.push_back(id.clone());
+        self.runs.insert(id, run);
+    }
+
+    fn get_mut(&mut self, id: &str) -> std::result::Result<&mut Run, ApiError> {
+        self.runs
+            .get_mut(id)
+            .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No run '{}'", id)))
+    }
+}
+
+/// An error answered as `{"error": message}`
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({"error": self.1}))).into_response()
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Reject requests without the bearer token
+async fn authorize(State(server): State<Arc<Server>>, request: Request, next: Next) -> Response {
+    let expected = format!("Bearer {}", server.token);
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|value| value.as_bytes())
+        .unwrap_or_default();
+    if utils::constant_time_eq(given, expected.as_bytes()) {
+        next.run(request).await
+    } else {
+        ApiError(
+            StatusCode::UNAUTHORIZED,
+            "missing or wrong bearer token".to_string(),
+        )
+        .into_response()
+    }
+}
+
+async fn capabilities(State(server): State<Arc<Server>>) -> Json<Value> {
+    let config = server.executor.config();
+    Json(json!({
+        "version": crate::VERSION,
+        "commands": server.commands,
+        "dry_run": config.safety.dry_run,
+        "simulate": config.safety.simulate,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct RunRequest {
+    args: Vec<String>,
+    #[serde(default)]
+    cwd: Option<PathBuf>,
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    simulate: bool,
+}
+
+async fn start_run(
+    State(server): State<Arc<Server>>,
+    Json(request): Json<RunRequest>,
+) -> ApiResult<(StatusCode, Json<Value>)> {
+    let bad_request = |message: String| ApiError(StatusCode::BAD_REQUEST, message);
+    ide::check_run_args(&request.args, &server.commands).map_err(bad_request)?;
+    let cwd = match request.cwd {
+        Some(cwd) => cwd,
+        None => std::env::current_dir().map_err(|e| bad_request(e.to_string()))?,
+    };
+    let cwd = server
+        .executor
+        .check_path(&cwd)
+        .map_err(|e| bad_request(e.to_string()))?;
+
+    let mut command = ide::child_command(
+        &server.exe,
+        &server.forward_args,
+        &request.args,
+        &cwd,
+        &server.executor,
+    );
+    if request.dry_run {
+        command.env(
+            format!("{}SAFETY__DRY_RUN", crate::config::ENV_PREFIX),
+            "true",
+        );
+    }
+    if request.simulate {
+        command.env(
+            format!("{}SAFETY__SIMULATE", crate::config::ENV_PREFIX),
+            "true",
+        );
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let (cancel, cancelled) = oneshot::channel();
+    debug!(
+        "Run {}: {} in {}",
+        id,
+        request.args.join(" "),
+        cwd.display()
+    );
+    let run = Run {
+        args: request.args,
+        cwd,
+        events: Vec::new(),
+        kept_bytes: 0,
+        truncated: false,
+        subscribers: Vec::new(),
+        finished: false,
+        cancel: Some(cancel),
+    };
+    server.runs.lock().unwrap().insert(id.clone(), run);
+    tokio::spawn(run_command(server.clone(), id.clone(), command, cancelled));
+
+    let events = format!("/v1/runs/{}/events", id);
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({"id": id, "events": events})),
+    ))
+}
+
+async fn run_status(
+    State(server): State<Arc<Server>>,
+    UrlPath(id): UrlPath<String>,
+) -> ApiResult<Json<Value>> {
+    let mut runs = server.runs.lock().unwrap();
+    Ok(Json(runs.get_mut(&id)?.to_json(&id)))
+}
+
+async fn run_events(
+    State(server): State<Arc<Server>>,
+    UrlPath(id): UrlPath<String>,
+) -> ApiResult<Sse<impl Stream<Item = std::result::Result<SseEvent, Infallible>>>> {
+    let events = server.runs.lock().unwrap().get_mut(&id)?.subscribe();
+    let events = events.map(|event| {
+        Ok(SseEvent::default()
+            .event(event.name())
+            .json_data(&event)
+            .unwrap_or_default())
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+async fn cancel_run(
+    State(server): State<Arc<Server>>,
+    UrlPath(id): UrlPath<String>,
+) -> ApiResult<Json<Value>> {
+    let cancel = server.runs.lock().unwrap().get_mut(&id)?.cancel.take();
+    let cancelled = cancel.is_some_and(|cancel| cancel.send(()).is_ok());
+    if cancelled {
+        info!("Cancelling run {}", id);
+    }
+    Ok(Json(json!({"cancelled": cancelled})))
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    failed: bool,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+async fn history(Query(query): Query<HistoryQuery>) -> ApiResult<Json<Value>> {
+    let filter = Filter {
+        failed_only: query.failed,
+        text: query.text.filter(|text| !text.is_empty()),
+        limit: query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+        ..Default::default()
+    };
+    let internal = |message: String| ApiError(StatusCode::INTERNAL_SERVER_ERROR, message);
+    let runs = tokio::task::spawn_blocking(move || History::open()?.list(&filter))
+        .await
+        .map_err(|e| internal(e.to_string()))?
+        .map_err(|e| internal(e.to_string()))?;
+    Ok(Json(json!({"runs": runs})))
+}
+
+/// Run one command line, publishing its output lines and then its exit
+async fn run_command(
+    server: Arc<Server>,
+    id: String,
+    mut command: Command,
+    mut cancelled: oneshot::Receiver<()>,
+) {
+    let publish = |event| {
+        if let Ok(run) = server.runs.lock().unwrap().get_mut(&id) {
+            run.publish(event);
+        }
+    };
+    let started = Instant::now();
+    let _terminal = process::isolate(&mut command, false);
+    let outcome = match command.spawn() {
+        Err(e) => Err(format!("Failed to start: {}", e)),
+        Ok(mut child) => {
+            let (sender, mut lines) = mpsc::unbounded_channel();
+            let stdout = child
+                .stdout
+                .take()
+                .map(|s| tokio::spawn(read_lines(s, "stdout", sender.clone())));
+            let stderr = child
+                .stderr
+                .take()
+                .map(|s| tokio::spawn(read_lines(s, "stderr", sender.clone())));
+            drop(sender);
+
+            let outcome = loop {
+                tokio::select! {
+                    Some(event) = lines.recv() => publish(event),
+                    status = child.wait() => break status.map_err(|e| e.to_string()),
+                    Ok(()) = &mut cancelled => {
+                        // The command forwards SIGTERM to the processes it started
+                        process::terminate(&mut child).await;
+                        break Err("Run cancelled".to_string());
+                    }
+                }
+            };
+            if outcome.is_ok() {
+                for task in stdout.into_iter().chain(stderr) {
+                    let _ = task.await;
+                }
+            } else {
+                // Processes the command started may still hold its output open
+                for task in stdout.iter().chain(stderr.iter()) {
+                    task.abort();
+                }
+            }
+            while let Ok(event) = lines.try_recv() {
+                publish(event);
+            }
+            outcome
+        }
+    };
+
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let exit = match outcome {
+        Ok(status) => Event::Exit {
+            exit_code: status.code(),
+            duration_ms,
+            error: None,
+        },
+        Err(error) => Event::Exit {
+            exit_code: None,
+            duration_ms,
+            error: Some(error),
+        },
+    };
+    publish(exit);
+}
+
+/// Send each line of a command's output as an `output` event
+async fn read_lines<R: AsyncRead + Unpin>(
+    stream: R,
+    name: &'static str,
+    events: mpsc::UnboundedSender<Event>,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let data = String::from_utf8_lossy(&line).into_owned();
+        let _ = events.send(Event::Output { stream: name, data });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run() -> Run {
+        Run {
+            args: vec!["git".to_string(), "status".to_string()],
+            cwd: PathBuf::from("/work"),
+            events: Vec::new(),
+            kept_bytes: 0,
+            truncated: false,
+            subscribers: Vec::new(),
+            finished: false,
+            cancel: None,
+        }
+    }
+
+    fn output(data: &str) -> Event {
+        Event::Output {
+            stream: "stdout",
+            data: data.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_run_replays_events_and_ends_streams() {
+        let mut run = run();
+        let early = run.subscribe();
+        run.publish(output("one\n"));
+        let late = run.subscribe();
+        run.publish(Event::Exit {
+            exit_code: Some(0),
+            duration_ms: 5,
+            error: None,
+        });
+
+        for stream in [early, late, run.subscribe()] {
+            let events = futures::executor::block_on(stream.collect::<Vec<_>>());
+            let names: Vec<_> = events.iter().map(Event::name).collect();
+            assert_eq!(names, vec!["output", "exit"]);
+        }
+        let status = run.to_json("id");
+        assert_eq!(status["stdout"], "one\n");
+        assert_eq!(status["running"], false);
+        assert_eq!(status["exit"]["exit_code"], 0);
+    }
+
+    #[test]
+    fn test_run_keeps_bounded_output() {
+        let mut run = run();
+        let line = "x".repeat(MAX_KEPT_OUTPUT / 2 + 1);
+        let stream = run.subscribe();
+        run.publish(output(&line));
+        run.publish(output(&line));
+        run.publish(Event::Exit {
+            exit_code: None,
+            duration_ms: 0,
+            error: Some("Run cancelled".to_string()),
+        });
+
+        assert!(run.truncated);
+        assert_eq!(run.events.len(), 2);
+        // Subscribers still see every line
+        assert_eq!(futures::executor::block_on(stream.count()), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_token_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("http/token");
+        write_token(&path, "abc").unwrap();
+        write_token(&path, "def").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "def");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
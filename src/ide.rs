@@ -0,0 +1,698 @@
+// file: src/ide.rs
+// version: 1.4.0
+// guid: 48d59ea7-458e-4ab5-a792-df70b6ba751d
+
+//! IDE integration mode (`--ide`)
+//!
+//! Serves JSON-RPC 2.0 on stdin/stdout as the backend of an editor extension. Messages are
+//! framed with `Content-Length` headers like the Language Server Protocol, which is what
+//! `vscode-jsonrpc` speaks; newline-delimited JSON is accepted as well, and replies use the
+//! framing of the first message received.
+//!
+//! Methods:
+//!
+//! - `initialize` `{workspaceFolders: [{uri, name}], rootUri?, clientInfo?}` scopes the
+//!   session to the workspace folders and returns the server's capabilities
+//! - `capabilities` returns the same capabilities again
+//! - `run` `{args, cwd?, dryRun?, simulate?, workDoneToken?}` runs one command line, e.g.
+//!   `["git", "status"]`, and returns `{exitCode, stdout, stderr}`. While it runs, output
+//!   lines are streamed as `$/progress` notifications (`begin`, `report`, `end`) under
+//!   `workDoneToken`, or the request id when no token is given
+//! - `$/cancelRequest` `{id}` stops a running command and the processes it started; its
+//!   request fails with code -32800
+//! - `shutdown` and `exit` end the session
+//!
+//! Every `run` is a separate invocation of this binary with the same `--config`, `--profile`
+//! and `--allow-outside-workspace` settings and what is left of the session budget, so
+//! commands keep their own validation, audit logging and output handling. When the configuration sets no `safety.workspace_roots`,
+//! the workspace folders become the roots for the session and for the commands it runs.
+
+use crate::config::ENV_PREFIX;
+use crate::error::{AgentError, Result};
+use crate::executor::Executor;
+use crate::logger::LOG_STDERR_ENV;
+use crate::{process, session, tools};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// Output kept per stream for the `run` result; streamed lines are not limited
+const MAX_CAPTURED_OUTPUT: usize = 1024 * 1024;
+
+/// A command the backend can run, as advertised in the capabilities
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandInfo {
+    pub name: String,
+    pub about: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    Headers,
+    Lines,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeParams {
+    #[serde(default)]
+    workspace_folders: Option<Vec<WorkspaceFolder>>,
+    #[serde(default)]
+    root_uri: Option<String>,
+    #[serde(default)]
+    root_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceFolder {
+    uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RunParams {
+    args: Vec<String>,
+    #[serde(default)]
+    cwd: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    simulate: bool,
+    #[serde(default)]
+    work_done_token: Option<Value>,
+}
+
+/// Commands started by `run` that can still be cancelled, keyed by request id
+type Running = Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>;
+
+struct Server {
+    executor: Executor,
+    commands: Vec<CommandInfo>,
+    forward_args: Vec<String>,
+    exe: PathBuf,
+    workspace: Vec<PathBuf>,
+    /// Workspace roots passed to commands when the configuration sets none
+    scoped_roots: Option<Vec<PathBuf>>,
+    initialized: bool,
+    shutting_down: bool,
+    out: mpsc::UnboundedSender<Value>,
+    running: Running,
+}
+
+/// Serve JSON-RPC on stdin/stdout until `exit` or end of input
+///
+/// `commands` are the subcommands `run` accepts and `forward_args` the global options
+/// every command is run with.
+pub async fn serve(
+    executor: Executor,
+    commands: Vec<CommandInfo>,
+    forward_args: Vec<String>,
+) -> Result<()> {
+    let framing = Arc::new(OnceLock::new());
+    let (out, rx) = mpsc::unbounded_channel();
+    let writer = tokio::spawn(write_messages(rx, framing.clone()));
+
+    let mut server = Server {
+        executor,
+        commands,
+        forward_args,
+        exe: std::env::current_exe()?,
+        workspace: Vec::new(),
+        scoped_roots: None,
+        initialized: false,
+        shutting_down: false,
+        out,
+        running: Arc::new(Mutex::new(HashMap::new())),
+    };
+    info!("IDE backend listening on stdin");
+
+    let mut reader = BufReader::new(tokio::io::stdin());
+    while let Some(message) = read_message(&mut reader, &framing).await? {
+        let message = match serde_json::from_str::<Value>(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                server.respond(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string())));
+                continue;
+            }
+        };
+        if !server.handle(message) {
+            break;
+        }
+    }
+
+    // Stop commands that are still running, then flush the remaining replies
+    for (_, cancel) in server.running.lock().unwrap().drain() {
+        let _ = cancel.send(());
+    }
+    drop(server);
+    writer
+        .await
+        .map_err(|e| AgentError::system(format!("IDE writer failed: {}", e)))?
+}
+
+impl Server {
+    /// Handle one message; returns false when the session should end
+    fn handle(&mut self, message: Value) -> bool {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // Requests without a method are invalid; responses need no reply, as the server
+            // sends no requests of its own
+            let is_response = message.get("result").is_some() || message.get("error").is_some();
+            if let Some(id) = id.filter(|_| !is_response) {
+                self.respond(id, Err(RpcError::new(INVALID_REQUEST, "Missing method")));
+            }
+            return true;
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        match (method, id) {
+            ("exit", _) => return false,
+            ("$/cancelRequest", _) => self.cancel(&params),
+            (_, None) => debug!("Ignoring notification {}", method),
+            (_, Some(id)) => {
+                if let Some(result) = self.dispatch(method, params, id.clone()) {
+                    self.respond(id, result);
+                }
+            }
+        }
+        true
+    }
+
+    /// Run a request; `None` means a task was spawned that replies on its own
+    fn dispatch(
+        &mut self,
+        method: &str,
+        params: Value,
+        id: Value,
+    ) -> Option<std::result::Result<Value, RpcError>> {
+        if self.shutting_down {
+            return Some(Err(RpcError::new(
+                INVALID_REQUEST,
+                "The server is shutting down",
+            )));
+        }
+        if !self.initialized && method != "initialize" {
+            return Some(Err(RpcError::new(
+                SERVER_NOT_INITIALIZED,
+                "initialize must be called first",
+            )));
+        }
+        match method {
+            "initialize" => Some(self.initialize(params)),
+            "capabilities" => Some(Ok(self.capabilities())),
+            "shutdown" => {
+                self.shutting_down = true;
+                Some(Ok(Value::Null))
+            }
+            "run" => self.run(id, params).err().map(Err),
+            _ => Some(Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {}", method),
+            ))),
+        }
+    }
+
+    fn initialize(&mut self, params: Value) -> std::result::Result<Value, RpcError> {
+        if self.initialized {
+            return Err(RpcError::new(INVALID_REQUEST, "Already initialized"));
+        }
+        let params: InitializeParams =
+            serde_json::from_value(params).map_err(|e| RpcError::invalid_params(e.to_string()))?;
+
+        let uris: Vec<String> = match params.workspace_folders {
+            Some(folders) if !folders.is_empty() => folders.into_iter().map(|f| f.uri).collect(),
+            _ => params.root_uri.into_iter().collect(),
+        };
+        let mut workspace = uris
+            .iter()
+            .map(|uri| {
+                uri_to_path(uri)
+                    .ok_or_else(|| RpcError::invalid_params(format!("Not a file URI: {}", uri)))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if workspace.is_empty() {
+            workspace.extend(params.root_path.map(PathBuf::from));
+        }
+        for folder in &mut workspace {
+            *folder = folder
+                .canonicalize()
+                .map_err(|e| RpcError::invalid_params(format!("{}: {}", folder.display(), e)))?;
+        }
+
+        if self.executor.config().safety.workspace_roots.is_empty() && !workspace.is_empty() {
+            let base = workspace[0].clone();
+            self.executor
+                .set_workspace_roots(&workspace, &base)
+                .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+            self.scoped_roots = Some(workspace.clone());
+        }
+        info!(
+            "IDE session initialized for {}",
+            workspace
+                .iter()
+                .map(|w| w.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        self.workspace = workspace;
+        self.initialized = true;
+        Ok(self.capabilities())
+    }
+
+    fn capabilities(&self) -> Value {
+        let config = self.executor.config();
+        let tools: BTreeMap<String, Option<PathBuf>> = self
+            .executor
+            .security()
+            .get_allowed_commands()
+            .into_iter()
+            .map(|name| {
+                let path = tools::resolve(&name, config.tools.get(&name)).ok();
+                (name, path)
+            })
+            .collect();
+        json!({
+            "serverInfo": {"name": "safe-ai-util", "version": crate::VERSION},
+            "capabilities": {
+                "commands": self.commands,
+                "workspaceFolders": self.workspace,
+                "workspaceRoots": self.scoped_roots.as_ref().unwrap_or(&config.safety.workspace_roots),
+                "sandboxed": self.executor.sandbox().is_enforced(),
+                "dryRun": config.safety.dry_run,
+                "simulate": config.safety.simulate,
+                "progress": true,
+                "cancellation": true,
+                "tools": tools,
+            }
+        })
+    }
+
+    fn run(&mut self, id: Value, params: Value) -> std::result::Result<(), RpcError> {
+        let params: RunParams =
+            serde_json::from_value(params).map_err(|e| RpcError::invalid_params(e.to_string()))?;
+        check_run_args(&params.args, &self.commands).map_err(RpcError::invalid_params)?;
+
+        let cwd = match &params.cwd {
+            Some(cwd) => PathBuf::from(cwd),
+            None => match self.workspace.first() {
+                Some(folder) => folder.clone(),
+                None => std::env::current_dir()
+                    .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?,
+            },
+        };
+        let cwd = self
+            .executor
+            .check_path(&cwd)
+            .map_err(|e| RpcError::invalid_params(e.to_string()))?;
+
+        let mut command = child_command(&self.exe, &self.forward_args, &params.args, &cwd, &self.executor);
+        if let Some(roots) = &self.scoped_roots {
+            let roots = toml::Value::Array(
+                roots
+                    .iter()
+                    .map(|r| toml::Value::String(r.display().to_string()))
+                    .collect(),
+            );
+            command.env(
+                format!("{}SAFETY__WORKSPACE_ROOTS", ENV_PREFIX),
+                roots.to_string(),
+            );
+        }
+        if params.dry_run {
+            command.env(format!("{}SAFETY__DRY_RUN", ENV_PREFIX), "true");
+        }
+        if params.simulate {
+            command.env(format!("{}SAFETY__SIMULATE", ENV_PREFIX), "true");
+        }
+
+        let (cancel, cancelled) = oneshot::channel();
+        self.running.lock().unwrap().insert(id.to_string(), cancel);
+        let token = params.work_done_token.unwrap_or_else(|| id.clone());
+        let title = params.args.join(" ");
+        debug!("Running {} in {}", title, cwd.display());
+        tokio::spawn(run_command(
+            command,
+            id,
+            token,
+            title,
+            self.out.clone(),
+            cancelled,
+            self.running.clone(),
+        ));
+        Ok(())
+    }
+
+    fn cancel(&self, params: &Value) {
+        let Some(id) = params.get("id") else {
+            return;
+        };
+        if let Some(cancel) = self.running.lock().unwrap().remove(&id.to_string()) {
+            info!("Cancelling request {}", id);
+            let _ = cancel.send(());
+        }
+    }
+
+    fn respond(&self, id: Value, result: std::result::Result<Value, RpcError>) {
+        let _ = self.out.send(response(id, result));
+    }
+}
+
+fn response(id: Value, result: std::result::Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": error.code, "message": error.message},
+        }),
+    }
+}
+
+fn progress(out: &mpsc::UnboundedSender<Value>, token: &Value, value: Value) {
+    let _ = out.send(json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": {"token": token, "value": value},
+    }));
+}
+
+/// Run one command, streaming its output, and send the reply to its request
+async fn run_command(
+    mut command: Command,
+    id: Value,
+    token: Value,
+    title: String,
+    out: mpsc::UnboundedSender<Value>,
+    mut cancelled: oneshot::Receiver<()>,
+    running: Running,
+) {
+    progress(
+        &out,
+        &token,
+        json!({"kind": "begin", "title": title, "cancellable": true}),
+    );
+
+    let _terminal = process::isolate(&mut command, false);
+    let result = match command.spawn() {
+        Err(e) => Err(RpcError::new(
+            INTERNAL_ERROR,
+            format!("Failed to start {}: {}", title, e),
+        )),
+        Ok(mut child) => {
+            let stdout = child
+                .stdout
+                .take()
+                .map(|s| tokio::spawn(stream_lines(s, "stdout", token.clone(), out.clone())));
+            let stderr = child
+                .stderr
+                .take()
+                .map(|s| tokio::spawn(stream_lines(s, "stderr", token.clone(), out.clone())));
+            tokio::select! {
+                status = child.wait() => match status {
+                    Ok(status) => {
+                        let stdout = match stdout {
+                            Some(task) => task.await.unwrap_or_default(),
+                            None => String::new(),
+                        };
+                        let stderr = match stderr {
+                            Some(task) => task.await.unwrap_or_default(),
+                            None => String::new(),
+                        };
+                        Ok(json!({"exitCode": status.code(), "stdout": stdout, "stderr": stderr}))
+                    }
+                    Err(e) => Err(RpcError::new(INTERNAL_ERROR, e.to_string())),
+                },
+                _ = &mut cancelled => {
+                    // The command forwards SIGTERM to the processes it started
+                    process::terminate(&mut child).await;
+                    // Processes the command started may still hold its output open
+                    for task in stdout.iter().chain(stderr.iter()) {
+                        task.abort();
+                    }
+                    Err(RpcError::new(REQUEST_CANCELLED, "Request cancelled"))
+                }
+            }
+        }
+    };
+
+    running.lock().unwrap().remove(&id.to_string());
+    let message = match &result {
+        Ok(result) => format!("exit code {}", result["exitCode"]),
+        Err(error) => error.message.clone(),
+    };
+    progress(&out, &token, json!({"kind": "end", "message": message}));
+    let _ = out.send(response(id, result));
+}
+
+/// Forward each line of a command's output as a progress report and collect it
+async fn stream_lines<R: AsyncRead + Unpin>(
+    stream: R,
+    name: &'static str,
+    token: Value,
+    out: mpsc::UnboundedSender<Value>,
+) -> String {
+    let mut reader = BufReader::new(stream);
+    let mut captured = String::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let text = String::from_utf8_lossy(&line);
+        if captured.len() + text.len() <= MAX_CAPTURED_OUTPUT {
+            captured.push_str(&text);
+        }
+        progress(
+            &out,
+            &token,
+            json!({"kind": "report", "stream": name, "message": text.trim_end_matches(['\r', '\n'])}),
+        );
+    }
+    captured
+}
+
+/// Invocation of this binary running one command line in `cwd` with the session's global
+/// options, its identity and what is left of its budget; output is piped
+pub(crate) fn child_command(
+    exe: &Path,
+    forward_args: &[String],
+    args: &[String],
+    cwd: &Path,
+    executor: &Executor,
+) -> Command {
+    let mut command = Command::new(exe);
+    command
+        .args(forward_args)
+        .args(args)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .env(LOG_STDERR_ENV, "1")
+        .envs(session::current().child_env())
+        .envs(executor.budget().child_env());
+    command
+}
+
+/// Check the command line of a `run` request: it must start with a known command, so
+/// global options such as `--config` or `--allow-outside-workspace` cannot be passed
+pub(crate) fn check_run_args(args: &[String], commands: &[CommandInfo]) -> std::result::Result<(), String> {
+    let Some(first) = args.first() else {
+        return Err("args must name a command".to_string());
+    };
+    if !commands.iter().any(|c| &c.name == first) {
+        return Err(format!(
+            "Unknown command '{}' (available: {})",
+            first,
+            commands
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Convert a `file://` URI to a path, decoding percent escapes
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // Only local files: `file:///path` or `file://localhost/path`
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !rest.starts_with('/') {
+        return None;
+    }
+
+    let bytes = rest.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+
+    // `file:///c:/repo` on Windows
+    if cfg!(windows) && path.as_bytes().get(2) == Some(&b':') {
+        return Some(PathBuf::from(&path[1..]));
+    }
+    Some(PathBuf::from(path))
+}
+
+/// Read the next message body, or `None` at end of input
+async fn read_message<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    framing: &OnceLock<Framing>,
+) -> Result<Option<String>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        if content_length.is_none() && line.trim_start().starts_with(['{', '[']) {
+            let _ = framing.set(Framing::Lines);
+            return Ok(Some(line.to_string()));
+        }
+        if line.is_empty() {
+            match content_length {
+                Some(length) => {
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).await?;
+                    let _ = framing.set(Framing::Headers);
+                    return String::from_utf8(body)
+                        .map(Some)
+                        .map_err(|e| AgentError::invalid_argument(e.to_string()));
+                }
+                // Blank lines between newline-delimited messages
+                None => continue,
+            }
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.trim().parse().map_err(|_| {
+                    AgentError::invalid_argument(format!("Invalid Content-Length: {}", value))
+                })?);
+            }
+        }
+    }
+}
+
+/// Write queued messages to stdout until every sender is gone
+async fn write_messages(
+    mut rx: mpsc::UnboundedReceiver<Value>,
+    framing: Arc<OnceLock<Framing>>,
+) -> Result<()> {
+    let mut stdout = tokio::io::stdout();
+    while let Some(message) = rx.recv().await {
+        let body = serde_json::to_string(&message)?;
+        let frame = match framing.get() {
+            Some(Framing::Lines) => format!("{}\n", body),
+            _ => format!("Content-Length: {}\r\n\r\n{}", body.len(), body),
+        };
+        stdout.write_all(frame.as_bytes()).await?;
+        stdout.flush().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_message_framing() {
+        let input = b"Content-Length: 17\r\nContent-Type: application/json\r\n\r\n{\"method\":\"run\"}\n\n{\"id\":1}\n";
+        let mut reader = BufReader::new(&input[..]);
+        let framing = OnceLock::new();
+        assert_eq!(
+            read_message(&mut reader, &framing)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("{\"method\":\"run\"}\n")
+        );
+        assert_eq!(framing.get(), Some(&Framing::Headers));
+        assert_eq!(
+            read_message(&mut reader, &framing)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("{\"id\":1}")
+        );
+        assert_eq!(read_message(&mut reader, &framing).await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///home/me/my%20repo"),
+            Some(PathBuf::from("/home/me/my repo"))
+        );
+        assert_eq!(
+            uri_to_path("file://localhost/srv/x"),
+            Some(PathBuf::from("/srv/x"))
+        );
+        assert_eq!(uri_to_path("https://example.com/x"), None);
+        assert_eq!(uri_to_path("file://server/share"), None);
+    }
+
+    #[test]
+    fn test_check_run_args() {
+        let commands = vec![CommandInfo {
+            name: "git".to_string(),
+            about: String::new(),
+        }];
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(check_run_args(&args(&["git", "status"]), &commands).is_ok());
+        assert!(check_run_args(&args(&[]), &commands).is_err());
+        assert!(check_run_args(&args(&["rm", "-rf"]), &commands).is_err());
+        // Global options cannot be slipped in before the command
+        assert!(check_run_args(&args(&["--profile=dev", "git"]), &commands).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+// file: src/integrity.rs
+// version: 1.0.0
+// guid: 94c1d7e3-2a6b-4f08-b5e9-6d3f0a8c71b2
+
+//! Binary integrity verification
+//!
+//! With `safety.verify_binaries` set, the executor hashes every external binary it resolves
+//! before running it. The first time a binary is seen its SHA-256 is recorded in
+//! `approved-binaries.json` in the state directory; afterwards a binary whose hash differs
+//! from the recorded one is refused with a security error until someone approves the new
+//! contents with `binaries approve <command>`. A tool swapped behind an agent's back, by a
+//! compromised package or a writable directory early on PATH, then stops the agent instead of
+//! running.
+//!
+//! Binaries are recorded by their canonical path, so a symlink such as a managed toolchain's
+//! `tools/bin/<name>` is checked through the file it points at, and installing another
+//! version records a new binary rather than changing an approved one.
+
+use crate::activity::FileLock;
+use crate::error::{AgentError, Result};
+use crate::utils;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A binary and the contents approved for it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovedBinary {
+    /// Command the binary was resolved for
+    pub command: String,
+    /// Canonical path of the binary
+    pub path: PathBuf,
+    pub sha256: String,
+    pub approved_at: DateTime<Utc>,
+}
+
+/// Outcome of checking a binary against its approval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Matches the approved hash
+    Verified,
+    /// Seen for the first time; its hash is now approved
+    Recorded,
+}
+
+/// The approved binaries, in the order they were first seen
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Approvals {
+    pub binaries: Vec<ApprovedBinary>,
+}
+
+impl Approvals {
+    /// Path of the persisted approvals
+    pub fn path() -> PathBuf {
+        utils::state_dir().join("approved-binaries.json")
+    }
+
+    /// Load the approvals, starting empty if no binary has been recorded yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Load, change and save the approvals while holding their lock
+    pub fn update<T>(change: impl FnOnce(&mut Self) -> T) -> Result<T> {
+        let path = Self::path();
+        let dir = path
+            .parent()
+            .ok_or_else(|| AgentError::system("Invalid approved binaries path"))?;
+        fs::create_dir_all(dir)?;
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut approvals = Self::load()?;
+        let result = change(&mut approvals);
+
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&approvals)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(result)
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&ApprovedBinary> {
+        self.binaries.iter().find(|binary| binary.path == path)
+    }
+
+    /// Check `sha256`, the hash of the binary at canonical `path`, against its approval,
+    /// recording it if the binary has not been seen before
+    pub fn check(&mut self, command: &str, path: &Path, sha256: &str) -> Result<Verdict> {
+        match self.find(path) {
+            Some(approved) if approved.sha256 == sha256 => Ok(Verdict::Verified),
+            Some(approved) => Err(AgentError::security(format!(
+                "Binary {} for {} changed since it was approved on {} (SHA-256 was {}, is now \
+                 {}); if the change is expected, approve it with `safe-ai-util binaries approve \
+                 {}`",
+                path.display(),
+                command,
+                approved.approved_at.format("%Y-%m-%d"),
+                short_hash(&approved.sha256),
+                short_hash(sha256),
+                command
+            ))),
+            None => {
+                self.approve(command, path, sha256);
+                Ok(Verdict::Recorded)
+            }
+        }
+    }
+
+    /// Approve the current contents of the binary at canonical `path`
+    pub fn approve(&mut self, command: &str, path: &Path, sha256: &str) {
+        let approved = ApprovedBinary {
+            command: command.to_string(),
+            path: path.to_path_buf(),
+            sha256: sha256.to_string(),
+            approved_at: Utc::now(),
+        };
+        match self.binaries.iter_mut().find(|binary| binary.path == path) {
+            Some(binary) => *binary = approved,
+            None => self.binaries.push(approved),
+        }
+    }
+}
+
+/// Canonical path and SHA-256 of a binary
+pub fn fingerprint(program: &Path) -> Result<(PathBuf, String)> {
+    let path = program.canonicalize().map_err(|e| {
+        AgentError::file_not_found(format!("Cannot resolve {}: {}", program.display(), e))
+    })?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(&path)?, &mut hasher)?;
+    Ok((path, hex::encode(hasher.finalize())))
+}
+
+/// Verify the binary `program` resolved for `command`, recording it if it is new
+pub fn verify(command: &str, program: &Path) -> Result<Verdict> {
+    let (path, sha256) = fingerprint(program)?;
+    // Known binaries are verified without taking the lock
+    if let Some(approved) = Approvals::load()?.find(&path) {
+        if approved.sha256 == sha256 {
+            return Ok(Verdict::Verified);
+        }
+    }
+    Approvals::update(|approvals| approvals.check(command, &path, &sha256))?
+}
+
+/// First 16 hex digits of a hash, enough to tell two apart
+pub fn short_hash(sha256: &str) -> &str {
+    &sha256[..16.min(sha256.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_records_then_verifies() {
+        let mut approvals = Approvals::default();
+        let git = Path::new("/usr/bin/git");
+        assert_eq!(
+            approvals.check("git", git, "aa").unwrap(),
+            Verdict::Recorded
+        );
+        assert_eq!(
+            approvals.check("git", git, "aa").unwrap(),
+            Verdict::Verified
+        );
+
+        let error = approvals.check("git", git, "bb").unwrap_err();
+        assert!(matches!(error, AgentError::Security(_)));
+        assert!(error.to_string().contains("binaries approve git"));
+
+        approvals.approve("git", git, "bb");
+        assert_eq!(approvals.binaries.len(), 1);
+        assert_eq!(
+            approvals.check("git", git, "bb").unwrap(),
+            Verdict::Verified
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_follows_symlinks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let binary = dir.path().join("tool");
+        fs::write(&binary, "abc").unwrap();
+        let (path, sha256) = fingerprint(&binary).unwrap();
+        assert_eq!(path, binary.canonicalize().unwrap());
+        assert_eq!(
+            sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(&binary, &link).unwrap();
+            assert_eq!(fingerprint(&link).unwrap().0, path);
+        }
+    }
+}
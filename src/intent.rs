@@ -0,0 +1,133 @@
+// file: src/intent.rs
+// version: 1.0.0
+// guid: 2d7e91b4-38f6-4c0a-a5d3-6b1f0e84c972
+
+//! Why an invocation was run
+//!
+//! Agents attach their intent to an invocation with `--reason` ("fixing issue #123") and any
+//! number of `--label` flags. The intent is recorded with every command in the execution
+//! history and the audit log, and added as `Reason:` and `Label:` trailers to commit messages
+//! the utility writes, so a person reviewing the work later can tell why each action was taken.
+
+use crate::error::{AgentError, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Longest reason accepted
+const MAX_REASON_CHARS: usize = 500;
+
+/// Longest label accepted
+const MAX_LABEL_CHARS: usize = 64;
+
+/// Reason and labels given for this invocation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Intent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+}
+
+static INTENT: OnceLock<Intent> = OnceLock::new();
+
+impl Intent {
+    /// Check the reason and labels: both must be single lines, and labels must not be blank
+    pub fn new(reason: Option<String>, labels: Vec<String>) -> Result<Self> {
+        let reason = reason
+            .map(|reason| reason.trim().to_string())
+            .filter(|reason| !reason.is_empty());
+        if let Some(reason) = &reason {
+            if reason.chars().any(char::is_control) {
+                return Err(AgentError::invalid_argument(
+                    "--reason must be a single line of text",
+                ));
+            }
+            if reason.chars().count() > MAX_REASON_CHARS {
+                return Err(AgentError::invalid_argument(format!(
+                    "--reason is longer than {} characters",
+                    MAX_REASON_CHARS
+                )));
+            }
+        }
+        let mut checked: Vec<String> = Vec::new();
+        for label in labels {
+            let label = label.trim().to_string();
+            if label.is_empty()
+                || label.chars().any(char::is_control)
+                || label.chars().count() > MAX_LABEL_CHARS
+            {
+                return Err(AgentError::invalid_argument(format!(
+                    "Invalid --label {:?}: labels are non-empty single lines of at most {} characters",
+                    label, MAX_LABEL_CHARS
+                )));
+            }
+            if !checked.contains(&label) {
+                checked.push(label);
+            }
+        }
+        Ok(Self {
+            reason,
+            labels: checked,
+        })
+    }
+
+    /// Whether neither a reason nor labels were given
+    pub fn is_empty(&self) -> bool {
+        self.reason.is_none() && self.labels.is_empty()
+    }
+
+    /// Commit message trailers carrying the intent, e.g. `Reason: fixing issue #123`
+    pub fn trailers(&self) -> Vec<String> {
+        self.reason
+            .iter()
+            .map(|reason| format!("Reason: {}", reason))
+            .chain(self.labels.iter().map(|label| format!("Label: {}", label)))
+            .collect()
+    }
+}
+
+/// Set the intent of this invocation; only the first call has an effect
+pub fn set(intent: Intent) {
+    let _ = INTENT.set(intent);
+}
+
+/// The intent of this invocation, empty unless [`set`] was called
+pub fn current() -> &'static Intent {
+    INTENT.get_or_init(Intent::default)
+}
+
+/// `git commit` arguments adding the intent of this invocation as trailers
+pub fn commit_trailer_args() -> Vec<String> {
+    current()
+        .trailers()
+        .into_iter()
+        .flat_map(|trailer| ["--trailer".to_string(), trailer])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intent_is_checked() {
+        let intent = Intent::new(
+            Some("  fixing issue #123 ".to_string()),
+            vec!["bugfix".to_string(), "bugfix".to_string(), "ci".to_string()],
+        )
+        .unwrap();
+        assert_eq!(intent.reason.as_deref(), Some("fixing issue #123"));
+        assert_eq!(intent.labels, vec!["bugfix", "ci"]);
+        assert_eq!(
+            intent.trailers(),
+            vec!["Reason: fixing issue #123", "Label: bugfix", "Label: ci"]
+        );
+
+        assert!(Intent::new(Some(" ".to_string()), Vec::new())
+            .unwrap()
+            .is_empty());
+        assert!(Intent::new(Some("one\nReason: forged".to_string()), Vec::new()).is_err());
+        assert!(Intent::new(None, vec![String::new()]).is_err());
+        assert!(Intent::new(None, vec!["x".repeat(65)]).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+// file: src/jobs.rs
+// version: 1.0.0
+// guid: 5b8e2d71-4c9a-4f36-a1d8-7e0b3c6f9a24
+
+//! Background jobs
+//!
+//! Long-running commands — `file watch`, dev servers, `pytest --looponfail` — would block an
+//! agent's only terminal. [`crate::executor::Executor::execute_detached`] validates such a
+//! command like any other and then hands it to a supervisor: a copy of this utility started
+//! in a session of its own (`jobs supervise <id>`, hidden), which runs the command with its
+//! output appended to a log file, enforces the job's timeout and records how it ended.
+//!
+//! Jobs are registered in `jobs.json` in the state directory, shared between processes and
+//! guarded by a lock file like the session registry; logs are kept in `jobs/<id>.log`.
+//! Finished jobs and their logs are removed after [`RETENTION_HOURS`].
+
+use crate::activity::{short_id, FileLock};
+use crate::error::{AgentError, Result};
+use crate::process;
+use crate::utils;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Hours a finished job and its log are kept
+pub const RETENTION_HOURS: i64 = 24;
+
+/// Time `jobs start` waits for the supervisor to start the command
+const START_WAIT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// State of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Supervisor started, command not yet
+    Starting,
+    Running,
+    /// The command exited by itself
+    Exited,
+    /// Stopped with `jobs kill` or by a signal
+    Killed,
+    /// Stopped after its timeout
+    TimedOut,
+    /// The command could not be started
+    Failed,
+    /// The supervisor disappeared without recording how the command ended
+    Lost,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Starting => "starting",
+            Self::Running => "running",
+            Self::Exited => "exited",
+            Self::Killed => "killed",
+            Self::TimedOut => "timed_out",
+            Self::Failed => "failed",
+            Self::Lost => "lost",
+        }
+    }
+
+    /// Whether the job's command may still be running
+    pub fn is_active(self) -> bool {
+        matches!(self, Self::Starting | Self::Running)
+    }
+}
+
+/// A background job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: String,
+    /// Name given with `--name`, usable instead of the ID
+    pub name: Option<String>,
+    /// Command line as requested
+    pub command: String,
+    /// Validated binary and arguments the supervisor runs
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub session_id: String,
+    /// Process of the supervisor
+    pub supervisor_pid: Option<u32>,
+    /// Process (and process group) of the command
+    pub pid: Option<u32>,
+    pub timeout_secs: Option<u64>,
+    pub log: PathBuf,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: JobStatus,
+    /// Exit code, if the command exited by itself
+    pub exit_code: Option<i32>,
+    /// Why the command could not be started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl JobRecord {
+    /// ID and name, for messages
+    pub fn describe(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({})", self.id, name),
+            None => self.id.clone(),
+        }
+    }
+
+    /// Whether the job has ended and nothing more will be written to its log
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.is_some() || matches!(self.status, JobStatus::Failed | JobStatus::Lost)
+    }
+}
+
+/// Options of a job
+#[derive(Debug, Clone, Default)]
+pub struct JobOptions {
+    pub name: Option<String>,
+    /// Stop the command after this long
+    pub timeout: Option<std::time::Duration>,
+}
+
+/// Jobs known to the registry, keyed by ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobRegistry {
+    pub jobs: BTreeMap<String, JobRecord>,
+}
+
+impl JobRegistry {
+    /// Path of the persisted registry
+    pub fn path() -> PathBuf {
+        utils::state_dir().join("jobs.json")
+    }
+
+    /// Directory of the job logs
+    pub fn log_dir() -> PathBuf {
+        utils::state_dir().join("jobs")
+    }
+
+    /// Load the registry, starting empty if none has been saved yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        let mut registry: Self = serde_json::from_str(&content)?;
+        registry.refresh(Utc::now());
+        Ok(registry)
+    }
+
+    /// Load, change and save the registry while holding its lock
+    pub fn update<T>(change: impl FnOnce(&mut Self) -> T) -> Result<T> {
+        let path = Self::path();
+        let dir = path
+            .parent()
+            .ok_or_else(|| AgentError::system("Invalid jobs path"))?;
+        fs::create_dir_all(dir)?;
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut registry = Self::load()?;
+        let result = change(&mut registry);
+        registry.prune(Utc::now());
+
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&registry)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(result)
+    }
+
+    /// Find a job by ID, unique ID prefix or name (the latest job of that name)
+    pub fn find(&self, key: &str) -> Result<&JobRecord> {
+        if let Some(job) = self.jobs.get(key) {
+            return Ok(job);
+        }
+        let by_prefix: Vec<&JobRecord> = self
+            .jobs
+            .values()
+            .filter(|job| job.id.starts_with(key))
+            .collect();
+        match by_prefix.as_slice() {
+            [job] => return Ok(job),
+            [] => {}
+            _ => {
+                return Err(AgentError::invalid_argument(format!(
+                    "Job ID '{}' is ambiguous",
+                    key
+                )))
+            }
+        }
+        self.jobs
+            .values()
+            .filter(|job| job.name.as_deref() == Some(key))
+            .max_by_key(|job| job.started_at)
+            .ok_or_else(|| {
+                AgentError::invalid_argument(format!(
+                    "No job '{}' (see `safe-ai-util jobs list`)",
+                    key
+                ))
+            })
+    }
+
+    /// Mark jobs whose supervisor is gone without recording an outcome as lost
+    pub fn refresh(&mut self, now: DateTime<Utc>) {
+        for job in self.jobs.values_mut() {
+            let unfinished = job.status.is_active()
+                || (job.status == JobStatus::Killed && job.finished_at.is_none());
+            if unfinished
+                && job
+                    .supervisor_pid
+                    .is_some_and(|pid| !process::is_alive(pid))
+            {
+                if job.status != JobStatus::Killed {
+                    job.status = JobStatus::Lost;
+                }
+                job.finished_at = Some(now);
+            }
+        }
+    }
+
+    /// Drop jobs finished longer than [`RETENTION_HOURS`] ago, with their logs
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        self.jobs.retain(|_, job| {
+            let expired = job
+                .finished_at
+                .is_some_and(|finished| now - finished > Duration::hours(RETENTION_HOURS));
+            if expired {
+                let _ = fs::remove_file(&job.log);
+            }
+            !expired
+        });
+    }
+}
+
+/// Register a job and start its supervisor, prepared by the executor as
+/// `<this utility> jobs supervise <id>` with the command's working directory and environment
+///
+/// Waits briefly for the command to start, so a command that cannot be started is reported
+/// as failed rather than as starting.
+pub(crate) async fn launch(
+    mut supervisor: Command,
+    command: &str,
+    program: PathBuf,
+    args: Vec<String>,
+    cwd: PathBuf,
+    id: String,
+    options: &JobOptions,
+) -> Result<JobRecord> {
+    let log_dir = JobRegistry::log_dir();
+    fs::create_dir_all(&log_dir)?;
+    let record = JobRecord {
+        log: log_dir.join(format!("{}.log", id)),
+        id: id.clone(),
+        name: options.name.clone(),
+        command: command.to_string(),
+        program,
+        args,
+        cwd,
+        session_id: crate::session::current().session_id().to_string(),
+        supervisor_pid: None,
+        pid: None,
+        timeout_secs: options.timeout.map(|timeout| timeout.as_secs().max(1)),
+        started_at: Utc::now(),
+        finished_at: None,
+        status: JobStatus::Starting,
+        exit_code: None,
+        error: None,
+    };
+    JobRegistry::update(|registry| registry.jobs.insert(id.clone(), record))?;
+
+    supervisor
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    process::detach(&mut supervisor);
+    let spawned = supervisor.spawn();
+    let pid = match spawned {
+        Ok(child) => child.id(),
+        Err(e) => {
+            let message = format!("Failed to start the job supervisor: {}", e);
+            JobRegistry::update(|registry| {
+                if let Some(job) = registry.jobs.get_mut(&id) {
+                    job.status = JobStatus::Failed;
+                    job.finished_at = Some(Utc::now());
+                    job.error = Some(message.clone());
+                }
+            })?;
+            return Err(AgentError::execution(message));
+        }
+    };
+    JobRegistry::update(|registry| {
+        if let Some(job) = registry.jobs.get_mut(&id) {
+            job.supervisor_pid = pid;
+        }
+    })?;
+
+    let deadline = tokio::time::Instant::now() + START_WAIT;
+    loop {
+        let registry = JobRegistry::load()?;
+        let job = registry.find(&id)?;
+        if job.status != JobStatus::Starting || tokio::time::Instant::now() >= deadline {
+            return Ok(job.clone());
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+/// Run a job's command and record how it ended; the body of `jobs supervise <id>`
+pub async fn supervise(id: &str) -> Result<()> {
+    let job = JobRegistry::load()?.find(id)?.clone();
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&job.log)?;
+
+    let mut cmd = Command::new(&job.program);
+    cmd.args(&job.args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // The command leads a group of its own, so `jobs kill` stops everything it started
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let message = format!("Failed to execute {}: {}", job.command, e);
+            finish(&job.id, |record| {
+                record.status = JobStatus::Failed;
+                record.error = Some(message.clone());
+            })?;
+            return Err(AgentError::execution(message));
+        }
+    };
+    let pid = child.id();
+    JobRegistry::update(|registry| {
+        if let Some(record) = registry.jobs.get_mut(&job.id) {
+            record.pid = pid;
+            record.status = JobStatus::Running;
+        }
+    })?;
+    info!("Job {} running: {}", job.id, job.command);
+
+    let wait = child.wait();
+    let (status, timed_out) = match job.timeout_secs {
+        Some(seconds) => {
+            match tokio::time::timeout(std::time::Duration::from_secs(seconds), wait).await {
+                Ok(status) => (status?, false),
+                Err(_) => {
+                    warn!("Job {} timed out after {}s", job.id, seconds);
+                    process::terminate(&mut child).await;
+                    (child.wait().await?, true)
+                }
+            }
+        }
+        None => (wait.await?, false),
+    };
+
+    finish(&job.id, |record| {
+        record.exit_code = status.code();
+        record.status = if timed_out {
+            JobStatus::TimedOut
+        } else if record.status == JobStatus::Killed || status.code().is_none() {
+            JobStatus::Killed
+        } else {
+            JobStatus::Exited
+        };
+    })
+}
+
+/// Record the end of a job
+fn finish(id: &str, change: impl FnOnce(&mut JobRecord)) -> Result<()> {
+    JobRegistry::update(|registry| {
+        if let Some(record) = registry.jobs.get_mut(id) {
+            change(record);
+            record.finished_at = Some(Utc::now());
+        }
+    })
+}
+
+/// Stop a running job: SIGTERM to its process group and, if it is still running after
+/// [`process::KILL_GRACE`] or with `force`, SIGKILL
+pub async fn kill(key: &str, force: bool) -> Result<JobRecord> {
+    let job = JobRegistry::update(|registry| -> Result<JobRecord> {
+        let id = registry.find(key)?.id.clone();
+        let job = registry.jobs.get_mut(&id).expect("job just found");
+        if job.is_finished() || job.status == JobStatus::Killed {
+            return Err(AgentError::invalid_argument(format!(
+                "Job {} is not running ({})",
+                job.describe(),
+                job.status.as_str()
+            )));
+        }
+        job.status = JobStatus::Killed;
+        Ok(job.clone())
+    })??;
+    let Some(pid) = job.pid else {
+        // Not started yet: stopping the supervisor prevents it from starting the command
+        if let Some(supervisor) = job.supervisor_pid {
+            process::kill_group(supervisor, true);
+        }
+        return Ok(job);
+    };
+
+    process::kill_group(pid, force);
+    let deadline = tokio::time::Instant::now() + process::KILL_GRACE;
+    while !force && tokio::time::Instant::now() < deadline {
+        if JobRegistry::load()?.find(&job.id)?.is_finished() {
+            return Ok(JobRegistry::load()?.find(&job.id)?.clone());
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    // Descendants may outlive the group leader, so the group is killed either way
+    process::kill_group(pid, true);
+    Ok(JobRegistry::load()?.find(&job.id)?.clone())
+}
+
+/// New job ID: the first block of a UUID, like a short session ID
+pub fn new_id() -> String {
+    short_id(&uuid::Uuid::new_v4().to_string()).to_string()
+}
+
+/// Binary started as the supervisor: this utility, or the `safe-ai-util` on `PATH` when the
+/// executor is used as a library by another program
+pub(crate) fn supervisor_binary() -> Result<PathBuf> {
+    let current = std::env::current_exe()?;
+    let is_utility = current
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem == "safe-ai-util" || stem == "copilot-agent-util");
+    if is_utility {
+        return Ok(current);
+    }
+    which::which("safe-ai-util")
+        .map_err(|_| AgentError::execution("Background jobs need the safe-ai-util binary on PATH"))
+}
+
+/// Working directory a job runs in
+pub(crate) fn job_cwd(cwd: Option<&Path>) -> PathBuf {
+    cwd.map(Path::to_path_buf)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(
+        id: &str,
+        name: Option<&str>,
+        status: JobStatus,
+        finished_hours_ago: Option<i64>,
+    ) -> JobRecord {
+        let now = Utc::now();
+        JobRecord {
+            id: id.to_string(),
+            name: name.map(str::to_string),
+            command: "npm run dev".to_string(),
+            program: PathBuf::from("/usr/bin/npm"),
+            args: vec!["run".to_string(), "dev".to_string()],
+            cwd: PathBuf::from("/work"),
+            session_id: "s".to_string(),
+            supervisor_pid: None,
+            pid: None,
+            timeout_secs: None,
+            log: PathBuf::from(format!("/nonexistent/{}.log", id)),
+            started_at: now - Duration::hours(48),
+            finished_at: finished_hours_ago.map(|hours| now - Duration::hours(hours)),
+            status,
+            exit_code: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_find_by_id_prefix_or_name_and_prune() {
+        let mut registry = JobRegistry::default();
+        for record in [
+            job("a1b2c3d4", Some("server"), JobStatus::Running, None),
+            job("a1ff0000", None, JobStatus::Exited, Some(1)),
+            job("e5f60000", None, JobStatus::Exited, Some(30)),
+        ] {
+            registry.jobs.insert(record.id.clone(), record);
+        }
+
+        assert_eq!(registry.find("a1b2").unwrap().id, "a1b2c3d4");
+        assert_eq!(registry.find("server").unwrap().id, "a1b2c3d4");
+        assert!(registry.find("a1").is_err());
+        assert!(registry.find("zzz").is_err());
+
+        registry.prune(Utc::now());
+        assert!(registry.jobs.contains_key("a1ff0000"));
+        assert!(!registry.jobs.contains_key("e5f60000"));
+        assert!(registry.jobs["a1b2c3d4"].status.is_active());
+    }
+}
@@ -0,0 +1,226 @@
+// file: src/json_order.rs
+// version: 1.0.0
+// guid: c512805c-3e89-435b-b287-e8cb1a0f7c30
+
+//! Key order of JSON documents that are read, changed and written back
+//!
+//! serde_json's maps keep their keys sorted. To write a file back in its own layout, the order
+//! of every object's keys is recorded with [`KeyOrder::of`] when the file is read, and the
+//! changed value is written through [`Ordered`]: an object's keys come out in the recorded
+//! order, followed by the keys the file did not have in sorted order. Objects whose keys were
+//! already sorted, and objects without a recorded order, are written sorted.
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+
+/// The key order of every object in a JSON document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyOrder {
+    /// Members of an object, in document order
+    keys: Vec<(String, KeyOrder)>,
+    /// Elements of an array
+    items: Vec<KeyOrder>,
+}
+
+impl KeyOrder {
+    /// Record the key order of the JSON document `text`
+    pub fn of(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text)
+    }
+
+    /// The order recorded for the member `key` of this object
+    pub fn get(&self, key: &str) -> Option<&KeyOrder> {
+        self.keys
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, order)| order)
+    }
+
+    /// Keys of this object in document order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Write the member `key` of this object with its keys sorted; its own members keep
+    /// their order
+    pub fn sort(&mut self, key: &str) {
+        if let Some((_, order)) = self.keys.iter_mut().find(|(name, _)| name == key) {
+            order.keys.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+    }
+
+    fn is_sorted(&self) -> bool {
+        self.keys.windows(2).all(|pair| pair[0].0 <= pair[1].0)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyOrder {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KeyOrderVisitor)
+    }
+}
+
+struct KeyOrderVisitor;
+
+impl<'de> Visitor<'de> for KeyOrderVisitor {
+    type Value = KeyOrder;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_unit<E>(self) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<KeyOrder, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(KeyOrder {
+            keys: Vec::new(),
+            items,
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<KeyOrder, A::Error> {
+        let mut keys = Vec::new();
+        while let Some(entry) = map.next_entry::<String, KeyOrder>()? {
+            keys.push(entry);
+        }
+        Ok(KeyOrder {
+            keys,
+            items: Vec::new(),
+        })
+    }
+}
+
+/// A JSON value that serializes with the key order recorded for it
+pub struct Ordered<'a> {
+    value: &'a Value,
+    order: Option<&'a KeyOrder>,
+}
+
+impl<'a> Ordered<'a> {
+    pub fn new(value: &'a Value, order: &'a KeyOrder) -> Self {
+        Self {
+            value,
+            order: Some(order),
+        }
+    }
+}
+
+impl Serialize for Ordered<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Object(map) => {
+                let mut seen = HashSet::new();
+                let mut keys = Vec::with_capacity(map.len());
+                if let Some(order) = self.order.filter(|order| !order.is_sorted()) {
+                    for key in order.keys() {
+                        if let Some((key, _)) = map.get_key_value(key) {
+                            if seen.insert(key) {
+                                keys.push(key);
+                            }
+                        }
+                    }
+                }
+                keys.extend(map.keys().filter(|key| !seen.contains(key)));
+
+                let mut out = serializer.serialize_map(Some(keys.len()))?;
+                for key in keys {
+                    let child = Ordered {
+                        value: &map[key],
+                        order: self.order.and_then(|order| order.get(key)),
+                    };
+                    out.serialize_entry(key, &child)?;
+                }
+                out.end()
+            }
+            Value::Array(items) => {
+                let mut out = serializer.serialize_seq(Some(items.len()))?;
+                for (i, item) in items.iter().enumerate() {
+                    let child = Ordered {
+                        value: item,
+                        order: self.order.and_then(|order| order.items.get(i)),
+                    };
+                    out.serialize_element(&child)?;
+                }
+                out.end()
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(text: &str, change: impl FnOnce(&mut Value)) -> String {
+        let mut value: Value = serde_json::from_str(text).unwrap();
+        let order = KeyOrder::of(text).unwrap();
+        change(&mut value);
+        serde_json::to_string(&Ordered::new(&value, &order)).unwrap()
+    }
+
+    #[test]
+    fn test_keys_keep_document_order() {
+        let text = r#"{"name":"app","version":"1.0.0","scripts":{"test":"jest","build":"tsc"},"files":[{"z":1,"a":2}]}"#;
+        assert_eq!(render(text, |_| {}), text);
+
+        // New keys follow the recorded ones, removed keys are skipped
+        let changed = render(text, |value| {
+            value["scripts"]["lint"] = json!("eslint");
+            value["scripts"]["check"] = json!("tsc --noEmit");
+            value.as_object_mut().unwrap().remove("version");
+        });
+        assert_eq!(
+            changed,
+            r#"{"name":"app","scripts":{"test":"jest","build":"tsc","check":"tsc --noEmit","lint":"eslint"},"files":[{"z":1,"a":2}]}"#
+        );
+    }
+
+    #[test]
+    fn test_sorted_objects_stay_sorted() {
+        let text = r#"{"dependencies":{"axios":"1","zod":"3"}}"#;
+        assert_eq!(
+            render(text, |value| value["dependencies"]["lodash"] = json!("4")),
+            r#"{"dependencies":{"axios":"1","lodash":"4","zod":"3"}}"#
+        );
+
+        let text = r#"{"b":{"y":1,"x":2},"a":1}"#;
+        let value: Value = serde_json::from_str(text).unwrap();
+        let mut order = KeyOrder::of(text).unwrap();
+        order.sort("b");
+        assert_eq!(
+            serde_json::to_string(&Ordered::new(&value, &order)).unwrap(),
+            r#"{"b":{"x":2,"y":1},"a":1}"#
+        );
+    }
+}
@@ -1,5 +1,5 @@
 // file: src/lib.rs
-// version: 2.0.0
+// version: 2.43.0
 // guid: d82472d1-7f0f-4eb4-b0a3-6e1547103eb4
 
 //! # Safe AI Utility
@@ -10,15 +10,62 @@
 //! This Rust implementation emphasizes memory safety, error handling, robust concurrent execution,
 //! and comprehensive security controls to prevent abuse.
 
+pub mod activity;
+pub mod batch;
+pub mod budget;
+pub mod capability;
 pub mod commands;
 pub mod config;
+pub mod container;
+pub mod credentials;
+pub mod diff;
+pub mod env_profile;
 pub mod error;
+pub mod error_codes;
 pub mod executor;
+pub mod generated;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod guardrails;
+pub mod handlers;
+pub mod history;
+pub mod http;
+pub mod ide;
+pub mod integrity;
+pub mod intent;
+pub mod jobs;
+pub mod json_order;
+pub mod line_editor;
 pub mod logger;
+pub mod manifest;
+pub mod merge;
+pub mod metrics;
+pub mod parsers;
+pub mod path_map;
+pub mod pipeline;
+pub mod plan;
+pub mod process;
+#[cfg(unix)]
+pub mod pty;
+pub mod publish;
+pub mod remote;
+pub mod repl;
 pub mod security;
+pub mod serve;
+pub mod session;
+pub mod simulate;
+pub mod snapshot;
+pub mod telemetry;
+pub mod toolchain;
+pub mod tools;
+pub mod transaction;
+pub mod transcript;
 pub mod utils;
+pub mod verbosity;
+pub mod workspace_lock;
 
 pub use error::{AgentError, Result};
+pub use process::CancellationToken;
 
 /// Version information for the utility
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,465 @@
+// file: src/line_editor.rs
+// version: 1.1.0
+// guid: 9a4c2e7b-1f58-4d03-b6e9-5c8d0a3f72b1
+
+//! Line editing for interactive prompts
+//!
+//! [`LineEditor`] reads a line in raw mode with the usual readline keys: Left/Right,
+//! Home/End, Ctrl-A/E, Backspace/Delete, Ctrl-U/K/W to delete before or after the cursor or
+//! the previous word, Up/Down through the history, Ctrl-C to drop the line and Ctrl-D on an
+//! empty line to end input. Tab asks a [`Completer`] for the words that can replace the one
+//! before the cursor: a single candidate is inserted, several are narrowed to their common
+//! prefix and listed below the prompt.
+
+use crossterm::{
+    cursor::{MoveToColumn, MoveUp},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::Print,
+    terminal::{self, Clear, ClearType},
+};
+use std::io::{self, Write};
+
+/// Supplies completions: given the line and the cursor position (in characters), the
+/// position where the word being completed starts and the words that can replace it
+pub type Completer<'a> = dyn FnMut(&str, usize) -> (usize, Vec<String>) + 'a;
+
+/// Text of the line being edited and the cursor position in it
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LineBuffer {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl LineBuffer {
+    /// A buffer holding `text` with the cursor at its end
+    pub fn new(text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let cursor = chars.len();
+        Self { chars, cursor }
+    }
+
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.chars.len());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.chars.len();
+    }
+
+    /// Delete everything before the cursor (Ctrl-U)
+    pub fn kill_to_start(&mut self) {
+        self.chars.drain(..self.cursor);
+        self.cursor = 0;
+    }
+
+    /// Delete everything after the cursor (Ctrl-K)
+    pub fn kill_to_end(&mut self) {
+        self.chars.truncate(self.cursor);
+    }
+
+    /// Delete the word before the cursor and the spaces after it (Ctrl-W)
+    pub fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.chars[start - 1] == ' ' {
+            start -= 1;
+        }
+        while start > 0 && self.chars[start - 1] != ' ' {
+            start -= 1;
+        }
+        self.chars.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    /// Replace the characters from `start` to the cursor with `text`
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        let start = start.min(self.cursor);
+        self.chars.splice(start..self.cursor, text.chars());
+        self.cursor = start + text.chars().count();
+    }
+}
+
+/// The longest prefix all `words` share
+pub fn common_prefix(words: &[String]) -> String {
+    let Some((first, rest)) = words.split_first() else {
+        return String::new();
+    };
+    let mut len = first.len();
+    for word in rest {
+        len = first
+            .char_indices()
+            .zip(word.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8())
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+/// Reads lines from the terminal, keeping their history
+pub struct LineEditor {
+    history: Vec<String>,
+    max_history: usize,
+}
+
+/// Leaves raw mode when dropped
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Read a secret such as a token after showing `prompt` on stderr, without echoing it; `None`
+/// after Ctrl-C or Ctrl-D
+pub fn read_secret(prompt: &str) -> io::Result<Option<String>> {
+    let mut err = io::stderr();
+    write!(err, "{}", prompt)?;
+    err.flush()?;
+    let mut secret = String::new();
+    let _raw = RawMode::enable()?;
+    let entered = loop {
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Char('c' | 'd') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                break false
+            }
+            KeyCode::Enter => break true,
+            KeyCode::Char(c) => secret.push(c),
+            KeyCode::Backspace => {
+                secret.pop();
+            }
+            _ => {}
+        }
+    };
+    write!(err, "\r\n")?;
+    Ok(entered.then_some(secret))
+}
+
+/// What a key press asks the editor to do
+enum Outcome {
+    Continue,
+    Submit,
+    Cancel,
+    Eof,
+}
+
+/// State of one [`LineEditor::read_line`]
+struct Edit<'a> {
+    prompt: &'a str,
+    buffer: LineBuffer,
+    /// Terminal row of the cursor, counted from the prompt's first row
+    row: usize,
+    /// Position in the history, `history.len()` for the line being typed
+    recalled: usize,
+    /// The line being typed while the history is browsed
+    draft: String,
+}
+
+impl LineEditor {
+    /// An editor remembering the last `max_history` lines, starting with `history`
+    pub fn new(history: Vec<String>, max_history: usize) -> Self {
+        let mut editor = Self {
+            history: Vec::new(),
+            max_history,
+        };
+        for line in history {
+            editor.add_history(&line);
+        }
+        editor
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Remember a line, unless it repeats the previous one
+    pub fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > self.max_history {
+            let excess = self.history.len() - self.max_history;
+            self.history.drain(..excess);
+        }
+    }
+
+    /// Read one line after showing `prompt`; `None` at the end of input (Ctrl-D), an empty
+    /// line after Ctrl-C
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        complete: &mut Completer,
+    ) -> io::Result<Option<String>> {
+        let _raw = RawMode::enable()?;
+        let mut out = io::stdout();
+        let mut edit = Edit {
+            prompt,
+            buffer: LineBuffer::default(),
+            row: 0,
+            recalled: self.history.len(),
+            draft: String::new(),
+        };
+        edit.redraw(&mut out)?;
+
+        loop {
+            let key = match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                Event::Resize(..) => {
+                    edit.redraw(&mut out)?;
+                    continue;
+                }
+                _ => continue,
+            };
+            match self.handle_key(&mut edit, key, &mut out, complete)? {
+                Outcome::Continue => edit.redraw(&mut out)?,
+                Outcome::Submit => {
+                    edit.buffer.end();
+                    edit.redraw(&mut out)?;
+                    queue!(out, Print("\r\n"))?;
+                    out.flush()?;
+                    return Ok(Some(edit.buffer.text()));
+                }
+                Outcome::Cancel => {
+                    edit.buffer.end();
+                    edit.redraw(&mut out)?;
+                    queue!(out, Print("^C\r\n"))?;
+                    out.flush()?;
+                    return Ok(Some(String::new()));
+                }
+                Outcome::Eof => {
+                    queue!(out, Print("\r\n"))?;
+                    out.flush()?;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn handle_key(
+        &self,
+        edit: &mut Edit,
+        key: KeyEvent,
+        out: &mut impl Write,
+        complete: &mut Completer,
+    ) -> io::Result<Outcome> {
+        let buffer = &mut edit.buffer;
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('c') => return Ok(Outcome::Cancel),
+                KeyCode::Char('d') if buffer.is_empty() => return Ok(Outcome::Eof),
+                KeyCode::Char('d') => buffer.delete(),
+                KeyCode::Char('a') => buffer.home(),
+                KeyCode::Char('e') => buffer.end(),
+                KeyCode::Char('b') => buffer.left(),
+                KeyCode::Char('f') => buffer.right(),
+                KeyCode::Char('u') => buffer.kill_to_start(),
+                KeyCode::Char('k') => buffer.kill_to_end(),
+                KeyCode::Char('w') => buffer.delete_word(),
+                KeyCode::Char('p') => self.recall(edit, -1),
+                KeyCode::Char('n') => self.recall(edit, 1),
+                _ => {}
+            }
+            return Ok(Outcome::Continue);
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(Outcome::Submit),
+            KeyCode::Char(c) => buffer.insert(c),
+            KeyCode::Backspace => buffer.backspace(),
+            KeyCode::Delete => buffer.delete(),
+            KeyCode::Left => buffer.left(),
+            KeyCode::Right => buffer.right(),
+            KeyCode::Home => buffer.home(),
+            KeyCode::End => buffer.end(),
+            KeyCode::Up => self.recall(edit, -1),
+            KeyCode::Down => self.recall(edit, 1),
+            KeyCode::Tab => edit.complete(out, complete)?,
+            _ => {}
+        }
+        Ok(Outcome::Continue)
+    }
+
+    /// Show the previous (`-1`) or next (`1`) line of the history
+    fn recall(&self, edit: &mut Edit, step: isize) {
+        let Some(target) = edit.recalled.checked_add_signed(step) else {
+            return;
+        };
+        if target > self.history.len() {
+            return;
+        }
+        if edit.recalled == self.history.len() {
+            edit.draft = edit.buffer.text();
+        }
+        edit.recalled = target;
+        let text = self.history.get(target).unwrap_or(&edit.draft);
+        edit.buffer = LineBuffer::new(text);
+    }
+}
+
+impl Edit<'_> {
+    /// Draw the prompt and the line again, and put the cursor where it belongs
+    fn redraw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let width = terminal::size()
+            .map(|(columns, _)| usize::from(columns).max(1))
+            .unwrap_or(80);
+        let prompt = self.prompt.chars().count();
+
+        if self.row > 0 {
+            queue!(out, MoveUp(self.row as u16))?;
+        }
+        queue!(
+            out,
+            MoveToColumn(0),
+            Clear(ClearType::FromCursorDown),
+            Print(self.prompt),
+            Print(self.buffer.text())
+        )?;
+        // A line ending at the right margin leaves the cursor there until the next character
+        let end = prompt + self.buffer.len();
+        if end > 0 && end % width == 0 {
+            queue!(out, Print("\r\n"))?;
+        }
+        let target = prompt + self.buffer.cursor();
+        let (row, column) = (target / width, target % width);
+        if end / width > row {
+            queue!(out, MoveUp((end / width - row) as u16))?;
+        }
+        queue!(out, MoveToColumn(column as u16))?;
+        self.row = row;
+        out.flush()
+    }
+
+    /// Complete the word before the cursor, listing the candidates when there are several
+    fn complete(&mut self, out: &mut impl Write, complete: &mut Completer) -> io::Result<()> {
+        let (start, candidates) = complete(&self.buffer.text(), self.buffer.cursor());
+        match candidates.as_slice() {
+            [] => {}
+            [only] => {
+                let done = !only.ends_with(['/', '=']);
+                self.buffer.replace_before_cursor(start, only);
+                if done {
+                    self.buffer.insert(' ');
+                }
+            }
+            _ => {
+                let prefix = common_prefix(&candidates);
+                if prefix.chars().count() > self.buffer.cursor().saturating_sub(start) {
+                    self.buffer.replace_before_cursor(start, &prefix);
+                    return Ok(());
+                }
+                // List them below the line, then draw the prompt again after them
+                self.buffer.end();
+                self.redraw(out)?;
+                queue!(
+                    out,
+                    Print("\r\n"),
+                    Print(candidates.join("  ")),
+                    Print("\r\n")
+                )?;
+                self.row = 0;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_buffer_editing() {
+        let mut buffer = LineBuffer::new("git stats");
+        buffer.backspace();
+        buffer.insert('u');
+        buffer.insert('s');
+        assert_eq!(buffer.text(), "git status");
+
+        buffer.home();
+        buffer.right();
+        buffer.right();
+        buffer.right();
+        buffer.kill_to_end();
+        buffer.insert(' ');
+        buffer.insert('l');
+        assert_eq!(buffer.text(), "git l");
+        buffer.delete_word();
+        assert_eq!((buffer.text().as_str(), buffer.cursor()), ("git ", 4));
+
+        buffer.replace_before_cursor(0, "run -m tasks.yaml ");
+        assert_eq!(buffer.cursor(), 18);
+        buffer.left();
+        buffer.kill_to_start();
+        assert_eq!(buffer.text(), " ");
+    }
+
+    #[test]
+    fn test_common_prefix_and_history() {
+        let words = |list: &[&str]| list.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(common_prefix(&words(&["status", "stash", "stage"])), "sta");
+        assert_eq!(common_prefix(&words(&["--manifest", "--max"])), "--ma");
+        assert_eq!(common_prefix(&words(&["jobs", "git"])), "");
+        assert_eq!(common_prefix(&[]), "");
+
+        let mut editor = LineEditor::new(words(&["a", "b", "b", "c"]), 2);
+        assert_eq!(editor.history(), ["b", "c"]);
+        editor.add_history("c");
+        editor.add_history(" ");
+        assert_eq!(editor.history(), ["b", "c"]);
+    }
+}
@@ -1,15 +1,26 @@
 // file: src/logger.rs
-// version: 1.2.0
+// version: 1.4.0
 // guid: 5a9fbb43-1e0b-4bea-a858-b74b58176503
 
 use crate::error::Result;
 use chrono;
 use std::fs;
-use std::io;
+use std::io::{self, IsTerminal};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
-/// Setup logging for the application with both stdout and file output
-pub fn setup_logging() -> Result<()> {
+/// Set by the IDE backend for the commands it runs, whose stdout is captured as output
+pub const LOG_STDERR_ENV: &str = "SAFE_AI_UTIL_LOG_STDERR";
+
+/// Where console log lines are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Console {
+    Stdout,
+    /// Keeps stdout free for command output or protocol messages
+    Stderr,
+}
+
+/// Setup logging for the application with both console and file output
+pub fn setup_logging(console: Console) -> Result<()> {
     // Create logs directory if it doesn't exist
     let logs_dir = "logs";
     if !std::path::Path::new(logs_dir).exists() {
@@ -38,11 +49,20 @@ pub fn setup_logging() -> Result<()> {
         .append(true)
         .open(&log_filename)?;
 
-    // Create stdout layer
-    let stdout_layer = fmt::layer()
-        .with_target(false)
-        .with_writer(io::stdout)
-        .with_filter(filter_stdout);
+    // Create console layer
+    let stdout_layer = match console {
+        Console::Stdout => fmt::layer()
+            .with_target(false)
+            .with_writer(io::stdout)
+            .with_filter(filter_stdout)
+            .boxed(),
+        Console::Stderr => fmt::layer()
+            .with_target(false)
+            .with_ansi(io::stderr().is_terminal())
+            .with_writer(io::stderr)
+            .with_filter(filter_stdout)
+            .boxed(),
+    };
 
     // Create file layer
     let file_layer = fmt::layer()
@@ -51,14 +71,23 @@ pub fn setup_logging() -> Result<()> {
         .with_writer(file)
         .with_filter(filter_file);
 
-    // Initialize subscriber with both layers
-    tracing_subscriber::registry()
+    // Initialize subscriber with both layers (plus OTLP export when enabled)
+    let registry = tracing_subscriber::registry()
         .with(stdout_layer)
-        .with(file_layer)
-        .init();
+        .with(file_layer);
+
+    #[cfg(feature = "otel")]
+    let registry = registry.with(crate::telemetry::layer());
+
+    registry.init();
 
     tracing::info!(
-        "Logging initialized - writing to stdout and {}",
+        "Logging initialized - writing to {} and {}",
+        if console == Console::Stdout {
+            "stdout"
+        } else {
+            "stderr"
+        },
         log_filename
     );
 
@@ -1,18 +1,37 @@
 // file: src/main.rs
-// version: 2.3.0
+// version: 2.78.0
 // guid: 9dc55dfd-921c-4db5-84e1-fbccd6b03a6b
 
 use anyhow::Result;
 use clap::{Arg, ArgMatches, Command};
+use clap_complete::engine::ArgValueCandidates;
 use copilot_agent_util::{
-    commands::{awk, buf, editor, file, git, linter, prettier, python, sed, system, uutils},
-    config::Config,
+    commands::{
+        awk, batch, binaries, buf, cargo, cloud, cmake, completions, config, db, deps, diagnostics, docker, doctor, editor, errors, export, file, generated, gh, git, go, grep, helm, history, http, jobs, json, jvm,
+        kubectl, linter, make, node, pipe, pkg, pre_commit, prettier, publish, python, repl, replay, run, scaffold, scripts, sed, serve, service, sessions, setup, snapshot, shell, stats, system, terraform, todos, toml, toolchain, tools, transaction, uutils, yaml,
+    },
+    batch::InputSource,
+    config::{Config, LoadOptions, ResolvedConfig},
+    error_codes,
     executor::Executor,
-    logger::setup_logging,
+    ide,
+    intent::{self, Intent},
+    logger::{setup_logging, Console, LOG_STDERR_ENV},
+    path_map::PathMap,
+    process,
+    repl::ReplOptions,
+    security::policy::{Policy, Remedy},
+    session,
+    snapshot::CommandChanges,
+    telemetry,
+    workspace_lock::{self, LockOptions, WorkspaceLock},
+    AgentError,
 };
 use std::env;
 use std::fs;
-use tracing::{error, info};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info, info_span, Instrument};
 
 /// Helper function to append additional arguments from environment variable
 #[allow(dead_code)]
@@ -30,20 +49,154 @@ fn append_additional_args(mut args: Vec<String>) -> Vec<String> {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging first
-    setup_logging()?;
+    // A shell asking for completions is answered before anything else runs
+    clap_complete::CompleteEnv::with_factory(build_cli).complete();
 
-    // Load configuration
-    let config = Config::load().await?;
-
-    info!("Starting Safe AI Utility");
+    // Host paths given by an editor outside WSL or the dev container are translated first
+    let args: Vec<_> = env::args_os().collect();
+    let paths = PathMap::detect()?;
+    let translated = paths.translate_args(&args);
 
     // Build CLI
-    let app = build_cli();
-    let matches = app.get_matches();
+    let matches = build_cli().get_matches_from(translated.as_ref().unwrap_or(&args));
+
+    // Initialize logging first; in IDE and serve mode, and for commands run by them, stdout
+    // carries protocol messages or captured output, and completion scripts are sourced from it
+    let serving = matches.get_flag("ide") || matches.subcommand_name() == Some("serve");
+    let console = if serving
+        || matches.subcommand_name() == Some("completions")
+        || env::var_os(LOG_STDERR_ENV).is_some()
+    {
+        Console::Stderr
+    } else {
+        Console::Stdout
+    };
+
+    // The output of a command given host paths is for the host too, so this invocation runs
+    // again with its output translated back; protocol messages of the servers are left alone
+    if let (Some(args), false) = (&translated, serving) {
+        std::process::exit(paths.relay(args).await?);
+    }
+    setup_logging(console)?;
+
+    // Every log line of this invocation carries the session correlation IDs
+    let session = session::current();
+    let span = info_span!(
+        "session",
+        session_id = %session.session_id(),
+        execution_id = %session.execution_id(),
+        parent_execution_id = tracing::field::Empty,
+    );
+    if let Some(parent) = session.parent_execution_id() {
+        span.record("parent_execution_id", parent);
+    }
+
+    let result = run(matches).instrument(span).await;
+    telemetry::shutdown();
+    result
+}
+
+async fn run(matches: ArgMatches) -> Result<()> {
+    // The config command inspects and repairs configuration, so it must work even when the
+    // configuration does not load
+    let load_options = LoadOptions {
+        file: matches.get_one::<String>("config").map(Path::new),
+        profile: matches.get_one::<String>("profile").map(String::as_str),
+    };
+    if let Some(("config", sub_matches)) = matches.subcommand() {
+        return finish(config::execute(sub_matches, load_options).await);
+    }
+
+    // Load configuration, including the file given with --config and the selected profile
+    let resolved = Config::resolve(load_options).await?;
+    let mut config = resolved.config.clone();
+    if matches.get_flag("dry-run") {
+        config.safety.dry_run = true;
+    }
+    if matches.get_flag("simulate") {
+        config.safety.simulate = true;
+    }
+    if matches.get_flag("escalate") {
+        config.execution.escalate_on_failure = true;
+    }
+    if matches.get_flag("no-network") {
+        config.safety.no_network = true;
+    }
+    if let Some(image) = matches.get_one::<String>("in-container") {
+        config.container.image = Some(image.clone());
+    }
+    if matches.get_flag("container-read-only") {
+        config.container.read_only = true;
+    }
+    if let Some(target) = matches.get_one::<String>("target") {
+        config.remote.target = Some(target.clone());
+    }
+    if let Some(seconds) = matches.get_one::<u64>("budget-time") {
+        config.budget.max_wall_clock_seconds = Some(*seconds);
+    }
+    if let Some(count) = matches.get_one::<u64>("budget-subprocesses") {
+        config.budget.max_subprocesses = Some(*count);
+    }
+    if let Some(bytes) = matches.get_one::<u64>("budget-network-bytes") {
+        config.budget.max_network_bytes = Some(*bytes);
+    }
+    if let Some(seconds) = matches.get_one::<u64>("lock-timeout") {
+        config.lock.timeout_seconds = *seconds;
+    }
+
+    // Every command this invocation runs is recorded with why it was run
+    let labels = matches.get_many::<String>("label").unwrap_or_default().cloned().collect();
+    intent::set(Intent::new(matches.get_one::<String>("reason").cloned(), labels)?);
+
+    info!("Starting Safe AI Utility");
 
     // Create executor with config
-    let executor = Executor::new(config).await?;
+    let mut executor = Executor::new(config).await?;
+    // While a command runs its process group receives Ctrl-C; between commands it ends the run
+    process::exit_on_interrupt();
+    executor.set_allow_outside_workspace(matches.get_flag("allow-outside-workspace"));
+    if let Some(rules) = matches.get_many::<String>("allow-content") {
+        executor.set_content_overrides(rules.cloned().collect());
+    }
+    if let Some(path) = matches.get_one::<String>("stdin-file") {
+        let path = executor.check_path(path)?;
+        if !path.is_file() {
+            return Err(AgentError::file_not_found(format!("Input file {} does not exist", path.display())).into());
+        }
+        executor.set_stdin(Some(InputSource::File { file: path }));
+    }
+    executor.set_pty(matches.get_flag("pty"));
+    if matches.contains_id("report-changes") {
+        let roots: Vec<PathBuf> = matches.get_many::<String>("report-changes").unwrap_or_default().map(PathBuf::from).collect();
+        executor.set_report_changes(&roots)?;
+    }
+
+    if matches.get_flag("ide") {
+        return finish(serve_ide(&matches, executor).await);
+    }
+    if let Some(("repl", sub_matches)) = matches.subcommand() {
+        let options = ReplOptions {
+            forward_args: forward_args(&matches).into_iter().filter(|arg| !arg.starts_with("--profile=")).collect(),
+            config_file: matches.get_one::<String>("config").map(|file| fs::canonicalize(file).unwrap_or_else(|_| file.into())),
+            profile: matches.get_one::<String>("profile").cloned(),
+            commands: command_infos().into_iter().filter(|command| command.name != "repl").collect(),
+            cli: build_cli(),
+            history: !sub_matches.get_flag("no-history"),
+        };
+        return finish(repl::execute(&executor, options).await);
+    }
+    if let Some(("serve", sub_matches)) = matches.subcommand() {
+        match sub_matches.subcommand() {
+            Some(("grpc", grpc_matches)) => return finish(serve::execute_grpc(grpc_matches, executor).await),
+            Some(("http", http_matches)) => {
+                let result = serve::execute_http(http_matches, executor, command_infos(), forward_args(&matches)).await;
+                return finish(result);
+            }
+            _ => {}
+        }
+        let dispatch = |args| dispatch_request(args, &executor, load_options);
+        return finish(serve::execute(sub_matches, dispatch).await);
+    }
 
     // Read additional arguments from file if specified
     let mut additional_args = Vec::new();
@@ -63,19 +216,209 @@ async fn main() -> Result<()> {
             }
             Err(e) => {
                 error!("Failed to read args file {}: {}", args_file, e);
+                telemetry::shutdown();
                 std::process::exit(1);
             }
         }
     }
 
+    // Mutating commands in the same workspace run one at a time
+    let lock = match lock_workspace(&matches, &executor).await {
+        Ok(lock) => lock,
+        Err(e) => return finish(Err(e)),
+    };
+
     // Route to appropriate command handler
-    match execute_command(&matches, &executor, &additional_args).await {
+    let result = execute_command(&matches, &executor, &additional_args).await;
+    drop(lock);
+    let result = suggest_profiles(result, load_options, &resolved).await;
+
+    // A dry run or simulation ends with the ordered plan of every process that would have been
+    // spawned, including the steps before a failure
+    if executor.config().safety.dry_run {
+        match matches.get_one::<String>("plan-format").map(String::as_str) {
+            Some("json") => println!(
+                "{}",
+                serde_json::to_string_pretty(&executor.plan().to_json())?
+            ),
+            _ => print!("{}", executor.plan().render_text()),
+        }
+    }
+    if let Some(changes) = executor.changes() {
+        report_changes(&changes, matches.get_one::<String>("changes-format").map(String::as_str));
+    }
+    finish(result)
+}
+
+/// Serve the IDE backend; commands run through it get the same global options
+async fn serve_ide(matches: &ArgMatches, executor: Executor) -> Result<()> {
+    Ok(ide::serve(executor, command_infos(), forward_args(matches)).await?)
+}
+
+/// The subcommands, as advertised by the IDE backend and `serve http`
+fn command_infos() -> Vec<ide::CommandInfo> {
+    build_cli()
+        .get_subcommands()
+        .map(|command| ide::CommandInfo {
+            name: command.get_name().to_string(),
+            about: command
+                .get_about()
+                .map(|about| about.to_string())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Global options to pass on to the commands a server runs as separate invocations
+fn forward_args(matches: &ArgMatches) -> Vec<String> {
+    let mut forward_args = Vec::new();
+    // Commands run in the workspace folders, so the config file path must be absolute
+    if let Some(file) = matches.get_one::<String>("config") {
+        let file = fs::canonicalize(file).unwrap_or_else(|_| file.into());
+        forward_args.push(format!("--config={}", file.display()));
+    }
+    if let Some(profile) = matches.get_one::<String>("profile") {
+        forward_args.push(format!("--profile={}", profile));
+    }
+    if matches.get_flag("allow-outside-workspace") {
+        forward_args.push("--allow-outside-workspace".to_string());
+    }
+    for rule in matches.get_many::<String>("allow-content").unwrap_or_default() {
+        forward_args.push(format!("--allow-content={}", rule));
+    }
+    if let Some(seconds) = matches.get_one::<u64>("lock-timeout") {
+        forward_args.push(format!("--lock-timeout={}", seconds));
+    }
+    if let Some(reason) = matches.get_one::<String>("reason") {
+        forward_args.push(format!("--reason={}", reason));
+    }
+    for label in matches.get_many::<String>("label").unwrap_or_default() {
+        forward_args.push(format!("--label={}", label));
+    }
+    forward_args
+}
+
+/// Run one command line received by `serve`, such as `["git", "status"]`, in this process
+async fn dispatch_request(args: Vec<String>, executor: &Executor, load_options: LoadOptions<'_>) -> Result<()> {
+    let matches = match build_cli().try_get_matches_from(std::iter::once("safe-ai-util".to_string()).chain(args)) {
+        Ok(matches) => matches,
+        // Help and version are printed as output; usage errors fail the request
+        Err(e) if !e.use_stderr() => {
+            let _ = e.print();
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(("config", sub_matches)) = matches.subcommand() {
+        return config::execute(sub_matches, load_options).await;
+    }
+    let _lock = lock_workspace(&matches, executor).await?;
+    execute_command(&matches, executor, &[]).await
+}
+
+/// Take the workspace lock when the command mutates the workspace
+async fn lock_workspace(matches: &ArgMatches, executor: &Executor) -> Result<Option<WorkspaceLock>> {
+    let config = executor.config();
+    let command = command_path(matches);
+    if !config.lock.enabled || config.safety.dry_run || !workspace_lock::is_mutating(&command) {
+        return Ok(None);
+    }
+    let options = LockOptions {
+        timeout: Duration::from_secs(config.lock.timeout_seconds),
+        steal: matches.get_flag("steal-lock"),
+    };
+    let base = executor.watch_scope(&[])?.base;
+    Ok(Some(WorkspaceLock::acquire(&base, &command, options).await?))
+}
+
+/// Subcommand names of an invocation, e.g. `git stack update`
+fn command_path(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut current = matches;
+    while let Some((name, sub_matches)) = current.subcommand() {
+        names.push(name);
+        current = sub_matches;
+    }
+    names.join(" ")
+}
+
+/// Add the profiles whose policy would allow a command the policy denied to its explanation
+async fn suggest_profiles(
+    mut result: Result<()>,
+    options: LoadOptions<'_>,
+    resolved: &ResolvedConfig,
+) -> Result<()> {
+    let Some(AgentError::PolicyViolation(denial)) =
+        result.as_mut().err().and_then(|e| e.downcast_mut::<AgentError>())
+    else {
+        return result;
+    };
+    // Only denials by the command policy can be re-evaluated
+    if !denial.rules.iter().any(|rule| rule.starts_with("policy.")) {
+        return result;
+    }
+    for name in Config::profile_names(&resolved.value) {
+        if resolved.profile.as_deref() == Some(name.as_str()) {
+            continue;
+        }
+        let options = LoadOptions { profile: Some(&name), ..options };
+        let Ok(other) = Config::resolve(options).await else {
+            continue;
+        };
+        if Policy::from_config(&other.config.policy).is_ok_and(|policy| policy.reevaluate(denial).is_ok()) {
+            denial.remedies.push(Remedy::Profile { name });
+        }
+    }
+    result
+}
+
+/// Print the files each command created, modified and deleted on stderr (`--report-changes`)
+fn report_changes(changes: &[CommandChanges], format: Option<&str>) {
+    if format == Some("json") {
+        eprintln!("{}", serde_json::json!({ "changes": changes }));
+        return;
+    }
+    for command in changes {
+        if command.changes.is_empty() {
+            eprintln!("No files changed by `{}`", command.command);
+            continue;
+        }
+        eprintln!("Files changed by `{}`:", command.command);
+        let kinds = [
+            ("created", &command.changes.created),
+            ("modified", &command.changes.modified),
+            ("deleted", &command.changes.deleted),
+        ];
+        for (kind, paths) in kinds {
+            for path in paths {
+                eprintln!("  {:<9}{}", kind, path.display());
+            }
+        }
+    }
+}
+
+/// Log the outcome of a command and exit non-zero on failure
+fn finish(result: Result<()>) -> Result<()> {
+    match result {
         Ok(_) => {
             info!("Command executed successfully");
             Ok(())
         }
         Err(e) => {
-            error!("Command execution failed: {}", e);
+            let code = error_codes::classify(&e);
+            error!(
+                "Command execution failed: {} [{}; see `safe-ai-util errors explain {}`]",
+                e, code, code
+            );
+            // Agents recognize an exhausted budget, a policy denial or a missing tool from a
+            // structured line on stderr
+            match e.chain().find_map(|cause| cause.downcast_ref::<AgentError>()) {
+                Some(AgentError::BudgetExceeded(exceeded)) => eprintln!("{}", exceeded.to_json()),
+                Some(AgentError::PolicyViolation(denial)) => eprintln!("{}", denial.to_json()),
+                Some(AgentError::ToolUnavailable(request)) => eprintln!("{}", request.to_json()),
+                _ => {}
+            }
+            telemetry::shutdown();
             std::process::exit(1);
         }
     }
@@ -100,12 +443,157 @@ fn build_cli() -> Command {
                 .action(clap::ArgAction::SetTrue)
                 .help("Show what would be done without executing")
         )
+        .arg(
+            Arg::new("simulate")
+                .long("simulate")
+                .action(clap::ArgAction::SetTrue)
+                .help("Like --dry-run, but commands return fabricated results tagged as simulated")
+        )
+        .arg(
+            Arg::new("plan-format")
+                .long("plan-format")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Format of the execution plan printed by --dry-run and --simulate")
+        )
+        .arg(
+            Arg::new("ide")
+                .long("ide")
+                .action(clap::ArgAction::SetTrue)
+                .help("Serve JSON-RPC on stdin/stdout as the backend of an editor extension")
+        )
+        .arg(
+            Arg::new("allow-outside-workspace")
+                .long("allow-outside-workspace")
+                .action(clap::ArgAction::SetTrue)
+                .help("Permit reading and writing paths outside safety.workspace_roots")
+        )
+        .arg(
+            Arg::new("allow-content")
+                .long("allow-content")
+                .value_name("RULE")
+                .action(clap::ArgAction::Append)
+                .help("Permit writes matching this content policy rule (or 'all'); may be repeated")
+        )
         .arg(
             Arg::new("config")
                 .long("config")
                 .short('c')
                 .value_name("FILE")
-                .help("Specify custom configuration file")
+                .help("Additional configuration file, applied after system, user and project config")
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .short('p')
+                .value_name("NAME")
+                .help("Apply a named profile: dev, ci, restricted or one defined in config")
+                .add(ArgValueCandidates::new(completions::profile_candidates))
+        )
+        .arg(
+            Arg::new("budget-time")
+                .long("budget-time")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64))
+                .help("Wall-clock seconds this session may run (budget.max_wall_clock_seconds)")
+        )
+        .arg(
+            Arg::new("budget-subprocesses")
+                .long("budget-subprocesses")
+                .value_name("COUNT")
+                .value_parser(clap::value_parser!(u64))
+                .help("Subprocesses this session may spawn (budget.max_subprocesses)")
+        )
+        .arg(
+            Arg::new("budget-network-bytes")
+                .long("budget-network-bytes")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u64))
+                .help("Bytes built-in network operations may transfer (budget.max_network_bytes)")
+        )
+        .arg(
+            Arg::new("lock-timeout")
+                .long("lock-timeout")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64))
+                .help("Seconds to wait for the workspace lock held by another mutating command (lock.timeout_seconds)")
+        )
+        .arg(
+            Arg::new("steal-lock")
+                .long("steal-lock")
+                .action(clap::ArgAction::SetTrue)
+                .help("Take over the workspace lock from the command holding it")
+        )
+        .arg(
+            Arg::new("stdin-file")
+                .long("stdin-file")
+                .value_name("FILE")
+                .help("Feed this file to the standard input of the commands run, instead of the terminal")
+        )
+        .arg(
+            Arg::new("escalate")
+                .long("escalate")
+                .action(clap::ArgAction::SetTrue)
+                .help("Re-run a failing command once with its tool's verbose flags and keep that output in history")
+        )
+        .arg(
+            Arg::new("no-network")
+                .long("no-network")
+                .action(clap::ArgAction::SetTrue)
+                .help("Run commands with networking disabled, so tests and builds cannot download anything (Linux and macOS)")
+        )
+        .arg(
+            Arg::new("in-container")
+                .long("in-container")
+                .value_name("IMAGE")
+                .help("Run commands in a container of IMAGE with the workspace mounted, using docker or podman (container.image)")
+        )
+        .arg(
+            Arg::new("container-read-only")
+                .long("container-read-only")
+                .action(clap::ArgAction::SetTrue)
+                .help("Mount the workspace read-only in the --in-container container")
+        )
+        .arg(
+            Arg::new("target")
+                .long("target")
+                .value_name("URL")
+                .help("Run commands on another machine over SSH, e.g. ssh://ci@build01/srv/app (remote.target)")
+        )
+        .arg(
+            Arg::new("pty")
+                .long("pty")
+                .action(clap::ArgAction::SetTrue)
+                .help("Run commands in a pseudo-terminal, for tools that misbehave without one")
+        )
+        .arg(
+            Arg::new("reason")
+                .long("reason")
+                .value_name("TEXT")
+                .help("Why this is being run, e.g. \"fixing issue #123\"; recorded in history, the audit log and commit messages")
+        )
+        .arg(
+            Arg::new("label")
+                .long("label")
+                .value_name("LABEL")
+                .action(clap::ArgAction::Append)
+                .help("Label recorded with the reason; may be repeated")
+        )
+        .arg(
+            Arg::new("report-changes")
+                .long("report-changes")
+                .value_name("PATH")
+                .num_args(0..=1)
+                .require_equals(true)
+                .value_delimiter(',')
+                .help("Report the files each command creates, modifies and deletes, in the workspace or the given comma-separated paths")
+        )
+        .arg(
+            Arg::new("changes-format")
+                .long("changes-format")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Format of the report printed by --report-changes")
         )
         .arg(
             Arg::new("args-file")
@@ -116,14 +604,67 @@ fn build_cli() -> Command {
         .subcommand(git::build_command())
         .subcommand(file::build_command())
         .subcommand(buf::build_command())
+        .subcommand(cargo::build_command())
+        .subcommand(go::build_command())
+        .subcommand(docker::build_command())
+        .subcommand(kubectl::build_command())
+        .subcommand(terraform::build_command())
+        .subcommand(make::build_command())
+        .subcommand(make::build_just_command())
+        .subcommand(gh::build_command())
+        .subcommand(http::build_command())
+        .subcommand(grep::build_command())
+        .subcommand(json::build_command())
+        .subcommand(toml::build_command())
+        .subcommand(yaml::build_command())
         .subcommand(python::build_command())
         .subcommand(system::build_command())
         .subcommand(linter::build_command())
+        .subcommand(node::build_command())
+        .subcommand(diagnostics::build_command())
         .subcommand(prettier::build_command())
+        .subcommand(scripts::build_command())
         .subcommand(sed::build_command())
         .subcommand(awk::build_command())
         .subcommand(editor::build_command())
         .subcommand(uutils::build_command())
+        .subcommand(scaffold::build_command())
+        .subcommand(stats::build_command())
+        .subcommand(config::build_command())
+        .subcommand(deps::build_command())
+        .subcommand(todos::build_command())
+        .subcommand(doctor::build_command())
+        .subcommand(export::build_command())
+        .subcommand(sessions::build_command())
+        .subcommand(shell::build_command())
+        .subcommand(batch::build_command())
+        .subcommand(run::build_command())
+        .subcommand(pipe::build_command())
+        .subcommand(jobs::build_command())
+        .subcommand(history::build_command())
+        .subcommand(replay::build_command())
+        .subcommand(transaction::build_command())
+        .subcommand(generated::build_command())
+        .subcommand(pre_commit::build_command())
+        .subcommand(pkg::build_command())
+        .subcommand(service::build_command())
+        .subcommand(cloud::build_command())
+        .subcommand(helm::build_command())
+        .subcommand(db::build_command())
+        .subcommand(jvm::build_command())
+        .subcommand(cmake::build_command())
+        .subcommand(snapshot::build_command())
+        .subcommand(serve::build_command())
+        .subcommand(setup::build_command())
+        .subcommand(completions::build_command())
+        .subcommand(tools::build_command())
+        .subcommand(toolchain::build_command())
+        .subcommand(copilot_agent_util::commands::process::build_command())
+        .subcommand(publish::build_command())
+        .subcommand(repl::build_command())
+        .subcommand(errors::build_command())
+        .subcommand(binaries::build_command())
+        .subcommand(copilot_agent_util::commands::env::build_command())
 }
 
 async fn execute_command(
@@ -140,14 +681,66 @@ async fn execute_command(
         Some(("git", sub_matches)) => git::execute(sub_matches, executor).await,
         Some(("file", sub_matches)) => file::execute(sub_matches, executor).await,
         Some(("buf", sub_matches)) => buf::execute(sub_matches, executor).await,
+        Some(("cargo", sub_matches)) => cargo::execute(sub_matches, executor).await,
+        Some(("go", sub_matches)) => go::execute(sub_matches, executor).await,
+        Some(("docker", sub_matches)) => docker::execute(sub_matches, executor).await,
+        Some(("kubectl", sub_matches)) => kubectl::execute(sub_matches, executor).await,
+        Some(("terraform", sub_matches)) => terraform::execute(sub_matches, executor).await,
+        Some(("make", sub_matches)) => make::execute(sub_matches, executor).await,
+        Some(("just", sub_matches)) => make::execute_just(sub_matches, executor).await,
+        Some(("gh", sub_matches)) => gh::execute(sub_matches, executor).await,
+        Some(("http", sub_matches)) => http::execute(sub_matches, executor).await,
+        Some(("grep", sub_matches)) => grep::execute(sub_matches, executor).await,
+        Some(("json", sub_matches)) => json::execute(sub_matches, executor).await,
+        Some(("toml", sub_matches)) => toml::execute(sub_matches, executor).await,
+        Some(("yaml", sub_matches)) => yaml::execute(sub_matches, executor).await,
         Some(("python", sub_matches)) => python::execute(sub_matches, executor).await,
         Some(("system", sub_matches)) => system::execute(sub_matches, executor).await,
         Some(("linter", sub_matches)) => linter::execute(sub_matches, executor).await,
+        Some(("node", sub_matches)) => node::execute(sub_matches, executor).await,
+        Some(("diagnostics", sub_matches)) => diagnostics::execute(sub_matches, executor).await,
         Some(("prettier", sub_matches)) => prettier::execute(sub_matches, executor).await,
+        Some(("scripts", sub_matches)) => scripts::execute(sub_matches, executor).await,
         Some(("sed", sub_matches)) => sed::execute(sub_matches, executor).await,
         Some(("awk", sub_matches)) => awk::execute(sub_matches, executor).await,
         Some(("editor", sub_matches)) => editor::execute(sub_matches, executor).await,
         Some(("uutils", sub_matches)) => uutils::execute(sub_matches, executor).await,
+        Some(("scaffold", sub_matches)) => scaffold::execute(sub_matches, executor).await,
+        Some(("stats", sub_matches)) => stats::execute(sub_matches, executor).await,
+        Some(("deps", sub_matches)) => deps::execute(sub_matches, executor).await,
+        Some(("todos", sub_matches)) => todos::execute(sub_matches, executor).await,
+        Some(("doctor", sub_matches)) => doctor::execute(sub_matches, executor).await,
+        Some(("sessions", sub_matches)) => sessions::execute(sub_matches, executor).await,
+        Some(("shell", sub_matches)) => shell::execute(sub_matches, executor).await,
+        Some(("batch", sub_matches)) => batch::execute(sub_matches, executor).await,
+        Some(("run", sub_matches)) => run::execute(sub_matches, executor).await,
+        Some(("pipe", sub_matches)) => pipe::execute(sub_matches, executor).await,
+        Some(("jobs", sub_matches)) => jobs::execute(sub_matches, executor).await,
+        Some(("history", sub_matches)) => history::execute(sub_matches, executor).await,
+        Some(("replay", sub_matches)) => replay::execute(sub_matches, executor).await,
+        Some(("transaction", sub_matches)) => transaction::execute(sub_matches, executor).await,
+        Some(("generated", sub_matches)) => generated::execute(sub_matches, executor).await,
+        Some(("pre-commit", sub_matches)) => pre_commit::execute(sub_matches, executor).await,
+        Some(("pkg", sub_matches)) => pkg::execute(sub_matches, executor).await,
+        Some(("service", sub_matches)) => service::execute(sub_matches, executor).await,
+        Some(("cloud", sub_matches)) => cloud::execute(sub_matches, executor).await,
+        Some(("helm", sub_matches)) => helm::execute(sub_matches, executor).await,
+        Some(("db", sub_matches)) => db::execute(sub_matches, executor).await,
+        Some(("jvm", sub_matches)) => jvm::execute(sub_matches, executor).await,
+        Some(("cmake", sub_matches)) => cmake::execute(sub_matches, executor).await,
+        Some(("snapshot", sub_matches)) => snapshot::execute(sub_matches, executor).await,
+        Some(("export", sub_matches)) => export::execute(sub_matches, executor, &build_cli()).await,
+        Some(("setup", sub_matches)) => setup::execute(sub_matches, executor, build_cli()).await,
+        Some(("completions", sub_matches)) => completions::execute(sub_matches, &build_cli()),
+        Some(("tools", sub_matches)) => tools::execute(sub_matches, executor).await,
+        Some(("toolchain", sub_matches)) => toolchain::execute(sub_matches, executor).await,
+        Some(("publish", sub_matches)) => publish::execute(sub_matches, executor).await,
+        Some(("errors", sub_matches)) => errors::execute(sub_matches).await,
+        Some(("binaries", sub_matches)) => binaries::execute(sub_matches, executor).await,
+        Some(("env", sub_matches)) => copilot_agent_util::commands::env::execute(sub_matches, executor).await,
+        Some(("process", sub_matches)) => {
+            copilot_agent_util::commands::process::execute(sub_matches, executor).await
+        }
         _ => {
             println!("No command specified. Use --help for usage information.");
             Ok(())
@@ -0,0 +1,849 @@
+// file: src/manifest.rs
+// version: 1.5.0
+// guid: 6c2e8f4a-3b1d-4a7e-9c5f-8d0b2e6a4f17
+
+//! Task manifests: named steps run as a dependency graph
+//!
+//! A manifest (`tasks.yaml`, or `tasks.toml` with `[[steps]]` tables) lists steps with a
+//! command, arguments, working directory, environment and the steps they depend on. `run
+//! --manifest` starts every step whose dependencies have finished, at most
+//! [`Manifest::jobs`] at a time, each through the same validation and capture path as a
+//! single command. A step that fails stops the run unless it sets `continue_on_error`, and
+//! steps depending on a failed step are skipped. A `budget` table, with the keys of the
+//! `[budget]` configuration section, limits the run further: steps that would exceed it fail
+//! with a budget error.
+//!
+//! ```yaml
+//! jobs: 2
+//! budget: { max_wall_clock_seconds: 600, max_subprocesses: 20 }
+//! steps:
+//!   - name: fmt
+//!     command: cargo
+//!     args: [fmt, --check]
+//!   - name: test
+//!     command: cargo
+//!     args: [test]
+//!     env: { RUST_BACKTRACE: "1" }
+//!     depends_on: [fmt]
+//! ```
+//!
+//! A step with a `matrix` runs once per combination of its values, as steps named like
+//! `test.python-3.11`, which run in parallel like any independent steps. `${matrix.KEY}` in
+//! the command, arguments, working directory, environment and `tools` is replaced by the
+//! variant's value, which is also exported as `MATRIX_KEY`. `tools` puts the directories of
+//! tool versions installed with mise first in `PATH`; steps cannot add other directories, so
+//! an allowed command name never runs a script from the workspace. Depending on a matrix step means depending on its variants that
+//! agree on the matrix keys both steps share, and [`Manifest::select`] and
+//! [`Manifest::matrix_report`] treat the variants as a group.
+//!
+//! ```yaml
+//! steps:
+//!   - name: test
+//!     matrix: { python: ["3.10", "3.11", "3.12"] }
+//!     tools: { python: "${matrix.python}" }
+//!     command: python
+//!     args: [-m, pytest]
+//!   - name: web
+//!     matrix: { node: ["18", "20"] }
+//!     tools: { node: "${matrix.node}" }
+//!     command: npm
+//!     args: [test]
+//! ```
+
+use crate::batch::{BatchResult, BatchStatus, CommandSpec, InputSource};
+use crate::config::BudgetConfig;
+use crate::error::{AgentError, Result};
+use crate::executor::Executor;
+use crate::tools;
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// A named step of a manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    pub name: String,
+    #[serde(flatten)]
+    pub spec: CommandSpec,
+    /// Steps that must finish before this one starts
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Whether dependents still run and the run goes on when this step fails
+    #[serde(default)]
+    pub continue_on_error: bool,
+    /// Values to run the step with, one variant per combination
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub matrix: BTreeMap<String, Vec<String>>,
+    /// Not supported: a manifest setting it is refused, since it would let a workspace
+    /// directory decide which binary runs under an allowed command name (use `tools`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<PathBuf>,
+    /// Tool versions installed with mise to put first in `PATH`, e.g. `node: "20"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tools: BTreeMap<String, String>,
+    /// The matrix step this step is a variant of, and its values
+    #[serde(skip)]
+    pub variant: Option<Variant>,
+}
+
+/// The matrix values of one variant of a matrix step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Variant {
+    /// Name of the matrix step
+    pub group: String,
+    pub values: BTreeMap<String, String>,
+}
+
+/// Outcome of every variant of one matrix step, from [`Manifest::matrix_report`]
+#[derive(Debug, Clone, Serialize)]
+pub struct MatrixReport {
+    pub step: String,
+    pub keys: Vec<String>,
+    pub passed: usize,
+    pub failed: usize,
+    pub variants: Vec<VariantOutcome>,
+}
+
+/// Outcome of one variant in a [`MatrixReport`]
+#[derive(Debug, Clone, Serialize)]
+pub struct VariantOutcome {
+    pub name: String,
+    pub values: BTreeMap<String, String>,
+    pub status: BatchStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+}
+
+/// A parsed and validated manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Steps running at the same time (default: number of CPUs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+    /// Limits of the run, on top of the session's budget
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget: Option<BudgetConfig>,
+    pub steps: Vec<Step>,
+}
+
+/// Options for [`Manifest::run`]
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Steps running at the same time; at least one
+    pub jobs: usize,
+    /// Start the remaining independent steps after a failure instead of stopping
+    pub keep_going: bool,
+    /// Directory receiving `<step>.log` with each step's output
+    pub log_dir: Option<PathBuf>,
+}
+
+/// Result of one step of a manifest run
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    pub name: String,
+    #[serde(flatten)]
+    pub result: BatchResult,
+}
+
+impl Manifest {
+    /// Read a manifest, as TOML for `.toml` files and YAML otherwise; relative working
+    /// directories and input files are resolved against the manifest's directory
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            AgentError::file_not_found(format!("Cannot read manifest {}: {}", path.display(), e))
+        })?;
+        let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+        let mut manifest = Self::parse(&text, is_toml)
+            .map_err(|e| AgentError::config(format!("{}: {}", path.display(), e)))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for step in &mut manifest.steps {
+            if let Some(cwd) = step.spec.cwd.as_mut().filter(|cwd| cwd.is_relative()) {
+                *cwd = base.join(&*cwd);
+            }
+            if let Some(InputSource::File { file: input }) = step.spec.stdin.as_mut() {
+                if input.is_relative() {
+                    *input = base.join(&*input);
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Parse and validate manifest text, expanding matrix steps into their variants
+    pub fn parse(text: &str, is_toml: bool) -> Result<Self> {
+        let mut manifest: Self = if is_toml {
+            toml::from_str(text).map_err(|e| AgentError::config(e.to_string()))?
+        } else {
+            serde_yaml::from_str(text).map_err(|e| AgentError::config(e.to_string()))?
+        };
+        manifest.expand_matrices()?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Replace each matrix step by one step per combination of its values, and point
+    /// dependencies on a matrix step at its matching variants
+    fn expand_matrices(&mut self) -> Result<()> {
+        if self.steps.iter().all(|s| s.matrix.is_empty()) {
+            return Ok(());
+        }
+
+        let mut steps = Vec::new();
+        // Name and values of every variant, to match dependencies against
+        let mut variants: Vec<(String, Variant)> = Vec::new();
+        for step in std::mem::take(&mut self.steps) {
+            if step.matrix.is_empty() {
+                steps.push(step);
+                continue;
+            }
+            for (key, values) in &step.matrix {
+                let valid = !key.is_empty()
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid {
+                    return Err(AgentError::config(format!(
+                        "Invalid matrix key '{}' in step '{}': use letters, digits and '_'",
+                        key, step.name
+                    )));
+                }
+                if values.is_empty() {
+                    return Err(AgentError::config(format!(
+                        "Matrix key '{}' of step '{}' has no values",
+                        key, step.name
+                    )));
+                }
+            }
+
+            for values in combinations(&step.matrix) {
+                let variant = step.variant_for(values)?;
+                variants.push((variant.name.clone(), variant.variant.clone().unwrap()));
+                steps.push(variant);
+            }
+        }
+
+        for step in &mut steps {
+            let own = step.variant.as_ref().map(|v| &v.values);
+            let mut depends_on = Vec::new();
+            for dependency in &step.depends_on {
+                if !variants.iter().any(|(_, v)| &v.group == dependency) {
+                    depends_on.push(dependency.clone());
+                    continue;
+                }
+                let before = depends_on.len();
+                depends_on.extend(
+                    variants
+                        .iter()
+                        .filter(|(_, v)| &v.group == dependency)
+                        .filter(|(_, v)| {
+                            own.map_or(true, |own| {
+                                v.values
+                                    .iter()
+                                    .all(|(key, value)| own.get(key).map_or(true, |o| o == value))
+                            })
+                        })
+                        .map(|(name, _)| name.clone()),
+                );
+                if depends_on.len() == before {
+                    return Err(AgentError::config(format!(
+                        "Step '{}' depends on '{}', which has no variant with the same matrix values",
+                        step.name, dependency
+                    )));
+                }
+            }
+            step.depends_on = depends_on;
+        }
+        self.steps = steps;
+        Ok(())
+    }
+
+    /// Check step names and dependencies, and that the steps form no cycle
+    fn validate(&self) -> Result<()> {
+        if self.jobs == Some(0) {
+            return Err(AgentError::config("jobs must be at least 1"));
+        }
+
+        let mut names = HashSet::new();
+        for step in &self.steps {
+            let valid = !step.name.is_empty()
+                && step
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+            if !valid {
+                return Err(AgentError::config(format!(
+                    "Invalid step name '{}': use letters, digits, '-', '_' and '.'",
+                    step.name
+                )));
+            }
+            if !step.path.is_empty() {
+                return Err(AgentError::config(format!(
+                    "Step '{}' sets path, which is not supported: use tools for mise tool versions",
+                    step.name
+                )));
+            }
+            if !names.insert(step.name.as_str()) {
+                return Err(AgentError::config(format!(
+                    "Duplicate step '{}'",
+                    step.name
+                )));
+            }
+        }
+        for step in &self.steps {
+            if let Some(missing) = step.depends_on.iter().find(|d| !names.contains(d.as_str())) {
+                return Err(AgentError::config(format!(
+                    "Step '{}' depends on unknown step '{}'",
+                    step.name, missing
+                )));
+            }
+        }
+
+        // Kahn's algorithm: whatever cannot be ordered is part of a cycle
+        let mut blocking: HashMap<&str, usize> = self
+            .steps
+            .iter()
+            .map(|s| (s.name.as_str(), s.depends_on.len()))
+            .collect();
+        let mut ready: VecDeque<&str> = blocking
+            .iter()
+            .filter(|(_, n)| **n == 0)
+            .map(|(name, _)| *name)
+            .collect();
+        let mut ordered = 0;
+        while let Some(name) = ready.pop_front() {
+            ordered += 1;
+            for step in self
+                .steps
+                .iter()
+                .filter(|s| s.depends_on.iter().any(|d| d == name))
+            {
+                let count = blocking.get_mut(step.name.as_str()).unwrap();
+                *count -= step.depends_on.iter().filter(|d| *d == name).count();
+                if *count == 0 {
+                    ready.push_back(&step.name);
+                }
+            }
+        }
+        if ordered < self.steps.len() {
+            let mut cyclic: Vec<&str> = blocking
+                .into_iter()
+                .filter(|(_, n)| *n > 0)
+                .map(|(name, _)| name)
+                .collect();
+            cyclic.sort_unstable();
+            return Err(AgentError::config(format!(
+                "Dependency cycle between steps: {}",
+                cyclic.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Keep only the named steps and the steps they depend on, directly or not
+    pub fn select(&mut self, targets: &[String]) -> Result<()> {
+        if targets.is_empty() {
+            return Ok(());
+        }
+        let mut wanted = HashSet::new();
+        let mut queue: Vec<&str> = targets.iter().map(String::as_str).collect();
+        while let Some(name) = queue.pop() {
+            // A matrix step's name stands for all of its variants
+            let steps: Vec<&Step> = match self.steps.iter().find(|s| s.name == name) {
+                Some(step) => vec![step],
+                None => self
+                    .steps
+                    .iter()
+                    .filter(|s| s.variant.as_ref().is_some_and(|v| v.group == name))
+                    .collect(),
+            };
+            if steps.is_empty() {
+                return Err(AgentError::invalid_argument(format!(
+                    "Unknown step '{}'",
+                    name
+                )));
+            }
+            for step in steps {
+                if wanted.insert(step.name.clone()) {
+                    queue.extend(step.depends_on.iter().map(String::as_str));
+                }
+            }
+        }
+        self.steps.retain(|s| wanted.contains(&s.name));
+        Ok(())
+    }
+
+    /// Run the steps in dependency order; results come back in manifest order
+    pub async fn run(&self, executor: &Executor, options: &RunOptions) -> Vec<StepResult> {
+        if let Some(dir) = &options.log_dir {
+            if let Err(e) = fs::create_dir_all(dir) {
+                warn!("Cannot create log directory {}: {}", dir.display(), e);
+            }
+        }
+
+        let mut done: HashMap<&str, BatchResult> = HashMap::new();
+        let mut pending: Vec<&Step> = self.steps.iter().collect();
+        let mut running = FuturesUnordered::new();
+        let mut stopped = false;
+
+        loop {
+            // Skipping a step can make its dependents skippable too, so repeat until stable
+            let mut changed = true;
+            while changed {
+                changed = false;
+                let mut i = 0;
+                while i < pending.len() {
+                    let step = pending[i];
+                    if !step
+                        .depends_on
+                        .iter()
+                        .all(|d| done.contains_key(d.as_str()))
+                    {
+                        i += 1;
+                        continue;
+                    }
+                    let blocked_by = step
+                        .depends_on
+                        .iter()
+                        .find(|d| !self.satisfied(d, &done[d.as_str()]));
+                    if let Some(dependency) = blocked_by {
+                        info!(
+                            "Step '{}' skipped: '{}' did not succeed",
+                            step.name, dependency
+                        );
+                        let error = format!("dependency '{}' did not succeed", dependency);
+                        done.insert(
+                            &step.name,
+                            BatchResult::without_output(
+                                step.spec.clone(),
+                                BatchStatus::Skipped,
+                                Some(error),
+                            ),
+                        );
+                        pending.remove(i);
+                        changed = true;
+                    } else if !stopped && running.len() < options.jobs.max(1) {
+                        info!("Step '{}' started: {}", step.name, step.spec);
+                        running.push(run_step(executor, step));
+                        pending.remove(i);
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+
+            let Some((step, result)) = running.next().await else {
+                break;
+            };
+            info!(
+                "Step '{}' {} in {} ms",
+                step.name,
+                result.status.as_str(),
+                result.duration_ms
+            );
+            if let Some(dir) = &options.log_dir {
+                write_log(dir, step, &result);
+            }
+            if !self.satisfied(&step.name, &result) && !options.keep_going {
+                stopped = true;
+            }
+            done.insert(&step.name, result);
+        }
+
+        for step in pending {
+            let error = "run stopped after a failure".to_string();
+            done.insert(
+                &step.name,
+                BatchResult::without_output(step.spec.clone(), BatchStatus::Skipped, Some(error)),
+            );
+        }
+
+        self.steps
+            .iter()
+            .map(|step| StepResult {
+                name: step.name.clone(),
+                result: done.remove(step.name.as_str()).unwrap(),
+            })
+            .collect()
+    }
+
+    /// Collect the results of the variants of each matrix step, in manifest order
+    pub fn matrix_report(&self, results: &[StepResult]) -> Vec<MatrixReport> {
+        let mut reports: Vec<MatrixReport> = Vec::new();
+        for (step, result) in self.steps.iter().zip(results) {
+            let Some(variant) = &step.variant else {
+                continue;
+            };
+            let index = match reports.iter().position(|r| r.step == variant.group) {
+                Some(index) => index,
+                None => {
+                    reports.push(MatrixReport {
+                        step: variant.group.clone(),
+                        keys: variant.values.keys().cloned().collect(),
+                        passed: 0,
+                        failed: 0,
+                        variants: Vec::new(),
+                    });
+                    reports.len() - 1
+                }
+            };
+            let report = &mut reports[index];
+            if result.result.status == BatchStatus::Succeeded {
+                report.passed += 1;
+            } else {
+                report.failed += 1;
+            }
+            report.variants.push(VariantOutcome {
+                name: step.name.clone(),
+                values: variant.values.clone(),
+                status: result.result.status,
+                exit_code: result.result.exit_code,
+                duration_ms: result.result.duration_ms,
+            });
+        }
+        reports
+    }
+
+    /// Whether a finished step lets its dependents run
+    fn satisfied(&self, name: &str, result: &BatchResult) -> bool {
+        result.status == BatchStatus::Succeeded
+            || (result.status != BatchStatus::Skipped
+                && self
+                    .steps
+                    .iter()
+                    .any(|s| s.name == name && s.continue_on_error))
+    }
+}
+
+impl Step {
+    /// The variant of this matrix step for one combination of its values
+    fn variant_for(&self, values: BTreeMap<String, String>) -> Result<Step> {
+        let pattern = Regex::new(r"\$\{matrix\.([^}]*)\}").unwrap();
+        let mut missing = None;
+        let mut substitute = |text: &str| -> String {
+            pattern
+                .replace_all(text, |caps: &Captures| match values.get(&caps[1]) {
+                    Some(value) => value.clone(),
+                    None => {
+                        missing.get_or_insert_with(|| caps[1].to_string());
+                        String::new()
+                    }
+                })
+                .into_owned()
+        };
+
+        let suffix: Vec<String> = values
+            .iter()
+            .map(|(key, value)| {
+                let value: String = value
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '-' })
+                    .collect();
+                format!("{}-{}", key, value)
+            })
+            .collect();
+        let mut step = self.clone();
+        step.name = format!("{}.{}", self.name, suffix.join("."));
+        step.matrix.clear();
+        step.spec.command = substitute(&self.spec.command);
+        step.spec.args = self.spec.args.iter().map(|a| substitute(a)).collect();
+        step.spec.cwd = self
+            .spec
+            .cwd
+            .as_ref()
+            .map(|cwd| PathBuf::from(substitute(&cwd.to_string_lossy())));
+        for value in step.spec.env.values_mut().chain(step.tools.values_mut()) {
+            *value = substitute(value);
+        }
+        if let Some(key) = missing {
+            return Err(AgentError::config(format!(
+                "Step '{}' uses ${{matrix.{}}}, which is not in its matrix",
+                self.name, key
+            )));
+        }
+        for (key, value) in &values {
+            step.spec
+                .env
+                .entry(format!("MATRIX_{}", key.to_ascii_uppercase()))
+                .or_insert_with(|| value.clone());
+        }
+        step.variant = Some(Variant {
+            group: self.name.clone(),
+            values,
+        });
+        Ok(step)
+    }
+
+    /// The command to run, with the mise `tools` directories first in `PATH`
+    fn command_spec(&self) -> Result<CommandSpec> {
+        if self.tools.is_empty() {
+            return Ok(self.spec.clone());
+        }
+        let mut dirs = Vec::new();
+        for (tool, version) in &self.tools {
+            dirs.push(tools::mise_bin_dir(tool, version)?.to_string_lossy().into_owned());
+        }
+        let inherited = self
+            .spec
+            .env
+            .get("PATH")
+            .cloned()
+            .or_else(|| std::env::var("PATH").ok())
+            .unwrap_or_default();
+        dirs.extend(inherited.split(':').filter(|e| e.starts_with('/')).map(str::to_string));
+
+        let mut spec = self.spec.clone();
+        spec.env.insert("PATH".to_string(), dirs.join(":"));
+        Ok(spec)
+    }
+}
+
+/// Every combination of one value per matrix key
+fn combinations(matrix: &BTreeMap<String, Vec<String>>) -> Vec<BTreeMap<String, String>> {
+    let mut combinations = vec![BTreeMap::new()];
+    for (key, values) in matrix {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.insert(key.clone(), value.clone());
+                    combination
+                })
+            })
+            .collect();
+    }
+    combinations
+}
+
+async fn run_step<'a>(executor: &Executor, step: &'a Step) -> (&'a Step, BatchResult) {
+    let started = Instant::now();
+    let outcome = match step.command_spec() {
+        Ok(spec) => executor.execute_spec_capture(&spec).await,
+        Err(e) => Err(e.into()),
+    };
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let result = match outcome {
+        Ok(output) => BatchResult {
+            spec: step.spec.clone(),
+            status: if output.success() {
+                BatchStatus::Succeeded
+            } else {
+                BatchStatus::Failed
+            },
+            exit_code: output.exit_code,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            truncated: output.truncated,
+            duration_ms,
+            error: None,
+        },
+        Err(e) => BatchResult {
+            duration_ms,
+            ..BatchResult::without_output(
+                step.spec.clone(),
+                BatchStatus::Error,
+                Some(e.to_string()),
+            )
+        },
+    };
+    (step, result)
+}
+
+/// Write a step's command, status and output to `<dir>/<step>.log`
+fn write_log(dir: &Path, step: &Step, result: &BatchResult) {
+    let mut log = format!("$ {}\nstatus: {}\n", step.spec, result.status.as_str());
+    if let Some(code) = result.exit_code {
+        log.push_str(&format!("exit code: {}\n", code));
+    }
+    if let Some(error) = &result.error {
+        log.push_str(&format!("error: {}\n", error));
+    }
+    log.push_str(&format!(
+        "\n--- stdout ---\n{}\n--- stderr ---\n{}",
+        result.stdout, result.stderr
+    ));
+
+    let path = dir.join(format!("{}.log", step.name));
+    if let Err(e) = fs::write(&path, log) {
+        warn!("Cannot write step log {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"
+jobs: 2
+steps:
+  - name: build
+    command: cargo
+    args: [build]
+  - name: test
+    command: cargo
+    args: [test]
+    env: { RUST_BACKTRACE: "1" }
+    depends_on: [build]
+  - name: docs
+    command: cargo
+    args: [doc]
+    cwd: docs
+    continue_on_error: true
+"#;
+
+    #[test]
+    fn test_parse_yaml_and_toml() {
+        let manifest = Manifest::parse(YAML, false).unwrap();
+        assert_eq!(manifest.jobs, Some(2));
+        assert_eq!(manifest.steps.len(), 3);
+        assert_eq!(manifest.steps[1].depends_on, vec!["build"]);
+        assert_eq!(manifest.steps[1].spec.env["RUST_BACKTRACE"], "1");
+        assert_eq!(manifest.steps[2].spec.cwd, Some(PathBuf::from("docs")));
+        assert!(manifest.steps[2].continue_on_error);
+
+        assert_eq!(manifest.budget, None);
+
+        let toml = r#"
+[budget]
+max_subprocesses = 3
+
+[[steps]]
+name = "lint"
+command = "cargo"
+args = ["clippy"]
+"#;
+        let manifest = Manifest::parse(toml, true).unwrap();
+        assert_eq!(manifest.budget.unwrap().max_subprocesses, Some(3));
+        assert_eq!(manifest.steps[0].spec.command, "cargo");
+        assert!(!manifest.steps[0].continue_on_error);
+    }
+
+    #[test]
+    fn test_invalid_graphs() {
+        let step = |name: &str, deps: &[&str]| Step {
+            name: name.to_string(),
+            spec: CommandSpec::parse("true").unwrap(),
+            depends_on: deps.iter().map(|d| d.to_string()).collect(),
+            continue_on_error: false,
+            matrix: BTreeMap::new(),
+            path: Vec::new(),
+            tools: BTreeMap::new(),
+            variant: None,
+        };
+        let check = |steps| {
+            Manifest {
+                jobs: None,
+                budget: None,
+                steps,
+            }
+            .validate()
+            .unwrap_err()
+            .to_string()
+        };
+
+        assert!(check(vec![
+            step("a", &["b"]),
+            step("b", &["c"]),
+            step("c", &["a"]),
+            step("d", &[])
+        ])
+        .contains("cycle between steps: a, b, c"));
+        assert!(check(vec![step("a", &["missing"])]).contains("unknown step 'missing'"));
+        assert!(check(vec![step("a", &[]), step("a", &[])]).contains("Duplicate step 'a'"));
+        assert!(check(vec![step("../a", &[])]).contains("Invalid step name"));
+        let mut tools = step("a", &[]);
+        tools.path = vec![PathBuf::from("tools")];
+        assert!(check(vec![tools]).contains("sets path, which is not supported"));
+    }
+
+    #[test]
+    fn test_select_includes_dependencies() {
+        let mut manifest = Manifest::parse(YAML, false).unwrap();
+        manifest.select(&["test".to_string()]).unwrap();
+        let names: Vec<_> = manifest.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "test"]);
+        assert!(manifest.select(&["nope".to_string()]).is_err());
+    }
+
+    const MATRIX: &str = r#"
+steps:
+  - name: deps
+    matrix: { python: ["3.10", "3.11"] }
+    command: pip
+    args: [install, -r, requirements.txt]
+    tools: { python: "${matrix.python}" }
+  - name: test
+    matrix: { python: ["3.10", "3.11"], db: [sqlite] }
+    command: python
+    args: [-m, pytest, --db, "${matrix.db}"]
+    depends_on: [deps]
+  - name: report
+    command: cat
+    depends_on: [test]
+"#;
+
+    #[test]
+    fn test_matrix_expansion() {
+        let manifest = Manifest::parse(MATRIX, false).unwrap();
+        let names: Vec<_> = manifest.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "deps.python-3.10",
+                "deps.python-3.11",
+                "test.db-sqlite.python-3.10",
+                "test.db-sqlite.python-3.11",
+                "report"
+            ]
+        );
+        assert_eq!(manifest.steps[1].tools["python"], "3.11");
+        let test = &manifest.steps[3];
+        assert_eq!(test.spec.args[3], "sqlite");
+        assert_eq!(test.spec.env["MATRIX_PYTHON"], "3.11");
+        assert_eq!(test.depends_on, vec!["deps.python-3.11"]);
+        assert_eq!(test.variant.as_ref().unwrap().group, "test");
+        assert_eq!(manifest.steps[4].depends_on.len(), 2);
+
+        let mut selected = manifest.clone();
+        selected.select(&["test".to_string()]).unwrap();
+        assert_eq!(selected.steps.len(), 4);
+
+        let unknown = MATRIX.replace("${matrix.db}", "${matrix.os}");
+        let error = Manifest::parse(&unknown, false).unwrap_err().to_string();
+        assert!(error.contains("uses ${matrix.os}"), "{}", error);
+        let empty = MATRIX.replace("db: [sqlite]", "db: []");
+        assert!(Manifest::parse(&empty, false).is_err());
+    }
+
+    #[test]
+    fn test_matrix_report() {
+        let manifest = Manifest::parse(MATRIX, false).unwrap();
+        let results: Vec<StepResult> = manifest
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                let status = if i == 2 {
+                    BatchStatus::Failed
+                } else {
+                    BatchStatus::Succeeded
+                };
+                StepResult {
+                    name: step.name.clone(),
+                    result: BatchResult::without_output(step.spec.clone(), status, None),
+                }
+            })
+            .collect();
+        let reports = manifest.matrix_report(&results);
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].step, "test");
+        assert_eq!(reports[1].keys, vec!["db", "python"]);
+        assert_eq!((reports[1].passed, reports[1].failed), (1, 1));
+        assert_eq!(reports[1].variants[0].values["python"], "3.10");
+    }
+}
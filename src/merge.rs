@@ -0,0 +1,448 @@
+// file: src/merge.rs
+// version: 1.0.1
+// guid: 3f7d1c92-8a4e-4b57-9e06-c2b5f81d4a7e
+
+//! Content-aware three-way merges for lockfiles
+//!
+//! Lockfiles are lists of entries keyed by package, so two branches that add or upgrade
+//! different packages conflict line by line but not entry by entry. The merges here compare
+//! the base, ours and theirs versions of each entry:
+//!
+//! - an entry changed (or added, or removed) on one side only takes that side's version
+//! - an entry changed the same way on both sides is kept once
+//! - an entry changed differently on both sides is a conflict
+//!
+//! `Cargo.lock` packages are keyed by name, version and source; when both sides changed the
+//! dependencies of the same package, the dependency lists are merged as sets. `package-lock.json`
+//! objects are merged key by key at every level, so both sides may add dependencies to the root
+//! package. The output is written in the tool's own layout, so the result is the same whichever
+//! branch is merged into which.
+
+use crate::error::{AgentError, Result};
+use crate::json_order::{KeyOrder, Ordered};
+use crate::tools;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Three-way merge of keyed entries; returns the merged entries and the keys that conflict
+fn merge_entries<K: Ord + Clone, V: PartialEq + Clone>(
+    base: &BTreeMap<K, V>,
+    ours: &BTreeMap<K, V>,
+    theirs: &BTreeMap<K, V>,
+) -> (BTreeMap<K, V>, Vec<K>) {
+    let keys: BTreeSet<&K> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+    let mut merged = BTreeMap::new();
+    let mut conflicts = Vec::new();
+    for key in keys {
+        match merge_value(base.get(key), ours.get(key), theirs.get(key)) {
+            Some(Some(value)) => {
+                merged.insert(key.clone(), value.clone());
+            }
+            Some(None) => {}
+            None => conflicts.push(key.clone()),
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Three-way merge of one value, `None` meaning absent; `None` overall is a conflict
+fn merge_value<'a, V: PartialEq>(
+    base: Option<&'a V>,
+    ours: Option<&'a V>,
+    theirs: Option<&'a V>,
+) -> Option<Option<&'a V>> {
+    if ours == theirs || theirs == base {
+        Some(ours)
+    } else if ours == base {
+        Some(theirs)
+    } else {
+        None
+    }
+}
+
+/// `Cargo.lock`, in the versions that have no `[metadata]` table (3 and later)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CargoLock {
+    version: Option<i64>,
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+    checksum: Option<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+    replace: Option<String>,
+}
+
+impl LockedPackage {
+    fn key(&self) -> (String, String, String) {
+        (
+            self.name.clone(),
+            self.version.clone(),
+            self.source.clone().unwrap_or_default(),
+        )
+    }
+}
+
+fn parse_cargo_lock(content: &str, side: &str) -> Result<CargoLock> {
+    toml::from_str(content).map_err(|e| {
+        AgentError::validation(format!("Cannot merge Cargo.lock: {} is not a supported lockfile: {}", side, e))
+    })
+}
+
+fn packages_by_key(lock: &CargoLock) -> BTreeMap<(String, String, String), LockedPackage> {
+    lock.package.iter().map(|package| (package.key(), package.clone())).collect()
+}
+
+/// Merge three versions of a `Cargo.lock`
+///
+/// Fails when packages conflict, or when the merged packages refer to a dependency by a name
+/// that is now ambiguous or missing; Cargo has to resolve those.
+pub fn merge_cargo_lock(base: &str, ours: &str, theirs: &str) -> Result<String> {
+    let base = parse_cargo_lock(base, "the base")?;
+    let ours = parse_cargo_lock(ours, "ours")?;
+    let theirs = parse_cargo_lock(theirs, "theirs")?;
+    let version = ours.version.max(theirs.version);
+    let (base, ours, theirs) = (packages_by_key(&base), packages_by_key(&ours), packages_by_key(&theirs));
+
+    let (mut merged, conflicts) = merge_entries(&base, &ours, &theirs);
+    let mut unresolved = Vec::new();
+    for key in conflicts {
+        match merge_dependencies(base.get(&key), ours.get(&key), theirs.get(&key)) {
+            Some(package) => {
+                merged.insert(key, package);
+            }
+            None => unresolved.push(format!("{} {}", key.0, key.1)),
+        }
+    }
+    if !unresolved.is_empty() {
+        return Err(AgentError::validation(format!(
+            "Cargo.lock packages changed differently on both sides: {}",
+            unresolved.join(", ")
+        )));
+    }
+
+    let mut packages: Vec<LockedPackage> = merged.into_values().collect();
+    packages.sort_by(compare_packages);
+    let mut problems = check_upgrades(&packages, &ours, &theirs);
+    problems.extend(check_references(&packages));
+    if !problems.is_empty() {
+        return Err(AgentError::validation(format!(
+            "Merged Cargo.lock needs Cargo to resolve it: {}",
+            problems.join("; ")
+        )));
+    }
+    Ok(render_cargo_lock(version, &packages))
+}
+
+/// Merge the dependency lists of a package both sides changed, if nothing else differs
+fn merge_dependencies(
+    base: Option<&LockedPackage>,
+    ours: Option<&LockedPackage>,
+    theirs: Option<&LockedPackage>,
+) -> Option<LockedPackage> {
+    let (ours, theirs) = (ours?, theirs?);
+    let without_dependencies = |package: &LockedPackage| LockedPackage {
+        dependencies: Vec::new(),
+        ..package.clone()
+    };
+    if without_dependencies(ours) != without_dependencies(theirs) {
+        return None;
+    }
+    let set = |package: Option<&LockedPackage>| -> BTreeMap<String, ()> {
+        package
+            .map(|package| package.dependencies.iter().map(|dep| (dep.clone(), ())).collect())
+            .unwrap_or_default()
+    };
+    let (dependencies, conflicts) = merge_entries(&set(base), &set(Some(ours)), &set(Some(theirs)));
+    debug_assert!(conflicts.is_empty(), "set members cannot conflict");
+    Some(LockedPackage {
+        dependencies: dependencies.into_keys().collect(),
+        ..ours.clone()
+    })
+}
+
+/// Cargo's order: name, then version, then source
+fn compare_packages(a: &LockedPackage, b: &LockedPackage) -> Ordering {
+    let version = |package: &LockedPackage| tools::parse_version(&package.version).unwrap_or_default();
+    a.name
+        .cmp(&b.name)
+        .then_with(|| tools::compare_versions(&version(a), &version(b)))
+        .then_with(|| a.version.cmp(&b.version))
+        .then_with(|| a.source.cmp(&b.source))
+}
+
+/// Packages each side has one version of, upgraded to different versions on both sides
+fn check_upgrades(
+    packages: &[LockedPackage],
+    ours: &BTreeMap<(String, String, String), LockedPackage>,
+    theirs: &BTreeMap<(String, String, String), LockedPackage>,
+) -> Vec<String> {
+    let count = |packages: &mut dyn Iterator<Item = &LockedPackage>, name: &str| {
+        packages.filter(|package| package.name == name).count()
+    };
+    let mut names: Vec<&str> = packages.iter().map(|package| package.name.as_str()).collect();
+    names.dedup();
+    names
+        .into_iter()
+        .filter(|name| {
+            count(&mut packages.iter(), name) > 1
+                && count(&mut ours.values(), name) <= 1
+                && count(&mut theirs.values(), name) <= 1
+        })
+        .map(|name| format!("{} was changed to different versions on both sides", name))
+        .collect()
+}
+
+/// Dependencies naming a package that is missing, or only by name when there are several
+/// versions of it
+fn check_references(packages: &[LockedPackage]) -> Vec<String> {
+    let mut versions: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for package in packages {
+        versions.entry(&package.name).or_default().push(&package.version);
+    }
+    let mut problems = Vec::new();
+    for package in packages {
+        for dependency in &package.dependencies {
+            let mut parts = dependency.split_whitespace();
+            let name = parts.next().unwrap_or_default();
+            let found = versions.get(name).map(Vec::as_slice).unwrap_or_default();
+            let problem = match parts.next() {
+                Some(version) if !found.contains(&version) => "is missing",
+                None if found.is_empty() => "is missing",
+                None if found.len() > 1 => "is ambiguous",
+                _ => continue,
+            };
+            problems.push(format!("{} {} depends on '{}', which {}", package.name, package.version, dependency, problem));
+        }
+    }
+    problems
+}
+
+/// Write packages the way Cargo does
+fn render_cargo_lock(version: Option<i64>, packages: &[LockedPackage]) -> String {
+    let quote = |text: &str| toml::Value::String(text.to_string()).to_string();
+    let mut out = String::from(
+        "# This file is automatically @generated by Cargo.\n# It is not intended for manual editing.\n",
+    );
+    if let Some(version) = version {
+        out.push_str(&format!("version = {}\n", version));
+    }
+    for package in packages {
+        out.push_str("\n[[package]]\n");
+        out.push_str(&format!("name = {}\n", quote(&package.name)));
+        out.push_str(&format!("version = {}\n", quote(&package.version)));
+        if let Some(source) = &package.source {
+            out.push_str(&format!("source = {}\n", quote(source)));
+        }
+        if let Some(checksum) = &package.checksum {
+            out.push_str(&format!("checksum = {}\n", quote(checksum)));
+        }
+        if !package.dependencies.is_empty() {
+            out.push_str("dependencies = [\n");
+            for dependency in &package.dependencies {
+                out.push_str(&format!(" {},\n", quote(dependency)));
+            }
+            out.push_str("]\n");
+        }
+        if let Some(replace) = &package.replace {
+            out.push_str(&format!("replace = {}\n", quote(replace)));
+        }
+    }
+    out
+}
+
+/// Merge three versions of a `package-lock.json`
+///
+/// Objects are merged key by key; other values, arrays included, are replaced whole and
+/// conflict when both sides changed them differently.
+pub fn merge_package_lock(base: &str, ours: &str, theirs: &str) -> Result<String> {
+    let parse = |content: &str, side: &str| -> Result<Value> {
+        serde_json::from_str(content).map_err(|e| {
+            AgentError::validation(format!("Cannot merge package-lock.json: {} is not valid JSON: {}", side, e))
+        })
+    };
+    let base = parse(base, "the base")?;
+    let ours_text = ours;
+    let ours = parse(ours, "ours")?;
+    let theirs = parse(theirs, "theirs")?;
+
+    let mut conflicts = Vec::new();
+    let merged = merge_json(Some(&base), Some(&ours), Some(&theirs), "", &mut conflicts);
+    if !conflicts.is_empty() {
+        return Err(AgentError::validation(format!(
+            "package-lock.json entries changed differently on both sides: {}",
+            conflicts.join(", ")
+        )));
+    }
+    let merged = merged.unwrap_or_else(|| Value::Object(Map::new()));
+    // Keys come out in our order, their new keys after; objects whose keys were sorted stay sorted
+    let order = KeyOrder::of(ours_text)?;
+    Ok(serde_json::to_string_pretty(&Ordered::new(&merged, &order))? + "\n")
+}
+
+/// Merge JSON values, recording the paths that conflict; `None` is absent
+fn merge_json(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    path: &str,
+    conflicts: &mut Vec<String>,
+) -> Option<Value> {
+    if let (Some(Value::Object(ours)), Some(Value::Object(theirs))) = (ours, theirs) {
+        let empty = Map::new();
+        let base = match base {
+            Some(Value::Object(base)) => base,
+            _ => &empty,
+        };
+        return Some(Value::Object(merge_objects(base, ours, theirs, path, conflicts)));
+    }
+    match merge_value(base, ours, theirs) {
+        Some(value) => value.cloned(),
+        None => {
+            conflicts.push(if path.is_empty() { "(root)".to_string() } else { path.to_string() });
+            ours.cloned()
+        }
+    }
+}
+
+/// Merge objects key by key
+fn merge_objects(
+    base: &Map<String, Value>,
+    ours: &Map<String, Value>,
+    theirs: &Map<String, Value>,
+    path: &str,
+    conflicts: &mut Vec<String>,
+) -> Map<String, Value> {
+    let keys: BTreeSet<&String> = ours.keys().chain(theirs.keys()).chain(base.keys()).collect();
+
+    let mut merged = Map::new();
+    for key in keys {
+        let child = format!("{}[{:?}]", path, key);
+        if let Some(value) = merge_json(base.get(key), ours.get(key), theirs.get(key), &child, conflicts) {
+            merged.insert(key.clone(), value);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str, dependencies: &[&str]) -> String {
+        let mut out = format!(
+            "\n[[package]]\nname = \"{}\"\nversion = \"{}\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\nchecksum = \"{}{}\"\n",
+            name, version, name, version
+        );
+        if !dependencies.is_empty() {
+            out.push_str("dependencies = [\n");
+            for dependency in dependencies {
+                out.push_str(&format!(" \"{}\",\n", dependency));
+            }
+            out.push_str("]\n");
+        }
+        out
+    }
+
+    fn cargo_lock(packages: &[String]) -> String {
+        format!(
+            "# This file is automatically @generated by Cargo.\n# It is not intended for manual editing.\nversion = 4\n{}",
+            packages.concat()
+        )
+    }
+
+    #[test]
+    fn test_merge_cargo_lock_unions_packages() {
+        let base = cargo_lock(&[package("app", "0.1.0", &["serde"]), package("serde", "1.0.0", &[])]);
+        let ours = cargo_lock(&[
+            package("anyhow", "1.0.0", &[]),
+            package("app", "0.1.0", &["anyhow", "serde"]),
+            package("serde", "1.0.0", &[]),
+        ]);
+        let theirs = cargo_lock(&[
+            package("app", "0.1.0", &["serde", "tokio"]),
+            package("serde", "1.0.1", &[]),
+            package("tokio", "1.0.0", &[]),
+        ]);
+
+        let expected = cargo_lock(&[
+            package("anyhow", "1.0.0", &[]),
+            package("app", "0.1.0", &["anyhow", "serde", "tokio"]),
+            package("serde", "1.0.1", &[]),
+            package("tokio", "1.0.0", &[]),
+        ]);
+        assert_eq!(merge_cargo_lock(&base, &ours, &theirs).unwrap(), expected);
+        assert_eq!(merge_cargo_lock(&base, &theirs, &ours).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_merge_cargo_lock_reports_conflicts() {
+        let base = cargo_lock(&[package("app", "0.1.0", &["serde"]), package("serde", "1.0.0", &[])]);
+        let ours = cargo_lock(&[package("app", "0.1.0", &["serde"]), package("serde", "1.0.0", &[])]);
+        // Another version of serde makes the bare "serde" reference ambiguous
+        let theirs = cargo_lock(&[
+            package("app", "0.1.0", &["serde"]),
+            package("serde", "1.0.0", &[]),
+            package("serde", "2.0.0", &[]),
+        ]);
+        let error = merge_cargo_lock(&base, &ours, &theirs).unwrap_err().to_string();
+        assert!(error.contains("'serde', which is ambiguous"), "{}", error);
+
+        let ours = base.replace("checksum = \"serde1.0.0\"", "checksum = \"ours\"");
+        let theirs = base.replace("checksum = \"serde1.0.0\"", "checksum = \"theirs\"");
+        let error = merge_cargo_lock(&base, &ours, &theirs).unwrap_err().to_string();
+        assert!(error.contains("serde 1.0.0"), "{}", error);
+    }
+
+    #[test]
+    fn test_merge_package_lock() {
+        let base = r#"{
+  "name": "app",
+  "lockfileVersion": 3,
+  "packages": {
+    "": { "dependencies": { "a": "^1.0.0" } },
+    "node_modules/a": { "version": "1.0.0" }
+  }
+}"#;
+        let ours = r#"{
+  "name": "app",
+  "lockfileVersion": 3,
+  "packages": {
+    "": { "dependencies": { "a": "^1.0.0", "c": "^1.0.0" } },
+    "node_modules/a": { "version": "1.0.0" },
+    "node_modules/c": { "version": "1.0.0" }
+  }
+}"#;
+        let theirs = r#"{
+  "name": "app",
+  "lockfileVersion": 3,
+  "packages": {
+    "": { "dependencies": { "a": "^1.1.0", "b": "^1.0.0" } },
+    "node_modules/a": { "version": "1.1.0" },
+    "node_modules/b": { "version": "1.0.0" }
+  }
+}"#;
+        let merged: Value = serde_json::from_str(&merge_package_lock(base, ours, theirs).unwrap()).unwrap();
+        assert_eq!(
+            merged["packages"][""]["dependencies"],
+            serde_json::json!({ "a": "^1.1.0", "b": "^1.0.0", "c": "^1.0.0" })
+        );
+        let keys: Vec<&String> = merged["packages"].as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["", "node_modules/a", "node_modules/b", "node_modules/c"]);
+        assert_eq!(merged["packages"]["node_modules/a"]["version"], "1.1.0");
+
+        let ours = ours.replace(r#""node_modules/a": { "version": "1.0.0" }"#, r#""node_modules/a": { "version": "1.2.0" }"#);
+        let error = merge_package_lock(base, &ours, theirs).unwrap_err().to_string();
+        assert!(error.contains(r#"["packages"]["node_modules/a"]["version"]"#), "{}", error);
+    }
+}
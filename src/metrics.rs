@@ -0,0 +1,413 @@
+// file: src/metrics.rs
+// version: 1.1.0
+// guid: 9a7378d3-1c50-4177-ab2a-0061b7083b43
+
+//! Execution metrics
+//!
+//! Each invocation of the utility is short-lived, so the metrics registry is persisted in
+//! the state directory and updated after every command the Executor runs. Counters and
+//! duration histograms are kept per command and subcommand (e.g. `git status`) and can be
+//! rendered in the Prometheus text exposition format.
+//!
+//! Only the subcommands in [`KNOWN_SUBCOMMANDS`] become labels; any other first argument is
+//! counted as `other`, and commands without a list get no subcommand. Arguments such as paths
+//! or expressions therefore never reach the metrics. Concurrent invocations serialize their
+//! updates through `metrics.json.lock` next to the registry.
+
+use crate::error::{AgentError, Result};
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Upper bounds (in seconds) of the duration histogram buckets
+pub const DURATION_BUCKETS: [f64; 10] = [0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Subcommands kept as the `subcommand` label, per command
+pub const KNOWN_SUBCOMMANDS: &[(&str, &[&str])] = &[
+    (
+        "git",
+        &[
+            "add", "am", "apply", "bisect", "blame", "branch", "checkout", "cherry-pick", "clean",
+            "clone", "commit", "config", "describe", "diff", "fetch", "grep", "init", "log",
+            "ls-files", "merge", "mv", "pull", "push", "rebase", "reflog", "remote", "reset",
+            "restore", "rev-parse", "rm", "show", "stash", "status", "switch", "tag", "worktree",
+        ],
+    ),
+    (
+        "cargo",
+        &[
+            "add", "bench", "build", "check", "clean", "clippy", "doc", "fetch", "fmt", "install",
+            "metadata", "new", "publish", "remove", "run", "test", "tree", "update",
+        ],
+    ),
+    (
+        "npm",
+        &[
+            "audit", "ci", "exec", "install", "outdated", "pack", "publish", "run", "test",
+            "uninstall", "update",
+        ],
+    ),
+    (
+        "go",
+        &[
+            "build", "env", "fmt", "generate", "get", "install", "list", "mod", "run", "test",
+            "vet", "work",
+        ],
+    ),
+    (
+        "docker",
+        &[
+            "build", "compose", "exec", "images", "inspect", "logs", "ps", "pull", "push", "rm",
+            "rmi", "run", "stop", "tag",
+        ],
+    ),
+    (
+        "kubectl",
+        &["apply", "delete", "describe", "diff", "exec", "get", "logs", "rollout", "scale"],
+    ),
+    (
+        "terraform",
+        &["apply", "destroy", "fmt", "init", "plan", "show", "validate"],
+    ),
+    (
+        "gh",
+        &["api", "issue", "pr", "release", "repo", "run", "workflow"],
+    ),
+    (
+        "buf",
+        &[
+            "breaking", "build", "curl", "dep", "format", "generate", "lint", "mod", "push",
+            "registry",
+        ],
+    ),
+];
+
+/// How long an update waits for another process to finish its own
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Age after which a lock left behind by a killed process is taken over
+const STALE_LOCK_AGE: Duration = Duration::from_secs(30);
+
+/// Persisted metrics for all commands
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsRegistry {
+    pub series: BTreeMap<String, CommandMetrics>,
+}
+
+/// Metrics for one command/subcommand pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandMetrics {
+    pub command: String,
+    pub subcommand: String,
+    pub executed: u64,
+    pub failures: u64,
+    pub duration: Histogram,
+}
+
+/// Cumulative histogram over [`DURATION_BUCKETS`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Observations less than or equal to each bucket bound
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: f64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; DURATION_BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Record one observation in seconds
+    pub fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Mean observation in seconds
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+}
+
+impl MetricsRegistry {
+    /// Path of the persisted registry
+    pub fn path() -> PathBuf {
+        utils::state_dir().join("metrics.json")
+    }
+
+    /// Load the registry, starting empty if none has been saved yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Persist the registry atomically
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        let dir = path
+            .parent()
+            .ok_or_else(|| AgentError::system("Invalid metrics path"))?;
+        fs::create_dir_all(dir)?;
+        // A temporary file of its own, so concurrent writers never rename each other's
+        let tmp = dir.join(format!("metrics.json.{}.tmp", Uuid::new_v4()));
+        let content = serde_json::to_string(self)?;
+        if let Err(e) = fs::write(&tmp, content).and_then(|_| fs::rename(&tmp, &path)) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Record one execution
+    pub fn record(&mut self, command: &str, args: &[String], success: bool, duration: Duration) {
+        let subcommand = subcommand_of(command, args);
+        let key = format!("{} {}", command, subcommand);
+        let entry = self.series.entry(key).or_insert_with(|| CommandMetrics {
+            command: command.to_string(),
+            subcommand,
+            executed: 0,
+            failures: 0,
+            duration: Histogram::default(),
+        });
+
+        entry.executed += 1;
+        if !success {
+            entry.failures += 1;
+        }
+        entry.duration.observe(duration.as_secs_f64());
+    }
+
+    /// Render the registry in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP safe_ai_commands_total Commands executed");
+        let _ = writeln!(out, "# TYPE safe_ai_commands_total counter");
+        for metrics in self.series.values() {
+            let _ = writeln!(
+                out,
+                "safe_ai_commands_total{{{}}} {}",
+                labels(metrics),
+                metrics.executed
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP safe_ai_command_failures_total Commands that failed or timed out"
+        );
+        let _ = writeln!(out, "# TYPE safe_ai_command_failures_total counter");
+        for metrics in self.series.values() {
+            let _ = writeln!(
+                out,
+                "safe_ai_command_failures_total{{{}}} {}",
+                labels(metrics),
+                metrics.failures
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP safe_ai_command_duration_seconds Command execution duration"
+        );
+        let _ = writeln!(out, "# TYPE safe_ai_command_duration_seconds histogram");
+        for metrics in self.series.values() {
+            let labels = labels(metrics);
+            for (count, bound) in metrics.duration.buckets.iter().zip(DURATION_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "safe_ai_command_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "safe_ai_command_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, metrics.duration.count
+            );
+            let _ = writeln!(
+                out,
+                "safe_ai_command_duration_seconds_sum{{{}}} {}",
+                labels, metrics.duration.sum
+            );
+            let _ = writeln!(
+                out,
+                "safe_ai_command_duration_seconds_count{{{}}} {}",
+                labels, metrics.duration.count
+            );
+        }
+
+        out
+    }
+}
+
+/// Record one execution in the persisted registry
+///
+/// Metrics are best effort: failures to read or write the registry are logged and never
+/// affect the command that was executed.
+pub fn record_execution(command: &str, args: &[String], success: bool, duration: Duration) {
+    let result = RegistryLock::acquire().and_then(|_lock| {
+        let mut registry = MetricsRegistry::load()?;
+        registry.record(command, args, success, duration);
+        registry.save()
+    });
+    if let Err(e) = result {
+        warn!("Failed to update metrics: {}", e);
+    }
+}
+
+/// Exclusive right to update the persisted registry, released on drop
+struct RegistryLock(PathBuf);
+
+impl RegistryLock {
+    fn acquire() -> Result<Self> {
+        let path = MetricsRegistry::path().with_extension("json.lock");
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let deadline = Instant::now() + LOCK_TIMEOUT;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Self(path)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > STALE_LOCK_AGE);
+                    if stale {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(AgentError::system("Timed out waiting for the metrics lock"));
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for RegistryLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Label for the first positional argument (skipping flags and the directory given to `-C`):
+/// the argument if it is a known subcommand of `command`, `other` if it is not, and empty for
+/// commands without known subcommands
+fn subcommand_of(command: &str, args: &[String]) -> String {
+    let Some((_, known)) = KNOWN_SUBCOMMANDS.iter().find(|(name, _)| *name == command) else {
+        return String::new();
+    };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-C" {
+            iter.next();
+        } else if !arg.starts_with('-') {
+            return if known.contains(&arg.as_str()) {
+                arg.clone()
+            } else {
+                "other".to_string()
+            };
+        }
+    }
+    String::new()
+}
+
+fn labels(metrics: &CommandMetrics) -> String {
+    format!(
+        "command=\"{}\",subcommand=\"{}\"",
+        escape_label(&metrics.command),
+        escape_label(&metrics.subcommand)
+    )
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_record_and_histogram() {
+        let mut registry = MetricsRegistry::default();
+        registry.record("git", &args(&["status"]), true, Duration::from_millis(50));
+        registry.record(
+            "git",
+            &args(&["-C", "repo", "status"]),
+            false,
+            Duration::from_secs(3),
+        );
+
+        let metrics = &registry.series["git status"];
+        assert_eq!(metrics.executed, 2);
+        assert_eq!(metrics.failures, 1);
+        assert_eq!(metrics.duration.buckets[0], 1);
+        assert_eq!(metrics.duration.buckets[4], 2);
+        assert!((metrics.duration.mean() - 1.525).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_prometheus_output() {
+        let mut registry = MetricsRegistry::default();
+        registry.record(
+            "cargo",
+            &args(&["build", "--release"]),
+            true,
+            Duration::from_secs(1),
+        );
+
+        let text = registry.to_prometheus();
+        assert!(text.contains("safe_ai_commands_total{command=\"cargo\",subcommand=\"build\"} 1"));
+        assert!(text.contains("le=\"+Inf\"} 1"));
+        assert!(text.contains("# TYPE safe_ai_command_duration_seconds histogram"));
+    }
+
+    #[test]
+    fn test_subcommand_labels_are_bounded() {
+        assert_eq!(subcommand_of("git", &args(&["-C", "repo", "status"])), "status");
+        assert_eq!(subcommand_of("git", &args(&["/home/user/secret.txt"])), "other");
+        assert_eq!(subcommand_of("sed", &args(&["s/password/x/", "notes.txt"])), "");
+        assert_eq!(subcommand_of("cargo", &args(&["--version"])), "");
+    }
+}
@@ -0,0 +1,61 @@
+// file: src/parsers/buf.rs
+// version: 1.0.0
+// guid: 1f814985-8418-4eda-b78c-6b349fa437e3
+
+//! `buf lint`, `build` and `breaking` with `--error-format=json`: one JSON object per line
+
+use super::{Diagnostic, Severity, Tool};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Annotation {
+    path: Option<String>,
+    start_line: Option<u32>,
+    start_column: Option<u32>,
+    /// Lint or breaking-change rule, or `COMPILE` for build errors
+    #[serde(rename = "type")]
+    rule: Option<String>,
+    message: String,
+}
+
+/// Diagnostics in buf's stdout; every annotation fails the command, so all are errors
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Annotation>(line).ok())
+        .map(|annotation| {
+            let diagnostic = Diagnostic::new(Tool::Buf, Severity::Error, annotation.message)
+                .with_code(annotation.rule);
+            match annotation.path {
+                Some(path) => diagnostic.at(path, annotation.start_line, annotation.start_column),
+                None => diagnostic,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_annotations() {
+        let output = concat!(
+            r#"{"path":"proto/user/v1/user.proto","start_line":5,"start_column":1,"end_line":5,"end_column":20,"type":"PACKAGE_VERSION_SUFFIX","message":"Package name \"user\" should be suffixed with a correctly formed version."}"#,
+            "\n",
+            r#"{"path":"proto/order.proto","start_line":3,"start_column":8,"end_line":3,"end_column":8,"type":"COMPILE","message":"syntax error: unexpected ';'"}"#,
+            "\n",
+        );
+
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].code.as_deref(),
+            Some("PACKAGE_VERSION_SUFFIX")
+        );
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "proto/order.proto:3:8: error[COMPILE]: syntax error: unexpected ';'"
+        );
+    }
+}
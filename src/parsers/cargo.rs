@@ -0,0 +1,267 @@
+// file: src/parsers/cargo.rs
+// version: 1.2.0
+// guid: 89bda992-3094-42db-91f6-f653bc7bc1e3
+
+//! `cargo ... --message-format=json`: one JSON object per line, of which `compiler-message`
+//! objects carry rustc's diagnostics
+//!
+//! `cargo test` also prints the test harness's plain output on stdout. A failed test is
+//! reported where it panicked, with the panic message, and [`test_counts`] adds up the
+//! `test result:` line of every test binary.
+
+use super::{Diagnostic, Severity, TestCounts, Tool};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+#[derive(Deserialize)]
+struct Message {
+    reason: String,
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Deserialize)]
+struct CompilerMessage {
+    message: String,
+    code: Option<Code>,
+    level: String,
+    #[serde(default)]
+    spans: Vec<Span>,
+}
+
+#[derive(Deserialize)]
+struct Code {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct Span {
+    file_name: String,
+    line_start: u32,
+    column_start: u32,
+    is_primary: bool,
+}
+
+/// Diagnostics in cargo's stdout: compiler messages, then failed tests
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Message>(line).ok())
+        .filter(|message| message.reason == "compiler-message")
+        .filter_map(|message| message.message.and_then(diagnostic))
+        .collect();
+    diagnostics.extend(failed_tests(output));
+    diagnostics
+}
+
+/// Counts of the `test result:` lines in `cargo test`'s stdout; `None` if no tests ran
+pub fn test_counts(output: &str) -> Option<TestCounts> {
+    static RESULT: OnceLock<Regex> = OnceLock::new();
+    let result = RESULT.get_or_init(|| {
+        Regex::new(r"^test result: \w+\. (\d+) passed; (\d+) failed; (\d+) ignored; \d+ measured; (\d+) filtered out")
+            .expect("valid test result pattern")
+    });
+    let mut counts = None;
+    for captures in output.lines().filter_map(|line| result.captures(line)) {
+        let count = |group: usize| captures[group].parse::<u32>().unwrap_or(0);
+        let total: &mut TestCounts = counts.get_or_insert_with(TestCounts::default);
+        total.passed += count(1);
+        total.failed += count(2);
+        total.ignored += count(3);
+        total.filtered_out += count(4);
+    }
+    counts
+}
+
+/// Where a test panicked and why
+struct Panic {
+    location: Option<(String, u32, u32)>,
+    message: String,
+}
+
+/// Failed tests in the test harness's output, located by their panic in the
+/// `---- name stdout ----` section the harness prints for each
+fn failed_tests(output: &str) -> Vec<Diagnostic> {
+    static FAILED: OnceLock<Regex> = OnceLock::new();
+    static SECTION: OnceLock<Regex> = OnceLock::new();
+    static PANICKED: OnceLock<Regex> = OnceLock::new();
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    let failed = FAILED
+        .get_or_init(|| Regex::new(r"^test (.+) \.\.\. FAILED$").expect("valid test pattern"));
+    let section = SECTION
+        .get_or_init(|| Regex::new(r"^---- (.+) stdout ----$").expect("valid section pattern"));
+    let panicked = PANICKED.get_or_init(|| {
+        Regex::new(r"^thread '.*'(?: \(\d+\))? panicked at (.*)$").expect("valid panic pattern")
+    });
+    let location = LOCATION
+        .get_or_init(|| Regex::new(r"(\S+):(\d+):(\d+):?$").expect("valid location pattern"));
+
+    let mut panics: HashMap<&str, Panic> = HashMap::new();
+    let mut test = None;
+    let mut lines = output.lines();
+    while let Some(line) = lines.next() {
+        if let Some(captures) = section.captures(line) {
+            test = captures.get(1).map(|name| name.as_str());
+            continue;
+        }
+        let (Some(test), Some(captures)) = (test, panicked.captures(line)) else {
+            continue;
+        };
+        let rest = captures.get(1).map_or("", |rest| rest.as_str());
+        let found = location.captures(rest).and_then(|captures| {
+            let start = captures.get(0)?.start();
+            Some((
+                start,
+                (
+                    captures[1].to_string(),
+                    captures[2].parse().ok()?,
+                    captures[3].parse().ok()?,
+                ),
+            ))
+        });
+        // Rust 1.73 and later print the location, then the message on the next line; older
+        // versions print `'message', location`
+        let message = match &found {
+            Some(_) if rest.ends_with(':') => lines.next().unwrap_or_default().to_string(),
+            Some((start, _)) => rest[..*start]
+                .trim_end_matches(", ")
+                .trim_matches('\'')
+                .to_string(),
+            None => rest.to_string(),
+        };
+        panics.entry(test).or_insert(Panic {
+            location: found.map(|(_, location)| location),
+            message,
+        });
+    }
+
+    output
+        .lines()
+        .filter_map(|line| failed.captures(line))
+        .filter_map(|captures| captures.get(1))
+        .map(|name| {
+            let name = name.as_str();
+            let panic = panics.remove(name);
+            let message = match &panic {
+                Some(panic) if !panic.message.is_empty() => panic.message.clone(),
+                _ => "test failed".to_string(),
+            };
+            let diagnostic =
+                Diagnostic::new(Tool::Cargo, Severity::Error, message).with_code(Some(name));
+            match panic.and_then(|panic| panic.location) {
+                Some((file, line, column)) => diagnostic.at(file, Some(line), Some(column)),
+                None => diagnostic,
+            }
+        })
+        .collect()
+}
+
+fn diagnostic(message: CompilerMessage) -> Option<Diagnostic> {
+    let severity = match message.level.as_str() {
+        "error" | "error: internal compiler error" => Severity::Error,
+        "warning" => Severity::Warning,
+        "note" | "help" => Severity::Info,
+        _ => return None,
+    };
+    let span = message.spans.iter().find(|span| span.is_primary);
+    // Totals such as "aborting due to 2 previous errors" repeat what was already reported
+    if span.is_none()
+        && message.code.is_none()
+        && (message.message.starts_with("aborting due to") || message.message.ends_with("emitted"))
+    {
+        return None;
+    }
+
+    let mut diagnostic = Diagnostic::new(Tool::Cargo, severity, message.message)
+        .with_code(message.code.map(|code| code.code));
+    if let Some(span) = span {
+        diagnostic = diagnostic.at(
+            span.file_name.clone(),
+            Some(span.line_start),
+            Some(span.column_start),
+        );
+    }
+    Some(diagnostic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compiler_messages() {
+        let output = [
+            r#"{"reason":"compiler-artifact","package_id":"demo 0.1.0","target":{"name":"demo"}}"#,
+            r#"{"reason":"compiler-message","message":{"message":"unused import: `std::fs`","code":{"code":"unused_imports","explanation":null},"level":"warning","spans":[{"file_name":"src/main.rs","line_start":1,"column_start":5,"is_primary":true}],"rendered":"warning: unused import"}}"#,
+            r#"{"reason":"compiler-message","message":{"message":"mismatched types","code":{"code":"E0308","explanation":"..."},"level":"error","spans":[{"file_name":"src/lib.rs","line_start":9,"column_start":3,"is_primary":false},{"file_name":"src/lib.rs","line_start":12,"column_start":17,"is_primary":true}]}}"#,
+            r#"{"reason":"compiler-message","message":{"message":"aborting due to 1 previous error","code":null,"level":"error","spans":[]}}"#,
+            r#"{"reason":"build-finished","success":false}"#,
+            "not json",
+        ]
+        .join("\n");
+
+        let diagnostics = parse(&output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("unused_imports"));
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "src/lib.rs:12:17: error[E0308]: mismatched types"
+        );
+    }
+
+    #[test]
+    fn test_parse_failed_tests() {
+        let output = r#"
+running 3 tests
+test tests::adds ... ok
+test tests::subtracts ... FAILED
+test tests::legacy ... FAILED
+
+failures:
+
+---- tests::subtracts stdout ----
+thread 'tests::subtracts' (4211) panicked at src/lib.rs:14:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+
+---- tests::legacy stdout ----
+thread 'tests::legacy' panicked at 'boom', src/lib.rs:20:5
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+failures:
+    tests::legacy
+    tests::subtracts
+
+test result: FAILED. 1 passed; 2 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.00s
+
+running 1 test
+test src/lib.rs - add (line 3) ... ok
+
+test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 2 filtered out; finished in 0.01s
+"#;
+
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "src/lib.rs:14:9: error[tests::subtracts]: assertion `left == right` failed"
+        );
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "src/lib.rs:20:5: error[tests::legacy]: boom"
+        );
+        assert_eq!(
+            test_counts(output),
+            Some(TestCounts {
+                passed: 2,
+                failed: 2,
+                ignored: 1,
+                filtered_out: 2,
+            })
+        );
+        assert_eq!(test_counts("not a test run"), None);
+    }
+}
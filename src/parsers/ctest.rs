@@ -0,0 +1,159 @@
+// file: src/parsers/ctest.rs
+// version: 1.0.0
+// guid: d5a83e60-17fb-4c29-8e4d-90b2f6c1a7e3
+
+//! ctest's progress lines
+//!
+//! ctest prints one line per finished test, `1/3 Test #1: name ....   Passed    0.01 sec`,
+//! with `***Failed`, `***Timeout`, `***Exception: SegFault`, `***Not Run` or `***Skipped` in
+//! place of `Passed`. The output of failed tests, printed below their line with
+//! `--output-on-failure`, is not attributed to them.
+
+use super::{Diagnostic, Severity, TestCounts, Tool};
+use regex::Regex;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// Outcome of one test
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    /// `passed`, `failed`, `timeout`, `exception`, `not_run` or `skipped`
+    pub status: &'static str,
+    /// What ctest said beyond the status, e.g. `SegFault` or `Disabled`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub seconds: f64,
+}
+
+impl TestResult {
+    fn failed(&self) -> bool {
+        matches!(self.status, "failed" | "timeout" | "exception")
+    }
+}
+
+/// The tests in ctest's output, in the order they finished
+pub fn results(output: &str) -> Vec<TestResult> {
+    static RESULT: OnceLock<Regex> = OnceLock::new();
+    let result = RESULT.get_or_init(|| {
+        Regex::new(r"^\s*(?:\d+/\d+ )?Test\s+#\d+: (\S+) \.*\s*(?:\*{3})?(.+?)\s+([\d.]+) sec$")
+            .expect("valid result pattern")
+    });
+    output
+        .lines()
+        .filter_map(|line| result.captures(line.trim_end()))
+        .map(|captures| {
+            let outcome = captures[2].trim();
+            let (status, rest) = [
+                ("Passed", "passed"),
+                ("Timeout", "timeout"),
+                ("Exception", "exception"),
+                ("Not Run", "not_run"),
+                ("Skipped", "skipped"),
+                ("Failed", "failed"),
+            ]
+            .into_iter()
+            .find_map(|(word, status)| Some((status, outcome.strip_prefix(word)?)))
+            // Other outcomes, such as `Subprocess aborted`, are failures too
+            .unwrap_or(("failed", outcome));
+            let detail = rest.trim_start_matches(':').trim().trim_matches(['(', ')']);
+            TestResult {
+                name: captures[1].to_string(),
+                status,
+                detail: (!detail.is_empty()).then(|| detail.to_string()),
+                seconds: captures[3].parse().unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Diagnostics for the tests that failed, timed out or crashed
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    results(output)
+        .into_iter()
+        .filter(TestResult::failed)
+        .map(|result| {
+            let mut message = match result.status {
+                "timeout" => format!("{} timed out", result.name),
+                "exception" => format!("{} crashed", result.name),
+                _ => format!("{} failed", result.name),
+            };
+            if let Some(detail) = &result.detail {
+                message.push_str(&format!(": {}", detail));
+            }
+            Diagnostic::new(Tool::Ctest, Severity::Error, message).with_code(Some(result.name))
+        })
+        .collect()
+}
+
+/// Tests that passed, failed and were skipped or not run; `None` if no test ran
+pub fn test_counts(results: &[TestResult]) -> Option<TestCounts> {
+    if results.is_empty() {
+        return None;
+    }
+    let count = |keep: fn(&TestResult) -> bool| results.iter().filter(|r| keep(r)).count() as u32;
+    Some(TestCounts {
+        passed: count(|r| r.status == "passed"),
+        failed: count(TestResult::failed),
+        ignored: count(|r| matches!(r.status, "not_run" | "skipped")),
+        filtered_out: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_results_and_diagnostics() {
+        let output = "\
+Test project /work/app/build
+      Start  1: unit_math
+ 1/5 Test  #1: unit_math ........................   Passed    0.01 sec
+      Start  2: unit_io
+ 2/5 Test  #2: unit_io ..........................***Failed    0.02 sec
+expected 3, got 4
+ 3/5 Test  #3: slow .............................***Timeout  10.01 sec
+ 4/5 Test  #4: crash ............................***Exception: SegFault  0.01 sec
+ 5/5 Test  #5: later ............................***Not Run (Disabled)   0.00 sec
+
+40% tests passed, 3 tests failed out of 5
+";
+        let results = results(output);
+        let statuses: Vec<(&str, &str)> = results
+            .iter()
+            .map(|r| (r.name.as_str(), r.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("unit_math", "passed"),
+                ("unit_io", "failed"),
+                ("slow", "timeout"),
+                ("crash", "exception"),
+                ("later", "not_run"),
+            ]
+        );
+        assert_eq!(results[2].seconds, 10.01);
+        assert_eq!(results[4].detail.as_deref(), Some("Disabled"));
+        assert_eq!(
+            test_counts(&results),
+            Some(TestCounts {
+                passed: 1,
+                failed: 3,
+                ignored: 1,
+                filtered_out: 0,
+            })
+        );
+
+        let lines: Vec<String> = parse(output).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "error[unit_io]: unit_io failed",
+                "error[slow]: slow timed out",
+                "error[crash]: crash crashed: SegFault",
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+// file: src/parsers/eslint.rs
+// version: 1.0.0
+// guid: acfe60d7-ebc8-488b-a5fc-9f29bb061087
+
+//! `eslint --format json`: an array with the messages of every linted file
+
+use super::{Diagnostic, Severity, Tool};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileResult {
+    file_path: String,
+    messages: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    rule_id: Option<String>,
+    severity: u8,
+    message: String,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+/// Diagnostics in eslint's stdout; anything that is not its JSON report yields none
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let Ok(files) = serde_json::from_str::<Vec<FileResult>>(output.trim()) else {
+        return Vec::new();
+    };
+    files
+        .into_iter()
+        .flat_map(|file| {
+            let path = file.file_path;
+            file.messages.into_iter().map(move |message| {
+                let severity = match message.severity {
+                    2 => Severity::Error,
+                    1 => Severity::Warning,
+                    _ => Severity::Info,
+                };
+                Diagnostic::new(Tool::Eslint, severity, message.message)
+                    .at(path.clone(), message.line, message.column)
+                    .with_code(message.rule_id)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_report() {
+        let output = r#"[
+            {"filePath":"/app/src/index.js","messages":[
+                {"ruleId":"no-unused-vars","severity":2,"message":"'x' is assigned a value but never used.","line":3,"column":7,"nodeType":"Identifier"},
+                {"ruleId":null,"fatal":true,"severity":2,"message":"Parsing error: Unexpected token","line":9,"column":1},
+                {"ruleId":"eqeqeq","severity":1,"message":"Expected '===' and instead saw '=='.","line":12,"column":9}
+            ],"errorCount":2,"warningCount":1},
+            {"filePath":"/app/src/clean.js","messages":[],"errorCount":0,"warningCount":0}
+        ]"#;
+
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "/app/src/index.js:3:7: error[no-unused-vars]: 'x' is assigned a value but never used."
+        );
+        assert_eq!(diagnostics[1].code, None);
+        assert_eq!(diagnostics[2].severity, Severity::Warning);
+        assert!(parse("Oops! Something went wrong!").is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+// file: src/parsers/go_test.rs
+// version: 1.1.0
+// guid: 2648341d-b637-49e4-b3d1-c39a15bf8b45
+
+//! `go test -json`: one event per line for every test and package
+//!
+//! A failed test is reported at the first `file.go:line:` in its output, which is where
+//! `t.Error` and friends point. Compile errors are printed as plain text on stderr, or as
+//! `build-output` events since Go 1.24, and are reported one by one.
+
+use super::{Diagnostic, Severity, TestCounts, Tool};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Event {
+    action: String,
+    package: Option<String>,
+    /// Set instead of `Package` on build events
+    import_path: Option<String>,
+    test: Option<String>,
+    output: Option<String>,
+}
+
+/// A Go source location starting a line of output
+struct Location {
+    file: String,
+    line: u32,
+    column: Option<u32>,
+    message: String,
+}
+
+impl Location {
+    fn find(line: &str) -> Option<Self> {
+        static LOCATION: OnceLock<Regex> = OnceLock::new();
+        let location = LOCATION.get_or_init(|| {
+            Regex::new(r"^\s*(\S+\.go):(\d+):(?:(\d+):)? ?(.*)$").expect("valid location pattern")
+        });
+        let captures = location.captures(line.trim_end())?;
+        Some(Self {
+            file: captures[1].to_string(),
+            line: captures[2].parse().ok()?,
+            column: captures
+                .get(3)
+                .and_then(|column| column.as_str().parse().ok()),
+            message: captures[4].to_string(),
+        })
+    }
+
+    fn into_diagnostic(self) -> Diagnostic {
+        Diagnostic::new(Tool::GoTest, Severity::Error, self.message).at(
+            self.file,
+            Some(self.line),
+            self.column,
+        )
+    }
+}
+
+/// Diagnostics in the JSON events on `go test -json`'s stdout and the compile errors on its
+/// stderr; output saved with both streams together can be passed as `stdout` alone
+pub fn parse(stdout: &str, stderr: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut events = Vec::new();
+    for line in stdout.lines().chain(stderr.lines()) {
+        match serde_json::from_str::<Event>(line) {
+            Ok(event) => events.push(event),
+            Err(_) => diagnostics.extend(Location::find(line).map(Location::into_diagnostic)),
+        }
+    }
+    let mut compile_failed = !diagnostics.is_empty();
+    let mut output: HashMap<(String, Option<String>), Vec<String>> = HashMap::new();
+    let mut failed_tests: Vec<(String, String)> = Vec::new();
+
+    for event in events {
+        let package = event.package.or(event.import_path).unwrap_or_default();
+        let key = (package, event.test);
+        match event.action.as_str() {
+            "output" | "build-output" => output
+                .entry(key)
+                .or_default()
+                .push(event.output.unwrap_or_default()),
+            "pass" | "skip" => {
+                output.remove(&key);
+            }
+            "fail" | "build-fail" => {
+                let lines = output.remove(&key).unwrap_or_default();
+                let (package, test) = key;
+                let Some(test) = test else {
+                    let errors: Vec<_> = lines
+                        .iter()
+                        .filter_map(|line| Location::find(line))
+                        .map(Location::into_diagnostic)
+                        .collect();
+                    if !errors.is_empty() {
+                        compile_failed = true;
+                        diagnostics.extend(errors);
+                    } else if !compile_failed && !failed_tests.iter().any(|(p, _)| *p == package) {
+                        let message = format!("package {} failed", package);
+                        diagnostics.push(Diagnostic::new(Tool::GoTest, Severity::Error, message));
+                    }
+                    continue;
+                };
+
+                // A test fails along with its failing subtests, which were already reported
+                let prefix = format!("{}/", test);
+                if !failed_tests
+                    .iter()
+                    .any(|(p, t)| *p == package && t.starts_with(&prefix))
+                {
+                    let failure = match lines.iter().find_map(|line| Location::find(line)) {
+                        Some(location) => {
+                            let mut failure = location.into_diagnostic();
+                            failure.message = format!("{} failed: {}", test, failure.message);
+                            failure
+                        }
+                        None => Diagnostic::new(
+                            Tool::GoTest,
+                            Severity::Error,
+                            format!("{} failed", test),
+                        ),
+                    };
+                    diagnostics.push(failure.with_code(Some(test.clone())));
+                }
+                failed_tests.push((package, test));
+            }
+            _ => {}
+        }
+    }
+    diagnostics
+}
+
+/// Tests, subtests included, that passed, failed and were skipped in `go test -json`'s
+/// stdout; `None` if no test ran
+pub fn test_counts(stdout: &str) -> Option<TestCounts> {
+    let mut counts = None;
+    for event in stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Event>(line).ok())
+        .filter(|event| event.test.is_some())
+    {
+        let total: &mut TestCounts = match event.action.as_str() {
+            "pass" | "fail" | "skip" => counts.get_or_insert_with(TestCounts::default),
+            _ => continue,
+        };
+        match event.action.as_str() {
+            "pass" => total.passed += 1,
+            "fail" => total.failed += 1,
+            _ => total.ignored += 1,
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_failed_tests() {
+        let stdout = [
+            r#"{"Action":"start","Package":"example.com/calc"}"#,
+            r#"{"Action":"run","Package":"example.com/calc","Test":"TestAdd"}"#,
+            r#"{"Action":"output","Package":"example.com/calc","Test":"TestAdd","Output":"=== RUN   TestAdd\n"}"#,
+            r#"{"Action":"output","Package":"example.com/calc","Test":"TestAdd","Output":"    calc_test.go:8: got 3, want 4\n"}"#,
+            r#"{"Action":"fail","Package":"example.com/calc","Test":"TestAdd","Elapsed":0}"#,
+            r#"{"Action":"output","Package":"example.com/calc","Test":"TestDiv/zero","Output":"    calc_test.go:21: expected an error\n"}"#,
+            r#"{"Action":"fail","Package":"example.com/calc","Test":"TestDiv/zero","Elapsed":0}"#,
+            r#"{"Action":"fail","Package":"example.com/calc","Test":"TestDiv","Elapsed":0}"#,
+            r#"{"Action":"pass","Package":"example.com/calc","Test":"TestSub","Elapsed":0}"#,
+            r#"{"Action":"output","Package":"example.com/calc","Output":"FAIL\n"}"#,
+            r#"{"Action":"fail","Package":"example.com/calc","Elapsed":0.01}"#,
+            r#"{"Action":"fail","Package":"example.com/empty","Elapsed":0}"#,
+        ]
+        .join("\n");
+
+        let diagnostics = parse(&stdout, "");
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "calc_test.go:8: error[TestAdd]: TestAdd failed: got 3, want 4"
+        );
+        assert_eq!(diagnostics[1].code.as_deref(), Some("TestDiv/zero"));
+        assert_eq!(diagnostics[1].line, Some(21));
+        assert_eq!(diagnostics[2].message, "package example.com/empty failed");
+        assert_eq!(
+            test_counts(&stdout),
+            Some(TestCounts {
+                passed: 1,
+                failed: 3,
+                ..TestCounts::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_compile_errors() {
+        let stdout = r#"{"Action":"output","Package":"example.com/calc","Output":"FAIL\texample.com/calc [build failed]\n"}
+{"Action":"fail","Package":"example.com/calc","Elapsed":0}"#;
+        let stderr = "# example.com/calc\n./calc.go:5:9: undefined: total\n";
+
+        let diagnostics = parse(stdout, stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(parse(&format!("{}\n{}", stderr, stdout), ""), diagnostics);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "./calc.go:5:9: error: undefined: total"
+        );
+
+        let events = r#"{"ImportPath":"example.com/calc","Action":"build-output","Output":"./calc.go:5:9: undefined: total\n"}
+{"ImportPath":"example.com/calc","Action":"build-fail"}
+{"Action":"fail","Package":"example.com/calc","Elapsed":0,"FailedBuild":"example.com/calc"}"#;
+        assert_eq!(parse(events, ""), diagnostics);
+    }
+}
@@ -0,0 +1,117 @@
+// file: src/parsers/gradle.rs
+// version: 1.0.0
+// guid: 6c2f9a41-8d37-4e15-b0c6-15e7a3d9f482
+
+//! Gradle's plain console output
+//!
+//! javac reports `File.java:12: error: message` and the Kotlin compiler `e: file:///File.kt:12:5
+//! message` (or `e: File.kt: (12, 5): message` before Kotlin 1.9), with `warning:` and `w:` for
+//! warnings. A failed test is printed as `Class > method() FAILED`, followed by the exception
+//! and the line of the test class it was thrown from.
+
+use super::{Diagnostic, Severity, Tool};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Diagnostics for the compile errors, warnings and failed tests in Gradle's output
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    static JAVAC: OnceLock<Regex> = OnceLock::new();
+    static KOTLIN: OnceLock<Regex> = OnceLock::new();
+    static TEST: OnceLock<Regex> = OnceLock::new();
+    static EXCEPTION: OnceLock<Regex> = OnceLock::new();
+    let javac = JAVAC.get_or_init(|| {
+        Regex::new(r"^(\S+?\.java):(\d+): (error|warning): (.*)$").expect("valid javac pattern")
+    });
+    let kotlin = KOTLIN.get_or_init(|| {
+        Regex::new(r"^([ew]): (?:file://)?(\S+?\.kts?)(?::(\d+):(\d+)|: \((\d+), (\d+)\):) (.*)$")
+            .expect("valid kotlin pattern")
+    });
+    let test =
+        TEST.get_or_init(|| Regex::new(r"^(\S.*? > .+) FAILED$").expect("valid test pattern"));
+    let exception = EXCEPTION.get_or_init(|| {
+        Regex::new(r"^\s+(\S+?)(?:: .*)? at (\S+?):(\d+)$").expect("valid exception pattern")
+    });
+
+    let number = |text: Option<regex::Match>| text.and_then(|n| n.as_str().parse().ok());
+    let mut diagnostics = Vec::new();
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        if let Some(captures) = javac.captures(line) {
+            let severity = match &captures[3] {
+                "error" => Severity::Error,
+                _ => Severity::Warning,
+            };
+            diagnostics.push(Diagnostic::new(Tool::Gradle, severity, &captures[4]).at(
+                &captures[1],
+                number(captures.get(2)),
+                None,
+            ));
+        } else if let Some(captures) = kotlin.captures(line) {
+            let severity = match &captures[1] {
+                "e" => Severity::Error,
+                _ => Severity::Warning,
+            };
+            diagnostics.push(Diagnostic::new(Tool::Gradle, severity, &captures[7]).at(
+                &captures[2],
+                number(captures.get(3).or(captures.get(5))),
+                number(captures.get(4).or(captures.get(6))),
+            ));
+        } else if let Some(captures) = test.captures(line) {
+            let name = captures[1].to_string();
+            let thrown = lines.peek().and_then(|next| exception.captures(next));
+            let failure = match thrown {
+                Some(thrown) => Diagnostic::new(
+                    Tool::Gradle,
+                    Severity::Error,
+                    format!("{} failed: {}", name, &thrown[1]),
+                )
+                .at(&thrown[2], number(thrown.get(3)), None),
+                None => Diagnostic::new(Tool::Gradle, Severity::Error, format!("{} failed", name)),
+            };
+            diagnostics.push(failure.with_code(Some(name)));
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compile_errors_and_test_failures() {
+        let output = "\
+> Task :compileJava FAILED
+/work/app/src/main/java/app/App.java:12: error: cannot find symbol
+        run();
+        ^
+  symbol:   method run()
+/work/app/src/main/java/app/App.java:5: warning: [deprecation] Date(int,int,int) in Date has been deprecated
+e: file:///work/app/src/main/kotlin/app/Util.kt:7:13 Unresolved reference 'missing'.
+w: /work/app/src/main/kotlin/app/Util.kt: (3, 9): Variable 'x' is never used
+
+> Task :test FAILED
+
+app.CalcTest > addsNumbers() FAILED
+    org.opentest4j.AssertionFailedError at CalcTest.java:14
+
+app.CalcTest > divides(int) > [2] 0 FAILED
+    java.lang.ArithmeticException: / by zero at CalcTest.java:22
+
+3 tests completed, 2 failed
+";
+        let diagnostics = parse(output);
+        let lines: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "/work/app/src/main/java/app/App.java:12: error: cannot find symbol",
+                "/work/app/src/main/java/app/App.java:5: warning: [deprecation] Date(int,int,int) in Date has been deprecated",
+                "/work/app/src/main/kotlin/app/Util.kt:7:13: error: Unresolved reference 'missing'.",
+                "/work/app/src/main/kotlin/app/Util.kt:3:9: warning: Variable 'x' is never used",
+                "CalcTest.java:14: error[app.CalcTest > addsNumbers()]: app.CalcTest > addsNumbers() failed: org.opentest4j.AssertionFailedError",
+                "CalcTest.java:22: error[app.CalcTest > divides(int) > [2] 0]: app.CalcTest > divides(int) > [2] 0 failed: java.lang.ArithmeticException",
+            ]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+// file: src/parsers/junit.rs
+// version: 1.0.0
+// guid: 3f71c8e2-5b94-4d06-a2e7-c8190d4b6f53
+
+//! JUnit XML reports, as Gradle, Maven's surefire and failsafe, and most other JVM test
+//! runners write them
+//!
+//! Every `<testsuite>` element carries the number of tests it ran and of those that failed,
+//! errored and were skipped, which is all a summary needs; the test cases are not read.
+
+use super::TestCounts;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Tests counted by the `<testsuite>` elements of a report; `None` if it has none
+pub fn test_counts(xml: &str) -> Option<TestCounts> {
+    static SUITE: OnceLock<Regex> = OnceLock::new();
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let suite =
+        SUITE.get_or_init(|| Regex::new(r"<testsuite\s[^>]*>").expect("valid suite pattern"));
+    let attribute = ATTRIBUTE.get_or_init(|| {
+        Regex::new(r#"\s(tests|failures|errors|skipped)\s*=\s*["'](\d+)["']"#)
+            .expect("valid attribute pattern")
+    });
+
+    let mut counts = None;
+    for tag in suite.find_iter(xml) {
+        let (mut tests, mut failed, mut skipped) = (0, 0, 0);
+        for captures in attribute.captures_iter(tag.as_str()) {
+            let value: u32 = captures[2].parse().unwrap_or_default();
+            match &captures[1] {
+                "tests" => tests = value,
+                "skipped" => skipped = value,
+                _ => failed += value,
+            }
+        }
+        let total: &mut TestCounts = counts.get_or_insert_with(TestCounts::default);
+        total.passed += tests.saturating_sub(failed + skipped);
+        total.failed += failed;
+        total.ignored += skipped;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_of_suites() {
+        let report = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="app.CalcTest" tests="5" skipped="1" failures="1" errors="1" timestamp="2026-10-17T09:00:00" time="0.05">
+  <testcase name="addsNumbers()" classname="app.CalcTest" time="0.01">
+    <failure message="expected: &lt;4&gt; but was: &lt;3&gt;" type="org.opentest4j.AssertionFailedError"/>
+  </testcase>
+</testsuite>"#;
+        assert_eq!(
+            test_counts(report),
+            Some(TestCounts {
+                passed: 2,
+                failed: 2,
+                ignored: 1,
+                filtered_out: 0,
+            })
+        );
+        // The totals of a <testsuites> wrapper are not counted twice
+        let wrapped = "<testsuites tests=\"2\"><testsuite name='a' tests='2' failures='0' errors='0'></testsuite></testsuites>";
+        assert_eq!(test_counts(wrapped).unwrap().passed, 2);
+        assert_eq!(test_counts("<testsuites/>"), None);
+    }
+}
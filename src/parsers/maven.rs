@@ -0,0 +1,113 @@
+// file: src/parsers/maven.rs
+// version: 1.0.0
+// guid: 0e8b5d27-a4c9-4f61-93b8-7d2c6e41f5a0
+
+//! Maven's batch-mode output
+//!
+//! The compiler plugins report `[ERROR] /path/File.java:[12,5] message` (or
+//! `[ERROR] file:///path/File.kt:12:5 message` for Kotlin), with `[WARNING]` for warnings.
+//! Surefire and failsafe list failed tests after their run, as `[ERROR]   Class.method:12 message`
+//! under `[ERROR] Failures:` and `[ERROR] Errors:`.
+
+use super::{Diagnostic, Severity, Tool};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Diagnostics for the compile errors, warnings and failed tests in Maven's output
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    static COMPILER: OnceLock<Regex> = OnceLock::new();
+    static TEST: OnceLock<Regex> = OnceLock::new();
+    let compiler = COMPILER.get_or_init(|| {
+        Regex::new(
+            r"^\[(ERROR|WARNING)\] (?:file://)?(\S+?\.(?:java|kt|kts|scala|groovy)):(?:\[(\d+),(\d+)\]|(\d+):(\d+)) (.*)$",
+        )
+        .expect("valid compiler pattern")
+    });
+    let test = TEST.get_or_init(|| {
+        Regex::new(r"^\[ERROR\] {2,}(\S+?)(?::(\d+)(?:->\S*)?)? (?:» )?(.*)$")
+            .expect("valid test pattern")
+    });
+
+    let number = |text: Option<regex::Match>| text.and_then(|n| n.as_str().parse().ok());
+    let mut diagnostics = Vec::new();
+    let mut in_results = false;
+    for line in output.lines() {
+        let line = line.trim_end();
+        if line == "[ERROR] Failures:" || line == "[ERROR] Errors:" {
+            in_results = true;
+            continue;
+        }
+        if in_results {
+            match test.captures(line) {
+                // Flaky tests list each run below the test
+                Some(captures) if &captures[1] == "Run" => {}
+                Some(captures) => {
+                    let name = captures[1].to_string();
+                    let mut message = format!("{} failed", name);
+                    if let Some(line) = captures.get(2) {
+                        message.push_str(&format!(" at line {}", line.as_str()));
+                    }
+                    if !captures[3].is_empty() {
+                        message.push_str(&format!(": {}", &captures[3]));
+                    }
+                    diagnostics.push(
+                        Diagnostic::new(Tool::Maven, Severity::Error, message)
+                            .with_code(Some(name)),
+                    );
+                }
+                None => in_results = false,
+            }
+            continue;
+        }
+        if let Some(captures) = compiler.captures(line) {
+            let severity = match &captures[1] {
+                "ERROR" => Severity::Error,
+                _ => Severity::Warning,
+            };
+            diagnostics.push(Diagnostic::new(Tool::Maven, severity, &captures[7]).at(
+                &captures[2],
+                number(captures.get(3).or(captures.get(5))),
+                number(captures.get(4).or(captures.get(6))),
+            ));
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compile_errors_and_test_results() {
+        let output = "\
+[INFO] --- compiler:3.13.0:compile (default-compile) @ app ---
+[WARNING] /work/app/src/main/java/app/App.java:[5,21] [deprecation] Date(int,int,int) in Date has been deprecated
+[ERROR] /work/app/src/main/java/app/App.java:[12,9] cannot find symbol
+[ERROR] file:///work/app/src/main/kotlin/app/Util.kt:7:13 Unresolved reference 'missing'.
+[INFO] -------------------------------------------------------
+[INFO]  T E S T S
+[ERROR] Tests run: 3, Failures: 1, Errors: 1, Skipped: 0, Time elapsed: 0.05 s <<< FAILURE! -- in app.CalcTest
+[INFO] Results:
+[INFO]
+[ERROR] Failures:
+[ERROR]   CalcTest.addsNumbers:14 expected: <4> but was: <3>
+[ERROR] Errors:
+[ERROR]   CalcTest.divides:22 » Arithmetic / by zero
+[INFO]
+[ERROR] Tests run: 3, Failures: 1, Errors: 1, Skipped: 0
+";
+        let diagnostics = parse(output);
+        let lines: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "/work/app/src/main/java/app/App.java:5:21: warning: [deprecation] Date(int,int,int) in Date has been deprecated",
+                "/work/app/src/main/java/app/App.java:12:9: error: cannot find symbol",
+                "/work/app/src/main/kotlin/app/Util.kt:7:13: error: Unresolved reference 'missing'.",
+                "error[CalcTest.addsNumbers]: CalcTest.addsNumbers failed at line 14: expected: <4> but was: <3>",
+                "error[CalcTest.divides]: CalcTest.divides failed at line 22: Arithmetic / by zero",
+            ]
+        );
+    }
+}
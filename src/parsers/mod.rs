@@ -0,0 +1,549 @@
+// file: src/parsers/mod.rs
+// version: 1.5.0
+// guid: 58719451-f2c6-4696-9ff2-edc99d28d706
+
+//! Typed diagnostics from the output of common tools
+//!
+//! Each parser turns what a tool prints in its machine-readable format into [`Diagnostic`]s,
+//! so wrapper commands report compiler errors, lint violations and failed tests the same way
+//! whichever tool found them. [`Tool::invocation`] adds the arguments that make a tool print
+//! that format, and [`Tool::parse`] reads it back.
+//!
+//! [`new_findings`] compares findings with those of a baseline - an earlier run, or a run on
+//! the base branch - so that legacy code with many existing findings can still be gated on the
+//! ones a change introduces. [`sarif`] writes diagnostics as a SARIF log for code scanning
+//! services.
+
+pub mod buf;
+pub mod cargo;
+pub mod ctest;
+pub mod eslint;
+pub mod go_test;
+pub mod gradle;
+pub mod junit;
+pub mod maven;
+pub mod pytest;
+pub mod ruff;
+pub mod shellcheck;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Notes and hints
+    Info,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+        }
+    }
+}
+
+/// A finding reported by a tool: a compiler error, a lint violation or a failed test
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub tool: Tool,
+    pub severity: Severity,
+    /// File the finding is in, as the tool reported it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+    /// Rule, lint or error code, or the name of the failed test
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(tool: Tool, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            tool,
+            severity,
+            file: None,
+            line: None,
+            column: None,
+            code: None,
+            message: message.into(),
+        }
+    }
+
+    /// Set where the finding is
+    pub fn at(mut self, file: impl Into<String>, line: Option<u32>, column: Option<u32>) -> Self {
+        self.file = Some(file.into());
+        self.line = line;
+        self.column = column;
+        self
+    }
+
+    /// Make an absolute path to the file relative to `root`, the directory the tool ran in,
+    /// so findings of runs in different checkouts compare equal
+    pub fn relative_to(mut self, root: &Path) -> Self {
+        let canonical = root.canonicalize().ok();
+        let roots = [Some(root), canonical.as_deref()];
+        let relative = self.file.as_deref().and_then(|file| {
+            roots
+                .iter()
+                .flatten()
+                .find_map(|root| Path::new(file).strip_prefix(root).ok())
+                .map(|path| path.to_string_lossy().into_owned())
+        });
+        if relative.is_some() {
+            self.file = relative;
+        }
+        self
+    }
+
+    /// What identifies a finding across runs: everything but its line and column, which
+    /// shift whenever code above it changes
+    fn fingerprint(&self) -> (Tool, Severity, Option<String>, Option<String>, String) {
+        (
+            self.tool,
+            self.severity,
+            self.file.clone(),
+            self.code.clone(),
+            self.message.clone(),
+        )
+    }
+
+    /// Set the rule or error code, ignoring empty ones
+    pub fn with_code(mut self, code: Option<impl Into<String>>) -> Self {
+        self.code = code
+            .map(Into::into)
+            .filter(|code: &String| !code.is_empty());
+        self
+    }
+}
+
+/// `file:line:column: severity[code]: message`, the format editors and CI annotators parse
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+                if let Some(column) = self.column {
+                    write!(f, "{}:", column)?;
+                }
+            }
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.severity.as_str())?;
+        if let Some(code) = &self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// How many tests a test run passed, failed and skipped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TestCounts {
+    pub passed: u32,
+    pub failed: u32,
+    /// Ignored or skipped
+    pub ignored: u32,
+    /// Not run because of a name filter; only cargo reports these
+    pub filtered_out: u32,
+}
+
+/// Tools whose output can be parsed into diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tool {
+    /// `cargo check`, `build`, `clippy` and `test` with `--message-format=json`
+    Cargo,
+    /// `eslint --format json`
+    Eslint,
+    /// `ruff check --output-format json`
+    Ruff,
+    /// `go test -json`
+    GoTest,
+    /// pytest's short test summary and failure sections
+    Pytest,
+    /// `buf lint`, `build` and `breaking` with `--error-format=json`
+    Buf,
+    /// `shellcheck --format json1`
+    Shellcheck,
+    /// `gradle --console=plain`
+    Gradle,
+    /// `mvn -B`
+    Maven,
+    /// `ctest --output-on-failure`
+    Ctest,
+}
+
+impl Tool {
+    pub const ALL: &'static [Tool] = &[
+        Self::Cargo,
+        Self::Eslint,
+        Self::Ruff,
+        Self::GoTest,
+        Self::Pytest,
+        Self::Buf,
+        Self::Shellcheck,
+        Self::Gradle,
+        Self::Maven,
+        Self::Ctest,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cargo => "cargo",
+            Self::Eslint => "eslint",
+            Self::Ruff => "ruff",
+            Self::GoTest => "go-test",
+            Self::Pytest => "pytest",
+            Self::Buf => "buf",
+            Self::Shellcheck => "shellcheck",
+            Self::Gradle => "gradle",
+            Self::Maven => "maven",
+            Self::Ctest => "ctest",
+        }
+    }
+
+    /// Program and arguments that run the tool with `args` so that it prints what
+    /// [`Tool::parse`] reads
+    ///
+    /// `args` are the tool's own arguments, starting with its subcommand for cargo (default
+    /// `check`), ruff (default `check .`) and buf (default `lint`) and with the tasks or goals
+    /// for gradle (default `build`) and maven (default `verify`); go test and pytest take
+    /// packages, paths and flags; shellcheck takes the scripts to check; ctest takes its options
+    /// (default `--test-dir build`).
+    pub fn invocation(self, args: &[String]) -> (&'static str, Vec<String>) {
+        let owned = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let or_default = |default: &[&str]| {
+            if args.is_empty() {
+                owned(default)
+            } else {
+                args.to_vec()
+            }
+        };
+        match self {
+            Self::Cargo => {
+                let mut args = or_default(&["check"]);
+                args.insert(1, "--message-format=json".to_string());
+                ("cargo", args)
+            }
+            Self::Eslint => {
+                let mut full = owned(&["--format", "json"]);
+                full.extend(or_default(&["."]));
+                ("eslint", full)
+            }
+            Self::Ruff => {
+                let mut args = or_default(&["check", "."]);
+                args.splice(1..1, owned(&["--output-format", "json"]));
+                ("ruff", args)
+            }
+            Self::GoTest => {
+                let mut full = owned(&["test", "-json"]);
+                full.extend(or_default(&["./..."]));
+                ("go", full)
+            }
+            Self::Pytest => {
+                // -rfE lists failures and errors in the short test summary
+                let mut full = owned(&["-m", "pytest", "-rfE"]);
+                full.extend_from_slice(args);
+                ("python3", full)
+            }
+            Self::Buf => {
+                let mut args = or_default(&["lint"]);
+                args.insert(1, "--error-format=json".to_string());
+                ("buf", args)
+            }
+            Self::Shellcheck => {
+                let mut full = owned(&["--format", "json1"]);
+                full.extend_from_slice(args);
+                ("shellcheck", full)
+            }
+            Self::Gradle => {
+                let mut full = owned(&["--console=plain"]);
+                full.extend(or_default(&["build"]));
+                ("gradle", full)
+            }
+            Self::Maven => {
+                let mut full = owned(&["-B"]);
+                full.extend(or_default(&["verify"]));
+                ("mvn", full)
+            }
+            Self::Ctest => {
+                let mut full = owned(&["--output-on-failure"]);
+                full.extend(or_default(&["--test-dir", "build"]));
+                ("ctest", full)
+            }
+        }
+    }
+
+    /// Diagnostics in the output of the tool run as [`Tool::invocation`] runs it, in the
+    /// order reported and without duplicates
+    pub fn parse(self, stdout: &str, stderr: &str) -> Vec<Diagnostic> {
+        let diagnostics = match self {
+            Self::Cargo => cargo::parse(stdout),
+            Self::Eslint => eslint::parse(stdout),
+            Self::Ruff => ruff::parse(stdout),
+            Self::GoTest => go_test::parse(stdout, stderr),
+            Self::Pytest => pytest::parse(stdout),
+            Self::Buf => buf::parse(stdout),
+            Self::Shellcheck => shellcheck::parse(stdout),
+            Self::Gradle => gradle::parse(&format!("{}\n{}", stdout, stderr)),
+            Self::Maven => maven::parse(stdout),
+            Self::Ctest => ctest::parse(stdout),
+        };
+        // cargo reports a warning once for every target that compiles the file
+        let mut seen = HashSet::new();
+        diagnostics
+            .into_iter()
+            .filter(|diagnostic| seen.insert(diagnostic.clone()))
+            .collect()
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Tool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|tool| tool.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|tool| tool.as_str()).collect();
+                format!(
+                    "Unknown tool '{}' (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// The findings in `diagnostics` that `baseline` does not have
+///
+/// Findings are matched on everything but their position, and as many times as the baseline
+/// has them: a third identical warning in a file that had two is new.
+pub fn new_findings(diagnostics: Vec<Diagnostic>, baseline: &[Diagnostic]) -> Vec<Diagnostic> {
+    let mut known: HashMap<_, usize> = HashMap::new();
+    for diagnostic in baseline {
+        *known.entry(diagnostic.fingerprint()).or_default() += 1;
+    }
+    diagnostics
+        .into_iter()
+        .filter(
+            |diagnostic| match known.get_mut(&diagnostic.fingerprint()) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            },
+        )
+        .collect()
+}
+
+/// Count diagnostics per severity, as JSON
+pub fn summary(diagnostics: &[Diagnostic]) -> Value {
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+    json!({
+        "total": diagnostics.len(),
+        "errors": count(Severity::Error),
+        "warnings": count(Severity::Warning),
+        "info": count(Severity::Info),
+    })
+}
+
+/// Diagnostics as a SARIF 2.1.0 log, with one run per tool
+pub fn sarif(diagnostics: &[Diagnostic]) -> Value {
+    let mut tools: Vec<Tool> = Vec::new();
+    for diagnostic in diagnostics {
+        if !tools.contains(&diagnostic.tool) {
+            tools.push(diagnostic.tool);
+        }
+    }
+    let runs: Vec<Value> = tools
+        .into_iter()
+        .map(|tool| {
+            let mut rules: Vec<&str> = Vec::new();
+            let results: Vec<Value> = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.tool == tool)
+                .map(|diagnostic| {
+                    let level = match diagnostic.severity {
+                        Severity::Error => "error",
+                        Severity::Warning => "warning",
+                        Severity::Info => "note",
+                    };
+                    let mut result = json!({
+                        "level": level,
+                        "message": {"text": diagnostic.message},
+                    });
+                    if let Some(code) = &diagnostic.code {
+                        if !rules.contains(&code.as_str()) {
+                            rules.push(code);
+                        }
+                        result["ruleId"] = json!(code);
+                    }
+                    if let Some(file) = &diagnostic.file {
+                        let mut location = json!({"artifactLocation": {"uri": file}});
+                        if let Some(line) = diagnostic.line {
+                            location["region"] = json!({"startLine": line});
+                            if let Some(column) = diagnostic.column {
+                                location["region"]["startColumn"] = json!(column);
+                            }
+                        }
+                        result["locations"] = json!([{"physicalLocation": location}]);
+                    }
+                    result
+                })
+                .collect();
+            let rules: Vec<Value> = rules.into_iter().map(|id| json!({"id": id})).collect();
+            json!({
+                "tool": {"driver": {"name": tool.as_str(), "rules": rules}},
+                "results": results,
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": runs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invocation_adds_machine_readable_format() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            Tool::Cargo.invocation(&args(&["clippy", "--", "-D", "warnings"])),
+            (
+                "cargo",
+                args(&["clippy", "--message-format=json", "--", "-D", "warnings"])
+            )
+        );
+        assert_eq!(
+            Tool::Ruff.invocation(&[]),
+            ("ruff", args(&["check", "--output-format", "json", "."]))
+        );
+        assert_eq!(
+            Tool::GoTest.invocation(&[]),
+            ("go", args(&["test", "-json", "./..."]))
+        );
+        for tool in Tool::ALL {
+            assert_eq!(tool.as_str().parse::<Tool>(), Ok(*tool));
+        }
+        assert!("make".parse::<Tool>().is_err());
+    }
+
+    #[test]
+    fn test_sarif_groups_results_by_tool() {
+        let diagnostics = vec![
+            Diagnostic::new(
+                Tool::Shellcheck,
+                Severity::Info,
+                "Double quote to prevent globbing",
+            )
+            .at("deploy.sh", Some(3), Some(6))
+            .with_code(Some("SC2086")),
+            Diagnostic::new(Tool::Ruff, Severity::Error, "`os` imported but unused")
+                .at("app.py", Some(1), None)
+                .with_code(Some("F401")),
+            Diagnostic::new(
+                Tool::Shellcheck,
+                Severity::Info,
+                "Double quote to prevent globbing",
+            )
+            .at("deploy.sh", Some(7), Some(2))
+            .with_code(Some("SC2086")),
+        ];
+        let log = sarif(&diagnostics);
+        assert_eq!(log["version"], "2.1.0");
+        let runs = log["runs"].as_array().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0]["tool"]["driver"]["name"], "shellcheck");
+        assert_eq!(
+            runs[0]["tool"]["driver"]["rules"],
+            json!([{"id": "SC2086"}])
+        );
+        assert_eq!(runs[0]["results"].as_array().unwrap().len(), 2);
+        let result = &runs[0]["results"][1];
+        assert_eq!(result["level"], "note");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"],
+            json!({"artifactLocation": {"uri": "deploy.sh"}, "region": {"startLine": 7, "startColumn": 2}})
+        );
+        assert!(
+            runs[1]["results"][0]["locations"][0]["physicalLocation"]["region"]
+                .get("startColumn")
+                .is_none()
+        );
+        assert!(sarif(&[])["runs"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_new_findings_ignore_moved_lines() {
+        let unused = |line| {
+            Diagnostic::new(Tool::Cargo, Severity::Warning, "unused variable: `x`")
+                .at("/work/base/src/lib.rs", Some(line), Some(9))
+                .with_code(Some("unused_variables"))
+                .relative_to(Path::new("/work/base"))
+        };
+        assert_eq!(unused(1).file.as_deref(), Some("src/lib.rs"));
+        let mismatched = Diagnostic::new(Tool::Cargo, Severity::Error, "mismatched types")
+            .at("src/lib.rs", Some(12), Some(5))
+            .with_code(Some("E0308"));
+
+        let baseline = vec![unused(3)];
+        let current = vec![unused(8), mismatched.clone(), unused(20)];
+        assert_eq!(
+            new_findings(current, &baseline),
+            vec![mismatched, unused(20)]
+        );
+    }
+
+    #[test]
+    fn test_display_and_duplicates() {
+        let diagnostic = Diagnostic::new(Tool::Ruff, Severity::Error, "`os` imported but unused")
+            .at("app.py", Some(1), Some(8))
+            .with_code(Some("F401"));
+        assert_eq!(
+            diagnostic.to_string(),
+            "app.py:1:8: error[F401]: `os` imported but unused"
+        );
+        let unlocated = Diagnostic::new(Tool::GoTest, Severity::Error, "package failed");
+        assert_eq!(unlocated.to_string(), "error: package failed");
+
+        let message = r#"{"reason":"compiler-message","message":{"message":"unused variable: `x`","code":{"code":"unused_variables"},"level":"warning","spans":[{"file_name":"src/lib.rs","line_start":2,"column_start":9,"is_primary":true}]}}"#;
+        let twice = format!("{}\n{}\n", message, message);
+        assert_eq!(Tool::Cargo.parse(&twice, "").len(), 1);
+    }
+}
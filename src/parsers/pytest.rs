@@ -0,0 +1,134 @@
+// file: src/parsers/pytest.rs
+// version: 1.0.0
+// guid: e3d84c94-f54a-47b7-9cb7-32d2cbb0a27f
+
+//! pytest's plain output
+//!
+//! The `short test summary info` that `-rfE` prints names every failed test and error; the
+//! section with the same title above it gives the line in the test file where it failed.
+
+use super::{Diagnostic, Severity, Tool};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Diagnostics for the failures and errors in pytest's stdout
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    static SUMMARY: OnceLock<Regex> = OnceLock::new();
+    static SECTION: OnceLock<Regex> = OnceLock::new();
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    let summary = SUMMARY.get_or_init(|| {
+        Regex::new(r"^(FAILED|ERROR) (.+?)(?: - (.*))?$").expect("valid summary pattern")
+    });
+    let section =
+        SECTION.get_or_init(|| Regex::new(r"^_{3,} (.+?) _{3,}$").expect("valid section pattern"));
+    let location =
+        LOCATION.get_or_init(|| Regex::new(r"^(\S+\.py):(\d+):").expect("valid location pattern"));
+
+    // Locations in each failure section, by section title
+    let mut sections: HashMap<&str, Vec<(&str, u32)>> = HashMap::new();
+    let mut title = None;
+    for line in output.lines() {
+        if let Some(captures) = section.captures(line) {
+            title = captures.get(1).map(|t| t.as_str());
+        } else if let (Some(title), Some(captures)) = (title, location.captures(line)) {
+            if let Ok(number) = captures[2].parse() {
+                let file = captures.get(1).map_or("", |f| f.as_str());
+                sections.entry(title).or_default().push((file, number));
+            }
+        }
+    }
+
+    output
+        .lines()
+        .filter_map(|line| summary.captures(line))
+        .map(|captures| {
+            let error = &captures[1] == "ERROR";
+            let node = &captures[2];
+            let (file, test) = match node.split_once("::") {
+                Some((file, test)) => (file, Some(test.replace("::", "."))),
+                None => (node, None),
+            };
+            let message = match (captures.get(3), &test) {
+                (Some(message), _) => message.as_str().to_string(),
+                (None, _) if !error => "test failed".to_string(),
+                (None, Some(_)) => "error in test setup or teardown".to_string(),
+                (None, None) => "could not collect tests".to_string(),
+            };
+
+            let titles = match &test {
+                Some(test) if error => vec![
+                    format!("ERROR at setup of {}", test),
+                    format!("ERROR at teardown of {}", test),
+                ],
+                Some(test) => vec![test.clone()],
+                None => vec![format!("ERROR collecting {}", file)],
+            };
+            let line = titles
+                .iter()
+                .filter_map(|title| sections.get(title.as_str()))
+                .flatten()
+                .find(|(path, _)| *path == file)
+                .map(|(_, line)| *line);
+            Diagnostic::new(Tool::Pytest, Severity::Error, message)
+                .at(file, line, None)
+                .with_code(test)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary_and_sections() {
+        let output = "\
+============================= test session starts ==============================
+collected 4 items
+
+tests/test_calc.py .FE                                                   [ 75%]
+tests/test_io.py E                                                       [100%]
+
+==================================== ERRORS ====================================
+____________________ ERROR at setup of TestCalc.test_divide ____________________
+
+    @pytest.fixture
+    def numbers():
+>       raise RuntimeError(\"no numbers\")
+E       RuntimeError: no numbers
+
+tests/test_calc.py:7: RuntimeError
+______________________ ERROR collecting tests/test_io.py _______________________
+tests/test_io.py:1: in <module>
+    import missing
+E   ModuleNotFoundError: No module named 'missing'
+=================================== FAILURES ===================================
+___________________________________ test_add ___________________________________
+
+    def test_add():
+>       assert add(1, 2) == 4
+E       assert 3 == 4
+
+tests/test_calc.py:12: AssertionError
+=========================== short test summary info ============================
+FAILED tests/test_calc.py::test_add - assert 3 == 4
+ERROR tests/test_calc.py::TestCalc::test_divide - RuntimeError: no numbers
+ERROR tests/test_io.py
+===================== 1 failed, 1 passed, 2 errors in 0.05s ====================
+";
+
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "tests/test_calc.py:12: error[test_add]: assert 3 == 4"
+        );
+        assert_eq!(diagnostics[1].code.as_deref(), Some("TestCalc.test_divide"));
+        assert_eq!(diagnostics[1].line, Some(7));
+        assert_eq!(
+            diagnostics[2].to_string(),
+            "tests/test_io.py:1: error: could not collect tests"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+// file: src/parsers/ruff.rs
+// version: 1.0.0
+// guid: 4c57e249-671d-475b-b528-c17812ee6d74
+
+//! `ruff check --output-format json`: an array of violations
+
+use super::{Diagnostic, Severity, Tool};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Violation {
+    /// `None` for syntax errors
+    code: Option<String>,
+    message: String,
+    filename: String,
+    location: Option<Location>,
+}
+
+#[derive(Deserialize)]
+struct Location {
+    row: u32,
+    column: u32,
+}
+
+/// Diagnostics in ruff's stdout; every violation fails `ruff check`, so all are errors
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let Ok(violations) = serde_json::from_str::<Vec<Violation>>(output.trim()) else {
+        return Vec::new();
+    };
+    violations
+        .into_iter()
+        .map(|violation| {
+            let (line, column) = match violation.location {
+                Some(location) => (Some(location.row), Some(location.column)),
+                None => (None, None),
+            };
+            Diagnostic::new(Tool::Ruff, Severity::Error, violation.message)
+                .at(violation.filename, line, column)
+                .with_code(violation.code)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_violations() {
+        let output = r#"[
+          {"cell":null,"code":"F401","end_location":{"column":10,"row":1},"filename":"/app/main.py","fix":{"applicability":"safe","edits":[],"message":"Remove unused import: `os`"},"location":{"column":8,"row":1},"message":"`os` imported but unused","noqa_row":1,"url":"https://docs.astral.sh/ruff/rules/unused-import"},
+          {"cell":null,"code":null,"end_location":{"column":1,"row":4},"filename":"/app/broken.py","fix":null,"location":{"column":5,"row":3},"message":"SyntaxError: Expected ')', found newline","noqa_row":null,"url":null}
+        ]"#;
+
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "/app/main.py:1:8: error[F401]: `os` imported but unused"
+        );
+        assert_eq!(diagnostics[1].code, None);
+        assert_eq!(diagnostics[1].line, Some(3));
+        assert!(parse("[]").is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+// file: src/parsers/shellcheck.rs
+// version: 1.0.0
+// guid: 0b7e5d2a-93c4-4f61-8a1e-6d2f4c9b3e70
+
+//! `shellcheck --format json1`: an object with a `comments` array, or the bare array printed
+//! by the older `--format json`
+
+use super::{Diagnostic, Severity, Tool};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Output {
+    Json1 { comments: Vec<Comment> },
+    Json(Vec<Comment>),
+}
+
+#[derive(Deserialize)]
+struct Comment {
+    file: String,
+    line: u32,
+    column: u32,
+    level: String,
+    code: u32,
+    message: String,
+}
+
+/// Diagnostics in shellcheck's stdout; `style` comments are reported as info
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let comments = match serde_json::from_str::<Output>(output.trim()) {
+        Ok(Output::Json1 { comments }) | Ok(Output::Json(comments)) => comments,
+        Err(_) => return Vec::new(),
+    };
+    comments
+        .into_iter()
+        .map(|comment| {
+            let severity = match comment.level.as_str() {
+                "error" => Severity::Error,
+                "warning" => Severity::Warning,
+                _ => Severity::Info,
+            };
+            Diagnostic::new(Tool::Shellcheck, severity, comment.message)
+                .at(comment.file, Some(comment.line), Some(comment.column))
+                .with_code(Some(format!("SC{}", comment.code)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_comments() {
+        let output = r#"{"comments":[
+          {"file":"deploy.sh","line":3,"endLine":3,"column":6,"endColumn":11,"level":"info","code":2086,"message":"Double quote to prevent globbing and word splitting.","fix":null},
+          {"file":"deploy.sh","line":5,"endLine":5,"column":1,"endColumn":7,"level":"warning","code":2164,"message":"Use 'cd ... || exit' or 'cd ... || return' in case cd fails.","fix":null},
+          {"file":"deploy.sh","line":9,"endLine":9,"column":3,"endColumn":4,"level":"style","code":2006,"message":"Use $(...) notation instead of legacy backticks `...`.","fix":null}
+        ]}"#;
+
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "deploy.sh:5:1: warning[SC2164]: Use 'cd ... || exit' or 'cd ... || return' in case cd fails."
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Info);
+        assert_eq!(diagnostics[2].severity, Severity::Info);
+
+        let legacy = r#"[{"file":"a.sh","line":1,"endLine":1,"column":1,"endColumn":2,"level":"error","code":1073,"message":"Couldn't parse this test expression."}]"#;
+        assert_eq!(parse(legacy)[0].severity, Severity::Error);
+        assert!(parse(r#"{"comments":[]}"#).is_empty());
+        assert!(parse("").is_empty());
+    }
+}
@@ -0,0 +1,510 @@
+// file: src/path_map.rs
+// version: 1.0.0
+// guid: 5b7e2d94-1c3a-4f86-a0d9-8e6f3b2c7a15
+
+//! Path translation between the host and WSL or dev container mounts
+//!
+//! VS Code expands `${workspaceFolder}` and friends as the host sees them. When the utility runs
+//! inside WSL, `C:\Users\me\proj` is `/mnt/c/Users/me/proj`; inside a dev container the
+//! workspace folder is mounted somewhere like `/workspaces/proj`. Arguments that are host paths
+//! are translated to local paths before the command line is parsed, and local paths in the
+//! output are translated back, so the same `tasks.json` works on either side and problem
+//! matchers still find the files.
+
+use crate::error::{AgentError, Result};
+use std::ffi::OsString;
+use std::process::{ExitStatus, Stdio};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+/// Environment variable with extra `HOST=LOCAL` mappings separated by `;`, or `off`
+pub const PATH_MAP_ENV: &str = "SAFE_AI_UTIL_PATH_MAP";
+
+/// Host workspace folder, usually set from `${localWorkspaceFolder}` in `devcontainer.json`
+pub const LOCAL_WORKSPACE_ENV: &str = "LOCAL_WORKSPACE_FOLDER";
+
+/// Where the workspace folder is mounted in the dev container
+pub const CONTAINER_WORKSPACE_ENV: &str = "CONTAINER_WORKSPACE_FOLDER";
+
+/// Translations that apply to this process
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathMap {
+    /// Host directories and where they are mounted here, longest host path first
+    mounts: Vec<Mount>,
+    /// Where WSL mounts the Windows drives, such as `/mnt/`, when running inside WSL
+    drives: Option<String>,
+}
+
+/// A host directory mounted at a local one
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mount {
+    /// With `/` separators and no trailing separator
+    host: String,
+    local: String,
+    /// Compared ignoring case, like Windows does
+    windows: bool,
+}
+
+impl PathMap {
+    /// Translations configured in the environment and detected for WSL
+    pub fn detect() -> Result<Self> {
+        Self::from_env(|name| std::env::var(name).ok(), wsl_mount_root())
+    }
+
+    /// Build the map from environment variables, with the WSL drive mount root if running in WSL
+    pub fn from_env(var: impl Fn(&str) -> Option<String>, drives: Option<String>) -> Result<Self> {
+        let extra = var(PATH_MAP_ENV).unwrap_or_default();
+        if extra.trim() == "off" {
+            return Ok(Self::default());
+        }
+
+        let mut map = Self {
+            mounts: Vec::new(),
+            drives,
+        };
+        for entry in extra
+            .split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (host, local) = entry.split_once('=').ok_or_else(|| {
+                AgentError::config(format!(
+                    "{} entry {:?} is not HOST=LOCAL",
+                    PATH_MAP_ENV, entry
+                ))
+            })?;
+            map.add(host, local)?;
+        }
+        if let (Some(host), Some(local)) = (var(LOCAL_WORKSPACE_ENV), var(CONTAINER_WORKSPACE_ENV))
+        {
+            map.add(&host, &local)?;
+        }
+        // Most specific first, so a nested mount wins over the one containing it
+        map.mounts.sort_by_key(|mount| std::cmp::Reverse(mount.host.len()));
+        Ok(map)
+    }
+
+    fn add(&mut self, host: &str, local: &str) -> Result<()> {
+        let windows = is_windows_path(host);
+        if !windows && !host.starts_with('/') {
+            return Err(AgentError::config(format!(
+                "Host path {:?} is not absolute",
+                host
+            )));
+        }
+        if !local.starts_with('/') {
+            return Err(AgentError::config(format!(
+                "Local path {:?} is not absolute",
+                local
+            )));
+        }
+        let host = if windows {
+            host.replace('\\', "/")
+        } else {
+            host.to_string()
+        };
+        self.mounts.push(Mount {
+            host: trim_separator(&host).to_string(),
+            local: trim_separator(local).to_string(),
+            windows,
+        });
+        Ok(())
+    }
+
+    /// Whether anything is translated at all
+    pub fn is_empty(&self) -> bool {
+        self.mounts.is_empty() && self.drives.is_none()
+    }
+
+    /// The command line with host paths translated, or `None` when no argument is a host path
+    ///
+    /// An argument is translated when it is a host path or an option of the form
+    /// `--name=PATH`; paths inside other text are left alone.
+    pub fn translate_args(&self, args: &[OsString]) -> Option<Vec<OsString>> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut changed = false;
+        let translated = args
+            .iter()
+            .map(
+                |arg| match arg.to_str().and_then(|arg| self.translate_arg(arg)) {
+                    Some(translated) => {
+                        changed = true;
+                        translated.into()
+                    }
+                    None => arg.clone(),
+                },
+            )
+            .collect();
+        changed.then_some(translated)
+    }
+
+    fn translate_arg(&self, arg: &str) -> Option<String> {
+        if let Some((name, value)) = arg.split_once('=').filter(|_| arg.starts_with('-')) {
+            return self
+                .to_local(value)
+                .map(|value| format!("{}={}", name, value));
+        }
+        self.to_local(arg)
+    }
+
+    /// The local path for a host path, if it lies in a mounted directory or on a Windows drive
+    pub fn to_local(&self, path: &str) -> Option<String> {
+        let windows = is_windows_path(path);
+        let normalized = if windows {
+            path.replace('\\', "/")
+        } else {
+            path.to_string()
+        };
+
+        for mount in self.mounts.iter().filter(|mount| mount.windows == windows) {
+            if let Some(rest) = strip_dir(&normalized, &mount.host, mount.windows) {
+                return Some(format!("{}{}", mount.local, rest));
+            }
+        }
+
+        let root = self.drives.as_deref()?;
+        let bytes = normalized.as_bytes();
+        if windows && bytes.len() >= 3 && bytes[1] == b':' {
+            let drive = (bytes[0] as char).to_ascii_lowercase();
+            return Some(format!("{}{}{}", root, drive, &normalized[2..]));
+        }
+        // Files of the distribution itself, as Windows sees them
+        for prefix in ["//wsl$/", "//wsl.localhost/"] {
+            let Some(rest) = strip_prefix_ignore_case(&normalized, prefix) else {
+                continue;
+            };
+            let rest = rest.find('/').map_or("/", |start| &rest[start..]);
+            return Some(rest.to_string());
+        }
+        None
+    }
+
+    /// Text with local paths translated back to host paths
+    ///
+    /// Paths are recognized where a token starts, e.g. after whitespace, a quote or `=`. Windows
+    /// paths are written with `/` separators, which Windows accepts and which need no escaping
+    /// in JSON output.
+    pub fn to_host(&self, text: &str) -> String {
+        if self.is_empty() {
+            return text.to_string();
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        let mut at_boundary = true;
+        while let Some(c) = rest.chars().next() {
+            if at_boundary && c == '/' {
+                if let Some((host, len)) = self.host_prefix(rest) {
+                    result.push_str(&host);
+                    rest = &rest[len..];
+                    at_boundary = false;
+                    continue;
+                }
+            }
+            result.push(c);
+            rest = &rest[c.len_utf8()..];
+            at_boundary = c.is_whitespace() || "\"'`=([<,:".contains(c);
+        }
+        result
+    }
+
+    /// The host form of the local directory `text` starts with, and the length of that directory
+    fn host_prefix(&self, text: &str) -> Option<(String, usize)> {
+        let mut mounts: Vec<&Mount> = self.mounts.iter().collect();
+        mounts.sort_by_key(|mount| std::cmp::Reverse(mount.local.len()));
+        for mount in mounts {
+            if text.starts_with(&mount.local) && ends_component(&text[mount.local.len()..]) {
+                return Some((mount.host.clone(), mount.local.len()));
+            }
+        }
+
+        let root = self.drives.as_deref()?;
+        let drive = text.strip_prefix(root)?.chars().next()?;
+        let len = root.len() + 1;
+        if drive.is_ascii_alphabetic() && ends_component(&text[len..]) {
+            return Some((format!("{}:", drive.to_ascii_uppercase()), len));
+        }
+        None
+    }
+
+    /// Run this invocation again with the translated arguments, passing its output through
+    /// [`PathMap::to_host`], and return its exit code
+    ///
+    /// Ctrl-C reaches the command directly, so this process only keeps relaying until it exits.
+    pub async fn relay(&self, args: &[OsString]) -> Result<i32> {
+        let exe = std::env::current_exe()
+            .map_err(|e| AgentError::system(format!("Failed to locate the executable: {}", e)))?;
+        let mut child = Command::new(exe)
+            .args(args.iter().skip(1))
+            .env(PATH_MAP_ENV, "off")
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                AgentError::execution(format!("Failed to run the translated command: {}", e))
+            })?;
+        tokio::spawn(async { while tokio::signal::ctrl_c().await.is_ok() {} });
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let (stdout, stderr, status) = tokio::join!(
+            self.copy_lines(stdout, tokio::io::stdout()),
+            self.copy_lines(stderr, tokio::io::stderr()),
+            child.wait(),
+        );
+        stdout?;
+        stderr?;
+        let status = status
+            .map_err(|e| AgentError::execution(format!("Failed to wait for command: {}", e)))?;
+        Ok(exit_code(status))
+    }
+
+    /// Copy output line by line; lines that are not UTF-8 are copied unchanged
+    async fn copy_lines(
+        &self,
+        from: impl AsyncRead + Unpin,
+        mut to: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let mut reader = BufReader::new(from);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            match std::str::from_utf8(&line) {
+                Ok(text) => to.write_all(self.to_host(text).as_bytes()).await?,
+                Err(_) => to.write_all(&line).await?,
+            }
+            to.flush().await?;
+        }
+    }
+}
+
+/// Where WSL mounts the Windows drives, when running inside WSL
+fn wsl_mount_root() -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let in_wsl = std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || std::path::Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists();
+    if !in_wsl {
+        return None;
+    }
+    let config = std::fs::read_to_string("/etc/wsl.conf").unwrap_or_default();
+    Some(automount_root(&config))
+}
+
+/// The `[automount] root` setting of `wsl.conf`, `/mnt/` by default, ending with `/`
+fn automount_root(config: &str) -> String {
+    let mut section = "";
+    for line in config.lines().map(str::trim) {
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            section = name.trim();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if section.eq_ignore_ascii_case("automount") && key.trim() == "root" {
+            let value = value.trim().trim_matches('"');
+            if value.starts_with('/') {
+                return format!("{}/", value.trim_end_matches('/'));
+            }
+        }
+    }
+    "/mnt/".to_string()
+}
+
+/// Whether a path is a Windows drive path (`C:\...`) or a UNC path (`\\server\...`)
+fn is_windows_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/');
+    drive || path.starts_with("\\\\")
+}
+
+fn trim_separator(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    }
+}
+
+/// What follows `dir` in `path` when `path` is `dir` or lies in it
+fn strip_dir<'a>(path: &'a str, dir: &str, ignore_case: bool) -> Option<&'a str> {
+    let rest = if ignore_case {
+        strip_prefix_ignore_case(path, dir)?
+    } else {
+        path.strip_prefix(dir)?
+    };
+    (rest.is_empty() || rest.starts_with('/') || dir.ends_with('/')).then_some(rest)
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &text[prefix.len()..])
+}
+
+/// Whether a path prefix is followed by the end of its last component, so `/mnt/c` does not
+/// match `/mnt/cdrom`
+fn ends_component(rest: &str) -> bool {
+    !rest
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || "-_.~+@".contains(c))
+}
+
+#[cfg(unix)]
+fn exit_code(status: ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
+
+#[cfg(not(unix))]
+fn exit_code(status: ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(vars: &[(&str, &str)], drives: Option<&str>) -> PathMap {
+        let vars: Vec<(String, String)> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let var = |name: &str| vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+        PathMap::from_env(var, drives.map(String::from)).unwrap()
+    }
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_wsl_drive_paths() {
+        let wsl = map(&[], Some("/mnt/"));
+        assert_eq!(
+            wsl.to_local(r"C:\Users\me\proj").as_deref(),
+            Some("/mnt/c/Users/me/proj")
+        );
+        assert_eq!(
+            wsl.to_local(r"d:\work/src\main.rs").as_deref(),
+            Some("/mnt/d/work/src/main.rs")
+        );
+        assert_eq!(
+            wsl.to_local(r"\\wsl$\Ubuntu\home\me").as_deref(),
+            Some("/home/me")
+        );
+        assert_eq!(
+            wsl.to_local(r"\\wsl.localhost\Ubuntu").as_deref(),
+            Some("/")
+        );
+        assert_eq!(wsl.to_local("/home/me"), None);
+        assert_eq!(wsl.to_local("C:"), None);
+
+        let translated = wsl
+            .translate_args(&args(&[
+                "safe-ai-util",
+                "--config=C:\\proj\\cfg.toml",
+                "file",
+                "C:\\proj\\a.txt",
+                "s/a/b/",
+            ]))
+            .unwrap();
+        assert_eq!(
+            translated,
+            args(&[
+                "safe-ai-util",
+                "--config=/mnt/c/proj/cfg.toml",
+                "file",
+                "/mnt/c/proj/a.txt",
+                "s/a/b/"
+            ])
+        );
+        assert_eq!(
+            wsl.translate_args(&args(&["safe-ai-util", "git", "status"])),
+            None
+        );
+
+        assert_eq!(
+            wsl.to_host("/mnt/c/proj/src/main.rs:3:1: error\n"),
+            "C:/proj/src/main.rs:3:1: error\n"
+        );
+        assert_eq!(
+            wsl.to_host(r#"{"file":"/mnt/c/proj/a.rs"} /mnt/cdrom x/mnt/c"#),
+            r#"{"file":"C:/proj/a.rs"} /mnt/cdrom x/mnt/c"#
+        );
+    }
+
+    #[test]
+    fn test_workspace_mounts() {
+        let container = map(
+            &[
+                (LOCAL_WORKSPACE_ENV, r"C:\Users\me\proj\"),
+                (CONTAINER_WORKSPACE_ENV, "/workspaces/proj"),
+                (
+                    PATH_MAP_ENV,
+                    "/Users/me/cache=/cache; C:\\Users\\me\\proj\\vendor=/vendor",
+                ),
+            ],
+            None,
+        );
+        assert_eq!(
+            container.to_local(r"c:\users\me\PROJ\src").as_deref(),
+            Some("/workspaces/proj/src")
+        );
+        assert_eq!(
+            container.to_local(r"C:\Users\me\proj\vendor\x").as_deref(),
+            Some("/vendor/x")
+        );
+        assert_eq!(container.to_local(r"C:\Users\me\project"), None);
+        assert_eq!(
+            container.to_local("/Users/me/cache/a").as_deref(),
+            Some("/cache/a")
+        );
+        assert_eq!(container.to_local(r"D:\other"), None);
+
+        assert_eq!(
+            container.to_host(
+                "see /workspaces/proj/src/lib.rs and '/cache' but not /workspaces/project"
+            ),
+            "see C:/Users/me/proj/src/lib.rs and '/Users/me/cache' but not /workspaces/project"
+        );
+    }
+
+    #[test]
+    fn test_configuration() {
+        assert!(map(&[(PATH_MAP_ENV, "off")], Some("/mnt/")).is_empty());
+        let bad = |value: &str| {
+            PathMap::from_env(
+                |name| (name == PATH_MAP_ENV).then(|| value.to_string()),
+                None,
+            )
+            .is_err()
+        };
+        assert!(bad("C:\\proj"));
+        assert!(bad("proj=/workspaces/proj"));
+        assert!(bad("C:\\proj=workspaces"));
+
+        assert_eq!(automount_root(""), "/mnt/");
+        assert_eq!(
+            automount_root("[automount]\nenabled = true\nroot = /win\n"),
+            "/win/"
+        );
+        assert_eq!(automount_root("[network]\nroot = /x/\n"), "/mnt/");
+    }
+}
@@ -0,0 +1,69 @@
+// file: src/pipeline.rs
+// version: 1.1.0
+// guid: 4a9d7e2b-6c1f-4b3a-8e5d-9f0c2a7b1e48
+
+//! Pipelines without a shell
+//!
+//! [`crate::executor::Executor::execute_pipeline`] connects the stdout of each command to the
+//! stdin of the next with operating system pipes, the way `git diff | grep TODO | wc -l`
+//! would in a shell, but nothing is ever interpreted by a shell: every stage is an argument
+//! vector validated like a single command. The stages run concurrently; the first reads no
+//! input and the output of the last is captured.
+
+use crate::executor::Truncation;
+use serde::Serialize;
+
+/// Captured result of a pipeline
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PipelineOutput {
+    /// Exit code of each stage, in stage order (`None` if terminated by a signal)
+    pub exit_codes: Vec<Option<i32>>,
+    /// Standard output of the last stage
+    pub stdout: String,
+    /// Standard error of every stage, in stage order
+    pub stderr: String,
+    /// Bytes dropped from the output of the last stage and the errors of all stages
+    pub truncated: Truncation,
+}
+
+impl PipelineOutput {
+    /// Whether every stage exited successfully
+    pub fn success(&self) -> bool {
+        self.exit_codes.iter().all(|code| *code == Some(0))
+    }
+
+    /// Index of the last stage that did not exit successfully
+    pub fn failed_stage(&self) -> Option<usize> {
+        self.exit_codes.iter().rposition(|code| *code != Some(0))
+    }
+
+    /// Exit status of the pipeline, as a shell with `set -o pipefail` reports it: that of the
+    /// last failing stage, or of the last stage when all succeeded
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.failed_stage() {
+            Some(stage) => self.exit_codes[stage],
+            None => self.exit_codes.last().copied().flatten(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipefail_exit_code() {
+        let output = |exit_codes| PipelineOutput {
+            exit_codes,
+            ..Default::default()
+        };
+        assert!(output(vec![Some(0), Some(0)]).success());
+        assert_eq!(output(vec![Some(0), Some(0)]).exit_code(), Some(0));
+
+        let failed = output(vec![Some(2), Some(1), Some(0)]);
+        assert!(!failed.success());
+        assert_eq!(failed.failed_stage(), Some(1));
+        assert_eq!(failed.exit_code(), Some(1));
+        assert_eq!(output(vec![None, Some(0)]).exit_code(), None);
+    }
+}
@@ -0,0 +1,239 @@
+// file: src/plan.rs
+// version: 1.3.1
+// guid: ee5acac1-425c-4e0f-9afb-724d399238da
+
+//! Dry-run execution plans
+//!
+//! In dry-run mode the executor prepares every process exactly as it would spawn it —
+//! resolved binary, sanitized arguments, working directory and environment changes — and
+//! records it as a [`PlanStep`] instead of running it. Multi-step commands such as
+//! `python pip install --upgrade-pip -r requirements.txt` therefore produce an ordered plan
+//! of every process, which is printed when the command finishes.
+//!
+//! In simulation mode each step also carries the result fabricated for it by
+//! [`crate::simulate`].
+
+use crate::batch::InputSource;
+use crate::security::audit;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::process::Command;
+
+/// Environment changes relative to the utility's own environment
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EnvDelta {
+    pub set: BTreeMap<String, String>,
+    pub unset: Vec<String>,
+}
+
+impl EnvDelta {
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.unset.is_empty()
+    }
+}
+
+/// A process that would be spawned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanStep {
+    /// Command as requested, e.g. `python3`
+    pub command: String,
+    /// Binary that would run, or the command name if it could not be resolved
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub env: EnvDelta,
+    /// Standard input, when the process would not read from nothing or the terminal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdin: Option<InputSource>,
+    /// Why the step would fail, e.g. a missing binary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Result returned in simulation mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulated: Option<SimulatedResult>,
+}
+
+/// Fabricated result of a simulated step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SimulatedResult {
+    pub exit_code: i32,
+    pub stdout: String,
+}
+
+impl PlanStep {
+    /// Describe a prepared process without spawning it
+    pub fn from_command(command: &str, prepared: &Command, note: Option<String>) -> Self {
+        let prepared = prepared.as_std();
+        let mut env = EnvDelta::default();
+        for (name, value) in prepared.get_envs() {
+            let name = name.to_string_lossy().into_owned();
+            match value {
+                Some(value) => {
+                    env.set.insert(name, value.to_string_lossy().into_owned());
+                }
+                None => env.unset.push(name),
+            }
+        }
+        env.unset.sort();
+        // Plans are printed and saved, so credentials are shown as in history
+        env.set = audit::redact_env(&env.set);
+
+        Self {
+            command: command.to_string(),
+            program: prepared.get_program().to_string_lossy().into_owned(),
+            args: prepared
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+            cwd: prepared
+                .get_current_dir()
+                .map(PathBuf::from)
+                .or_else(|| std::env::current_dir().ok())
+                .unwrap_or_default(),
+            env,
+            stdin: None,
+            note,
+            simulated: None,
+        }
+    }
+
+    /// The step as a shell-style command line
+    pub fn command_line(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(&self.args)
+            .map(|part| shell_quote(part))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Steps recorded during a dry run, in the order they would run
+#[derive(Debug, Default)]
+pub struct Plan {
+    steps: Mutex<Vec<PlanStep>>,
+    /// Whether steps are simulated rather than only planned
+    simulated: bool,
+}
+
+impl Plan {
+    pub fn new(simulated: bool) -> Self {
+        Self {
+            steps: Mutex::default(),
+            simulated,
+        }
+    }
+
+    pub fn record(&self, step: PlanStep) {
+        self.steps.lock().unwrap().push(step);
+    }
+
+    pub fn steps(&self) -> Vec<PlanStep> {
+        self.steps.lock().unwrap().clone()
+    }
+
+    /// Human-readable plan
+    pub fn render_text(&self) -> String {
+        let steps = self.steps();
+        let label = if self.simulated { "SIMULATION" } else { "DRY RUN" };
+        if steps.is_empty() {
+            return format!("{}: no processes would be spawned\n", label);
+        }
+
+        let mut text = format!(
+            "{}: execution plan ({} step{}){}\n",
+            label,
+            steps.len(),
+            if steps.len() == 1 { "" } else { "s" },
+            if self.simulated { ", results fabricated" } else { "" }
+        );
+        for (index, step) in steps.iter().enumerate() {
+            let _ = writeln!(text, "  {}. {}", index + 1, step.command_line());
+            let _ = writeln!(text, "     cwd: {}", step.cwd.display());
+            for (name, value) in &step.env.set {
+                let _ = writeln!(text, "     env: {}={}", name, value);
+            }
+            for name in &step.env.unset {
+                let _ = writeln!(text, "     env: unset {}", name);
+            }
+            match &step.stdin {
+                Some(InputSource::File { file: path }) => {
+                    let _ = writeln!(text, "     stdin: {}", path.display());
+                }
+                Some(InputSource::Text { text: input }) => {
+                    let _ = writeln!(text, "     stdin: {} byte(s) of text", input.len());
+                }
+                None => {}
+            }
+            if let Some(note) = &step.note {
+                let _ = writeln!(text, "     note: {}", note);
+            }
+            if let Some(result) = &step.simulated {
+                let _ = writeln!(
+                    text,
+                    "     simulated: exit {}, {} line(s) of output",
+                    result.exit_code,
+                    result.stdout.lines().count()
+                );
+            }
+        }
+        text
+    }
+
+    /// Machine-readable plan
+    pub fn to_json(&self) -> Value {
+        json!({ "dry_run": true, "simulated": self.simulated, "steps": self.steps() })
+    }
+}
+
+/// Quote an argument for display when it contains characters a shell would interpret
+pub(crate) fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_from_command() {
+        let mut command = Command::new("/usr/bin/python3");
+        command
+            .args(["-m", "pip", "install", "requests>=2"])
+            .current_dir("/tmp")
+            .env("PATH", "/usr/bin")
+            .env("PGPASSWORD", "hunter2")
+            .env_remove("PYTHONPATH");
+
+        let step = PlanStep::from_command("python3", &command, None);
+        assert_eq!(step.program, "/usr/bin/python3");
+        assert_eq!(step.cwd, PathBuf::from("/tmp"));
+        assert_eq!(step.env.set["PATH"], "/usr/bin");
+        assert_ne!(step.env.set["PGPASSWORD"], "hunter2");
+        assert_eq!(step.env.unset, ["PYTHONPATH"]);
+        assert_eq!(
+            step.command_line(),
+            "/usr/bin/python3 -m pip install 'requests>=2'"
+        );
+
+        let plan = Plan::default();
+        plan.record(step.clone());
+        plan.record(step);
+        let text = plan.render_text();
+        assert!(text.starts_with("DRY RUN: execution plan (2 steps)\n  1. /usr/bin/python3"));
+        assert!(text.contains("     env: unset PYTHONPATH\n"));
+        assert!(!text.contains("hunter2"));
+        assert_eq!(plan.to_json()["steps"][1]["env"]["set"]["PATH"], "/usr/bin");
+    }
+}
@@ -0,0 +1,740 @@
+// file: src/process.rs
+// version: 1.6.0
+// guid: 7f384d92-b203-4e62-a35b-6ecebc683bac
+
+//! Child process trees
+//!
+//! Commands often start processes of their own — `pytest` workers, `buf` plugins, `npm`
+//! scripts. On Unix every child the Executor spawns leads a new process group, so the whole
+//! tree can be signalled at once:
+//!
+//! - Ctrl-C and SIGTERM received by this process are forwarded to the group, so tools get the
+//!   chance to shut down cleanly; a second signal stops the tree. A child that runs in the
+//!   foreground of a terminal is handed the terminal instead, so it receives Ctrl-C (and can
+//!   prompt for input) directly.
+//! - On timeout or cancellation the group gets SIGTERM and, after [`KILL_GRACE`], SIGKILL.
+//!
+//! Cancellation is driven by a [`CancellationToken`]; library users get one from
+//! [`crate::executor::Executor::cancellation_token`].
+//!
+//! Output read with [`output`] can be bounded: a [`Capture`] keeps the start and the end of a
+//! stream and drops what lies between, so a command that prints gigabytes cannot exhaust
+//! memory. [`output_to`] also forwards what it reads to an [`OutputSink`] as it arrives.
+//!
+//! [`offline_command`] builds a command that runs without network access, for
+//! `safety.no_network` and `shell --no-network`.
+
+use crate::error::{AgentError, Result};
+use std::collections::VecDeque;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Time a process tree gets to exit after SIGTERM before it is killed
+pub const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// Number of children currently being waited on
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+
+/// Gives the terminal back to this process when dropped, if a child was handed it
+#[derive(Debug)]
+pub struct Terminal {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    reclaim: bool,
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if self.reclaim {
+            // SAFETY: plain libc calls on the process's own stdin
+            unsafe { hand_terminal(libc::getpgrp()) };
+        }
+    }
+}
+
+/// Make `cmd` lead a new process group
+///
+/// With `interactive`, a child that inherits stdin from a process in the foreground of a
+/// terminal is also given the terminal while it runs; keep the returned guard alive until the
+/// child has exited.
+pub fn isolate(cmd: &mut Command, interactive: bool) -> Terminal {
+    #[cfg(unix)]
+    {
+        cmd.process_group(0);
+        if interactive && owns_terminal() {
+            // SAFETY: the closure only makes async-signal-safe libc calls
+            unsafe {
+                cmd.pre_exec(|| {
+                    hand_terminal(libc::getpid());
+                    Ok(())
+                });
+            }
+            return Terminal { reclaim: true };
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (cmd, interactive);
+    Terminal { reclaim: false }
+}
+
+/// Wait for a child, forwarding signals to its process group and stopping the tree on
+/// timeout or cancellation
+pub async fn wait(
+    child: &mut Child,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<ExitStatus> {
+    let _waiting = Waiting::enter();
+    let mut signals = Signals::new()?;
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+    let mut interrupted = false;
+
+    loop {
+        tokio::select! {
+            status = child.wait() => {
+                let status = status.map_err(|e| {
+                    AgentError::execution(format!("Failed to wait for command: {}", e))
+                })?;
+                if interrupted {
+                    return Err(AgentError::cancelled(format!("Command was interrupted ({})", status)));
+                }
+                return Ok(status);
+            }
+            _ = &mut deadline => {
+                terminate(child).await;
+                return Err(AgentError::timeout("Command execution timed out"));
+            }
+            _ = cancel.cancelled() => {
+                terminate(child).await;
+                return Err(AgentError::cancelled("Command execution was cancelled"));
+            }
+            signal = signals.recv() => {
+                if interrupted {
+                    terminate(child).await;
+                    return Err(AgentError::cancelled("Command was interrupted"));
+                }
+                interrupted = true;
+                warn!("Forwarding signal {} to the command; send it again to stop it", signal);
+                forward(child, signal);
+            }
+        }
+    }
+}
+
+/// Which of a child's output streams a chunk was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Receives a child's output while it runs, whatever [`Capture`] keeps of it
+pub type OutputSink = tokio::sync::mpsc::UnboundedSender<(OutputStream, Vec<u8>)>;
+
+/// Exit status and output of a child read by [`output`]
+#[derive(Debug)]
+pub struct CapturedOutput {
+    pub status: ExitStatus,
+    pub stdout: Capture,
+    pub stderr: Capture,
+}
+
+/// Like [`Child::wait_with_output`], with [`wait`]'s signal handling and cancellation; the
+/// child's stdout and stderr must be piped
+///
+/// Each stream keeps at most `limit` bytes (see [`Capture`]); 0 keeps everything.
+pub async fn output(
+    child: Child,
+    timeout: Duration,
+    cancel: &CancellationToken,
+    limit: usize,
+) -> Result<CapturedOutput> {
+    output_to(child, timeout, cancel, limit, None).await
+}
+
+/// [`output`], also sending each chunk read to `sink`
+pub async fn output_to(
+    mut child: Child,
+    timeout: Duration,
+    cancel: &CancellationToken,
+    limit: usize,
+    sink: Option<&OutputSink>,
+) -> Result<CapturedOutput> {
+    let forward = |stream| sink.map(|sink| (sink.clone(), stream));
+    let stdout = child
+        .stdout
+        .take()
+        .map(|stream| tokio::spawn(read_all(stream, limit, forward(OutputStream::Stdout))));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stream| tokio::spawn(read_all(stream, limit, forward(OutputStream::Stderr))));
+
+    match wait(&mut child, timeout, cancel).await {
+        Ok(status) => Ok(CapturedOutput {
+            status,
+            stdout: collect(stdout, limit).await,
+            stderr: collect(stderr, limit).await,
+        }),
+        Err(e) => {
+            // Processes that left the group may still hold the pipes open
+            for task in stdout.iter().chain(stderr.iter()) {
+                task.abort();
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Bytes read from a stream, at most `limit` of them kept
+///
+/// Past the limit the first and the last half of the stream are kept and the bytes between
+/// them are dropped, so both the start of a command's output and its final errors survive.
+/// A limit of 0 keeps everything.
+#[derive(Debug, Default)]
+pub struct Capture {
+    limit: usize,
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    omitted: u64,
+}
+
+impl Capture {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    /// Append bytes read from the stream
+    pub fn push(&mut self, mut data: &[u8]) {
+        if self.limit == 0 {
+            self.head.extend_from_slice(data);
+            return;
+        }
+        let head_limit = self.limit / 2;
+        if self.head.len() < head_limit {
+            let take = (head_limit - self.head.len()).min(data.len());
+            self.head.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+
+        let tail_limit = self.limit - head_limit;
+        if data.len() >= tail_limit {
+            self.omitted += (self.tail.len() + data.len() - tail_limit) as u64;
+            self.tail.clear();
+            self.tail.extend(&data[data.len() - tail_limit..]);
+        } else {
+            self.tail.extend(data);
+            let excess = self.tail.len().saturating_sub(tail_limit);
+            self.tail.drain(..excess);
+            self.omitted += excess as u64;
+        }
+    }
+
+    /// Number of bytes dropped from the middle of the stream
+    pub fn omitted(&self) -> u64 {
+        self.omitted
+    }
+
+    /// The bytes kept, with a line where bytes were dropped saying how many
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = self.head;
+        bytes.extend_from_slice(omission_marker(self.omitted).as_bytes());
+        bytes.extend(self.tail);
+        bytes
+    }
+
+    /// Like [`Capture::into_bytes`], keeping the capture
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.head.clone();
+        bytes.extend_from_slice(omission_marker(self.omitted).as_bytes());
+        bytes.extend(&self.tail);
+        bytes
+    }
+}
+
+/// Line marking where `omitted` bytes were dropped, or nothing
+fn omission_marker(omitted: u64) -> String {
+    if omitted == 0 {
+        return String::new();
+    }
+    format!(
+        "\n[... {} bytes omitted; raise output.max_capture_bytes to keep more ...]\n",
+        omitted
+    )
+}
+
+/// Stop a child and everything it started: SIGTERM to the group, then SIGKILL after
+/// [`KILL_GRACE`]
+pub async fn terminate(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        signal_group(pid, libc::SIGTERM);
+        if tokio::time::timeout(KILL_GRACE, child.wait())
+            .await
+            .is_err()
+        {
+            debug!("Process group {} ignored SIGTERM", pid);
+        }
+        // Descendants outlive the group leader, so the group is killed either way
+        signal_group(pid, libc::SIGKILL);
+    }
+    #[cfg(windows)]
+    if let Some(pid) = child.id() {
+        let _ = std::process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+    let _ = child.start_kill();
+    let _ = child.wait().await;
+}
+
+/// Start `cmd` in a session of its own, so it outlives this process and is unaffected by
+/// its terminal and signals
+pub fn detach(cmd: &mut Command) {
+    #[cfg(unix)]
+    // SAFETY: the closure only makes an async-signal-safe libc call
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// `sandbox-exec` profile denying network access beyond the machine itself
+#[cfg(target_os = "macos")]
+const SANDBOX_PROFILE: &str = "(version 1)(allow default)(deny network*)\
+     (allow network* (local ip \"localhost:*\"))(allow network* (remote ip \"localhost:*\"))\
+     (allow network* (remote unix-socket))";
+
+#[cfg(target_os = "macos")]
+const SANDBOX_EXEC: &str = "/usr/bin/sandbox-exec";
+
+/// Check that commands can be run without network access on this machine
+pub fn check_network_isolation() -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: plain libc query
+        if unsafe { libc::geteuid() } == 0 {
+            return Ok(());
+        }
+        // Sysctls that turn unprivileged user namespaces off, and the value that does so
+        let switches = [
+            ("/proc/sys/kernel/unprivileged_userns_clone", "0"),
+            ("/proc/sys/user/max_user_namespaces", "0"),
+            (
+                "/proc/sys/kernel/apparmor_restrict_unprivileged_userns",
+                "1",
+            ),
+        ];
+        for (path, off) in switches {
+            if std::fs::read_to_string(path).is_ok_and(|value| value.trim() == off) {
+                return Err(AgentError::validation(format!(
+                    "Network isolation needs unprivileged user namespaces, which {} = {} disables",
+                    path, off
+                )));
+            }
+        }
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        if !std::path::Path::new(SANDBOX_EXEC).exists() {
+            return Err(AgentError::validation(format!(
+                "Network isolation needs {}, which is not installed",
+                SANDBOX_EXEC
+            )));
+        }
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    Err(AgentError::validation(
+        "Network isolation is only supported on Linux and macOS",
+    ))
+}
+
+/// A command running `program` with `args` without network access
+///
+/// On Linux the command is started with [`deny_network`]; on macOS it runs under
+/// `sandbox-exec` with a profile denying all network access except to localhost and Unix
+/// sockets.
+pub fn offline_command(program: &std::path::Path, args: &[String]) -> Result<Command> {
+    #[cfg(target_os = "macos")]
+    {
+        let mut cmd = Command::new(SANDBOX_EXEC);
+        cmd.arg("-p").arg(SANDBOX_PROFILE).arg(program).args(args);
+        Ok(cmd)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        deny_network(&mut cmd)?;
+        Ok(cmd)
+    }
+}
+
+/// Start `cmd` in network and user namespaces of its own, where the only interface is a
+/// loopback, so it cannot reach the network but can still listen on localhost (Linux only)
+///
+/// The user namespace maps the current user to itself, so files keep their owners; it needs
+/// unprivileged user namespaces unless this process runs as root.
+pub fn deny_network(cmd: &mut Command) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::ffi::CString;
+
+        // SAFETY: plain libc queries
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        // Allocating is not allowed between fork and exec, so everything is prepared here
+        let paths = ["/proc/self/setgroups", "/proc/self/uid_map", "/proc/self/gid_map"]
+            .map(|path| CString::new(path).expect("no NUL in path"));
+        let contents = [
+            "deny".to_string(),
+            format!("{} {} 1", uid, uid),
+            format!("{} {} 1", gid, gid),
+        ];
+        // SAFETY: the closure only makes async-signal-safe libc calls on prepared buffers
+        unsafe {
+            cmd.pre_exec(move || {
+                if uid == 0 {
+                    if libc::unshare(libc::CLONE_NEWNET) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    return loopback_up();
+                }
+                if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                for (path, content) in paths.iter().zip(&contents) {
+                    let fd = libc::open(path.as_ptr(), libc::O_WRONLY);
+                    if fd < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    let written = libc::write(fd, content.as_ptr().cast(), content.len());
+                    libc::close(fd);
+                    if written < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                loopback_up()
+            });
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = cmd;
+        Err(AgentError::validation(
+            "Network isolation is only supported on Linux",
+        ))
+    }
+}
+
+/// Bring up the loopback interface of a new network namespace, between fork and exec
+#[cfg(target_os = "linux")]
+unsafe fn loopback_up() -> std::io::Result<()> {
+    let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut request: libc::ifreq = std::mem::zeroed();
+    for (to, from) in request.ifr_name.iter_mut().zip(b"lo") {
+        *to = *from as libc::c_char;
+    }
+    request.ifr_ifru.ifru_flags = (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+    let result = libc::ioctl(fd, libc::SIOCSIFFLAGS as _, &request);
+    let error = std::io::Error::last_os_error();
+    libc::close(fd);
+    if result < 0 {
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Whether a process exists; always true where this cannot be checked
+pub fn is_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: signal 0 only checks that the process exists and may be signalled
+        let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+        result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+/// Stop a process group that is not a child of this process: SIGTERM, or SIGKILL with
+/// `force`
+pub fn kill_group(pgid: u32, force: bool) {
+    #[cfg(unix)]
+    signal_group(pgid, if force { libc::SIGKILL } else { libc::SIGTERM });
+    #[cfg(windows)]
+    {
+        let _ = force;
+        let _ = std::process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pgid.to_string()])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+}
+
+/// Exit on Ctrl-C or SIGTERM while no child is running
+///
+/// [`wait`] installs signal handlers, and they stay installed for the life of the process;
+/// without this a signal arriving between two commands would be ignored.
+pub fn exit_on_interrupt() {
+    tokio::spawn(async {
+        let mut signals = match Signals::new() {
+            Ok(signals) => signals,
+            Err(e) => {
+                warn!("Failed to install signal handlers: {}", e);
+                return;
+            }
+        };
+        loop {
+            let signal = signals.recv().await;
+            if WAITING.load(Ordering::SeqCst) == 0 {
+                std::process::exit(128 + signal);
+            }
+        }
+    });
+}
+
+/// Marks a child as being waited on, so [`exit_on_interrupt`] leaves signals to [`wait`]
+struct Waiting;
+
+impl Waiting {
+    fn enter() -> Self {
+        WAITING.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        WAITING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+struct Signals {
+    interrupt: tokio::signal::unix::Signal,
+    terminate: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+        })
+    }
+
+    /// Wait for the next signal and return its number
+    async fn recv(&mut self) -> i32 {
+        tokio::select! {
+            Some(()) = self.interrupt.recv() => libc::SIGINT,
+            Some(()) = self.terminate.recv() => libc::SIGTERM,
+            else => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    /// Wait for Ctrl-C and return the number SIGINT has on Unix
+    async fn recv(&mut self) -> i32 {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => 2,
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
+
+/// Pass a signal on to the child's process group
+fn forward(child: &Child, signal: i32) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        signal_group(pid, signal);
+    }
+    // Windows delivers Ctrl-C to every process attached to the console
+    #[cfg(not(unix))]
+    let _ = (child, signal);
+}
+
+#[cfg(unix)]
+fn signal_group(pgid: u32, signal: i32) {
+    // SAFETY: killpg has no memory safety requirements; ESRCH for a vanished group is fine
+    unsafe {
+        libc::killpg(pgid as libc::pid_t, signal);
+    }
+}
+
+/// Whether stdin is a terminal and this process is in its foreground group
+#[cfg(unix)]
+fn owns_terminal() -> bool {
+    // SAFETY: plain libc queries on the process's own stdin
+    unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1
+            && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+    }
+}
+
+/// Make `pgid` the foreground group of the terminal on stdin
+///
+/// A background process that does this gets SIGTTOU, so the signal is ignored meanwhile.
+#[cfg(unix)]
+unsafe fn hand_terminal(pgid: libc::pid_t) {
+    let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+    libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+    libc::signal(libc::SIGTTOU, previous);
+}
+
+async fn read_all(
+    mut stream: impl AsyncRead + Unpin,
+    limit: usize,
+    sink: Option<(OutputSink, OutputStream)>,
+) -> Capture {
+    let mut capture = Capture::new(limit);
+    let mut buffer = [0; 8192];
+    while let Ok(read @ 1..) = stream.read(&mut buffer).await {
+        capture.push(&buffer[..read]);
+        if let Some((sink, stream)) = &sink {
+            let _ = sink.send((*stream, buffer[..read].to_vec()));
+        }
+    }
+    capture
+}
+
+async fn collect(task: Option<JoinHandle<Capture>>, limit: usize) -> Capture {
+    match task {
+        Some(task) => task.await.unwrap_or_else(|_| Capture::new(limit)),
+        None => Capture::new(limit),
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+    use std::time::Instant;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    /// Whether a process still exists and is not a zombie
+    fn alive(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .map(|stat| !stat.contains(") Z "))
+            .unwrap_or(false)
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_process_tree() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 30 & echo $!; wait"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped());
+        let _terminal = isolate(&mut cmd, false);
+        let mut child = cmd.spawn().unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let grandchild = lines.next_line().await.unwrap().unwrap();
+        assert!(alive(&grandchild));
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+        let started = Instant::now();
+        let error = wait(&mut child, Duration::from_secs(30), &cancel)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AgentError::Cancelled(_)));
+        assert!(started.elapsed() < KILL_GRACE);
+
+        let stopped = Instant::now();
+        while alive(&grandchild) && stopped.elapsed() < Duration::from_secs(2) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!alive(&grandchild));
+    }
+
+    #[tokio::test]
+    async fn test_output_and_timeout() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let _terminal = isolate(&mut cmd, false);
+        let cancel = CancellationToken::new();
+        let output = output(cmd.spawn().unwrap(), Duration::from_secs(10), &cancel, 0)
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.into_bytes(), b"out\n");
+        assert_eq!(output.stderr.into_bytes(), b"err\n");
+
+        let mut cmd = Command::new("sleep");
+        cmd.arg("30");
+        let _terminal = isolate(&mut cmd, false);
+        let mut child = cmd.spawn().unwrap();
+        let error = wait(&mut child, Duration::from_millis(100), &cancel)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AgentError::Timeout(_)));
+    }
+
+    #[tokio::test]
+    async fn test_output_keeps_head_and_tail() {
+        let mut cmd = Command::new("seq");
+        cmd.args(["1", "100000"]).stdout(Stdio::piped());
+        let _terminal = isolate(&mut cmd, false);
+        let cancel = CancellationToken::new();
+        let output = output(cmd.spawn().unwrap(), Duration::from_secs(10), &cancel, 16)
+            .await
+            .unwrap();
+        let total = (1..=100000).map(|n: u32| n.to_string().len() as u64 + 1).sum::<u64>();
+        assert_eq!(output.stdout.omitted(), total - 16);
+        let text = String::from_utf8(output.stdout.into_bytes()).unwrap();
+        assert!(text.starts_with("1\n2\n3\n4\n"), "{}", text);
+        assert!(text.ends_with("...]\n\n100000\n"), "{}", text);
+        assert!(text.contains(&format!("[... {} bytes omitted", total - 16)));
+
+        let mut capture = Capture::new(4);
+        capture.push(b"ab");
+        capture.push(b"cd");
+        assert_eq!(capture.omitted(), 0);
+        assert_eq!(capture.into_bytes(), b"abcd");
+    }
+}
@@ -0,0 +1,589 @@
+// file: src/pty.rs
+// version: 1.1.1
+// guid: 4b9e2d71-6c3a-4f08-a5d2-e17c8b3f9a60
+
+//! Pseudo-terminals for commands that need one
+//!
+//! Some tools behave differently without a terminal: pip and cargo drop their progress output,
+//! git refuses to prompt, pagers are skipped or hang. In PTY mode (`--pty`, `pty = true` in a
+//! `[tools.<command>]` table, or [`crate::executor::Executor::set_pty`]) a command's stdin,
+//! stdout and stderr are the slave side of a new pseudo-terminal, and the command leads a
+//! session whose controlling terminal it is. Everything it writes is read from the master side
+//! and recorded, with what is typed if asked; [`clean_transcript`] turns output into plain text
+//! for captured results and [`Recording::to_asciicast`] keeps a replayable copy.
+
+use crate::error::{AgentError, Result};
+use crate::utils;
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::ffi::CStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Interval at which the copying threads check whether the session is over
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Recordings older than this are deleted when a new one is saved
+const RECORDING_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Size of the pseudo-terminal when this process has no terminal to copy it from
+const DEFAULT_SIZE: (u16, u16) = (24, 80);
+
+/// A pseudo-terminal pair, before the command using it is started
+pub struct Pty {
+    master: OwnedFd,
+    slave: OwnedFd,
+    /// Rows and columns
+    size: (u16, u16),
+}
+
+impl Pty {
+    /// Open a pseudo-terminal the size of this process's terminal, or 80x24 without one
+    pub fn open() -> Result<Self> {
+        let error = |call: &str| {
+            AgentError::system(format!(
+                "Cannot open a pseudo-terminal ({}: {})",
+                call,
+                io::Error::last_os_error()
+            ))
+        };
+        // SAFETY: plain libc calls; the returned descriptor is owned from here on
+        let master = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(error("posix_openpt"));
+            }
+            OwnedFd::from_raw_fd(fd)
+        };
+        // SAFETY: `master` is an open pseudo-terminal master
+        unsafe {
+            if libc::grantpt(master.as_raw_fd()) != 0 {
+                return Err(error("grantpt"));
+            }
+            if libc::unlockpt(master.as_raw_fd()) != 0 {
+                return Err(error("unlockpt"));
+            }
+        }
+        let name = slave_name(master.as_raw_fd()).map_err(|_| error("ptsname"))?;
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&name)?;
+
+        let (rows, cols) = terminal_size().unwrap_or(DEFAULT_SIZE);
+        let size = libc::winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCSWINSZ reads a winsize that outlives the call
+        unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ as _, &size) };
+
+        Ok(Self {
+            master,
+            slave: slave.into(),
+            size: (rows, cols),
+        })
+    }
+
+    /// Rows and columns of the terminal
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Make the slave side `cmd`'s stdin, stdout and stderr and its controlling terminal
+    ///
+    /// The command starts a session of its own, which also makes it lead a process group, so
+    /// it must not be passed to [`crate::process::isolate`].
+    pub fn attach(&self, cmd: &mut Command) -> Result<()> {
+        cmd.stdin(Stdio::from(self.slave.try_clone()?))
+            .stdout(Stdio::from(self.slave.try_clone()?))
+            .stderr(Stdio::from(self.slave.try_clone()?));
+        // SAFETY: the closure only makes async-signal-safe libc calls
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Start copying the command's output once it has been spawned
+    ///
+    /// `input` is written to the terminal with echo turned off and followed by end-of-file;
+    /// without it, keystrokes on this process's terminal are forwarded if `forward_terminal`
+    /// is set, and the command reads end-of-file otherwise.
+    pub fn start(self, options: SessionOptions) -> Result<Session> {
+        let Pty { master, slave, .. } = self;
+        if options.input.is_some() {
+            disable_echo(&slave)?;
+        }
+        // The command has its own copies; ours would keep the terminal open after it exits
+        drop(slave);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let recorder = Recorder {
+            started: Instant::now(),
+            recording: Arc::new(Mutex::new(Recording::default())),
+        };
+        let reader = {
+            let master = File::from(master.try_clone()?);
+            let stop = stop.clone();
+            let recorder = recorder.clone();
+            let echo = options.echo;
+            tokio::task::spawn_blocking(move || copy_output(master, echo, &stop, &recorder))
+        };
+
+        let mut raw_mode = None;
+        let mut writer = File::from(master);
+        let forwarder = match options.input {
+            Some(input) => {
+                let recorder = options.record_input.then(|| recorder.clone());
+                Some(tokio::task::spawn_blocking(move || {
+                    if let Some(recorder) = recorder {
+                        recorder.record(Stream::Input, &input);
+                    }
+                    let mut end = if input.ends_with(b"\n") || input.is_empty() {
+                        Vec::new()
+                    } else {
+                        // End-of-file only ends input at the start of a line, otherwise it ends the line
+                        vec![EOF]
+                    };
+                    end.push(EOF);
+                    if let Err(e) = writer
+                        .write_all(&input)
+                        .and_then(|_| writer.write_all(&end))
+                    {
+                        debug!("Could not write input to the terminal: {}", e);
+                    }
+                }))
+            }
+            None if options.forward_terminal => {
+                crossterm::terminal::enable_raw_mode()?;
+                raw_mode = Some(RawMode);
+                let stop = stop.clone();
+                let recorder = options.record_input.then(|| recorder.clone());
+                Some(tokio::task::spawn_blocking(move || {
+                    forward_keystrokes(&mut writer, &stop, recorder.as_ref())
+                }))
+            }
+            None => {
+                if let Err(e) = writer.write_all(&[EOF]) {
+                    debug!("Could not close the terminal's input: {}", e);
+                }
+                None
+            }
+        };
+
+        Ok(Session {
+            stop,
+            recorder,
+            reader,
+            forwarder,
+            _raw_mode: raw_mode,
+        })
+    }
+}
+
+/// How a [`Session`] connects the pseudo-terminal to this process
+#[derive(Debug, Default)]
+pub struct SessionOptions {
+    /// Copy the command's output to this process's stdout as it is written
+    pub echo: bool,
+    /// Input written to the command instead of keystrokes
+    pub input: Option<Vec<u8>>,
+    /// Put this process's terminal in raw mode and forward what is typed
+    pub forward_terminal: bool,
+    /// Record the input, typed or given, along with the output
+    pub record_input: bool,
+}
+
+/// A running command's pseudo-terminal
+pub struct Session {
+    stop: Arc<AtomicBool>,
+    recorder: Recorder,
+    reader: JoinHandle<()>,
+    forwarder: Option<JoinHandle<()>>,
+    _raw_mode: Option<RawMode>,
+}
+
+impl Session {
+    /// Time since anything was last written to or typed into the terminal
+    pub fn idle(&self) -> Duration {
+        let last = self.recorder.lock().events.last().map(|event| event.time);
+        self.recorder.started.elapsed() - last.unwrap_or_default()
+    }
+
+    /// Stop once the command has exited; returns what passed through the terminal
+    ///
+    /// Output still buffered in the terminal is read first, so nothing the command wrote
+    /// before exiting is lost.
+    pub async fn finish(self) -> Recording {
+        self.stop.store(true, Ordering::Relaxed);
+        // Input the command never read may keep its writer blocked; it is abandoned then
+        if let Some(forwarder) = self.forwarder {
+            let _ = tokio::time::timeout(POLL_INTERVAL * 2, forwarder).await;
+        }
+        let _ = self.reader.await;
+        std::mem::take(&mut *self.recorder.lock())
+    }
+}
+
+/// Direction of data passing through a pseudo-terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    /// Written by the command
+    Output,
+    /// Typed or given as input
+    Input,
+}
+
+/// Data that passed through a pseudo-terminal
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Time since the session started
+    pub time: Duration,
+    pub stream: Stream,
+    pub data: Vec<u8>,
+}
+
+/// What passed through a pseudo-terminal during a session, in order
+#[derive(Debug, Default)]
+pub struct Recording {
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    /// Everything the command wrote
+    pub fn output(&self) -> Vec<u8> {
+        self.events
+            .iter()
+            .filter(|event| event.stream == Stream::Output)
+            .flat_map(|event| event.data.iter().copied())
+            .collect()
+    }
+
+    /// The recording in asciicast v2 format, replayable with `asciinema play`: `header`
+    /// (e.g. `width`, `height`, `timestamp`, `command`) on the first line, then one
+    /// `[seconds, "o" | "i", text]` line per event
+    pub fn to_asciicast(&self, mut header: Map<String, Value>) -> String {
+        header.insert("version".to_string(), json!(2));
+        let mut lines = vec![Value::Object(header).to_string()];
+        // A character split across reads is completed by the next event of its stream
+        let mut pending: [Vec<u8>; 2] = Default::default();
+        for event in &self.events {
+            let (code, carry) = match event.stream {
+                Stream::Output => ("o", &mut pending[0]),
+                Stream::Input => ("i", &mut pending[1]),
+            };
+            carry.extend_from_slice(&event.data);
+            let complete = match std::str::from_utf8(carry) {
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                _ => carry.len(),
+            };
+            let rest = carry.split_off(complete);
+            let text = String::from_utf8_lossy(carry).into_owned();
+            *carry = rest;
+            if !text.is_empty() {
+                lines.push(json!([event.time.as_secs_f64(), code, text]).to_string());
+            }
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+/// Shared [`Recording`] the copying threads add to
+#[derive(Clone)]
+struct Recorder {
+    started: Instant,
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl Recorder {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recording> {
+        self.recording
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn record(&self, stream: Stream, data: &[u8]) {
+        let time = self.started.elapsed();
+        self.lock().events.push(Event {
+            time,
+            stream,
+            data: data.to_vec(),
+        });
+    }
+}
+
+/// Restores this process's terminal when dropped
+struct RawMode;
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+/// The end-of-file character of a terminal in canonical mode (Ctrl-D)
+const EOF: u8 = 0x04;
+
+/// Save a recording in the state directory, deleting recordings older than a day; returns the
+/// recording's path
+pub fn save_recording(command: &str, transcript: &[u8]) -> io::Result<PathBuf> {
+    let dir = utils::state_dir().join("pty");
+    fs::create_dir_all(&dir)?;
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let expired = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > RECORDING_RETENTION);
+            if expired {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    let name: String = command
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let path = dir.join(format!(
+        "{}-{}-{}.log",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+        std::process::id(),
+        name
+    ));
+    fs::write(&path, transcript)?;
+    Ok(path)
+}
+
+/// Plain text of a terminal recording: escape sequences removed, line endings normalized, and
+/// only the final state of lines redrawn with carriage returns (progress bars) kept
+pub fn clean_transcript(raw: &[u8]) -> String {
+    static ESCAPES: OnceLock<Regex> = OnceLock::new();
+    let escapes = ESCAPES.get_or_init(|| {
+        Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)|\x1b[@-Z\\-_]")
+            .expect("valid escape sequence pattern")
+    });
+    let text = String::from_utf8_lossy(raw);
+    let text = escapes.replace_all(&text, "");
+
+    let mut cleaned = String::with_capacity(text.len());
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        cleaned.push_str(line.rsplit('\r').next().unwrap_or_default());
+        cleaned.push('\n');
+    }
+    // Splitting yields one more piece than there are newlines
+    cleaned.pop();
+    cleaned
+}
+
+/// Read the master side until the command's side is closed, or until nothing is left to read
+/// once the session is stopped
+fn copy_output(mut master: File, echo: bool, stop: &AtomicBool, recorder: &Recorder) {
+    let mut buffer = [0u8; 8192];
+    let mut stdout = io::stdout();
+    loop {
+        match readable(master.as_raw_fd()) {
+            Ok(true) => {}
+            Ok(false) if stop.load(Ordering::Relaxed) => break,
+            Ok(false) => continue,
+            Err(e) => {
+                debug!("Could not poll the terminal: {}", e);
+                break;
+            }
+        }
+        // Reading fails with EIO once every copy of the slave side is closed
+        let count = match master.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(count) => count,
+        };
+        recorder.record(Stream::Output, &buffer[..count]);
+        if echo {
+            let _ = stdout
+                .write_all(&buffer[..count])
+                .and_then(|_| stdout.flush());
+        }
+    }
+}
+
+/// Copy what is typed on this process's terminal to the command until the session is stopped,
+/// recording it with `recorder`
+fn forward_keystrokes(master: &mut File, stop: &AtomicBool, recorder: Option<&Recorder>) {
+    let mut stdin = io::stdin();
+    let mut buffer = [0u8; 1024];
+    while !stop.load(Ordering::Relaxed) {
+        match readable(libc::STDIN_FILENO) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(_) => return,
+        }
+        match stdin.read(&mut buffer) {
+            Ok(0) | Err(_) => return,
+            Ok(count) => {
+                if let Some(recorder) = recorder {
+                    recorder.record(Stream::Input, &buffer[..count]);
+                }
+                if master.write_all(&buffer[..count]).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Wait up to [`POLL_INTERVAL`] for `fd` to become readable (or hung up)
+fn readable(fd: RawFd) -> io::Result<bool> {
+    let mut poll = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `poll` is a single valid pollfd
+    match unsafe { libc::poll(&mut poll, 1, POLL_INTERVAL.as_millis() as libc::c_int) } {
+        -1 => {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                Ok(false)
+            } else {
+                Err(error)
+            }
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Turn off echo on the terminal, so input written to it is not recorded as output
+fn disable_echo(slave: &OwnedFd) -> io::Result<()> {
+    // SAFETY: termios is plain data filled in by tcgetattr
+    unsafe {
+        let mut termios = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(slave.as_raw_fd(), &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        termios.c_lflag &= !(libc::ECHO | libc::ECHONL);
+        if libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Rows and columns of the terminal on this process's stdout
+fn terminal_size() -> Option<(u16, u16)> {
+    // SAFETY: TIOCGWINSZ fills in a winsize that outlives the call
+    let size = unsafe {
+        let mut size = std::mem::zeroed::<libc::winsize>();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ as _, &mut size) != 0 {
+            return None;
+        }
+        size
+    };
+    (size.ws_row > 0 && size.ws_col > 0).then_some((size.ws_row, size.ws_col))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn slave_name(master: RawFd) -> io::Result<PathBuf> {
+    let mut buffer = [0 as libc::c_char; 128];
+    // SAFETY: the buffer's length is passed along with it
+    if unsafe { libc::ptsname_r(master, buffer.as_mut_ptr(), buffer.len()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: ptsname_r wrote a NUL-terminated name
+    let name = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+    Ok(PathBuf::from(name.to_string_lossy().into_owned()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn slave_name(master: RawFd) -> io::Result<PathBuf> {
+    // SAFETY: the name is copied before anything else can call ptsname
+    unsafe {
+        let name = libc::ptsname(master);
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(PathBuf::from(
+            CStr::from_ptr(name).to_string_lossy().into_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_transcript() {
+        let raw = b"\x1b[1mBuilding\x1b[0m\r\n 10%\r 50%\r100%\r\n\x1b]0;title\x07done";
+        assert_eq!(clean_transcript(raw), "Building\n100%\ndone");
+    }
+
+    #[test]
+    fn test_asciicast_keeps_split_characters_whole() {
+        let event = |millis, stream, data: &[u8]| Event {
+            time: Duration::from_millis(millis),
+            stream,
+            data: data.to_vec(),
+        };
+        let recording = Recording {
+            events: vec![
+                event(0, Stream::Output, b"caf\xc3"),
+                event(500, Stream::Input, b"ls\r"),
+                event(1500, Stream::Output, b"\xa9\r\n"),
+            ],
+        };
+        let mut header = Map::new();
+        header.insert("width".to_string(), json!(80));
+        let cast = recording.to_asciicast(header);
+        let lines: Vec<&str> = cast.lines().collect();
+        assert_eq!(lines[0], r#"{"version":2,"width":80}"#);
+        assert_eq!(lines[1], r#"[0.0,"o","caf"]"#);
+        assert_eq!(lines[2], r#"[0.5,"i","ls\r"]"#);
+        assert_eq!(lines[3], "[1.5,\"o\",\"\u{e9}\\r\\n\"]");
+        assert_eq!(recording.output(), "caf\u{e9}\r\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_command_sees_a_terminal() {
+        let pty = Pty::open().unwrap();
+        let mut cmd = Command::new("python3");
+        cmd.args([
+            "-c",
+            "import sys; print(sys.stdin.isatty(), sys.stdout.isatty(), input())",
+        ]);
+        pty.attach(&mut cmd).unwrap();
+        let mut child = cmd.spawn().unwrap();
+        drop(cmd);
+        let session = pty
+            .start(SessionOptions {
+                input: Some(b"typed\n".to_vec()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(child.wait().await.unwrap().success());
+        let recording = session.finish().await;
+        assert_eq!(clean_transcript(&recording.output()), "True True typed\n");
+    }
+}
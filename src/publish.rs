@@ -0,0 +1,299 @@
+// file: src/publish.rs
+// version: 1.0.0
+// guid: 48793c16-f1ed-4844-814e-382015a446d6
+
+//! Published artifacts and their provenance
+//!
+//! Every `publish` run keeps what it built in `publish/<run id>/` in the state directory: the
+//! artifacts themselves (the `.crate`, the npm tarball, the wheels and sdists) and
+//! `provenance.intoto.json`, an in-toto statement with a SLSA provenance predicate naming the
+//! artifacts by hash, the source commit they were built from, the tool versions and the run.
+//! `publish/records.json` lists the runs, dry runs included, newest last.
+
+use crate::activity::FileLock;
+use crate::error::{AgentError, Result};
+use crate::utils;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the provenance document in a run's directory
+pub const PROVENANCE_FILE: &str = "provenance.intoto.json";
+
+/// Predicate type of the provenance statement
+pub const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+
+/// Registry a package is published to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ecosystem {
+    Cargo,
+    Npm,
+    Pypi,
+}
+
+impl Ecosystem {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cargo => "cargo",
+            Self::Npm => "npm",
+            Self::Pypi => "pypi",
+        }
+    }
+
+    /// Registry packages go to unless another is chosen
+    pub fn default_registry(self) -> &'static str {
+        match self {
+            Self::Cargo => "crates.io",
+            Self::Npm => "registry.npmjs.org",
+            Self::Pypi => "pypi",
+        }
+    }
+}
+
+impl fmt::Display for Ecosystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// A file that was published, or would have been
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactFile {
+    /// File name, as stored in the run's directory
+    pub name: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl ArtifactFile {
+    /// Hash a file
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = fs::read(path)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| AgentError::validation(format!("Not a file: {}", path.display())))?;
+        Ok(Self {
+            name,
+            sha256: hex::encode(Sha256::digest(&content)),
+            size: content.len() as u64,
+        })
+    }
+}
+
+/// Commit the artifacts were built from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceInfo {
+    /// URL of the `origin` remote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Whether the working tree had uncommitted changes
+    #[serde(default)]
+    pub dirty: bool,
+}
+
+/// One `publish` run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublishRecord {
+    /// Execution ID of the run, also the name of its directory
+    pub id: String,
+    pub session_id: String,
+    pub ecosystem: Ecosystem,
+    pub package: String,
+    pub version: String,
+    pub registry: String,
+    /// Whether the artifacts were uploaded, rather than checked in a dry run
+    pub uploaded: bool,
+    pub artifacts: Vec<ArtifactFile>,
+    /// Command that uploaded the artifacts, or would have
+    pub command: Vec<String>,
+    /// Versions of the tools that built and uploaded the artifacts
+    pub tools: BTreeMap<String, String>,
+    pub source: SourceInfo,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+impl PublishRecord {
+    /// Directory keeping the artifacts and provenance of the run with this ID
+    pub fn dir_for(id: &str) -> PathBuf {
+        utils::state_dir().join("publish").join(id)
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        Self::dir_for(&self.id)
+    }
+
+    /// The run's provenance: an in-toto statement about the artifacts with a SLSA v1 predicate
+    pub fn provenance(&self) -> Value {
+        let subject: Vec<Value> = self
+            .artifacts
+            .iter()
+            .map(|artifact| json!({ "name": artifact.name, "digest": { "sha256": artifact.sha256 } }))
+            .collect();
+        let dependencies: Vec<Value> = match (&self.source.repository, &self.source.commit) {
+            (repository, Some(commit)) => vec![json!({
+                "uri": format!("git+{}@{}", repository.as_deref().unwrap_or("file:."), commit),
+                "digest": { "gitCommit": commit },
+                "annotations": { "dirty": self.source.dirty },
+            })],
+            (_, None) => Vec::new(),
+        };
+        let mut versions = serde_json::Map::new();
+        versions.insert("safe-ai-util".to_string(), json!(env!("CARGO_PKG_VERSION")));
+        for (tool, version) in &self.tools {
+            versions.insert(tool.clone(), json!(version));
+        }
+
+        json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "subject": subject,
+            "predicateType": PREDICATE_TYPE,
+            "predicate": {
+                "buildDefinition": {
+                    "buildType": concat!(env!("CARGO_PKG_REPOSITORY"), "/publish/v1"),
+                    "externalParameters": {
+                        "ecosystem": self.ecosystem,
+                        "package": self.package,
+                        "version": self.version,
+                        "registry": self.registry,
+                        "command": self.command,
+                        "upload": self.uploaded,
+                    },
+                    "internalParameters": { "sessionId": self.session_id },
+                    "resolvedDependencies": dependencies,
+                },
+                "runDetails": {
+                    "builder": {
+                        "id": env!("CARGO_PKG_REPOSITORY"),
+                        "version": versions,
+                    },
+                    "metadata": {
+                        "invocationId": self.id,
+                        "startedOn": self.started_at,
+                        "finishedOn": self.finished_at,
+                    },
+                },
+            },
+        })
+    }
+
+    /// Write the provenance document into the run's directory, returning its path
+    pub fn write_provenance(&self) -> Result<PathBuf> {
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(PROVENANCE_FILE);
+        fs::write(&path, serde_json::to_string_pretty(&self.provenance())?)?;
+        Ok(path)
+    }
+}
+
+/// The `publish` runs, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublishLog {
+    pub records: Vec<PublishRecord>,
+}
+
+impl PublishLog {
+    /// Path of the persisted log
+    pub fn path() -> PathBuf {
+        utils::state_dir().join("publish").join("records.json")
+    }
+
+    /// Load the log, starting empty if nothing has been published yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Load, change and save the log while holding its lock
+    pub fn update<T>(change: impl FnOnce(&mut Self) -> T) -> Result<T> {
+        let path = Self::path();
+        let dir = path
+            .parent()
+            .ok_or_else(|| AgentError::system("Invalid publish records path"))?;
+        fs::create_dir_all(dir)?;
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut log = Self::load()?;
+        let result = change(&mut log);
+
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&log)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(result)
+    }
+
+    /// The run with this ID or ID prefix
+    pub fn find(&self, id: &str) -> Option<&PublishRecord> {
+        self.records
+            .iter()
+            .rev()
+            .find(|record| record.id.starts_with(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provenance_names_artifacts_source_and_tools() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let crate_file = dir.path().join("demo-0.1.0.crate");
+        fs::write(&crate_file, b"crate").unwrap();
+        let artifact = ArtifactFile::from_path(&crate_file).unwrap();
+        assert_eq!(artifact.size, 5);
+
+        let now = Utc::now();
+        let record = PublishRecord {
+            id: "run-1".to_string(),
+            session_id: "session-1".to_string(),
+            ecosystem: Ecosystem::Cargo,
+            package: "demo".to_string(),
+            version: "0.1.0".to_string(),
+            registry: "crates.io".to_string(),
+            uploaded: false,
+            artifacts: vec![artifact.clone()],
+            command: vec!["cargo".to_string(), "publish".to_string()],
+            tools: BTreeMap::from([("cargo".to_string(), "1.80.0".to_string())]),
+            source: SourceInfo {
+                repository: Some("https://example.com/demo.git".to_string()),
+                commit: Some("abc123".to_string()),
+                dirty: true,
+            },
+            started_at: now,
+            finished_at: now,
+        };
+        let statement = record.provenance();
+        assert_eq!(statement["predicateType"], PREDICATE_TYPE);
+        assert_eq!(statement["subject"][0]["name"], "demo-0.1.0.crate");
+        assert_eq!(statement["subject"][0]["digest"]["sha256"], artifact.sha256);
+        let predicate = &statement["predicate"];
+        assert_eq!(
+            predicate["buildDefinition"]["externalParameters"]["ecosystem"],
+            "cargo"
+        );
+        assert_eq!(
+            predicate["buildDefinition"]["resolvedDependencies"][0]["uri"],
+            "git+https://example.com/demo.git@abc123"
+        );
+        assert_eq!(
+            predicate["runDetails"]["builder"]["version"]["cargo"],
+            "1.80.0"
+        );
+        assert_eq!(predicate["runDetails"]["metadata"]["invocationId"], "run-1");
+    }
+}
@@ -0,0 +1,257 @@
+// file: src/remote.rs
+// version: 1.0.0
+// guid: 8b2d5f47-1c9e-4a63-b7d0-e5a3c6f19824
+
+//! Remote execution over SSH
+//!
+//! With `--target ssh://[user@]host[:port][/directory]`, or `remote.target`, every command the
+//! executor spawns runs on another machine, such as a build server, through the local `ssh`
+//! client. Commands are validated, audited and recorded here as usual; only the process runs
+//! remotely, with its output streamed back:
+//!
+//! - The remote directory is the URL's path, or the local working directory's path if the URL
+//!   has none. A command's own working directory is taken relative to it.
+//! - Tools are the remote machine's: commands are looked up on its `PATH`, and tool pins and
+//!   `safety.verify_binaries` do not apply.
+//! - The environment policy is applied on the remote side: the variables the executor blocks
+//!   are unset there, and only the variables it sets for the command are passed.
+//! - `ssh` runs in batch mode, so a host needing a password or an unknown host key fails
+//!   instead of prompting; keys, users and jump hosts come from `~/.ssh/config`.
+//!
+//! Operations the utility performs itself, such as the `file` commands, still happen locally.
+
+use crate::config::Config;
+use crate::error::{AgentError, Result};
+use crate::security::sandbox::canonicalize_lenient;
+use crate::tools;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command;
+
+static TARGET: OnceLock<String> = OnceLock::new();
+
+/// A machine commands run on over SSH
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    ssh: PathBuf,
+    /// The target as given, e.g. `ssh://ci@build01:2222/srv/app`
+    url: String,
+    /// `[user@]host`
+    destination: String,
+    port: Option<u16>,
+    directory: Option<PathBuf>,
+}
+
+impl Remote {
+    /// The remote target configured in `config`, if any
+    ///
+    /// The `ssh` client is looked up now, so a missing one fails before anything runs.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(target) = &config.remote.target else {
+            return Ok(None);
+        };
+        let ssh = tools::resolve("ssh", config.tools.get("ssh"))?;
+        Self::parse(target, ssh).map(Some)
+    }
+
+    /// Parse an `ssh://[user@]host[:port][/directory]` target
+    pub fn parse(target: &str, ssh: PathBuf) -> Result<Self> {
+        let invalid = |reason: &str| {
+            AgentError::invalid_argument(format!("Invalid --target '{}': {}", target, reason))
+        };
+        let rest = target
+            .strip_prefix("ssh://")
+            .ok_or_else(|| invalid("only ssh:// targets are supported"))?;
+        let (authority, directory) = match rest.find('/') {
+            Some(at) => (&rest[..at], Some(PathBuf::from(&rest[at..]))),
+            None => (rest, None),
+        };
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (Some(user), host_port),
+            None => (None, authority),
+        };
+        // [v6 address]:port, or host:port
+        let (host, port) = match host_port.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, after) = bracketed
+                    .split_once(']')
+                    .ok_or_else(|| invalid("unclosed '['"))?;
+                (host, after.strip_prefix(':'))
+            }
+            None => match host_port.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            },
+        };
+        let port = port
+            .map(|port| port.parse::<u16>().map_err(|_| invalid("bad port")))
+            .transpose()?;
+        let valid = |part: &str| {
+            !part.is_empty()
+                && !part.starts_with('-')
+                && !part.chars().any(|c| c.is_whitespace() || c.is_control())
+        };
+        if !valid(host) || user.is_some_and(|user| !valid(user)) {
+            return Err(invalid("expected ssh://[user@]host[:port][/directory]"));
+        }
+        Ok(Self {
+            ssh,
+            url: target.to_string(),
+            destination: match user {
+                Some(user) => format!("{}@{}", user, host),
+                None => host.to_string(),
+            },
+            port,
+            directory,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// `ssh` and its arguments running the prepared `cmd` remotely, with the variables set on
+    /// it and those in `unset` removed
+    ///
+    /// `base` is the local working directory, which stands for the remote directory; the
+    /// command's own working directory is taken relative to it.
+    pub fn command_line(
+        &self,
+        cmd: &Command,
+        base: &Path,
+        unset: &[&str],
+    ) -> (PathBuf, Vec<String>) {
+        let cmd = cmd.as_std();
+        let root = self.directory.as_deref().unwrap_or(base);
+        let directory = match cmd.get_current_dir() {
+            Some(dir) if dir.is_relative() => root.join(dir),
+            Some(dir) => match canonicalize_lenient(dir).strip_prefix(base) {
+                Ok(relative) => root.join(relative),
+                Err(_) => root.to_path_buf(),
+            },
+            None => root.to_path_buf(),
+        };
+
+        let mut words = vec!["env".to_string()];
+        for name in unset {
+            words.push("-u".to_string());
+            words.push(name.to_string());
+        }
+        for (key, value) in cmd.get_envs() {
+            // The local PATH means nothing on the remote machine
+            if key == "PATH" {
+                continue;
+            }
+            match value {
+                Some(value) => words.push(format!(
+                    "{}={}",
+                    key.to_string_lossy(),
+                    value.to_string_lossy()
+                )),
+                None => {
+                    words.push("-u".to_string());
+                    words.push(key.to_string_lossy().into_owned());
+                }
+            }
+        }
+        let program = Path::new(cmd.get_program());
+        words.push(match program.strip_prefix(base) {
+            Ok(relative) if program.is_absolute() => root.join(relative).display().to_string(),
+            _ if program.is_absolute() => program
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            _ => program.display().to_string(),
+        });
+        words.extend(cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()));
+
+        let script = format!(
+            "cd {} && exec {}",
+            shell_words::quote(&directory.display().to_string()),
+            shell_words::join(&words)
+        );
+        let mut args = vec!["-o".to_string(), "BatchMode=yes".to_string()];
+        if let Some(port) = self.port {
+            args.push("-p".to_string());
+            args.push(port.to_string());
+        }
+        args.push("--".to_string());
+        args.push(self.destination.clone());
+        args.push(script);
+        (self.ssh.clone(), args)
+    }
+}
+
+/// Record the target commands of this invocation run on, for the audit log; only the first
+/// call has an effect
+pub fn set_current(url: &str) {
+    let _ = TARGET.set(url.to_string());
+}
+
+/// The target commands of this invocation run on, if not this machine
+pub fn current() -> Option<&'static str> {
+    TARGET.get().map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(target: &str) -> Result<Remote> {
+        Remote::parse(target, PathBuf::from("/usr/bin/ssh"))
+    }
+
+    #[test]
+    fn test_parse() {
+        let build = remote("ssh://ci@build01:2222/srv/app").unwrap();
+        assert_eq!(build.destination, "ci@build01");
+        assert_eq!(build.port, Some(2222));
+        assert_eq!(build.directory.as_deref(), Some(Path::new("/srv/app")));
+
+        let v6 = remote("ssh://[::1]:22").unwrap();
+        assert_eq!((v6.destination.as_str(), v6.port), ("::1", Some(22)));
+        assert_eq!(remote("ssh://build01").unwrap().directory, None);
+
+        for bad in [
+            "build01",
+            "http://build01",
+            "ssh://-oProxyCommand=x",
+            "ssh://h:port",
+            "ssh://",
+        ] {
+            assert!(remote(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_command_line() {
+        let mut cmd = Command::new("/usr/bin/cargo");
+        cmd.args(["test", "it's"])
+            .current_dir("/home/me/app/crate")
+            .env("PATH", "/usr/bin")
+            .env("SAFE_AI_SESSION_ID", "abc");
+
+        let build = remote("ssh://ci@build01:2222/srv/app").unwrap();
+        let (ssh, args) = build.command_line(&cmd, Path::new("/home/me/app"), &["LD_PRELOAD"]);
+        assert_eq!(ssh, Path::new("/usr/bin/ssh"));
+        assert_eq!(
+            args,
+            [
+                "-o",
+                "BatchMode=yes",
+                "-p",
+                "2222",
+                "--",
+                "ci@build01",
+                "cd /srv/app/crate && exec env -u LD_PRELOAD 'SAFE_AI_SESSION_ID=abc' cargo test 'it'\\''s'",
+            ]
+        );
+
+        // Without a directory in the URL the local path is used
+        let (_, args) =
+            remote("ssh://build01")
+                .unwrap()
+                .command_line(&cmd, Path::new("/home/me/app"), &[]);
+        assert!(args[4].starts_with("cd /home/me/app/crate && exec env "));
+    }
+}
@@ -0,0 +1,523 @@
+// file: src/repl.rs
+// version: 1.0.0
+// guid: 6e0b3d8f-2c71-4a95-8f4e-d1a7c9b25e03
+
+//! Interactive prompt (`repl`)
+//!
+//! Every line is split like a shell command line and run as a command of this utility, such
+//! as `git status` or `run -m tasks.yaml lint`, by an invocation that is handed the terminal,
+//! so interactive commands and Ctrl-C behave as they do from the shell. The session keeps a
+//! working directory, a profile and environment overrides for the commands that follow, set
+//! with built-ins:
+//!
+//! ```text
+//! cd [DIR]            profile [NAME|none]     set NAME=VALUE
+//! unset NAME          env                     help            exit
+//! ```
+//!
+//! On a terminal lines are edited with [`LineEditor`], Tab completes like the shell
+//! completions do, and the history is kept in `repl_history` in the state directory. Otherwise
+//! lines are read from stdin without a prompt, so a prepared sequence can be piped in.
+
+use crate::commands::completions;
+use crate::config::{Config, LoadOptions, PROFILE_ENV};
+use crate::error::{AgentError, Result};
+use crate::executor::Executor;
+use crate::ide::{self, CommandInfo};
+use crate::line_editor::LineEditor;
+use crate::process::{self, CancellationToken};
+use crate::utils;
+use clap::Command;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{info, warn};
+
+/// File in the state directory keeping the lines typed at the prompt
+pub const HISTORY_FILE: &str = "repl_history";
+
+/// Lines of history kept
+const MAX_HISTORY: usize = 1000;
+
+/// Built-in commands and their usage
+const BUILTINS: &[(&str, &str)] = &[
+    (
+        "cd",
+        "cd [DIR]              Change the working directory (default: where the session started)",
+    ),
+    (
+        "profile",
+        "profile [NAME|none]   Show or change the profile commands run with",
+    ),
+    (
+        "set",
+        "set NAME=VALUE        Set an environment variable for the commands",
+    ),
+    (
+        "unset",
+        "unset NAME            Remove an environment variable from the commands",
+    ),
+    (
+        "env",
+        "env                   Show the working directory, profile and variables set",
+    ),
+    (
+        "help",
+        "help                  Show this help; COMMAND --help shows a command's",
+    ),
+    (
+        "exit",
+        "exit                  End the session (also quit, or Ctrl-D)",
+    ),
+];
+
+/// How commands typed at the prompt are run
+pub struct ReplOptions {
+    /// Global options given before `repl`, except `--profile`
+    pub forward_args: Vec<String>,
+    /// Configuration file given with `--config`, against which profiles are checked
+    pub config_file: Option<PathBuf>,
+    /// Profile given with `--profile`
+    pub profile: Option<String>,
+    /// Commands a line may start with
+    pub commands: Vec<CommandInfo>,
+    /// The command line interface, for completion
+    pub cli: Command,
+    /// Read and save the history file
+    pub history: bool,
+}
+
+/// What the commands of a session share
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub cwd: PathBuf,
+    pub profile: Option<String>,
+    /// Variables set (`Some`) or removed (`None`) for the commands
+    pub env: BTreeMap<String, Option<String>>,
+}
+
+/// A line that changes the session instead of running a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Builtin {
+    Cd(Option<String>),
+    Profile(Option<String>),
+    Set(String, String),
+    Unset(String),
+    Env,
+    Help,
+    Exit,
+}
+
+impl Builtin {
+    /// The built-in a line starts with, if any; `help COMMAND` is left to the command line
+    /// interface
+    pub fn parse(words: &[String]) -> Option<std::result::Result<Self, String>> {
+        let (name, args) = words.split_first()?;
+        let usage = |name: &str| {
+            let (_, usage) = BUILTINS.iter().find(|(n, _)| *n == name).unwrap();
+            Err(format!(
+                "Usage: {}",
+                usage.split("  ").next().unwrap_or(usage)
+            ))
+        };
+        let builtin = match (name.as_str(), args) {
+            ("cd", []) => Ok(Self::Cd(None)),
+            ("cd", [dir]) => Ok(Self::Cd(Some(dir.clone()))),
+            ("cd", _) => usage("cd"),
+            ("profile", []) => Ok(Self::Profile(None)),
+            ("profile", [name]) => Ok(Self::Profile(Some(name.clone()))),
+            ("profile", _) => usage("profile"),
+            ("set", [assignment]) => match assignment.split_once('=') {
+                Some((name, value)) if is_variable_name(name) => {
+                    Ok(Self::Set(name.to_string(), value.to_string()))
+                }
+                _ => usage("set"),
+            },
+            ("set", _) => usage("set"),
+            ("unset", [name]) if is_variable_name(name) => Ok(Self::Unset(name.clone())),
+            ("unset", _) => usage("unset"),
+            ("env", []) => Ok(Self::Env),
+            ("help", []) => Ok(Self::Help),
+            ("exit" | "quit", []) => Ok(Self::Exit),
+            _ => return None,
+        };
+        Some(builtin)
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Session {
+    /// The prompt: the profile, if any, and the working directory with `~` for home
+    pub fn prompt(&self) -> String {
+        let cwd = match dirs::home_dir().and_then(|home| {
+            self.cwd
+                .strip_prefix(home)
+                .ok()
+                .map(|rest| rest.to_path_buf())
+        }) {
+            Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+            Some(rest) => format!("~/{}", rest.display()),
+            None => self.cwd.display().to_string(),
+        };
+        match &self.profile {
+            Some(profile) => format!("[{}] {}> ", profile, cwd),
+            None => format!("{}> ", cwd),
+        }
+    }
+}
+
+/// The prompt loop
+struct Repl<'a> {
+    executor: &'a Executor,
+    options: ReplOptions,
+    session: Session,
+    start: PathBuf,
+    exe: PathBuf,
+    interactive: bool,
+}
+
+/// Run the prompt until `exit` or the end of input
+pub async fn run(executor: &Executor, options: ReplOptions) -> Result<()> {
+    let start = std::env::current_dir()?;
+    let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+    let session = Session {
+        cwd: start.clone(),
+        profile: options.profile.clone(),
+        env: BTreeMap::new(),
+    };
+    let mut repl = Repl {
+        executor,
+        session,
+        start,
+        exe: std::env::current_exe()?,
+        interactive,
+        options,
+    };
+    info!("REPL started (interactive: {})", interactive);
+
+    if !interactive {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Some(line) = lines.next_line().await? {
+            if !repl.handle(&line).await {
+                break;
+            }
+        }
+        return Ok(());
+    }
+
+    let history_path = utils::state_dir().join(HISTORY_FILE);
+    let history = match repl.options.history {
+        true => fs::read_to_string(&history_path)
+            .map(|content| content.lines().map(str::to_string).collect())
+            .unwrap_or_default(),
+        false => Vec::new(),
+    };
+    let mut editor = LineEditor::new(history, MAX_HISTORY);
+    println!(
+        "Type commands such as `git status`; `help` lists the built-ins, `exit` ends the session"
+    );
+    loop {
+        let prompt = repl.session.prompt();
+        let mut cli = repl.options.cli.clone();
+        let cwd = repl.session.cwd.clone();
+        let mut complete = |line: &str, cursor: usize| complete(&mut cli, line, cursor, &cwd);
+        let line = tokio::task::block_in_place(|| editor.read_line(&prompt, &mut complete))?;
+        let Some(line) = line else {
+            break;
+        };
+        editor.add_history(&line);
+        if repl.options.history {
+            let content = editor.history().join("\n") + "\n";
+            let saved = fs::create_dir_all(utils::state_dir())
+                .and_then(|_| fs::write(&history_path, content));
+            if let Err(e) = saved {
+                warn!("Failed to save the REPL history: {}", e);
+            }
+        }
+        if !repl.handle(&line).await {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Candidates for the word before `cursor`: built-ins and commands for the first word,
+/// otherwise what the shell completion offers
+fn complete(cli: &mut Command, line: &str, cursor: usize, cwd: &Path) -> (usize, Vec<String>) {
+    let before: String = line.chars().take(cursor).collect();
+    let start = before
+        .rfind(' ')
+        .map_or(0, |i| before[..=i].chars().count());
+    let Ok(mut words) = shell_words::split(&before) else {
+        return (start, Vec::new());
+    };
+    if before.is_empty() || before.ends_with(' ') {
+        words.push(String::new());
+    }
+    let current = words.last().cloned().unwrap_or_default();
+    words.insert(0, cli.get_name().to_string());
+    let index = words.len() - 1;
+    if index == 2 && words[1] == "cd" {
+        return (start, directories(cwd, &current));
+    }
+    let mut candidates = completions::complete_line(cli, &words, index, cwd);
+    if index == 1 {
+        candidates.extend(
+            BUILTINS
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .filter(|name| name.starts_with(&current)),
+        );
+        candidates.sort();
+        candidates.dedup();
+    }
+    (start, candidates)
+}
+
+/// Directories `partial` can be completed to, relative to `cwd`, each ending with `/`
+fn directories(cwd: &Path, partial: &str) -> Vec<String> {
+    let (parent, prefix) = match partial.rfind('/') {
+        Some(i) => (&partial[..=i], &partial[i + 1..]),
+        None => ("", partial),
+    };
+    let Ok(entries) = fs::read_dir(cwd.join(shellexpand::tilde(parent).as_ref())) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            name.starts_with(prefix) && (!name.starts_with('.') || prefix.starts_with('.'))
+        })
+        .map(|name| format!("{}{}/", parent, name))
+        .collect();
+    names.sort();
+    names
+}
+
+impl Repl<'_> {
+    /// Handle one line; false once the session should end
+    async fn handle(&mut self, line: &str) -> bool {
+        let mut words = match shell_words::split(line) {
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("Cannot parse the line: {}", e);
+                return true;
+            }
+        };
+        if words.is_empty() {
+            return true;
+        }
+        if let Some(builtin) = Builtin::parse(&words) {
+            let result = match builtin {
+                Ok(Builtin::Exit) => return false,
+                Ok(builtin) => self.apply(builtin).await,
+                Err(usage) => Err(AgentError::invalid_argument(usage)),
+            };
+            if let Err(e) = result {
+                eprintln!("{}", e);
+            }
+            return true;
+        }
+        if words[0] == "help" {
+            words.remove(0);
+            words.push("--help".to_string());
+        }
+        if let Err(e) = ide::check_run_args(&words, &self.options.commands) {
+            eprintln!("{}", e);
+            return true;
+        }
+        match self.run_command(&words).await {
+            Ok(0) => {}
+            Ok(code) => eprintln!("[exit {}]", code),
+            Err(e) => eprintln!("{}", e),
+        }
+        true
+    }
+
+    async fn apply(&mut self, builtin: Builtin) -> Result<()> {
+        let session = &mut self.session;
+        match builtin {
+            Builtin::Cd(dir) => {
+                let target = match dir {
+                    Some(dir) => session.cwd.join(shellexpand::tilde(&dir).as_ref()),
+                    None => self.start.clone(),
+                };
+                let target = target.canonicalize().map_err(|e| {
+                    AgentError::file_not_found(format!("{}: {}", target.display(), e))
+                })?;
+                if !target.is_dir() {
+                    return Err(AgentError::invalid_argument(format!(
+                        "{} is not a directory",
+                        target.display()
+                    )));
+                }
+                self.executor
+                    .check_path(&target)
+                    .map_err(|e| AgentError::permission(e.to_string()))?;
+                session.cwd = target;
+            }
+            Builtin::Profile(None) => {
+                println!("{}", session.profile.as_deref().unwrap_or("(none)"));
+            }
+            Builtin::Profile(Some(name)) if name == "none" => {
+                session.profile = None;
+                // A profile from the environment would still apply
+                session.env.insert(PROFILE_ENV.to_string(), None);
+            }
+            Builtin::Profile(Some(name)) => {
+                Config::resolve(LoadOptions {
+                    file: self.options.config_file.as_deref(),
+                    profile: Some(&name),
+                })
+                .await?;
+                session.profile = Some(name);
+            }
+            Builtin::Set(name, value) => {
+                session.env.insert(name, Some(value));
+            }
+            Builtin::Unset(name) => {
+                session.env.insert(name, None);
+            }
+            Builtin::Env => {
+                println!("cwd      {}", session.cwd.display());
+                println!(
+                    "profile  {}",
+                    session.profile.as_deref().unwrap_or("(none)")
+                );
+                for (name, value) in &session.env {
+                    match value {
+                        Some(value) => println!("set      {}={}", name, value),
+                        None => println!("unset    {}", name),
+                    }
+                }
+            }
+            Builtin::Help => {
+                for (_, usage) in BUILTINS {
+                    println!("{}", usage);
+                }
+                let names: Vec<&str> = self
+                    .options
+                    .commands
+                    .iter()
+                    .map(|command| command.name.as_str())
+                    .collect();
+                println!("\nCommands: {}", names.join(", "));
+            }
+            Builtin::Exit => {}
+        }
+        Ok(())
+    }
+
+    /// Run a command line in the session and return its exit code
+    async fn run_command(&self, words: &[String]) -> Result<i32> {
+        let mut global = self.options.forward_args.clone();
+        if let Some(profile) = &self.session.profile {
+            global.push(format!("--profile={}", profile));
+        }
+        let mut command =
+            ide::child_command(&self.exe, &global, words, &self.session.cwd, self.executor);
+        // Piped input belongs to the session, not to its commands
+        let stdin = match self.interactive {
+            true => Stdio::inherit(),
+            false => Stdio::null(),
+        };
+        command
+            .stdin(stdin)
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+        for (name, value) in &self.session.env {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name),
+            };
+        }
+
+        let _terminal = process::isolate(&mut command, self.interactive);
+        let mut child = command.spawn().map_err(|e| {
+            AgentError::execution(format!("Failed to run {}: {}", words.join(" "), e))
+        })?;
+        let status = process::wait(&mut child, Duration::MAX, &CancellationToken::new()).await?;
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        shell_words::split(line).unwrap()
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(
+            Builtin::parse(&words("cd src")),
+            Some(Ok(Builtin::Cd(Some("src".into()))))
+        );
+        assert_eq!(
+            Builtin::parse(&words("set RUST_LOG='debug,hyper=info'")),
+            Some(Ok(Builtin::Set(
+                "RUST_LOG".into(),
+                "debug,hyper=info".into()
+            )))
+        );
+        assert_eq!(Builtin::parse(&words("quit")), Some(Ok(Builtin::Exit)));
+        assert_eq!(
+            Builtin::parse(&words("set 1X=2")),
+            Some(Err("Usage: set NAME=VALUE".into()))
+        );
+        assert!(Builtin::parse(&words("cd a b")).unwrap().is_err());
+        // Commands, and help for a command, are not built-ins
+        assert_eq!(Builtin::parse(&words("git status")), None);
+        assert_eq!(Builtin::parse(&words("help git")), None);
+    }
+
+    #[test]
+    fn test_prompt() {
+        let session = Session {
+            cwd: PathBuf::from("/srv/project"),
+            profile: Some("ci".into()),
+            env: BTreeMap::new(),
+        };
+        assert_eq!(session.prompt(), "[ci] /srv/project> ");
+        let session = Session {
+            profile: None,
+            ..session
+        };
+        assert_eq!(session.prompt(), "/srv/project> ");
+    }
+
+    #[test]
+    fn test_complete_first_word_and_values() {
+        let mut cli = Command::new("safe-ai-util")
+            .subcommand(Command::new("git").subcommand(Command::new("status")))
+            .subcommand(Command::new("config"));
+        let cwd = std::env::temp_dir();
+        let (start, candidates) = complete(&mut cli, "c", 1, &cwd);
+        assert_eq!(start, 0);
+        assert_eq!(candidates, ["cd", "config"]);
+        let (start, candidates) = complete(&mut cli, "git st", 6, &cwd);
+        assert_eq!(start, 4);
+        assert_eq!(candidates, ["status"]);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("src/commands")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        let (start, candidates) = complete(&mut cli, "cd src/c", 8, dir.path());
+        assert_eq!((start, candidates), (3, vec!["src/commands/".to_string()]));
+    }
+}
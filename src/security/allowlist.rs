@@ -1,5 +1,5 @@
 // file: src/security/allowlist.rs
-// version: 1.0.0
+// version: 1.0.1
 // guid: e5f6a7b8-c9d0-1234-ef56-567890123456
 
 //! Command allowlist management module
@@ -171,6 +171,7 @@ impl AllowlistConfig {
             "make", "cmake", "ninja",
             "mvn", "gradle", "sbt",
             "dotnet", "nuget",
+            "mise",
         ];
 
         for cmd in &safe_commands {
@@ -1,5 +1,5 @@
 // file: src/security/mod.rs
-// version: 1.0.0
+// version: 1.17.0
 // guid: a1b2c3d4-e5f6-7890-abcd-ef1234567890
 
 //! Security module for the Copilot Agent Utility
@@ -8,7 +8,11 @@
 //! for remote code execution or other malicious activities.
 
 pub mod allowlist;
+pub mod content;
+pub mod policy;
+pub mod sandbox;
 pub mod sanitizer;
+pub mod schedule;
 pub mod validator;
 pub mod audit;
 
@@ -40,6 +44,7 @@ impl SecurityManager {
 
         // Git operations
         commands.insert("git".to_string());
+        commands.insert("gh".to_string());
 
         // Protocol buffers
         commands.insert("buf".to_string());
@@ -58,6 +63,7 @@ impl SecurityManager {
         commands.insert("python3".to_string());
         commands.insert("pip".to_string());
         commands.insert("pip3".to_string());
+        commands.insert("twine".to_string());
 
         // Node.js development
         commands.insert("node".to_string());
@@ -75,11 +81,47 @@ impl SecurityManager {
         commands.insert("find".to_string());
         commands.insert("grep".to_string());
 
+        // Read-only text filters, for pipelines
+        for filter in ["wc", "head", "tail", "sort", "uniq", "cut", "tr"] {
+            commands.insert(filter.to_string());
+        }
+
         // Development tools
         commands.insert("make".to_string());
+        commands.insert("just".to_string());
         commands.insert("cmake".to_string());
+        commands.insert("ctest".to_string());
         commands.insert("docker".to_string());
         commands.insert("kubectl".to_string());
+        commands.insert("helm".to_string());
+        commands.insert("terraform".to_string());
+        commands.insert("tofu".to_string());
+
+        // PostgreSQL queries (`db`)
+        commands.insert("psql".to_string());
+
+        // JVM builds (`jvm`); gradlew only runs after its wrapper jar is verified
+        for tool in ["gradle", "gradlew", "mvn"] {
+            commands.insert(tool.to_string());
+        }
+
+        // Tool installation (`tools install`)
+        commands.insert("mise".to_string());
+
+        // System package managers (`pkg`); installs also need policy.allow_packages
+        for manager in ["brew", "apt-get", "dnf", "rpm"] {
+            commands.insert(manager.to_string());
+        }
+
+        // User services (`service`); units must be listed in policy.services
+        for manager in ["systemctl", "journalctl", "launchctl"] {
+            commands.insert(manager.to_string());
+        }
+
+        // Cloud CLIs (`cloud`); changes also need policy.allow_cloud_mutations
+        for cli in ["aws", "gcloud", "az"] {
+            commands.insert(cli.to_string());
+        }
 
         // Linting and formatting
         commands.insert("eslint".to_string());
@@ -87,11 +129,15 @@ impl SecurityManager {
         commands.insert("black".to_string());
         commands.insert("flake8".to_string());
         commands.insert("mypy".to_string());
+        commands.insert("ruff".to_string());
         commands.insert("golangci-lint".to_string());
         commands.insert("shellcheck".to_string());
         commands.insert("hadolint".to_string());
         commands.insert("yamllint".to_string());
         commands.insert("markdownlint".to_string());
+        commands.insert("gofmt".to_string());
+        commands.insert("shfmt".to_string());
+        commands.insert("clang-format".to_string());
 
         commands
     }
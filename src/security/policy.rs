@@ -0,0 +1,1251 @@
+// file: src/security/policy.rs
+// version: 1.9.1
+// guid: 3cfcc5cc-2062-4c7b-9fd1-2db80e814c0b
+
+//! Admin-defined command policy
+//!
+//! The `[policy]` section of the configuration lets administrators narrow what the
+//! utility may run beyond the built-in allowlist: whole commands can be allowed or
+//! denied, and argument rules match a regex against the space-joined arguments of a
+//! command. Rules are evaluated in order and the first match decides. The interactive
+//! `shell`, which runs whatever is typed, is denied unless `allow_shell` is set, package
+//! lifecycle scripts, which run whatever a dependency ships, unless `allow_scripts` is, and
+//! privileged containers, which can take over the host, unless `allow_privileged` is. System
+//! package managers (brew, apt-get, dnf) may only install, upgrade or remove packages when
+//! `allow_packages` is set, whether `pkg install` or another command runs them. Service
+//! managers (systemctl, journalctl, launchctl) may only name the user services listed in
+//! `services`, and the aws, gcloud and az CLIs may only describe, list and get resources
+//! unless `allow_cloud_mutations` is set.
+//!
+//! Maintenance windows gate commands by time: a command matching a window, such as
+//! `kubectl apply`, runs only while the window's cron-style schedule matches the current
+//! minute, and is otherwise blocked with the time the next window opens.
+//!
+//! A blocked command is explained by a [`Denial`]: the rules that decided, a trace of every
+//! check with its input, and the changes that would allow the command, so an agent can adapt
+//! instead of retrying. Rules are identified by their configuration path, e.g.
+//! `policy.rules[2]`.
+
+use super::schedule::Schedule;
+use crate::config::{PolicyAction, PolicyConfig};
+use crate::error::{AgentError, Result};
+use crate::error_codes;
+use chrono::{DateTime, Local, NaiveDateTime, SecondsFormat, Utc};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use tracing::debug;
+
+/// Why a command was blocked, and what would allow it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Denial {
+    pub message: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// Rules that blocked the command
+    pub rules: Vec<String>,
+    /// Checks evaluated, in order, up to the one that blocked the command
+    pub trace: Vec<Check>,
+    /// Changes that would each allow the command
+    pub remedies: Vec<Remedy>,
+    /// Whether the interactive shell was refused rather than a command
+    #[serde(skip)]
+    pub shell: bool,
+    /// Whether package lifecycle scripts were refused rather than a command
+    #[serde(skip)]
+    pub scripts: bool,
+    /// Whether a privileged container was refused rather than a command
+    #[serde(skip)]
+    pub privileged: bool,
+    /// Whether changing system packages was refused rather than a command
+    #[serde(skip)]
+    pub packages: bool,
+    /// Whether changing cloud resources was refused rather than a command
+    #[serde(skip)]
+    pub cloud: bool,
+}
+
+/// One evaluated policy expression
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub rule: String,
+    pub expression: String,
+    /// Value the expression was evaluated on
+    pub input: String,
+    pub matched: bool,
+}
+
+/// A change that would allow a blocked command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Remedy {
+    /// Change a configuration key
+    Config { key: String, change: String },
+    /// Pass a command-line flag to this utility
+    Flag { flag: String },
+    /// Select a profile whose policy allows the command
+    Profile { name: String },
+    /// Use arguments an earlier allow rule accepts
+    Arguments { rule: String, pattern: String },
+    /// Wait for a maintenance window; `opens_at` is `None` if none opens in four years
+    Wait {
+        window: String,
+        opens_at: Option<String>,
+    },
+}
+
+impl Denial {
+    /// A denial explained only by `message`
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            command: String::new(),
+            args: Vec::new(),
+            rules: Vec::new(),
+            trace: Vec::new(),
+            remedies: Vec::new(),
+            shell: false,
+            scripts: false,
+            privileged: false,
+            packages: false,
+            cloud: false,
+        }
+    }
+
+    /// A denial by the policy switch `key`, e.g. `policy.allow_shell`, being off
+    fn switched_off(key: &str, message: String) -> Self {
+        let rule = key.to_string();
+        Self {
+            rules: vec![rule.clone()],
+            trace: vec![Check {
+                rule: rule.clone(),
+                expression: key.trim_start_matches("policy.").to_string(),
+                input: "false".to_string(),
+                matched: false,
+            }],
+            remedies: vec![Remedy::Config {
+                key: rule,
+                change: "set to true".to_string(),
+            }],
+            ..Self::new(message)
+        }
+    }
+
+    /// Machine-readable form, printed on stderr when a run ends because of it
+    pub fn to_json(&self) -> Value {
+        json!({
+            "error": "policy_denied",
+            "code": error_codes::POLICY_VIOLATION,
+            "message": self.message,
+            "command": self.command,
+            "args": self.args,
+            "rules": self.rules,
+            "trace": self.trace,
+            "remedies": self.remedies,
+        })
+    }
+}
+
+impl fmt::Display for Denial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Builds the trace of one evaluation
+struct Evaluation<'a> {
+    command: &'a str,
+    args: &'a [String],
+    trace: Vec<Check>,
+}
+
+impl Evaluation<'_> {
+    fn check(&mut self, rule: String, expression: String, input: &str, matched: bool) -> bool {
+        self.trace.push(Check {
+            rule,
+            expression,
+            input: input.to_string(),
+            matched,
+        });
+        matched
+    }
+
+    fn deny(self, message: String, rules: Vec<String>, remedies: Vec<Remedy>) -> AgentError {
+        AgentError::PolicyViolation(Box::new(Denial {
+            message,
+            command: self.command.to_string(),
+            args: self.args.to_vec(),
+            rules,
+            trace: self.trace,
+            remedies,
+            shell: false,
+            scripts: false,
+            privileged: false,
+            packages: false,
+            cloud: false,
+        }))
+    }
+}
+
+/// A compiled policy rule
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    command: String,
+    args: Regex,
+    action: PolicyAction,
+    reason: Option<String>,
+}
+
+/// A compiled maintenance window
+#[derive(Debug, Clone)]
+struct CompiledWindow {
+    name: String,
+    commands: Vec<String>,
+    args: Option<Regex>,
+    text: String,
+    schedule: Schedule,
+    utc: bool,
+    reason: Option<String>,
+}
+
+impl CompiledWindow {
+    fn applies_to(&self, command: &str, joined: &str) -> bool {
+        self.commands.iter().any(|c| c == command)
+            && self
+                .args
+                .as_ref()
+                .map_or(true, |args| args.is_match(joined))
+    }
+
+    /// `now` in the time zone the schedule is read in
+    fn clock(&self, now: DateTime<Utc>) -> NaiveDateTime {
+        if self.utc {
+            now.naive_utc()
+        } else {
+            now.with_timezone(&Local).naive_local()
+        }
+    }
+}
+
+/// Compiled command policy
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    allow_commands: Vec<String>,
+    deny_commands: Vec<String>,
+    rules: Vec<CompiledRule>,
+    allow_shell: bool,
+    allow_scripts: bool,
+    allow_privileged: bool,
+    allow_packages: bool,
+    allow_cloud_mutations: bool,
+    services: Vec<String>,
+    windows: Vec<CompiledWindow>,
+}
+
+impl Policy {
+    /// Compile the policy from configuration, rejecting invalid rule patterns
+    pub fn from_config(config: &PolicyConfig) -> Result<Self> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                let args = Regex::new(&rule.args).map_err(|e| {
+                    AgentError::config(format!(
+                        "Invalid policy pattern '{}' for {}: {}",
+                        rule.args, rule.command, e
+                    ))
+                })?;
+                Ok(CompiledRule {
+                    command: rule.command.clone(),
+                    args,
+                    action: rule.action,
+                    reason: rule.reason.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let windows = config
+            .windows
+            .iter()
+            .map(|window| {
+                let invalid = |e: String| {
+                    AgentError::config(format!(
+                        "Invalid maintenance window '{}': {}",
+                        window.name, e
+                    ))
+                };
+                Ok(CompiledWindow {
+                    name: window.name.clone(),
+                    commands: window.commands.clone(),
+                    args: window
+                        .args
+                        .as_deref()
+                        .map(Regex::new)
+                        .transpose()
+                        .map_err(|e| invalid(e.to_string()))?,
+                    text: window.schedule.clone(),
+                    schedule: Schedule::parse(&window.schedule).map_err(invalid)?,
+                    utc: window.utc,
+                    reason: window.reason.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            allow_commands: config.allow_commands.clone(),
+            deny_commands: config.deny_commands.clone(),
+            rules,
+            allow_shell: config.allow_shell,
+            allow_scripts: config.allow_scripts,
+            allow_privileged: config.allow_privileged,
+            allow_packages: config.allow_packages,
+            allow_cloud_mutations: config.allow_cloud_mutations,
+            services: config.services.clone(),
+            windows,
+        })
+    }
+
+    /// Whether the interactive shell may be opened
+    pub fn evaluate_shell(&self) -> Result<()> {
+        if !self.allow_shell {
+            return Err(AgentError::PolicyViolation(Box::new(Denial {
+                command: "shell".to_string(),
+                shell: true,
+                ..Denial::switched_off(
+                    "policy.allow_shell",
+                    "the interactive shell is disabled; set policy.allow_shell = true to allow it"
+                        .to_string(),
+                )
+            })));
+        }
+        Ok(())
+    }
+
+    /// Whether `command`, a package manager, may run the lifecycle scripts of the packages it
+    /// installs
+    pub fn evaluate_scripts(&self, command: &str) -> Result<()> {
+        if !self.allow_scripts {
+            return Err(AgentError::PolicyViolation(Box::new(Denial {
+                command: command.to_string(),
+                scripts: true,
+                ..Denial::switched_off(
+                    "policy.allow_scripts",
+                    format!(
+                        "{} lifecycle scripts are disabled; set policy.allow_scripts = true to allow them",
+                        command
+                    ),
+                )
+            })));
+        }
+        Ok(())
+    }
+
+    /// Whether `command`, a container runtime, may start privileged containers
+    pub fn evaluate_privileged(&self, command: &str) -> Result<()> {
+        if !self.allow_privileged {
+            return Err(AgentError::PolicyViolation(Box::new(Denial {
+                command: command.to_string(),
+                privileged: true,
+                ..Denial::switched_off(
+                    "policy.allow_privileged",
+                    format!(
+                        "privileged {} containers are disabled; set policy.allow_privileged = true to allow them",
+                        command
+                    ),
+                )
+            })));
+        }
+        Ok(())
+    }
+
+    /// Whether `command`, a system package manager, may install, upgrade or remove packages
+    pub fn evaluate_packages(&self, command: &str) -> Result<()> {
+        if !self.allow_packages {
+            return Err(AgentError::PolicyViolation(Box::new(Denial {
+                command: command.to_string(),
+                packages: true,
+                ..Denial::switched_off(
+                    "policy.allow_packages",
+                    format!(
+                        "changing system packages with {} is disabled; set policy.allow_packages = true to allow it",
+                        command
+                    ),
+                )
+            })));
+        }
+        Ok(())
+    }
+
+    /// Whether `command`, a cloud CLI, may create, change or delete cloud resources
+    pub fn evaluate_cloud_mutations(&self, command: &str) -> Result<()> {
+        if !self.allow_cloud_mutations {
+            return Err(AgentError::PolicyViolation(Box::new(Denial {
+                command: command.to_string(),
+                cloud: true,
+                ..Denial::switched_off(
+                    "policy.allow_cloud_mutations",
+                    format!(
+                        "{} may only describe, list and get resources; set policy.allow_cloud_mutations = true to allow changes",
+                        command
+                    ),
+                )
+            })));
+        }
+        Ok(())
+    }
+
+    /// Evaluate what `denial` refused against this policy, e.g. another profile's
+    pub fn reevaluate(&self, denial: &Denial) -> Result<()> {
+        if denial.shell {
+            self.evaluate_shell()
+        } else if denial.scripts {
+            self.evaluate_scripts(&denial.command)
+        } else if denial.privileged {
+            self.evaluate_privileged(&denial.command)
+        } else if denial.packages {
+            self.evaluate_packages(&denial.command)
+        } else if denial.cloud {
+            self.evaluate_cloud_mutations(&denial.command)
+        } else {
+            self.evaluate(&denial.command, &denial.args)
+        }
+    }
+
+    /// Evaluate a command against the policy
+    pub fn evaluate(&self, command: &str, args: &[String]) -> Result<()> {
+        self.evaluate_at(command, args, Utc::now())
+    }
+
+    /// Evaluate a command against the policy as if it ran at `now`
+    pub fn evaluate_at(&self, command: &str, args: &[String], now: DateTime<Utc>) -> Result<()> {
+        let mut evaluation = Evaluation {
+            command,
+            args,
+            trace: Vec::new(),
+        };
+
+        if !self.deny_commands.is_empty() {
+            let key = "policy.deny_commands".to_string();
+            let expression = format!("command in [{}]", self.deny_commands.join(", "));
+            let denied = self.deny_commands.iter().any(|c| c == command);
+            if evaluation.check(key.clone(), expression, command, denied) {
+                return Err(evaluation.deny(
+                    format!("command '{}' is denied by policy", command),
+                    vec![key.clone()],
+                    vec![Remedy::Config {
+                        key,
+                        change: format!("remove '{}'", command),
+                    }],
+                ));
+            }
+        }
+
+        if !self.allow_commands.is_empty() {
+            let key = "policy.allow_commands".to_string();
+            let expression = format!("command in [{}]", self.allow_commands.join(", "));
+            let allowed = self.allow_commands.iter().any(|c| c == command);
+            if !evaluation.check(key.clone(), expression, command, allowed) {
+                return Err(evaluation.deny(
+                    format!("command '{}' is not in the policy allow list", command),
+                    vec![key.clone()],
+                    vec![Remedy::Config {
+                        key,
+                        change: format!("add '{}'", command),
+                    }],
+                ));
+            }
+        }
+
+        if changes_packages(command, args) {
+            self.evaluate_packages(command)?;
+        }
+        if cloud_mutates(command, args) {
+            self.evaluate_cloud_mutations(command)?;
+        }
+
+        let units = service_units(command, args);
+        if !units.is_empty() {
+            let key = "policy.services".to_string();
+            let expression = format!("unit in [{}]", self.services.join(", "));
+            for unit in units {
+                let allowed = self
+                    .services
+                    .iter()
+                    .any(|pattern| unit_matches(pattern, unit));
+                if !evaluation.check(key.clone(), expression.clone(), unit, allowed) {
+                    return Err(evaluation.deny(
+                        format!("service '{}' is not in policy.services", unit),
+                        vec![key.clone()],
+                        vec![Remedy::Config {
+                            key,
+                            change: format!("add '{}'", unit),
+                        }],
+                    ));
+                }
+            }
+        }
+
+        // Rules for other commands are left out of the trace
+        let joined = rule_args(command, args).join(" ");
+        let applicable = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.command == "*" || rule.command == command);
+        let mut matched = None;
+        for (i, rule) in applicable {
+            let expression = format!("args =~ '{}'", rule.args);
+            let is_match = rule.args.is_match(&joined);
+            if evaluation.check(rule_key(i), expression, &joined, is_match) {
+                matched = Some((i, rule));
+                break;
+            }
+        }
+
+        match matched {
+            Some((i, rule)) if rule.action == PolicyAction::Deny => {
+                let message = match &rule.reason {
+                    Some(reason) => format!("{} {}: {}", command, joined, reason),
+                    None => format!("{} {} matches deny rule '{}'", command, joined, rule.args),
+                };
+                // An earlier allow rule would have decided first
+                let mut remedies: Vec<Remedy> = self.rules[..i]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, earlier)| {
+                        earlier.action == PolicyAction::Allow
+                            && (earlier.command == "*" || earlier.command == command)
+                    })
+                    .map(|(j, earlier)| Remedy::Arguments {
+                        rule: rule_key(j),
+                        pattern: earlier.args.to_string(),
+                    })
+                    .collect();
+                remedies.push(Remedy::Config {
+                    key: rule_key(i),
+                    change: "remove the rule or narrow its args pattern".to_string(),
+                });
+                Err(evaluation.deny(message, vec![rule_key(i)], remedies))
+            }
+            Some((_, rule)) => {
+                debug!("Policy rule '{}' allows {} {}", rule.args, command, joined);
+                self.check_windows(evaluation, &joined, now)
+            }
+            None => self.check_windows(evaluation, &joined, now),
+        }
+    }
+
+    /// Block a command that maintenance windows apply to unless one of them is open
+    fn check_windows(
+        &self,
+        mut evaluation: Evaluation,
+        joined: &str,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let command = evaluation.command;
+        let windows: Vec<(usize, &CompiledWindow)> = self
+            .windows
+            .iter()
+            .enumerate()
+            .filter(|(_, window)| window.applies_to(command, joined))
+            .collect();
+        if windows.is_empty() {
+            return Ok(());
+        }
+        for (i, window) in &windows {
+            let clock = window.clock(now);
+            let expression = format!(
+                "schedule '{}'{} matches",
+                window.text,
+                if window.utc { " UTC" } else { "" }
+            );
+            let input = clock.format("%a %Y-%m-%d %H:%M").to_string();
+            let open = window.schedule.matches(clock);
+            if evaluation.check(window_key(*i), expression, &input, open) {
+                debug!(
+                    "Maintenance window '{}' allows {} {}",
+                    window.name, command, joined
+                );
+                return Ok(());
+            }
+        }
+
+        // The window that opens first, and the wait until it does
+        let next = windows
+            .iter()
+            .filter_map(|(_, window)| {
+                let clock = window.clock(now);
+                let opens = window.schedule.next_match(clock)?;
+                Some((opens - clock, opens, *window))
+            })
+            .min_by_key(|(wait, _, _)| *wait);
+        let window = next.map_or(windows[0].1, |(_, _, window)| window);
+        let mut message = format!(
+            "{} is only allowed during maintenance window '{}' ({}{}); ",
+            format!("{} {}", command, joined).trim_end(),
+            window.name,
+            window.text,
+            if window.utc { " UTC" } else { "" }
+        );
+        match next {
+            Some((wait, opens, _)) => message.push_str(&format!(
+                "the next window opens {}{} (in {})",
+                opens.format("%a %Y-%m-%d %H:%M"),
+                if window.utc { " UTC" } else { "" },
+                format_wait(wait)
+            )),
+            None => message.push_str("no window opens in the next four years"),
+        }
+        if let Some(reason) = &window.reason {
+            message.push_str(&format!(": {}", reason));
+        }
+        let remedies = vec![Remedy::Wait {
+            window: window.name.clone(),
+            opens_at: next
+                .map(|(wait, _, _)| (now + wait).to_rfc3339_opts(SecondsFormat::Secs, true)),
+        }];
+        let rules = windows.iter().map(|(i, _)| window_key(*i)).collect();
+        Err(evaluation.deny(message, rules, remedies))
+    }
+}
+
+/// The arguments rules and windows match against: git's global options such as
+/// `-C <dir>` and `-c <key>=<value>` come before the subcommand, so they are skipped
+/// to keep patterns anchored on the subcommand (`^push`) from being sidestepped
+fn rule_args<'a>(command: &str, args: &'a [String]) -> &'a [String] {
+    if command != "git" {
+        return args;
+    }
+    let mut args = args;
+    while let Some(first) = args.first() {
+        let skip = match first.as_str() {
+            "-C" | "-c" | "--git-dir" | "--work-tree" | "--namespace" | "--config-env" => 2,
+            "-p"
+            | "-P"
+            | "--paginate"
+            | "--no-pager"
+            | "--bare"
+            | "--no-replace-objects"
+            | "--literal-pathspecs"
+            | "--glob-pathspecs"
+            | "--noglob-pathspecs"
+            | "--icase-pathspecs"
+            | "--no-optional-locks" => 1,
+            option
+                if option.starts_with("--git-dir=")
+                    || option.starts_with("--work-tree=")
+                    || option.starts_with("--namespace=")
+                    || option.starts_with("--config-env=") =>
+            {
+                1
+            }
+            _ => break,
+        };
+        args = &args[skip.min(args.len())..];
+    }
+    args
+}
+
+fn rule_key(index: usize) -> String {
+    format!("policy.rules[{}]", index)
+}
+
+fn window_key(index: usize) -> String {
+    format!("policy.windows[{}]", index)
+}
+
+/// `45m`, `5h 20m` or `2d 16h`
+fn format_wait(wait: chrono::Duration) -> String {
+    let minutes = wait.num_minutes().max(1);
+    match minutes {
+        0..=59 => format!("{}m", minutes),
+        60..=1439 => format!("{}h {}m", minutes / 60, minutes % 60),
+        _ => format!("{}d {}h", minutes / 1440, minutes % 1440 / 60),
+    }
+}
+
+/// Whether `args` make the package manager `command` install, upgrade or remove packages
+pub fn changes_packages(command: &str, args: &[String]) -> bool {
+    let changing: &[&str] = match command {
+        "brew" => &[
+            "install",
+            "reinstall",
+            "upgrade",
+            "uninstall",
+            "remove",
+            "rm",
+            "tap",
+        ],
+        "apt-get" => &[
+            "install",
+            "reinstall",
+            "remove",
+            "purge",
+            "upgrade",
+            "dist-upgrade",
+            "full-upgrade",
+            "autoremove",
+            "build-dep",
+        ],
+        "dnf" => &[
+            "install",
+            "reinstall",
+            "remove",
+            "erase",
+            "upgrade",
+            "update",
+            "downgrade",
+            "autoremove",
+            "swap",
+            "distro-sync",
+        ],
+        _ => return false,
+    };
+    args.iter()
+        .find(|arg| !arg.starts_with('-'))
+        .is_some_and(|subcommand| changing.contains(&subcommand.as_str()))
+}
+
+/// Options of the cloud CLIs that may come before the command and take a value
+const CLOUD_VALUE_OPTIONS: &[&str] = &[
+    // aws
+    "--region",
+    "--profile",
+    "--output",
+    "--endpoint-url",
+    "--query",
+    "--color",
+    "--ca-bundle",
+    "--cli-read-timeout",
+    "--cli-connect-timeout",
+    "--cli-binary-format",
+    // gcloud
+    "--project",
+    "--account",
+    "--configuration",
+    "--format",
+    "--verbosity",
+    "--impersonate-service-account",
+    "--billing-project",
+    // az
+    "--subscription",
+    "-o",
+];
+
+/// Options of the cloud CLIs that may come before the command and take no value
+const CLOUD_SWITCHES: &[&str] = &[
+    "--debug",
+    "--no-verify-ssl",
+    "--no-paginate",
+    "--no-sign-request",
+    "--no-cli-pager",
+    "--quiet",
+    "-q",
+    "--log-http",
+    "--verbose",
+    "--only-show-errors",
+    "--help",
+    "-h",
+    "--version",
+];
+
+/// gcloud and az commands that change resources or local credentials
+const CLOUD_MUTATING_VERBS: &[&str] = &[
+    "create", "delete", "update", "set", "add", "remove", "deploy", "start", "stop", "restart",
+    "reset", "resize", "import", "export", "move", "patch", "apply", "enable", "disable", "attach",
+    "detach", "ssh", "scp", "cancel", "rollback", "purge", "upgrade", "login", "logout",
+    "activate", "revoke", "submit", "publish", "rotate", "restore", "invoke", "exec", "copy", "cp",
+    "mv", "rm", "sync", "generate",
+];
+
+/// Whether `args` make the cloud CLI `command` (aws, gcloud or az) do anything but describe,
+/// list, get or show resources
+///
+/// aws takes a service and an operation; gcloud and az take command groups followed by a verb.
+/// Options before the command must be known global ones, since an unknown option could take
+/// the next word as its value. Commands that cannot be classified count as mutations.
+pub fn cloud_mutates(command: &str, args: &[String]) -> bool {
+    if !matches!(command, "aws" | "gcloud" | "az") {
+        return false;
+    }
+    let reads = |word: &str| {
+        matches!(
+            word,
+            "ls" | "list" | "describe" | "get" | "show" | "help" | "version"
+        ) || ["describe-", "list-", "get-", "show-"]
+            .iter()
+            .any(|prefix| word.starts_with(prefix))
+    };
+    let mut words = 0;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with('-') {
+            let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+            if CLOUD_VALUE_OPTIONS.contains(&name) {
+                if !arg.contains('=') {
+                    args.next();
+                }
+            } else if !CLOUD_SWITCHES.contains(&name) {
+                return true;
+            }
+            continue;
+        }
+        if command == "aws" {
+            // The service, then the operation
+            if words == 1 {
+                return !reads(arg);
+            }
+        } else if reads(arg) {
+            return false;
+        } else if CLOUD_MUTATING_VERBS.contains(&arg.as_str())
+            || ["set-", "add-", "remove-", "create-", "delete-", "update-"]
+                .iter()
+                .any(|prefix| arg.starts_with(prefix))
+        {
+            return true;
+        }
+        words += 1;
+    }
+    words > 0
+}
+
+/// The services a service manager `command` acts on: the arguments after systemctl's
+/// subcommand, journalctl's `--unit`s and the labels of launchctl's `gui/<uid>/<label>`
+/// targets
+pub fn service_units<'a>(command: &str, args: &'a [String]) -> Vec<&'a str> {
+    match command {
+        "systemctl" => args
+            .iter()
+            .filter(|arg| !arg.starts_with('-'))
+            .skip(1)
+            .map(String::as_str)
+            .collect(),
+        "journalctl" => {
+            let mut units = Vec::new();
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "-u" | "--unit" | "--user-unit" => {
+                        units.extend(args.next().map(String::as_str))
+                    }
+                    _ => units.extend(
+                        arg.strip_prefix("--unit=")
+                            .or_else(|| arg.strip_prefix("--user-unit="))
+                            .or_else(|| arg.strip_prefix("-u")),
+                    ),
+                }
+            }
+            units
+        }
+        "launchctl" => args
+            .iter()
+            .filter_map(|arg| Some(arg.strip_prefix("gui/")?.split_once('/')?.1))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether `unit` matches `pattern`, where `*` matches any run of characters; the
+/// `.service` suffix is optional on both
+fn unit_matches(pattern: &str, unit: &str) -> bool {
+    let pattern = pattern.strip_suffix(".service").unwrap_or(pattern);
+    let unit = unit.strip_suffix(".service").unwrap_or(unit);
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = unit.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PolicyRule;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn rule(command: &str, pattern: &str, action: PolicyAction) -> PolicyRule {
+        PolicyRule {
+            command: command.to_string(),
+            args: pattern.to_string(),
+            action,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn test_command_allow_and_deny_lists() {
+        let policy = Policy::from_config(&PolicyConfig {
+            allow_commands: vec!["git".to_string(), "cargo".to_string()],
+            deny_commands: vec!["cargo".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(policy.evaluate("git", &args(&["status"])).is_ok());
+        assert!(matches!(
+            policy.evaluate("cargo", &args(&["build"])),
+            Err(AgentError::PolicyViolation(_))
+        ));
+        assert!(policy.evaluate("npm", &args(&["install"])).is_err());
+        assert!(policy.evaluate_shell().is_err());
+    }
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let policy = Policy::from_config(&PolicyConfig {
+            rules: vec![
+                rule("git", r"^push .*--force-with-lease", PolicyAction::Allow),
+                rule(
+                    "git",
+                    r"^push\b.*(--force|-f)\b.*\bmain\b",
+                    PolicyAction::Deny,
+                ),
+                rule("rm", r"-rf? /$", PolicyAction::Deny),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(policy
+            .evaluate("git", &args(&["push", "--force", "origin", "main"]))
+            .is_err());
+        assert!(policy
+            .evaluate("git", &args(&["push", "--force", "origin", "feature"]))
+            .is_ok());
+        assert!(policy
+            .evaluate(
+                "git",
+                &args(&["push", "--force-with-lease", "origin", "main"])
+            )
+            .is_ok());
+        assert!(policy.evaluate("rm", &args(&["-rf", "/"])).is_err());
+    }
+
+    #[test]
+    fn test_rules_skip_git_global_options() {
+        let policy = Policy::from_config(&PolicyConfig {
+            rules: vec![rule(
+                "git",
+                r"^push\b.*(--force|-f)\b.*\bmain\b",
+                PolicyAction::Deny,
+            )],
+            ..Default::default()
+        })
+        .unwrap();
+
+        for prefix in [
+            &["-C", "."][..],
+            &["-c", "user.name=x"][..],
+            &["--no-pager", "-C", "sub", "--git-dir=.git"][..],
+        ] {
+            let mut argv = args(prefix);
+            argv.extend(args(&["push", "--force", "origin", "main"]));
+            assert!(policy.evaluate("git", &argv).is_err(), "{:?}", argv);
+        }
+        assert!(policy
+            .evaluate("git", &args(&["-C", ".", "push", "origin", "main"]))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_maintenance_windows() {
+        use crate::config::MaintenanceWindow;
+        use chrono::TimeZone;
+
+        let window = |name: &str, schedule: &str| MaintenanceWindow {
+            name: name.to_string(),
+            commands: vec!["kubectl".to_string()],
+            args: Some(r"^(apply|delete)\b".to_string()),
+            schedule: schedule.to_string(),
+            utc: true,
+            reason: Some("change freeze outside deploy hours".to_string()),
+        };
+        let policy = Policy::from_config(&PolicyConfig {
+            windows: vec![
+                window("weekdays", "* 9-16 * * mon-thu"),
+                window("saturday", "0-29 10 * * sat"),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+        // 2024-05-09 is a Thursday
+        let at = |day, hour, minute| Utc.with_ymd_and_hms(2024, 5, day, hour, minute, 0).unwrap();
+        let apply = args(&["apply", "-f", "prod.yaml"]);
+
+        assert!(policy.evaluate_at("kubectl", &apply, at(9, 16, 59)).is_ok());
+        assert!(policy
+            .evaluate_at("kubectl", &args(&["get", "pods"]), at(10, 3, 0))
+            .is_ok());
+        assert!(policy
+            .evaluate_at("kubectl", &apply, at(11, 10, 15))
+            .is_ok());
+
+        let Err(AgentError::PolicyViolation(denial)) =
+            policy.evaluate_at("kubectl", &apply, at(9, 17, 0))
+        else {
+            panic!("kubectl apply allowed outside its windows");
+        };
+        assert_eq!(
+            denial.message,
+            "kubectl apply -f prod.yaml is only allowed during maintenance window 'saturday' \
+             (0-29 10 * * sat UTC); the next window opens Sat 2024-05-11 10:00 UTC (in 1d 17h): \
+             change freeze outside deploy hours"
+        );
+        assert_eq!(denial.rules, vec!["policy.windows[0]", "policy.windows[1]"]);
+        assert_eq!(denial.trace[1].input, "Thu 2024-05-09 17:00");
+        assert_eq!(
+            denial.remedies,
+            vec![Remedy::Wait {
+                window: "saturday".to_string(),
+                opens_at: Some("2024-05-11T10:00:00Z".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_denial_explains_rules() {
+        let policy = Policy::from_config(&PolicyConfig {
+            allow_commands: vec!["git".to_string(), "rm".to_string()],
+            rules: vec![
+                rule("rm", r"-rf? /$", PolicyAction::Deny),
+                rule("git", r"^push .*--force-with-lease", PolicyAction::Allow),
+                rule("git", r"^push\b.*--force\b", PolicyAction::Deny),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let Err(AgentError::PolicyViolation(denial)) =
+            policy.evaluate("git", &args(&["push", "--force", "origin"]))
+        else {
+            panic!("force push allowed");
+        };
+        assert_eq!(denial.rules, vec!["policy.rules[2]"]);
+        // The rm rule does not apply to git and is left out
+        let trace: Vec<(&str, bool)> = denial
+            .trace
+            .iter()
+            .map(|check| (check.rule.as_str(), check.matched))
+            .collect();
+        assert_eq!(
+            trace,
+            vec![
+                ("policy.allow_commands", true),
+                ("policy.rules[1]", false),
+                ("policy.rules[2]", true),
+            ]
+        );
+        assert_eq!(denial.trace[2].input, "push --force origin");
+        assert_eq!(
+            denial.remedies[0],
+            Remedy::Arguments {
+                rule: "policy.rules[1]".to_string(),
+                pattern: "^push .*--force-with-lease".to_string(),
+            }
+        );
+        assert_eq!(denial.to_json()["remedies"][1]["kind"], "config");
+
+        let Err(AgentError::PolicyViolation(denial)) = policy.evaluate("cargo", &[]) else {
+            panic!("cargo allowed");
+        };
+        assert_eq!(
+            denial.remedies,
+            vec![Remedy::Config {
+                key: "policy.allow_commands".to_string(),
+                change: "add 'cargo'".to_string(),
+            }]
+        );
+        let Err(AgentError::PolicyViolation(denial)) = policy.evaluate_shell() else {
+            panic!("shell allowed");
+        };
+        assert!(policy.reevaluate(&denial).is_err());
+        let open = Policy::from_config(&PolicyConfig {
+            allow_shell: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(open.reevaluate(&denial).is_ok());
+
+        let Err(AgentError::PolicyViolation(denial)) = policy.evaluate_scripts("npm") else {
+            panic!("lifecycle scripts allowed");
+        };
+        assert_eq!(denial.rules, ["policy.allow_scripts"]);
+        assert!(open.reevaluate(&denial).is_err());
+        let scripts = Policy::from_config(&PolicyConfig {
+            allow_scripts: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(scripts.reevaluate(&denial).is_ok());
+
+        let Err(AgentError::PolicyViolation(denial)) = policy.evaluate_privileged("docker") else {
+            panic!("privileged containers allowed");
+        };
+        assert_eq!(denial.rules, ["policy.allow_privileged"]);
+        assert!(scripts.reevaluate(&denial).is_err());
+        let privileged = Policy::from_config(&PolicyConfig {
+            allow_privileged: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(privileged.reevaluate(&denial).is_ok());
+
+        assert!(privileged
+            .evaluate("brew", &args(&["list", "--versions"]))
+            .is_ok());
+        let Err(AgentError::PolicyViolation(denial)) =
+            privileged.evaluate("apt-get", &args(&["-y", "install", "jq"]))
+        else {
+            panic!("package install allowed");
+        };
+        assert_eq!(denial.rules, ["policy.allow_packages"]);
+        assert!(privileged.reevaluate(&denial).is_err());
+        let packages = Policy::from_config(&PolicyConfig {
+            allow_packages: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(packages.reevaluate(&denial).is_ok());
+        assert!(packages
+            .evaluate("dnf", &args(&["install", "-y", "jq"]))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_cloud_mutations_need_switch() {
+        for (command, mutates) in [
+            (
+                "aws ec2 describe-instances --filters Name=tag:env,Values=dev",
+                false,
+            ),
+            (
+                "aws --region eu-west-1 --output json sts get-caller-identity",
+                false,
+            ),
+            ("aws s3 ls s3://logs/list-", false),
+            ("aws s3 rm s3://logs/app.log", true),
+            ("aws ec2 terminate-instances --instance-ids list-1", true),
+            ("aws --frobnicate s3 ls", true),
+            ("gcloud compute instances list --zone us-central1-a", false),
+            ("gcloud --project dev config get-value account", false),
+            ("gcloud compute instances delete list", true),
+            ("gcloud run deploy api --source .", true),
+            ("gcloud run services list", false),
+            ("az account show", false),
+            ("az lock list", false),
+            ("az --subscription dev vm list -g rg", false),
+            ("az group delete --name rg", true),
+            ("az storage", true),
+            ("git push", false),
+        ] {
+            let (command, rest) = command.split_once(' ').unwrap();
+            let args = args(&rest.split(' ').collect::<Vec<_>>());
+            assert_eq!(
+                cloud_mutates(command, &args),
+                mutates,
+                "{} {:?}",
+                command,
+                args
+            );
+        }
+
+        let policy = Policy::from_config(&PolicyConfig::default()).unwrap();
+        assert!(policy.evaluate("az", &args(&["vm", "list"])).is_ok());
+        let Err(AgentError::PolicyViolation(denial)) =
+            policy.evaluate("az", &args(&["vm", "delete", "--name", "build"]))
+        else {
+            panic!("cloud mutation allowed");
+        };
+        assert_eq!(denial.rules, ["policy.allow_cloud_mutations"]);
+        let cloud = Policy::from_config(&PolicyConfig {
+            allow_cloud_mutations: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(cloud.reevaluate(&denial).is_ok());
+    }
+
+    #[test]
+    fn test_services_must_be_listed() {
+        let policy = Policy::from_config(&PolicyConfig {
+            services: vec!["api-dev".to_string(), "worker@*.service".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        for (command, allowed) in [
+            ("systemctl --user restart api-dev.service", true),
+            ("systemctl --user stop --no-block worker@2", true),
+            ("systemctl --user stop api-dev dbus", false),
+            ("journalctl --user --unit api-dev --lines 50", true),
+            ("journalctl --user -uworker@1.service", true),
+            ("journalctl --user --unit=pipewire", false),
+            ("launchctl kickstart -k gui/501/api-dev", true),
+            ("launchctl kill SIGTERM gui/501/com.apple.Finder", false),
+            ("systemctl --user status", true),
+        ] {
+            let (command, rest) = command.split_once(' ').unwrap();
+            let args = args(&rest.split(' ').collect::<Vec<_>>());
+            assert_eq!(
+                policy.evaluate(command, &args).is_ok(),
+                allowed,
+                "{} {:?}",
+                command,
+                args
+            );
+        }
+
+        let Err(AgentError::PolicyViolation(denial)) =
+            policy.evaluate("systemctl", &args(&["--user", "stop", "dbus"]))
+        else {
+            panic!("dbus stopped");
+        };
+        assert_eq!(denial.rules, ["policy.services"]);
+        assert!(
+            matches!(&denial.remedies[0], Remedy::Config { change, .. } if change == "add 'dbus'")
+        );
+        assert!(unit_matches("*-dev", "api-dev"));
+        assert!(!unit_matches("api", "api-dev"));
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_config_error() {
+        let config = PolicyConfig {
+            rules: vec![rule("git", "(", PolicyAction::Deny)],
+            ..Default::default()
+        };
+        assert!(matches!(
+            Policy::from_config(&config),
+            Err(AgentError::Config(_))
+        ));
+
+        let config = PolicyConfig {
+            windows: vec![crate::config::MaintenanceWindow {
+                name: "deploys".to_string(),
+                commands: vec!["kubectl".to_string()],
+                args: None,
+                schedule: "* 9-17".to_string(),
+                utc: false,
+                reason: None,
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            Policy::from_config(&config),
+            Err(AgentError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,242 @@
+// file: src/security/sandbox.rs
+// version: 1.2.1
+// guid: 1b7b0959-5940-4548-9a37-381702def35a
+
+//! Filesystem sandbox roots
+//!
+//! When `safety.workspace_roots` is set, every path an operation reads or writes must lie
+//! inside one of the roots. Paths are canonicalized first, so `..` segments and symlinks
+//! cannot be used to escape; paths that do not exist yet are resolved through their
+//! nearest existing ancestor. An empty root list disables the check, as does the
+//! `--allow-outside-workspace` flag.
+
+use crate::error::{AgentError, Result};
+use std::path::{Component, Path, PathBuf};
+use tracing::debug;
+
+/// Canonicalized workspace roots that paths are checked against
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    roots: Vec<PathBuf>,
+    /// Directory relative paths are resolved against
+    base: PathBuf,
+    allow_outside: bool,
+}
+
+impl Sandbox {
+    /// Build a sandbox from configured roots; relative roots are resolved against `base`
+    pub fn new(roots: &[PathBuf], base: &Path) -> Result<Self> {
+        let base = canonicalize_lenient(base);
+        let roots = roots
+            .iter()
+            .map(|root| {
+                let root = base.join(expand_home(root));
+                root.canonicalize().map_err(|e| {
+                    AgentError::config(format!(
+                        "Workspace root {} cannot be used: {}",
+                        root.display(),
+                        e
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            roots,
+            base,
+            allow_outside: false,
+        })
+    }
+
+    /// Permit paths outside the roots (`--allow-outside-workspace`)
+    pub fn set_allow_outside(&mut self, allow: bool) {
+        self.allow_outside = allow;
+    }
+
+    /// Whether `--allow-outside-workspace` is in effect
+    pub fn allows_outside(&self) -> bool {
+        self.allow_outside
+    }
+
+    /// The canonicalized roots; empty when paths are not restricted
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// The canonicalized directory relative paths are resolved against
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Whether paths are being checked at all
+    pub fn is_enforced(&self) -> bool {
+        !self.roots.is_empty() && !self.allow_outside
+    }
+
+    /// Canonicalize a path and refuse it if it lies outside every root
+    pub fn check_path(&self, path: &Path) -> Result<PathBuf> {
+        let resolved = canonicalize_lenient(&self.base.join(expand_home(path)));
+        if !self.is_enforced() || self.roots.iter().any(|root| resolved.starts_with(root)) {
+            return Ok(resolved);
+        }
+        Err(AgentError::security(format!(
+            "{} is outside the workspace roots ({}); pass --allow-outside-workspace to permit it",
+            path.display(),
+            self.roots
+                .iter()
+                .map(|r| r.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )))
+    }
+
+    /// Check every argument of an external command that looks like a path
+    ///
+    /// Relative arguments without `..` cannot leave the base directory unless they name an
+    /// existing symlink, so only absolute paths, `~` paths, paths with `..` and existing
+    /// paths are resolved. Option values (`--file=PATH`, `-oPATH`) are checked too; URLs are
+    /// not.
+    pub fn check_args(&self, args: &[String]) -> Result<()> {
+        if !self.is_enforced() {
+            return Ok(());
+        }
+        for arg in args {
+            let candidate = match arg.strip_prefix('-') {
+                Some(option) => match option.split_once('=') {
+                    Some((_, value)) => value,
+                    // A short option's value can follow its letter (`-o/tmp/out`)
+                    None if !option.starts_with('-') => option
+                        .char_indices()
+                        .nth(1)
+                        .map_or("", |(i, _)| &option[i..]),
+                    None => continue,
+                },
+                None => arg.as_str(),
+            };
+            if candidate.is_empty() || candidate.contains("://") {
+                continue;
+            }
+            let path = Path::new(candidate);
+            let path_like = path.is_absolute()
+                || candidate.starts_with('~')
+                || path.components().any(|c| c == Component::ParentDir)
+                || self.base.join(path).symlink_metadata().is_ok();
+            if path_like {
+                debug!(
+                    "Checking path argument {} against the workspace roots",
+                    candidate
+                );
+                self.check_path(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Expand a leading `~` to the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).as_ref())
+}
+
+/// Canonicalize a path that may not exist: the nearest existing ancestor is canonicalized
+/// and the remaining components are appended with `.` and `..` applied lexically
+pub fn canonicalize_lenient(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+
+    let mut existing = path.to_path_buf();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.file_name(), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                rest.push(name.to_os_string());
+                existing = parent.to_path_buf();
+            }
+            // Only `..` or the root remain; resolve them lexically below
+            _ => break,
+        }
+    }
+
+    let mut resolved = existing.canonicalize().unwrap_or(existing);
+    for name in rest.into_iter().rev() {
+        resolved.push(name);
+    }
+
+    // `file_name` skips `..`, so any left over from a non-existent prefix are applied here
+    let mut normalized = PathBuf::new();
+    for component in resolved.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_path() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("workspace");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(dir.path().join("secret.txt"), "x").unwrap();
+
+        let mut sandbox = Sandbox::new(&[PathBuf::from(".")], &root).unwrap();
+        assert!(sandbox.check_path(Path::new("src/lib.rs")).is_ok());
+        assert!(sandbox.check_path(Path::new("new/dir/file.txt")).is_ok());
+        assert!(sandbox.check_path(Path::new("../secret.txt")).is_err());
+        assert!(sandbox
+            .check_path(Path::new("src/../../secret.txt"))
+            .is_err());
+        assert!(sandbox.check_path(Path::new("missing/../../x")).is_err());
+        assert!(sandbox.check_path(&dir.path().join("secret.txt")).is_err());
+
+        sandbox.set_allow_outside(true);
+        assert!(sandbox.check_path(Path::new("../secret.txt")).is_ok());
+
+        let open = Sandbox::new(&[], &root).unwrap();
+        assert!(!open.is_enforced());
+        assert!(open.check_path(Path::new("../secret.txt")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_and_args() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("workspace");
+        fs::create_dir_all(&root).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("escape")).unwrap();
+
+        let sandbox = Sandbox::new(std::slice::from_ref(&root), &root).unwrap();
+        assert!(sandbox.check_path(Path::new("escape/x")).is_err());
+
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(sandbox
+            .check_args(&args(&[
+                "commit",
+                "-m",
+                "fix origin/main",
+                "https://x/../y"
+            ]))
+            .is_ok());
+        assert!(sandbox.check_args(&args(&["add", "escape"])).is_err());
+        assert!(sandbox
+            .check_args(&args(&["--output=/etc/passwd"]))
+            .is_err());
+        assert!(sandbox.check_args(&args(&["-C", "/"])).is_err());
+        assert!(sandbox
+            .check_args(&args(&["-o/tmp/outside", "in.txt"]))
+            .is_err());
+        assert!(sandbox.check_args(&args(&["-o", "/tmp/outside"])).is_err());
+        assert!(sandbox.check_args(&args(&["-I../include"])).is_err());
+        assert!(sandbox.check_args(&args(&["-rn", "-o", "out.txt"])).is_ok());
+    }
+}
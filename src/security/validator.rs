@@ -1,5 +1,5 @@
 // file: src/security/validator.rs
-// version: 1.0.0
+// version: 1.11.0
 // guid: c3d4e5f6-a7b8-9012-cdef-345678901234
 
 //! Command validation module
@@ -15,12 +15,15 @@ use tracing::{debug, warn};
 pub fn validate_command_arguments(command: &str, args: &[String]) -> Result<()> {
     match command {
         "git" => validate_git_arguments(args),
+        "gh" => validate_gh_arguments(args),
         "buf" => validate_buf_arguments(args),
         "cargo" => validate_cargo_arguments(args),
         "go" => validate_go_arguments(args),
         "docker" => validate_docker_arguments(args),
         "npm" | "yarn" | "pnpm" => validate_node_arguments(args),
         "python" | "python3" => validate_python_arguments(args),
+        "systemctl" | "journalctl" | "launchctl" => validate_service_arguments(command, args),
+        "psql" => validate_psql_arguments(args),
         "ls" | "cat" | "cp" | "mv" | "rm" | "mkdir" | "find" | "grep" => {
             validate_file_arguments(command, args)
         }
@@ -30,6 +33,13 @@ pub fn validate_command_arguments(command: &str, args: &[String]) -> Result<()>
 
 /// Validate git command arguments
 fn validate_git_arguments(args: &[String]) -> Result<()> {
+    // Allow leading `-C <dir>` pairs so commands can target another work tree
+    let mut args = args;
+    while args.len() >= 2 && args[0] == "-C" {
+        debug!("Git command targets directory: {}", args[1]);
+        args = &args[2..];
+    }
+
     if args.is_empty() {
         return Err(AgentError::validation("Git command requires arguments"));
     }
@@ -40,7 +50,8 @@ fn validate_git_arguments(args: &[String]) -> Result<()> {
     let allowed_subcommands = [
         "status", "add", "commit", "push", "pull", "fetch", "log", "diff",
         "branch", "checkout", "merge", "rebase", "reset", "clean", "stash",
-        "tag", "remote", "config", "show", "blame", "cherry-pick",
+        "tag", "remote", "config", "show", "blame", "cherry-pick", "rev-parse",
+        "init", "clone", "mv", "ls-files", "ls-remote", "worktree", "merge-file",
     ];
 
     if !allowed_subcommands.contains(&subcommand.as_str()) {
@@ -56,12 +67,57 @@ fn validate_git_arguments(args: &[String]) -> Result<()> {
         "clean" => validate_git_clean_args(&args[1..])?,
         "config" => validate_git_config_args(&args[1..])?,
         "remote" => validate_git_remote_args(&args[1..])?,
+        "clone" => validate_git_clone_args(&args[1..])?,
+        "worktree" => validate_git_worktree_args(&args[1..])?,
         _ => {}
     }
 
     Ok(())
 }
 
+/// Validate GitHub CLI arguments: only issue, label, pull request, workflow run and release
+/// asset management is allowed. Deleting a repository and changing its visibility are refused
+/// with the reason, since neither can be taken back.
+fn validate_gh_arguments(args: &[String]) -> Result<()> {
+    let allowed: [(&str, &[&str]); 7] = [
+        ("issue", &["list", "view", "create", "edit", "close", "reopen", "comment"]),
+        ("label", &["list", "create"]),
+        ("pr", &["create", "view", "checkout"]),
+        ("run", &["watch"]),
+        ("release", &["upload"]),
+        ("repo", &["view"]),
+        ("auth", &["status"]),
+    ];
+
+    let group = args
+        .first()
+        .ok_or_else(|| AgentError::validation("GitHub CLI command requires arguments"))?;
+    let action = args.get(1).map(String::as_str).unwrap_or("");
+
+    let visibility = args
+        .iter()
+        .any(|arg| arg == "--visibility" || arg.starts_with("--visibility="));
+    let forbidden = match (group.as_str(), action) {
+        ("repo", "delete") => Some("deleting a repository cannot be undone"),
+        ("repo", "edit") if visibility => Some("changing the visibility of a repository can publish private code"),
+        _ => None,
+    };
+    if let Some(reason) = forbidden {
+        return Err(AgentError::security(format!(
+            "GitHub CLI command '{} {}' is never allowed: {}",
+            group, action, reason
+        )));
+    }
+
+    match allowed.iter().find(|(name, _)| name == group) {
+        Some((_, actions)) if actions.contains(&action) => Ok(()),
+        _ => Err(AgentError::security(format!(
+            "GitHub CLI command '{} {}' is not allowed for security reasons",
+            group, action
+        ))),
+    }
+}
+
 /// Validate git reset arguments to prevent destructive operations
 fn validate_git_reset_args(args: &[String]) -> Result<()> {
     for arg in args {
@@ -164,6 +220,50 @@ fn validate_git_remote_args(args: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// Validate git worktree arguments: only listing, adding and removing work trees is allowed
+fn validate_git_worktree_args(args: &[String]) -> Result<()> {
+    let action = args.first().map(String::as_str).unwrap_or("list");
+    if !["list", "add", "remove", "prune"].contains(&action) {
+        return Err(AgentError::security(format!(
+            "Git worktree operation '{}' is not allowed for security reasons",
+            action
+        )));
+    }
+
+    Ok(())
+}
+
+/// Validate git clone arguments to prevent code execution through clone options
+fn validate_git_clone_args(args: &[String]) -> Result<()> {
+    let blocked_options = [
+        "-u",
+        "--upload-pack",
+        "-c",
+        "--config",
+        "--template",
+        "--separate-git-dir",
+        "--recurse-submodules",
+    ];
+
+    for arg in args {
+        let option = arg.split('=').next().unwrap_or(arg);
+        if blocked_options.contains(&option) {
+            return Err(AgentError::security(format!(
+                "Git clone option '{}' is not allowed for security reasons",
+                arg
+            )));
+        }
+        if arg.starts_with("ext::") || arg.starts_with("fd::") {
+            return Err(AgentError::security(format!(
+                "Git transport '{}' is not allowed for security reasons",
+                arg
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 /// Validate buf command arguments
 fn validate_buf_arguments(args: &[String]) -> Result<()> {
     if args.is_empty() {
@@ -175,7 +275,7 @@ fn validate_buf_arguments(args: &[String]) -> Result<()> {
     // Allow only safe buf subcommands
     let allowed_subcommands = [
         "generate", "lint", "format", "breaking", "build", "push", "export",
-        "mod", "dep", "registry", "config", "beta",
+        "mod", "dep", "registry", "config", "beta", "curl",
     ];
 
     if !allowed_subcommands.contains(&subcommand.as_str()) {
@@ -278,7 +378,7 @@ fn validate_docker_arguments(args: &[String]) -> Result<()> {
     // Allow only safe docker subcommands
     let allowed_subcommands = [
         "build", "run", "ps", "images", "logs", "inspect", "version",
-        "info", "system", "network", "volume", "compose",
+        "info", "system", "network", "volume", "compose", "image", "rm",
     ];
 
     if !allowed_subcommands.contains(&subcommand.as_str()) {
@@ -307,12 +407,10 @@ fn validate_docker_run_args(args: &[String]) -> Result<()> {
         if arg.starts_with("--user") && arg.contains("root") {
             warn!("Docker run as root user detected");
         }
-        if arg.starts_with("--volume") || arg.starts_with("-v") {
-            if arg.contains(":/") {
-                return Err(AgentError::security(
-                    "Docker volume mount to root filesystem is not allowed"
-                ));
-            }
+        if (arg.starts_with("--volume") || arg.starts_with("-v")) && arg.contains(":/") {
+            return Err(AgentError::security(
+                "Docker volume mount to root filesystem is not allowed"
+            ));
         }
     }
     Ok(())
@@ -404,6 +502,91 @@ fn validate_file_arguments(command: &str, args: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// Validate service manager arguments: systemctl and journalctl only act on the user's own
+/// units (`--user`), and launchctl only on the user's GUI domain
+fn validate_service_arguments(command: &str, args: &[String]) -> Result<()> {
+    if command == "launchctl" {
+        let subcommand = args.first().map(String::as_str).unwrap_or_default();
+        if !["print", "kickstart", "kill"].contains(&subcommand) {
+            return Err(AgentError::security(format!(
+                "launchctl subcommand '{}' is not allowed",
+                subcommand
+            )));
+        }
+        if !args.iter().any(|arg| arg.starts_with("gui/")) {
+            return Err(AgentError::security(
+                "launchctl may only target services in the user's gui/<uid> domain",
+            ));
+        }
+        return Ok(());
+    }
+
+    if !args.iter().any(|arg| arg == "--user") {
+        return Err(AgentError::security(format!(
+            "{} may only manage user units; pass --user",
+            command
+        )));
+    }
+    if command == "systemctl" {
+        let subcommand = args
+            .iter()
+            .find(|arg| !arg.starts_with('-'))
+            .map(String::as_str)
+            .unwrap_or_default();
+        let allowed_subcommands = ["status", "show", "start", "stop", "restart", "is-active"];
+        if !allowed_subcommands.contains(&subcommand) {
+            return Err(AgentError::security(format!(
+                "systemctl subcommand '{}' is not allowed",
+                subcommand
+            )));
+        }
+    }
+    // systemctl and journalctl expand glob patterns in unit names
+    if let Some(arg) = args.iter().find(|arg| arg.contains(['*', '?', '['])) {
+        return Err(AgentError::security(format!(
+            "Unit patterns are not allowed: {}",
+            arg
+        )));
+    }
+    Ok(())
+}
+
+/// Validate psql arguments: SQL only comes from `--command`, which must not be a meta-command
+/// (`\!` runs a shell), and psql reads and writes no files of its own
+fn validate_psql_arguments(args: &[String]) -> Result<()> {
+    let file_options = ["-f", "--file", "-o", "--output", "-L", "--log-file"];
+    if let Some(arg) = args.iter().find(|arg| {
+        file_options
+            .iter()
+            .any(|option| *arg == option || arg.starts_with(&format!("{}=", option)))
+    }) {
+        return Err(AgentError::security(format!(
+            "psql option '{}' is not allowed",
+            arg
+        )));
+    }
+
+    let commands: Vec<&str> = args
+        .iter()
+        .enumerate()
+        .filter_map(|(i, arg)| match arg.as_str() {
+            "-c" | "--command" => args.get(i + 1).map(String::as_str),
+            _ => arg.strip_prefix("--command="),
+        })
+        .collect();
+    if commands.is_empty() {
+        return Err(AgentError::security(
+            "psql only runs SQL given with --command",
+        ));
+    }
+    if commands.iter().any(|command| command.trim_start().starts_with('\\')) {
+        return Err(AgentError::security(
+            "psql meta-commands are not allowed",
+        ));
+    }
+    Ok(())
+}
+
 /// Generic argument validation
 fn validate_generic_arguments(args: &[String]) -> Result<()> {
     // Basic checks for all commands
@@ -433,6 +616,37 @@ mod tests {
         assert!(validate_git_arguments(&["upload-pack".to_string()]).is_err());
     }
 
+    #[test]
+    fn test_git_directory_and_clone_validation() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(validate_git_arguments(&args(&["-C", "project", "status"])).is_ok());
+        assert!(validate_git_arguments(&args(&["-C", "project", "daemon"])).is_err());
+        assert!(validate_git_arguments(&args(&["clone", "--depth", "1", "https://github.com/jdfalk/x"])).is_ok());
+        assert!(validate_git_arguments(&args(&["clone", "--template=/tmp/hooks", "repo"])).is_err());
+        assert!(validate_git_arguments(&args(&["clone", "ext::sh -c evil", "dest"])).is_err());
+        assert!(validate_git_arguments(&args(&["worktree", "add", "--detach", "/tmp/base", "main"])).is_ok());
+        assert!(validate_git_arguments(&args(&["worktree", "move", "a", "b"])).is_err());
+        assert!(validate_git_arguments(&args(&["merge-file", "-L", "ours", "a", "o", "b"])).is_ok());
+    }
+
+    #[test]
+    fn test_gh_validation() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(validate_gh_arguments(&args(&["issue", "list", "--label", "todo"])).is_ok());
+        assert!(validate_gh_arguments(&args(&["issue", "close", "12"])).is_ok());
+        assert!(validate_gh_arguments(&args(&["issue", "delete", "12"])).is_err());
+        assert!(validate_gh_arguments(&args(&["api", "repos/x/y"])).is_err());
+        assert!(validate_gh_arguments(&args(&["extension", "install", "x"])).is_err());
+        assert!(validate_gh_arguments(&args(&["pr", "checkout", "7"])).is_ok());
+        assert!(validate_gh_arguments(&args(&["release", "upload", "v1.0", "dist.tgz"])).is_ok());
+        let error = validate_gh_arguments(&args(&["repo", "delete", "x/y", "--yes"])).unwrap_err();
+        assert!(error.to_string().contains("never allowed"));
+        assert!(validate_gh_arguments(&args(&["repo", "edit", "--visibility=public"])).is_err());
+        assert!(validate_gh_arguments(&args(&["repo", "edit", "--visibility", "public"])).is_err());
+    }
+
     #[test]
     fn test_python_validation() {
         // Safe operations
@@ -460,9 +674,34 @@ mod tests {
         // Safe operations
         assert!(validate_docker_arguments(&["ps".to_string()]).is_ok());
         assert!(validate_docker_arguments(&["images".to_string()]).is_ok());
+        assert!(validate_docker_arguments(&["image".to_string(), "prune".to_string()]).is_ok());
 
         // Dangerous operations
         assert!(validate_docker_arguments(&["run".to_string(), "--privileged".to_string()]).is_err());
         assert!(validate_docker_arguments(&["exec".to_string()]).is_err());
     }
+
+    #[test]
+    fn test_service_validation() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(validate_command_arguments("systemctl", &args(&["--user", "restart", "web.service"])).is_ok());
+        assert!(validate_command_arguments("systemctl", &args(&["restart", "web.service"])).is_err());
+        assert!(validate_command_arguments("systemctl", &args(&["--user", "enable", "web"])).is_err());
+        assert!(validate_command_arguments("systemctl", &args(&["--user", "stop", "web-*"])).is_err());
+        assert!(validate_command_arguments("journalctl", &args(&["--user", "--unit", "web"])).is_ok());
+        assert!(validate_command_arguments("journalctl", &args(&["--unit", "sshd"])).is_err());
+        assert!(validate_command_arguments("launchctl", &args(&["kickstart", "-k", "gui/501/dev.web"])).is_ok());
+        assert!(validate_command_arguments("launchctl", &args(&["kickstart", "system/com.apple.sshd"])).is_err());
+        assert!(validate_command_arguments("launchctl", &args(&["bootout", "gui/501/dev.web"])).is_err());
+    }
+
+    #[test]
+    fn test_psql_validation() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(validate_command_arguments("psql", &args(&["--csv", "--dbname", "postgres://localhost/app", "--command", "SELECT 1"])).is_ok());
+        assert!(validate_command_arguments("psql", &args(&["--command", "\\! id"])).is_err());
+        assert!(validate_command_arguments("psql", &args(&["-c", " \\copy t to 'out.csv'"])).is_err());
+        assert!(validate_command_arguments("psql", &args(&["--file=setup.sql"])).is_err());
+        assert!(validate_command_arguments("psql", &args(&["--dbname", "app"])).is_err());
+    }
 }
@@ -0,0 +1,394 @@
+// file: src/serve.rs
+// version: 1.2.0
+// guid: 3f9c2d71-8a4e-4b56-b0d3-6e15a7c92f48
+
+//! Long-lived stdio mode (`serve stdio`)
+//!
+//! An agent issuing hundreds of commands pays for starting the utility, loading configuration
+//! and setting up logging every time. `serve stdio` does that once and then runs commands in
+//! the same process, one JSON request per line on stdin:
+//!
+//! ```text
+//! {"id": 1, "cmd": "git", "args": ["status", "--short"]}
+//! {"id": 2, "cmd": "file", "args": ["write", "notes.txt"], "stdin": "text\n"}
+//! {"cmd": "shutdown"}
+//! ```
+//!
+//! Each request is answered with JSON lines on stdout: `output` events carrying the lines the
+//! command writes to stdout and stderr as they are written, then one `exit` event:
+//!
+//! ```text
+//! {"id": 1, "event": "output", "stream": "stdout", "data": " M src/main.rs\n"}
+//! {"id": 1, "event": "exit", "exit_code": 0, "duration_ms": 12}
+//! ```
+//!
+//! A failed command's `exit` event has exit code 1 and an `error` message, plus `details` with
+//! the structured budget, policy denial or capability request when there is one. Requests run
+//! one at a time in the order received; the options given before `serve` apply to all of
+//! them. Commands write to the process' own stdout and stderr, which are redirected to pipes
+//! while a request runs, so this mode is only available on Unix.
+
+use crate::error::{AgentError, Result};
+use crate::error_codes;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::future::Future;
+use tracing::{error, info};
+
+/// Request that ends the session
+pub const SHUTDOWN: &str = "shutdown";
+
+/// One line of input
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Echoed in every event of the request
+    #[serde(default)]
+    pub id: Value,
+    /// Command, such as `git`
+    pub cmd: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Text given to the command on stdin; it reads nothing otherwise
+    #[serde(default)]
+    pub stdin: Option<String>,
+}
+
+impl Request {
+    /// Command line for the dispatcher; options before the command are not accepted, so
+    /// `--allow-outside-workspace` and the like cannot be passed per request
+    fn command_line(&self) -> std::result::Result<Vec<String>, String> {
+        if self.cmd.is_empty() || self.cmd.starts_with('-') {
+            return Err(format!("'{}' is not a command", self.cmd));
+        }
+        if self.cmd == "serve" {
+            return Err("serve cannot be run from serve".to_string());
+        }
+        Ok(std::iter::once(self.cmd.clone())
+            .chain(self.args.iter().cloned())
+            .collect())
+    }
+}
+
+/// `exit` event for the outcome of a command
+fn exit_event(id: &Value, result: &anyhow::Result<()>, duration_ms: u128) -> Value {
+    match result {
+        Ok(()) => json!({"id": id, "event": "exit", "exit_code": 0, "duration_ms": duration_ms}),
+        Err(e) => {
+            let details = match e
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<AgentError>())
+            {
+                Some(AgentError::BudgetExceeded(exceeded)) => Some(exceeded.to_json()),
+                Some(AgentError::PolicyViolation(denial)) => Some(denial.to_json()),
+                Some(AgentError::ToolUnavailable(request)) => Some(request.to_json()),
+                _ => None,
+            };
+            let mut event = json!({
+                "id": id,
+                "event": "exit",
+                "exit_code": 1,
+                "duration_ms": duration_ms,
+                "error": e.to_string(),
+                "code": error_codes::classify(e),
+            });
+            if let Some(details) = details {
+                event["details"] = details;
+            }
+            event
+        }
+    }
+}
+
+/// Serve requests from stdin until `shutdown` or end of input
+///
+/// `dispatch` runs one command line, such as `["git", "status"]`, in this process.
+#[cfg(unix)]
+pub async fn stdio<D, F>(mut dispatch: D) -> Result<()>
+where
+    D: FnMut(Vec<String>) -> F,
+    F: Future<Output = anyhow::Result<()>>,
+{
+    use std::io::Write;
+    use std::time::Instant;
+
+    let streams = redirect::Streams::take()?;
+    let events = streams.events();
+    let mut requests = streams.requests();
+    let _ = events.send(json!({"event": "ready", "version": crate::VERSION}));
+    info!("Serving requests on stdin");
+
+    while let Some(line) = requests.recv().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = events
+                    .send(json!({"id": Value::Null, "event": "error", "message": e.to_string()}));
+                continue;
+            }
+        };
+        if request.cmd == SHUTDOWN {
+            let _ = events.send(json!({"id": request.id, "event": "shutdown"}));
+            break;
+        }
+        let args = match request.command_line() {
+            Ok(args) => args,
+            Err(message) => {
+                let _ =
+                    events.send(json!({"id": request.id, "event": "error", "message": message}));
+                continue;
+            }
+        };
+
+        let started = Instant::now();
+        let capture = streams.capture(&request.id, request.stdin.as_deref())?;
+        let result = dispatch(args).await;
+        if let Err(e) = &result {
+            error!("Command execution failed: {}", e);
+        }
+        let _ = std::io::stdout().flush();
+        capture.finish().await?;
+        let _ = events.send(exit_event(
+            &request.id,
+            &result,
+            started.elapsed().as_millis(),
+        ));
+    }
+    info!("Serve session ended");
+    Ok(())
+}
+
+/// Serve requests from stdin until `shutdown` or end of input
+#[cfg(not(unix))]
+pub async fn stdio<D, F>(_dispatch: D) -> Result<()>
+where
+    D: FnMut(Vec<String>) -> F,
+    F: Future<Output = anyhow::Result<()>>,
+{
+    Err(AgentError::system("serve stdio is only available on Unix"))
+}
+
+#[cfg(unix)]
+mod redirect {
+    //! Swapping the standard file descriptors of the process for pipes
+
+    use crate::error::Result;
+    use serde_json::{json, Value};
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    use std::sync::mpsc as std_mpsc;
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+    use tokio::sync::mpsc;
+    use tracing::warn;
+
+    /// How long output of a finished command may still be read, e.g. from a process it left
+    /// running in the background with our pipe as its stdout
+    const DRAIN_GRACE: Duration = Duration::from_secs(1);
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// A copy of `fd` that is not inherited by child processes
+    fn duplicate(fd: RawFd) -> io::Result<File> {
+        // SAFETY: F_DUPFD_CLOEXEC returns a new descriptor owned by nothing else
+        let copy = check(unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) })?;
+        Ok(unsafe { File::from_raw_fd(copy) })
+    }
+
+    /// Point the standard descriptor `target` at `file`; the copy is inherited by children
+    fn replace(target: RawFd, file: &File) -> io::Result<()> {
+        // SAFETY: dup2 only changes which file `target` refers to
+        check(unsafe { libc::dup2(file.as_raw_fd(), target) }).map(|_| ())
+    }
+
+    /// A pipe whose ends are not inherited by child processes
+    fn pipe() -> io::Result<(File, File)> {
+        let mut fds = [0; 2];
+        // SAFETY: pipe fills in two new descriptors on success
+        check(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+        let (read, write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        for file in [&read, &write] {
+            check(unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) })?;
+        }
+        Ok((read, write))
+    }
+
+    /// The protocol streams, moved away from the standard descriptors commands use
+    pub struct Streams {
+        input: File,
+        events: std_mpsc::Sender<Value>,
+        /// The original stderr, where stray output goes between requests
+        stderr: File,
+        null: File,
+    }
+
+    impl Streams {
+        pub fn take() -> Result<Self> {
+            let input = duplicate(libc::STDIN_FILENO)?;
+            let output = duplicate(libc::STDOUT_FILENO)?;
+            let stderr = duplicate(libc::STDERR_FILENO)?;
+            let null = OpenOptions::new().read(true).open("/dev/null")?;
+            replace(libc::STDIN_FILENO, &null)?;
+            replace(libc::STDOUT_FILENO, &stderr)?;
+
+            let (events, received) = std_mpsc::channel::<Value>();
+            std::thread::spawn(move || {
+                let mut output = output;
+                for event in received {
+                    let line = event.to_string() + "\n";
+                    if output
+                        .write_all(line.as_bytes())
+                        .and_then(|_| output.flush())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+            Ok(Self {
+                input,
+                events,
+                stderr,
+                null,
+            })
+        }
+
+        pub fn events(&self) -> std_mpsc::Sender<Value> {
+            self.events.clone()
+        }
+
+        /// Request lines, read on a thread of their own
+        pub fn requests(&self) -> mpsc::UnboundedReceiver<String> {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            if let Ok(input) = self.input.try_clone() {
+                std::thread::spawn(move || {
+                    for line in BufReader::new(input).lines() {
+                        let Ok(line) = line else { break };
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            receiver
+        }
+
+        /// Send what is written to stdout and stderr to `id` until [`Capture::finish`]
+        pub fn capture(&self, id: &Value, stdin: Option<&str>) -> Result<Capture<'_>> {
+            let _ = io::stdout().flush();
+            let (out_read, out_write) = pipe()?;
+            let (err_read, err_write) = pipe()?;
+            replace(libc::STDOUT_FILENO, &out_write)?;
+            replace(libc::STDERR_FILENO, &err_write)?;
+            if let Some(text) = stdin {
+                let (in_read, mut in_write) = pipe()?;
+                replace(libc::STDIN_FILENO, &in_read)?;
+                let text = text.to_string();
+                std::thread::spawn(move || in_write.write_all(text.as_bytes()));
+            }
+            let readers = [(out_read, "stdout"), (err_read, "stderr")]
+                .into_iter()
+                .map(|(read, stream)| forward(read, id.clone(), stream, self.events.clone()))
+                .collect();
+            Ok(Capture {
+                streams: self,
+                readers,
+            })
+        }
+    }
+
+    /// Forward the lines of a pipe as `output` events
+    fn forward(
+        read: File,
+        id: Value,
+        stream: &'static str,
+        events: std_mpsc::Sender<Value>,
+    ) -> JoinHandle<()> {
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(read);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                let data = String::from_utf8_lossy(&line);
+                let _ = events
+                    .send(json!({"id": id, "event": "output", "stream": stream, "data": data}));
+            }
+        })
+    }
+
+    /// Output of the running request
+    pub struct Capture<'a> {
+        streams: &'a Streams,
+        readers: Vec<JoinHandle<()>>,
+    }
+
+    impl Capture<'_> {
+        /// Restore the standard descriptors and wait for the output written so far
+        pub async fn finish(self) -> Result<()> {
+            replace(libc::STDOUT_FILENO, &self.streams.stderr)?;
+            replace(libc::STDERR_FILENO, &self.streams.stderr)?;
+            replace(libc::STDIN_FILENO, &self.streams.null)?;
+            let started = Instant::now();
+            while !self.readers.iter().all(JoinHandle::is_finished) {
+                if started.elapsed() >= DRAIN_GRACE {
+                    // Its later output is still forwarded under the same id
+                    warn!("A process started by the command still holds its output open");
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_command_line() {
+        let request: Request =
+            serde_json::from_str(r#"{"id": 7, "cmd": "git", "args": ["status"]}"#).unwrap();
+        assert_eq!(request.command_line().unwrap(), vec!["git", "status"]);
+        assert_eq!(request.id, json!(7));
+
+        for cmd in ["--allow-outside-workspace", "serve", ""] {
+            let request = Request {
+                id: Value::Null,
+                cmd: cmd.to_string(),
+                args: Vec::new(),
+                stdin: None,
+            };
+            assert!(request.command_line().is_err(), "{}", cmd);
+        }
+    }
+
+    #[test]
+    fn test_exit_event() {
+        let event = exit_event(&json!("a"), &Ok(()), 5);
+        assert_eq!(
+            event,
+            json!({"id": "a", "event": "exit", "exit_code": 0, "duration_ms": 5})
+        );
+
+        let failed: anyhow::Result<()> = Err(AgentError::invalid_argument("bad").into());
+        let event = exit_event(&json!(2), &failed, 1);
+        assert_eq!(event["exit_code"], 1);
+        assert!(event["error"].as_str().unwrap().contains("bad"));
+        assert!(event.get("details").is_none());
+    }
+}
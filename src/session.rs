@@ -0,0 +1,112 @@
+// file: src/session.rs
+// version: 1.0.0
+// guid: 6544c0e1-0346-4d9b-be69-aa56ab584880
+
+//! Session and correlation identifiers
+//!
+//! Every invocation belongs to a session. The session ID is taken from `SAFE_AI_SESSION_ID`
+//! when an outer process (an agent, or a parent safe-ai-util run) already started one, and is
+//! generated otherwise. Each invocation also gets its own execution ID, which is handed to
+//! child processes as `SAFE_AI_PARENT_EXECUTION_ID` so nested runs can be linked together.
+
+use std::sync::OnceLock;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Environment variable carrying the session ID
+pub const SESSION_ID_ENV: &str = "SAFE_AI_SESSION_ID";
+
+/// Environment variable carrying the execution ID of the invoking safe-ai-util process
+pub const PARENT_EXECUTION_ID_ENV: &str = "SAFE_AI_PARENT_EXECUTION_ID";
+
+/// Identifiers shared by every log line and child process of this invocation
+#[derive(Debug, Clone)]
+pub struct SessionContext {
+    session_id: String,
+    execution_id: String,
+    parent_execution_id: Option<String>,
+}
+
+impl SessionContext {
+    /// Build the context from the environment, generating IDs where none are inherited
+    pub fn from_env() -> Self {
+        let session_id = match std::env::var(SESSION_ID_ENV) {
+            Ok(id) if is_valid_id(&id) => id,
+            Ok(id) => {
+                warn!("Ignoring invalid {} value: {:?}", SESSION_ID_ENV, id);
+                Uuid::new_v4().to_string()
+            }
+            Err(_) => Uuid::new_v4().to_string(),
+        };
+
+        let parent_execution_id = std::env::var(PARENT_EXECUTION_ID_ENV)
+            .ok()
+            .filter(|id| is_valid_id(id));
+
+        Self {
+            session_id,
+            execution_id: Uuid::new_v4().to_string(),
+            parent_execution_id,
+        }
+    }
+
+    /// Session ID shared by all nested executions
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// ID of this invocation
+    pub fn execution_id(&self) -> &str {
+        &self.execution_id
+    }
+
+    /// ID of the safe-ai-util invocation that spawned this one, if any
+    pub fn parent_execution_id(&self) -> Option<&str> {
+        self.parent_execution_id.as_deref()
+    }
+
+    /// Environment variables to propagate into child processes
+    pub fn child_env(&self) -> [(&'static str, &str); 2] {
+        [
+            (SESSION_ID_ENV, self.session_id.as_str()),
+            (PARENT_EXECUTION_ID_ENV, self.execution_id.as_str()),
+        ]
+    }
+}
+
+/// Get the session context for this process
+pub fn current() -> &'static SessionContext {
+    static CONTEXT: OnceLock<SessionContext> = OnceLock::new();
+    CONTEXT.get_or_init(SessionContext::from_env)
+}
+
+/// Accept UUIDs and similar opaque tokens, rejecting anything that could smuggle data into logs
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_id() {
+        assert!(is_valid_id(&Uuid::new_v4().to_string()));
+        assert!(is_valid_id("agent-run_42"));
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("id\nINJECTED LOG LINE"));
+        assert!(!is_valid_id(&"a".repeat(200)));
+    }
+
+    #[test]
+    fn test_child_env_links_to_this_execution() {
+        let context = current();
+        let env = context.child_env();
+        assert_eq!(env[0], (SESSION_ID_ENV, context.session_id()));
+        assert_eq!(env[1], (PARENT_EXECUTION_ID_ENV, context.execution_id()));
+    }
+}
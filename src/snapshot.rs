@@ -0,0 +1,284 @@
+// file: src/snapshot.rs
+// version: 1.2.0
+// guid: 9b3e7f20-6d4c-4a18-8e52-c1f0a7d94b36
+
+//! Workspace snapshots for `--report-changes`
+//!
+//! A snapshot hashes every file under the watched paths, honouring `.gitignore` and skipping
+//! `.git`, so comparing the snapshots taken before and after a command shows the files it
+//! created, modified and deleted. Files whose size and modification time did not change keep
+//! the hash of the earlier snapshot instead of being read again, and a file only counts as
+//! modified when its content changed.
+//!
+//! A [`ChangeTracker`] keeps the snapshot taken after one command as the snapshot before the
+//! next, so only files touched in between are hashed again. Commands running concurrently
+//! (a batch) see each other's changes.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::debug;
+
+/// Size, modification time and content hash of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: [u8; 32],
+}
+
+/// The paths a snapshot covers
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    /// Directory files are reported relative to
+    pub base: PathBuf,
+    /// Watched files and directories
+    pub roots: Vec<PathBuf>,
+    /// Directories left out
+    pub excluded: Vec<PathBuf>,
+    /// Include files ignored by `.gitignore`, such as generated code
+    pub include_ignored: bool,
+}
+
+impl Scope {
+    /// Hash the files in scope
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot {
+            files: BTreeMap::new(),
+            scope: self.clone(),
+        };
+        snapshot.scan(None);
+        snapshot
+    }
+}
+
+/// Hashes of the files under a set of watched paths
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// Paths relative to `scope.base` when inside it
+    files: BTreeMap<PathBuf, Entry>,
+    scope: Scope,
+}
+
+/// Files a command created, modified and deleted, relative to the workspace when inside it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Changes {
+    pub created: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// What one command changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommandChanges {
+    pub command: String,
+    #[serde(flatten)]
+    pub changes: Changes,
+}
+
+impl Snapshot {
+    /// Take the snapshot again, re-reading only files whose size or modification time changed
+    pub fn retake(&self) -> Self {
+        let mut snapshot = Self {
+            files: BTreeMap::new(),
+            scope: self.scope.clone(),
+        };
+        snapshot.scan(Some(&self.files));
+        snapshot
+    }
+
+    /// What changed between this snapshot and the later `after`
+    pub fn changes(&self, after: &Snapshot) -> Changes {
+        let mut changes = Changes::default();
+        for (path, entry) in &after.files {
+            match self.files.get(path) {
+                None => changes.created.push(path.clone()),
+                Some(before) if before.hash != entry.hash => changes.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.deleted = self
+            .files
+            .keys()
+            .filter(|path| !after.files.contains_key(*path))
+            .cloned()
+            .collect();
+        changes
+    }
+
+    /// Files in the snapshot, as paths reported in [`Changes`]
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+
+    /// Where a file reported in [`Changes`] is
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.scope.base.join(path)
+    }
+
+    fn scan(&mut self, previous: Option<&BTreeMap<PathBuf, Entry>>) {
+        for root in &self.scope.roots {
+            let walker = ignore::WalkBuilder::new(root)
+                .standard_filters(!self.scope.include_ignored)
+                .hidden(false)
+                .require_git(false)
+                .filter_entry(|entry| entry.file_name() != ".git")
+                .build();
+            for entry in walker.flatten() {
+                let path = entry.path();
+                if !entry.file_type().is_some_and(|t| t.is_file())
+                    || self.scope.excluded.iter().any(|dir| path.starts_with(dir))
+                {
+                    continue;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                let key = path
+                    .strip_prefix(&self.scope.base)
+                    .unwrap_or(path)
+                    .to_path_buf();
+                let (len, modified) = (metadata.len(), metadata.modified().ok());
+                let unchanged = previous
+                    .and_then(|files| files.get(&key))
+                    .filter(|old| old.len == len && old.modified == modified && modified.is_some());
+                let hash = match unchanged {
+                    Some(old) => old.hash,
+                    None => match fs::read(path) {
+                        Ok(content) => Sha256::digest(&content).into(),
+                        Err(e) => {
+                            debug!("Not hashing {}: {}", path.display(), e);
+                            continue;
+                        }
+                    },
+                };
+                self.files.insert(
+                    key,
+                    Entry {
+                        len,
+                        modified,
+                        hash,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Snapshots the watched paths around each command and collects what the commands changed
+#[derive(Debug)]
+pub struct ChangeTracker {
+    scope: Scope,
+    /// Snapshot after the last command, reused as the next command's starting point
+    last: Mutex<Option<Snapshot>>,
+    reports: Mutex<Vec<CommandChanges>>,
+}
+
+impl ChangeTracker {
+    pub fn new(scope: Scope) -> Self {
+        Self {
+            scope,
+            last: Mutex::new(None),
+            reports: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Snapshot the watched paths before a command runs
+    pub fn before(&self) -> Snapshot {
+        let last = self.last.lock().unwrap().clone();
+        match last {
+            Some(snapshot) => snapshot.retake(),
+            None => self.scope.snapshot(),
+        }
+    }
+
+    /// Snapshot the watched paths again after `command` ran and record what it changed
+    pub fn after(&self, command: String, before: Snapshot) {
+        let after = before.retake();
+        let changes = before.changes(&after);
+        *self.last.lock().unwrap() = Some(after);
+        self.reports
+            .lock()
+            .unwrap()
+            .push(CommandChanges { command, changes });
+    }
+
+    /// What each command changed, in the order they finished
+    pub fn reports(&self) -> Vec<CommandChanges> {
+        self.reports.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_between_snapshots() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn a() {}\n").unwrap();
+        fs::write(root.join("src/same.rs"), "same\n").unwrap();
+        fs::write(root.join("old.txt"), "old\n").unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+
+        let scope = Scope {
+            base: root.to_path_buf(),
+            roots: vec![root.to_path_buf()],
+            excluded: vec![root.join("logs")],
+            include_ignored: false,
+        };
+        let before = scope.snapshot();
+        fs::write(root.join("src/lib.rs"), "fn a() { }\n").unwrap();
+        // Rewritten with the same content: not a modification
+        fs::write(root.join("src/same.rs"), "same\n").unwrap();
+        fs::write(root.join("src/new.rs"), "\n").unwrap();
+        fs::remove_file(root.join("old.txt")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("target/build.o"), "ignored").unwrap();
+        fs::write(root.join("logs/run.log"), "excluded").unwrap();
+
+        let changes = before.changes(&before.retake());
+        assert_eq!(changes.created, vec![PathBuf::from("src/new.rs")]);
+        assert_eq!(changes.modified, vec![PathBuf::from("src/lib.rs")]);
+        assert_eq!(changes.deleted, vec![PathBuf::from("old.txt")]);
+    }
+
+    #[test]
+    fn test_tracker_reports_each_command() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        let tracker = ChangeTracker::new(Scope {
+            base: root.to_path_buf(),
+            roots: vec![root.join("src")],
+            excluded: Vec::new(),
+            include_ignored: false,
+        });
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        let before = tracker.before();
+        fs::write(root.join("src/a.rs"), "a\n").unwrap();
+        fs::write(root.join("outside.txt"), "not watched\n").unwrap();
+        tracker.after("touch src/a.rs".to_string(), before);
+        let before = tracker.before();
+        tracker.after("true".to_string(), before);
+
+        let reports = tracker.reports();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].command, "touch src/a.rs");
+        assert_eq!(reports[0].changes.created, vec![PathBuf::from("src/a.rs")]);
+        assert!(reports[1].changes.is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+// file: src/telemetry.rs
+// version: 1.0.0
+// guid: c165e261-c745-4b6a-91ae-89baba838e8c
+
+//! Execution telemetry
+//!
+//! Every Executor invocation is recorded as an `execute` tracing span carrying the command,
+//! a hash of its arguments, the exit code, and the duration. When the crate is built with
+//! the `otel` feature and `OTEL_EXPORTER_OTLP_ENDPOINT` is set, those spans are exported to
+//! an OpenTelemetry collector over OTLP/HTTP; otherwise they only feed the regular logs.
+
+use sha2::{Digest, Sha256};
+
+/// Name of the span emitted for each command execution
+pub const EXECUTION_SPAN: &str = "execute";
+
+/// Stable, non-reversible fingerprint of an argument list
+///
+/// Arguments can contain secrets or personal paths, so spans carry a hash rather than the
+/// raw values. Arguments are NUL-separated to keep `["a b"]` and `["a", "b"]` distinct.
+pub fn args_hash(args: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for arg in args {
+        hasher.update(arg.as_bytes());
+        hasher.update([0u8]);
+    }
+    hex::encode(&hasher.finalize()[..8])
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+    use opentelemetry_sdk::Resource;
+    use std::sync::OnceLock;
+    use tracing::Subscriber;
+    use tracing_opentelemetry::OpenTelemetryLayer;
+    use tracing_subscriber::registry::LookupSpan;
+
+    static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+    /// Build the OTLP export layer if an endpoint is configured
+    pub fn layer<S>() -> Option<OpenTelemetryLayer<S, SdkTracer>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+
+        let exporter = match opentelemetry_otlp::SpanExporter::builder().with_http().build() {
+            Ok(exporter) => exporter,
+            Err(e) => {
+                eprintln!("Failed to initialize OTLP exporter: {}", e);
+                return None;
+            }
+        };
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(env!("CARGO_PKG_NAME"))
+                    .build(),
+            )
+            .build();
+        let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+        let _ = PROVIDER.set(provider);
+
+        Some(tracing_opentelemetry::layer().with_tracer(tracer))
+    }
+
+    /// Flush pending spans and stop the exporter
+    pub fn shutdown() {
+        if let Some(provider) = PROVIDER.get() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to flush OTLP spans: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+pub use otlp::layer;
+
+/// Flush and stop trace export (no-op without the `otel` feature)
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    otlp::shutdown();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_hash_is_stable_and_separator_aware() {
+        let a = args_hash(&["status".to_string(), "-s".to_string()]);
+        assert_eq!(a, args_hash(&["status".to_string(), "-s".to_string()]));
+        assert_eq!(a.len(), 16);
+        assert_ne!(
+            args_hash(&["a b".to_string()]),
+            args_hash(&["a".to_string(), "b".to_string()])
+        );
+    }
+}
@@ -0,0 +1,372 @@
+// file: src/toolchain.rs
+// version: 1.0.0
+// guid: 2b7d91e4-6c3a-4f08-b5d2-e48a0c17f963
+
+//! Tools downloaded into a managed directory
+//!
+//! `toolchain install buf@1.32.0` downloads a release into `tools/<name>/<version>/` in the
+//! state directory (`~/.safe-ai-util/tools` by default) after checking its SHA-256 against the
+//! checksums the project publishes with the release, or against one given on the command line.
+//! The installed version becomes the active one: `tools/bin/<name>` links to it, and
+//! [`crate::tools::resolve`] prefers that binary over PATH for commands not pinned to a path.
+//! `tools/toolchain.json` records what was installed, from where, and its checksum.
+//!
+//! Releases of the tools the commands use are known by name (see [`release`]); anything else
+//! can be installed from a URL given with its checksum.
+
+use crate::activity::FileLock;
+use crate::error::{AgentError, Result};
+use crate::utils;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory holding the managed tools
+pub fn tools_dir() -> PathBuf {
+    utils::state_dir().join("tools")
+}
+
+/// Directory with the active version of every managed tool
+pub fn bin_dir() -> PathBuf {
+    tools_dir().join("bin")
+}
+
+/// The active managed binary for a command, if one is installed
+pub fn managed_binary(name: &str) -> Option<PathBuf> {
+    let path = bin_dir().join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    path.is_file().then_some(path)
+}
+
+/// Operating system and CPU architecture, as Rust names them (`linux`, `aarch64`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    pub os: &'static str,
+    pub arch: &'static str,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        Self {
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+        }
+    }
+
+    fn exe(self) -> &'static str {
+        if self.os == "windows" {
+            ".exe"
+        } else {
+            ""
+        }
+    }
+}
+
+/// Where the expected checksum of a download comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksums {
+    /// A file listing `<sha256>  <file name>` for every asset of the release
+    File(String),
+    /// Only the checksum given on the command line
+    Given,
+}
+
+/// A downloadable release of a tool for one platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub url: String,
+    pub checksums: Checksums,
+    /// Path of the binary inside the archive, or `None` when the download is the binary
+    pub binary: Option<String>,
+}
+
+impl Release {
+    /// File name of the download, as checksum files list it
+    pub fn file_name(&self) -> &str {
+        self.url.rsplit('/').next().unwrap_or(&self.url)
+    }
+}
+
+/// Tools with a known release location
+pub const KNOWN_TOOLS: &[&str] = &["buf", "golangci-lint", "rg", "shfmt"];
+
+/// The release of a known tool for a platform, or `None` for other tools and platforms
+/// the project does not build for
+pub fn release(name: &str, version: &str, platform: Platform) -> Option<Release> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let exe = platform.exe();
+    let (os, arch) = (platform.os, platform.arch);
+    let github = |repo: &str, tag: &str, file: &str| {
+        format!(
+            "https://github.com/{}/releases/download/{}/{}",
+            repo, tag, file
+        )
+    };
+    match name {
+        "buf" => {
+            let os = match os {
+                "linux" => "Linux",
+                "macos" => "Darwin",
+                "windows" => "Windows",
+                _ => return None,
+            };
+            let arch = match (os, arch) {
+                (_, "x86_64") => "x86_64",
+                ("Linux", "aarch64") => "aarch64",
+                (_, "aarch64") => "arm64",
+                _ => return None,
+            };
+            let tag = format!("v{}", version);
+            Some(Release {
+                url: github("bufbuild/buf", &tag, &format!("buf-{}-{}{}", os, arch, exe)),
+                checksums: Checksums::File(github("bufbuild/buf", &tag, "sha256.txt")),
+                binary: None,
+            })
+        }
+        "shfmt" => {
+            let os = match os {
+                "macos" => "darwin",
+                other => other,
+            };
+            let arch = go_arch(arch)?;
+            let tag = format!("v{}", version);
+            Some(Release {
+                url: github(
+                    "mvdan/sh",
+                    &tag,
+                    &format!("shfmt_{}_{}_{}{}", tag, os, arch, exe),
+                ),
+                checksums: Checksums::File(github("mvdan/sh", &tag, "sha256sums.txt")),
+                binary: None,
+            })
+        }
+        "golangci-lint" => {
+            let os = match os {
+                "macos" => "darwin",
+                other => other,
+            };
+            let arch = go_arch(arch)?;
+            let tag = format!("v{}", version);
+            let stem = format!("golangci-lint-{}-{}-{}", version, os, arch);
+            let ext = if os == "windows" { "zip" } else { "tar.gz" };
+            Some(Release {
+                url: github("golangci/golangci-lint", &tag, &format!("{}.{}", stem, ext)),
+                checksums: Checksums::File(github(
+                    "golangci/golangci-lint",
+                    &tag,
+                    &format!("golangci-lint-{}-checksums.txt", version),
+                )),
+                binary: Some(format!("{}/golangci-lint{}", stem, exe)),
+            })
+        }
+        "rg" => {
+            let target = match (os, arch) {
+                ("linux", "x86_64") => "x86_64-unknown-linux-musl",
+                ("linux", "aarch64") => "aarch64-unknown-linux-gnu",
+                ("macos", "x86_64") => "x86_64-apple-darwin",
+                ("macos", "aarch64") => "aarch64-apple-darwin",
+                ("windows", "x86_64") => "x86_64-pc-windows-msvc",
+                _ => return None,
+            };
+            let stem = format!("ripgrep-{}-{}", version, target);
+            let ext = if os == "windows" { "zip" } else { "tar.gz" };
+            let file = format!("{}.{}", stem, ext);
+            Some(Release {
+                url: github("BurntSushi/ripgrep", version, &file),
+                checksums: Checksums::File(github(
+                    "BurntSushi/ripgrep",
+                    version,
+                    &format!("{}.sha256", file),
+                )),
+                binary: Some(format!("{}/rg{}", stem, exe)),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn go_arch(arch: &str) -> Option<&'static str> {
+    match arch {
+        "x86_64" => Some("amd64"),
+        "aarch64" => Some("arm64"),
+        _ => None,
+    }
+}
+
+/// The checksum a checksum file lists for `file_name`, lowercased; a file holding a single
+/// checksum applies to whatever it was published next to
+pub fn find_checksum(content: &str, file_name: &str) -> Option<String> {
+    let entries: Vec<(&str, Option<&str>)> = content
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let hash = words.next()?;
+            // `sha256sum -b` marks binary files with a leading `*`
+            let name = words.next().map(|name| name.trim_start_matches('*'));
+            (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+                .then_some((hash, name))
+        })
+        .collect();
+    let hash = match entries.as_slice() {
+        [(hash, None)] => Some(*hash),
+        _ => entries
+            .iter()
+            .find(|(_, name)| name.is_some_and(|name| name.rsplit('/').next() == Some(file_name)))
+            .map(|(hash, _)| *hash),
+    }?;
+    Some(hash.to_ascii_lowercase())
+}
+
+/// Make an installed binary the active version of its tool
+pub fn activate(name: &str, binary: &Path) -> Result<PathBuf> {
+    let dir = bin_dir();
+    fs::create_dir_all(&dir)?;
+    let link = dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    if link.symlink_metadata().is_ok() {
+        fs::remove_file(&link)?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(binary, &link)?;
+    #[cfg(not(unix))]
+    fs::copy(binary, &link)?;
+    Ok(link)
+}
+
+/// Remove the active link of a tool
+pub fn deactivate(name: &str) -> Result<()> {
+    let link = bin_dir().join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    if link.symlink_metadata().is_ok() {
+        fs::remove_file(&link)?;
+    }
+    Ok(())
+}
+
+/// A version of a tool in the managed directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledTool {
+    pub name: String,
+    pub version: String,
+    /// The installed binary, in `tools/<name>/<version>/`
+    pub binary: PathBuf,
+    /// Where the download came from
+    pub url: String,
+    /// SHA-256 of the download that was verified
+    pub sha256: String,
+    /// Whether `tools/bin/<name>` points at this version
+    pub active: bool,
+    pub installed_at: DateTime<Utc>,
+}
+
+/// The managed tools, in installation order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Toolchain {
+    pub tools: Vec<InstalledTool>,
+}
+
+impl Toolchain {
+    /// Path of the persisted record
+    pub fn path() -> PathBuf {
+        tools_dir().join("toolchain.json")
+    }
+
+    /// Load the record, starting empty if nothing has been installed yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Load, change and save the record while holding its lock
+    pub fn update<T>(change: impl FnOnce(&mut Self) -> T) -> Result<T> {
+        let path = Self::path();
+        let dir = path
+            .parent()
+            .ok_or_else(|| AgentError::system("Invalid toolchain record path"))?;
+        fs::create_dir_all(dir)?;
+        let _lock = FileLock::acquire(&path.with_extension("lock"))?;
+
+        let mut toolchain = Self::load()?;
+        let result = change(&mut toolchain);
+
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&toolchain)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(result)
+    }
+
+    pub fn find(&self, name: &str, version: &str) -> Option<&InstalledTool> {
+        self.tools
+            .iter()
+            .find(|tool| tool.name == name && tool.version == version)
+    }
+
+    /// Record an installed version as the active one, replacing an earlier record of it
+    pub fn install(&mut self, tool: InstalledTool) {
+        self.tools
+            .retain(|t| !(t.name == tool.name && t.version == tool.version));
+        for other in self.tools.iter_mut().filter(|t| t.name == tool.name) {
+            other.active = false;
+        }
+        self.tools.push(tool);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_releases() {
+        let linux_arm = Platform {
+            os: "linux",
+            arch: "aarch64",
+        };
+        let buf = release("buf", "v1.32.0", linux_arm).unwrap();
+        assert_eq!(
+            buf.url,
+            "https://github.com/bufbuild/buf/releases/download/v1.32.0/buf-Linux-aarch64"
+        );
+        assert_eq!(buf.file_name(), "buf-Linux-aarch64");
+        assert_eq!(buf.binary, None);
+
+        let mac = Platform {
+            os: "macos",
+            arch: "aarch64",
+        };
+        assert!(release("buf", "1.32.0", mac)
+            .unwrap()
+            .url
+            .ends_with("/buf-Darwin-arm64"));
+        let lint = release("golangci-lint", "1.59.1", mac).unwrap();
+        assert_eq!(
+            lint.binary.as_deref(),
+            Some("golangci-lint-1.59.1-darwin-arm64/golangci-lint")
+        );
+        assert!(release("rg", "14.1.0", linux_arm)
+            .unwrap()
+            .url
+            .ends_with("/14.1.0/ripgrep-14.1.0-aarch64-unknown-linux-gnu.tar.gz"));
+        assert!(release("protoc", "27.0", mac).is_none());
+    }
+
+    #[test]
+    fn test_find_checksum() {
+        let hash = "ab".repeat(32);
+        let other = "cd".repeat(32);
+        let listing = format!("{}  buf-Linux-x86_64\n{} *buf-Darwin-arm64\n", hash, other);
+        assert_eq!(
+            find_checksum(&listing, "buf-Linux-x86_64"),
+            Some(hash.clone())
+        );
+        assert_eq!(find_checksum(&listing, "buf-Darwin-arm64"), Some(other));
+        assert_eq!(find_checksum(&listing, "buf-Windows-x86_64.exe"), None);
+        assert_eq!(
+            find_checksum(&hash.to_uppercase(), "anything"),
+            Some(hash.clone())
+        );
+        assert_eq!(find_checksum("not a checksum\n", "buf"), None);
+    }
+}
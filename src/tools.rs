@@ -0,0 +1,365 @@
+// file: src/tools.rs
+// version: 1.6.0
+// guid: 7544f864-d281-4d65-8a9f-5ed561c33349
+
+//! Pinned external tools
+//!
+//! `[tools.<name>]` tables in the configuration pin the binary used for a command and the
+//! minimum version it must report:
+//!
+//! ```toml
+//! [tools.git]
+//! path = "/usr/bin/git"
+//! min_version = "2.40"
+//! ```
+//!
+//! The executor resolves every command through [`resolve`] before spawning it, so a
+//! missing binary, a binary other than the pinned one or an outdated version fails before
+//! anything runs, with a [`CapabilityRequest`] saying how to install what is needed. Commands
+//! run the version installed with `toolchain install` when there is one (see
+//! [`toolchain::managed_binary`]), and the one on PATH otherwise. A command given its own
+//! `PATH` is looked up there instead (see [`resolve_in`]); manifest steps build one from the
+//! tool versions they ask mise for (see [`mise_bin_dir`]). A binary found that way must still
+//! come from a directory of this process's own `PATH` or from a mise install, so a workspace
+//! script cannot stand in for an allowed command.
+
+use crate::capability::CapabilityRequest;
+use crate::config::ToolPin;
+use crate::error::{AgentError, Result};
+use crate::toolchain;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+/// Extract the first dotted version number (e.g. `2.43.0`) from text such as
+/// `git version 2.43.0`
+pub fn parse_version(text: &str) -> Option<Vec<u64>> {
+    let regex = Regex::new(r"\d+(?:\.\d+)+|\d+").unwrap();
+    let found = regex.find(text)?;
+    found
+        .as_str()
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Compare versions component by component, treating missing components as zero
+pub fn compare_versions(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    for i in 0..len {
+        let ordering = a
+            .get(i)
+            .copied()
+            .unwrap_or(0)
+            .cmp(&b.get(i).copied().unwrap_or(0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Check a pin without running anything: the path must be absolute and the minimum
+/// version must be a version number
+pub fn validate_pin(name: &str, pin: &ToolPin) -> Result<()> {
+    if let Some(path) = &pin.path {
+        if !path.is_absolute() {
+            return Err(AgentError::config(format!(
+                "tools.{}.path must be an absolute path, got '{}'",
+                name,
+                path.display()
+            )));
+        }
+    }
+    if let Some(min_version) = &pin.min_version {
+        if parse_version(min_version).is_none() {
+            return Err(AgentError::config(format!(
+                "tools.{}.min_version '{}' is not a version number",
+                name, min_version
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the binary for a command, verifying it against its pin if there is one
+pub fn resolve(name: &str, pin: Option<&ToolPin>) -> Result<PathBuf> {
+    resolve_in(name, pin, None)
+}
+
+/// [`resolve`], searching `search_path` (a `PATH` value) instead of the managed tools and
+/// this process's `PATH`
+///
+/// A command pinned to a path is still looked up, and the binary found must be the pinned one.
+pub fn resolve_in(name: &str, pin: Option<&ToolPin>, search_path: Option<&str>) -> Result<PathBuf> {
+    let min_version = pin.and_then(|p| p.min_version.as_deref());
+    let not_found =
+        |_| AgentError::ToolUnavailable(Box::new(CapabilityRequest::missing(name, min_version)));
+    let pinned = pin.and_then(|p| p.path.as_ref());
+    if let Some(pinned) = pinned {
+        if !pinned.is_file() {
+            return Err(AgentError::config(format!(
+                "{} is pinned to {}, which does not exist",
+                name,
+                pinned.display()
+            )));
+        }
+    }
+    let found = match search_path {
+        Some(search_path) => {
+            let cwd = std::env::current_dir().unwrap_or_default();
+            which::which_in(name, Some(search_path), cwd)
+        }
+        None => match toolchain::managed_binary(name) {
+            Some(path) => Ok(path),
+            None => which::which(name),
+        },
+    };
+    let path = match (pinned, found) {
+        (Some(pinned), Ok(found)) => {
+            let canonical =
+                |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            if canonical(&found) != canonical(pinned) {
+                return Err(AgentError::config(format!(
+                    "{} resolves to {}, but tools.{}.path pins {}",
+                    name,
+                    found.display(),
+                    name,
+                    pinned.display()
+                )));
+            }
+            pinned.clone()
+        }
+        (Some(pinned), Err(_)) => {
+            return Err(AgentError::config(format!(
+                "{} is pinned to {}, but no {} is on PATH",
+                name,
+                pinned.display(),
+                name
+            )));
+        }
+        (None, found) => {
+            let found = found.map_err(not_found)?;
+            if search_path.is_some() && !is_trusted_location(&found) {
+                return Err(AgentError::security(format!(
+                    "{} resolves to {}, which is neither on PATH nor installed with mise",
+                    name,
+                    found.display()
+                )));
+            }
+            found
+        }
+    };
+
+    if let Some(min_version) = min_version {
+        let required = parse_version(min_version).ok_or_else(|| {
+            AgentError::config(format!(
+                "tools.{}.min_version '{}' is not a version number",
+                name, min_version
+            ))
+        })?;
+        let found = tool_version(&path)?;
+        if compare_versions(&found, &required) == Ordering::Less {
+            let message = format!(
+                "{} at {} is version {}, but tools.{}.min_version requires {}",
+                name,
+                path.display(),
+                join_version(&found),
+                name,
+                min_version
+            );
+            return Err(AgentError::ToolUnavailable(Box::new(CapabilityRequest::outdated(
+                name,
+                message,
+                min_version,
+                join_version(&found),
+            ))));
+        }
+        debug!(
+            "{} at {} is version {} (>= {})",
+            name,
+            path.display(),
+            join_version(&found),
+            min_version
+        );
+    }
+
+    Ok(path)
+}
+
+/// Run `<path> --version` and parse the version it reports
+pub fn tool_version(path: &Path) -> Result<Vec<u64>> {
+    let output = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| {
+            AgentError::execution(format!("Failed to run {} --version: {}", path.display(), e))
+        })?;
+    // Some tools (older Python releases among them) print their version on stderr
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    parse_version(&text).ok_or_else(|| {
+        AgentError::config(format!(
+            "Could not determine the version of {} from '{}'",
+            path.display(),
+            text.trim()
+        ))
+    })
+}
+
+/// The `bin` directory of a tool version installed with mise, from `mise where tool@version`
+pub fn mise_bin_dir(tool: &str, version: &str) -> Result<PathBuf> {
+    let mise = which::which("mise").map_err(|_| {
+        AgentError::execution(format!(
+            "{}@{} needs mise, which is not installed",
+            tool, version
+        ))
+    })?;
+    let output = Command::new(mise)
+        .args(["where", &format!("{}@{}", tool, version)])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| AgentError::execution(format!("Failed to run mise where: {}", e)))?;
+    let dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    if !output.status.success() || !dir.is_absolute() || !dir.is_dir() {
+        return Err(AgentError::execution(format!(
+            "{}@{} is not installed; run 'mise install {}@{}'",
+            tool, version, tool, version
+        )));
+    }
+    let bin = dir.join("bin");
+    Ok(if bin.is_dir() { bin } else { dir })
+}
+
+/// Where mise keeps installed tool versions: `installs` under `MISE_DATA_DIR`, or under
+/// `mise` in the user's data directory
+pub fn mise_installs_dir() -> Option<PathBuf> {
+    std::env::var_os("MISE_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::data_dir().map(|dir| dir.join("mise")))
+        .map(|dir| dir.join("installs"))
+}
+
+/// Whether a binary lives in a directory of this process's own `PATH` or inside a mise
+/// install, the only places a command given its own `PATH` may be taken from
+pub fn is_trusted_location(program: &Path) -> bool {
+    let canonical = |dir: &Path| dir.canonicalize().ok();
+    let Some(dir) = program.parent().and_then(canonical) else {
+        return false;
+    };
+    let on_path = std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path)
+            .filter(|entry| entry.is_absolute())
+            .any(|entry| canonical(&entry).as_ref() == Some(&dir))
+    });
+    on_path
+        || mise_installs_dir()
+            .and_then(|installs| canonical(&installs))
+            .is_some_and(|installs| dir.starts_with(installs))
+}
+
+/// Format a parsed version as dotted text
+pub fn join_version(version: &[u64]) -> String {
+    version
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(parse_version("git version 2.43.0"), Some(vec![2, 43, 0]));
+        assert_eq!(parse_version("Python 3.12.1\n"), Some(vec![3, 12, 1]));
+        assert_eq!(parse_version("buf 1"), Some(vec![1]));
+        assert_eq!(parse_version("no digits"), None);
+
+        assert_eq!(compare_versions(&[2, 40], &[2, 40, 0]), Ordering::Equal);
+        assert_eq!(compare_versions(&[2, 9], &[2, 40]), Ordering::Less);
+        assert_eq!(compare_versions(&[3], &[2, 99, 1]), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_validate_pin() {
+        let pin = |path: Option<&str>, min: Option<&str>| ToolPin {
+            path: path.map(PathBuf::from),
+            min_version: min.map(str::to_string),
+            ..Default::default()
+        };
+        assert!(validate_pin("git", &pin(None, Some("2.40"))).is_ok());
+        assert!(validate_pin("git", &pin(Some("bin/git"), None)).is_err());
+        assert!(validate_pin("git", &pin(None, Some("latest"))).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_pinned_tool() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let tool = dir.path().join("fake-tool");
+        std::fs::write(&tool, "#!/bin/sh\necho 'fake-tool version 1.4.2'\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let pin = |min: &str| ToolPin {
+            path: Some(tool.clone()),
+            min_version: Some(min.to_string()),
+            ..Default::default()
+        };
+        let search_path = dir.path().display().to_string();
+        let resolve = |pin: &ToolPin| resolve_in("fake-tool", Some(pin), Some(&search_path));
+        assert_eq!(resolve(&pin("1.4")).unwrap(), tool);
+        let error = resolve(&pin("1.5")).unwrap_err();
+        assert!(error.to_string().contains("is version 1.4.2"));
+        let AgentError::ToolUnavailable(request) = error else {
+            panic!("expected a capability request");
+        };
+        assert_eq!(request.install, "safe-ai-util tools install fake-tool@1.5");
+
+        let missing = ToolPin {
+            path: Some(dir.path().join("missing")),
+            min_version: None,
+            ..Default::default()
+        };
+        assert!(resolve(&missing).is_err());
+
+        // The binary on PATH must be the pinned one
+        let other = tempfile::TempDir::new().unwrap();
+        std::fs::copy(&tool, other.path().join("fake-tool")).unwrap();
+        let other_path = other.path().display().to_string();
+        let error = resolve_in("fake-tool", Some(&pin("1.4")), Some(&other_path)).unwrap_err();
+        assert!(error.to_string().contains("but tools.fake-tool.path pins"));
+        let error = resolve_in("fake-tool", Some(&pin("1.4")), Some("/nonexistent")).unwrap_err();
+        assert!(error.to_string().contains("but no fake-tool is on PATH"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_search_path_only_trusts_path_and_mise() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let tool = dir.path().join("sh");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let system = std::env::var("PATH").unwrap();
+        let search_path = format!("{}:{}", dir.path().display(), system);
+        let error = resolve_in("sh", None, Some(&search_path)).unwrap_err();
+        assert!(matches!(error, AgentError::Security(_)));
+        assert!(!is_trusted_location(&tool));
+
+        let found = resolve_in("sh", None, Some(&system)).unwrap();
+        assert!(is_trusted_location(&found));
+    }
+}
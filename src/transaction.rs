@@ -0,0 +1,207 @@
+// file: src/transaction.rs
+// version: 1.0.2
+// guid: 7c2f5a18-e94b-4d36-8a01-5b9d3e6f2c47
+
+//! Transactions: file-modifying steps that succeed or are undone together
+//!
+//! Beginning a transaction copies every file in its [`Scope`] to `transactions/<id>/` in the
+//! state directory. When a step fails, rolling back restores the files the steps modified or
+//! deleted from those copies and removes the files they created, so the watched paths are as
+//! they were before the first step; directories the steps created are left in place, empty.
+//! Committing or rolling back removes the copies. A backup left behind by a process that
+//! was killed is kept for inspection.
+
+use crate::error::Result;
+use crate::snapshot::{Changes, Scope, Snapshot};
+use crate::utils;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use tracing::debug;
+use uuid::Uuid;
+
+/// Files backed up before a group of steps runs
+#[derive(Debug)]
+pub struct Transaction {
+    id: String,
+    /// Directory holding the copies
+    dir: PathBuf,
+    before: Snapshot,
+}
+
+impl Transaction {
+    /// Back up the files in `scope`
+    pub fn begin(scope: &Scope) -> Result<Self> {
+        Self::begin_in(&utils::state_dir().join("transactions"), scope)
+    }
+
+    /// Back up the files in `scope` to a new directory in `dir`
+    pub fn begin_in(dir: &Path, scope: &Scope) -> Result<Self> {
+        let id = Uuid::new_v4().to_string();
+        let transaction = Self {
+            dir: dir.join(&id),
+            id,
+            before: scope.snapshot(),
+        };
+        let copied = transaction.before.paths().try_for_each(|path| {
+            let backup = transaction.backup_path(path);
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(transaction.before.resolve(path), backup).map(|_| ())
+        });
+        // A partial backup could never be used, so it is not left behind
+        if let Err(e) = copied {
+            let _ = transaction.remove_backup();
+            return Err(e.into());
+        }
+        debug!(
+            "Transaction {} backed up {} files to {}",
+            transaction.id,
+            transaction.before.paths().count(),
+            transaction.dir.display()
+        );
+        Ok(transaction)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// What the steps changed so far
+    pub fn changes(&self) -> Changes {
+        self.before.changes(&self.before.retake())
+    }
+
+    /// Keep the changes and remove the backup; returns what the steps changed
+    pub fn commit(self) -> Result<Changes> {
+        let changes = self.changes();
+        self.remove_backup()?;
+        Ok(changes)
+    }
+
+    /// Undo the changes and remove the backup; returns what was undone
+    pub fn rollback(self) -> Result<Changes> {
+        let changes = self.changes();
+        for path in &changes.created {
+            match fs::remove_file(self.before.resolve(path)) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        for path in changes.modified.iter().chain(&changes.deleted) {
+            let target = self.before.resolve(path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(self.backup_path(path), target)?;
+        }
+        self.remove_backup()?;
+        Ok(changes)
+    }
+
+    /// Where the copy of a file in the snapshot is kept
+    fn backup_path(&self, path: &Path) -> PathBuf {
+        // Files outside the base are reported with absolute paths
+        let relative: PathBuf = path
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect();
+        self.dir.join("files").join(relative)
+    }
+
+    fn remove_backup(&self) -> Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollback_restores_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn a() {}\n").unwrap();
+        fs::write(root.join("old.txt"), "old\n").unwrap();
+        let scope = Scope {
+            base: root.to_path_buf(),
+            roots: vec![root.to_path_buf()],
+            excluded: Vec::new(),
+            include_ignored: false,
+        };
+
+        let transaction = Transaction::begin_in(state.path(), &scope).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn a() { broken\n").unwrap();
+        fs::remove_file(root.join("old.txt")).unwrap();
+        fs::write(root.join("src/new.rs"), "\n").unwrap();
+        let undone = transaction.rollback().unwrap();
+
+        assert_eq!(undone.created, vec![PathBuf::from("src/new.rs")]);
+        assert_eq!(undone.modified, vec![PathBuf::from("src/lib.rs")]);
+        assert_eq!(undone.deleted, vec![PathBuf::from("old.txt")]);
+        assert_eq!(
+            fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+            "fn a() {}\n"
+        );
+        assert_eq!(fs::read_to_string(root.join("old.txt")).unwrap(), "old\n");
+        assert!(!root.join("src/new.rs").exists());
+        assert_eq!(fs::read_dir(state.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_commit_keeps_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a\n").unwrap();
+        let scope = Scope {
+            base: root.to_path_buf(),
+            roots: vec![root.to_path_buf()],
+            excluded: Vec::new(),
+            include_ignored: false,
+        };
+
+        let transaction = Transaction::begin_in(state.path(), &scope).unwrap();
+        fs::write(root.join("a.txt"), "b\n").unwrap();
+        let changes = transaction.commit().unwrap();
+
+        assert_eq!(changes.modified, vec![PathBuf::from("a.txt")]);
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "b\n");
+        assert_eq!(fs::read_dir(state.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_failed_backup_is_removed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let outside = tempfile::TempDir::new().unwrap();
+        let state = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        fs::write(outside.path().join("a.txt"), "a\n").unwrap();
+        // A file named like the first component of the outside root's absolute path takes
+        // the place of the directory its backup needs
+        let first = outside
+            .path()
+            .components()
+            .find_map(|component| match component {
+                Component::Normal(name) => Some(name.to_owned()),
+                _ => None,
+            });
+        fs::write(root.join(first.unwrap()), "\n").unwrap();
+        let scope = Scope {
+            base: root.to_path_buf(),
+            roots: vec![root.to_path_buf(), outside.path().to_path_buf()],
+            excluded: Vec::new(),
+            include_ignored: false,
+        };
+
+        assert!(Transaction::begin_in(state.path(), &scope).is_err());
+        assert_eq!(fs::read_dir(state.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,317 @@
+// file: src/transcript.rs
+// version: 1.1.0
+// guid: 4f8a2d61-c39e-4b07-9e15-a7d0b3c82f46
+
+//! Session transcripts
+//!
+//! A transcript shows what a session did to a repository: the commands it ran with their
+//! output, taken from the execution history, and the diffs of the files it touched. Diffs need
+//! a recording: `sessions record` saves the state of the git work tree for the session in
+//! `transcripts/<session>.json` in the state directory (a `git stash create` commit, or `HEAD`
+//! when the tree is clean, plus the untracked files), and an export diffs the work tree
+//! against it. Files that were untracked when the recording started are not diffed.
+//!
+//! Transcripts render as Markdown for people and as JSON for tools.
+
+use crate::error::Result;
+use crate::history::{self, Run};
+use crate::utils;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Largest new file whose content is shown in a transcript
+const MAX_NEW_FILE_BYTES: usize = 256 * 1024;
+
+/// Where a session's recording started
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    pub session_id: String,
+    pub started_at: DateTime<Utc>,
+    /// Top level of the git work tree
+    pub workspace: PathBuf,
+    /// Commit holding the work tree as it was, or `None` outside a repository with commits
+    pub base: Option<String>,
+    /// Untracked files, relative to the workspace, when the recording started
+    #[serde(default)]
+    pub untracked: Vec<String>,
+    /// Invocation that started the recording, whose own git commands are left out
+    pub execution_id: String,
+}
+
+impl Recording {
+    /// File of the recording for `session_id`
+    pub fn path(session_id: &str) -> PathBuf {
+        utils::state_dir()
+            .join("transcripts")
+            .join(format!("{}.json", session_id))
+    }
+
+    /// The recording for `session_id`, if one was started
+    pub fn load(session_id: &str) -> Result<Option<Self>> {
+        let path = Self::path(session_id);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    /// Save the recording, replacing an earlier one for the same session
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path(&self.session_id);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// How a file changed during a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A file the session touched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    /// Path relative to the workspace
+    pub path: String,
+    pub status: ChangeStatus,
+    /// Unified diff of the change; empty when the content is unknown
+    pub diff: String,
+}
+
+/// A command the session ran, with its captured output
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptCommand {
+    #[serde(flatten)]
+    pub run: Run,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// What a session did
+#[derive(Debug, Clone, Serialize)]
+pub struct Transcript {
+    pub session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<PathBuf>,
+    /// Start of the recording; without one every recorded command is included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recorded_since: Option<DateTime<Utc>>,
+    pub exported_at: DateTime<Utc>,
+    /// Oldest first
+    pub commands: Vec<TranscriptCommand>,
+    /// `None` without a recording to diff against
+    pub changes: Option<Vec<FileChange>>,
+}
+
+impl Transcript {
+    /// Render the transcript as Markdown
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Session transcript\n\n");
+        out.push_str(&format!("- Session: `{}`\n", self.session_id));
+        if let Some(workspace) = &self.workspace {
+            out.push_str(&format!("- Workspace: `{}`\n", workspace.display()));
+        }
+        if let Some(since) = self.recorded_since {
+            out.push_str(&format!("- Recorded since: {}\n", format_time(since)));
+        }
+        out.push_str(&format!("- Exported: {}\n", format_time(self.exported_at)));
+        let failed = self.commands.iter().filter(|c| c.run.failed()).count();
+        out.push_str(&format!(
+            "- Commands: {} ({} failed)\n",
+            self.commands.len(),
+            failed
+        ));
+        if let Some(changes) = &self.changes {
+            out.push_str(&format!("- Files changed: {}\n", changes.len()));
+        }
+
+        out.push_str("\n## Commands\n");
+        if self.commands.is_empty() {
+            out.push_str("\nNo commands were recorded.\n");
+        }
+        for (i, command) in self.commands.iter().enumerate() {
+            let run = &command.run;
+            out.push_str(&format!("\n### {}. `{}`\n\n", i + 1, run.command_line()));
+            out.push_str(&format!(
+                "Exit code {} after {}, started {} in `{}`\n",
+                run.exit_code
+                    .map_or_else(|| "-".to_string(), |code| code.to_string()),
+                history::format_duration(run.duration_ms),
+                format_time(run.started_at),
+                run.cwd.display()
+            ));
+            if let Some(reason) = &run.reason {
+                out.push_str(&format!("\nReason: {}\n", reason));
+            }
+            if !run.labels.is_empty() {
+                out.push_str(&format!("\nLabels: {}\n", run.labels.join(", ")));
+            }
+            if let Some(error) = &run.error {
+                out.push_str(&format!("\nError: {}\n", error));
+            }
+            if let Some(output) = command.output.as_deref().filter(|o| !o.is_empty()) {
+                out.push('\n');
+                out.push_str(&fenced("text", output));
+            }
+        }
+
+        out.push_str("\n## Changes\n");
+        match &self.changes {
+            None => out.push_str(
+                "\nNo recording was started for this session, so changes are unknown; run \
+                 `sessions record` at the start of a session to capture them.\n",
+            ),
+            Some(changes) if changes.is_empty() => out.push_str("\nNo files changed.\n"),
+            Some(changes) => {
+                for change in changes {
+                    let status = match change.status {
+                        ChangeStatus::Added => "added",
+                        ChangeStatus::Modified => "modified",
+                        ChangeStatus::Deleted => "deleted",
+                    };
+                    out.push_str(&format!("\n### `{}` ({})\n\n", change.path, status));
+                    if change.diff.is_empty() {
+                        out.push_str("Content was not recorded.\n");
+                    } else {
+                        out.push_str(&fenced("diff", &change.diff));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Split `git diff --name-status -z` and the matching patch into per-file changes
+///
+/// Both must come from the same diff with renames disabled, so the patch has one
+/// `diff --git` section per file, in the same order.
+pub fn parse_changes(name_status: &str, patch: &str) -> Vec<FileChange> {
+    let mut sections = Vec::new();
+    for line in patch.split_inclusive('\n') {
+        if line.starts_with("diff --git ") || sections.is_empty() {
+            sections.push(String::new());
+        }
+        if let Some(section) = sections.last_mut() {
+            section.push_str(line);
+        }
+    }
+    let mut sections = sections.into_iter();
+
+    let fields: Vec<&str> = name_status.split('\0').filter(|f| !f.is_empty()).collect();
+    fields
+        .chunks(2)
+        .filter_map(|entry| {
+            let [status, path] = entry else {
+                return None;
+            };
+            let status = match status.chars().next() {
+                Some('A') => ChangeStatus::Added,
+                Some('D') => ChangeStatus::Deleted,
+                _ => ChangeStatus::Modified,
+            };
+            Some(FileChange {
+                path: path.to_string(),
+                status,
+                diff: sections.next().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// A file created during the session that git does not track, shown as an addition
+pub fn added_file(workspace: &Path, path: &str) -> FileChange {
+    let diff = match fs::read(workspace.join(path)) {
+        Ok(content) if content.len() > MAX_NEW_FILE_BYTES => String::new(),
+        Ok(content) if content.contains(&0) => {
+            format!("Binary files /dev/null and b/{} differ\n", path)
+        }
+        Ok(content) => {
+            let text = String::from_utf8_lossy(&content);
+            let lines: Vec<&str> = text.lines().collect();
+            let mut diff = format!(
+                "--- /dev/null\n+++ b/{}\n@@ -0,0 +1,{} @@\n",
+                path,
+                lines.len()
+            );
+            for line in lines {
+                diff.push('+');
+                diff.push_str(line);
+                diff.push('\n');
+            }
+            diff
+        }
+        Err(_) => String::new(),
+    };
+    FileChange {
+        path: path.to_string(),
+        status: ChangeStatus::Added,
+        diff,
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+/// `content` in a code fence longer than any run of backticks inside it
+fn fenced(language: &str, content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    let newline = if content.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}\n{}{}{}\n", fence, language, content, newline, fence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_changes() {
+        let name_status = "D\0old.txt\0M\0src/lib.rs\0";
+        let patch = "diff --git a/old.txt b/old.txt\ndeleted file mode 100644\n-gone\n\
+                     diff --git a/src/lib.rs b/src/lib.rs\n-a\n+b\n";
+        let changes = parse_changes(name_status, patch);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].status, ChangeStatus::Deleted);
+        assert_eq!(
+            changes[0].diff,
+            "diff --git a/old.txt b/old.txt\ndeleted file mode 100644\n-gone\n"
+        );
+        assert_eq!(changes[1].path, "src/lib.rs");
+        assert_eq!(changes[1].status, ChangeStatus::Modified);
+        assert_eq!(
+            changes[1].diff,
+            "diff --git a/src/lib.rs b/src/lib.rs\n-a\n+b\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_fences_output() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("notes.md"), "```\ncode\n```\n").unwrap();
+        let transcript = Transcript {
+            session_id: "s-1".to_string(),
+            workspace: None,
+            recorded_since: None,
+            exported_at: Utc::now(),
+            commands: Vec::new(),
+            changes: Some(vec![added_file(dir.path(), "notes.md")]),
+        };
+        let markdown = transcript.to_markdown();
+        assert!(markdown.contains("- Commands: 0 (0 failed)"));
+        assert!(markdown.contains("### `notes.md` (added)"));
+        assert!(
+            markdown.contains("````diff\n--- /dev/null\n+++ b/notes.md\n@@ -0,0 +1,3 @@\n+```\n")
+        );
+    }
+}
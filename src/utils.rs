@@ -1,11 +1,29 @@
 // file: src/utils.rs
-// version: 1.0.0
+// version: 1.4.0
 // guid: 0784e6f5-a659-4507-bd5d-dd33b38f6974
 
 //! Utility functions for the Copilot Agent Utility
 
 use crate::error::{AgentError, Result};
-use std::path::Path;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Environment variable that overrides the state directory
+pub const STATE_DIR_ENV: &str = "SAFE_AI_STATE_DIR";
+
+/// Directory for state that persists across invocations (metrics, history, jobs)
+///
+/// Defaults to `~/.safe-ai-util` and can be overridden with `SAFE_AI_STATE_DIR`.
+pub fn state_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var(STATE_DIR_ENV) {
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".safe-ai-util")
+}
 
 /// Validate that a path is safe to operate on
 pub fn validate_path(path: &Path) -> Result<()> {
@@ -23,6 +41,21 @@ pub fn validate_path(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Ask a yes/no question at the terminal, defaulting to no; without a terminal to ask on,
+/// fail and point at `--yes`
+pub fn confirm(prompt: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Err(AgentError::validation(
+            "Confirmation required; re-run with --yes to proceed",
+        ));
+    }
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
 /// Sanitize a string for safe use in commands
 pub fn sanitize_string(input: &str) -> String {
     // Remove or escape potentially dangerous characters
@@ -32,6 +65,27 @@ pub fn sanitize_string(input: &str) -> String {
         .collect()
 }
 
+/// Format a byte count with a binary unit, e.g. `1.5 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Compare secrets such as tokens in time that does not depend on where they differ
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,6 +103,13 @@ mod tests {
         assert!(validate_path(&path).is_err());
     }
 
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(4 * 1024 * 1024 * 1024), "4.0 GiB");
+    }
+
     #[test]
     fn test_sanitize_string() {
         let input = "test-file.txt; rm -rf /";
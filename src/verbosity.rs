@@ -0,0 +1,198 @@
+// file: src/verbosity.rs
+// version: 1.0.0
+// guid: 3a9d6e12-5c4b-4f87-b1e0-7d28c9f4a603
+
+//! Verbose re-runs of failed commands
+//!
+//! With `execution.escalate_on_failure` (or `--escalate`), a command that exits with a
+//! non-zero status is run once more with the flags that make its tool explain itself, and the
+//! output of that run is attached to the failed run in history. The flags come from
+//! `tools.<name>.verbose_args` when set, and from the table below otherwise:
+//!
+//! ```toml
+//! [tools.make]
+//! verbose_args = ["--debug=b"]
+//! ```
+//!
+//! Only subcommands that are safe to repeat are listed, so a failed `git push` or
+//! `npm publish` is never sent twice.
+
+use crate::config::ToolPin;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// How a tool is made verbose
+struct Entry {
+    command: &'static str,
+    /// Subcommands the flags apply to, given as the first argument; empty for tools without
+    /// subcommands, whose flags go first
+    subcommands: &'static [&'static str],
+    args: &'static [&'static str],
+    env: &'static [(&'static str, &'static str)],
+}
+
+const KNOWLEDGE_BASE: &[Entry] = &[
+    Entry {
+        command: "buf",
+        subcommands: &["build", "lint", "breaking", "generate", "format"],
+        args: &["--debug"],
+        env: &[],
+    },
+    Entry {
+        command: "cargo",
+        subcommands: &["build", "check", "test", "clippy", "doc", "fetch"],
+        args: &["-vv"],
+        env: &[],
+    },
+    Entry {
+        command: "eslint",
+        subcommands: &[],
+        args: &["--debug"],
+        env: &[],
+    },
+    Entry {
+        command: "git",
+        subcommands: &["fetch", "pull", "ls-remote", "clone"],
+        args: &[],
+        env: &[("GIT_TRACE", "1"), ("GIT_CURL_VERBOSE", "1")],
+    },
+    Entry {
+        command: "go",
+        subcommands: &["build", "test", "vet", "generate"],
+        args: &["-v", "-x"],
+        env: &[],
+    },
+    Entry {
+        command: "golangci-lint",
+        subcommands: &["run"],
+        args: &["-v"],
+        env: &[],
+    },
+    Entry {
+        command: "mypy",
+        subcommands: &[],
+        args: &["-v"],
+        env: &[],
+    },
+    Entry {
+        command: "npm",
+        subcommands: &["install", "ci", "test", "run", "pack"],
+        args: &["--loglevel", "verbose"],
+        env: &[],
+    },
+    Entry {
+        command: "pip",
+        subcommands: &["install", "download", "wheel"],
+        args: &["-v"],
+        env: &[],
+    },
+    Entry {
+        command: "prettier",
+        subcommands: &[],
+        args: &["--log-level", "debug"],
+        env: &[],
+    },
+    Entry {
+        command: "pytest",
+        subcommands: &[],
+        args: &["-vv"],
+        env: &[],
+    },
+    Entry {
+        command: "ruff",
+        subcommands: &["check", "format"],
+        args: &["-v"],
+        env: &[],
+    },
+    Entry {
+        command: "terraform",
+        subcommands: &["init", "validate", "plan"],
+        args: &[],
+        env: &[("TF_LOG", "DEBUG")],
+    },
+];
+
+/// Arguments and environment for the verbose re-run of a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Escalation {
+    pub args: Vec<String>,
+    /// Variables set on top of the command's environment
+    pub env: BTreeMap<String, String>,
+}
+
+/// How to re-run `command` with `args` verbosely, or `None` if the tool or subcommand is not
+/// known
+///
+/// `tools.<name>.verbose_args` are added before a `--` separator, or at the end; flags from the
+/// built-in table follow the subcommand.
+pub fn escalate(command: &str, args: &[String], pin: Option<&ToolPin>) -> Option<Escalation> {
+    if let Some(verbose_args) = pin.map(|pin| &pin.verbose_args).filter(|a| !a.is_empty()) {
+        let at = args
+            .iter()
+            .position(|arg| arg == "--")
+            .unwrap_or(args.len());
+        let mut escalated = args.to_vec();
+        escalated.splice(at..at, verbose_args.iter().cloned());
+        return Some(Escalation {
+            args: escalated,
+            env: BTreeMap::new(),
+        });
+    }
+
+    let name = Path::new(command).file_name()?.to_str()?;
+    let entry = KNOWLEDGE_BASE.iter().find(|entry| entry.command == name)?;
+    let at = if entry.subcommands.is_empty() {
+        0
+    } else {
+        let subcommand = args.first()?;
+        if !entry.subcommands.contains(&subcommand.as_str()) {
+            return None;
+        }
+        1
+    };
+    let mut escalated = args.to_vec();
+    escalated.splice(at..at, entry.args.iter().map(|arg| arg.to_string()));
+    Some(Escalation {
+        args: escalated,
+        env: entry
+            .env
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_escalate() {
+        let cargo = escalate("cargo", &strings(&["test", "--", "--nocapture"]), None).unwrap();
+        assert_eq!(cargo.args, strings(&["test", "-vv", "--", "--nocapture"]));
+        assert!(cargo.env.is_empty());
+
+        let git = escalate("/usr/bin/git", &strings(&["fetch", "origin"]), None).unwrap();
+        assert_eq!(git.args, strings(&["fetch", "origin"]));
+        assert_eq!(git.env["GIT_TRACE"], "1");
+
+        let pytest = escalate("pytest", &strings(&["tests/"]), None).unwrap();
+        assert_eq!(pytest.args, strings(&["-vv", "tests/"]));
+
+        // Subcommands that change remote state are not repeated
+        assert_eq!(escalate("git", &strings(&["push"]), None), None);
+        assert_eq!(escalate("cargo", &[], None), None);
+        assert_eq!(escalate("ls", &strings(&["-l"]), None), None);
+
+        let pin = ToolPin {
+            verbose_args: strings(&["--debug=b"]),
+            ..Default::default()
+        };
+        let make = escalate("make", &strings(&["all", "--", "x"]), Some(&pin)).unwrap();
+        assert_eq!(make.args, strings(&["all", "--debug=b", "--", "x"]));
+    }
+}
@@ -0,0 +1,351 @@
+// file: src/workspace_lock.rs
+// version: 1.0.19
+// guid: 4b81e6d0-2f3a-4c97-a5d8-916e0c7b2f53
+
+//! Advisory workspace lock serializing mutating commands
+//!
+//! Two agents running `buf generate` or `git rebase` in the same checkout at once corrupt each
+//! other's output. Commands listed in [`MUTATING`] take `.safe-ai-util.lock` at the workspace
+//! root before they run and remove it when they finish. The file names the holder (process,
+//! session, agent and command), so whoever waits can tell what they are waiting for.
+//!
+//! A second mutating command waits for the lock up to `lock.timeout_seconds` and then fails.
+//! A lock whose process has exited is taken over with a warning, and `--steal-lock` takes over
+//! a live one. Invocations nested in the holder (the steps of a `transaction` or `batch` that
+//! run the utility again) inherit the lock through `SAFE_AI_LOCK_OWNER` instead of waiting
+//! for themselves. The lock is advisory: tools run outside the utility do not see it.
+
+use crate::activity::AGENT_ENV;
+use crate::error::{AgentError, Result};
+use crate::{process, session};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Name of the lock file at the workspace root
+pub const LOCK_FILE: &str = ".safe-ai-util.lock";
+
+/// Environment variable handing the execution ID of the lock holder to nested invocations
+pub const LOCK_OWNER_ENV: &str = "SAFE_AI_LOCK_OWNER";
+
+/// Commands that take the lock, with every subcommand of the ones listed
+pub const MUTATING: &[&str] = &[
+    "batch",
+    "buf generate",
+    "buf format",
+    "buf dep update",
+    "buf mod init",
+    "cargo fmt",
+    "deps",
+    "env import-profile",
+    "file rename",
+    "file move-dir",
+    "file write",
+    "file sync",
+    "generated clean",
+    "generated record",
+    "gh pr checkout",
+    "git add",
+    "git commit",
+    "git pull",
+    "git checkout",
+    "git merge",
+    "git rebase",
+    "git reset",
+    "git stash",
+    "git stack",
+    "git merge-driver install",
+    "go mod",
+    "go generate",
+    "http download",
+    "json set",
+    "json merge",
+    "jvm spotless-apply",
+    "just run",
+    "make run",
+    "node install",
+    "node ci",
+    "pipe",
+    "pkg install",
+    "pre-commit run",
+    "pre-commit install",
+    "pre-commit autoupdate",
+    "prettier",
+    "process",
+    "publish cargo",
+    "publish npm",
+    "publish pypi",
+    "python venv",
+    "python pip",
+    "replay",
+    "run",
+    "scaffold",
+    "scripts fmt",
+    "sed",
+    "snapshot test",
+    "terraform init",
+    "terraform plan",
+    "terraform apply",
+    "toml set",
+    "toml delete",
+    "transaction",
+    "yaml set",
+    "yaml delete",
+];
+
+/// Seconds after which a lock file that cannot be read is considered abandoned
+const UNREADABLE_LOCK_SECS: u64 = 10;
+
+/// Interval between attempts while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Execution ID handed to child processes while the lock is held
+static OWNER: OnceLock<String> = OnceLock::new();
+
+/// Who holds the lock
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockHolder {
+    pub pid: u32,
+    pub session_id: String,
+    pub execution_id: String,
+    /// Agent from `SAFE_AI_AGENT`, if set
+    pub agent: Option<String>,
+    pub user: Option<String>,
+    pub command: String,
+    pub acquired_at: DateTime<Utc>,
+}
+
+impl LockHolder {
+    fn current(command: &str) -> Self {
+        let session = session::current();
+        Self {
+            pid: std::process::id(),
+            session_id: session.session_id().to_string(),
+            execution_id: session.execution_id().to_string(),
+            agent: std::env::var(AGENT_ENV).ok().filter(|agent| !agent.is_empty()),
+            user: std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok(),
+            command: command.to_string(),
+            acquired_at: Utc::now(),
+        }
+    }
+
+    /// Command, agent and process, for messages
+    pub fn describe(&self) -> String {
+        let mut who = vec![format!("session {}", &self.session_id[..self.session_id.len().min(8)])];
+        who.extend(self.agent.clone());
+        who.extend(self.user.clone());
+        who.push(format!("pid {}", self.pid));
+        format!(
+            "`{}` ({}) since {}",
+            self.command,
+            who.join(", "),
+            self.acquired_at.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
+}
+
+/// How to deal with a lock held by someone else
+#[derive(Debug, Clone, Copy)]
+pub struct LockOptions {
+    /// How long to wait for the holder to finish; zero fails at once
+    pub timeout: Duration,
+    /// Take the lock over from a live holder
+    pub steal: bool,
+}
+
+/// Whether a command path such as `git rebase` takes the lock
+pub fn is_mutating(command: &str) -> bool {
+    MUTATING.iter().any(|entry| {
+        command == *entry
+            || command
+                .strip_prefix(entry)
+                .is_some_and(|rest| rest.starts_with(' '))
+    })
+}
+
+/// Environment variable handing the lock to child processes, while it is held
+pub fn child_env() -> Option<(&'static str, &'static str)> {
+    OWNER.get().map(|owner| (LOCK_OWNER_ENV, owner.as_str()))
+}
+
+/// The workspace lock, released when dropped
+#[derive(Debug)]
+pub struct WorkspaceLock {
+    path: PathBuf,
+    /// Execution ID written to the file; `None` when the lock is inherited from a parent
+    owner: Option<String>,
+}
+
+impl WorkspaceLock {
+    /// Take the lock of the workspace at `base` for `command`
+    pub async fn acquire(base: &Path, command: &str, options: LockOptions) -> Result<Self> {
+        let path = base.join(LOCK_FILE);
+        let inherited = std::env::var(LOCK_OWNER_ENV).ok();
+        let started = Instant::now();
+        let mut announced = false;
+        loop {
+            let holder = LockHolder::current(command);
+            match create(&path, &holder) {
+                Ok(()) => {
+                    debug!("Took the workspace lock {}", path.display());
+                    let _ = OWNER.set(holder.execution_id.clone());
+                    return Ok(Self {
+                        path,
+                        owner: Some(holder.execution_id),
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            match Self::holder_at(&path) {
+                Some(current) if inherited.as_deref() == Some(current.execution_id.as_str()) => {
+                    debug!("Workspace lock inherited from {}", current.describe());
+                    let _ = OWNER.set(current.execution_id);
+                    return Ok(Self { path, owner: None });
+                }
+                Some(current) if !process::is_alive(current.pid) => {
+                    warn!("Taking over the workspace lock of exited process: {}", current.describe());
+                    remove_if_held_by(&path, &current.execution_id);
+                    continue;
+                }
+                Some(current) if options.steal => {
+                    warn!("Stealing the workspace lock held by {}", current.describe());
+                    remove_if_held_by(&path, &current.execution_id);
+                    continue;
+                }
+                Some(current) => {
+                    if started.elapsed() >= options.timeout {
+                        return Err(AgentError::timeout(format!(
+                            "The workspace lock {} is held by {}; wait with --lock-timeout or take it over with --steal-lock",
+                            path.display(),
+                            current.describe()
+                        )));
+                    }
+                    if !announced {
+                        eprintln!(
+                            "Waiting up to {}s for the workspace lock held by {}",
+                            options.timeout.as_secs(),
+                            current.describe()
+                        );
+                        announced = true;
+                    }
+                }
+                None => {
+                    // Being written, or left unreadable by a process that died while writing it
+                    let abandoned = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age.as_secs() >= UNREADABLE_LOCK_SECS);
+                    if abandoned {
+                        warn!("Removing unreadable workspace lock {}", path.display());
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if started.elapsed() >= options.timeout {
+                        return Err(AgentError::timeout(format!(
+                            "The workspace lock {} cannot be read",
+                            path.display()
+                        )));
+                    }
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Who holds the lock of the workspace at `base`, if anyone
+    pub fn holder(base: &Path) -> Option<LockHolder> {
+        Self::holder_at(&base.join(LOCK_FILE))
+    }
+
+    fn holder_at(path: &Path) -> Option<LockHolder> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+impl Drop for WorkspaceLock {
+    fn drop(&mut self) {
+        // A lock stolen in the meantime belongs to the thief
+        if let Some(owner) = &self.owner {
+            remove_if_held_by(&self.path, owner);
+        }
+    }
+}
+
+/// Create the lock file for `holder`; fails with `AlreadyExists` when it is taken
+fn create(path: &Path, holder: &LockHolder) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let content = serde_json::to_string_pretty(holder)?;
+    file.write_all(content.as_bytes())?;
+    file.write_all(b"\n")
+}
+
+fn remove_if_held_by(path: &Path, execution_id: &str) {
+    if WorkspaceLock::holder_at(path).is_some_and(|holder| holder.execution_id == execution_id) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(timeout_ms: u64, steal: bool) -> LockOptions {
+        LockOptions {
+            timeout: Duration::from_millis(timeout_ms),
+            steal,
+        }
+    }
+
+    fn held_by(base: &Path, pid: u32, execution_id: &str) {
+        let holder = LockHolder {
+            pid,
+            execution_id: execution_id.to_string(),
+            ..LockHolder::current("buf generate")
+        };
+        create(&base.join(LOCK_FILE), &holder).unwrap();
+    }
+
+    #[test]
+    fn test_is_mutating() {
+        assert!(is_mutating("git rebase"));
+        assert!(is_mutating("git stack update"));
+        assert!(is_mutating("buf generate"));
+        assert!(!is_mutating("git status"));
+        assert!(!is_mutating("git merge-driver cargo-lock"));
+        assert!(!is_mutating("buf lint"));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_steals_and_takes_over() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let base = dir.path();
+
+        let lock = WorkspaceLock::acquire(base, "git rebase", options(0, false)).await.unwrap();
+        assert_eq!(WorkspaceLock::holder(base).unwrap().command, "git rebase");
+        drop(lock);
+        assert!(!base.join(LOCK_FILE).exists());
+
+        // A live holder: time out, then steal
+        held_by(base, std::process::id(), "other");
+        let error = WorkspaceLock::acquire(base, "git rebase", options(300, false)).await.unwrap_err();
+        assert!(error.to_string().contains("`buf generate`"), "{}", error);
+        let lock = WorkspaceLock::acquire(base, "git rebase", options(0, true)).await.unwrap();
+        assert_eq!(WorkspaceLock::holder(base).unwrap().command, "git rebase");
+        drop(lock);
+
+        // A holder that has exited
+        held_by(base, u32::MAX / 2, "exited");
+        let lock = WorkspaceLock::acquire(base, "git rebase", options(0, false)).await.unwrap();
+        assert_eq!(WorkspaceLock::holder(base).unwrap().command, "git rebase");
+        drop(lock);
+    }
+}